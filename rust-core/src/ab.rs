@@ -0,0 +1,170 @@
+//! A/B comparison of two ranking configurations (`magector ab`)
+//!
+//! Both configurations run the same validation cases against the same
+//! index; only their `boost` rules and `[weights]` differ. A case is a win
+//! for B when it passes under B but not A, a loss the other way round, and a
+//! tie otherwise. Significance is an exact two-sided sign test (McNemar) on
+//! the wins and losses: ties say nothing about which ranking is better.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::validation::{TestCase, TestResult};
+
+/// Outcome counts of B against A for a group of cases
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AbStats {
+    pub total: usize,
+    pub passed_a: usize,
+    pub passed_b: usize,
+    /// Passing under B only
+    pub wins: usize,
+    /// Passing under A only
+    pub losses: usize,
+    /// Two-sided p-value of the wins/losses split; 1.0 without any
+    pub p_value: f64,
+}
+
+impl AbStats {
+    fn add(&mut self, a: bool, b: bool) {
+        self.total += 1;
+        self.passed_a += a as usize;
+        self.passed_b += b as usize;
+        self.wins += (b && !a) as usize;
+        self.losses += (a && !b) as usize;
+    }
+
+    fn finish(&mut self) {
+        self.p_value = sign_test_p(self.wins, self.losses);
+    }
+
+    /// B's accuracy minus A's, in percentage points
+    pub fn accuracy_delta(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.passed_b as f64 - self.passed_a as f64) / self.total as f64 * 100.0
+    }
+}
+
+/// A case whose outcome differs between the configurations
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AbChange {
+    pub test_id: String,
+    pub query: String,
+    pub category: String,
+    /// Passes under B (a win) rather than A (a loss)
+    pub win: bool,
+    pub top_a: Option<String>,
+    pub top_b: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AbReport {
+    pub overall: AbStats,
+    pub categories: BTreeMap<String, AbStats>,
+    pub changes: Vec<AbChange>,
+}
+
+/// Compare the results of `cases` under A and B (both in case order)
+pub fn compare(cases: &[TestCase], a: &[TestResult], b: &[TestResult]) -> AbReport {
+    let mut report = AbReport::default();
+    for ((case, a), b) in cases.iter().zip(a).zip(b) {
+        report.overall.add(a.passed, b.passed);
+        report.categories.entry(case.category.clone()).or_default().add(a.passed, b.passed);
+        if a.passed != b.passed {
+            report.changes.push(AbChange {
+                test_id: case.id.clone(),
+                query: case.query.clone(),
+                category: case.category.clone(),
+                win: b.passed,
+                top_a: a.top_results.first().map(|r| r.path.clone()),
+                top_b: b.top_results.first().map(|r| r.path.clone()),
+            });
+        }
+    }
+    report.overall.finish();
+    report.categories.values_mut().for_each(AbStats::finish);
+    report
+}
+
+/// Exact two-sided sign test: the probability of a split at least this
+/// uneven if wins and losses were equally likely
+pub fn sign_test_p(wins: usize, losses: usize) -> f64 {
+    let n = wins + losses;
+    if n == 0 {
+        return 1.0;
+    }
+    // Sum C(n, i) / 2^n for i <= min(wins, losses), in log space so large n
+    // doesn't underflow
+    let tail = wins.min(losses);
+    let ln_half_n = n as f64 * 0.5f64.ln();
+    let mut ln_choose = 0.0f64;
+    let mut sum = 0.0f64;
+    for i in 0..=tail {
+        if i > 0 {
+            ln_choose += ((n - i + 1) as f64).ln() - (i as f64).ln();
+        }
+        sum += (ln_choose + ln_half_n).exp();
+    }
+    (2.0 * sum).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(id: &str, category: &str) -> TestCase {
+        TestCase {
+            id: id.to_string(),
+            query: format!("query {}", id),
+            category: category.to_string(),
+            expected_patterns: Vec::new(),
+            unexpected_patterns: Vec::new(),
+            min_score: 0.0,
+            max_score: None,
+            description: String::new(),
+        }
+    }
+
+    fn result(id: &str, passed: bool) -> TestResult {
+        TestResult {
+            test_id: id.to_string(),
+            query: String::new(),
+            passed,
+            score: 0.0,
+            matched_expected: Vec::new(),
+            missed_expected: Vec::new(),
+            matched_unexpected: Vec::new(),
+            top_results: Vec::new(),
+            execution_time_ms: 0,
+            details: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let cases = [case("T1", "plugin"), case("T2", "plugin"), case("T3", "controller"), case("T4", "controller")];
+        let a = [result("T1", false), result("T2", true), result("T3", true), result("T4", false)];
+        let b = [result("T1", true), result("T2", true), result("T3", false), result("T4", true)];
+        let report = compare(&cases, &a, &b);
+
+        assert_eq!((report.overall.wins, report.overall.losses), (2, 1));
+        assert_eq!((report.overall.passed_a, report.overall.passed_b), (2, 3));
+        assert!((report.overall.accuracy_delta() - 25.0).abs() < 1e-9);
+        let plugin = &report.categories["plugin"];
+        assert_eq!((plugin.total, plugin.wins, plugin.losses), (2, 1, 0));
+        assert_eq!(report.changes.len(), 3);
+        assert!(!report.changes[1].win);
+    }
+
+    #[test]
+    fn test_sign_test_p() {
+        assert_eq!(sign_test_p(0, 0), 1.0);
+        assert_eq!(sign_test_p(3, 3), 1.0);
+        // 2 * (1 + 10) / 1024
+        assert!((sign_test_p(9, 1) - 22.0 / 1024.0).abs() < 1e-12);
+        assert!((sign_test_p(1, 9) - sign_test_p(9, 1)).abs() < 1e-12);
+        assert!(sign_test_p(600, 400) < 1e-9);
+    }
+}
@@ -0,0 +1,158 @@
+//! Project aliases mined from code
+//!
+//! Teams search in the abbreviations and names their code uses: "MSI" for
+//! Magento's Inventory modules, a vendor's short class prefix, an internal
+//! product name. A part of class names and namespaces (`Msi` in
+//! `MsiStockResolver`) is an alias when several files use it, it is not
+//! itself a word of any module name, and nearly all of those files sit in
+//! modules sharing a name word, say "inventory". A query using the alias is
+//! expanded with those words. Mined when an index is built and kept in its
+//! header ([`crate::vectordb::IndexHeader::aliases`]).
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::IndexMetadata;
+
+/// Files that must use a word before it can be an alias
+const MIN_FILES: usize = 3;
+
+/// Share of an alias's files whose module must carry an expansion word
+const MIN_SHARE: f32 = 0.8;
+
+/// Expansion words per alias
+const MAX_EXPANSIONS: usize = 3;
+
+/// Parts of a class name or namespace segment: runs of capitals stay one
+/// acronym (`MSIStock` → `MSI`, `Stock`; `MsiStock` → `Msi`, `Stock`)
+fn identifier_parts(identifier: &str) -> Vec<String> {
+    let chars: Vec<char> = identifier.chars().collect();
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            parts.push(std::mem::take(&mut current));
+            continue;
+        }
+        let prev_lower = i > 0 && (chars[i - 1].is_lowercase() || chars[i - 1].is_ascii_digit());
+        let acronym_end = i > 0 && chars[i - 1].is_uppercase() && chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+        if c.is_uppercase() && (prev_lower || acronym_end) {
+            parts.push(std::mem::take(&mut current));
+        }
+        current.push(c);
+    }
+    parts.push(current);
+    parts.into_iter().filter(|p| p.len() >= 2 && !p.chars().all(|c| c.is_ascii_digit())).collect()
+}
+
+/// Lowercase words of a module name (`Magento_InventorySales`,
+/// `magento_module-inventory-sales` → magento, inventory, sales)
+fn module_words(module: &str) -> Vec<String> {
+    module
+        .split(['_', '-'])
+        .flat_map(identifier_parts)
+        .map(|w| w.to_lowercase())
+        .filter(|w| w.len() >= 3 && w != "module")
+        .collect()
+}
+
+/// Words of the module part of a name, without its vendor
+fn module_name_words(module: &str) -> Vec<String> {
+    module_words(module.split_once('_').map_or(module, |(_, name)| name))
+}
+
+/// Aliases of an index's items: alias → words a query using it is
+/// expanded with
+pub fn mine(items: &[&IndexMetadata]) -> BTreeMap<String, Vec<String>> {
+    let mut dictionary: HashSet<String> = HashSet::new();
+    // Files using each class name/namespace part, with their module
+    let mut uses: HashMap<String, HashMap<&str, &str>> = HashMap::new();
+    for meta in items {
+        let Some(ref module) = meta.module else { continue };
+        dictionary.extend(module_words(module));
+        let identifiers = meta.namespace.iter().flat_map(|ns| ns.split('\\')).chain(meta.class_name.as_deref());
+        for part in identifiers.flat_map(identifier_parts) {
+            uses.entry(part.to_lowercase()).or_default().insert(meta.path.as_str(), module.as_str());
+        }
+    }
+
+    let mut aliases = BTreeMap::new();
+    for (alias, files) in uses {
+        if files.len() < MIN_FILES || dictionary.contains(&alias) {
+            continue;
+        }
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for module in files.values() {
+            for word in module_name_words(module).into_iter().collect::<HashSet<_>>() {
+                *counts.entry(word).or_insert(0) += 1;
+            }
+        }
+        let mut expansions: Vec<(String, usize)> =
+            counts.into_iter().filter(|(_, n)| *n as f32 >= files.len() as f32 * MIN_SHARE).collect();
+        expansions.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        if !expansions.is_empty() {
+            aliases.insert(alias, expansions.into_iter().take(MAX_EXPANSIONS).map(|(word, _)| word).collect());
+        }
+    }
+    aliases
+}
+
+/// `query` followed by the expansions of its aliases it does not already
+/// contain
+pub fn expand(query: &str, aliases: &BTreeMap<String, Vec<String>>) -> String {
+    let lower = query.to_lowercase();
+    let words: Vec<&str> = lower.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    let mut expanded = query.to_string();
+    let mut added: HashSet<&str> = HashSet::new();
+    for expansion in words.iter().filter_map(|w| aliases.get(*w)).flatten() {
+        if !words.contains(&expansion.as_str()) && added.insert(expansion) {
+            expanded.push(' ');
+            expanded.push_str(expansion);
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(path: &str, module: &str, namespace: &str, class: &str) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            class_name: Some(class.to_string()),
+            namespace: Some(namespace.to_string()),
+            module: Some(module.to_string()),
+            search_text: String::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_mine_aliases() {
+        assert_eq!(identifier_parts("MSIStockResolver"), ["MSI", "Stock", "Resolver"]);
+        assert_eq!(identifier_parts("MsiStock2"), ["Msi", "Stock2"]);
+
+        let items = [
+            item("a/1.php", "Magento_InventorySales", "Magento\\InventorySales\\Model", "MsiStockResolver"),
+            item("a/2.php", "Magento_InventoryIndexer", "Magento\\InventoryIndexer\\Model", "MsiIndexer"),
+            item("a/3.php", "magento_module-inventory-api", "Magento\\InventoryApi\\Api", "MSIStatus"),
+            item("b/1.php", "Magento_Sales", "Magento\\Sales\\Model", "OrderResolver"),
+            item("b/2.php", "Magento_Catalog", "Magento\\Catalog\\Model", "ProductResolver"),
+            item("b/3.php", "Acme_Falcon", "Acme\\Falcon\\Model", "StockResolver"),
+        ];
+        let refs: Vec<&IndexMetadata> = items.iter().collect();
+        let aliases = mine(&refs);
+        assert_eq!(aliases.get("msi").unwrap(), &["inventory"]);
+        // Spread over unrelated modules
+        assert!(!aliases.contains_key("resolver"));
+        // Module name words are found without an alias
+        assert!(!aliases.contains_key("inventory") && !aliases.contains_key("falcon"));
+        // "Model" is in every namespace: too common to point anywhere
+        assert!(!aliases.contains_key("model"));
+
+        assert_eq!(expand("MSI reservations", &aliases), "MSI reservations inventory");
+        assert_eq!(expand("msi inventory", &aliases), "msi inventory");
+        assert_eq!(expand("stock", &aliases), "stock");
+    }
+}
@@ -5,6 +5,8 @@
 use tree_sitter::{Language, Parser, Node, Query, QueryCursor};
 use streaming_iterator::StreamingIterator;
 
+use crate::vectordb::ClassConstant;
+
 /// Get PHP language for tree-sitter
 fn get_php_language() -> Language {
     tree_sitter_php::LANGUAGE_PHP.into()
@@ -26,13 +28,22 @@ pub struct PhpAstMetadata {
     pub namespace: Option<String>,
     pub class_name: Option<String>,
     pub class_type: Option<String>, // class, interface, trait, abstract, final
+    /// Parent class, fully qualified (see [`Self::resolve_name`])
     pub extends: Option<String>,
+    /// Implemented interfaces, fully qualified
     pub implements: Vec<String>,
+    /// Traits the class uses, fully qualified (see [`Self::resolve_name`])
     pub traits: Vec<String>,
     pub methods: Vec<PhpMethod>,
     pub properties: Vec<PhpProperty>,
-    pub constants: Vec<String>,
+    /// Class constants and enum cases, in source order
+    pub constants: Vec<ClassConstant>,
     pub uses: Vec<UseStatement>,
+    /// `new class { ... }` expressions; their methods are in `methods` too
+    pub anonymous_classes: Vec<PhpAnonymousClass>,
+    /// Closures of [`MIN_CLOSURE_LINES`] or more assigned to a variable or
+    /// property
+    pub closures: Vec<PhpClosure>,
     // Magento-specific
     pub is_controller: bool,
     pub is_repository: bool,
@@ -49,6 +60,33 @@ pub struct PhpAstMetadata {
     pub di_injections: Vec<String>,
 }
 
+impl PhpAstMetadata {
+    /// Fully qualified form of a class name as written in this file: a
+    /// leading `\` is dropped, a first segment matching a `use` import
+    /// (or its alias) is replaced by the imported name, and anything else
+    /// is taken to be in the file's namespace
+    pub fn resolve_name(&self, name: &str) -> String {
+        if let Some(absolute) = name.strip_prefix('\\') {
+            return absolute.to_string();
+        }
+        let (first, rest) = match name.split_once('\\') {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (name, None),
+        };
+        let imported = self.uses.iter().find(|u| {
+            let alias = u.alias.as_deref().unwrap_or_else(|| u.full_path.rsplit('\\').next().unwrap_or(&u.full_path));
+            alias.eq_ignore_ascii_case(first)
+        });
+        let imported = imported.map(|u| u.full_path.trim_start_matches('\\'));
+        match (imported, rest, self.namespace.as_deref()) {
+            (Some(path), Some(rest), _) => format!("{}\\{}", path, rest),
+            (Some(path), None, _) => path.to_string(),
+            (None, _, Some(ns)) => format!("{}\\{}", ns, name),
+            (None, _, None) => name.to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PhpMethod {
     pub name: String,
@@ -75,6 +113,48 @@ pub struct PhpProperty {
     pub is_static: bool,
 }
 
+/// Closures shorter than this (callbacks like `fn ($x) => $x->getId()`)
+/// aren't recorded
+pub const MIN_CLOSURE_LINES: usize = 3;
+
+/// An anonymous class, under a synthetic name
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhpAnonymousClass {
+    /// `Outer@anonymous:LINE`, with the enclosing class (or `class` outside
+    /// one) and the 1-based line of the `new class`
+    pub name: String,
+    pub extends: Option<String>,
+    pub implements: Vec<String>,
+    pub methods: Vec<String>,
+    pub line: usize,
+}
+
+/// A closure or arrow function assigned to a variable or property
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhpClosure {
+    /// Assignment target as written: `$handler`, `$this->callback`
+    pub name: String,
+    /// Parameter list as written, without the parentheses
+    pub parameters: String,
+    pub line: usize,
+}
+
+/// A `const` or an enum `case` from its name and value nodes
+fn class_constant(name: &Node, value: Option<Node>, source: &[u8], is_case: bool) -> Option<ClassConstant> {
+    let name = name.utf8_text(source).ok()?.to_string();
+    let is_string = value.is_some_and(|v| matches!(v.kind(), "string" | "encapsed_string"));
+    let value = value.and_then(|v| v.utf8_text(source).ok()).map(|text| {
+        if is_string {
+            // Quotes, and the `b` prefix of binary strings
+            let text = text.strip_prefix(['b', 'B']).unwrap_or(text);
+            text.get(1..text.len().saturating_sub(1)).unwrap_or_default().to_string()
+        } else {
+            text.to_string()
+        }
+    });
+    Some(ClassConstant { name, value, is_string, is_case })
+}
+
 #[derive(Debug, Clone)]
 pub struct UseStatement {
     pub full_path: String,
@@ -87,6 +167,19 @@ pub struct PluginMethod {
     pub target_method: String,
 }
 
+/// Where a method is declared in PHP source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberSpan {
+    /// `Class::method`, or the bare method name outside a class
+    pub name: String,
+    /// 1-based first and last line
+    pub line: usize,
+    pub end_line: usize,
+    /// Byte range in the source, doc comment not included
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
 /// Result of running a tree-sitter query against PHP source code.
 #[derive(Debug, Clone)]
 pub struct AstQueryMatch {
@@ -98,6 +191,35 @@ pub struct AstQueryMatch {
     pub captures: Vec<(String, String)>,
 }
 
+fn collect_member_spans(node: &Node, source: &[u8], class: Option<&str>, spans: &mut Vec<MemberSpan>) {
+    if node.kind() == "method_declaration" {
+        let name = node.child_by_field_name("name").and_then(|n| n.utf8_text(source).ok()).unwrap_or("");
+        spans.push(MemberSpan {
+            name: match class {
+                Some(class) => format!("{}::{}", class, name),
+                None => name.to_string(),
+            },
+            line: node.start_position().row + 1,
+            end_line: node.end_position().row + 1,
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+        });
+        return;
+    }
+    let declared = match node.kind() {
+        "class_declaration" | "interface_declaration" | "trait_declaration" | "enum_declaration" => {
+            node.child_by_field_name("name").and_then(|n| n.utf8_text(source).ok())
+        }
+        _ => None,
+    };
+    let class = declared.or(class);
+    for i in 0..node.child_count() {
+        if let Some(child) = node.child(i) {
+            collect_member_spans(&child, source, class, spans);
+        }
+    }
+}
+
 impl PhpAstAnalyzer {
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
         let language = get_php_language();
@@ -128,6 +250,19 @@ impl PhpAstAnalyzer {
 
         // Walk the tree and extract information
         self.walk_tree(&root, source_bytes, &mut metadata);
+        // Imports may follow the class in odd files, so resolve at the end
+        let resolve = |metadata: &PhpAstMetadata, names: &[String]| -> Vec<String> {
+            names.iter().map(|name| metadata.resolve_name(name)).collect()
+        };
+        metadata.traits = resolve(&metadata, &metadata.traits);
+        metadata.implements = resolve(&metadata, &metadata.implements);
+        metadata.extends = metadata.extends.as_deref().map(|name| metadata.resolve_name(name));
+        let mut anonymous_classes = std::mem::take(&mut metadata.anonymous_classes);
+        for class in &mut anonymous_classes {
+            class.implements = resolve(&metadata, &class.implements);
+            class.extends = class.extends.as_deref().map(|name| metadata.resolve_name(name));
+        }
+        metadata.anonymous_classes = anonymous_classes;
 
         // Detect Magento patterns based on collected data
         self.detect_magento_patterns(&mut metadata);
@@ -135,6 +270,17 @@ impl PhpAstAnalyzer {
         metadata
     }
 
+    /// Methods of the classes, interfaces and traits in `source`, in source
+    /// order. Empty if the source can't be parsed.
+    pub fn member_spans(&mut self, source: &str) -> Vec<MemberSpan> {
+        let Some(tree) = self.parser.parse(source, None) else {
+            return Vec::new();
+        };
+        let mut spans = Vec::new();
+        collect_member_spans(&tree.root_node(), source.as_bytes(), None, &mut spans);
+        spans
+    }
+
     /// Run a tree-sitter query against PHP source code.
     /// Returns matches with line numbers, snippets, and named captures.
     pub fn run_query(&mut self, source: &str, query_source: &str) -> Result<Vec<AstQueryMatch>, String> {
@@ -200,6 +346,19 @@ impl PhpAstAnalyzer {
             "trait_declaration" => {
                 self.extract_trait(node, source, metadata);
             }
+            "enum_declaration" => {
+                self.extract_enum(node, source, metadata);
+            }
+            "const_declaration" => {
+                self.extract_constants(node, source, metadata);
+            }
+            "enum_case" => {
+                let name = node.child_by_field_name("name");
+                let value = node.child_by_field_name("value");
+                if let Some(constant) = name.and_then(|name| class_constant(&name, value, source, true)) {
+                    metadata.constants.push(constant);
+                }
+            }
             "method_declaration" => {
                 self.extract_method(node, source, metadata);
             }
@@ -209,6 +368,23 @@ impl PhpAstAnalyzer {
             "namespace_use_declaration" => {
                 self.extract_use(node, source, metadata);
             }
+            "anonymous_class" => {
+                self.extract_anonymous_class(node, source, metadata);
+            }
+            "assignment_expression" => {
+                self.extract_closure(node, source, metadata);
+            }
+            // `use TraitA, TraitB { ... }` in a class body
+            "use_declaration" => {
+                for i in 0..node.child_count() {
+                    let Some(child) = node.child(i) else { continue };
+                    if matches!(child.kind(), "name" | "qualified_name") {
+                        if let Ok(text) = child.utf8_text(source) {
+                            metadata.traits.push(text.to_string());
+                        }
+                    }
+                }
+            }
             _ => {}
         }
 
@@ -290,6 +466,59 @@ impl PhpAstAnalyzer {
         }
     }
 
+    fn extract_anonymous_class(&self, node: &Node, source: &[u8], metadata: &mut PhpAstMetadata) {
+        let line = node.start_position().row + 1;
+        let mut class = PhpAnonymousClass {
+            name: format!("{}@anonymous:{}", metadata.class_name.as_deref().unwrap_or("class"), line),
+            extends: None,
+            implements: Vec::new(),
+            methods: Vec::new(),
+            line,
+        };
+        for i in 0..node.child_count() {
+            let Some(child) = node.child(i) else { continue };
+            match child.kind() {
+                "base_clause" => class.extends = type_names(&child, source).into_iter().next(),
+                "class_interface_clause" => class.implements = type_names(&child, source),
+                "declaration_list" => {
+                    for j in 0..child.child_count() {
+                        let Some(member) = child.child(j).filter(|m| m.kind() == "method_declaration") else { continue };
+                        if let Some(name) = member.child_by_field_name("name").and_then(|n| n.utf8_text(source).ok()) {
+                            class.methods.push(name.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        metadata.anonymous_classes.push(class);
+    }
+
+    fn extract_closure(&self, node: &Node, source: &[u8], metadata: &mut PhpAstMetadata) {
+        let Some(closure) = node
+            .child_by_field_name("right")
+            .filter(|right| matches!(right.kind(), "anonymous_function" | "arrow_function"))
+        else {
+            return;
+        };
+        if closure.end_position().row + 1 - closure.start_position().row < MIN_CLOSURE_LINES {
+            return;
+        }
+        let Some(name) = node.child_by_field_name("left").and_then(|left| left.utf8_text(source).ok()) else {
+            return;
+        };
+        let parameters = closure
+            .child_by_field_name("parameters")
+            .and_then(|p| p.utf8_text(source).ok())
+            .map(|p| p.trim_start_matches('(').trim_end_matches(')').trim().to_string())
+            .unwrap_or_default();
+        metadata.closures.push(PhpClosure {
+            name: name.to_string(),
+            parameters,
+            line: closure.start_position().row + 1,
+        });
+    }
+
     fn extract_interface(&self, node: &Node, source: &[u8], metadata: &mut PhpAstMetadata) {
         let child_count = node.child_count();
         for i in 0..child_count {
@@ -318,6 +547,54 @@ impl PhpAstAnalyzer {
         }
     }
 
+    fn extract_enum(&self, node: &Node, source: &[u8], metadata: &mut PhpAstMetadata) {
+        // A class, interface or trait in the same file names it; the enum
+        // only contributes its cases
+        if metadata.class_type.as_deref().is_some_and(|t| !t.starts_with("enum")) {
+            return;
+        }
+        if let Some(name) = node.child_by_field_name("name").and_then(|n| n.utf8_text(source).ok()) {
+            metadata.class_name = Some(name.to_string());
+        }
+        let mut class_type = "enum".to_string();
+        for i in 0..node.child_count() {
+            let Some(child) = node.child(i) else { continue };
+            match child.kind() {
+                // Backed enums: `enum Status: string`
+                "primitive_type" => {
+                    if let Ok(text) = child.utf8_text(source) {
+                        class_type = format!("enum: {}", text);
+                    }
+                }
+                "class_interface_clause" => {
+                    for j in 0..child.child_count() {
+                        if let Some(impl_child) = child.child(j) {
+                            if impl_child.kind() == "name" || impl_child.kind() == "qualified_name" {
+                                if let Ok(text) = impl_child.utf8_text(source) {
+                                    metadata.implements.push(text.to_string());
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        metadata.class_type = Some(class_type);
+    }
+
+    fn extract_constants(&self, node: &Node, source: &[u8], metadata: &mut PhpAstMetadata) {
+        for i in 0..node.child_count() {
+            let Some(element) = node.child(i).filter(|c| c.kind() == "const_element") else { continue };
+            // `name = value`: the name, then the first named node after it
+            let mut named = (0..element.named_child_count()).filter_map(|j| element.named_child(j));
+            let Some(name) = named.next().filter(|n| n.kind() == "name") else { continue };
+            if let Some(constant) = class_constant(&name, named.next(), source, false) {
+                metadata.constants.push(constant);
+            }
+        }
+    }
+
     fn extract_method(&self, node: &Node, source: &[u8], metadata: &mut PhpAstMetadata) {
         let mut method = PhpMethod {
             name: String::new(),
@@ -484,6 +761,13 @@ impl PhpAstAnalyzer {
                     for j in 0..clause_child_count {
                         if let Some(clause_child) = child.child(j) {
                             match clause_child.kind() {
+                                // Newer grammars put the alias of `use A\B as C`
+                                // directly in the clause, after the path
+                                "name" if !use_stmt.full_path.is_empty() => {
+                                    if let Ok(text) = clause_child.utf8_text(source) {
+                                        use_stmt.alias = Some(text.to_string());
+                                    }
+                                }
                                 "qualified_name" | "name" => {
                                     if let Ok(text) = clause_child.utf8_text(source) {
                                         use_stmt.full_path = text.to_string();
@@ -517,64 +801,96 @@ impl PhpAstAnalyzer {
         }
     }
 
+    /// Flags from the class's names. `extends` and `implements` are fully
+    /// qualified by now, so both the short name and the parent's namespace
+    /// count: a class extending `Magento\Catalog\Block\Product\View` is a
+    /// block, one implementing `Acme\Action\ReportInterface` isn't a
+    /// controller.
     fn detect_magento_patterns(&self, metadata: &mut PhpAstMetadata) {
-        // Controller detection
-        metadata.is_controller = metadata.implements.iter().any(|i| i.contains("ActionInterface"))
-            || metadata.extends.as_ref().map_or(false, |e| e.contains("Action"))
+        let parent = metadata.extends.as_deref().map(|e| (namespace_of(e), short_name(e)));
+        let parent_is = |names: &[&str], namespaces: &[&str]| {
+            parent.is_some_and(|(ns, name)| {
+                names.contains(&name) || namespaces.iter().any(|n| ns.ends_with(n) || ns.contains(&format!("{}\\", n)))
+            })
+        };
+        let implements = |test: &dyn Fn(&str) -> bool| metadata.implements.iter().any(|i| test(short_name(i)));
+
+        // Controller detection: ActionInterface, HttpGetActionInterface, ...
+        metadata.is_controller = implements(&|i| i.ends_with("ActionInterface"))
+            || parent.is_some_and(|(_, name)| name.ends_with("Action"))
+            || parent_is(&[], &["\\Controller"])
             || metadata.methods.iter().any(|m| m.name == "execute");
 
         // Repository detection
-        metadata.is_repository = metadata.implements.iter().any(|i| i.contains("RepositoryInterface"))
-            || metadata.class_name.as_ref().map_or(false, |n| n.contains("Repository"))
-            || metadata.namespace.as_ref().map_or(false, |n| n.contains("Repository"));
+        metadata.is_repository = implements(&|i| i.ends_with("RepositoryInterface"))
+            || metadata.class_name.as_ref().is_some_and(|n| n.contains("Repository"))
+            || metadata.namespace.as_ref().is_some_and(|n| n.contains("Repository"));
 
         // Plugin detection — method-based or class name / namespace
         metadata.is_plugin = !metadata.plugin_methods.is_empty()
-            || metadata.class_name.as_ref().map_or(false, |n| n.contains("Plugin"))
-            || metadata.namespace.as_ref().map_or(false, |n| n.contains("\\Plugin\\"));
+            || metadata.class_name.as_ref().is_some_and(|n| n.contains("Plugin"))
+            || metadata.namespace.as_ref().is_some_and(|n| n.contains("\\Plugin\\"));
 
-        // Observer detection
-        metadata.is_observer = metadata.implements.iter().any(|i| i.contains("ObserverInterface"));
+        // Observer detection, also for observers built as anonymous classes
+        metadata.is_observer = implements(&|i| i == "ObserverInterface")
+            || metadata
+                .anonymous_classes
+                .iter()
+                .any(|c| c.implements.iter().any(|i| short_name(i) == "ObserverInterface"));
 
-        // Model detection
-        metadata.is_model = metadata.extends.as_ref().map_or(false, |e| {
-            e.contains("AbstractModel") || e.contains("AbstractDb")
-        });
+        // Model detection: models, resource models and collections
+        metadata.is_model =
+            parent_is(&["AbstractModel", "AbstractExtensibleModel", "AbstractDb", "AbstractCollection"], &[]);
 
         // Block detection
-        metadata.is_block = metadata.extends.as_ref().map_or(false, |e| {
-            e.contains("Template") || e.contains("AbstractBlock")
-        });
+        metadata.is_block = parent_is(&["Template", "AbstractBlock"], &["\\Block"]);
 
         // Helper detection — extends AbstractHelper or namespace/class contains Helper
-        metadata.is_helper = metadata.extends.as_ref().map_or(false, |e| {
-            e.contains("AbstractHelper") || e.contains("AbstractData")
-        }) || metadata.class_name.as_ref().map_or(false, |n| n.contains("Helper"))
-            || metadata.namespace.as_ref().map_or(false, |n| n.contains("\\Helper\\") || n.ends_with("\\Helper"));
+        metadata.is_helper = parent_is(&["AbstractHelper", "AbstractData"], &["\\Helper"])
+            || metadata.class_name.as_ref().is_some_and(|n| n.contains("Helper"))
+            || metadata.namespace.as_ref().is_some_and(|n| n.contains("\\Helper\\") || n.ends_with("\\Helper"));
 
         // Setup detection — implements DataPatchInterface, SchemaPatchInterface, etc.
-        metadata.is_setup = metadata.implements.iter().any(|i| {
-            i.contains("PatchInterface") || i.contains("InstallSchemaInterface")
-                || i.contains("InstallDataInterface") || i.contains("UpgradeSchemaInterface")
-                || i.contains("UpgradeDataInterface")
-        }) || metadata.namespace.as_ref().map_or(false, |n| n.contains("\\Setup\\") || n.ends_with("\\Setup"))
-            || metadata.class_name.as_ref().map_or(false, |n| {
+        metadata.is_setup = implements(&|i| {
+            i.ends_with("PatchInterface") || i == "InstallSchemaInterface"
+                || i == "InstallDataInterface" || i == "UpgradeSchemaInterface"
+                || i == "UpgradeDataInterface"
+        }) || metadata.namespace.as_ref().is_some_and(|n| n.contains("\\Setup\\") || n.ends_with("\\Setup"))
+            || metadata.class_name.as_ref().is_some_and(|n| {
                 n.contains("InstallSchema") || n.contains("InstallData")
                     || n.contains("UpgradeSchema") || n.contains("UpgradeData")
                     || n.contains("Patch")
             });
 
         // GraphQL Resolver detection
-        metadata.is_resolver = metadata.implements.iter().any(|i| {
-            i.contains("ResolverInterface") || i.contains("BatchResolverInterface")
-        });
+        metadata.is_resolver = implements(&|i| i == "ResolverInterface" || i == "BatchResolverInterface");
 
         // API Interface detection
-        metadata.is_api_interface = metadata.class_type.as_ref().map_or(false, |t| t == "interface")
-            && metadata.namespace.as_ref().map_or(false, |n| n.contains("Api"));
+        metadata.is_api_interface = metadata.class_type.as_ref().is_some_and(|t| t == "interface")
+            && metadata.namespace.as_ref().is_some_and(|n| n.contains("Api"));
     }
 }
 
+/// Last segment of a qualified name
+fn short_name(name: &str) -> &str {
+    name.rsplit('\\').next().unwrap_or(name)
+}
+
+/// Namespace of a qualified name; empty for a bare name
+fn namespace_of(name: &str) -> &str {
+    name.rsplit_once('\\').map(|(ns, _)| ns).unwrap_or("")
+}
+
+/// Names and qualified names directly under `node` (`extends`/`implements`
+/// clauses)
+fn type_names(node: &Node, source: &[u8]) -> Vec<String> {
+    (0..node.child_count())
+        .filter_map(|i| node.child(i))
+        .filter(|c| matches!(c.kind(), "name" | "qualified_name"))
+        .filter_map(|c| c.utf8_text(source).ok().map(str::to_string))
+        .collect()
+}
+
 impl Default for PhpAstAnalyzer {
     fn default() -> Self {
         Self::new().expect("Failed to create PHP AST analyzer")
@@ -771,11 +1087,9 @@ impl JsAstAnalyzer {
         for i in 0..child_count {
             if let Some(child) = node.child(i) {
                 match child.kind() {
-                    "identifier" => {
-                        if class.name.is_empty() {
-                            if let Ok(text) = child.utf8_text(source) {
-                                class.name = text.to_string();
-                            }
+                    "identifier" if class.name.is_empty() => {
+                        if let Ok(text) = child.utf8_text(source) {
+                            class.name = text.to_string();
                         }
                     }
                     "class_heritage" => {
@@ -869,10 +1183,19 @@ impl JsAstAnalyzer {
 
     fn extract_call(&self, node: &Node, source: &[u8], metadata: &mut JsAstMetadata) {
         if let Ok(text) = node.utf8_text(source) {
-            // Check for require()
-            if text.starts_with("require(") {
-                if let Some(start) = text.find('\'').or_else(|| text.find('"')) {
-                    if let Some(end) = text[start + 1..].find(|c| c == '\'' || c == '"') {
+            // Check for require(), whose first argument may be a list of modules
+            if text.starts_with("require(") || text.starts_with("requirejs(") {
+                let args = text[text.find('(').unwrap_or(0) + 1..].trim_start();
+                if let Some(list) = args.strip_prefix('[') {
+                    let list = list.split(']').next().unwrap_or("");
+                    for dep in list.split(',') {
+                        let dep = dep.trim().trim_matches(|c| c == '\'' || c == '"');
+                        if !dep.is_empty() && !metadata.dependencies.contains(&dep.to_string()) {
+                            metadata.dependencies.push(dep.to_string());
+                        }
+                    }
+                } else if let Some(start) = text.find('\'').or_else(|| text.find('"')) {
+                    if let Some(end) = text[start + 1..].find(['\'', '"']) {
                         let path = &text[start + 1..start + 1 + end];
                         if !metadata.dependencies.contains(&path.to_string()) {
                             metadata.dependencies.push(path.to_string());
@@ -905,7 +1228,7 @@ impl JsAstAnalyzer {
             if let Some(idx) = source.find("'mixins':") {
                 let rest = &source[idx..];
                 if let Some(start) = rest.find('\'').or_else(|| rest.find('"')) {
-                    if let Some(end) = rest[start + 1..].find(|c| c == '\'' || c == '"') {
+                    if let Some(end) = rest[start + 1..].find(['\'', '"']) {
                         metadata.mixin_target = Some(rest[start + 1..start + 1 + end].to_string());
                     }
                 }
@@ -957,6 +1280,210 @@ class Product extends AbstractModel implements ProductInterface
         assert!(meta.is_model);
     }
 
+    #[test]
+    fn test_constants_and_enums() {
+        let mut analyzer = PhpAstAnalyzer::new().unwrap();
+        let source = r#"<?php
+namespace Vendor\Sales\Model;
+
+class Order
+{
+    const STATE_NEW = 'new';
+    public const EVENT_PLACED = "vendor_order_placed", LIMIT = 10;
+    private const ALIAS = self::STATE_NEW;
+}
+"#;
+        let meta = analyzer.analyze(source);
+        let constants: Vec<(&str, Option<&str>, bool)> =
+            meta.constants.iter().map(|c| (c.name.as_str(), c.value.as_deref(), c.is_string)).collect();
+        assert_eq!(
+            constants,
+            [
+                ("STATE_NEW", Some("new"), true),
+                ("EVENT_PLACED", Some("vendor_order_placed"), true),
+                ("LIMIT", Some("10"), false),
+                ("ALIAS", Some("self::STATE_NEW"), false),
+            ]
+        );
+
+        let source = r#"<?php
+namespace Vendor\Sales\Model;
+
+enum Status: string implements HasLabel
+{
+    case Pending = 'pending';
+    case Shipped = 'shipped';
+
+    public function label(): string { return ucfirst($this->value); }
+}
+
+enum Suit
+{
+    case Hearts;
+}
+"#;
+        let meta = analyzer.analyze(source);
+        assert_eq!(meta.class_name.as_deref(), Some("Suit"));
+        assert_eq!(meta.implements, ["Vendor\\Sales\\Model\\HasLabel"]);
+        let cases: Vec<(&str, Option<&str>, bool)> =
+            meta.constants.iter().map(|c| (c.name.as_str(), c.value.as_deref(), c.is_case)).collect();
+        assert_eq!(cases, [("Pending", Some("pending"), true), ("Shipped", Some("shipped"), true), ("Hearts", None, true)]);
+        assert_eq!(meta.methods[0].name, "label");
+
+        let meta = analyzer.analyze("<?php\nenum Status: string { case Pending = 'pending'; }");
+        assert_eq!((meta.class_name.as_deref(), meta.class_type.as_deref()), (Some("Status"), Some("enum: string")));
+
+        let meta = analyzer.analyze("<?php\nclass Order {}\nenum Status: string implements HasLabel { case Pending = 'pending'; }");
+        assert_eq!((meta.class_name.as_deref(), meta.class_type.as_deref()), (Some("Order"), Some("class")));
+        assert!(meta.implements.is_empty());
+        assert_eq!(meta.constants.len(), 1);
+    }
+
+    #[test]
+    fn test_trait_use_resolution() {
+        let mut analyzer = PhpAstAnalyzer::new().unwrap();
+        let source = r#"<?php
+namespace Vendor\Sales\Model;
+
+use Magento\Framework\Model\ResourceModel\Db\VersionControl as Vc;
+use Vendor\Core\Traits\LoggerTrait;
+
+class Order
+{
+    use LoggerTrait;
+    use Vc\SnapshotTrait, CalculatesTotals {
+        CalculatesTotals::total insteadof Vc\SnapshotTrait;
+    }
+    use \Vendor\Core\Traits\Cacheable;
+
+    public function place() {}
+}
+"#;
+        let meta = analyzer.analyze(source);
+        assert_eq!(
+            meta.traits,
+            [
+                "Vendor\\Core\\Traits\\LoggerTrait",
+                "Magento\\Framework\\Model\\ResourceModel\\Db\\VersionControl\\SnapshotTrait",
+                "Vendor\\Sales\\Model\\CalculatesTotals",
+                "Vendor\\Core\\Traits\\Cacheable",
+            ]
+        );
+        assert!(meta.uses.len() == 2, "trait use isn't an import");
+        assert_eq!(meta.methods.len(), 1);
+    }
+
+    #[test]
+    fn test_parent_name_resolution() {
+        let mut analyzer = PhpAstAnalyzer::new().unwrap();
+        let source = r#"<?php
+namespace Vendor\Catalog\Controller\Product;
+
+use Magento\Framework\App\Action\Action;
+use Magento\Framework\App\Action\HttpGetActionInterface as GetAction;
+
+class View extends Action implements GetAction, \ArrayAccess, Api\ViewInterface
+{
+}
+"#;
+        let meta = analyzer.analyze(source);
+        assert_eq!(meta.extends.as_deref(), Some("Magento\\Framework\\App\\Action\\Action"));
+        assert_eq!(
+            meta.implements,
+            [
+                "Magento\\Framework\\App\\Action\\HttpGetActionInterface",
+                "ArrayAccess",
+                "Vendor\\Catalog\\Controller\\Product\\Api\\ViewInterface",
+            ]
+        );
+        assert!(meta.is_controller);
+
+        // A same-named interface elsewhere isn't an observer or a controller
+        let source = r#"<?php
+namespace Acme\Report;
+
+use Acme\Action\ReportInterface;
+
+class Daily implements ReportInterface, ObserverInterfaceFactory
+{
+}
+"#;
+        let meta = analyzer.analyze(source);
+        assert!(!meta.is_controller);
+        assert!(!meta.is_observer);
+    }
+
+    #[test]
+    fn test_anonymous_classes_and_closures() {
+        let mut analyzer = PhpAstAnalyzer::new().unwrap();
+        let source = r#"<?php
+namespace Vendor\Hooks;
+
+use Magento\Framework\Event\ObserverInterface;
+
+class Registrar
+{
+    public function register($events)
+    {
+        $events->add('sales_order_place_after', new class($this->logger) implements ObserverInterface {
+            public function execute(\Magento\Framework\Event\Observer $observer) {}
+        });
+        $this->onSave = function (Order $order, bool $force) use ($logger) {
+            $logger->info('saved');
+            return $order;
+        };
+        $ids = fn($item) => $item->getId();
+    }
+}
+"#;
+        let meta = analyzer.analyze(source);
+        assert_eq!(meta.class_name.as_deref(), Some("Registrar"));
+        assert_eq!(
+            meta.anonymous_classes,
+            [PhpAnonymousClass {
+                name: "Registrar@anonymous:10".into(),
+                extends: None,
+                implements: vec!["Magento\\Framework\\Event\\ObserverInterface".into()],
+                methods: vec!["execute".into()],
+                line: 10,
+            }]
+        );
+        assert!(meta.is_observer);
+        assert_eq!(
+            meta.closures,
+            [PhpClosure { name: "$this->onSave".into(), parameters: "Order $order, bool $force".into(), line: 13 }]
+        );
+    }
+
+    #[test]
+    fn test_member_spans() {
+        let mut analyzer = PhpAstAnalyzer::new().unwrap();
+        let source = r#"<?php
+class Cart
+{
+    private $items = [];
+
+    public function add($item)
+    {
+        $this->items[] = $item;
+    }
+
+    abstract protected function total(): float;
+}
+
+trait Discount
+{
+    public function apply() {}
+}
+"#;
+        let spans = analyzer.member_spans(source);
+        let names: Vec<&str> = spans.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Cart::add", "Cart::total", "Discount::apply"]);
+        assert_eq!((spans[0].line, spans[0].end_line), (6, 9));
+        assert!(source[spans[0].start_byte..spans[0].end_byte].starts_with("public function add"));
+        assert!(source[spans[1].start_byte..spans[1].end_byte].ends_with("float;"));
+    }
+
     #[test]
     fn test_js_amd_detection() {
         let mut analyzer = JsAstAnalyzer::new().unwrap();
@@ -976,6 +1503,19 @@ define([
         assert_eq!(meta.module_type, Some("amd".to_string()));
         assert!(meta.define_deps.contains(&"jquery".to_string()));
     }
+
+    #[test]
+    fn test_js_require_list() {
+        let mut analyzer = JsAstAnalyzer::new().unwrap();
+        let source = r#"
+require(['jquery', "Magento_Customer/js/customer-data"], function ($, customerData) {
+    customerData.reload(['cart']);
+});
+require('mage/url');
+"#;
+        let meta = analyzer.analyze(source);
+        assert_eq!(meta.dependencies, vec!["jquery", "Magento_Customer/js/customer-data", "mage/url"]);
+    }
 }
 
 #[cfg(test)]
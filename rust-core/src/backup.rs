@@ -0,0 +1,383 @@
+//! Backups of an index's full state (`magector backup` / `magector restore`)
+//!
+//! A backup is a zstd-compressed tar archive (`.tar.zst`) of the index database, the
+//! manifest incremental indexing keeps next to it, the SONA file, the text
+//! cache `migrate` re-embeds from and the project's `magector.toml`, led by
+//! `backup.json` recording the version that wrote it and the size and
+//! SHA-256 of every file. Take one before an experiment that rewrites the
+//! index (a different pooling or coarse dimension, `fsck --repair`) and
+//! restore it if the experiment goes wrong.
+//!
+//! Restore unpacks into a staging directory and checks everything before
+//! touching the current state: the archive format, each file's hash, that
+//! this version can load the index and parse the config. Only then are the
+//! files moved into place. A manifest, SONA file or text cache the backup
+//! lacks is removed, so it can't describe a different index than the
+//! restored one.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{ProjectConfig, CONFIG_FILE};
+use crate::migrate::TextCache;
+use crate::sona::SonaEngine;
+use crate::vectordb::VectorDB;
+use crate::watcher::FileManifest;
+
+/// Archive format version, recorded in `backup.json`
+const BACKUP_FORMAT: u32 = 1;
+
+/// Name of the description of the backup, the archive's first entry
+const BACKUP_JSON: &str = "backup.json";
+
+/// Names of the backed up files in the archive
+const DATABASE: &str = "index.db";
+const MANIFEST: &str = "index.manifest";
+const SONA: &str = "index.sona";
+const TEXTS: &str = "index.texts";
+
+/// Every name a backed up file may have. Entry names come from the archive
+/// itself, so anything else (`../x`, `/etc/x`) is refused before a file is
+/// created.
+const FILE_NAMES: [&str; 5] = [DATABASE, MANIFEST, SONA, TEXTS, CONFIG_FILE];
+
+/// zstd compression level of the archive
+const ZSTD_LEVEL: i32 = 3;
+
+/// A file in a backup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// Name in the archive (`index.db`, `magector.toml`, ...)
+    pub name: String,
+    pub size: u64,
+    /// SHA-256 of the contents, hex
+    pub sha256: String,
+}
+
+/// Contents of `backup.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    /// Archive format version
+    pub format: u32,
+    /// Version of magector that wrote the backup
+    pub version: String,
+    /// Unix time the backup was taken
+    pub created: u64,
+    /// Live items in the index
+    pub items: usize,
+    pub files: Vec<BackupFile>,
+}
+
+/// Where each file of a backup lives for the index at `db_path`; the config
+/// only with a Magento root
+fn locations(db_path: &Path, magento_root: Option<&Path>) -> Vec<(&'static str, Option<PathBuf>)> {
+    vec![
+        (DATABASE, Some(db_path.to_path_buf())),
+        (MANIFEST, Some(FileManifest::sidecar_path(db_path))),
+        (SONA, Some(db_path.with_extension("sona"))),
+        (TEXTS, Some(TextCache::sidecar_path(db_path))),
+        (CONFIG_FILE, magento_root.map(|root| root.join(CONFIG_FILE))),
+    ]
+}
+
+/// SHA-256 of everything `reader` yields, hex
+fn sha256<R: Read>(mut reader: R) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Header of a regular file entry; the path is set when it's appended
+fn tar_header(size: u64, mtime: u64) -> tar::Header {
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    header
+}
+
+/// Refuse a file name that isn't one of [`FILE_NAMES`]
+fn check_name(name: &str) -> Result<()> {
+    let plain = !name.is_empty()
+        && !name.contains(['/', '\\'])
+        && !name.contains("..")
+        && !Path::new(name).has_root();
+    if !plain || !FILE_NAMES.contains(&name) {
+        bail!("Unexpected file in backup: {:?}", name);
+    }
+    Ok(())
+}
+
+/// Back up the index at `db_path`, the files next to it and, with a
+/// Magento root, its `magector.toml` into `output`
+pub fn create(db_path: &Path, magento_root: Option<&Path>, output: &Path) -> Result<BackupInfo> {
+    let db = VectorDB::load_existing(db_path)?;
+    // Hash and copy through the same handle, so a save in between can't
+    // make the two disagree
+    let mut sources = Vec::new();
+    let mut files = Vec::new();
+    for (name, path) in locations(db_path, magento_root) {
+        let Some(path) = path.filter(|p| p.exists()) else { continue };
+        let mut file = File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let sha256 = sha256(BufReader::new(&mut file))?;
+        let size = file.metadata()?.len();
+        file.rewind()?;
+        files.push(BackupFile { name: name.to_string(), size, sha256 });
+        sources.push(file);
+    }
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let info = BackupInfo {
+        format: BACKUP_FORMAT,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created,
+        items: db.len(),
+        files,
+    };
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = output.with_extension("tmp");
+    {
+        let mut encoder = zstd::Encoder::new(BufWriter::new(File::create(&tmp)?), ZSTD_LEVEL)?;
+        encoder.include_checksum(true)?;
+        let mut archive = tar::Builder::new(encoder);
+        let json = serde_json::to_vec_pretty(&info)?;
+        let mut header = tar_header(json.len() as u64, created);
+        archive.append_data(&mut header, BACKUP_JSON, json.as_slice())?;
+        for (file, source) in info.files.iter().zip(sources) {
+            let mut header = tar_header(file.size, created);
+            let mut source = source.take(file.size);
+            archive.append_data(&mut header, &file.name, &mut source)?;
+            if source.limit() != 0 {
+                bail!("{} changed while it was backed up", file.name);
+            }
+        }
+        archive.into_inner()?.finish()?.flush()?;
+    }
+    fs::rename(&tmp, output).context("Failed to move the backup into place")?;
+    Ok(info)
+}
+
+/// Unpack `archive` into `staging`, checking each file against
+/// `backup.json`
+fn unpack(archive: &Path, staging: &Path) -> Result<BackupInfo> {
+    let file = File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+    let mut reader = tar::Archive::new(zstd::Decoder::new(BufReader::new(file))?);
+    let mut info: Option<BackupInfo> = None;
+    let mut unpacked: Vec<String> = Vec::new();
+    for entry in reader.entries().context("Corrupted backup archive")? {
+        let mut entry = entry.context("Corrupted backup archive")?;
+        if entry.header().entry_type() != tar::EntryType::Regular {
+            bail!("Unexpected entry type in backup archive");
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let size = entry.size();
+        let Some(ref info) = info else {
+            if name != BACKUP_JSON {
+                bail!("{} is not a magector backup (no {})", archive.display(), BACKUP_JSON);
+            }
+            let mut json = Vec::new();
+            entry.read_to_end(&mut json).context("Corrupted backup archive")?;
+            let parsed: BackupInfo = serde_json::from_slice(&json).context("Unreadable backup.json")?;
+            if parsed.format > BACKUP_FORMAT {
+                bail!(
+                    "Backup format {} (written by magector {}) is newer than this version ({}) reads — upgrade to restore it",
+                    parsed.format,
+                    parsed.version,
+                    env!("CARGO_PKG_VERSION")
+                );
+            }
+            for file in &parsed.files {
+                check_name(&file.name)?;
+            }
+            info = Some(parsed);
+            continue;
+        };
+        check_name(&name)?;
+        let Some(expected) = info.files.iter().find(|f| f.name == name) else {
+            bail!("Unexpected file in backup: {:?}", name);
+        };
+        if unpacked.contains(&name) {
+            bail!("Corrupted backup: {} appears twice", name);
+        }
+        let path = staging.join(&name);
+        let mut out = BufWriter::new(File::create(&path)?);
+        let copied = io::copy(&mut entry, &mut out).context("Corrupted backup archive")?;
+        out.flush()?;
+        if copied != size || size != expected.size || sha256(File::open(&path)?)? != expected.sha256 {
+            bail!("Corrupted backup: {} does not match its recorded size and SHA-256", name);
+        }
+        unpacked.push(name);
+    }
+    // Read on to the end of the zstd frame so its checksum is verified too
+    io::copy(&mut reader.into_inner(), &mut io::sink()).context("Corrupted backup archive")?;
+    let Some(info) = info else { bail!("{} is empty", archive.display()) };
+    if let Some(missing) = info.files.iter().find(|f| !unpacked.contains(&f.name)) {
+        bail!("Incomplete backup: {} is missing", missing.name);
+    }
+    Ok(info)
+}
+
+/// Restore a backup written by [`create`] to the index at `db_path` and,
+/// with a Magento root, its `magector.toml`, replacing what is there.
+/// Returns the backup's description and warnings about its contents.
+pub fn restore(archive: &Path, db_path: &Path, magento_root: Option<&Path>) -> Result<(BackupInfo, Vec<String>)> {
+    let staging = db_path.with_extension("restore");
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    let result = restore_from(archive, &staging, db_path, magento_root);
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn restore_from(
+    archive: &Path,
+    staging: &Path,
+    db_path: &Path,
+    magento_root: Option<&Path>,
+) -> Result<(BackupInfo, Vec<String>)> {
+    let info = unpack(archive, staging)?;
+    let staged = |name: &str| Some(staging.join(name)).filter(|p| p.exists());
+    let Some(db) = staged(DATABASE) else { bail!("Backup holds no index") };
+    VectorDB::load_existing(&db).with_context(|| {
+        format!("The index in this backup (magector {}) can't be loaded by this version", info.version)
+    })?;
+    if let Some(config) = staged(CONFIG_FILE) {
+        ProjectConfig::load_file(&config).with_context(|| {
+            format!("The {} in this backup (magector {}) isn't valid for this version", CONFIG_FILE, info.version)
+        })?;
+    }
+    let mut warnings = Vec::new();
+    if let Some(sona) = staged(SONA) {
+        warnings.extend(SonaEngine::check_file(&sona).into_iter().map(|p| format!("SONA file: {}", p)));
+    }
+    if staged(CONFIG_FILE).is_some() && magento_root.is_none() {
+        warnings.push(format!("{} not restored: no Magento root given", CONFIG_FILE));
+    }
+
+    if let Some(parent) = db_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    for (name, target) in locations(db_path, magento_root) {
+        let Some(target) = target else { continue };
+        match staged(name) {
+            // The staging directory sits next to the index; the config may
+            // be on another file system
+            Some(source) if fs::rename(&source, &target).is_err() => {
+                fs::copy(&source, &target).with_context(|| format!("Failed to restore {}", target.display()))?;
+            }
+            Some(_) => {}
+            None if name != CONFIG_FILE && target.exists() => fs::remove_file(&target)?,
+            None => {}
+        }
+    }
+    Ok((info, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::EMBEDDING_DIM;
+    use crate::IndexMetadata;
+
+    fn meta(path: &str) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            search_text: String::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let db_path = root.join(".magector/index.db");
+        let mut db = VectorDB::new();
+        db.insert(&vec![0.1; EMBEDDING_DIM], meta("a.php"));
+        db.save(&db_path).unwrap();
+        SonaEngine::new().save(&db_path.with_extension("sona")).unwrap();
+        fs::write(root.join(CONFIG_FILE), "[sona]\nmax_terms = 500\n").unwrap();
+
+        let archive = root.join("backup.tar.zst");
+        let info = create(&db_path, Some(root), &archive).unwrap();
+        assert_eq!(info.items, 1);
+        let names: Vec<&str> = info.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, [DATABASE, SONA, CONFIG_FILE]);
+
+        // An experiment rewrites the index, adds a manifest and edits the config
+        let original = fs::read(&db_path).unwrap();
+        db.insert(&vec![0.2; EMBEDDING_DIM], meta("b.php"));
+        db.save(&db_path).unwrap();
+        FileManifest::new().save(&FileManifest::sidecar_path(&db_path)).unwrap();
+        fs::write(root.join(CONFIG_FILE), "").unwrap();
+
+        let (_, warnings) = restore(&archive, &db_path, Some(root)).unwrap();
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(fs::read(&db_path).unwrap(), original);
+        assert!(!FileManifest::sidecar_path(&db_path).exists());
+        assert!(fs::read_to_string(root.join(CONFIG_FILE)).unwrap().contains("max_terms"));
+        assert!(!db_path.with_extension("restore").exists());
+
+        // A damaged archive leaves the current state alone
+        let mut bytes = fs::read(&archive).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0xff;
+        fs::write(&archive, bytes).unwrap();
+        db.save(&db_path).unwrap();
+        assert!(restore(&archive, &db_path, Some(root)).is_err());
+        assert_eq!(VectorDB::load_existing(&db_path).unwrap().len(), 2);
+    }
+
+    /// Archive whose `backup.json` lists `listed` and whose one file entry
+    /// is named `entry`, written raw since tar refuses such names itself
+    fn crafted_archive(path: &Path, listed: &str, entry: &str, payload: &[u8]) {
+        let info = BackupInfo {
+            format: BACKUP_FORMAT,
+            version: "0".into(),
+            created: 0,
+            items: 0,
+            files: vec![BackupFile { name: listed.into(), size: payload.len() as u64, sha256: sha256(payload).unwrap() }],
+        };
+        let json = serde_json::to_vec(&info).unwrap();
+        let mut archive = tar::Builder::new(zstd::Encoder::new(File::create(path).unwrap(), ZSTD_LEVEL).unwrap());
+        archive.append_data(&mut tar_header(json.len() as u64, 0), BACKUP_JSON, json.as_slice()).unwrap();
+        let mut header = tar_header(payload.len() as u64, 0);
+        header.as_old_mut().name[..entry.len()].copy_from_slice(entry.as_bytes());
+        header.set_cksum();
+        archive.append(&header, payload).unwrap();
+        archive.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_restore_refuses_paths_outside_staging() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("project/.magector/index.db");
+        let archive = dir.path().join("evil.tar.zst");
+        let payload = b"ssh-ed25519 AAAA attacker";
+
+        for (listed, entry) in [
+            ("../../escape", "../../escape"),
+            ("/tmp/magector-escape", "/tmp/magector-escape"),
+            ("..\\escape", "..\\escape"),
+            ("notes.txt", "notes.txt"),
+            (DATABASE, "../../escape"),
+        ] {
+            crafted_archive(&archive, listed, entry, payload);
+            let err = restore(&archive, &db_path, None).unwrap_err();
+            assert!(err.to_string().contains("Unexpected file"), "{listed} / {entry}: {err:#}");
+            assert!(!dir.path().join("project/escape").exists());
+            assert!(!db_path.exists());
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! Code map (`magector map`)
+//!
+//! Clusters the indexed files by their vectors and projects them onto a
+//! plane, so an unfamiliar codebase can be looked at as a picture: which
+//! areas exist, how large they are, and which files sit among another
+//! module's code. Each file is the normalized mean of its items' vectors.
+//! Files are grouped with k-means (k-means++ seeding from a fixed seed, so
+//! the same index always gives the same map) and projected onto their first
+//! two principal components. A cluster is labelled with the module most of
+//! its files belong to; files outside a module are grouped by their first
+//! two path segments.
+
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::vectordb::{IndexMetadata, VectorDB};
+
+/// k-means rounds at most; stops earlier once no file changes cluster
+const MAX_ITERATIONS: usize = 50;
+
+/// Power iteration rounds per principal component
+const PCA_ITERATIONS: usize = 100;
+
+/// Share of a cluster its dominant group needs before files of other
+/// groups in it count as strays
+pub const STRAY_DOMINANCE: f32 = 0.6;
+
+/// Seed of the k-means++ choices
+const SEED: u64 = 0x6d61_6765_6374_6f72;
+
+/// A file on the map
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MapPoint {
+    pub path: String,
+    /// Module, or the first two path segments outside modules
+    pub group: String,
+    pub x: f32,
+    pub y: f32,
+    pub cluster: usize,
+    /// In a cluster dominated by another group
+    pub stray: bool,
+}
+
+/// A cluster of files
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MapCluster {
+    pub id: usize,
+    /// Dominant group
+    pub label: String,
+    pub size: usize,
+    /// Share of the files in the dominant group
+    pub share: f32,
+    /// Largest groups with their file counts, largest first
+    pub groups: Vec<(String, usize)>,
+}
+
+/// Outcome of [`build`]
+#[derive(Debug, Clone, Serialize)]
+pub struct CodeMap {
+    pub clusters: Vec<MapCluster>,
+    pub points: Vec<MapPoint>,
+}
+
+impl CodeMap {
+    /// One line per file: `path,group,x,y,cluster,label,stray`
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("path,group,x,y,cluster,label,stray\n");
+        for p in &self.points {
+            csv.push_str(&format!(
+                "{},{},{:.5},{:.5},{},{},{}\n",
+                csv_field(&p.path),
+                csv_field(&p.group),
+                p.x,
+                p.y,
+                p.cluster,
+                csv_field(&self.clusters[p.cluster].label),
+                p.stray
+            ));
+        }
+        csv
+    }
+
+    /// Files in a cluster dominated by another group
+    pub fn strays(&self) -> impl Iterator<Item = &MapPoint> {
+        self.points.iter().filter(|p| p.stray)
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Cluster count for `files` files when none is given: √(n/2), 2–50
+pub fn default_clusters(files: usize) -> usize {
+    ((files as f64 / 2.0).sqrt().round() as usize).clamp(2, 50)
+}
+
+/// Map the files of `db` whose path starts with `prefix` into `k` clusters
+/// (0 picks [`default_clusters`])
+pub fn build(db: &VectorDB, prefix: &str, k: usize) -> CodeMap {
+    let files = file_vectors(db, |meta| meta.path.starts_with(prefix))
+        .into_iter()
+        .map(|(path, module, vector)| {
+            let group = group_of(&path, module.as_deref());
+            (path, group, vector)
+        })
+        .collect();
+    build_from(files, k)
+}
+
+/// `(path, module, vector)` of the files of `db` whose items pass `keep`,
+/// in path order: the normalized mean of the file's item vectors
+pub fn file_vectors(db: &VectorDB, keep: impl Fn(&IndexMetadata) -> bool) -> Vec<(String, Option<String>, Vec<f32>)> {
+    let mut files: BTreeMap<&str, (Option<&str>, Vec<f32>)> = BTreeMap::new();
+    for (id, meta) in db.metadata_iter() {
+        if !keep(meta) {
+            continue;
+        }
+        let Some(vector) = db.get_vector(id) else { continue };
+        let (_, sum) = files.entry(meta.path.as_str()).or_insert_with(|| (meta.module.as_deref(), vec![0.0; vector.len()]));
+        sum.iter_mut().zip(vector).for_each(|(s, v)| *s += v);
+    }
+    files
+        .into_iter()
+        .map(|(path, (module, mut vector))| {
+            normalize(&mut vector);
+            (path.to_string(), module.map(str::to_string), vector)
+        })
+        .collect()
+}
+
+/// [`build`] for `(path, group, vector)` triples with unit vectors
+pub fn build_from(files: Vec<(String, String, Vec<f32>)>, k: usize) -> CodeMap {
+    if files.is_empty() {
+        return CodeMap { clusters: Vec::new(), points: Vec::new() };
+    }
+    let k = if k == 0 { default_clusters(files.len()) } else { k }.min(files.len());
+    let vectors: Vec<&[f32]> = files.iter().map(|(_, _, v)| v.as_slice()).collect();
+    let assignment = kmeans(&vectors, k);
+    let projection = project(&vectors);
+
+    let mut members: Vec<HashMap<&str, usize>> = vec![HashMap::new(); k];
+    for ((_, group, _), &cluster) in files.iter().zip(&assignment) {
+        *members[cluster].entry(group.as_str()).or_default() += 1;
+    }
+    let clusters: Vec<MapCluster> = members
+        .iter()
+        .enumerate()
+        .map(|(id, groups)| {
+            let mut groups: Vec<(String, usize)> = groups.iter().map(|(g, &n)| (g.to_string(), n)).collect();
+            groups.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            let size = groups.iter().map(|(_, n)| n).sum();
+            let (label, top) = groups.first().map_or((String::new(), 0), |(g, n)| (g.clone(), *n));
+            groups.truncate(3);
+            MapCluster { id, label, size, share: if size == 0 { 0.0 } else { top as f32 / size as f32 }, groups }
+        })
+        .collect();
+
+    let points = files
+        .into_iter()
+        .zip(assignment)
+        .zip(projection)
+        .map(|(((path, group, _), cluster), (x, y))| {
+            let c = &clusters[cluster];
+            let stray = c.share >= STRAY_DOMINANCE && c.label != group;
+            MapPoint { path, group, x, y, cluster, stray }
+        })
+        .collect();
+    CodeMap { clusters, points }
+}
+
+/// Module of a file, or its first two path segments
+fn group_of(path: &str, module: Option<&str>) -> String {
+    match module {
+        Some(module) if !module.is_empty() => module.to_string(),
+        _ => path.split('/').take(2).collect::<Vec<_>>().join("/"),
+    }
+}
+
+fn normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+/// splitmix64 step: a uniform number in [0, 1)
+fn next_unit(state: &mut u64) -> f64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)) as f64 / (u64::MAX as f64 + 1.0)
+}
+
+/// Cluster of each vector after k-means with k-means++ seeding
+fn kmeans(vectors: &[&[f32]], k: usize) -> Vec<usize> {
+    let mut state = SEED;
+    let mut centroids: Vec<Vec<f32>> = vec![vectors[0].to_vec()];
+    let mut nearest: Vec<f32> = vectors.par_iter().map(|v| squared_distance(v, &centroids[0])).collect();
+    while centroids.len() < k {
+        let total: f64 = nearest.iter().map(|&d| d as f64).sum();
+        let next = if total <= 0.0 {
+            centroids.len()
+        } else {
+            let mut target = next_unit(&mut state) * total;
+            nearest.iter().position(|&d| {
+                target -= d as f64;
+                target < 0.0
+            })
+            .unwrap_or(vectors.len() - 1)
+        };
+        let centroid = vectors[next].to_vec();
+        nearest.par_iter_mut().zip(vectors).for_each(|(d, v)| *d = d.min(squared_distance(v, &centroid)));
+        centroids.push(centroid);
+    }
+
+    let mut assignment = vec![usize::MAX; vectors.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let next: Vec<usize> = vectors
+            .par_iter()
+            .map(|v| {
+                (0..k)
+                    .min_by(|&a, &b| squared_distance(v, &centroids[a]).total_cmp(&squared_distance(v, &centroids[b])))
+                    .unwrap_or(0)
+            })
+            .collect();
+        if next == assignment {
+            break;
+        }
+        assignment = next;
+        let dim = vectors[0].len();
+        let mut sums = vec![vec![0.0f32; dim]; k];
+        let mut counts = vec![0usize; k];
+        for (v, &c) in vectors.iter().zip(&assignment) {
+            sums[c].iter_mut().zip(v.iter()).for_each(|(s, x)| *s += x);
+            counts[c] += 1;
+        }
+        // An emptied cluster keeps its centroid
+        for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+            if count > 0 {
+                *centroid = sum.into_iter().map(|s| s / count as f32).collect();
+            }
+        }
+    }
+    assignment
+}
+
+/// Coordinates of each vector on its first two principal components
+fn project(vectors: &[&[f32]]) -> Vec<(f32, f32)> {
+    let dim = vectors[0].len();
+    let n = vectors.len() as f32;
+    let mut mean = vec![0.0f32; dim];
+    for v in vectors {
+        mean.iter_mut().zip(v.iter()).for_each(|(m, x)| *m += x / n);
+    }
+    let centered: Vec<Vec<f32>> = vectors.par_iter().map(|v| v.iter().zip(&mean).map(|(x, m)| x - m).collect()).collect();
+
+    let first = principal_component(&centered, &[]);
+    let second = principal_component(&centered, std::slice::from_ref(&first));
+    let dot = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    centered.par_iter().map(|v| (dot(v, &first), dot(v, &second))).collect()
+}
+
+/// Leading eigenvector of the covariance of `rows`, orthogonal to `found`
+fn principal_component(rows: &[Vec<f32>], found: &[Vec<f32>]) -> Vec<f32> {
+    let dim = rows[0].len();
+    let mut state = SEED;
+    let mut v: Vec<f32> = (0..dim).map(|_| next_unit(&mut state) as f32 - 0.5).collect();
+    for _ in 0..PCA_ITERATIONS {
+        for f in found {
+            let d: f32 = v.iter().zip(f).map(|(x, y)| x * y).sum();
+            v.iter_mut().zip(f).for_each(|(x, y)| *x -= d * y);
+        }
+        normalize(&mut v);
+        // Xᵀ(Xv), summed over the rows in parallel
+        v = rows
+            .par_iter()
+            .fold(
+                || vec![0.0f32; dim],
+                |mut acc, row| {
+                    let d: f32 = row.iter().zip(&v).map(|(x, y)| x * y).sum();
+                    acc.iter_mut().zip(row).for_each(|(a, x)| *a += d * x);
+                    acc
+                },
+            )
+            .reduce(|| vec![0.0f32; dim], |mut a, b| {
+                a.iter_mut().zip(b).for_each(|(x, y)| *x += y);
+                a
+            });
+    }
+    for f in found {
+        let d: f32 = v.iter().zip(f).map(|(x, y)| x * y).sum();
+        v.iter_mut().zip(f).for_each(|(x, y)| *x -= d * y);
+    }
+    normalize(&mut v);
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit vector around axis `axis`, nudged by `offset`
+    fn around(axis: usize, offset: usize) -> Vec<f32> {
+        let mut v = vec![0.0f32; 16];
+        v[axis] = 1.0;
+        v[8 + offset % 8] = 0.1;
+        normalize(&mut v);
+        v
+    }
+
+    #[test]
+    fn test_map_clusters_by_vector_and_flags_strays() {
+        let mut files = Vec::new();
+        for i in 0..6 {
+            files.push((format!("app/code/Acme/Cart/F{}.php", i), "Acme_Cart".to_string(), around(0, i)));
+            files.push((format!("app/code/Acme/Ship/F{}.php", i), "Acme_Ship".to_string(), around(1, i)));
+        }
+        // A shipping file that reads like cart code
+        files.push(("app/code/Acme/Ship/CartTotals.php".to_string(), "Acme_Ship".to_string(), around(0, 7)));
+
+        let map = build_from(files, 2);
+        assert_eq!(map.clusters.len(), 2);
+        let mut labels: Vec<(&str, usize)> = map.clusters.iter().map(|c| (c.label.as_str(), c.size)).collect();
+        labels.sort();
+        assert_eq!(labels, [("Acme_Cart", 7), ("Acme_Ship", 6)]);
+        let strays: Vec<&str> = map.strays().map(|p| p.path.as_str()).collect();
+        assert_eq!(strays, ["app/code/Acme/Ship/CartTotals.php"]);
+
+        // The two groups land apart on the first component
+        let x = |path: &str| map.points.iter().find(|p| p.path == path).unwrap().x;
+        assert!((x("app/code/Acme/Cart/F0.php") - x("app/code/Acme/Ship/F0.php")).abs() > 0.5);
+
+        let csv = map.to_csv();
+        assert!(csv.starts_with("path,group,x,y,cluster,label,stray\n"));
+        assert_eq!(csv.lines().count(), 14);
+        assert!(csv.contains("CartTotals.php,Acme_Ship,"));
+        assert_eq!(group_of("lib/internal/Magento/Framework/App.php", None), "lib/internal");
+        assert_eq!(default_clusters(2), 2);
+        assert_eq!(default_clusters(20_000), 50);
+    }
+}
@@ -0,0 +1,299 @@
+//! Project configuration loaded from `magector.toml` in the Magento root
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::embedder::Pooling;
+use crate::indexer::WalkOptions;
+use crate::sona::SonaLimits;
+use crate::vectordb::ScoreWeights;
+
+/// File name looked up in the Magento root
+pub const CONFIG_FILE: &str = "magector.toml";
+
+/// Top-level `magector.toml` contents. Every field is optional; a missing
+/// file behaves like an empty one.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub index: IndexConfig,
+    /// Static ranking rules for search results (`boost = [...]`)
+    pub boost: Vec<BoostRule>,
+    /// `[weights]` — default weights of the hybrid score parts; serve
+    /// requests may override them per query
+    pub weights: ScoreWeights,
+    /// `[redact]` — secrets masked in indexed content and returned snippets
+    pub redact: RedactConfig,
+    /// `[sona]` — size caps of the learned SONA state
+    pub sona: SonaLimits,
+}
+
+/// `[redact]` section — see [`crate::redact`]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactConfig {
+    /// Apply the built-in patterns (passwords, API keys, crypt keys,
+    /// private keys); on unless set to false
+    pub defaults: bool,
+    /// Extra regular expressions; when one has a capture group only the
+    /// group is masked
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactConfig {
+    fn default() -> Self {
+        Self { defaults: true, patterns: Vec::new() }
+    }
+}
+
+/// `[index]` section — defaults for `magector index`; CLI flags win
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexConfig {
+    /// Module globs to index (same as `--only-modules`)
+    pub only_modules: Vec<String>,
+    /// Embedding batch size (same as `--batch-size`)
+    pub batch_size: Option<usize>,
+    /// Reproducible output (same as `--deterministic`)
+    pub deterministic: Option<bool>,
+    /// Follow symlinked directories (same as `--follow-symlinks`)
+    pub follow_symlinks: Option<bool>,
+    /// Index MFTF test XML (same as `--include-tests`)
+    pub include_tests: Option<bool>,
+    /// Index Markdown docs (same as `--include-docs`)
+    pub include_docs: Option<bool>,
+    /// Apply the root .gitignore (same as `--gitignore`)
+    pub gitignore: Option<bool>,
+    /// Core index to layer under this project's index (same as
+    /// `--core-index`); relative paths are resolved from the Magento root
+    pub core_index: Option<PathBuf>,
+    /// Source trees indexed besides the Magento root, as `[NAME=]PATH`
+    /// (same as `--extra-root`); relative paths are resolved from the
+    /// Magento root
+    pub extra_roots: Vec<String>,
+    /// Pooling over token embeddings (same as `--pooling`)
+    pub pooling: Option<Pooling>,
+    /// Coarse search graph dimensions (same as `--coarse-dim`)
+    pub coarse_dim: Option<usize>,
+    /// Two-stage file → chunk search (same as `--two-stage`)
+    pub two_stage: Option<bool>,
+    /// Per-file structural summaries (same as `--summaries`)
+    pub summaries: Option<bool>,
+    /// Last git commit time per file (same as `--git-times`)
+    pub git_times: Option<bool>,
+    /// Code owners per file (same as `--owners`)
+    pub owners: Option<bool>,
+    /// Separate metadata vectors (same as `--meta-vectors`)
+    pub meta_vectors: Option<bool>,
+    /// One item per PHP method (same as `--method-chunks`)
+    pub method_chunks: Option<bool>,
+}
+
+impl IndexConfig {
+    /// The walk these settings select; unset switches are off
+    pub fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            follow_symlinks: self.follow_symlinks.unwrap_or(false),
+            include_tests: self.include_tests.unwrap_or(false),
+            include_docs: self.include_docs.unwrap_or(false),
+        }
+    }
+
+    /// These settings with each one left unset (`None` or an empty list)
+    /// taken from `defaults`, e.g. CLI flags over `magector.toml`
+    pub fn or(self, defaults: IndexConfig) -> IndexConfig {
+        let or_list = |list: Vec<String>, default: Vec<String>| if list.is_empty() { default } else { list };
+        IndexConfig {
+            only_modules: or_list(self.only_modules, defaults.only_modules),
+            batch_size: self.batch_size.or(defaults.batch_size),
+            deterministic: self.deterministic.or(defaults.deterministic),
+            follow_symlinks: self.follow_symlinks.or(defaults.follow_symlinks),
+            include_tests: self.include_tests.or(defaults.include_tests),
+            include_docs: self.include_docs.or(defaults.include_docs),
+            gitignore: self.gitignore.or(defaults.gitignore),
+            core_index: self.core_index.or(defaults.core_index),
+            extra_roots: or_list(self.extra_roots, defaults.extra_roots),
+            pooling: self.pooling.or(defaults.pooling),
+            coarse_dim: self.coarse_dim.or(defaults.coarse_dim),
+            two_stage: self.two_stage.or(defaults.two_stage),
+            summaries: self.summaries.or(defaults.summaries),
+            git_times: self.git_times.or(defaults.git_times),
+            owners: self.owners.or(defaults.owners),
+            meta_vectors: self.meta_vectors.or(defaults.meta_vectors),
+            method_chunks: self.method_chunks.or(defaults.method_chunks),
+        }
+    }
+}
+
+/// One `boost` entry: results matching every condition given get their
+/// score multiplied by `factor`. A rule without conditions applies to all.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoostRule {
+    /// Result path starts with this (relative to the Magento root)
+    pub path_prefix: Option<String>,
+    /// Result has this Magento type (`di_config`, `plugin`, ...)
+    pub magento_type: Option<String>,
+    /// Query contains this text (case-insensitive)
+    pub query_contains: Option<String>,
+    pub factor: f32,
+}
+
+impl BoostRule {
+    /// Whether a result at `path` of `magento_type` matches for a query
+    /// (already lowercased)
+    pub fn matches(&self, path: &str, magento_type: Option<&str>, query_lower: &str) -> bool {
+        self.path_prefix.as_deref().is_none_or(|prefix| path.starts_with(prefix))
+            && self.magento_type.as_deref().is_none_or(|t| magento_type == Some(t))
+            && self
+                .query_contains
+                .as_deref()
+                .is_none_or(|text| query_lower.contains(&text.to_lowercase()))
+    }
+}
+
+impl ProjectConfig {
+    /// Load `magector.toml` from `magento_root`, or defaults if absent.
+    pub fn load(magento_root: &Path) -> Result<Self> {
+        let path = magento_root.join(CONFIG_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text).with_context(|| format!("Invalid {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Load a config file at an explicit path, which must exist
+    pub fn load_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        for (i, rule) in config.boost.iter().enumerate() {
+            if !rule.factor.is_finite() || rule.factor <= 0.0 {
+                bail!("boost rule {}: factor must be a positive number, got {}", i + 1, rule.factor);
+            }
+        }
+        config.weights.validate()?;
+        config.sona.validate()?;
+        crate::redact::Redactor::new(&config.redact)?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_index_section() {
+        let config = ProjectConfig::parse(
+            r#"
+            # comments and empty sections are fine
+            [index]
+            only_modules = ["Magento_Checkout", "Vendor_*"]
+            batch_size = 64
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.index.only_modules, vec!["Magento_Checkout", "Vendor_*"]);
+        assert_eq!(config.index.batch_size, Some(64));
+        assert_eq!(config.index.deterministic, None);
+        assert_eq!(config.index.pooling, None);
+        assert_eq!(ProjectConfig::parse("[index]\ncoarse_dim = 128").unwrap().index.coarse_dim, Some(128));
+        assert_eq!(ProjectConfig::parse("[index]\npooling = \"cls\"").unwrap().index.pooling, Some(Pooling::Cls));
+        assert_eq!(
+            ProjectConfig::parse("[index]\nextra_roots = [\"pay=../extensions/pay\"]").unwrap().index.extra_roots,
+            ["pay=../extensions/pay"]
+        );
+
+        assert!(ProjectConfig::parse("").unwrap().index.only_modules.is_empty());
+        assert!(ProjectConfig::parse("[index]\nbatchsize = 1").is_err());
+    }
+
+    #[test]
+    fn test_cli_flags_override_index_section() {
+        let toml = ProjectConfig::parse(
+            r#"
+            [index]
+            include_docs = true
+            follow_symlinks = true
+            deterministic = true
+            batch_size = 64
+            only_modules = ["Vendor_*"]
+            "#,
+        )
+        .unwrap()
+        .index;
+        // `--include-docs false --include-tests --batch-size 8`
+        let flags = IndexConfig {
+            include_docs: Some(false),
+            include_tests: Some(true),
+            batch_size: Some(8),
+            ..Default::default()
+        };
+        let merged = flags.or(toml);
+        assert_eq!(merged.include_docs, Some(false));
+        assert_eq!(merged.include_tests, Some(true));
+        assert_eq!(merged.batch_size, Some(8));
+        // Flags not given keep the file's values
+        assert_eq!(merged.follow_symlinks, Some(true));
+        assert_eq!(merged.deterministic, Some(true));
+        assert_eq!(merged.only_modules, ["Vendor_*"]);
+        assert_eq!(merged.gitignore, None);
+    }
+
+    #[test]
+    fn test_parse_boost_rules() {
+        let config = ProjectConfig::parse(
+            r#"
+            boost = [
+                { path_prefix = "app/code/MyVendor", factor = 1.1 },
+                { magento_type = "di_config", query_contains = "DI.xml", factor = 1.2 },
+            ]
+
+            [index]
+            batch_size = 32
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.boost.len(), 2);
+        assert_eq!(config.index.batch_size, Some(32));
+
+        let [vendor, di] = &config.boost[..] else { unreachable!() };
+        assert!(vendor.matches("app/code/MyVendor/Cart/Model/Cart.php", Some("model"), "cart"));
+        assert!(!vendor.matches("vendor/magento/module-checkout/Model/Cart.php", Some("model"), "cart"));
+        assert!(di.matches("app/code/MyVendor/Cart/etc/di.xml", Some("di_config"), "cart di.xml preference"));
+        assert!(!di.matches("app/code/MyVendor/Cart/etc/di.xml", Some("di_config"), "cart preference"));
+
+        assert!(ProjectConfig::parse("boost = [{ factor = 0 }]").is_err());
+        assert!(ProjectConfig::parse("boost = [{ path = \"app\", factor = 1.5 }]").is_err());
+        assert!(ProjectConfig::parse("boost = [{ path_prefix = \"app\" }]").is_err());
+
+        let weighted = ProjectConfig::parse("[weights]\nkeyword = 0.5").unwrap();
+        assert_eq!(weighted.weights, ScoreWeights { keyword: 0.5, ..Default::default() });
+        assert_eq!(ProjectConfig::parse("").unwrap().weights, ScoreWeights::default());
+        assert!(ProjectConfig::parse("[weights]\nsona = -1").is_err());
+    }
+
+    #[test]
+    fn test_parse_redact_section() {
+        assert!(ProjectConfig::parse("").unwrap().redact.defaults);
+        let config = ProjectConfig::parse("[redact]\ndefaults = false\npatterns = ['sk_live_\\w+']").unwrap();
+        assert!(!config.redact.defaults);
+        assert_eq!(config.redact.patterns, vec![r"sk_live_\w+"]);
+        assert!(ProjectConfig::parse("[redact]\npatterns = ['(']").is_err());
+    }
+
+    #[test]
+    fn test_parse_sona_section() {
+        assert_eq!(ProjectConfig::parse("").unwrap().sona, SonaLimits::default());
+        let config = ProjectConfig::parse("[sona]\nmax_terms = 500").unwrap();
+        assert_eq!(config.sona, SonaLimits { max_terms: 500, ..Default::default() });
+        assert!(ProjectConfig::parse("[sona]\nmax_patterns = 0").is_err());
+        assert!(ProjectConfig::parse("[sona]\nmax_queries = 10").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! Console command registry (`magector console-commands`, serve `console_commands`)
+//!
+//! `bin/magento` commands are Symfony console commands, registered by adding
+//! the class to the `commands` array argument of
+//! `Magento\Framework\Console\CommandListInterface` (or `CommandList`) in a
+//! di.xml. The command name lives in the class: `setName()` in
+//! `configure()`, a `$defaultName` property or an `#[AsCommand]` attribute,
+//! often through a class constant. [`CommandRegistry::commands`] joins the
+//! registrations and the classes. The indexer tags registered classes with
+//! their command names, so a search for `bin/magento indexer:reindex` ranks
+//! the implementing class first.
+
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::magento::DiArgument;
+
+/// Types whose `commands` argument lists the `bin/magento` commands
+pub const COMMAND_LIST_TYPES: &[&str] =
+    &["Magento\\Framework\\Console\\CommandListInterface", "Magento\\Framework\\Console\\CommandList"];
+
+/// A `bin/magento` command joined from its registration and its class
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConsoleCommand {
+    /// Command name; `None` when the class was not found or builds its
+    /// name at runtime
+    pub name: Option<String>,
+    pub class: String,
+    pub description: Option<String>,
+    pub aliases: Vec<String>,
+    /// PHP file defining the class
+    pub file: Option<String>,
+    /// di.xml files registering the class
+    pub registered_in: Vec<String>,
+}
+
+impl ConsoleCommand {
+    fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+        [self.name.as_ref(), Some(&self.class), self.description.as_ref()]
+            .into_iter()
+            .flatten()
+            .chain(self.aliases.iter())
+            .any(|part| part.to_lowercase().contains(&filter))
+    }
+}
+
+/// Name, description and aliases a command class declares
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandDefinition {
+    pub name: String,
+    pub description: Option<String>,
+    pub aliases: Vec<String>,
+}
+
+/// Registrations from di.xml and definitions from command classes
+#[derive(Debug, Clone, Default)]
+pub struct CommandRegistry {
+    /// Class → di.xml files registering it
+    registered: BTreeMap<String, Vec<String>>,
+    /// Class → its definition and file
+    definitions: HashMap<String, (CommandDefinition, String)>,
+}
+
+/// Whether the PHP file at `path` may define a command class (it sits in a
+/// `Console/` or `Command/` directory)
+pub fn is_command_source(path: &str) -> bool {
+    path.ends_with(".php") && (path.contains("/Console/") || path.contains("/Command/"))
+}
+
+/// Whether a query term names a command (`indexer:reindex`,
+/// `setup:di:compile`)
+pub fn is_command_name(term: &str) -> bool {
+    term.split(':').count() > 1
+        && term.split(':').all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        })
+}
+
+/// Fully qualified name of the class a PHP file declares
+fn php_class(content: &str) -> Option<String> {
+    let class_re = Regex::new(r"(?m)^\s*(?:(?:abstract|final|readonly)\s+)*class\s+(\w+)").unwrap();
+    let namespace_re = Regex::new(r"(?m)^\s*namespace\s+([\w\\]+)").unwrap();
+    let class = &class_re.captures(content)?[1];
+    Some(match namespace_re.captures(content) {
+        Some(ns) => format!("{}\\{}", &ns[1], class),
+        None => class.to_string(),
+    })
+}
+
+/// Name, description and aliases set in a command class, `None` when it
+/// sets no literal name. Values may be string literals or constants of the
+/// class (`self::NAME`).
+pub fn parse_definition(content: &str) -> Option<CommandDefinition> {
+    const VALUE: &str = r#"(?:'([^']*)'|"([^"]*)"|(?:self|static)::(\w+))"#;
+    let const_re = Regex::new(r#"const\s+(\w+)\s*=\s*(?:'([^']*)'|"([^"]*)")"#).unwrap();
+    let constants: HashMap<&str, &str> = const_re
+        .captures_iter(content)
+        .filter_map(|c| Some((c.get(1)?.as_str(), c.get(2).or_else(|| c.get(3))?.as_str())))
+        .collect();
+    let value = |caps: Captures| -> Option<String> {
+        match caps.get(1).or_else(|| caps.get(2)) {
+            Some(literal) => Some(literal.as_str().to_string()),
+            None => constants.get(caps.get(3)?.as_str()).map(|v| v.to_string()),
+        }
+    };
+    let first = |patterns: &[&str]| {
+        patterns.iter().find_map(|pattern| {
+            let re = Regex::new(&pattern.replace("{}", VALUE)).unwrap();
+            re.captures(content).and_then(value).filter(|v| !v.is_empty())
+        })
+    };
+    let name = first(&[
+        r"#\[AsCommand\(\s*(?:name\s*:\s*)?{}",
+        r"\$defaultName\s*=\s*{}",
+        r"->setName\(\s*{}",
+        r"parent::__construct\(\s*{}",
+    ])?;
+    let description = first(&[
+        r"#\[AsCommand\([^\]]*?description\s*:\s*{}",
+        r"\$defaultDescription\s*=\s*{}",
+        r"->setDescription\(\s*{}",
+    ]);
+    let aliases_re = Regex::new(r"->setAliases\(\s*(?:\[|array\()([^\])]*)").unwrap();
+    let quoted_re = Regex::new(r#"'([^']+)'|"([^"]+)""#).unwrap();
+    let aliases = aliases_re
+        .captures(content)
+        .map(|caps| {
+            quoted_re
+                .captures_iter(&caps[1])
+                .filter_map(|q| q.get(1).or_else(|| q.get(2)).map(|m| m.as_str().to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(CommandDefinition { name, description, aliases })
+}
+
+impl CommandRegistry {
+    /// Classes added to a command list's `commands` argument in a di.xml
+    pub fn add_di(&mut self, arguments: &[DiArgument], file: &str) {
+        for argument in arguments {
+            if argument.name != "commands" || !COMMAND_LIST_TYPES.contains(&argument.target.trim_start_matches('\\')) {
+                continue;
+            }
+            for class in &argument.items {
+                let files = self.registered.entry(class.trim_start_matches('\\').to_string()).or_default();
+                if !files.iter().any(|f| f == file) {
+                    files.push(file.to_string());
+                }
+            }
+        }
+    }
+
+    /// The command a PHP class defines, if any
+    pub fn add_php(&mut self, content: &str, file: &str) {
+        if let (Some(class), Some(definition)) = (php_class(content), parse_definition(content)) {
+            self.definitions.insert(class, (definition, file.to_string()));
+        }
+    }
+
+    /// Whether a di.xml registers `class` as a command
+    pub fn is_registered(&self, class: &str) -> bool {
+        self.registered.contains_key(class.trim_start_matches('\\'))
+    }
+
+    /// Registered commands sorted by name (classes without a known name
+    /// last), optionally only those whose name, class, description or alias
+    /// contains `filter` (case-insensitive)
+    pub fn commands(&self, filter: Option<&str>) -> Vec<ConsoleCommand> {
+        let mut commands: Vec<ConsoleCommand> = self
+            .registered
+            .iter()
+            .map(|(class, files)| {
+                let definition = self.definitions.get(class);
+                let mut registered_in = files.clone();
+                registered_in.sort();
+                ConsoleCommand {
+                    name: definition.map(|(d, _)| d.name.clone()),
+                    class: class.clone(),
+                    description: definition.and_then(|(d, _)| d.description.clone()),
+                    aliases: definition.map(|(d, _)| d.aliases.clone()).unwrap_or_default(),
+                    file: definition.map(|(_, file)| file.clone()),
+                    registered_in,
+                }
+            })
+            .filter(|c| filter.filter(|f| !f.is_empty()).is_none_or(|f| c.matches(f)))
+            .collect();
+        commands.sort_by(|a, b| (a.name.is_none(), &a.name, &a.class).cmp(&(b.name.is_none(), &b.name, &b.class)));
+        commands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registered.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magento::XmlAnalyzer;
+
+    const REINDEX: &str = r#"<?php
+namespace Magento\Indexer\Console\Command;
+
+/**
+ * Command to run indexers
+ */
+class IndexerReindexCommand extends AbstractIndexerManageCommand
+{
+    const INPUT_KEY_ALL = 'all';
+    private const NAME = 'indexer:reindex';
+
+    protected function configure()
+    {
+        $this->setName(self::NAME)
+            ->setDescription('Reindexes Data')
+            ->setAliases(['index:reindex']);
+    }
+}
+"#;
+
+    #[test]
+    fn test_parse_definition() {
+        let reindex = parse_definition(REINDEX).unwrap();
+        assert_eq!(reindex.name, "indexer:reindex");
+        assert_eq!(reindex.description.as_deref(), Some("Reindexes Data"));
+        assert_eq!(reindex.aliases, ["index:reindex"]);
+
+        let attribute = "#[AsCommand(name: 'acme:sync', description: 'Sync stock')]\nclass Sync extends Command {}";
+        let sync = parse_definition(attribute).unwrap();
+        assert_eq!((sync.name.as_str(), sync.description.as_deref()), ("acme:sync", Some("Sync stock")));
+        assert_eq!(parse_definition("protected static $defaultName = \"cache:flush\";").unwrap().name, "cache:flush");
+        assert!(parse_definition("$this->setName($this->name);").is_none());
+
+        assert!(is_command_name("indexer:reindex"));
+        assert!(is_command_name("setup:di:compile"));
+        assert!(!is_command_name("reindex"));
+        assert!(!is_command_name("https://example.com"));
+        assert!(!is_command_name("note:"));
+    }
+
+    #[test]
+    fn test_command_registry() {
+        let di = r#"<config>
+            <type name="Magento\Framework\Console\CommandListInterface">
+                <arguments>
+                    <argument name="commands" xsi:type="array">
+                        <item name="reindex" xsi:type="object">Magento\Indexer\Console\Command\IndexerReindexCommand</item>
+                        <item name="info" xsi:type="object">Magento\Indexer\Console\Command\IndexerInfoCommand</item>
+                    </argument>
+                </arguments>
+            </type>
+            <type name="Magento\Indexer\Model\Processor">
+                <arguments>
+                    <argument name="commands" xsi:type="array">
+                        <item name="x" xsi:type="object">Not\A\Command</item>
+                    </argument>
+                </arguments>
+            </type>
+        </config>"#;
+        let mut registry = CommandRegistry::default();
+        registry.add_di(&XmlAnalyzer::new().analyze(di).arguments, "Magento/Indexer/etc/di.xml");
+        registry.add_php(REINDEX, "Magento/Indexer/Console/Command/IndexerReindexCommand.php");
+
+        assert!(registry.is_registered("\\Magento\\Indexer\\Console\\Command\\IndexerReindexCommand"));
+        assert!(!registry.is_registered("Not\\A\\Command"));
+        let commands = registry.commands(None);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].name.as_deref(), Some("indexer:reindex"));
+        assert_eq!(commands[0].file.as_deref(), Some("Magento/Indexer/Console/Command/IndexerReindexCommand.php"));
+        assert_eq!(commands[0].registered_in, ["Magento/Indexer/etc/di.xml"]);
+        // Registered, but the class was not read
+        assert_eq!(commands[1].name, None);
+        assert_eq!(commands[1].class, "Magento\\Indexer\\Console\\Command\\IndexerInfoCommand");
+
+        assert_eq!(registry.commands(Some("REINDEX")).len(), 1);
+        assert_eq!(registry.commands(Some("index:reindex")).len(), 1);
+    }
+}
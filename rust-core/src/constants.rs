@@ -0,0 +1,117 @@
+//! String constant lookup (`magector find-string`)
+//!
+//! Event names, status codes and config paths usually live in class
+//! constants and backed enum cases (`const EVENT_PLACED = 'sales_order_place_after'`).
+//! The indexer stores each PHP file's constants in its metadata (see
+//! [`crate::vectordb::ClassConstant`]), so finding the constant behind a
+//! string seen in a log or the database needs no source scan.
+
+use serde::Serialize;
+
+use crate::vectordb::IndexMetadata;
+
+/// A string constant whose value matches the looked-up string
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StringConstant {
+    pub value: String,
+    /// `Class::NAME`, or just `NAME` outside a class
+    pub constant: String,
+    /// Fully qualified class, when the file declares one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    pub is_case: bool,
+}
+
+/// String constants of `items` whose value contains `value` (ignoring
+/// case), or equals it with `exact`. Exact matches come first, then by
+/// path; each file is looked at once.
+pub fn find<'a>(items: impl IntoIterator<Item = &'a IndexMetadata>, value: &str, exact: bool) -> Vec<StringConstant> {
+    let needle = value.to_lowercase();
+    let mut seen = std::collections::HashSet::new();
+    let mut found: Vec<StringConstant> = Vec::new();
+    for meta in items {
+        if meta.constants.is_empty() || !seen.insert(meta.path.as_str()) {
+            continue;
+        }
+        for constant in &meta.constants {
+            let Some(ref text) = constant.value.as_ref().filter(|_| constant.is_string) else { continue };
+            let matched = if exact { *text == value } else { text.to_lowercase().contains(&needle) };
+            if !matched {
+                continue;
+            }
+            let class = meta.class_name.as_ref().map(|class| match meta.namespace {
+                Some(ref ns) => format!("{}\\{}", ns, class),
+                None => class.clone(),
+            });
+            found.push(StringConstant {
+                value: text.to_string(),
+                constant: match meta.class_name {
+                    Some(ref class) => format!("{}::{}", class, constant.name),
+                    None => constant.name.clone(),
+                },
+                class,
+                path: meta.path.clone(),
+                module: meta.module.clone(),
+                is_case: constant.is_case,
+            });
+        }
+    }
+    found.sort_by(|a, b| (a.value != value).cmp(&(b.value != value)).then_with(|| a.path.cmp(&b.path)));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vectordb::ClassConstant;
+
+    fn constant(name: &str, value: &str, is_string: bool) -> ClassConstant {
+        ClassConstant { name: name.to_string(), value: Some(value.to_string()), is_string, is_case: false }
+    }
+
+    fn item(path: &str, class: &str, constants: Vec<ClassConstant>) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            class_name: Some(class.to_string()),
+            namespace: Some("Vendor\\Sales\\Model".to_string()),
+            module: Some("Vendor_Sales".to_string()),
+            search_text: String::new(),
+            urls: Vec::new(),
+            constants,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_string_constants() {
+        let order = item(
+            "app/code/Vendor/Sales/Model/Order.php",
+            "Order",
+            vec![
+                constant("STATE_NEW", "new", true),
+                constant("EVENT_PLACED", "vendor_order_placed", true),
+                constant("LIMIT", "10", false),
+            ],
+        );
+        let items = vec![
+            item("app/code/Vendor/Sales/Model/Status.php", "Status", vec![constant("PLACED", "placed", true)]),
+            // A method item of the same file repeats the constants
+            order.clone(),
+            order,
+        ];
+
+        let found = find(&items, "PLACED", false);
+        let names: Vec<&str> = found.iter().map(|c| c.constant.as_str()).collect();
+        assert_eq!(names, ["Order::EVENT_PLACED", "Status::PLACED"]);
+        assert_eq!(found[0].class.as_deref(), Some("Vendor\\Sales\\Model\\Order"));
+
+        let found = find(&items, "placed", false);
+        assert_eq!(found[0].constant, "Status::PLACED", "exact matches first");
+        assert_eq!(find(&items, "placed", true).len(), 1);
+        assert!(find(&items, "10", false).is_empty(), "only string values");
+    }
+}
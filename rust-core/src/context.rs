@@ -0,0 +1,248 @@
+//! Context packs for LLM agents (serve `assemble_context`)
+//!
+//! A search, reduced to what fits in a token budget: the best hit per file
+//! with its source snippet, each followed by the di.xml and layout XML lines
+//! that wire its class. Items keep that order and say why they are there, so
+//! a client can paste the pack into a prompt as is; secrets are masked with
+//! the project's redaction patterns.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::path_guard::ReadScope;
+use crate::source::read_source;
+use crate::vectordb::SearchResult;
+use crate::Indexer;
+
+/// Search hits considered for a pack
+const PACK_CANDIDATES: usize = 20;
+
+/// Longest snippet taken from a hit, in lines
+const MAX_SNIPPET_LINES: usize = 80;
+
+/// Most declaration lines quoted from one related XML file
+const MAX_MENTION_LINES: usize = 12;
+
+/// A snippet cut to fit must keep at least this many tokens
+const MIN_ITEM_TOKENS: usize = 40;
+
+/// One entry of a context pack
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContextItem {
+    pub path: String,
+    /// 1-based lines the snippet starts and ends at
+    pub start_line: usize,
+    pub end_line: usize,
+    pub snippet: String,
+    pub reason: String,
+    pub tokens: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContextPack {
+    pub query: String,
+    pub budget: usize,
+    pub tokens: usize,
+    pub items: Vec<ContextItem>,
+    /// Items left out because the budget ran out
+    pub omitted: usize,
+}
+
+/// Rough token count of text sent to an LLM (about 4 bytes per token)
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Collects items in order until the budget is spent
+#[derive(Debug)]
+pub struct ContextBuilder {
+    budget: usize,
+    tokens: usize,
+    items: Vec<ContextItem>,
+    seen: HashSet<(String, usize)>,
+    omitted: usize,
+}
+
+impl ContextBuilder {
+    pub fn new(budget: usize) -> Self {
+        Self { budget, tokens: 0, items: Vec::new(), seen: HashSet::new(), omitted: 0 }
+    }
+
+    /// Add a snippet of `path` starting at `start_line`. One that doesn't fit
+    /// is cut to whole lines in the remaining budget, or left out when less
+    /// than [`MIN_ITEM_TOKENS`] would remain. The same path and start line
+    /// are only added once. Returns whether the item is in the pack.
+    pub fn push(&mut self, path: &str, start_line: usize, snippet: &str, reason: String) -> bool {
+        if !self.seen.insert((path.to_string(), start_line)) {
+            return false;
+        }
+        let overhead = estimate_tokens(path) + estimate_tokens(&reason);
+        let left = self.budget.saturating_sub(self.tokens + overhead);
+
+        let mut kept = String::new();
+        let mut lines = 0;
+        for line in snippet.lines() {
+            if estimate_tokens(&kept) + estimate_tokens(line) + 1 > left {
+                break;
+            }
+            kept.push_str(line);
+            kept.push('\n');
+            lines += 1;
+        }
+        let complete = lines == snippet.lines().count();
+        if lines == 0 || (!complete && estimate_tokens(&kept) < MIN_ITEM_TOKENS) {
+            self.omitted += 1;
+            return false;
+        }
+
+        let tokens = overhead + estimate_tokens(&kept);
+        self.tokens += tokens;
+        self.items.push(ContextItem {
+            path: path.to_string(),
+            start_line,
+            end_line: start_line + lines - 1,
+            snippet: kept,
+            reason: if complete { reason } else { format!("{} (truncated)", reason) },
+            tokens,
+        });
+        true
+    }
+
+    pub fn finish(self, query: &str) -> ContextPack {
+        ContextPack {
+            query: query.to_string(),
+            budget: self.budget,
+            tokens: self.tokens,
+            items: self.items,
+            omitted: self.omitted,
+        }
+    }
+}
+
+/// The lines of a hit: its span when recorded, else the head of the file,
+/// at most [`MAX_SNIPPET_LINES`]. Returns the first line number and the text.
+pub fn hit_snippet(content: &str, result: &SearchResult) -> (usize, String) {
+    let (start, end) = match result.metadata.span {
+        Some(span) => (span.start_line.max(1), span.end_line),
+        None => (1, MAX_SNIPPET_LINES),
+    };
+    let end = end.min(start + MAX_SNIPPET_LINES - 1);
+    let text: Vec<&str> = content.lines().skip(start - 1).take(end + 1 - start).collect();
+    (start, text.join("\n"))
+}
+
+/// Lines of `content` that contain `needle`, up to [`MAX_MENTION_LINES`],
+/// each prefixed with its line number. `None` if there are none.
+pub fn mentions(content: &str, needle: &str) -> Option<(usize, String)> {
+    let found: Vec<(usize, &str)> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.contains(needle))
+        .take(MAX_MENTION_LINES)
+        .map(|(i, line)| (i + 1, line.trim()))
+        .collect();
+    let first = found.first()?.0;
+    let text: Vec<String> = found.iter().map(|(n, line)| format!("{}: {}", n, line)).collect();
+    Some((first, text.join("\n")))
+}
+
+/// Search `query` and build a pack of at most `budget` tokens
+pub fn assemble(indexer: &mut Indexer, query: &str, budget: usize) -> Result<ContextPack> {
+    let results = indexer.search(query, PACK_CANDIDATES)?;
+    // Stored paths only lead to files under the indexed roots
+    let scope = ReadScope::new(indexer.magento_root())?.with_extra_roots(&indexer.extra_root_paths());
+    // Secrets are masked like in indexed text, before anything is quoted
+    let redactor = indexer.redactor().clone();
+    let read = |path: &str| {
+        let source = read_source(&scope.resolve(path).ok()?).ok()?;
+        Some(redactor.redact(&source.text).into_owned())
+    };
+
+    let mut layout_paths: Vec<String> = indexer
+        .indexed_paths()
+        .into_iter()
+        .filter(|p| p.contains("/layout/") && p.ends_with(".xml"))
+        .collect();
+    layout_paths.sort();
+    // Read once, on the first hit with a class
+    let mut layouts: Option<Vec<(String, String)>> = None;
+    let xref = indexer.config_xref().ok();
+
+    let mut pack = ContextBuilder::new(budget);
+    let mut files = HashSet::new();
+    for (rank, result) in results.iter().enumerate() {
+        let meta = &result.metadata;
+        if !files.insert(meta.path.clone()) {
+            continue;
+        }
+        let Some(content) = read(&meta.path) else {
+            continue;
+        };
+        let (start, snippet) = hit_snippet(&content, result);
+        let kind = meta.magento_type.as_deref().map(|t| format!(", {}", t)).unwrap_or_default();
+        let overrides = meta.overrides.as_deref().map(|o| format!(", overrides {}", o)).unwrap_or_default();
+        let reason = format!("search hit {} (score {:.2}{}{})", rank + 1, result.score, kind, overrides);
+        pack.push(&meta.path, start, &snippet, reason);
+
+        let Some(class) = &meta.class_name else {
+            continue;
+        };
+        let fqcn = match &meta.namespace {
+            Some(ns) => format!("{}\\{}", ns, class),
+            None => class.clone(),
+        };
+        if let Some(xref) = xref {
+            for di in xref.di.explain(&fqcn, &[]).files {
+                if let Some((line, text)) = read(&di.file).and_then(|c| mentions(&c, &fqcn)) {
+                    pack.push(&di.file, line, &text, format!("DI wiring of {} ({})", class, di.area));
+                }
+            }
+        }
+        let layouts = layouts
+            .get_or_insert_with(|| layout_paths.iter().filter_map(|p| Some((p.clone(), read(p)?))).collect());
+        for (layout, content) in layouts.iter() {
+            if let Some((line, text)) = mentions(content, &fqcn) {
+                pack.push(layout, line, &text, format!("layout using {}", class));
+            }
+        }
+    }
+    Ok(pack.finish(query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_builder_budget() {
+        let mut pack = ContextBuilder::new(50);
+        assert!(pack.push("a.php", 1, "short line", "hit 1".into()));
+        assert!(!pack.push("a.php", 1, "short line", "hit 1 again".into()));
+        let long: Vec<String> = (0..100).map(|i| format!("line number {} of a long file", i)).collect();
+        // 40+ tokens don't fit in what is left
+        assert!(!pack.push("b.php", 1, &long.join("\n"), "hit 2".into()));
+        assert!(pack.push("c.xml", 7, "<type name=\"X\"/>", "DI wiring".into()));
+
+        let pack = pack.finish("q");
+        assert_eq!(pack.items.len(), 2);
+        assert_eq!(pack.omitted, 1);
+        assert!(pack.tokens <= 50);
+        assert_eq!((pack.items[1].start_line, pack.items[1].end_line), (7, 7));
+
+        let mut big = ContextBuilder::new(200);
+        assert!(big.push("b.php", 1, &long.join("\n"), "hit".into()));
+        let item = &big.finish("q").items[0];
+        assert!(item.reason.ends_with("(truncated)"));
+        assert!(item.end_line > 10 && item.end_line < 100);
+    }
+
+    #[test]
+    fn test_mentions() {
+        let xml = "<config>\n  <preference for=\"A\\B\" type=\"C\\D\"/>\n  <type name=\"E\"/>\n  <type name=\"C\\D\"/>\n</config>";
+        let (line, text) = mentions(xml, "C\\D").unwrap();
+        assert_eq!(line, 2);
+        assert_eq!(text, "2: <preference for=\"A\\B\" type=\"C\\D\"/>\n4: <type name=\"C\\D\"/>");
+        assert!(mentions(xml, "Z\\Z").is_none());
+    }
+}
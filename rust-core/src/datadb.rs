@@ -14,6 +14,10 @@ use serde_json;
 use std::collections::HashMap;
 use std::path::Path;
 
+/// Enrichment row returned by null-risk queries:
+/// `(file, line, chain, first_method, second_method)`.
+pub type NullRiskRow = (String, i64, String, String, String);
+
 /// Unified SQLite database wrapping a single connection to `.magector/data.db`.
 pub struct DataDb {
     conn: Connection,
@@ -170,6 +174,7 @@ impl DataDb {
     }
 
     /// Insert a single enrichment row.
+    #[allow(clippy::too_many_arguments)]
     pub fn enrich_insert(
         &self,
         file: &str,
@@ -198,7 +203,7 @@ impl DataDb {
         &self,
         first_method: Option<&str>,
         limit: usize,
-    ) -> Result<Vec<(String, i64, String, String, String)>> {
+    ) -> Result<Vec<NullRiskRow>> {
         let mut results = Vec::new();
 
         match first_method {
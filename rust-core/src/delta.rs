@@ -0,0 +1,214 @@
+//! Delta patches between two index versions (`magector delta` / `magector apply`)
+//!
+//! A patch holds only the items added or changed between a base and a target
+//! DB plus the IDs removed, so a distributed core index can be updated without
+//! shipping the whole file. Stable IDs turn the diff into a keyed compare.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::vectordb::{IndexHeader, IndexMetadata, VectorDB};
+
+/// Leading bytes of every patch file
+const MAGIC: &[u8; 4] = b"MGDX";
+
+/// Patch format version, written after the magic
+const DELTA_VERSION: u8 = 1;
+
+/// Compact the patched DB when more than this share of HNSW points is dead
+const COMPACT_THRESHOLD: f64 = 0.20;
+
+/// Difference between two index versions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeltaPatch {
+    /// [`VectorDB::content_hash`] of the DB the patch applies to
+    pub base_hash: String,
+    /// Content hash after applying, checked by [`DeltaPatch::apply`]
+    pub target_hash: String,
+    /// JSON-encoded header of the target DB
+    header: String,
+    /// Added or changed items, sorted by ID
+    pub upserts: Vec<(usize, Vec<f32>, IndexMetadata)>,
+    /// Metadata vectors of the upserted items that have one
+    pub meta_upserts: Vec<(usize, Vec<f32>)>,
+    /// IDs live in the base but gone from the target, sorted
+    pub removals: Vec<usize>,
+}
+
+impl DeltaPatch {
+    /// Diff `base` against `target`
+    pub fn between(base: &VectorDB, target: &VectorDB) -> Result<Self> {
+        let mut target_ids: Vec<usize> = target.metadata_iter().map(|(id, _)| id).collect();
+        target_ids.sort_unstable();
+        let upserts: Vec<(usize, Vec<f32>, IndexMetadata)> = target_ids
+            .into_iter()
+            .filter_map(|id| {
+                let (vector, meta) = target.get(id)?;
+                let unchanged = base.get(id).is_some_and(|(v, m)| v == vector && m == meta)
+                    && base.meta_vector(id) == target.meta_vector(id);
+                (!unchanged).then(|| (id, vector.to_vec(), meta.clone()))
+            })
+            .collect();
+        let meta_upserts = upserts
+            .iter()
+            .filter_map(|(id, _, _)| Some((*id, target.meta_vector(*id)?.to_vec())))
+            .collect();
+
+        let mut removals: Vec<usize> = base
+            .metadata_iter()
+            .map(|(id, _)| id)
+            .filter(|&id| target.get(id).is_none())
+            .collect();
+        removals.sort_unstable();
+
+        Ok(Self {
+            base_hash: base.content_hash(),
+            target_hash: target.content_hash(),
+            header: serde_json::to_string(target.header())?,
+            upserts,
+            meta_upserts,
+            removals,
+        })
+    }
+
+    /// Whether applying the patch would change nothing but the header
+    pub fn is_empty(&self) -> bool {
+        self.upserts.is_empty() && self.removals.is_empty()
+    }
+
+    /// Write the patch (magic, version byte, bincode payload)
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut writer = BufWriter::with_capacity(1 << 20, file);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[DELTA_VERSION])?;
+        bincode::serde::encode_into_std_write(self, &mut writer, bincode::config::standard())
+            .context("Failed to serialize delta patch")?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read a patch written by [`DeltaPatch::write`]
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        if bytes.len() < MAGIC.len() + 1 || &bytes[..MAGIC.len()] != MAGIC {
+            bail!("{} is not a magector delta patch", path.display());
+        }
+        let version = bytes[MAGIC.len()];
+        if version != DELTA_VERSION {
+            bail!("Unsupported delta patch version {} (expected {})", version, DELTA_VERSION);
+        }
+        let (patch, _) = bincode::serde::decode_from_slice(&bytes[MAGIC.len() + 1..], bincode::config::standard())
+            .context("Failed to decode delta patch")?;
+        Ok(patch)
+    }
+
+    /// Apply the patch to `db` in place.
+    ///
+    /// Fails without touching `db` unless its contents match the base the
+    /// patch was made from, and fails if the result doesn't hash to the target.
+    pub fn apply(&self, db: &mut VectorDB) -> Result<()> {
+        let base_hash = db.content_hash();
+        if base_hash != self.base_hash {
+            bail!(
+                "Patch does not apply to this index: content hash {} does not match the patch base {}",
+                short_hash(&base_hash),
+                short_hash(&self.base_hash)
+            );
+        }
+        let header: IndexHeader = serde_json::from_str(&self.header).context("Invalid header in delta patch")?;
+
+        let changed = self.upserts.iter().map(|(id, _, _)| *id);
+        for id in self.removals.iter().copied().chain(changed) {
+            if db.get(id).is_some() {
+                db.tombstone(id);
+            }
+        }
+        let wanted: Vec<usize> = self.upserts.iter().map(|(id, _, _)| *id).collect();
+        let assigned = db.insert_batch_with_ids(self.upserts.clone());
+        if assigned != wanted {
+            bail!("Delta patch produced conflicting vector IDs");
+        }
+        db.insert_meta_vectors(self.meta_upserts.clone());
+        db.set_header(header);
+
+        if db.tombstone_ratio() > COMPACT_THRESHOLD {
+            db.compact();
+        }
+
+        let result_hash = db.content_hash();
+        if result_hash != self.target_hash {
+            bail!(
+                "Patched index content hash {} does not match the patch target {}",
+                short_hash(&result_hash),
+                short_hash(&self.target_hash)
+            );
+        }
+        Ok(())
+    }
+}
+
+fn short_hash(hash: &str) -> &str {
+    &hash[..hash.len().min(12)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::EMBEDDING_DIM;
+
+    fn item(path: &str, seed: f32) -> (Vec<f32>, IndexMetadata) {
+        let mut vector = vec![0.1f32; EMBEDDING_DIM];
+        vector[0] = seed;
+        let meta: IndexMetadata = serde_json::from_value(serde_json::json!({
+            "path": path, "file_type": "php", "magento_type": null, "class_name": null,
+            "class_type": null, "method_name": null, "methods": [], "namespace": null,
+            "module": null, "area": null, "extends": null, "implements": [],
+            "is_controller": false, "is_repository": false, "is_plugin": false,
+            "is_observer": false, "is_model": false, "is_block": false, "is_resolver": false,
+            "is_api_interface": false, "is_ui_component": false, "is_widget": false,
+            "is_mixin": false, "js_dependencies": [], "search_text": path, "is_generated": false,
+            "owners": [], "collection": "code", "headings": [], "urls": [], "constants": [],
+            "traits": [], "trait_methods": [], "inherited_types": [], "implementation": null, "root": null,
+        }))
+        .unwrap();
+        (vector, meta)
+    }
+
+    #[test]
+    fn test_delta_roundtrip() {
+        let mut old = VectorDB::new();
+        old.insert_batch(vec![item("a.php", 0.9), item("b.php", 0.5), item("c.php", 0.3)]);
+        let mut new = VectorDB::new();
+        let ids = new.insert_batch(vec![item("a.php", 0.9), item("b.php", 0.7), item("d.php", 0.2)]);
+        new.insert_meta_vectors(vec![(ids[2], vec![0.4f32; EMBEDDING_DIM])]);
+        new.set_header(IndexHeader { root: "/srv/magento".into(), ..Default::default() });
+
+        let patch = DeltaPatch::between(&old, &new).unwrap();
+        // b.php changed, d.php added, c.php removed; a.php is not shipped
+        assert_eq!(patch.upserts.len(), 2);
+        assert_eq!(patch.meta_upserts.len(), 1);
+        assert_eq!(patch.removals.len(), 1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("patch.mdx");
+        patch.write(&path).unwrap();
+        let patch = DeltaPatch::read(&path).unwrap();
+
+        let mut target = VectorDB::new();
+        target.insert_batch(vec![item("a.php", 0.9), item("b.php", 0.5), item("c.php", 0.3)]);
+        patch.apply(&mut target).unwrap();
+        assert_eq!(target.content_hash(), new.content_hash());
+        assert_eq!(target.header().root, "/srv/magento");
+        assert_eq!(target.len(), 3);
+
+        // Applying twice is rejected: the DB no longer matches the base
+        assert!(patch.apply(&mut target).is_err());
+    }
+}
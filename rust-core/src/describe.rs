@@ -14,6 +14,8 @@ use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
 
+use crate::vectordb::relative_path;
+
 const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1/messages";
 const DEFAULT_MODEL: &str = "claude-sonnet-4-5-20250929";
 
@@ -186,14 +188,6 @@ fn find_di_xml_files(magento_root: &Path) -> Vec<PathBuf> {
         .collect()
 }
 
-fn relative_path(magento_root: &Path, full_path: &Path) -> String {
-    full_path
-        .strip_prefix(magento_root)
-        .unwrap_or(full_path)
-        .to_string_lossy()
-        .to_string()
-}
-
 fn now_timestamp() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -0,0 +1,420 @@
+//! DI wiring of a class (serve `explain_di`)
+//!
+//! Magento merges every module's `etc/di.xml` with the area-specific
+//! `etc/<area>/di.xml` files on top. [`DiGraph`] keeps each declaration with
+//! the file and area it came from, so [`DiGraph::explain`] can answer "how is
+//! X wired": which preferences resolve to it, which plugins wrap it (also
+//! through the interfaces it is preferred for), what its constructor gets
+//! injected and which di.xml files mention it at all.
+
+use serde::Serialize;
+
+use crate::ast::PhpAstMetadata;
+use crate::magento::{DiArgument, PluginDeclaration, VirtualTypeDeclaration, XmlMetadata};
+
+/// `global` for `etc/di.xml`, the area directory (`frontend`, `adminhtml`,
+/// `webapi_rest`, ...) for `etc/<area>/di.xml`
+pub fn di_area(file: &str) -> String {
+    let mut dirs = file.rsplit('/').skip(1);
+    match dirs.next() {
+        Some("etc") | None => "global".to_string(),
+        Some(area) => area.to_string(),
+    }
+}
+
+/// A `<preference>` with its origin
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiPreference {
+    #[serde(rename = "for")]
+    pub for_type: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub area: String,
+    pub file: String,
+}
+
+/// A `<plugin>` on the class or on an interface preferred to it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiPlugin {
+    pub target: String,
+    pub name: String,
+    pub class: String,
+    pub sort_order: Option<i32>,
+    pub disabled: bool,
+    pub area: String,
+    pub file: String,
+}
+
+/// A di.xml `<argument>` on the class with its origin
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiArgumentOverride {
+    pub xsi_type: String,
+    /// Text value: the injected class for `object` arguments
+    pub value: String,
+    /// Classes of `object` items inside an `array` argument
+    pub items: Vec<String>,
+    pub area: String,
+    pub file: String,
+}
+
+/// A constructor parameter and what DI puts there
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiInjection {
+    pub name: String,
+    /// Declared type; `None` for di.xml arguments with no matching parameter
+    pub type_hint: Option<String>,
+    /// di.xml arguments set for the parameter, per area
+    pub arguments: Vec<DiArgumentOverride>,
+    /// Preferences resolving the declared type, per area
+    pub preferences: Vec<DiPreference>,
+}
+
+/// A `<virtualType>` based on the class
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiVirtualType {
+    pub name: String,
+    pub area: String,
+    pub file: String,
+}
+
+/// A di.xml file mentioning the class
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiFile {
+    pub file: String,
+    pub area: String,
+}
+
+/// Everything the DI config says about one class
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiExplanation {
+    pub class: String,
+    /// Preferences resolving some type (usually an interface) to the class
+    pub preferred_for: Vec<DiPreference>,
+    /// Preferences replacing the class itself
+    pub replaced_by: Vec<DiPreference>,
+    /// Sorted by area, then sort order
+    pub plugins: Vec<DiPlugin>,
+    pub injections: Vec<DiInjection>,
+    /// Virtual types based on the class
+    pub virtual_types: Vec<DiVirtualType>,
+    pub files: Vec<DiFile>,
+}
+
+/// DI declarations of every di.xml, with their origin
+#[derive(Debug, Clone, Default)]
+pub struct DiGraph {
+    preferences: Vec<DiPreference>,
+    plugins: Vec<(PluginDeclaration, String)>,
+    arguments: Vec<(DiArgument, String)>,
+    virtual_types: Vec<(VirtualTypeDeclaration, String)>,
+    /// `<type name>` entries as (type, file)
+    types: Vec<(String, String)>,
+}
+
+fn normalize(class: &str) -> &str {
+    class.trim().trim_start_matches('\\')
+}
+
+/// Constructor parameters of a parsed class as (name, type), class types
+/// resolved to FQCNs through the file's namespace and `use` statements
+pub fn constructor_params(php: &PhpAstMetadata) -> Vec<(String, Option<String>)> {
+    let resolve = |hint: &str| -> String {
+        let hint = hint.trim_start_matches('?');
+        if let Some(fqcn) = hint.strip_prefix('\\') {
+            return fqcn.to_string();
+        }
+        // Builtins and unions stay as written
+        if hint.contains('|') || hint.chars().next().is_some_and(|c| c.is_lowercase()) {
+            return hint.to_string();
+        }
+        let (first, rest) = match hint.split_once('\\') {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (hint, None),
+        };
+        let imported = php.uses.iter().find_map(|u| {
+            let name = u.alias.as_deref().unwrap_or_else(|| u.full_path.rsplit('\\').next().unwrap_or(""));
+            (name == first).then(|| normalize(&u.full_path).to_string())
+        });
+        let base = match (imported, &php.namespace) {
+            (Some(imported), _) => imported,
+            (None, Some(ns)) => format!("{}\\{}", ns, first),
+            (None, None) => first.to_string(),
+        };
+        match rest {
+            Some(rest) => format!("{}\\{}", base, rest),
+            None => base,
+        }
+    };
+    php.methods
+        .iter()
+        .find(|m| m.name == "__construct")
+        .map(|m| m.parameters.iter().map(|p| (p.name.clone(), p.type_hint.as_deref().map(resolve))).collect())
+        .unwrap_or_default()
+}
+
+impl DiGraph {
+    /// Add the declarations of one di.xml (relative path `file`)
+    pub fn add(&mut self, meta: &XmlMetadata, file: &str) {
+        let area = di_area(file);
+        for (for_type, type_name) in &meta.preferences {
+            self.preferences.push(DiPreference {
+                for_type: normalize(for_type).to_string(),
+                type_name: normalize(type_name).to_string(),
+                area: area.clone(),
+                file: file.to_string(),
+            });
+        }
+        self.plugins.extend(meta.plugins.iter().map(|p| (p.clone(), file.to_string())));
+        self.arguments.extend(meta.arguments.iter().map(|a| (a.clone(), file.to_string())));
+        self.virtual_types.extend(meta.virtual_types.iter().map(|vt| (vt.clone(), file.to_string())));
+        self.types.extend(meta.types.iter().map(|t| (normalize(t).to_string(), file.to_string())));
+    }
+
+    /// Preferences for `type_name`
+    fn preferences_for(&self, type_name: &str) -> Vec<DiPreference> {
+        self.preferences.iter().filter(|p| p.for_type == type_name).cloned().collect()
+    }
+
+    /// Wiring of `class`, given its constructor parameters as (name, type)
+    /// when its source is known
+    pub fn explain(&self, class: &str, constructor: &[(String, Option<String>)]) -> DiExplanation {
+        let class = normalize(class);
+        let preferred_for: Vec<DiPreference> = self.preferences.iter().filter(|p| p.type_name == class).cloned().collect();
+        let replaced_by = self.preferences_for(class);
+
+        // Plugins on an interface apply to the class it is preferred to
+        let mut plugins: Vec<DiPlugin> = self
+            .plugins
+            .iter()
+            .filter(|(p, _)| {
+                let target = normalize(&p.target_class);
+                target == class || preferred_for.iter().any(|pref| pref.for_type == target)
+            })
+            .map(|(p, file)| DiPlugin {
+                target: normalize(&p.target_class).to_string(),
+                name: p.name.clone(),
+                class: normalize(&p.plugin_class).to_string(),
+                sort_order: p.sort_order,
+                disabled: p.disabled,
+                area: di_area(file),
+                file: file.clone(),
+            })
+            .collect();
+        plugins.sort_by(|a, b| {
+            (a.area != "global", &a.area, a.sort_order.unwrap_or(0), &a.name)
+                .cmp(&(b.area != "global", &b.area, b.sort_order.unwrap_or(0), &b.name))
+        });
+
+        let overrides = |name: &str| -> Vec<DiArgumentOverride> {
+            self.arguments
+                .iter()
+                .filter(|(a, _)| normalize(&a.target) == class && a.name == name)
+                .map(|(a, file)| DiArgumentOverride {
+                    xsi_type: a.xsi_type.clone(),
+                    value: a.value.clone(),
+                    items: a.items.clone(),
+                    area: di_area(file),
+                    file: file.clone(),
+                })
+                .collect()
+        };
+        let mut injections: Vec<DiInjection> = constructor
+            .iter()
+            .map(|(name, type_hint)| DiInjection {
+                name: name.clone(),
+                type_hint: type_hint.clone(),
+                arguments: overrides(name),
+                preferences: type_hint.as_deref().map(|t| self.preferences_for(normalize(t))).unwrap_or_default(),
+            })
+            .collect();
+        // Arguments set in di.xml for parameters the constructor doesn't show
+        // (source unknown, or inherited from a parent)
+        for (argument, _) in self.arguments.iter().filter(|(a, _)| normalize(&a.target) == class) {
+            if !injections.iter().any(|i| i.name == argument.name) {
+                injections.push(DiInjection {
+                    name: argument.name.clone(),
+                    type_hint: None,
+                    arguments: overrides(&argument.name),
+                    preferences: Vec::new(),
+                });
+            }
+        }
+
+        let virtual_types = self
+            .virtual_types
+            .iter()
+            .filter(|(vt, _)| normalize(&vt.base_type) == class)
+            .map(|(vt, file)| DiVirtualType { name: vt.name.clone(), area: di_area(file), file: file.clone() })
+            .collect();
+
+        let mut files: Vec<DiFile> = self
+            .preferences
+            .iter()
+            .filter(|p| p.for_type == class || p.type_name == class)
+            .map(|p| p.file.clone())
+            .chain(
+                self.plugins
+                    .iter()
+                    .filter(|(p, _)| normalize(&p.target_class) == class || normalize(&p.plugin_class) == class)
+                    .map(|(_, f)| f.clone()),
+            )
+            .chain(
+                self.arguments
+                    .iter()
+                    .filter(|(a, _)| {
+                        normalize(&a.target) == class
+                            || normalize(&a.value) == class
+                            || a.items.iter().any(|i| normalize(i) == class)
+                    })
+                    .map(|(_, f)| f.clone()),
+            )
+            .chain(self.virtual_types.iter().filter(|(vt, _)| normalize(&vt.base_type) == class).map(|(_, f)| f.clone()))
+            .chain(self.types.iter().filter(|(t, _)| t == class).map(|(_, f)| f.clone()))
+            .map(|file| DiFile { area: di_area(&file), file })
+            .collect();
+        files.sort_by(|a, b| a.file.cmp(&b.file));
+        files.dedup();
+
+        DiExplanation {
+            class: class.to_string(),
+            preferred_for,
+            replaced_by,
+            plugins,
+            injections,
+            virtual_types,
+            files,
+        }
+    }
+
+    /// Class the virtual type `name` is based on, through any chain of
+    /// virtual types; other names are returned as given
+    pub fn resolve_virtual_type(&self, name: &str) -> String {
+        let mut class = normalize(name).to_string();
+        // Bounded so a cycle in broken config ends
+        for _ in 0..self.virtual_types.len() {
+            match self.virtual_types.iter().find(|(vt, _)| normalize(&vt.name) == class) {
+                Some((vt, _)) if !vt.base_type.is_empty() => class = normalize(&vt.base_type).to_string(),
+                _ => break,
+            }
+        }
+        class
+    }
+
+    /// Every `<preference>`, in the order the files were added
+    pub fn preferences(&self) -> &[DiPreference] {
+        &self.preferences
+    }
+
+    /// Every `<plugin>` with the di.xml declaring it
+    pub fn plugins(&self) -> impl Iterator<Item = (&PluginDeclaration, &str)> {
+        self.plugins.iter().map(|(p, file)| (p, file.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.preferences.is_empty() && self.plugins.is_empty() && self.arguments.is_empty() && self.types.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magento::XmlAnalyzer;
+
+    #[test]
+    fn test_explain() {
+        let analyzer = XmlAnalyzer::new();
+        let mut graph = DiGraph::default();
+        graph.add(
+            &analyzer.analyze(
+                r#"<config>
+    <preference for="Magento\Checkout\Api\PaymentInformationManagementInterface" type="Magento\Checkout\Model\PaymentInformationManagement"/>
+    <type name="Magento\Checkout\Api\PaymentInformationManagementInterface">
+        <plugin name="validate_agreements" type="Magento\CheckoutAgreements\Model\Checkout\Plugin\Validation" sortOrder="20"/>
+    </type>
+    <type name="Magento\Checkout\Model\PaymentInformationManagement">
+        <arguments>
+            <argument name="logger" xsi:type="object">Magento\Checkout\Model\Logger\Virtual</argument>
+        </arguments>
+    </type>
+    <virtualType name="CheckoutPaymentManagementDebug" type="Magento\Checkout\Model\PaymentInformationManagement"/>
+</config>"#,
+            ),
+            "vendor/magento/module-checkout/etc/di.xml",
+        );
+        graph.add(
+            &analyzer.analyze(
+                r#"<config>
+    <preference for="Psr\Log\LoggerInterface" type="Magento\Framework\Logger\LoggerProxy"/>
+    <type name="Magento\Checkout\Model\PaymentInformationManagement">
+        <plugin name="vendor_fee" type="Vendor\Fee\Plugin\AddFee" sortOrder="10"/>
+    </type>
+</config>"#,
+            ),
+            "app/code/Vendor/Fee/etc/webapi_rest/di.xml",
+        );
+
+        let explanation = graph.explain(
+            "\\Magento\\Checkout\\Model\\PaymentInformationManagement",
+            &[
+                ("logger".to_string(), Some("Psr\\Log\\LoggerInterface".to_string())),
+                ("cartManagement".to_string(), Some("Magento\\Quote\\Api\\CartManagementInterface".to_string())),
+            ],
+        );
+        assert_eq!(explanation.class, "Magento\\Checkout\\Model\\PaymentInformationManagement");
+        assert_eq!(explanation.preferred_for.len(), 1);
+        assert_eq!(explanation.preferred_for[0].area, "global");
+        assert!(explanation.replaced_by.is_empty());
+        // Global plugins first, the interface's plugin included
+        let plugins: Vec<(&str, &str)> = explanation.plugins.iter().map(|p| (p.name.as_str(), p.area.as_str())).collect();
+        assert_eq!(plugins, vec![("validate_agreements", "global"), ("vendor_fee", "webapi_rest")]);
+        let logger = &explanation.injections[0];
+        assert_eq!(logger.arguments[0].value, "Magento\\Checkout\\Model\\Logger\\Virtual");
+        assert_eq!(logger.preferences[0].area, "webapi_rest");
+        assert!(explanation.injections[1].arguments.is_empty());
+        assert_eq!(explanation.virtual_types[0].name, "CheckoutPaymentManagementDebug");
+        assert_eq!(
+            graph.resolve_virtual_type("CheckoutPaymentManagementDebug"),
+            "Magento\\Checkout\\Model\\PaymentInformationManagement"
+        );
+        assert_eq!(graph.resolve_virtual_type("\\Psr\\Log\\LoggerInterface"), "Psr\\Log\\LoggerInterface");
+        assert_eq!(explanation.files.len(), 2);
+        assert_eq!(di_area("app/etc/di.xml"), "global");
+    }
+
+    #[test]
+    fn test_constructor_params() {
+        let Ok(mut analyzer) = crate::ast::PhpAstAnalyzer::new() else { return };
+        let php = analyzer.analyze(
+            r#"<?php
+namespace Magento\Checkout\Model;
+
+use Psr\Log\LoggerInterface as Logger;
+use Magento\Quote\Api;
+
+class PaymentInformationManagement
+{
+    public function __construct(
+        Logger $logger,
+        Api\CartManagementInterface $cartManagement,
+        ?Session $session,
+        \Magento\Framework\App\RequestInterface $request,
+        array $data = []
+    ) {
+    }
+}"#,
+        );
+        let params = constructor_params(&php);
+        let types: Vec<Option<&str>> = params.iter().map(|(_, t)| t.as_deref()).collect();
+        assert_eq!(
+            types,
+            vec![
+                Some("Psr\\Log\\LoggerInterface"),
+                Some("Magento\\Quote\\Api\\CartManagementInterface"),
+                Some("Magento\\Checkout\\Model\\Session"),
+                Some("Magento\\Framework\\App\\RequestInterface"),
+                Some("array")
+            ]
+        );
+        assert_eq!(params[0].0, "logger");
+    }
+}
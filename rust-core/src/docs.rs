@@ -0,0 +1,160 @@
+//! Project documentation (`index --include-docs`)
+//!
+//! Markdown files (dev docs, ADRs, module READMEs) are indexed into the
+//! `docs` [`Collection`](crate::vectordb::Collection) as `doc` items, one per
+//! section so a long guide does not blur into one vector. Sections start at
+//! headings up to `###` (ATX, or setext `===`/`---` underlines) outside
+//! fenced code blocks and YAML front matter; text before the first heading
+//! is a section of its own. Each section records its heading breadcrumbs,
+//! outermost first.
+
+/// Extensions indexed as documentation
+pub const DOC_EXTENSIONS: &[&str] = &["md", "markdown"];
+
+/// Deepest heading level that starts a new section
+const MAX_SECTION_LEVEL: usize = 3;
+
+/// One section of a Markdown document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocSection {
+    /// Titles of the enclosing headings and the section's own, outermost
+    /// first; empty for text before the first heading
+    pub headings: Vec<String>,
+    /// Byte range in the document, heading included
+    pub start: usize,
+    pub end: usize,
+}
+
+impl DocSection {
+    /// Breadcrumbs joined for display (`Deployment › Production`)
+    pub fn title(&self) -> String {
+        self.headings.join(" › ")
+    }
+}
+
+/// Whether a file at `path` is documentation outside `vendor/` (Composer
+/// packages ship their own READMEs and changelogs, which are noise here)
+pub fn is_doc_path(path: &str) -> bool {
+    !path.starts_with("vendor/")
+        && path.rsplit_once('.').is_some_and(|(_, ext)| DOC_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Level and text of an ATX heading line (`## Title`)
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let line = line.trim_end();
+    let level = line.bytes().take_while(|&b| b == b'#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+/// Level of a setext underline (`===` is 1, `---` is 2)
+fn underline(line: &str) -> Option<usize> {
+    let line = line.trim();
+    match line.as_bytes().first()? {
+        b'=' if line.len() >= 2 && line.bytes().all(|b| b == b'=') => Some(1),
+        b'-' if line.len() >= 2 && line.bytes().all(|b| b == b'-') => Some(2),
+        _ => None,
+    }
+}
+
+/// Whether a line can be the text of a setext heading (not blank, a list
+/// item, a quote or a table row)
+fn is_paragraph(line: &str) -> bool {
+    let trimmed = line.trim();
+    !trimmed.is_empty() && !line.starts_with("    ") && !trimmed.starts_with(['-', '*', '+', '>', '|'])
+}
+
+/// Split `text` into sections. A heading directly followed by another
+/// starts no section of its own; it stays in the next one's breadcrumbs
+/// when that one is nested under it.
+pub fn sections(text: &str) -> Vec<DocSection> {
+    let mut sections: Vec<DocSection> = Vec::new();
+    let mut trail: Vec<(usize, String)> = Vec::new();
+    let mut current = DocSection { headings: Vec::new(), start: 0, end: 0 };
+    let mut body_start = 0;
+    let mut fence: Option<&str> = None;
+    let mut previous: Option<(usize, &str)> = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let mut found = None;
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if offset == 0 && line.trim_end() == "---" {
+            // YAML front matter
+            fence = Some("---");
+        } else if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+        } else if let Some((level, title)) = heading(line) {
+            found = Some((level, title.to_string(), offset));
+        } else if let (Some(level), Some((at, title))) = (underline(line), previous) {
+            found = Some((level, title.trim().to_string(), at));
+        }
+        previous = (fence.is_none() && found.is_none() && is_paragraph(line)).then_some((offset, line));
+        offset += line.len();
+
+        let Some((level, title, at)) = found.filter(|(level, _, _)| *level <= MAX_SECTION_LEVEL) else {
+            continue;
+        };
+        if !text[body_start.min(at)..at].trim().is_empty() {
+            current.end = at;
+            sections.push(current);
+            current = DocSection { headings: Vec::new(), start: at, end: at };
+        }
+        trail.retain(|(outer, _)| *outer < level);
+        trail.push((level, title));
+        current.headings = trail.iter().map(|(_, title)| title.clone()).collect();
+        body_start = offset;
+    }
+    current.end = text.len();
+    if !text[body_start.min(current.end)..].trim().is_empty() || sections.is_empty() {
+        sections.push(current);
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections() {
+        let doc = "Intro text.\n\n# ADR 7: Checkout\n\n## Context\nWe need it.\n\n```sh\n# not a heading\n```\n\
+                   #### Detail\nstays in Context\n## Decision\n### Consequences\nFaster.\n";
+        let found = sections(doc);
+        let titles: Vec<_> = found.iter().map(DocSection::title).collect();
+        assert_eq!(titles, ["", "ADR 7: Checkout › Context", "ADR 7: Checkout › Decision › Consequences"]);
+        assert_eq!(&doc[found[0].start..found[0].end], "Intro text.\n\n");
+        let context = &doc[found[1].start..found[1].end];
+        assert!(context.starts_with("# ADR 7") && context.contains("# not a heading") && context.contains("#### Detail"));
+        assert!(doc[found[2].start..].starts_with("## Decision"));
+        assert_eq!(found.last().unwrap().end, doc.len());
+
+        // A heading without a body is still one section
+        assert_eq!(sections("# Title\n"), [DocSection { headings: vec!["Title".to_string()], start: 0, end: 8 }]);
+
+        assert!(is_doc_path("docs/adr/0007-checkout.md"));
+        assert!(is_doc_path("app/code/Acme/Cart/README.MD"));
+        assert!(!is_doc_path("vendor/magento/module-cart/README.md"));
+        assert!(!is_doc_path("app/code/Acme/Cart/Model/Cart.php"));
+    }
+
+    #[test]
+    fn test_setext_and_front_matter() {
+        let doc = "---\ntitle: Deploy\n---\nAcme Store\n==========\n\nOverview.\n\nDeployment\n----------\nRun it.\n\n\
+                   - a list item\n---\n\nStill deployment.\n";
+        let found = sections(doc);
+        let titles: Vec<_> = found.iter().map(DocSection::title).collect();
+        assert_eq!(titles, ["", "Acme Store", "Acme Store › Deployment"]);
+        assert!(doc[found[1].start..found[1].end].starts_with("Acme Store\n====="));
+        assert!(doc[found[2].start..].contains("Still deployment."));
+    }
+}
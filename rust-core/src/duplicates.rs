@@ -0,0 +1,195 @@
+//! Near-duplicate files across modules (`magector analyze duplicates`)
+//!
+//! Copy-pasted helpers and cloned plugins embed almost alike. Each file's
+//! vector (the mean of its items', see [`crate::codemap::file_vectors`]) is
+//! searched for in the HNSW graph until its nearest items span enough
+//! distinct files; those that belong to another module are compared exactly, and pairs at or above the
+//! similarity threshold are reported, most similar first. Files in the same
+//! module and generated code are left out.
+
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::codemap::file_vectors;
+use crate::vectordb::VectorDB;
+
+/// Default cosine similarity a pair needs
+pub const DEFAULT_THRESHOLD: f32 = 0.95;
+
+/// Nearest files looked at per file
+const CANDIDATES: usize = 20;
+
+/// Most items searched to find them, when the items of a few files (one
+/// per method) crowd out the rest
+const MAX_ITEMS_SEARCHED: usize = CANDIDATES * 32;
+
+/// Two files of different modules that embed almost alike
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicatePair {
+    pub similarity: f32,
+    pub path_a: String,
+    pub module_a: String,
+    pub path_b: String,
+    pub module_b: String,
+    /// Class of each file, when it declares one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class_a: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class_b: Option<String>,
+}
+
+/// Pairs of `file_type` files (all types when empty) in different modules
+/// whose vectors have a cosine similarity of at least `threshold`, most
+/// similar first
+pub fn find(db: &VectorDB, file_type: &str, threshold: f32) -> Vec<DuplicatePair> {
+    let files = file_vectors(db, |meta| {
+        meta.module.is_some() && !meta.is_generated && (file_type.is_empty() || meta.file_type == file_type)
+    });
+    let index: HashMap<&str, usize> = files.iter().enumerate().map(|(i, (path, _, _))| (path.as_str(), i)).collect();
+    let mut classes: HashMap<&str, &str> = HashMap::new();
+    for (_, meta) in db.metadata_iter() {
+        if let Some(class) = meta.class_name.as_deref() {
+            classes.entry(meta.path.as_str()).or_insert(class);
+        }
+    }
+
+    let pairs: BTreeSet<(usize, usize)> = files
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(i, (_, module, vector))| {
+            let mut found = Vec::new();
+            for j in nearest_files(db, vector, &index) {
+                if j != i && files[j].1 != *module && dot(vector, &files[j].2) >= threshold {
+                    found.push((i.min(j), i.max(j)));
+                }
+            }
+            found
+        })
+        .collect();
+
+    let mut report: Vec<DuplicatePair> = pairs
+        .into_iter()
+        .map(|(i, j)| {
+            let (a, b) = (&files[i], &files[j]);
+            DuplicatePair {
+                similarity: dot(&a.2, &b.2),
+                path_a: a.0.clone(),
+                module_a: a.1.clone().unwrap_or_default(),
+                path_b: b.0.clone(),
+                module_b: b.1.clone().unwrap_or_default(),
+                class_a: classes.get(a.0.as_str()).map(|c| c.to_string()),
+                class_b: classes.get(b.0.as_str()).map(|c| c.to_string()),
+            }
+        })
+        .collect();
+    report.sort_by(|a, b| b.similarity.total_cmp(&a.similarity).then_with(|| a.path_a.cmp(&b.path_a)));
+    report
+}
+
+/// Indexes (into `index`) of the files of the items nearest `vector`, up
+/// to [`CANDIDATES`] distinct ones. The search widens while too few files
+/// show up.
+fn nearest_files(db: &VectorDB, vector: &[f32], index: &HashMap<&str, usize>) -> Vec<usize> {
+    let mut k = CANDIDATES * 4;
+    loop {
+        let hits = db.search(vector, k);
+        let mut seen = HashSet::new();
+        let files: Vec<usize> = hits
+            .iter()
+            .filter_map(|hit| index.get(hit.metadata.path.as_str()).copied())
+            .filter(|&j| seen.insert(j))
+            .take(CANDIDATES)
+            .collect();
+        if files.len() == CANDIDATES || hits.len() < k || k >= MAX_ITEMS_SEARCHED {
+            return files;
+        }
+        k *= 2;
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::EMBEDDING_DIM;
+    use crate::IndexMetadata;
+
+    fn meta(path: &str, module: &str) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            class_name: Some(path.rsplit('/').next().unwrap().trim_end_matches(".php").to_string()),
+            module: Some(module.to_string()),
+            search_text: format!("search {}", path),
+            ..Default::default()
+        }
+    }
+
+    /// Unit vector along `axis`, tilted towards the next axis by `tilt`
+    fn vector(axis: usize, tilt: f32) -> Vec<f32> {
+        let mut v = vec![0.0f32; EMBEDDING_DIM];
+        v[axis] = 1.0;
+        v[axis + 1] = tilt;
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        v.into_iter().map(|x| x / norm).collect()
+    }
+
+    #[test]
+    fn test_finds_cross_module_near_duplicates() {
+        let mut db = VectorDB::new();
+        db.insert_batch(vec![
+            (vector(0, 0.0), meta("app/code/Acme/Cart/Helper/Price.php", "Acme_Cart")),
+            // A copy in another module
+            (vector(0, 0.05), meta("app/code/Acme/Quote/Helper/Price.php", "Acme_Quote")),
+            // Alike, but in the same module as the original
+            (vector(0, 0.02), meta("app/code/Acme/Cart/Helper/PriceFormat.php", "Acme_Cart")),
+            // Another module, different code
+            (vector(10, 0.0), meta("app/code/Acme/Ship/Model/Rate.php", "Acme_Ship")),
+            (
+                vector(0, 0.01),
+                IndexMetadata {
+                    is_generated: true,
+                    ..meta("generated/code/Acme/Cart/Helper/Price/Interceptor.php", "Acme_Ship")
+                },
+            ),
+        ]);
+
+        let pairs = find(&db, "php", DEFAULT_THRESHOLD);
+        let found: Vec<(&str, &str)> = pairs.iter().map(|p| (p.path_a.as_str(), p.path_b.as_str())).collect();
+        assert_eq!(
+            found,
+            [
+                ("app/code/Acme/Cart/Helper/PriceFormat.php", "app/code/Acme/Quote/Helper/Price.php"),
+                ("app/code/Acme/Cart/Helper/Price.php", "app/code/Acme/Quote/Helper/Price.php"),
+            ]
+        );
+        assert!(pairs[0].similarity > pairs[1].similarity);
+        assert_eq!((pairs[1].module_a.as_str(), pairs[1].module_b.as_str()), ("Acme_Cart", "Acme_Quote"));
+        assert_eq!(pairs[1].class_b.as_deref(), Some("Price"));
+        assert!(find(&db, "xml", DEFAULT_THRESHOLD).is_empty());
+        assert!(find(&db, "", 0.9999).is_empty());
+    }
+
+    #[test]
+    fn test_items_of_one_file_dont_hide_its_duplicate() {
+        // Two files with one item per method; each file's own items are all
+        // nearer to it than any item of the other
+        let mut items = Vec::new();
+        for i in 0..CANDIDATES + 10 {
+            let tilt = i as f32 * 0.001;
+            items.push((vector(20, tilt), meta("app/code/Acme/Cart/Model/Totals.php", "Acme_Cart")));
+            items.push((vector(20, 0.2 + tilt), meta("app/code/Acme/Quote/Model/Totals.php", "Acme_Quote")));
+        }
+        let mut db = VectorDB::new();
+        db.insert_batch(items);
+
+        let pairs = find(&db, "php", DEFAULT_THRESHOLD);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].path_a, "app/code/Acme/Cart/Model/Totals.php");
+        assert_eq!(pairs[0].path_b, "app/code/Acme/Quote/Model/Totals.php");
+    }
+}
@@ -3,22 +3,114 @@
 //! Uses bge-small-en-v1.5 model for 384-dimensional embeddings
 
 use anyhow::{Context, Result};
-use ndarray::Array1;
 use ort::session::{builder::GraphOptimizationLevel, Session};
 use ort::value::Tensor;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 use tokenizers::Tokenizer;
 
 /// Embedding dimension for bge-small-en-v1.5
 pub const EMBEDDING_DIM: usize = 384;
 
+/// Model used when an index names none, downloaded on first use
+pub const DEFAULT_MODEL: &str = "bge-small-en-v1.5";
+
 /// Maximum sequence length
 const MAX_SEQ_LEN: usize = 256;
 
+/// How token embeddings are reduced to one vector per text.
+///
+/// Vectors pooled differently are not comparable, so the strategy is
+/// recorded in the index header and reused for queries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pooling {
+    /// First (`[CLS]`) token, what the bge models were trained with
+    Cls,
+    /// Average of the non-padding tokens
+    #[default]
+    Mean,
+    /// Element-wise maximum over the non-padding tokens
+    Max,
+}
+
+impl Pooling {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Pooling::Cls => "cls",
+            Pooling::Mean => "mean",
+            Pooling::Max => "max",
+        }
+    }
+
+    /// Pool one sequence of `hidden`-wide token vectors (`tokens` is
+    /// row-major, one row per position) over the positions where `mask` is set
+    pub fn pool(&self, tokens: &[f32], mask: &[i64], hidden: usize) -> Vec<f32> {
+        let dim = EMBEDDING_DIM.min(hidden);
+        let mut rows = mask
+            .iter()
+            .enumerate()
+            .filter(|(_, &m)| m > 0)
+            .map(|(j, _)| &tokens[j * hidden..j * hidden + dim]);
+        let mut pooled = vec![0.0f32; EMBEDDING_DIM];
+        match self {
+            Pooling::Cls => {
+                if let Some(first) = rows.next() {
+                    pooled[..dim].copy_from_slice(first);
+                }
+            }
+            Pooling::Mean => {
+                let mut count = 0.0f32;
+                for row in rows {
+                    for (acc, &x) in pooled.iter_mut().zip(row) {
+                        *acc += x;
+                    }
+                    count += 1.0;
+                }
+                if count > 0.0 {
+                    pooled.iter_mut().for_each(|x| *x /= count);
+                }
+            }
+            Pooling::Max => {
+                if let Some(first) = rows.next() {
+                    pooled[..dim].copy_from_slice(first);
+                    for row in rows {
+                        for (acc, &x) in pooled.iter_mut().zip(row) {
+                            *acc = acc.max(x);
+                        }
+                    }
+                }
+            }
+        }
+        pooled
+    }
+}
+
+impl std::fmt::Display for Pooling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Pooling {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cls" => Ok(Pooling::Cls),
+            "mean" => Ok(Pooling::Mean),
+            "max" => Ok(Pooling::Max),
+            _ => Err(format!("unknown pooling {:?} (expected cls, mean or max)", s)),
+        }
+    }
+}
+
 /// Semantic embedder using ONNX runtime
 pub struct Embedder {
     session: Session,
     tokenizer: Tokenizer,
+    pooling: Pooling,
+    model: String,
 }
 
 impl Embedder {
@@ -65,7 +157,8 @@ impl Embedder {
         let tokenizer = Tokenizer::from_file(tokenizer_path)
             .map_err(|e| anyhow::anyhow!("Failed to load tokenizer: {}", e))?;
 
-        Ok(Self { session, tokenizer })
+        let model = model_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(Self { session, tokenizer, pooling: Pooling::default(), model })
     }
 
     /// Download and initialize with default model (bge-small-en-v1.5)
@@ -86,6 +179,24 @@ impl Embedder {
         Self::new(&model_path, &tokenizer_path, max_threads)
     }
 
+    /// Initialize with `model` from `cache_dir`. The default model (also
+    /// for an empty name) is downloaded if missing; any other model must be
+    /// placed there as `<model>.onnx` with its `<model>.tokenizer.json`, and
+    /// produce [`EMBEDDING_DIM`]-dimensional token embeddings.
+    pub fn from_model(cache_dir: &Path, model: &str, max_threads: Option<usize>) -> Result<Self> {
+        if model.is_empty() || model == DEFAULT_MODEL {
+            return Self::from_pretrained_with_threads(cache_dir, max_threads);
+        }
+        let model_path = cache_dir.join(format!("{}.onnx", model));
+        let tokenizer_path = cache_dir.join(format!("{}.tokenizer.json", model));
+        for path in [&model_path, &tokenizer_path] {
+            if !path.exists() {
+                anyhow::bail!("Model {} not found: expected {}", model, path.display());
+            }
+        }
+        Self::new(&model_path, &tokenizer_path, max_threads)
+    }
+
     /// Download the default model
     fn download_model(cache_dir: &Path) -> Result<()> {
         use std::fs;
@@ -126,6 +237,20 @@ impl Embedder {
         Ok(())
     }
 
+    /// Pooling applied to token embeddings (default: mean)
+    pub fn pooling(&self) -> Pooling {
+        self.pooling
+    }
+
+    pub fn set_pooling(&mut self, pooling: Pooling) {
+        self.pooling = pooling;
+    }
+
+    /// Name of the loaded model (its file name without `.onnx`)
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
     /// Generate embedding for a single text
     pub fn embed(&mut self, text: &str) -> Result<Vec<f32>> {
         let embeddings = self.embed_batch(&[text])?;
@@ -192,32 +317,21 @@ impl Embedder {
         // Shape is [batch_size, seq_len, hidden_dim]
         let seq_len = output_shape[1] as usize;
         let hidden_dim = output_shape[2] as usize;
+        if hidden_dim != EMBEDDING_DIM {
+            anyhow::bail!(
+                "Model {} produces {}-dimensional embeddings, the index needs {}",
+                self.model,
+                hidden_dim,
+                EMBEDDING_DIM
+            );
+        }
 
-        // Mean pooling with attention mask
         let mut embeddings = Vec::with_capacity(batch_size);
 
         for i in 0..batch_size {
-            // Compute mean of non-padded tokens
-            let mut sum = Array1::<f32>::zeros(EMBEDDING_DIM);
-            let mut count = 0.0f32;
-
-            for j in 0..seq_len {
-                let mask_idx = i * MAX_SEQ_LEN + j;
-                if mask_idx < attention_mask_copy.len() && attention_mask_copy[mask_idx] > 0 {
-                    for k in 0..EMBEDDING_DIM.min(hidden_dim) {
-                        let idx = i * seq_len * hidden_dim + j * hidden_dim + k;
-                        sum[k] += output_data[idx];
-                    }
-                    count += 1.0;
-                }
-            }
-
-            // Normalize
-            let embedding: Vec<f32> = if count > 0.0 {
-                sum.iter().map(|&x| x / count).collect()
-            } else {
-                vec![0.0; EMBEDDING_DIM]
-            };
+            let tokens = &output_data[i * seq_len * hidden_dim..(i + 1) * seq_len * hidden_dim];
+            let mask = &attention_mask_copy[i * MAX_SEQ_LEN..i * MAX_SEQ_LEN + seq_len.min(MAX_SEQ_LEN)];
+            let embedding = self.pooling.pool(tokens, mask, hidden_dim);
 
             // Check for NaN/Inf before normalization — these corrupt the HNSW graph
             let has_bad_values = embedding.iter().any(|x| x.is_nan() || x.is_infinite());
@@ -253,4 +367,21 @@ mod tests {
     fn test_embedding_dimension() {
         assert_eq!(EMBEDDING_DIM, 384);
     }
+
+    #[test]
+    fn test_pooling() {
+        // Three positions of a 384-wide output, the last one padding
+        let mut tokens = vec![0.0f32; 3 * EMBEDDING_DIM];
+        tokens[0] = 1.0;
+        tokens[EMBEDDING_DIM] = 3.0;
+        tokens[2 * EMBEDDING_DIM] = 100.0;
+        let mask = [1, 1, 0];
+        assert_eq!(Pooling::Cls.pool(&tokens, &mask, EMBEDDING_DIM)[0], 1.0);
+        assert_eq!(Pooling::Mean.pool(&tokens, &mask, EMBEDDING_DIM)[0], 2.0);
+        assert_eq!(Pooling::Max.pool(&tokens, &mask, EMBEDDING_DIM)[0], 3.0);
+        assert_eq!(Pooling::Mean.pool(&tokens, &[0, 0, 0], EMBEDDING_DIM), vec![0.0; EMBEDDING_DIM]);
+
+        assert_eq!("CLS".parse::<Pooling>(), Ok(Pooling::Cls));
+        assert!("sum".parse::<Pooling>().is_err());
+    }
 }
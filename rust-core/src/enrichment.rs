@@ -0,0 +1,244 @@
+//! Enrichment suggestions from search failures (`magector analyze enrichment`)
+//!
+//! Search only sees what an item's `search_text` says about it: a query for
+//! "salable quantity" cannot find an indexer class whose enrichment never
+//! mentions either word. Failed validation cases name the items that should
+//! have matched; zero-result queries in the MCP server log only say what was
+//! asked, so their targets are the items mentioning every other word of the
+//! query. The query words a target's search text and path lack are grouped
+//! by the target's module and Magento type into suggestions a maintainer can
+//! turn into enrichment code.
+
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::idf::TermStats;
+use crate::validation::TestResult;
+use crate::IndexMetadata;
+
+/// Query words that never make an item findable
+const QUERY_NOISE: &[&str] = &[
+    "the", "and", "for", "with", "how", "where", "what", "which", "does", "from", "into", "that", "this", "when",
+    "find", "code", "file", "files", "class", "magento",
+];
+
+/// A pattern or word set matching more items than this is too vague to name
+/// the items that should have matched
+const MAX_TARGETS: usize = 20;
+
+/// Words to add to the search text of a group of items
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    /// Missing words in query order (`salable quantity`)
+    pub terms: String,
+    /// Module of the items (`Magento_InventoryIndexer`)
+    pub module: Option<String>,
+    /// Magento type of the items (`indexer`)
+    pub magento_type: Option<String>,
+    /// Items lacking the words; empty when no item mentions the rest of a
+    /// zero-result query
+    pub files: Vec<String>,
+    /// Failing queries asking for them
+    pub queries: Vec<String>,
+    /// Validation cases among those queries
+    pub test_ids: Vec<String>,
+}
+
+impl Suggestion {
+    /// One-line suggestion ("add 'salable quantity' enrichment to 3 indexer
+    /// files in Magento_InventoryIndexer")
+    pub fn summary(&self) -> String {
+        if self.files.is_empty() {
+            return format!("add '{}' enrichment: no indexed item mentions it", self.terms);
+        }
+        let kind = self.magento_type.as_deref().map(|t| format!("{} ", t)).unwrap_or_default();
+        let plural = if self.files.len() == 1 { "" } else { "s" };
+        let place = self.module.as_deref().map(|m| format!(" in {}", m)).unwrap_or_default();
+        format!("add '{}' enrichment to {} {}file{}{}", self.terms, self.files.len(), kind, plural, place)
+    }
+}
+
+/// Queries the MCP server logged as returning nothing, in log order without
+/// repeats
+pub fn zero_result_queries(log: &str) -> Vec<String> {
+    let re = Regex::new(r#"returned 0 results for "(.*)""#).unwrap();
+    let mut queries: Vec<String> = Vec::new();
+    for caps in log.lines().filter_map(|line| re.captures(line)) {
+        if !queries.iter().any(|q| q == &caps[1]) {
+            queries.push(caps[1].to_string());
+        }
+    }
+    queries
+}
+
+/// Distinct lowercase words of a query worth adding to an item, in order
+fn query_words(query: &str, stats: &TermStats) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in query.split(|c: char| !c.is_alphanumeric()).filter(|w| w.len() >= 3).map(str::to_lowercase) {
+        if !QUERY_NOISE.contains(&word.as_str()) && !stats.is_stop_term(&word) && !words.contains(&word) {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// Lowercase path and search text of an item, what keyword matching sees
+fn haystack(meta: &IndexMetadata) -> String {
+    format!("{} {}", meta.path, meta.search_text).to_lowercase()
+}
+
+/// Whether `meta` matches a validation pattern, as the validator judges it
+fn matches_pattern(meta: &IndexMetadata, pattern: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    meta.path.to_lowercase().contains(&pattern)
+        || meta.class_name.as_ref().is_some_and(|c| c.to_lowercase().contains(&pattern))
+        || meta.magento_type.as_ref().is_some_and(|t| t.to_lowercase().contains(&pattern))
+        || meta.search_text.to_lowercase().contains(&pattern)
+}
+
+/// Suggestions for the failed cases of a validation report and the
+/// zero-result queries of a log, over the items of an index; most asked
+/// for first
+pub fn suggest(items: &[IndexMetadata], failures: &[TestResult], zero_result: &[String]) -> Vec<Suggestion> {
+    let mut stats = TermStats::default();
+    for meta in items {
+        stats.add(&meta.search_text);
+    }
+    let haystacks: Vec<String> = items.iter().map(haystack).collect();
+    let mut groups: BTreeMap<(String, Option<String>, Option<String>), Suggestion> = BTreeMap::new();
+    let mut add = |words: &[String], target: Option<usize>, query: &str, test_id: Option<&str>| {
+        let missing: Vec<&str> = words
+            .iter()
+            .filter(|w| target.is_none_or(|i| !haystacks[i].contains(w.as_str())))
+            .map(String::as_str)
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        let meta = target.map(|i| &items[i]);
+        let key = (
+            missing.join(" "),
+            meta.and_then(|m| m.module.clone()),
+            meta.and_then(|m| m.magento_type.clone()),
+        );
+        let suggestion = groups.entry(key.clone()).or_insert_with(|| Suggestion {
+            terms: key.0,
+            module: key.1,
+            magento_type: key.2,
+            files: Vec::new(),
+            queries: Vec::new(),
+            test_ids: Vec::new(),
+        });
+        if let Some(meta) = meta {
+            if !suggestion.files.contains(&meta.path) {
+                suggestion.files.push(meta.path.clone());
+            }
+        }
+        if !suggestion.queries.iter().any(|q| q == query) {
+            suggestion.queries.push(query.to_string());
+        }
+        if let Some(id) = test_id {
+            if !suggestion.test_ids.iter().any(|t| t == id) {
+                suggestion.test_ids.push(id.to_string());
+            }
+        }
+    };
+
+    for result in failures.iter().filter(|r| !r.passed) {
+        let words = query_words(&result.query, &stats);
+        for pattern in &result.missed_expected {
+            let targets: Vec<usize> = (0..items.len()).filter(|&i| matches_pattern(&items[i], pattern)).collect();
+            if targets.len() > MAX_TARGETS {
+                continue;
+            }
+            for i in targets {
+                add(&words, Some(i), &result.query, Some(&result.test_id));
+            }
+        }
+    }
+
+    for query in zero_result {
+        let words = query_words(query, &stats);
+        let (known, unknown): (Vec<String>, Vec<String>) = words.into_iter().partition(|w| stats.df(w) > 0);
+        if unknown.is_empty() {
+            continue;
+        }
+        let targets: Vec<usize> = if known.is_empty() {
+            Vec::new()
+        } else {
+            (0..items.len()).filter(|&i| known.iter().all(|w| haystacks[i].contains(w.as_str()))).collect()
+        };
+        if targets.is_empty() || targets.len() > MAX_TARGETS {
+            add(&unknown, None, query, None);
+        } else {
+            for i in targets {
+                add(&unknown, Some(i), query, None);
+            }
+        }
+    }
+
+    let mut suggestions: Vec<Suggestion> = groups.into_values().collect();
+    suggestions.sort_by(|a, b| {
+        b.queries.len().cmp(&a.queries.len()).then(b.files.len().cmp(&a.files.len())).then(a.terms.cmp(&b.terms))
+    });
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(path: &str, module: &str, magento_type: &str, search_text: &str) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            magento_type: Some(magento_type.to_string()),
+            module: Some(module.to_string()),
+            search_text: search_text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_suggest_enrichment() {
+        let items = vec![
+            item("InventoryIndexer/Indexer/Stock/StockIndexer.php", "Magento_InventoryIndexer", "indexer", "stock index"),
+            item("InventoryIndexer/Indexer/SourceItem/SourceItemIndexer.php", "Magento_InventoryIndexer", "indexer", "source item"),
+            item("Catalog/Model/Product.php", "Magento_Catalog", "model", "product entity"),
+        ];
+        let failure = TestResult {
+            test_id: "TC042".to_string(),
+            query: "salable quantity stock indexer".to_string(),
+            passed: false,
+            score: 0.0,
+            matched_expected: vec![],
+            missed_expected: vec!["InventoryIndexer/Indexer".to_string()],
+            matched_unexpected: vec![],
+            top_results: vec![],
+            execution_time_ms: 0,
+            details: String::new(),
+        };
+        let log = "[t] [WARN] Serve returned 0 results for \"wishlist sharing\" — trying execFileSync fallback\n\
+                   [t] [INFO] search: query returned 0 results for \"product wishlist\"\n\
+                   [t] [INFO] search: query returned 0 results for \"wishlist sharing\"\n";
+        let zero = zero_result_queries(log);
+        assert_eq!(zero, ["wishlist sharing", "product wishlist"]);
+
+        let suggestions = suggest(&items, &[failure], &zero);
+        assert_eq!(suggestions.len(), 4);
+        // The stock indexer lacks two words, the source item one more
+        let stock = suggestions.iter().find(|s| s.terms == "salable quantity").unwrap();
+        assert_eq!(stock.files, ["InventoryIndexer/Indexer/Stock/StockIndexer.php"]);
+        assert_eq!(stock.test_ids, ["TC042"]);
+        assert_eq!(stock.summary(), "add 'salable quantity' enrichment to 1 indexer file in Magento_InventoryIndexer");
+        assert!(suggestions.iter().any(|s| s.terms == "salable quantity stock"));
+        // "product" finds the catalog model, which should mention wishlists
+        let product = suggestions.iter().find(|s| s.queries == ["product wishlist"]).unwrap();
+        assert_eq!((product.terms.as_str(), product.module.as_deref()), ("wishlist", Some("Magento_Catalog")));
+        // Nothing mentions either word of the other query
+        let sharing = suggestions.iter().find(|s| s.terms == "wishlist sharing").unwrap();
+        assert!(sharing.files.is_empty());
+        assert_eq!(sharing.summary(), "add 'wishlist sharing' enrichment: no indexed item mentions it");
+    }
+}
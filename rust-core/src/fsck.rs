@@ -0,0 +1,303 @@
+//! Index integrity check (`magector fsck`)
+//!
+//! An index can be damaged and still load: a crash between writing the
+//! database and its manifest, a disk error inside a vector, a SONA file cut
+//! short. Loading recovers from most of these quietly by tombstoning items
+//! or resetting learned state, and search simply gets worse. `fsck` checks
+//! the database (checksum, vectors against metadata, vector values and
+//! length, whether the graph finds its own items), the manifest incremental
+//! indexing keeps next to it and the SONA file, and with `repair` fixes
+//! what it can in place. Files whose items had to be dropped, and files the
+//! manifest lost track of, are indexed again by the next `index` run.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::sona::SonaEngine;
+use crate::vectordb::{IntegrityReport, VectorDB};
+use crate::watcher::{FileManifest, FileRecord};
+
+/// Part of the index a finding is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Area {
+    Database,
+    Graph,
+    Manifest,
+    Sona,
+}
+
+impl Area {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Area::Database => "database",
+            Area::Graph => "graph",
+            Area::Manifest => "manifest",
+            Area::Sona => "sona",
+        }
+    }
+}
+
+/// One problem found
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub area: Area,
+    pub problem: String,
+    pub repaired: bool,
+}
+
+/// Outcome of [`check`]
+#[derive(Debug, Clone, Serialize)]
+pub struct FsckReport {
+    pub database: PathBuf,
+    pub integrity: IntegrityReport,
+    pub findings: Vec<Finding>,
+    /// Files repair dropped from the index or marked changed in the
+    /// manifest; the next `index` run indexes them again
+    pub reindex: Vec<String>,
+}
+
+impl FsckReport {
+    /// Findings still to be fixed
+    pub fn unrepaired(&self) -> usize {
+        self.findings.iter().filter(|f| !f.repaired).count()
+    }
+}
+
+fn finding(area: Area, problem: String) -> Finding {
+    Finding { area, problem, repaired: false }
+}
+
+/// Check the index at `db_path` and what is stored next to it, searching
+/// for the vectors of up to `graph_samples` items; with `repair`, fix the
+/// problems found and save
+pub fn check(db_path: &Path, graph_samples: usize, repair: bool) -> Result<FsckReport> {
+    let (mut db, checksum) = VectorDB::load_unverified(db_path)?;
+    let integrity = db.check_integrity(graph_samples);
+    let mut findings = Vec::new();
+    // A checksum mismatch means the stored content itself is damaged; repair
+    // can't restore it, and saving would stamp a fresh checksum over it
+    let content_damaged = checksum.is_err();
+    if let Err(e) = checksum {
+        findings.push(finding(Area::Database, format!("{e} — reindex with `index --force` or restore from a backup")));
+    }
+    let structural = findings.len();
+    let counted = [
+        (integrity.orphan_vectors.len(), "vectors are stored without metadata"),
+        (integrity.missing_vectors.len(), "items have no vector"),
+        (integrity.invalid_vectors.len(), "vectors are invalid (wrong size, NaN/infinite or zero) and can't be found"),
+        (integrity.unnormalized.len(), "vectors are not unit length"),
+    ];
+    for (count, problem) in counted {
+        if count > 0 {
+            findings.push(finding(Area::Database, format!("{} {}", count, problem)));
+        }
+    }
+    if integrity.graph_damaged() {
+        findings.push(finding(
+            Area::Graph,
+            format!(
+                "searching for their own vector missed {} of {} items",
+                integrity.unreachable.len(),
+                integrity.graph_checked
+            ),
+        ));
+    }
+
+    let mut reindex = Vec::new();
+    if repair && !content_damaged && findings.len() > structural {
+        reindex = db.repair(&integrity);
+        db.save_atomic(db_path)?;
+        findings[structural..].iter_mut().for_each(|f| f.repaired = true);
+    }
+
+    let manifest_path = FileManifest::sidecar_path(db_path);
+    if manifest_path.exists() {
+        check_manifest(&db, &manifest_path, repair, &mut findings, &mut reindex)?;
+    }
+    let sona_path = db_path.with_extension("sona");
+    if sona_path.exists() {
+        check_sona(&sona_path, repair, &mut findings)?;
+    }
+
+    reindex.sort();
+    reindex.dedup();
+    Ok(FsckReport { database: db_path.to_path_buf(), integrity, findings, reindex })
+}
+
+/// Compare the manifest's files and vector IDs with the index. Repair drops
+/// entries of files the index lacks and records indexed files it lacks as
+/// changed, so the next `index` run picks both up again.
+fn check_manifest(
+    db: &VectorDB,
+    path: &Path,
+    repair: bool,
+    findings: &mut Vec<Finding>,
+    reindex: &mut Vec<String>,
+) -> Result<()> {
+    let Some(mut manifest) = FileManifest::load(path) else {
+        let mut unreadable = finding(
+            Area::Manifest,
+            "unreadable: the next index run rebuilds it, treating every indexed file as unchanged".to_string(),
+        );
+        if repair {
+            std::fs::remove_file(path)?;
+            unreadable.repaired = true;
+        }
+        findings.push(unreadable);
+        return Ok(());
+    };
+
+    let ids_by_path = db.ids_by_path();
+    // Files whose chunks all failed to embed are tracked without IDs
+    let unindexed: Vec<String> = manifest
+        .files
+        .iter()
+        .filter(|(p, record)| !record.vector_ids.is_empty() && !ids_by_path.contains_key(*p))
+        .map(|(p, _)| p.clone())
+        .collect();
+    let untracked: Vec<String> = ids_by_path.keys().filter(|p| !manifest.files.contains_key(*p)).cloned().collect();
+    let outdated = manifest
+        .files
+        .iter()
+        .filter(|(p, record)| {
+            ids_by_path.get(*p).is_some_and(|ids| {
+                record.vector_ids.iter().collect::<HashSet<_>>() != ids.iter().collect::<HashSet<_>>()
+            })
+        })
+        .count();
+
+    let start = findings.len();
+    if !unindexed.is_empty() {
+        findings.push(finding(Area::Manifest, format!("{} files are tracked but not in the index", unindexed.len())));
+    }
+    if !untracked.is_empty() {
+        findings.push(finding(
+            Area::Manifest,
+            format!("{} indexed files are not tracked, so changes to them go unnoticed", untracked.len()),
+        ));
+    }
+    if outdated > 0 {
+        findings.push(finding(Area::Manifest, format!("{} files have outdated vector IDs", outdated)));
+    }
+    if repair && findings.len() > start {
+        manifest.apply_deleted(&unindexed);
+        for p in &untracked {
+            let record = FileRecord { mtime: SystemTime::UNIX_EPOCH, size: 0, vector_ids: Vec::new(), hash: String::new() };
+            manifest.files.insert(p.clone(), record);
+        }
+        manifest.set_vector_ids(&ids_by_path);
+        manifest.save(path)?;
+        findings[start..].iter_mut().for_each(|f| f.repaired = true);
+        reindex.extend(unindexed.into_iter().chain(untracked));
+    }
+    Ok(())
+}
+
+/// Check that the SONA file reads back whole. Repair keeps the original as
+/// `.sona.bak` and saves what [`SonaEngine::open`] recovers, with damaged
+/// LoRA and EWC state reset.
+fn check_sona(path: &Path, repair: bool, findings: &mut Vec<Finding>) -> Result<()> {
+    let problems = SonaEngine::check_file(path);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    if repair {
+        std::fs::copy(path, path.with_extension("sona.bak"))?;
+        let mut engine = SonaEngine::open(path)?;
+        if !engine.lora.a.iter().chain(&engine.lora.b).all(|x| x.is_finite()) {
+            engine.lora = Default::default();
+            engine.ewc = Default::default();
+        }
+        if !engine.ewc.fisher.iter().chain(&engine.ewc.star_weights).all(|x| x.is_finite()) {
+            engine.ewc = Default::default();
+        }
+        engine.save(path)?;
+    }
+    findings.extend(problems.into_iter().map(|problem| Finding { area: Area::Sona, problem, repaired: repair }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::EMBEDDING_DIM;
+    use crate::IndexMetadata;
+
+    fn item(path: &str, i: usize) -> (Vec<f32>, IndexMetadata) {
+        let mut vector = vec![0.0f32; EMBEDDING_DIM];
+        vector[i] = 1.0;
+        let meta = IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            search_text: String::new(),
+            ..Default::default()
+        };
+        (vector, meta)
+    }
+
+    #[test]
+    fn test_fsck_manifest_and_sona() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("index.db");
+        let mut db = VectorDB::new();
+        db.insert_batch(vec![item("a.php", 0), item("b.php", 1), item("c.php", 2)]);
+        db.save(&db_path).unwrap();
+        let report = check(&db_path, 10, false).unwrap();
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+
+        // The manifest lost b.php, still has a deleted file and an old ID
+        let mut manifest = FileManifest::new();
+        for p in ["a.php", "c.php", "gone.php"] {
+            let record = FileRecord { mtime: SystemTime::now(), size: 1, vector_ids: vec![1], hash: String::new() };
+            manifest.files.insert(p.to_string(), record);
+        }
+        manifest.set_vector_ids(&db.ids_by_path());
+        manifest.files.get_mut("gone.php").unwrap().vector_ids = vec![99];
+        manifest.files.get_mut("c.php").unwrap().vector_ids = vec![42];
+        let manifest_path = FileManifest::sidecar_path(&db_path);
+        manifest.save(&manifest_path).unwrap();
+        let sona_path = db_path.with_extension("sona");
+        std::fs::write(&sona_path, [2u8, 0xff, 0xff]).unwrap();
+
+        let report = check(&db_path, 10, false).unwrap();
+        let areas: Vec<Area> = report.findings.iter().map(|f| f.area).collect();
+        assert_eq!(areas, [Area::Manifest, Area::Manifest, Area::Manifest, Area::Sona]);
+        assert_eq!(report.unrepaired(), 4);
+
+        let report = check(&db_path, 10, true).unwrap();
+        assert_eq!(report.unrepaired(), 0);
+        assert_eq!(report.reindex, ["b.php", "gone.php"]);
+        let manifest = FileManifest::load(&manifest_path).unwrap();
+        assert_eq!(manifest.files["b.php"].mtime, SystemTime::UNIX_EPOCH);
+        assert_eq!(manifest.files["c.php"].vector_ids, db.ids_by_path()["c.php"]);
+        assert!(!manifest.files.contains_key("gone.php"));
+        assert!(dir.path().join("index.sona.bak").exists());
+
+        assert!(check(&db_path, 10, false).unwrap().findings.is_empty());
+    }
+
+    #[test]
+    fn test_fsck_repair_leaves_checksum_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("index.db");
+        let mut db = VectorDB::new();
+        db.insert_batch(vec![item("a.php", 0), item("b.php", 1)]);
+        db.save(&db_path).unwrap();
+
+        // Scale one vector: still decodes, but the checksum fails and the
+        // vector is no longer unit length
+        let mut bytes = std::fs::read(&db_path).unwrap();
+        let at = bytes.windows(4).position(|w| w == 1.0f32.to_le_bytes()).unwrap();
+        bytes[at..at + 4].copy_from_slice(&2.0f32.to_le_bytes());
+        std::fs::write(&db_path, &bytes).unwrap();
+
+        let report = check(&db_path, 10, true).unwrap();
+        assert!(report.findings[0].problem.contains("restore from a backup"), "{:?}", report.findings);
+        assert_eq!(report.unrepaired(), report.findings.len());
+        assert_eq!(std::fs::read(&db_path).unwrap(), bytes, "a damaged index must not be re-saved");
+    }
+}
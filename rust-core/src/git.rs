@@ -0,0 +1,185 @@
+//! File history from git (`index --git-times`, `index --owners`, `semdiff --rev`)
+//!
+//! One `git log` walk from HEAD backwards records, for each indexed file,
+//! the commit time of the newest commit that touched it. The walk stops as
+//! soon as every file has been seen, so a recently active codebase costs a
+//! few hundred commits rather than its whole history. Authorship needs the
+//! whole history instead. Files git does not track (Composer packages,
+//! generated code) get nothing.
+
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Up to this many files are passed to git as pathspecs, which lets it skip
+/// the diff of commits touching none of them; more are matched while reading
+const MAX_PATHSPECS: usize = 200;
+
+/// Read `git log --format=%x00%ct --name-only` output: each commit is a
+/// `\0<unix time>` line followed by the files it changed, newest commit
+/// first. Records the first time seen for each of `wanted` and stops once
+/// all are found.
+fn read_log(log: impl BufRead, wanted: &HashSet<&str>, times: &mut HashMap<String, u64>) -> std::io::Result<()> {
+    let mut commit_time = None;
+    for line in log.lines() {
+        let line = line?;
+        if let Some(time) = line.strip_prefix('\0') {
+            commit_time = time.trim().parse().ok();
+            continue;
+        }
+        let (Some(time), path) = (commit_time, line.trim_end()) else {
+            continue;
+        };
+        if wanted.contains(path) && !times.contains_key(path) {
+            times.insert(path.to_string(), time);
+            if times.len() == wanted.len() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `git log` over `root` printing `format` per commit and the files it
+/// changed, limited to `wanted` when they are few enough
+fn log_command(root: &Path, format: &str, wanted: &HashSet<&str>) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C")
+        .arg(root)
+        .args(["-c", "core.quotepath=off", "log", format, "--name-only", "--no-renames", "--relative"]);
+    if wanted.len() <= MAX_PATHSPECS {
+        cmd.arg("--").args(wanted);
+    }
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    cmd
+}
+
+/// Commit time (Unix seconds) of the last commit touching each of `paths`,
+/// which are relative to `root` with `/` separators. Paths git has never
+/// committed are missing from the map. Fails when `root` is not inside a
+/// git work tree or git is not installed.
+pub fn last_commit_times(root: &Path, paths: &[&str]) -> Result<HashMap<String, u64>> {
+    let wanted: HashSet<&str> = paths.iter().copied().collect();
+    let mut times = HashMap::new();
+    if wanted.is_empty() {
+        return Ok(times);
+    }
+
+    let mut child = log_command(root, "--format=%x00%ct", &wanted).spawn().context("Failed to run git")?;
+    let stdout = child.stdout.take().context("git stdout not captured")?;
+    read_log(BufReader::new(stdout), &wanted, &mut times).context("Failed to read git log")?;
+
+    // Stopped early: the rest of the history is not needed
+    if times.len() == wanted.len() {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Ok(times);
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!("git log failed in {}: {}", root.display(), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(times)
+}
+
+/// Read `git log --format=%x00%an --name-only` output into commit counts
+/// per author for each of `wanted`, authors in order of first appearance
+fn count_authors(
+    log: impl BufRead,
+    wanted: &HashSet<&str>,
+) -> std::io::Result<HashMap<String, Vec<(String, usize)>>> {
+    let mut counts: HashMap<String, Vec<(String, usize)>> = HashMap::new();
+    let mut author: Option<String> = None;
+    for line in log.lines() {
+        let line = line?;
+        if let Some(name) = line.strip_prefix('\0') {
+            author = Some(name.trim().to_string());
+            continue;
+        }
+        let (Some(author), path) = (&author, line.trim_end()) else {
+            continue;
+        };
+        if !wanted.contains(path) {
+            continue;
+        }
+        let authors = counts.entry(path.to_string()).or_default();
+        match authors.iter_mut().find(|(name, _)| name == author) {
+            Some((_, count)) => *count += 1,
+            None => authors.push((author.clone(), 1)),
+        }
+    }
+    Ok(counts)
+}
+
+/// The author with the most commits touching each of `paths`; on a tie,
+/// the one who committed most recently. Paths git has never committed are
+/// missing from the map.
+pub fn dominant_authors(root: &Path, paths: &[&str]) -> Result<HashMap<String, String>> {
+    let wanted: HashSet<&str> = paths.iter().copied().collect();
+    if wanted.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mut child = log_command(root, "--format=%x00%an", &wanted).spawn().context("Failed to run git")?;
+    let stdout = child.stdout.take().context("git stdout not captured")?;
+    let counts = count_authors(BufReader::new(stdout), &wanted).context("Failed to read git log")?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!("git log failed in {}: {}", root.display(), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(counts
+        .into_iter()
+        .filter_map(|(path, authors)| {
+            // max_by_key keeps the last maximum; reversed, that is the newest
+            let (author, _) = authors.into_iter().rev().max_by_key(|(_, count)| *count)?;
+            Some((path, author))
+        })
+        .collect())
+}
+
+/// Content of `file` at revision `rev` (`git show <rev>:<file>`), run in
+/// the file's directory so any work tree it belongs to works
+pub fn show_revision(file: &Path, rev: &str) -> Result<String> {
+    let dir = file.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = file.file_name().context("Not a file path")?.to_string_lossy();
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("{}:./{}", rev, name))
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!("git show {}:{} failed: {}", rev, file.display(), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_log() {
+        let log = "\x00300\n\napp/code/Acme/Cart/Model/Cart.php\nREADME.md\n\
+                   \x00200\n\napp/code/Acme/Cart/etc/di.xml\napp/code/Acme/Cart/Model/Cart.php\n\
+                   \x00100\n\napp/code/Acme/Cart/registration.php\n";
+        let wanted: HashSet<&str> =
+            ["app/code/Acme/Cart/Model/Cart.php", "app/code/Acme/Cart/etc/di.xml", "vendor/x.php"].into();
+        let mut times = HashMap::new();
+        read_log(log.as_bytes(), &wanted, &mut times).unwrap();
+        assert_eq!(times.len(), 2);
+        assert_eq!(times["app/code/Acme/Cart/Model/Cart.php"], 300);
+        assert_eq!(times["app/code/Acme/Cart/etc/di.xml"], 200);
+    }
+
+    #[test]
+    fn test_count_authors() {
+        let log = "\x00Jane\n\na.php\n\x00Bob\n\na.php\nb.php\n\x00Bob\n\na.php\n\x00Jane\n\nb.php\n";
+        let wanted: HashSet<&str> = ["a.php", "b.php"].into();
+        let counts = count_authors(log.as_bytes(), &wanted).unwrap();
+        assert_eq!(counts["a.php"], vec![("Jane".to_string(), 1), ("Bob".to_string(), 2)]);
+        assert_eq!(counts["b.php"], vec![("Bob".to_string(), 1), ("Jane".to_string(), 1)]);
+    }
+}
@@ -0,0 +1,160 @@
+//! GraphQL schema ↔ resolver cross-reference (serve `graphql_field`)
+//!
+//! Magento binds schema fields to PHP through directives in each module's
+//! `etc/schema.graphqls`: `products(...): Products @resolver(class: "...")`.
+//! Modules add fields to shared types with `extend type`, so one type's
+//! fields come from many files. [`parse_schema`] reads the field → resolver
+//! pairs of one file; [`GraphQlXref`] collects them for the whole codebase so
+//! a resolver class can be traced to the fields it serves and back.
+
+use regex::Regex;
+use serde::Serialize;
+
+/// A schema field with a `@resolver` directive
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GraphQlField {
+    /// Owning type (`Query`, `Mutation`, `ProductInterface`, ...)
+    pub type_name: String,
+    pub field: String,
+    /// Resolver class, without the leading backslash
+    pub resolver: String,
+    /// Schema file, relative to the Magento root (empty when parsed standalone)
+    pub file: String,
+}
+
+impl GraphQlField {
+    /// `Type.field`
+    pub fn path(&self) -> String {
+        format!("{}.{}", self.type_name, self.field)
+    }
+}
+
+/// Field → resolver pairs of one `.graphqls` file
+pub fn parse_schema(content: &str) -> Vec<GraphQlField> {
+    let type_re = Regex::new(r"^\s*(?:extend\s+)?(?:type|interface|input)\s+(\w+)").unwrap();
+    let field_re = Regex::new(r"^\s*(\w+)\s*[(:]").unwrap();
+    let resolver_re = Regex::new(r#"@resolver\s*\(\s*class\s*:\s*"([^"]+)""#).unwrap();
+
+    let mut fields = Vec::new();
+    let mut type_name: Option<String> = None;
+    let mut field: Option<String> = None;
+    // Parenthesis depth: argument lists may span lines and look like fields
+    let mut depth = 0i32;
+    for line in content.lines() {
+        let code = line.split('#').next().unwrap_or("");
+        if depth == 0 {
+            if let Some(caps) = type_re.captures(code) {
+                type_name = Some(caps[1].to_string());
+                field = None;
+            } else if code.trim_start().starts_with('}') {
+                type_name = None;
+                field = None;
+            } else if let Some(caps) = field_re.captures(code) {
+                field = Some(caps[1].to_string());
+            }
+        }
+        depth += code.matches('(').count() as i32 - code.matches(')').count() as i32;
+        depth = depth.max(0);
+
+        if let (Some(caps), Some(type_name), Some(field)) = (resolver_re.captures(code), &type_name, &field) {
+            fields.push(GraphQlField {
+                type_name: type_name.clone(),
+                field: field.clone(),
+                resolver: caps[1].replace("\\\\", "\\").trim_start_matches('\\').to_string(),
+                file: String::new(),
+            });
+        }
+    }
+    fields
+}
+
+/// Resolver bindings from every schema file
+#[derive(Debug, Clone, Default)]
+pub struct GraphQlXref {
+    fields: Vec<GraphQlField>,
+}
+
+impl GraphQlXref {
+    /// Add the bindings of one schema file (relative path `file`)
+    pub fn add_schema(&mut self, content: &str, file: &str) {
+        self.fields.extend(parse_schema(content).into_iter().map(|mut f| {
+            f.file = file.to_string();
+            f
+        }));
+    }
+
+    /// Fields resolved by `class`
+    pub fn fields_for_class(&self, class: &str) -> Vec<&GraphQlField> {
+        let class = class.trim_start_matches('\\');
+        self.fields.iter().filter(|f| f.resolver == class).collect()
+    }
+
+    /// Bindings of `Type.field`, or of `field` on any type (case-insensitive)
+    pub fn lookup(&self, name: &str) -> Vec<&GraphQlField> {
+        let name = name.to_lowercase();
+        self.fields
+            .iter()
+            .filter(|f| match name.split_once('.') {
+                Some((type_name, field)) => f.type_name.to_lowercase() == type_name && f.field.to_lowercase() == field,
+                None => f.field.to_lowercase() == name,
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+type Query {
+    products (
+        search: String @doc(description: "Performs a full-text search"),
+        filter: ProductAttributeFilterInput,
+        pageSize: Int = 20
+    ): Products @resolver(class: "Magento\\CatalogGraphQl\\Model\\Resolver\\Products") @doc(description: "Search products")
+    category (id: Int): CategoryTree @resolver(class: "\\Magento\\CatalogGraphQl\\Model\\Resolver\\CategoryTree") @deprecated
+}
+
+interface ProductInterface @typeResolver(class: "Magento\\CatalogGraphQl\\Model\\ProductInterfaceTypeResolverComposite") {
+    id: Int @deprecated(reason: "Use uid")
+    # price: Price @resolver(class: "Old\\Resolver")
+    price_range: PriceRange! @resolver(class: "Magento\\CatalogGraphQl\\Model\\Resolver\\Product\\PriceRange")
+}
+
+extend type Mutation {
+    createEmptyCart: String @resolver(class: "\\Magento\\QuoteGraphQl\\Model\\Resolver\\CreateEmptyCart")
+}
+"#;
+
+    #[test]
+    fn test_parse_schema() {
+        let fields = parse_schema(SCHEMA);
+        let paths: Vec<String> = fields.iter().map(GraphQlField::path).collect();
+        assert_eq!(
+            paths,
+            vec!["Query.products", "Query.category", "ProductInterface.price_range", "Mutation.createEmptyCart"]
+        );
+        assert_eq!(fields[0].resolver, "Magento\\CatalogGraphQl\\Model\\Resolver\\Products");
+        assert_eq!(fields[1].resolver, "Magento\\CatalogGraphQl\\Model\\Resolver\\CategoryTree");
+    }
+
+    #[test]
+    fn test_xref_lookup() {
+        let mut xref = GraphQlXref::default();
+        xref.add_schema(SCHEMA, "Magento/CatalogGraphQl/etc/schema.graphqls");
+        let products = xref.lookup("Query.products");
+        assert_eq!(products.len(), 1);
+        assert_eq!(products[0].file, "Magento/CatalogGraphQl/etc/schema.graphqls");
+        assert_eq!(xref.lookup("query.PRODUCTS").len(), 1);
+        assert_eq!(xref.lookup("createEmptyCart").len(), 1);
+        assert!(xref.lookup("Mutation.products").is_empty());
+        let served = xref.fields_for_class("\\Magento\\CatalogGraphQl\\Model\\Resolver\\Products");
+        assert_eq!(served.len(), 1);
+        assert_eq!(served[0].path(), "Query.products");
+    }
+}
@@ -0,0 +1,153 @@
+//! Term document frequencies of an index (IDF keyword weighting)
+//!
+//! Counts how many live items mention each word of their `search_text`, so
+//! `hybrid_search` can scale a query term's keyword bonus by how rare the
+//! term is in this index: "Magento" or "Model" appear almost everywhere and
+//! say little about a match, "reindex" or "wishlist" say a lot. Words in
+//! more than [`STOP_TERM_SHARE`] of the items are stop terms, left out of
+//! keyword scoring and SONA term learning altogether. Derived from the
+//! metadata when an index is loaded and kept up to date as items are
+//! inserted and removed; only the stop term list is saved (in the header).
+
+use std::collections::{HashMap, HashSet};
+
+/// Items whose share of the index is at most `1 / FULL_WEIGHT_RATIO` get the
+/// full keyword bonus for a term
+const FULL_WEIGHT_RATIO: f32 = 100.0;
+
+/// Share of the items above which a word is a stop term
+pub const STOP_TERM_SHARE: f32 = 0.30;
+
+/// Indexes with fewer items have no stop terms: a handful of files says
+/// nothing about which words are noise
+const MIN_STOP_TERM_DOCS: usize = 100;
+
+/// Document frequencies over the words of live items' search text
+#[derive(Debug, Clone, Default)]
+pub struct TermStats {
+    docs: usize,
+    df: HashMap<String, u32>,
+}
+
+/// Distinct lowercase words of `text` (letters and digits, at least 3 long)
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.len() >= 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+impl TermStats {
+    /// Count an item with search text `text`
+    pub fn add(&mut self, text: &str) {
+        self.docs += 1;
+        for word in words(text) {
+            *self.df.entry(word).or_insert(0) += 1;
+        }
+    }
+
+    /// Stop counting an item added with `text`
+    pub fn remove(&mut self, text: &str) {
+        self.docs = self.docs.saturating_sub(1);
+        for word in words(text) {
+            if let Some(count) = self.df.get_mut(&word) {
+                *count -= 1;
+                if *count == 0 {
+                    self.df.remove(&word);
+                }
+            }
+        }
+    }
+
+    /// Items counted
+    pub fn docs(&self) -> usize {
+        self.docs
+    }
+
+    /// Items mentioning `term` (a query term; surrounding punctuation is
+    /// ignored)
+    pub fn df(&self, term: &str) -> u32 {
+        let term = term.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        self.df.get(&term).copied().unwrap_or(0)
+    }
+
+    /// Whether `term` is in more than [`STOP_TERM_SHARE`] of the items
+    pub fn is_stop_term(&self, term: &str) -> bool {
+        self.docs >= MIN_STOP_TERM_DOCS && self.df(term) as f32 > self.docs as f32 * STOP_TERM_SHARE
+    }
+
+    /// Every stop term, sorted
+    pub fn stop_terms(&self) -> Vec<String> {
+        if self.docs < MIN_STOP_TERM_DOCS {
+            return Vec::new();
+        }
+        let mut terms: Vec<String> = self
+            .df
+            .iter()
+            .filter(|(_, &df)| df as f32 > self.docs as f32 * STOP_TERM_SHARE)
+            .map(|(term, _)| term.clone())
+            .collect();
+        terms.sort();
+        terms
+    }
+
+    /// Weight (0–1) of a keyword match on `term`: `ln(N / df)` over the same
+    /// for a term in `1 / FULL_WEIGHT_RATIO` of the N items (or in a single
+    /// item, for small indexes). Terms no item mentions as a word, such as
+    /// `di.xml`, and indexes of fewer than two items weigh 1.
+    pub fn weight(&self, term: &str) -> f32 {
+        let df = self.df(term);
+        if df == 0 || self.docs < 2 {
+            return 1.0;
+        }
+        let docs = self.docs as f32;
+        let full = docs.min(FULL_WEIGHT_RATIO).ln();
+        ((docs / df as f32).ln() / full).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_term_weights() {
+        let mut stats = TermStats::default();
+        for i in 0..200 {
+            let text = if i == 0 {
+                "Magento Model wishlist".to_string()
+            } else if i % 2 == 0 {
+                format!("Magento\\Catalog\\Model\\Product{}", i)
+            } else {
+                format!("Magento Catalog Block{}", i)
+            };
+            stats.add(&text);
+        }
+        assert_eq!(stats.docs(), 200);
+        assert_eq!(stats.df("Magento"), 200);
+        assert_eq!(stats.df("model"), 100);
+        assert_eq!(stats.weight("magento"), 0.0);
+        assert_eq!(stats.weight("wishlist"), 1.0);
+        let model = stats.weight("model");
+        assert!(model > 0.1 && model < 0.2, "{model}");
+        // Unknown words and punctuation-only matches keep the full bonus
+        assert_eq!(stats.weight("di.xml"), 1.0);
+        assert_eq!(stats.weight("\"wishlist\""), 1.0);
+        // "catalog" is in 199 of 200 items, "model" in half, "block" in none
+        // as a word of its own
+        assert_eq!(stats.stop_terms(), ["catalog", "magento", "model"]);
+        assert!(stats.is_stop_term("Model") && !stats.is_stop_term("wishlist"));
+
+        stats.remove("Magento Model wishlist");
+        assert_eq!(stats.df("wishlist"), 0);
+        assert_eq!(stats.docs(), 199);
+
+        // In a two-item index a word of one item weighs fully
+        let mut small = TermStats::default();
+        small.add("checkout cart");
+        small.add("cart");
+        assert_eq!(small.weight("checkout"), 1.0);
+        assert_eq!(small.weight("cart"), 0.0);
+        assert!(small.stop_terms().is_empty() && !small.is_stop_term("cart"));
+    }
+}
@@ -0,0 +1,192 @@
+//! Ignore rules (`.magectorignore`, `.gitignore`)
+//!
+//! Besides the built-in exclusions, file discovery skips paths matched by a
+//! `.magectorignore` file in the project root and, with `index --gitignore`,
+//! by the root `.gitignore`. Both use gitignore syntax: `#` comments, `*`,
+//! `?`, `[...]` and `**` globs, `!` to re-include, a trailing `/` for
+//! directories only, and patterns containing a `/` anchored at the root
+//! while the others match a name at any depth. The last matching rule wins;
+//! `.magectorignore` rules come after `.gitignore`'s, so `!vendor/` takes
+//! back what `.gitignore` leaves out.
+//!
+//! `.gitignore` is opt-in because Magento projects ignore `vendor/`, which
+//! holds the core code Magector indexes.
+
+use glob::{MatchOptions, Pattern};
+use std::fs;
+use std::path::Path;
+
+/// Project-specific ignore file
+pub const IGNORE_FILE: &str = ".magectorignore";
+
+/// `*` and `?` stop at `/`, like in `.gitignore`
+const MATCH: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// One pattern line
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: Pattern,
+    /// `!pattern`: re-include what earlier rules excluded
+    negated: bool,
+    /// `pattern/`: directories only
+    dir_only: bool,
+    /// Contains a `/`: matched against the path from the root rather than
+    /// the name
+    anchored: bool,
+}
+
+/// Rules of the ignore files of a project
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    /// Rules of `root`'s `.magectorignore`, after those of its `.gitignore`
+    /// when `gitignore` is set. Missing files add nothing.
+    pub fn load(root: &Path, gitignore: bool) -> Self {
+        let mut rules = Self::default();
+        let files: &[&str] = if gitignore { &[".gitignore", IGNORE_FILE] } else { &[IGNORE_FILE] };
+        for file in files {
+            if let Ok(content) = fs::read_to_string(root.join(file)) {
+                let before = rules.len();
+                rules.extend(&content);
+                tracing::info!("Loaded {} patterns from {}", rules.len() - before, file);
+            }
+        }
+        rules
+    }
+
+    /// Rules of one file's `content`
+    pub fn parse(content: &str) -> Self {
+        let mut rules = Self::default();
+        rules.extend(content);
+        rules
+    }
+
+    fn extend(&mut self, content: &str) {
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let line = line.replace('\\', "/");
+            let dir_only = line.ends_with('/');
+            let body = line.trim_end_matches('/');
+            let anchored = body.contains('/');
+            let body = body.trim_start_matches('/');
+            if body.is_empty() {
+                continue;
+            }
+            match Pattern::new(body) {
+                Ok(pattern) => self.rules.push(Rule { pattern, negated, dir_only, anchored }),
+                Err(e) => tracing::warn!("Skipping ignore pattern {:?}: {}", line, e),
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether the rules exclude `relative` itself (`/`-separated, from the
+    /// root). Directory walks skip excluded directories, so their contents
+    /// aren't checked.
+    pub fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        let mut excluded = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let subject = if rule.anchored { relative } else { name };
+            if rule.pattern.matches_with(subject, MATCH) {
+                excluded = !rule.negated;
+            }
+        }
+        excluded
+    }
+
+    /// Whether the file `relative` is excluded, itself or through one of its
+    /// directories
+    pub fn excludes(&self, relative: &str) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let dirs = relative.match_indices('/').map(|(i, _)| &relative[..i]);
+        dirs.into_iter().any(|dir| self.matches(dir, true)) || self.matches(relative, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gitignore_syntax() {
+        let rules = IgnoreRules::parse(
+            "# client code\n\
+             app/code/Client\n\
+             /generated-assets/\n\
+             *.min.js\n\
+             docs/**/draft-?.md\n\
+             build/\n\
+             lib/*\n\
+             !lib/keep\n\
+             \n",
+        );
+        assert_eq!(rules.len(), 7);
+
+        // Anchored patterns match from the root, name patterns at any depth
+        assert!(rules.matches("app/code/Client", true));
+        assert!(!rules.matches("vendor/app/code/Client", true));
+        assert!(rules.excludes("app/code/Client/Model/Order.php"));
+        assert!(rules.matches("generated-assets", true));
+        assert!(rules.matches("app/design/frontend/theme.min.js", false));
+        assert!(!rules.matches("app/design/frontend/theme.js", false));
+        assert!(rules.matches("docs/a/b/draft-1.md", false));
+        assert!(rules.matches("docs/draft-2.md", false), "** matches no directory too");
+        assert!(!rules.matches("docs/a/draft-10.md", false));
+
+        // Trailing slash: directories only
+        assert!(rules.matches("app/code/Acme/build", true));
+        assert!(!rules.matches("app/code/Acme/build", false));
+
+        // * stops at /, and ! re-includes
+        assert!(rules.matches("lib/internal", true));
+        assert!(!rules.matches("lib/keep", true));
+        assert!(!rules.excludes("lib/keep/file.php"));
+        assert!(rules.excludes("lib/internal/file.php"));
+
+        assert!(!IgnoreRules::default().excludes("app/code/Client/Model/Order.php"));
+    }
+
+    #[test]
+    fn test_magectorignore_overrides_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "/vendor/*\n/var/\n").unwrap();
+        fs::write(dir.path().join(IGNORE_FILE), "!/vendor/magento\napp/code/Client/\n").unwrap();
+
+        let without = IgnoreRules::load(dir.path(), false);
+        assert_eq!(without.len(), 2);
+        assert!(!without.excludes("vendor/acme/module/Model/A.php"));
+        assert!(without.excludes("app/code/Client/Model/A.php"));
+
+        let with = IgnoreRules::load(dir.path(), true);
+        assert!(with.excludes("vendor/acme/module/Model/A.php"));
+        assert!(!with.excludes("vendor/magento/module-catalog/Model/Product.php"));
+        assert!(with.excludes("var/cache/x.php"));
+    }
+}
@@ -0,0 +1,176 @@
+//! API interface implementations
+//!
+//! A query like "product repository interface" finds the service contract
+//! (`Magento\Catalog\Api\ProductRepositoryInterface`), but the code worth
+//! reading is the class di.xml prefers for it. Once all files are indexed,
+//! the preference of each API interface is resolved, through virtual types,
+//! to an indexed class, including those of a core index, and the file of
+//! that class is stored in [`IndexMetadata::implementation`]. Search returns
+//! the implementation with the interface ([`group`]).
+//!
+//! A global (`etc/di.xml`) preference wins over area ones, and among those
+//! the last declaration wins, as in Magento's merged config.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::di::DiGraph;
+use crate::overrides::class_key;
+use crate::vectordb::{IndexMetadata, SearchResult};
+
+/// Class key of each interface → class key of the class preferred for it
+pub(crate) fn preferred(di: &DiGraph) -> HashMap<String, String> {
+    let mut global = HashMap::new();
+    let mut areas = HashMap::new();
+    for preference in di.preferences() {
+        let map = if preference.area == "global" { &mut global } else { &mut areas };
+        map.insert(preference.for_type.to_lowercase(), preference.type_name.as_str());
+    }
+    for (interface, class) in areas {
+        global.entry(interface).or_insert(class);
+    }
+    global.into_iter().map(|(interface, class)| (interface, di.resolve_virtual_type(class).to_lowercase())).collect()
+}
+
+/// Path of each API interface → file of the class di.xml prefers for it.
+/// Interfaces without a preference, whose class isn't indexed, or outside
+/// `only` when given, are left out. The first file of a class wins, so list
+/// overlay items first.
+pub fn implementations<'a>(
+    items: impl IntoIterator<Item = &'a IndexMetadata>,
+    di: &DiGraph,
+    only: Option<&HashSet<String>>,
+) -> HashMap<String, String> {
+    let mut classes: HashMap<String, &str> = HashMap::new();
+    let mut interfaces: Vec<(&str, String)> = Vec::new();
+    for meta in items {
+        let Some(key) = class_key(meta) else { continue };
+        if meta.is_api_interface {
+            if only.is_some_and(|paths| !paths.contains(&meta.path)) {
+                continue;
+            }
+            interfaces.push((meta.path.as_str(), key));
+        } else {
+            classes.entry(key).or_insert(meta.path.as_str());
+        }
+    }
+
+    let preferred = preferred(di);
+    interfaces
+        .into_iter()
+        .filter_map(|(path, key)| {
+            let class = classes.get(preferred.get(&key)?)?;
+            Some((path.to_string(), class.to_string()))
+        })
+        .collect()
+}
+
+/// Attach to each API interface result its implementation. A hit for the
+/// implementation moves under the interface; otherwise `lookup` gives the
+/// item of the implementation's file, grouped with the interface's score.
+/// Only the best-ranked interface of an implementation takes it.
+pub fn group(results: &mut Vec<SearchResult>, lookup: impl Fn(&str) -> Option<SearchResult>) {
+    let mut i = 0;
+    while i < results.len() {
+        let Some(path) = results[i].metadata.implementation.clone() else {
+            i += 1;
+            continue;
+        };
+        let claimed = results[..i].iter().any(|r| r.implementation.as_ref().is_some_and(|m| m.metadata.path == path));
+        if !claimed {
+            let score = results[i].score;
+            let hit = results.iter().position(|r| r.metadata.path == path && r.implementation.is_none());
+            let implementation = match hit {
+                Some(at) => {
+                    let hit = results.remove(at);
+                    if at < i {
+                        i -= 1;
+                    }
+                    Some(hit)
+                }
+                None => lookup(&path).map(|r| SearchResult { score, ..r }),
+            };
+            results[i].implementation = implementation.map(Box::new);
+        }
+        i += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magento::XmlMetadata;
+
+    fn item(path: &str, class: &str, is_api_interface: bool) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            class_name: Some(class.rsplit('\\').next().unwrap().to_string()),
+            class_type: Some(if is_api_interface { "interface" } else { "class" }.to_string()),
+            namespace: class.rsplit_once('\\').map(|(ns, _)| ns.to_string()),
+            is_resolver: false,
+            is_api_interface,
+            search_text: String::new(),
+            ..Default::default()
+        }
+    }
+
+    fn di(preferences: &[(&str, &str, &str)]) -> DiGraph {
+        let mut di = DiGraph::default();
+        for (file, for_type, type_name) in preferences {
+            let meta = XmlMetadata {
+                preferences: vec![(for_type.to_string(), type_name.to_string())],
+                ..Default::default()
+            };
+            di.add(&meta, file);
+        }
+        di
+    }
+
+    #[test]
+    fn test_implementations_follow_preferences() {
+        let items = vec![
+            item("Api/ProductRepositoryInterface.php", "Magento\\Catalog\\Api\\ProductRepositoryInterface", true),
+            item("Model/ProductRepository.php", "Magento\\Catalog\\Model\\ProductRepository", false),
+            item("Custom/ProductRepository.php", "Vendor\\Catalog\\ProductRepository", false),
+            item("Api/StockInterface.php", "Magento\\Stock\\Api\\StockInterface", true),
+            item("Api/UnwiredInterface.php", "Magento\\Stock\\Api\\UnwiredInterface", true),
+        ];
+        let di = di(&[
+            ("Catalog/etc/adminhtml/di.xml", "Magento\\Catalog\\Api\\ProductRepositoryInterface", "Vendor\\Catalog\\ProductRepository"),
+            ("Catalog/etc/di.xml", "\\Magento\\Catalog\\Api\\ProductRepositoryInterface", "Magento\\Catalog\\Model\\ProductRepository"),
+            ("Stock/etc/di.xml", "Magento\\Stock\\Api\\StockInterface", "Magento\\Stock\\Model\\Missing"),
+        ]);
+
+        let resolved = implementations(&items, &di, None);
+        assert_eq!(resolved["Api/ProductRepositoryInterface.php"], "Model/ProductRepository.php", "global wins");
+        assert_eq!(resolved.len(), 1);
+        let only = HashSet::from(["Api/StockInterface.php".to_string()]);
+        assert!(implementations(&items, &di, Some(&only)).is_empty());
+    }
+
+    #[test]
+    fn test_group_moves_or_looks_up_implementations() {
+        let interface = IndexMetadata {
+            implementation: Some("Model/ProductRepository.php".into()),
+            ..item("Api/ProductRepositoryInterface.php", "Magento\\Catalog\\Api\\ProductRepositoryInterface", true)
+        };
+        let class = item("Model/ProductRepository.php", "Magento\\Catalog\\Model\\ProductRepository", false);
+        let other = item("Model/Other.php", "Magento\\Catalog\\Model\\Other", false);
+
+        let mut results = vec![
+            SearchResult::new(1, 0.9, interface.clone()),
+            SearchResult::new(2, 0.8, other.clone()),
+            SearchResult::new(3, 0.7, class.clone()),
+        ];
+        group(&mut results, |_| None);
+        let paths: Vec<&str> = results.iter().map(|r| r.metadata.path.as_str()).collect();
+        assert_eq!(paths, ["Api/ProductRepositoryInterface.php", "Model/Other.php"]);
+        assert_eq!(results[0].implementation.as_ref().unwrap().score, 0.7);
+
+        let mut results = vec![SearchResult::new(2, 0.8, other), SearchResult::new(1, 0.6, interface)];
+        group(&mut results, |path| (path == class.path).then(|| SearchResult::new(3, 0.0, class.clone())));
+        let implementation = results[1].implementation.as_ref().unwrap();
+        assert_eq!((implementation.id, implementation.score), (3, 0.6));
+        assert!(results[0].implementation.is_none());
+    }
+}
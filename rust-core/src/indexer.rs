@@ -6,18 +6,30 @@ use rayon::prelude::*;
 use std::cell::RefCell;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
 
+use crate::config::IndexConfig;
 use crate::ast::{PhpAstAnalyzer, JsAstAnalyzer, PhpAstMetadata, JsAstMetadata};
-use crate::embedder::Embedder;
+use crate::embedder::{Embedder, Pooling, EMBEDDING_DIM};
+use crate::migrate::{EmbedText, Embeddings, TextCache};
+use crate::snapshot;
+use crate::ignore::IgnoreRules;
+use crate::source::{read_source, SourceText};
+use crate::preflight::{check_disk_space, format_bytes, IndexEstimate, SpaceCheck};
 use crate::magento::{
-    detect_area, detect_file_type, extract_module_info, split_camel_case,
-    XmlAnalyzer, SetupAnalyzer, SqlReferenceAnalyzer,
+    detect_area, detect_file_type, extract_module_info, is_generated_code, is_mftf_path, module_names_for_path, split_camel_case,
+    ConfigXref, XmlAnalyzer, SetupAnalyzer, SqlReferenceAnalyzer,
+};
+use crate::vectordb::{
+    normalize_separators, relative_path, stable_id, Collection, ExtraRoot, FileMetrics, IndexHeader, IndexMetadata, ItemVector, RootMap,
+    SourceSpan, VectorDB,
 };
-use crate::vectordb::{IndexMetadata, VectorDB};
 
-use std::collections::HashSet;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 /// File patterns to index
 pub(crate) const INCLUDE_EXTENSIONS: &[&str] = &["php", "xml", "phtml", "js", "graphqls"];
@@ -46,9 +58,55 @@ pub(crate) const EXCLUDE_PATHS: &[&str] = &[
     "dev/tools",
 ];
 
+/// Files never indexed because they hold deployment secrets (database
+/// credentials, the crypt key)
+pub(crate) const EXCLUDE_FILES: &[&str] = &["app/etc/env.php"];
+
+/// `--include-tests` verdict for the directory at `rel`: `Some(false)` walks
+/// it even when excluded by name, `Some(true)` skips it, `None` applies the
+/// usual rules. Module `Test/` directories are walked only to reach `Test/Mftf`.
+fn test_dir_verdict(rel: &str, name: &str) -> Option<bool> {
+    if is_mftf_path(&format!("{}/", rel)) {
+        return Some(false);
+    }
+    if name == "Test" {
+        return Some(false);
+    }
+    let parent = rel.rsplit_once('/').map_or("", |(parent, _)| parent);
+    (parent == "Test" || parent.ends_with("/Test")).then_some(true)
+}
+
+/// Files under a walked `Test/` directory other than MFTF XML (unit and
+/// integration tests stay excluded with `--include-tests`)
+pub(crate) fn skips_test_file(rel: &str) -> bool {
+    (rel.starts_with("Test/") || rel.contains("/Test/")) && !(is_mftf_path(rel) && rel.ends_with(".xml"))
+}
+
+/// Whether a file with extension `ext` at `rel` is indexed; Markdown docs
+/// only with `include_docs`, patch files wherever [`crate::patches`] looks
+pub(crate) fn is_indexable(rel: &str, ext: &str, include_docs: bool) -> bool {
+    INCLUDE_EXTENSIONS.contains(&ext)
+        || (include_docs && crate::docs::is_doc_path(rel))
+        || crate::patches::is_patch_path(rel)
+}
+
 /// Maximum file size to index (100KB)
 pub(crate) const MAX_FILE_SIZE: u64 = 100_000;
 
+/// Code and enrichment of a file's embedded text are cut to this many bytes
+const MAX_EMBED_TEXT: usize = 8000;
+
+/// Cut `text` to at most `max` bytes, at a char boundary
+fn truncate_at_char_boundary(text: &mut String, max: usize) {
+    if text.len() > max {
+        let mut end = max;
+        while end > 0 && !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+}
+
 /// Indexing statistics
 #[derive(Debug, Default)]
 pub struct IndexStats {
@@ -57,6 +115,8 @@ pub struct IndexStats {
     pub files_skipped: usize,
     pub vectors_created: usize,
     pub errors: usize,
+    /// Files decoded from a non-UTF-8 encoding
+    pub lossy_decodes: usize,
     pub php_files: usize,
     pub js_files: usize,
     pub xml_files: usize,
@@ -65,20 +125,297 @@ pub struct IndexStats {
 
 /// Intermediate result from parsing (before embedding)
 pub(crate) struct ParsedFile {
-    embed_text: String,
+    /// Context placed before the code, inside the model's token window
+    /// (description, served fields, URLs, ...)
+    lead: String,
+    code: String,
+    /// Enrichment terms after the code: class and method names, type
+    /// signals, path parts and the search text
+    enrichment: String,
     metadata: IndexMetadata,
+    /// Source was not UTF-8 and was decoded lossily
+    lossy: bool,
+}
+
+impl ParsedFile {
+    /// Text of the item's vector: lead, code and enrichment
+    fn embed_text(&self) -> String {
+        format!("{}{}{}", self.lead, self.code, self.enrichment)
+    }
+
+    /// Text of the metadata vector of an `index --meta-vectors` index: the
+    /// lead and enrichment without the code
+    fn meta_text(&self) -> String {
+        format!("{}{}", self.lead, self.enrichment.trim_start())
+    }
+
+    /// Texts the item's vectors are embedded from; with `meta_vectors` the
+    /// vector gets only the code and the metadata vector the rest
+    fn embed_texts(&self, meta_vectors: bool) -> EmbedText {
+        if meta_vectors {
+            EmbedText { text: self.code.clone(), meta: Some(self.meta_text()) }
+        } else {
+            EmbedText { text: self.embed_text(), meta: None }
+        }
+    }
+}
+
+/// Why a path was left out of indexing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SkipReason {
+    /// Built-in exclusion (node_modules, Test, pub/static, ...)
+    ExcludedDir,
+    /// Matched a `.magectorignore` (or, with `--gitignore`, `.gitignore`)
+    /// pattern
+    Ignored,
+    /// Larger than `MAX_FILE_SIZE`
+    TooLarge,
+    /// Outside the `--only-modules` subset
+    OutsideModules,
+    /// Binary content (NUL bytes), not source text
+    Binary,
+    /// Could not be read (permissions, vanished mid-run, ...)
+    Unreadable,
+    /// Read but failed to parse
+    ParseError,
+    /// Zero-length file
+    Empty,
+    /// Holds deployment secrets (`EXCLUDE_FILES`)
+    Secrets,
+}
+
+impl SkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ExcludedDir => "excluded dir",
+            Self::Ignored => "ignore rules",
+            Self::TooLarge => "too large",
+            Self::OutsideModules => "outside --only-modules",
+            Self::Binary => "binary",
+            Self::Unreadable => "unreadable",
+            Self::ParseError => "parse failure",
+            Self::Empty => "empty",
+            Self::Secrets => "secrets",
+        }
+    }
+
+    /// Classify a [`Indexer::parse_file`] error
+    fn from_parse_error(err: &anyhow::Error) -> Self {
+        match err.root_cause().downcast_ref::<std::io::Error>() {
+            Some(io) if io.kind() == std::io::ErrorKind::InvalidData => Self::Binary,
+            Some(_) => Self::Unreadable,
+            None => Self::ParseError,
+        }
+    }
+}
+
+/// Sidecar report of files that were skipped or failed, next to the DB
+pub const ISSUES_FILE: &str = "index_issues.json";
+
+/// Per-file entries kept in the report; counts are never capped
+const MAX_RECORDED_ISSUES: usize = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexIssue {
+    pub path: String,
+    pub reason: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Files skipped or failed during a run, by reason
+#[derive(Debug, Default)]
+pub struct IndexIssues {
+    counts: BTreeMap<SkipReason, usize>,
+    issues: Vec<IndexIssue>,
+}
+
+impl IndexIssues {
+    pub fn record(&mut self, path: String, reason: SkipReason, detail: Option<String>) {
+        *self.counts.entry(reason).or_default() += 1;
+        if self.issues.len() < MAX_RECORDED_ISSUES {
+            self.issues.push(IndexIssue { path, reason: reason.as_str(), detail });
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    pub fn counts(&self) -> &BTreeMap<SkipReason, usize> {
+        &self.counts
+    }
+
+    pub fn sidecar_path(db_path: &Path) -> PathBuf {
+        db_path.with_file_name(ISSUES_FILE)
+    }
+
+    /// Write the report. Issues are sorted by path so reruns diff cleanly.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut issues = self.issues.clone();
+        issues.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.reason.cmp(b.reason)));
+        let by_reason: BTreeMap<&str, usize> =
+            self.counts.iter().map(|(r, n)| (r.as_str(), *n)).collect();
+        let report = serde_json::json!({
+            "total": self.total(),
+            "by_reason": by_reason,
+            "truncated": self.total() > issues.len(),
+            "issues": issues,
+        });
+        fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+}
+
+/// Result of [`Indexer::plan`]: what a run would index
+pub struct IndexPlan {
+    /// Files in indexing order
+    pub files: Vec<PathBuf>,
+    /// Paths left out, with the reason. Excluded directories are listed
+    /// once, not per file.
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+    pub estimate: IndexEstimate,
+}
+
+impl IndexPlan {
+    /// File counts per module (canonical `Vendor_Module`), largest first.
+    /// Files outside any module are counted under `(no module)`.
+    pub fn module_counts(&self, root: &Path) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for f in &self.files {
+            let module = module_names_for_path(&relative_path(root, f))
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| "(no module)".to_string());
+            *counts.entry(module).or_default() += 1;
+        }
+        Self::sorted_counts(counts)
+    }
+
+    /// File counts per detected Magento type (controller, plugin, ...), largest first
+    pub fn type_counts(&self, root: &Path) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for f in &self.files {
+            let file_type = detect_file_type(&relative_path(root, f));
+            *counts.entry(file_type.as_str().to_string()).or_default() += 1;
+        }
+        Self::sorted_counts(counts)
+    }
+
+    fn sorted_counts(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+        let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+}
+
+/// Which files a walk of the source roots covers. Recorded in the index
+/// header, so the file watcher walks the tree the way it was indexed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WalkOptions {
+    /// Follow symlinked directories (`--follow-symlinks`)
+    pub follow_symlinks: bool,
+    /// MFTF test XML (`--include-tests`)
+    pub include_tests: bool,
+    /// Markdown docs outside `vendor/` (`--include-docs`)
+    pub include_docs: bool,
+}
+
+/// Directory walk over a source tree. With `follow_symlinks`, symlinked
+/// directories and files are followed and every directory or file is
+/// yielded once by (device, inode): symlink cycles and a second link to an
+/// already-walked module are skipped.
+pub(crate) struct SourceWalk {
+    inner: walkdir::IntoIter,
+    visited: Option<HashSet<(u64, u64)>>,
+}
+
+impl SourceWalk {
+    pub(crate) fn new(root: &Path, follow_symlinks: bool) -> Self {
+        let walk = WalkDir::new(root).follow_links(follow_symlinks);
+        // Name order keeps the path that wins for a duplicate stable across runs
+        let walk = if follow_symlinks { walk.sort_by_file_name() } else { walk };
+        Self {
+            inner: walk.into_iter(),
+            visited: follow_symlinks.then(HashSet::new),
+        }
+    }
+
+    /// Don't descend into the directory yielded last
+    pub(crate) fn skip_current_dir(&mut self) {
+        self.inner.skip_current_dir();
+    }
+
+    #[cfg(unix)]
+    fn file_id(entry: &walkdir::DirEntry) -> Option<(u64, u64)> {
+        use std::os::unix::fs::MetadataExt;
+        entry.metadata().ok().map(|m| (m.dev(), m.ino()))
+    }
+
+    #[cfg(not(unix))]
+    fn file_id(_entry: &walkdir::DirEntry) -> Option<(u64, u64)> {
+        None
+    }
+}
+
+impl Iterator for SourceWalk {
+    type Item = walkdir::Result<walkdir::DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.inner.next()? {
+                Ok(entry) => entry,
+                Err(e) if e.loop_ancestor().is_some() => {
+                    tracing::debug!("Skipping symlink cycle: {}", e);
+                    continue;
+                }
+                Err(e) => return Some(Err(e)),
+            };
+            if let Some(visited) = self.visited.as_mut() {
+                if let Some(id) = Self::file_id(&entry) {
+                    if !visited.insert(id) {
+                        tracing::debug!("Already indexed via another path: {:?}", entry.path());
+                        if entry.file_type().is_dir() {
+                            self.inner.skip_current_dir();
+                        }
+                        continue;
+                    }
+                }
+            }
+            return Some(Ok(entry));
+        }
+    }
 }
 
 /// Default embedding batch size — larger batches amortize ONNX overhead.
 /// Override via MAGECTOR_BATCH_SIZE env var or --batch-size CLI flag.
 const DEFAULT_EMBED_BATCH_SIZE: usize = 256;
 
-/// Save index to disk every N batches during PHASE 2 (crash recovery)
+/// Save index to disk every N embedding batches (crash recovery)
 const SAVE_INTERVAL_BATCHES: usize = 50;
 
 /// Log progress every N batches
 const LOG_INTERVAL_BATCHES: usize = 10;
 
+/// Depth of the parse → embed queue, in embedding batches worth of files.
+/// Lets parse workers run ahead of the embedder without buffering the
+/// whole codebase in memory.
+const PIPELINE_QUEUE_BATCHES: usize = 4;
+
+/// Indexing order as (label, path prefixes); unmatched paths go last.
+/// Custom code is what users query first, so the DB is checkpointed at every
+/// tier boundary and an interrupted run keeps the most valuable content.
+const PRIORITY_TIERS: &[(&str, &[&str])] = &[
+    ("app/code", &["app/code/"]),
+    ("app/design", &["app/design/"]),
+    ("vendor/magento", &["vendor/magento/", "lib/"]),
+    ("vendor", &["vendor/"]),
+];
+
+/// Tiers covering project code: the checkpoint after them is announced as a
+/// usable partial index (the MCP server serves it during first builds).
+const PRIORITY_CHECKPOINT_TIERS: usize = 2;
+
 // Thread-local AST analyzers (avoids mutex contention in parallel parsing)
 thread_local! {
     static TL_PHP_ANALYZER: RefCell<Option<PhpAstAnalyzer>> = RefCell::new(PhpAstAnalyzer::new().ok());
@@ -86,7 +423,8 @@ thread_local! {
 }
 
 /// Whether AST analyzers are available (checked once at init)
-struct AstAvailability {
+#[derive(Clone, Copy)]
+pub(crate) struct AstAvailability {
     php: bool,
     js: bool,
 }
@@ -96,15 +434,163 @@ pub struct Indexer {
     embedder: Embedder,
     vectordb: VectorDB,
     xml_analyzer: XmlAnalyzer,
+    /// Masks secrets in file content before it is parsed and embedded
+    redactor: crate::redact::Redactor,
+    /// menu.xml and widget.xml links, for enriching the controllers, blocks
+    /// and templates they point at. Built from the discovered files, or on
+    /// the first incremental run.
+    config_xref: Option<ConfigXref>,
+    /// Files `config_xref` was built from; `index_files` keeps it current
+    /// with the changed files instead of walking the roots again
+    config_sources: BTreeSet<PathBuf>,
+    /// Files removed or re-indexed since the last link passes, and the
+    /// classes the removed ones declared ([`Self::link_scope`])
+    unlinked_paths: HashSet<String>,
+    unlinked_classes: HashSet<String>,
     magento_root: PathBuf,
     ast_available: AstAvailability,
     pub sona: Option<crate::sona::SonaEngine>,
     pub db_path: Option<PathBuf>,
     descriptions_db: Option<PathBuf>,
-    /// Custom ignore patterns loaded from .magectorignore
-    ignore_patterns: Vec<String>,
+    /// Rules of .magectorignore (and .gitignore with `gitignore`)
+    ignore: IgnoreRules,
+    /// Apply the root .gitignore too
+    gitignore: bool,
     /// Embedding batch size (configurable)
     batch_size: usize,
+    /// Reproducible output: items embedded and inserted in path order
+    deterministic: bool,
+    /// `--only-modules` globs (raw, as recorded in the index header)
+    only_modules: Vec<String>,
+    /// Compiled form of `only_modules`; empty means every module
+    module_filter: Vec<glob::Pattern>,
+    /// Source trees indexed besides the Magento root (`--extra-root`)
+    extra_roots: Vec<ExtraRoot>,
+    /// Ignore rules of each extra root, in `extra_roots` order
+    extra_ignore: Vec<IgnoreRules>,
+    /// Follow symlinked directories and files during discovery
+    follow_symlinks: bool,
+    /// Index MFTF XML under module `Test/Mftf` directories
+    include_tests: bool,
+    /// Index Markdown docs into the `docs` collection
+    include_docs: bool,
+    /// Vector prefix the HNSW graph is built from; 0 = full vectors
+    coarse_dim: usize,
+    /// Search files first, then their chunks
+    two_stage: bool,
+    /// Store a one-line structural summary with each item
+    summaries: bool,
+    /// Store when each file was last committed to git
+    git_times: bool,
+    /// Store each file's owners from CODEOWNERS or git authorship
+    owners: bool,
+    /// Embed code and metadata text into separate vectors
+    meta_vectors: bool,
+    /// Split PHP files into one item per method
+    method_chunks: bool,
+    /// Compare content hashes before re-embedding files whose mtime or
+    /// size changed
+    incremental: bool,
+    /// Pre-built read-only index layered under this one
+    core: Option<CoreIndex>,
+    /// `boost` rules from magector.toml, applied to search scores
+    boost_rules: Vec<crate::config::BoostRule>,
+    /// Module → enabled, from `app/etc/config.php`
+    module_states: BTreeMap<String, bool>,
+    /// Files modified by the project's patches
+    patches: crate::patches::PatchRegistry,
+    /// Score part weights used unless a search passes its own
+    score_weights: crate::vectordb::ScoreWeights,
+    /// Default latency budget of a serve search (`--deadline-ms`)
+    search_budget: Option<Duration>,
+    /// Running average of a full (reranked) search, embedding excluded
+    search_cost: Duration,
+    /// Applied to every query before it is embedded and matched
+    rewriter: Option<Box<dyn crate::query::QueryRewriter>>,
+    /// Texts embedded since the last save, merged into the text cache next
+    /// to the index on save (see [`crate::migrate`])
+    texts: TextCache,
+    /// Files re-indexed by [`Indexer::index_files`] since the indexer was
+    /// created
+    reindexed_files: u64,
+    /// SONA signals learned since the indexer was created
+    sona_signals: u64,
+    /// Checked between the stages of a search (see [`Indexer::with_cancel_token`])
+    cancel: CancelToken,
+}
+
+/// Retrieval instruction prepended to queries (not documents) for bge-small
+const QUERY_PREFIX: &str = "Represent this sentence: ";
+
+/// Embedding batch of a [`Indexer::search_batch`]; cancellation is checked
+/// between batches
+const CANCEL_CHECK_BATCH: usize = 16;
+
+/// Set from another thread to stop a search at its next check (serve
+/// `cancel`). Clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        Ok(())
+    }
+}
+
+/// Error of a search stopped through its [`CancelToken`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("search cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// The indexer with a [`CancelToken`] its searches check; the token is
+/// dropped with the scope
+pub struct CancelScope<'a>(&'a mut Indexer);
+
+impl std::ops::Deref for CancelScope<'_> {
+    type Target = Indexer;
+
+    fn deref(&self) -> &Indexer {
+        self.0
+    }
+}
+
+impl std::ops::DerefMut for CancelScope<'_> {
+    fn deref_mut(&mut self) -> &mut Indexer {
+        self.0
+    }
+}
+
+impl Drop for CancelScope<'_> {
+    fn drop(&mut self) {
+        self.0.cancel = CancelToken::default();
+    }
+}
+
+/// A read-only "core" index (typically vendor/magento, built once and
+/// shared as an artifact). The local index becomes an overlay: files the
+/// core covers are never indexed locally, and searches merge both.
+struct CoreIndex {
+    path: PathBuf,
+    db: VectorDB,
+    paths: HashSet<String>,
 }
 
 impl Indexer {
@@ -121,9 +607,6 @@ impl Indexer {
         max_threads: Option<usize>,
         batch_size: Option<usize>,
     ) -> Result<Self> {
-        tracing::info!("Initializing embedder...");
-        let embedder = Embedder::from_pretrained_with_threads(model_cache_dir, max_threads)?;
-
         let batch_size = batch_size
             .or_else(|| std::env::var("MAGECTOR_BATCH_SIZE").ok().and_then(|v| v.parse().ok()))
             .unwrap_or(DEFAULT_EMBED_BATCH_SIZE);
@@ -131,6 +614,10 @@ impl Indexer {
         tracing::info!("Opening vector database...");
         let vectordb = VectorDB::open(db_path)?;
 
+        // Queries must be embedded with the model of the stored vectors
+        tracing::info!("Initializing embedder...");
+        let mut embedder = Embedder::from_model(model_cache_dir, &vectordb.header().model, max_threads)?;
+
         // Check AST analyzer availability (thread-local instances created per-thread)
         let php_ok = PhpAstAnalyzer::new().is_ok();
         let js_ok = JsAstAnalyzer::new().is_ok();
@@ -146,23 +633,86 @@ impl Indexer {
             crate::sona::SonaEngine::open(&sona_path).ok()
         };
 
-        // Load .magectorignore patterns
-        let ignore_patterns = Self::load_ignore_file(magento_root);
+        let gitignore = vectordb.header().gitignore;
+        let ignore = IgnoreRules::load(magento_root, gitignore);
+
+        // A partial index keeps its module subset for watcher updates
+        let only_modules = vectordb.header().only_modules.clone();
+        let module_filter = Self::compile_module_filter(&only_modules)?;
+        let extra_roots = vectordb.header().extra_roots.clone();
+        let extra_ignore = Self::load_extra_ignore(&extra_roots, gitignore);
+        let follow_symlinks = vectordb.header().follow_symlinks;
+        let include_tests = vectordb.header().include_tests;
+        let include_docs = vectordb.header().include_docs;
+        let coarse_dim = vectordb.header().coarse_dim;
+        let two_stage = vectordb.header().two_stage;
+        let summaries = vectordb.header().summaries;
+        let git_times = vectordb.header().git_times;
+        let owners = vectordb.header().owners;
+        let meta_vectors = vectordb.header().meta_vectors;
+        let method_chunks = vectordb.header().method_chunks;
+        // Queries must be pooled like the stored vectors
+        embedder.set_pooling(vectordb.header().pooling);
 
         tracing::info!("Embedding batch size: {}", batch_size);
 
-        Ok(Self {
+        let mut indexer = Self {
             embedder,
             vectordb,
             xml_analyzer: XmlAnalyzer::new(),
+            redactor: Default::default(),
+            config_xref: None,
+            config_sources: BTreeSet::new(),
+            unlinked_paths: HashSet::new(),
+            unlinked_classes: HashSet::new(),
             magento_root: magento_root.to_path_buf(),
             ast_available: AstAvailability { php: php_ok, js: js_ok },
             sona: sona.or_else(|| Some(crate::sona::SonaEngine::new())),
             db_path: Some(db_path.to_path_buf()),
             descriptions_db: None,
-            ignore_patterns,
+            ignore,
+            gitignore,
             batch_size,
-        })
+            deterministic: false,
+            only_modules,
+            module_filter,
+            extra_roots,
+            extra_ignore,
+            follow_symlinks,
+            include_tests,
+            include_docs,
+            coarse_dim,
+            two_stage,
+            summaries,
+            git_times,
+            owners,
+            meta_vectors,
+            method_chunks,
+            incremental: false,
+            core: None,
+            boost_rules: Vec::new(),
+            module_states: BTreeMap::new(),
+            patches: Default::default(),
+            score_weights: Default::default(),
+            search_budget: None,
+            search_cost: Duration::ZERO,
+            rewriter: None,
+            texts: TextCache::default(),
+            reindexed_files: 0,
+            sona_signals: 0,
+            cancel: CancelToken::default(),
+        };
+        indexer.map_roots(&[]);
+        indexer.refresh_stop_terms();
+
+        // An overlay reopens the core it was built against
+        let core_index = indexer.vectordb.header().core_index.clone();
+        if !core_index.is_empty() {
+            if let Err(e) = indexer.set_core_index(Path::new(&core_index)) {
+                tracing::warn!("Core index unavailable, searching the overlay only: {:#}", e);
+            }
+        }
+        Ok(indexer)
     }
 
     /// Set the descriptions database path for embedding enrichment.
@@ -170,6 +720,522 @@ impl Indexer {
         self.descriptions_db = Some(path);
     }
 
+    /// Enable deterministic indexing for reproducible CI artifacts.
+    ///
+    /// Parsed items are sorted by path before embedding and insertion; with
+    /// stable IDs (path + chunk index) identical sources then always produce
+    /// identical DB bytes. Implies a full rebuild (resume state would depend
+    /// on history).
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Restrict indexing to modules matching any of the given globs
+    /// (`Magento_Checkout`, `Vendor_*`). An empty list indexes everything.
+    ///
+    /// The subset is recorded in the index header; indexing with a different
+    /// subset than the one on disk triggers a full rebuild.
+    pub fn set_only_modules(&mut self, modules: &[String]) -> Result<()> {
+        self.module_filter = Self::compile_module_filter(modules)?;
+        self.only_modules = modules.to_vec();
+        Ok(())
+    }
+
+    /// Index source trees outside the Magento root into the same database
+    /// (private extensions in their own checkouts). Their files are stored
+    /// by absolute path and tagged with the root's name. Recorded in the
+    /// index header so resume runs and the file watcher walk them too.
+    pub fn set_extra_roots(&mut self, roots: Vec<ExtraRoot>) -> Result<()> {
+        let main = ExtraRoot::new(None, &self.magento_root);
+        let mut extra_roots: Vec<ExtraRoot> = Vec::new();
+        for root in roots {
+            if !Path::new(&root.path).is_dir() {
+                anyhow::bail!("Extra root not found: {}", root.path);
+            }
+            // Files under two roots would be indexed twice
+            if let Some(other) = std::iter::once(&main)
+                .chain(&extra_roots)
+                .find(|r| r.contains(&root.path) || root.contains(&r.path))
+            {
+                anyhow::bail!("Extra root {} overlaps {}", root.path, other.path);
+            }
+            if extra_roots.iter().any(|r| r.name == root.name) {
+                anyhow::bail!("Duplicate extra root name: {}", root.name);
+            }
+            extra_roots.push(root);
+        }
+        self.extra_ignore = Self::load_extra_ignore(&extra_roots, self.gitignore);
+        self.extra_roots = extra_roots;
+        Ok(())
+    }
+
+    fn load_extra_ignore(roots: &[ExtraRoot], gitignore: bool) -> Vec<IgnoreRules> {
+        roots.iter().map(|r| IgnoreRules::load(Path::new(&r.path), gitignore)).collect()
+    }
+
+    /// Whether the ignore rules of the root holding `path` exclude it,
+    /// matched relative to that root. `path` is absolute or, as stored,
+    /// relative to the Magento root.
+    fn ignored(&self, path: &Path) -> bool {
+        let absolute = normalize_separators(&path.to_string_lossy());
+        match self.extra_roots.iter().position(|r| r.contains(&absolute)) {
+            Some(i) => self.extra_ignore[i].excludes(&relative_path(Path::new(&self.extra_roots[i].path), path)),
+            None => self.ignore.excludes(&relative_path(&self.magento_root, path)),
+        }
+    }
+
+    /// Directories of the extra roots, for walks besides the Magento root
+    pub fn extra_root_paths(&self) -> Vec<PathBuf> {
+        self.extra_roots.iter().map(|r| PathBuf::from(&r.path)).collect()
+    }
+
+    /// Rebase stored paths onto this machine's Magento root. Paths are kept
+    /// root-relative; absolute ones under the root the index was built from
+    /// (or under a `--map-root` source) are rewritten. Applied on open with
+    /// no extra maps. Returns the number of paths changed.
+    pub fn map_roots(&mut self, maps: &[RootMap]) -> usize {
+        let mut maps = maps.to_vec();
+        let built_at = self.vectordb.header().root.clone();
+        let here = Self::absolute_root(&self.magento_root);
+        if !built_at.is_empty() && !self.magento_root.as_os_str().is_empty() && built_at != here {
+            tracing::info!("Index built at {}, opened at {}", built_at, here);
+            maps.push(RootMap { from: built_at, to: here });
+        }
+        let changed = self.vectordb.remap_paths(&maps, &self.magento_root);
+        if changed > 0 {
+            tracing::info!("Remapped {} stored paths", changed);
+        }
+        changed
+    }
+
+    /// Layer this index over a pre-built read-only core index. Files the
+    /// core covers are skipped by indexing and the watcher; searches query
+    /// both and prefer the overlay when a path appears in each.
+    pub fn set_core_index(&mut self, path: &Path) -> Result<()> {
+        if !path.exists() {
+            anyhow::bail!("Core index not found: {}", path.display());
+        }
+        let mut db = VectorDB::open(path)?;
+        if db.header().pooling != self.embedder.pooling() {
+            anyhow::bail!(
+                "Core index {} uses {} pooling, this index uses {}",
+                path.display(),
+                db.header().pooling,
+                self.embedder.pooling()
+            );
+        }
+        let built_at = db.header().root.clone();
+        let here = Self::absolute_root(&self.magento_root);
+        let maps: Vec<RootMap> = if built_at.is_empty() || built_at == here {
+            Vec::new()
+        } else {
+            vec![RootMap { from: built_at, to: here }]
+        };
+        db.remap_paths(&maps, &self.magento_root);
+        let paths: HashSet<String> = db.metadata_iter().map(|(_, meta)| meta.path.clone()).collect();
+        tracing::info!("Core index {:?}: {} vectors, {} files", path, db.len(), paths.len());
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        self.core = Some(CoreIndex { path, db, paths });
+        Ok(())
+    }
+
+    /// Vectors in the core index, if one is layered under this index
+    pub fn core_vectors(&self) -> Option<usize> {
+        self.core.as_ref().map(|c| c.db.len())
+    }
+
+    /// Raw vectors of the items of `paths` and of the items `ids`. A path
+    /// the overlay lacks is looked up in the core index.
+    pub fn item_vectors(&self, paths: &[&str], ids: &[usize]) -> Vec<ItemVector> {
+        let mut items = self.vectordb.vectors_by_path(paths);
+        if let Some(ref core) = self.core {
+            let found: HashSet<&str> = items.iter().map(|item| item.path.as_str()).collect();
+            let missing: Vec<&str> = paths.iter().copied().filter(|p| !found.contains(p)).collect();
+            items.extend(core.db.vectors_by_path(&missing));
+        }
+        items.extend(ids.iter().filter_map(|&id| {
+            self.vectordb.item_vector(id).or_else(|| self.core.as_ref()?.db.item_vector(id))
+        }));
+        items
+    }
+
+    /// Whether a file is indexed locally: inside the module subset, not
+    /// ignored and not covered by the core index
+    pub fn indexes_locally(&self, path: &Path) -> bool {
+        self.in_module_subset(path)
+            && !self.ignored(path)
+            && self
+                .core
+                .as_ref()
+                .is_none_or(|c| !c.paths.contains(&relative_path(&self.magento_root, path)))
+    }
+
+    fn build_header(&self, building: bool) -> IndexHeader {
+        IndexHeader {
+            only_modules: self.only_modules.clone(),
+            building,
+            follow_symlinks: self.follow_symlinks,
+            include_tests: self.include_tests,
+            include_docs: self.include_docs,
+            gitignore: self.gitignore,
+            root: Self::absolute_root(&self.magento_root),
+            extra_roots: self.extra_roots.clone(),
+            pooling: self.embedder.pooling(),
+            model: self.embedder.model().to_string(),
+            coarse_dim: self.coarse_dim,
+            two_stage: self.two_stage,
+            summaries: self.summaries,
+            git_times: self.git_times,
+            owners: self.owners,
+            meta_vectors: self.meta_vectors,
+            method_chunks: self.method_chunks,
+            // Kept by resume runs; a fresh DB starts untuned
+            ef_search: self.vectordb.header().ef_search,
+            ef_tuned_items: self.vectordb.header().ef_tuned_items,
+            core_index: self
+                .core
+                .as_ref()
+                .map(|c| normalize_separators(&c.path.to_string_lossy()))
+                .unwrap_or_default(),
+            // The checksum is filled in on save, tags only on snapshots
+            ..Default::default()
+        }
+    }
+
+    /// Absolute form of a root with `/` separators, as stored in the header
+    fn absolute_root(root: &Path) -> String {
+        let absolute = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+        let absolute = normalize_separators(&absolute.to_string_lossy());
+        match absolute.trim_end_matches('/') {
+            "" => absolute,
+            trimmed => trimmed.to_string(),
+        }
+    }
+
+    /// Follow symlinked module directories (modman / dev setups). Each
+    /// directory and file is walked once by inode, so symlink cycles and
+    /// a second link to the same module are skipped. Recorded in the index
+    /// header so the file watcher walks the same tree.
+    pub fn set_follow_symlinks(&mut self, follow: bool) {
+        self.follow_symlinks = follow;
+    }
+
+    /// Index MFTF tests, action groups and data fixtures (`Test/Mftf/**/*.xml`)
+    /// as `mftf_test` items. Unit and integration tests stay excluded.
+    /// Recorded in the index header so the file watcher walks the same tree.
+    pub fn set_include_tests(&mut self, include: bool) {
+        self.include_tests = include;
+    }
+
+    /// Index Markdown files outside `vendor/` (dev docs, ADRs, module
+    /// READMEs) into the `docs` collection, one item per section. Recorded
+    /// in the index header so the file watcher picks up doc edits.
+    pub fn set_include_docs(&mut self, include: bool) {
+        self.include_docs = include;
+    }
+
+    /// The walk set by [`Self::set_follow_symlinks`],
+    /// [`Self::set_include_tests`] and [`Self::set_include_docs`]
+    pub fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            follow_symlinks: self.follow_symlinks,
+            include_tests: self.include_tests,
+            include_docs: self.include_docs,
+        }
+    }
+
+    /// Skip what the root `.gitignore` ignores, besides `.magectorignore`.
+    /// Recorded in the index header, so resumed runs and the watcher keep it.
+    pub fn set_gitignore(&mut self, enabled: bool) {
+        self.gitignore = enabled;
+        self.ignore = IgnoreRules::load(&self.magento_root, enabled);
+        self.extra_ignore = Self::load_extra_ignore(&self.extra_roots, enabled);
+    }
+
+    /// Pooling strategy for embeddings (`cls`, `mean`, `max`).
+    ///
+    /// Recorded in the index header and reused for queries; indexing with a
+    /// different strategy than the one on disk triggers a full rebuild. A
+    /// core index must use the same strategy.
+    pub fn set_pooling(&mut self, pooling: Pooling) -> Result<()> {
+        if let Some(ref core) = self.core {
+            if core.db.header().pooling != pooling {
+                anyhow::bail!(
+                    "Core index {} uses {} pooling, cannot index with {}",
+                    core.path.display(),
+                    core.db.header().pooling,
+                    pooling
+                );
+            }
+        }
+        self.embedder.set_pooling(pooling);
+        Ok(())
+    }
+
+    /// Build the search graph from the first `dim` dimensions of each
+    /// vector and rerank candidates with the full vectors (0 disables).
+    ///
+    /// Faster coarse search for Matryoshka-trained models. Changing it only
+    /// rebuilds the graph; nothing is re-embedded.
+    pub fn set_coarse_dim(&mut self, dim: usize) -> Result<()> {
+        if dim >= EMBEDDING_DIM {
+            anyhow::bail!("Coarse dimension must be below the embedding dimension ({})", EMBEDDING_DIM);
+        }
+        self.coarse_dim = dim;
+        Ok(())
+    }
+
+    /// Two-stage retrieval: a file-level graph of chunk centroids picks
+    /// candidate files, then their chunks are scored. Worth it once files
+    /// are split into many chunks; derived on load, nothing is re-embedded.
+    pub fn set_two_stage(&mut self, enabled: bool) {
+        self.two_stage = enabled;
+    }
+
+    /// Store a one-line structural summary of each file in its metadata
+    /// ([`crate::summary::summarize`]), returned with search results. Makes
+    /// the index larger; changing it rewrites the metadata, nothing is
+    /// re-embedded.
+    pub fn set_summaries(&mut self, enabled: bool) {
+        self.summaries = enabled;
+    }
+
+    /// Store when each file was last committed ([`crate::git`]), for the
+    /// `recency` score weight. Read from git at the end of every index run
+    /// and for files the watcher re-indexes.
+    pub fn set_git_times(&mut self, enabled: bool) {
+        self.git_times = enabled;
+    }
+
+    /// Embed each item's code and its metadata text (path, class, methods,
+    /// enrichment terms) into separate vectors, blended at query time by
+    /// [`ScoreWeights::metadata`](crate::vectordb::ScoreWeights::metadata).
+    /// Doubles the embedding work; changing it rebuilds the index.
+    pub fn set_meta_vectors(&mut self, enabled: bool) {
+        self.meta_vectors = enabled;
+    }
+
+    /// Split each PHP file into one item per method, with its name and line
+    /// range, plus one for the code outside methods, so a query about one
+    /// method of a long class finds that method. Changing it rebuilds the
+    /// index.
+    pub fn set_method_chunks(&mut self, enabled: bool) {
+        self.method_chunks = enabled;
+    }
+
+    /// Re-embed only files whose content changed: a file whose mtime or
+    /// size differs from the manifest but whose content hash doesn't (a
+    /// branch switch, deploy copy or `touch`) keeps its vectors. Applies
+    /// when an existing index is updated; full builds embed everything.
+    pub fn set_incremental(&mut self, enabled: bool) {
+        self.incremental = enabled;
+    }
+
+    /// Store each file's owners ([`crate::owners`]) for `search --owner`.
+    /// Resolved at the end of every index run and for files the watcher
+    /// re-indexes.
+    pub fn set_owners(&mut self, enabled: bool) {
+        self.owners = enabled;
+    }
+
+    /// Static ranking rules applied to every search (magector.toml `boost`)
+    pub fn set_boost_rules(&mut self, rules: Vec<crate::config::BoostRule>) {
+        self.boost_rules = rules;
+    }
+
+    /// Enabled flag per module (`Vendor_Module`), used to mark results
+    /// from disabled modules and weight them with
+    /// [`ScoreWeights::disabled`](crate::vectordb::ScoreWeights::disabled)
+    pub fn set_module_states(&mut self, modules: BTreeMap<String, bool>) {
+        self.module_states = modules;
+    }
+
+    /// Read module states from the project's `app/etc/config.php`. A file
+    /// that cannot be parsed is logged and leaves every module unmarked.
+    pub fn load_module_states(&mut self, magento_root: &Path) {
+        match crate::project::ProjectInfo::scan(magento_root) {
+            Ok(info) => self.set_module_states(info.modules),
+            Err(e) => tracing::warn!("Module states unavailable, results are not marked: {:#}", e),
+        }
+    }
+
+    /// Patched files, used to set
+    /// [`SearchResult::patched_by`](crate::vectordb::SearchResult::patched_by)
+    pub fn set_patches(&mut self, patches: crate::patches::PatchRegistry) {
+        self.patches = patches;
+    }
+
+    /// Link patched files from the project's composer.json and patch
+    /// directories. Unreadable declarations are logged and leave results
+    /// unmarked.
+    pub fn load_patches(&mut self, magento_root: &Path) {
+        match crate::patches::PatchRegistry::scan(magento_root) {
+            Ok(patches) => self.set_patches(patches),
+            Err(e) => tracing::warn!("Patches unavailable, results are not marked: {:#}", e),
+        }
+    }
+
+    /// Default score part weights (magector.toml `[weights]`)
+    pub fn set_score_weights(&mut self, weights: crate::vectordb::ScoreWeights) {
+        self.score_weights = weights;
+    }
+
+    pub fn score_weights(&self) -> crate::vectordb::ScoreWeights {
+        self.score_weights
+    }
+
+    /// Default latency budget for [`Self::search_within`] callers
+    pub fn set_search_budget(&mut self, budget: Option<Duration>) {
+        self.search_budget = budget;
+    }
+
+    pub fn search_budget(&self) -> Option<Duration> {
+        self.search_budget
+    }
+
+    /// Searches made through the returned scope stop with [`Cancelled`] at
+    /// their next check once `token` is cancelled: before and after
+    /// embedding, and between reranking the overlay and the core index
+    pub fn with_cancel_token(&mut self, token: CancelToken) -> CancelScope<'_> {
+        self.cancel = token;
+        CancelScope(self)
+    }
+
+    /// Files re-indexed incrementally since the indexer was created
+    pub fn reindexed_files(&self) -> u64 {
+        self.reindexed_files
+    }
+
+    /// SONA signals learned since the indexer was created
+    pub fn sona_signals(&self) -> u64 {
+        self.sona_signals
+    }
+
+    /// Query preprocessing for every search (`--rewriter-cmd`)
+    pub fn set_query_rewriter(&mut self, rewriter: Option<Box<dyn crate::query::QueryRewriter>>) {
+        self.rewriter = rewriter;
+    }
+
+    /// The query a search runs with: the rewriter's output, or `query`
+    /// itself without a rewriter or when it fails, with the project's
+    /// aliases expanded
+    pub fn rewrite_query(&self, query: &str) -> String {
+        let query = match self.rewriter.as_ref().map(|r| r.rewrite(query)) {
+            Some(Ok(rewritten)) => {
+                tracing::debug!("Query rewritten: {:?} -> {:?}", query, rewritten);
+                rewritten
+            }
+            Some(Err(e)) => {
+                tracing::warn!("Query rewriter failed, searching the original query: {}", e);
+                query.to_string()
+            }
+            None => query.to_string(),
+        };
+        crate::aliases::expand(&query, &self.vectordb.header().aliases)
+    }
+
+    /// Redaction patterns of a project config (`[redact]`), applied to
+    /// files indexed from now on
+    pub fn set_redaction(&mut self, config: &crate::config::RedactConfig) -> Result<()> {
+        self.redactor = crate::redact::Redactor::new(config)?;
+        Ok(())
+    }
+
+    /// Redaction patterns applied to indexed files, for callers that quote
+    /// file content back
+    pub fn redactor(&self) -> &crate::redact::Redactor {
+        &self.redactor
+    }
+
+    /// Apply the ranking settings of a project config: `boost`, `[weights]`
+    /// and the `[sona]` size caps
+    pub fn apply_ranking(&mut self, config: &crate::config::ProjectConfig) {
+        self.set_boost_rules(config.boost.clone());
+        self.set_score_weights(config.weights);
+        if let Some(ref mut sona) = self.sona {
+            sona.set_limits(config.sona);
+        }
+    }
+
+    fn compile_module_filter(modules: &[String]) -> Result<Vec<glob::Pattern>> {
+        modules
+            .iter()
+            .map(|m| glob::Pattern::new(m).with_context(|| format!("Invalid module pattern: {}", m)))
+            .collect()
+    }
+
+    /// Whether a file belongs to the indexed module subset.
+    /// Always true when no `--only-modules` filter is set.
+    pub fn in_module_subset(&self, path: &Path) -> bool {
+        Self::module_filter_matches(&self.module_filter, &self.magento_root, path)
+    }
+
+    fn module_filter_matches(filter: &[glob::Pattern], root: &Path, path: &Path) -> bool {
+        if filter.is_empty() {
+            return true;
+        }
+        module_names_for_path(&relative_path(root, path))
+            .iter()
+            .any(|name| filter.iter().any(|p| p.matches(name)))
+    }
+
+    pub fn magento_root(&self) -> &Path {
+        &self.magento_root
+    }
+
+    /// Config cross-reference of the codebase, scanned on first use when no
+    /// index run has built it yet
+    pub fn config_xref(&mut self) -> Result<&ConfigXref> {
+        if self.config_xref.is_none() {
+            self.config_sources = ConfigXref::source_files(&self.magento_root, &self.extra_root_paths())?.into_iter().collect();
+            self.rebuild_config_xref();
+        }
+        Ok(self.config_xref.as_ref().expect("config xref scanned above"))
+    }
+
+    /// Parse `config_sources` into the config cross-reference; files deleted
+    /// since are skipped
+    fn rebuild_config_xref(&mut self) {
+        let sources = self.config_sources.iter().map(PathBuf::as_path);
+        self.config_xref = Some(ConfigXref::from_files(&self.magento_root, sources, &self.xml_analyzer));
+    }
+
+    /// Route, controllers, layout handles, blocks and templates the URL path
+    /// `url` dispatches to
+    pub fn trace_request(&mut self, url: &str) -> Result<crate::trace::RequestTrace> {
+        let index = crate::trace::FileIndex::new(self.vectordb.metadata_iter().map(|(_, meta)| {
+            let class = meta.class_name.as_ref().map(|name| match meta.namespace {
+                Some(ref ns) => format!("{}\\{}", ns, name),
+                None => name.clone(),
+            });
+            (meta.path.clone(), class)
+        }));
+        crate::trace::trace_request(self.config_xref()?, &index, url)
+    }
+
+    /// Header of the loaded index (module subset, ...)
+    pub fn index_header(&self) -> &IndexHeader {
+        self.vectordb.header()
+    }
+
+    /// Path (relative to magento_root) of the indexed file declaring `class`
+    pub fn class_file(&self, class: &str) -> Option<String> {
+        let class = class.trim_start_matches('\\');
+        self.vectordb.metadata_iter().find_map(|(_, meta)| {
+            let name = meta.class_name.as_deref()?;
+            let fqcn = match meta.namespace {
+                Some(ref ns) => format!("{}\\{}", ns, name),
+                None => name.to_string(),
+            };
+            (fqcn == class).then(|| meta.path.clone())
+        })
+    }
+
+    /// Live vector IDs grouped by file path (relative to magento_root).
+    pub fn vector_ids_by_path(&self) -> std::collections::HashMap<String, Vec<usize>> {
+        self.vectordb.ids_by_path()
+    }
+
     /// Collect paths (relative to magento_root, as stored in IndexMetadata)
     /// of files that already have at least one vector in the current DB.
     /// Used by resume mode to avoid re-embedding work from a previous run.
@@ -198,11 +1264,12 @@ impl Indexer {
     ///
     /// `force=true` clears the existing index and re-embeds everything.
     /// `force=false` (the default) auto-resumes from any partial index saved
-    /// by a previous run — files already present in the DB are skipped during
-    /// both PHASE 1 parsing and PHASE 2 embedding, and the existing HNSW is
-    /// preserved rather than thrown away.
+    /// by a previous run — files already present in the DB are skipped by the
+    /// parse/embed pipeline, and the existing HNSW is preserved rather than
+    /// thrown away.
     pub fn index_with_options(&mut self, force: bool) -> Result<IndexStats> {
         let mut stats = IndexStats::default();
+        let mut force = force || self.deterministic;
 
         println!();
         println!("  __  __    _    ____ _____ ____ _____ ___  ____  ");
@@ -215,8 +1282,84 @@ impl Indexer {
         println!();
 
         println!("📁 Source: {:?}", self.magento_root);
-        if !self.ignore_patterns.is_empty() {
-            println!("📋 .magectorignore: {} custom patterns loaded", self.ignore_patterns.len());
+        if !self.ignore.is_empty() {
+            let files = if self.gitignore { ".gitignore + .magectorignore" } else { ".magectorignore" };
+            println!("📋 {}: {} ignore patterns loaded", files, self.ignore.len());
+        }
+        if self.follow_symlinks {
+            println!("🔗 Following symlinks (each directory indexed once)");
+        }
+        if self.include_tests {
+            println!("🧪 Including MFTF test XML (Test/Mftf)");
+        }
+        if self.include_docs {
+            println!("📚 Including Markdown docs (docs collection)");
+        }
+        if self.deterministic {
+            println!("🔒 Deterministic mode: sorted insertion, stable IDs, full rebuild");
+        }
+        if !self.only_modules.is_empty() {
+            println!("🧩 Partial index: only modules matching {}", self.only_modules.join(", "));
+        }
+        for root in &self.extra_roots {
+            println!("📁 Extra root {}: {}", root.name, root.path);
+        }
+        if !force && !self.vectordb.is_empty() && self.vectordb.header().only_modules != self.only_modules {
+            println!("🔁 Module subset differs from the existing index — rebuilding");
+            force = true;
+        }
+        if self.embedder.pooling() != Pooling::default() {
+            println!("🧮 Pooling: {}", self.embedder.pooling());
+        }
+        if !force && !self.vectordb.is_empty() && self.vectordb.header().pooling != self.embedder.pooling() {
+            println!(
+                "🔁 Pooling differs from the existing index ({} → {}) — rebuilding",
+                self.vectordb.header().pooling,
+                self.embedder.pooling()
+            );
+            force = true;
+        }
+        if self.meta_vectors {
+            println!("🧾 Meta vectors: code and metadata embedded separately");
+        }
+        if !force && !self.vectordb.is_empty() && self.vectordb.header().meta_vectors != self.meta_vectors {
+            println!("🔁 Meta vectors {} — rebuilding", if self.meta_vectors { "enabled" } else { "disabled" });
+            force = true;
+        }
+        if self.method_chunks {
+            println!("✂️  Method chunks: one item per PHP method");
+        }
+        if !force && !self.vectordb.is_empty() && self.vectordb.header().method_chunks != self.method_chunks {
+            println!("🔁 Method chunks {} — rebuilding", if self.method_chunks { "enabled" } else { "disabled" });
+            force = true;
+        }
+        if self.coarse_dim > 0 {
+            println!("🎯 Coarse search: {}-dim graph, full {}-dim rerank", self.coarse_dim, EMBEDDING_DIM);
+        }
+        if self.two_stage {
+            println!("🪜 Two-stage search: files first, then their chunks");
+        }
+        let mut regraph = false;
+        if !force && !self.vectordb.is_empty() && self.vectordb.header().coarse_dim != self.coarse_dim {
+            println!("🔁 Coarse dimension changed — rebuilding the search graph");
+            self.vectordb.set_coarse_dim(self.coarse_dim);
+            regraph = true;
+        }
+        if !force && !self.vectordb.is_empty() && self.vectordb.header().two_stage != self.two_stage {
+            self.vectordb.set_two_stage(self.two_stage);
+            regraph = true;
+        }
+        if self.summaries {
+            println!("📝 Storing file summaries in the metadata");
+        }
+        let mut resummarized = false;
+        if !force && !self.vectordb.is_empty() && self.vectordb.header().summaries != self.summaries {
+            println!("🔁 Summaries {} — rewriting the metadata", if self.summaries { "enabled" } else { "disabled" });
+            let summaries = self.summaries;
+            self.vectordb.update_metadata(|meta| {
+                meta.summary = summaries.then(|| crate::summary::summarize(meta));
+            });
+            resummarized = true;
         }
 
         // Decide resume vs full rebuild. Build the already-indexed path set
@@ -249,12 +1392,37 @@ impl Indexer {
             // No existing index — nothing to clear, nothing to resume.
             self.vectordb.clear();
         }
+        if self.incremental && !resume {
+            println!("🧮 --incremental: no index to update — building it in full");
+        }
 
         println!("🔍 Discovering files...");
 
-        let all_files = self.discover_files()?;
+        let mut discovery_skips = Vec::new();
+        let mut all_files = self.discover_files(Some(&mut discovery_skips))?;
+        // Before the core filter: overlay code may be referenced by core config
+        self.config_sources =
+            all_files.iter().filter(|f| ConfigXref::is_source(&relative_path(&self.magento_root, f))).cloned().collect();
+        self.rebuild_config_xref();
+        if let Some(ref core) = self.core {
+            let before = all_files.len();
+            all_files.retain(|f| !core.paths.contains(&relative_path(&self.magento_root, f)));
+            println!(
+                "🧱 Core index {}: {} files served from it, indexing the overlay only",
+                core.path.display(),
+                before - all_files.len()
+            );
+        }
         stats.files_found = all_files.len();
 
+        let mut issues = IndexIssues::default();
+        for (path, reason) in discovery_skips {
+            if reason == SkipReason::TooLarge {
+                issues.record(relative_path(&self.magento_root, &path), reason, None);
+            }
+        }
+        let issues = std::sync::Mutex::new(issues);
+
         // In resume mode, use FileManifest for true incremental indexing:
         // detect added/modified/deleted files via mtime+size comparison,
         // not just "is path in DB".
@@ -267,7 +1435,7 @@ impl Indexer {
                     // No manifest on disk — first run after upgrade.
                     // Build from filesystem (treats all indexed files as current).
                     tracing::info!("No manifest found — building from filesystem for existing index");
-                    crate::watcher::FileManifest::from_existing_index(&self.magento_root, &already_indexed)
+                    crate::watcher::FileManifest::from_existing_index(&self.magento_root, &self.extra_root_paths(), &already_indexed, self.walk_options())
                 })
         } else {
             crate::watcher::FileManifest::new()
@@ -275,18 +1443,26 @@ impl Indexer {
 
         let (files, skipped_resume): (Vec<PathBuf>, usize) = if resume {
             // Detect changes against manifest
-            let changes = manifest.detect_changes(&self.magento_root)?;
+            let mut changes = manifest.detect_changes_in(&self.magento_root, &self.extra_root_paths(), self.walk_options())?;
+            // Files an ignore rule added since they were indexed go like
+            // deleted ones
+            let newly_ignored: Vec<String> =
+                manifest.files.keys().filter(|path| self.ignored(Path::new(path))).cloned().collect();
+            changes.modified.retain(|f| !self.ignored(f));
+            changes.deleted.extend(newly_ignored);
+            let touched_count = if self.incremental {
+                let touched = manifest.settle_unchanged(&self.magento_root, &mut changes);
+                manifest.record_missing_hashes(&self.magento_root, &changes);
+                touched
+            } else {
+                0
+            };
             let modified_count = changes.modified.len();
             let deleted_count = changes.deleted.len();
-            let added_count = changes.added.len();
 
             // Tombstone vectors for modified files (will be re-indexed)
             for path in &changes.modified {
-                let relative = path
-                    .strip_prefix(&self.magento_root)
-                    .unwrap_or(path)
-                    .to_string_lossy()
-                    .to_string();
+                let relative = relative_path(&self.magento_root, path);
                 self.remove_vectors_for_path(&relative);
             }
 
@@ -305,10 +1481,12 @@ impl Indexer {
             // Files to process = new + modified
             let to_process: Vec<PathBuf> = changes.added
                 .into_iter()
-                .chain(changes.modified.into_iter())
+                .filter(|f| self.indexes_locally(f))
+                .chain(changes.modified)
                 .collect();
+            let added_count = to_process.len() - modified_count;
 
-            let skipped = stats.files_found - to_process.len() - deleted_count;
+            let skipped = stats.files_found.saturating_sub(to_process.len() + deleted_count);
 
             if modified_count > 0 || deleted_count > 0 || added_count > 0 {
                 println!(
@@ -316,6 +1494,9 @@ impl Indexer {
                     added_count, modified_count, deleted_count, skipped
                 );
             }
+            if touched_count > 0 {
+                println!("🧮 {} files touched but unchanged (same content hash) — kept their vectors", touched_count);
+            }
 
             (to_process, skipped)
         } else {
@@ -332,23 +1513,27 @@ impl Indexer {
         }
 
         // Show file type breakdown (of the files we'll actually process)
-        let mut php_files = 0;
-        let mut js_files = 0;
-        let mut xml_files = 0;
-        let mut other_files = 0;
-        for f in &files {
-            match f.extension().and_then(|e| e.to_str()).unwrap_or("") {
-                "php" | "phtml" => php_files += 1,
-                "js" => js_files += 1,
-                "xml" => xml_files += 1,
-                _ => other_files += 1,
+        Self::print_breakdown(&files);
+
+        // Preflight: refuse to start an hour of embedding that cannot be saved
+        let estimate = IndexEstimate::for_vectors(self.vectordb.len() + files.len());
+        Self::print_estimate(&estimate);
+        if let Some(ref db_path) = self.db_path {
+            match check_disk_space(db_path, &estimate) {
+                SpaceCheck::Insufficient { available, required } => anyhow::bail!(
+                    "Not enough disk space for the index: ~{} needed, {} available on the filesystem holding {:?}",
+                    format_bytes(required),
+                    format_bytes(available),
+                    db_path
+                ),
+                SpaceCheck::Low { available, required } => println!(
+                    "⚠️  Low disk space: ~{} needed, {} available\n",
+                    format_bytes(required),
+                    format_bytes(available)
+                ),
+                SpaceCheck::Ok => {}
             }
         }
-        println!("File breakdown:");
-        println!("  PHP/PHTML: {} files", php_files);
-        println!("  JavaScript: {} files", js_files);
-        println!("  XML: {} files", xml_files);
-        println!("  Other: {} files\n", other_files);
 
         // Early-out: nothing to do. A previous run finished (or all discovered
         // files are already embedded) — just report stats and return.
@@ -358,32 +1543,104 @@ impl Indexer {
             // Still save manifest (deleted files may have been tombstoned above)
             if let Some(ref mp) = manifest_path {
                 if !resume {
-                    manifest = crate::watcher::FileManifest::from_existing_index(&self.magento_root, &self.indexed_paths());
+                    manifest = crate::watcher::FileManifest::from_existing_index(&self.magento_root, &self.extra_root_paths(), &self.indexed_paths(), self.walk_options());
                 }
+                manifest.set_vector_ids(&self.vectordb.ids_by_path());
                 if let Err(e) = manifest.save(mp) {
                     tracing::warn!("Failed to save manifest: {}", e);
                 }
             }
+            // Deleted files may have been the only copy of a class
+            let (_, relinked) = self.link_overrides();
+            let (_, retraited) = self.link_traits(None);
+            let (_, reinherited) = self.link_inheritance(None);
+            let (_, reimplemented) = self.link_implementations(None);
+            let (_, retagged) = self.tag_roots();
+            let (_, restamped) = self.stamp_commit_times();
+            let (_, reowned) = self.assign_owners();
             // Save DB if we tombstoned any vectors (deleted/modified files)
-            if resume && self.vectordb.len() != preexisting_vectors {
-                if let Some(ref db_path) = self.db_path {
-                    if let Err(e) = self.save_atomic(db_path) {
+            if resume
+                && (regraph
+                    || resummarized
+                    || relinked
+                    || retraited
+                    || reinherited
+                    || reimplemented
+                    || retagged
+                    || restamped
+                    || reowned
+                    || self.vectordb.len() != preexisting_vectors)
+            {
+                if let Some(db_path) = self.db_path.clone() {
+                    if let Err(e) = self.save_atomic(&db_path) {
                         tracing::warn!("Failed to save index after cleanup: {}", e);
                     }
                 }
             }
+            self.refresh_stop_terms();
             return Ok(stats);
         }
 
-        // Phase 1: Parse files in parallel (no embedding needed)
-        println!("════════════════════════════════════════════════════════════");
-        println!("PHASE 1: Parsing files with AST analyzers");
-        println!("════════════════════════════════════════════════════════════\n");
+        // Parsing and embedding run as an overlapped pipeline: rayon workers
+        // parse files and push their items into a bounded queue while this
+        // thread drains it into ONNX batches. The bound keeps memory flat on
+        // large codebases and applies back-pressure when embedding is slower.
+        let batch_size = self.batch_size;
+        println!("════════════════════════════════════════════════════════════");
+        println!("PIPELINE: AST parsing + semantic embeddings (ONNX, batch={})", batch_size);
+        println!("════════════════════════════════════════════════════════════\n");
+
+        // LLM descriptions are injected into embedding text as items arrive,
+        // so open the descriptions DB before the pipeline starts.
+        let desc_db = match self.descriptions_db {
+            Some(ref desc_db_path) if desc_db_path.exists() => {
+                match crate::describe::DescriptionDb::open_readonly(desc_db_path) {
+                    Ok(db) => Some(db),
+                    Err(e) => {
+                        tracing::warn!("Could not open descriptions DB: {}", e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        let summaries = self.summaries;
+        let meta_vectors = self.meta_vectors;
+
+        // In non-resume mode we previously replaced vectordb entirely with a
+        // fresh capacity-tuned instance. In resume mode that would wipe the
+        // state we just loaded from disk. Only do the reset on a fresh run.
+        // (On a resume the HNSW will be slightly oversized relative to what a
+        // fresh-capacity allocation would give, but correctness beats
+        // micro-optimization here.) Item counts aren't known until parsing
+        // finishes, so size for one item per file.
+        if !resume && preexisting_vectors == 0 {
+            self.vectordb = VectorDB::with_capacity(files.len());
+        }
+        self.vectordb.set_header(self.build_header(true));
+
+        // Parse workers finish one priority tier before starting the next
+        // and the consumer checkpoints the DB at each boundary. Deterministic
+        // mode drains everything first, so its boundaries are no-ops.
+        // (Resume lists come from the manifest, hence the re-sort.)
+        let mut files = files;
+        let root = &self.magento_root;
+        files.sort_by_cached_key(|f| Self::priority_tier(&relative_path(root, f)));
+        let mut tier_ranges: Vec<(usize, std::ops::Range<usize>)> = Vec::new();
+        for (i, f) in files.iter().enumerate() {
+            let tier = Self::priority_tier(&relative_path(root, f));
+            match tier_ranges.last_mut() {
+                Some((t, range)) if *t == tier => range.end = i + 1,
+                _ => tier_ranges.push((tier, i..i + 1)),
+            }
+        }
 
+        // One bar over files: a file is done once it was skipped, failed,
+        // or all of its items have been embedded.
         let pb = ProgressBar::new(files.len() as u64);
         pb.set_style(
             ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) ~{eta} remaining")
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) ~{eta} remaining {msg}")
                 .unwrap()
                 .progress_chars("█▓░"),
         );
@@ -392,177 +1649,297 @@ impl Indexer {
         let indexed = AtomicUsize::new(0);
         let skipped = AtomicUsize::new(0);
         let errors = AtomicUsize::new(0);
+        let lossy = AtomicUsize::new(0);
         let php_count = AtomicUsize::new(0);
         let js_count = AtomicUsize::new(0);
         let xml_count = AtomicUsize::new(0);
         let other_count = AtomicUsize::new(0);
 
-        // Clone refs needed for parallel processing
+        // Split borrows: parse workers only need the analyzer, while this
+        // thread owns the embedder and the vector DB.
         let magento_root = self.magento_root.clone();
         let xml_analyzer = &self.xml_analyzer;
-        let ast_php = self.ast_available.php;
-        let ast_js = self.ast_available.js;
+        let redactor = &self.redactor;
+        let config_xref = self.config_xref.as_ref().expect("config xref built during discovery");
+        let ast = self.ast_available;
+        let method_chunks = self.method_chunks;
+        let deterministic = self.deterministic;
+        let embedder = &mut self.embedder;
+        let vectordb = &mut self.vectordb;
+        let texts = &mut self.texts;
+        let db_path = self.db_path.as_deref();
 
-        let parsed_results: Vec<_> = files
-            .par_iter()
-            .filter_map(|file_path| {
-                pb.inc(1);
-
-                let ext = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
-                match ext {
-                    "php" | "phtml" => php_count.fetch_add(1, Ordering::Relaxed),
-                    "js" => js_count.fetch_add(1, Ordering::Relaxed),
-                    "xml" => xml_count.fetch_add(1, Ordering::Relaxed),
-                    _ => other_count.fetch_add(1, Ordering::Relaxed),
-                };
-
-                match Self::parse_file(file_path, &magento_root, xml_analyzer, ast_php, ast_js) {
-                    Ok(Some(items)) => {
-                        indexed.fetch_add(1, Ordering::Relaxed);
-                        Some(items)
+        let mut embedded = 0;
+        let mut enriched = 0usize;
+        let mut batch_num = 0;
+        let pipeline_start = std::time::Instant::now();
+
+        std::thread::scope(|scope| -> Result<()> {
+            let (tx, rx) = std::sync::mpsc::sync_channel::<Vec<ParsedFile>>(
+                batch_size * PIPELINE_QUEUE_BATCHES,
+            );
+
+            let pb_parse = pb.clone();
+            let (indexed, skipped, errors, lossy, issues) = (&indexed, &skipped, &errors, &lossy, &issues);
+            let (php_count, js_count, xml_count, other_count) =
+                (&php_count, &js_count, &xml_count, &other_count);
+            let files = &files;
+            let tier_ranges = &tier_ranges;
+            let magento_root = &magento_root;
+            scope.spawn(move || {
+                let relative = |path: &Path| relative_path(magento_root, path);
+                let parse_all = |tier: &[PathBuf]| tier.par_iter().try_for_each_with(tx.clone(), |tx, file_path| {
+                    let ext = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+                    match ext {
+                        "php" | "phtml" => php_count.fetch_add(1, Ordering::Relaxed),
+                        "js" => js_count.fetch_add(1, Ordering::Relaxed),
+                        "xml" => xml_count.fetch_add(1, Ordering::Relaxed),
+                        _ => other_count.fetch_add(1, Ordering::Relaxed),
+                    };
+
+                    match Self::parse_file(file_path, magento_root, xml_analyzer, redactor, config_xref, ast, method_chunks) {
+                        Ok(Some(items)) if !items.is_empty() => {
+                            indexed.fetch_add(1, Ordering::Relaxed);
+                            if items[0].lossy {
+                                lossy.fetch_add(1, Ordering::Relaxed);
+                            }
+                            return tx.send(items);
+                        }
+                        Ok(Some(_)) => {
+                            indexed.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Ok(None) => {
+                            skipped.fetch_add(1, Ordering::Relaxed);
+                            issues.lock().unwrap().record(relative(file_path), SkipReason::Empty, None);
+                        }
+                        Err(e) => {
+                            tracing::debug!("Error processing {:?}: {}", file_path, e);
+                            errors.fetch_add(1, Ordering::Relaxed);
+                            issues.lock().unwrap().record(
+                                relative(file_path),
+                                SkipReason::from_parse_error(&e),
+                                Some(format!("{:#}", e)),
+                            );
+                        }
                     }
-                    Ok(None) => {
-                        skipped.fetch_add(1, Ordering::Relaxed);
-                        None
+                    pb_parse.inc(1);
+                    Ok(())
+                });
+
+                // A send error means the consumer bailed out — stop parsing.
+                // An empty batch marks a tier boundary.
+                for (i, (_, range)) in tier_ranges.iter().enumerate() {
+                    if parse_all(&files[range.clone()]).is_err() {
+                        return;
                     }
-                    Err(e) => {
-                        tracing::debug!("Error processing {:?}: {}", file_path, e);
-                        errors.fetch_add(1, Ordering::Relaxed);
-                        None
+                    if i + 1 < tier_ranges.len() && tx.send(Vec::new()).is_err() {
+                        return;
                     }
                 }
-            })
-            .flatten()
-            .collect();
+            });
+
+            // Items waiting to be embedded, with their chunk index within the
+            // file; the flag marks the last item of a file so the bar only
+            // advances once the whole file is stored.
+            let mut pending: Vec<(ParsedFile, usize, bool)> = Vec::with_capacity(batch_size);
+            let mut parsing_done = false;
+            let mut receive = |items: Vec<ParsedFile>, pending: &mut Vec<(ParsedFile, usize, bool)>| {
+                let last = items.len() - 1;
+                for (i, mut item) in items.into_iter().enumerate() {
+                    if summaries {
+                        item.metadata.summary = Some(crate::summary::summarize(&item.metadata));
+                    }
+                    // Checkout times differ between machines
+                    if let (true, Some(metrics)) = (deterministic, item.metadata.metrics.as_mut()) {
+                        metrics.modified = None;
+                    }
+                    if let Some(desc) = desc_db.as_ref().and_then(|db| db.get(&item.metadata.path)) {
+                        // Prepend description to the embedded text
+                        let prefix = format!("Description: {}\n\n", desc.description);
+                        item.lead.insert_str(0, &prefix);
+                        enriched += 1;
+                    }
+                    pending.push((item, i, i == last));
+                }
+            };
+
+            // Deterministic mode trades the overlap for a stable order: wait
+            // for every file, then embed in path order so batch composition
+            // (and therefore every float) is identical across runs.
+            if deterministic {
+                for items in rx.iter().filter(|items| !items.is_empty()) {
+                    receive(items, &mut pending);
+                }
+                pending.sort_by(|a, b| {
+                    a.0.metadata.path.cmp(&b.0.metadata.path).then(a.1.cmp(&b.1))
+                });
+                parsing_done = true;
+            }
+
+            // Set once a tier is fully parsed; the DB is saved as soon as its
+            // items are embedded.
+            let mut checkpoint = false;
+            let mut boundary = 0;
+
+            while !parsing_done || !pending.is_empty() {
+                while !parsing_done && !checkpoint && pending.len() < batch_size {
+                    match rx.recv() {
+                        Ok(items) if items.is_empty() => checkpoint = true,
+                        Ok(items) => receive(items, &mut pending),
+                        Err(_) => parsing_done = true,
+                    }
+                }
+                if checkpoint && pending.is_empty() {
+                    checkpoint = false;
+                    let tier = tier_ranges[boundary].0;
+                    let next_tier = tier_ranges[boundary + 1].0;
+                    boundary += 1;
+                    let msg = if tier < PRIORITY_CHECKPOINT_TIERS && next_tier >= PRIORITY_CHECKPOINT_TIERS {
+                        let labels: Vec<&str> = PRIORITY_TIERS[..PRIORITY_CHECKPOINT_TIERS]
+                            .iter()
+                            .map(|(label, _)| *label)
+                            .collect();
+                        format!(
+                            "⚡ Priority files indexed ({}): {} vectors — partial index usable",
+                            labels.join(", "),
+                            vectordb.len()
+                        )
+                    } else {
+                        format!("Checkpoint: {} indexed, {} vectors written to disk", Self::tier_label(tier), vectordb.len())
+                    };
+                    if let Some(db_path) = db_path {
+                        Self::checkpoint_save(vectordb, db_path, &pb, &msg);
+                    }
+                    continue;
+                }
+                if pending.is_empty() {
+                    break;
+                }
+
+                let take = pending.len().min(batch_size);
+                let chunk: Vec<(ParsedFile, usize, bool)> = pending.drain(..take).collect();
+                let parsed: Vec<&ParsedFile> = chunk.iter().map(|(p, _, _)| p).collect();
+
+                let (embed_texts, (embeddings, meta_embeddings)) = Self::embed_parsed(embedder, &parsed, meta_vectors)?;
+
+                let files_done = chunk.iter().filter(|(_, _, last)| *last).count();
+                let batch_len = chunk.len();
+                let batch_items: Vec<(usize, Vec<f32>, IndexMetadata)> = embeddings
+                    .into_iter()
+                    .zip(chunk)
+                    .map(|(emb, (parsed, n, _))| (stable_id(&parsed.metadata.path, n), emb, parsed.metadata))
+                    .collect();
+                let ids = vectordb.insert_batch_with_ids(batch_items);
+                for (&id, text) in ids.iter().zip(embed_texts) {
+                    texts.insert(id, text);
+                }
+                vectordb.insert_meta_vectors(ids.into_iter().zip(meta_embeddings).collect());
+
+                embedded += batch_len;
+                batch_num += 1;
+                pb.inc(files_done as u64);
+                pb.set_message(format!("Embedded {} vectors", embedded));
+
+                // Log progress periodically — use pb.println() so indicatif doesn't overwrite,
+                // and tracing::info! so it also appears in the log file when piped
+                if batch_num % LOG_INTERVAL_BATCHES == 0 {
+                    let elapsed = pipeline_start.elapsed();
+                    let rate = embedded as f64 / elapsed.as_secs_f64();
+                    let msg = format!(
+                        "[PIPELINE] files {}/{} ({:.1}%) embedded={} batch {} elapsed={:.0}s rate={:.0} items/s",
+                        pb.position(), files.len(),
+                        (pb.position() as f64 / files.len() as f64) * 100.0,
+                        embedded, batch_num,
+                        elapsed.as_secs_f64(), rate,
+                    );
+                    pb.println(&msg);
+                    tracing::info!("{}", msg);
+                }
+
+                // Incremental save to disk — enables partial recovery on crash/restart
+                if batch_num % SAVE_INTERVAL_BATCHES == 0 {
+                    if let Some(db_path) = db_path {
+                        let msg = format!("Incremental save: {} vectors written to disk", embedded);
+                        Self::checkpoint_save(vectordb, db_path, &pb, &msg);
+                    }
+                }
+            }
 
-        pb.finish_with_message("✓ Parsing complete");
+            Ok(())
+        })?;
+
+        pb.finish_with_message(format!("✓ Generated {} embeddings", embedded));
+        let items: Vec<&IndexMetadata> = self.vectordb.metadata_iter().map(|(_, meta)| meta).collect();
+        let aliases = crate::aliases::mine(&items);
+        self.vectordb.set_header(IndexHeader { aliases, ..self.build_header(false) });
 
         stats.files_indexed = indexed.load(Ordering::Relaxed);
         stats.files_skipped = skipped.load(Ordering::Relaxed);
         stats.errors = errors.load(Ordering::Relaxed);
+        stats.lossy_decodes = lossy.load(Ordering::Relaxed);
         stats.php_files = php_count.load(Ordering::Relaxed);
         stats.js_files = js_count.load(Ordering::Relaxed);
         stats.xml_files = xml_count.load(Ordering::Relaxed);
         stats.other_files = other_count.load(Ordering::Relaxed);
 
-        println!("\n✓ Parsing complete:");
+        println!("\n✓ Pipeline complete in {:.1}s:", pipeline_start.elapsed().as_secs_f64());
         println!("  Files parsed: {}", stats.files_indexed);
         println!("  Files skipped: {}", stats.files_skipped);
         println!("  Errors: {}", stats.errors);
-        println!("  Items to embed: {}\n", parsed_results.len());
-
-        // Inject LLM descriptions into embedding text (prepend before raw content)
-        let mut parsed_results = parsed_results;
-        if let Some(ref desc_db_path) = self.descriptions_db {
-            if desc_db_path.exists() {
-                match crate::describe::DescriptionDb::open_readonly(desc_db_path) {
-                    Ok(desc_db) => {
-                        let mut enriched = 0usize;
-                        for item in &mut parsed_results {
-                            if let Some(desc) = desc_db.get(&item.metadata.path) {
-                                // Prepend description to embed_text
-                                let prefix = format!("Description: {}\n\n", desc.description);
-                                item.embed_text.insert_str(0, &prefix);
-                                enriched += 1;
-                            }
-                        }
-                        if enriched > 0 {
-                            println!("✓ Enriched {} items with LLM descriptions\n", enriched);
-                        }
-                    }
-                    Err(e) => {
-                        tracing::warn!("Could not open descriptions DB: {}", e);
-                    }
-                }
-            }
+        if stats.lossy_decodes > 0 {
+            println!("  Non-UTF-8 files decoded: {}", stats.lossy_decodes);
         }
-
-        // Phase 2: Generate embeddings in batches
-        let batch_size = self.batch_size;
-        println!("════════════════════════════════════════════════════════════");
-        println!("PHASE 2: Generating semantic embeddings (ONNX, batch={})", batch_size);
-        println!("════════════════════════════════════════════════════════════\n");
-
-        // In non-resume mode we previously replaced vectordb entirely with a
-        // fresh capacity-tuned instance. In resume mode that would wipe the
-        // state we just loaded from disk. Only do the reset on a fresh run.
-        // (On a resume the HNSW will be slightly oversized relative to what a
-        // fresh-capacity allocation would give, but correctness beats
-        // micro-optimization here.)
-        if !resume && preexisting_vectors == 0 {
-            self.vectordb = VectorDB::with_capacity(parsed_results.len());
+        println!("  Items embedded: {}", embedded);
+        if enriched > 0 {
+            println!("  Enriched with LLM descriptions: {}", enriched);
         }
 
-        let total_items = parsed_results.len();
-        let total_batches = (total_items + batch_size - 1) / batch_size;
-        let pb = ProgressBar::new(total_items as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) ~{eta} remaining")
-                .unwrap()
-                .progress_chars("█▓░"),
-        );
-        pb.enable_steady_tick(std::time::Duration::from_millis(100));
-
-        let mut embedded = 0;
-        let mut batch_num = 0;
-        let phase2_start = std::time::Instant::now();
-
-        // Process in batches with incremental saves and progress logging
-        for chunk in parsed_results.chunks(batch_size) {
-            let texts: Vec<&str> = chunk.iter().map(|p| p.embed_text.as_str()).collect();
-
-            let embeddings = self.embedder.embed_batch(&texts)?;
-
-            let batch_items: Vec<(Vec<f32>, IndexMetadata)> = embeddings
-                .into_iter()
-                .zip(chunk.iter())
-                .map(|(emb, parsed)| (emb, parsed.metadata.clone()))
+        let issues = issues.into_inner().unwrap();
+        if issues.total() > 0 {
+            let by_reason: Vec<String> = issues
+                .counts()
+                .iter()
+                .map(|(reason, n)| format!("{} {}", n, reason.as_str()))
                 .collect();
-
-            let batch_len = batch_items.len();
-            self.vectordb.insert_batch(batch_items);
-
-            embedded += batch_len;
-            batch_num += 1;
-            pb.inc(batch_len as u64);
-            pb.set_message(format!("Embedded {} vectors", embedded));
-
-            // Log progress periodically — use pb.println() so indicatif doesn't overwrite,
-            // and tracing::info! so it also appears in the log file when piped
-            if batch_num % LOG_INTERVAL_BATCHES == 0 || batch_num == total_batches {
-                let elapsed = phase2_start.elapsed();
-                let rate = embedded as f64 / elapsed.as_secs_f64();
-                let remaining = total_items - embedded;
-                let eta_secs = if rate > 0.0 { remaining as f64 / rate } else { 0.0 };
-                let msg = format!(
-                    "[PHASE 2] {}/{} ({:.1}%) batch {}/{} elapsed={:.0}s eta={:.0}s rate={:.0} items/s",
-                    embedded, total_items,
-                    (embedded as f64 / total_items as f64) * 100.0,
-                    batch_num, total_batches,
-                    elapsed.as_secs_f64(), eta_secs, rate,
-                );
-                pb.println(&msg);
-                tracing::info!("{}", msg);
-            }
-
-            // Incremental save to disk — enables partial recovery on crash/restart
-            if batch_num % SAVE_INTERVAL_BATCHES == 0 {
-                if let Some(ref db_path) = self.db_path {
-                    if let Err(e) = self.vectordb.save_atomic(db_path) {
-                        tracing::warn!("Incremental save failed (non-fatal): {e}");
-                    } else {
-                        let msg = format!("Incremental save: {} vectors written to disk", embedded);
-                        pb.println(&msg);
-                        tracing::info!("{}", msg);
-                    }
-                }
+            println!("  Skipped/failed files: {} ({})", issues.total(), by_reason.join(", "));
+        }
+        if let Some(ref db_path) = self.db_path {
+            let report = IndexIssues::sidecar_path(db_path);
+            match issues.write(&report) {
+                Ok(()) if issues.total() > 0 => println!("  Details: {}", report.display()),
+                Ok(()) => {}
+                Err(e) => tracing::warn!("{:#}", e),
             }
         }
 
-        pb.finish_with_message(format!("✓ Generated {} embeddings", embedded));
-
+        let (overrides, _) = self.link_overrides();
+        if overrides > 0 {
+            println!("  Files overriding a vendor class: {}", overrides);
+        }
+        let (trait_users, _) = self.link_traits(None);
+        if trait_users > 0 {
+            println!("  Classes with methods from traits: {}", trait_users);
+        }
+        let (heirs, _) = self.link_inheritance(None);
+        if heirs > 0 {
+            println!("  Classes with Magento types from a parent: {}", heirs);
+        }
+        let (implemented, _) = self.link_implementations(None);
+        if implemented > 0 {
+            println!("  API interfaces with a preferred implementation: {}", implemented);
+        }
+        let (from_extra_roots, _) = self.tag_roots();
+        if from_extra_roots > 0 {
+            println!("  Items from extra roots: {}", from_extra_roots);
+        }
+        if self.git_times {
+            println!("  Files with a git commit time: {}", self.stamp_commit_times().0);
+        }
+        if self.owners {
+            println!("  Files with an owner: {}", self.assign_owners().0);
+        }
         stats.vectors_created = self.vectordb.len();
+        self.refresh_stop_terms();
 
         println!("\n════════════════════════════════════════════════════════════");
         println!("                    INDEXING COMPLETE                       ");
@@ -574,22 +1951,19 @@ impl Indexer {
         if let Some(ref mp) = manifest_path {
             if !resume {
                 // Full index — build manifest from filesystem
-                manifest = crate::watcher::FileManifest::from_existing_index(&self.magento_root, &self.indexed_paths());
+                manifest = crate::watcher::FileManifest::from_existing_index(&self.magento_root, &self.extra_root_paths(), &self.indexed_paths(), self.walk_options());
             } else {
                 // Incremental — update manifest entries for the files we just processed
                 let root = &self.magento_root;
+                let record_of =
+                    if self.incremental { crate::watcher::FileRecord::hashed } else { crate::watcher::FileRecord::of };
                 for f in &files {
-                    let rel = f.strip_prefix(root).unwrap_or(f).to_string_lossy().to_string();
-                    if let Ok(meta) = std::fs::metadata(f) {
-                        let mtime = meta.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                        manifest.files.insert(rel, crate::watcher::FileRecord {
-                            mtime,
-                            size: meta.len(),
-                            vector_ids: Vec::new(),
-                        });
+                    if let Some(record) = record_of(f, Vec::new()) {
+                        manifest.files.insert(relative_path(root, f), record);
                     }
                 }
             }
+            manifest.set_vector_ids(&self.vectordb.ids_by_path());
             if let Err(e) = manifest.save(mp) {
                 tracing::warn!("Failed to save manifest: {}", e);
             } else {
@@ -600,28 +1974,196 @@ impl Indexer {
         Ok(stats)
     }
 
-    /// Discover files to index (no symlink following for speed)
-    pub(crate) fn discover_files(&self) -> Result<Vec<PathBuf>> {
+    /// Discovery only — no model, parsing or embedding (`index --dry-run`).
+    /// Applies the ignore rules and the `only_modules` filter of `config`
+    /// like a real run.
+    pub fn plan(magento_root: &Path, extra_roots: &[ExtraRoot], config: &IndexConfig) -> Result<IndexPlan> {
+        let filter = Self::compile_module_filter(&config.only_modules)?;
+        let walk = config.walk_options();
+        let mut skipped = Vec::new();
         let mut files = Vec::new();
-        let root = &self.magento_root;
-        let ignore = &self.ignore_patterns;
+        for root in std::iter::once(magento_root).chain(extra_roots.iter().map(|r| Path::new(&r.path))) {
+            let ignore = IgnoreRules::load(root, config.gitignore.unwrap_or(false));
+            files.extend(Self::discover(
+                root,
+                &ignore,
+                &filter,
+                walk.follow_symlinks,
+                walk.include_tests,
+                walk.include_docs,
+                Some(&mut skipped),
+            )?);
+        }
 
-        for entry in WalkDir::new(root)
-            .follow_links(false)
-            .into_iter()
-            .filter_entry(|e| !Self::should_skip_entry(e, root, ignore))
-        {
+        // Detection pass: files that are not text would fail to parse
+        let binary: HashSet<&PathBuf> = files
+            .par_iter()
+            .filter(|f| {
+                read_source(f).is_err_and(|e| e.kind() == std::io::ErrorKind::InvalidData)
+            })
+            .collect();
+        skipped.extend(binary.iter().map(|f| ((*f).clone(), SkipReason::Binary)));
+        let files: Vec<PathBuf> = files.iter().filter(|f| !binary.contains(f)).cloned().collect();
+
+        skipped.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        let estimate = IndexEstimate::for_vectors(files.len());
+        Ok(IndexPlan { files, skipped, estimate })
+    }
+
+    /// Print per-type and per-tier file counts
+    pub fn print_breakdown(files: &[PathBuf]) {
+        let mut php_files = 0;
+        let mut js_files = 0;
+        let mut xml_files = 0;
+        let mut doc_files = 0;
+        let mut patch_files = 0;
+        let mut other_files = 0;
+        for f in files {
+            match f.extension().and_then(|e| e.to_str()).unwrap_or("") {
+                "php" | "phtml" => php_files += 1,
+                "js" => js_files += 1,
+                "xml" => xml_files += 1,
+                ext if crate::docs::DOC_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()) => doc_files += 1,
+                ext if crate::patches::PATCH_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()) => patch_files += 1,
+                _ => other_files += 1,
+            }
+        }
+        println!("File breakdown:");
+        println!("  PHP/PHTML: {} files", php_files);
+        println!("  JavaScript: {} files", js_files);
+        println!("  XML: {} files", xml_files);
+        if doc_files > 0 {
+            println!("  Markdown: {} files", doc_files);
+        }
+        if patch_files > 0 {
+            println!("  Patches: {} files", patch_files);
+        }
+        println!("  Other: {} files\n", other_files);
+    }
+
+    /// Print the size estimate line
+    pub fn print_estimate(estimate: &IndexEstimate) {
+        println!(
+            "📐 Estimated index: ~{} vectors, ~{} on disk ({} free needed during save)\n",
+            estimate.vectors,
+            format_bytes(estimate.db_bytes),
+            format_bytes(estimate.required_bytes())
+        );
+    }
+
+    /// Position of a path (relative to magento_root) in [`PRIORITY_TIERS`];
+    /// unmatched paths get `PRIORITY_TIERS.len()`.
+    fn priority_tier(relative: &str) -> usize {
+        PRIORITY_TIERS
+            .iter()
+            .position(|(_, prefixes)| prefixes.iter().any(|p| relative.starts_with(p)))
+            .unwrap_or(PRIORITY_TIERS.len())
+    }
+
+    fn tier_label(tier: usize) -> &'static str {
+        PRIORITY_TIERS.get(tier).map(|(label, _)| *label).unwrap_or("other files")
+    }
+
+    /// Save a pipeline checkpoint; failures are logged, not fatal
+    fn checkpoint_save(vectordb: &VectorDB, db_path: &Path, pb: &ProgressBar, msg: &str) {
+        if let Err(e) = vectordb.save_atomic(db_path) {
+            tracing::warn!("Incremental save failed (non-fatal): {e}");
+        } else {
+            pb.println(msg);
+            tracing::info!("{}", msg);
+        }
+    }
+
+    /// Discover files to index in priority order (symlinks only followed
+    /// with [`Self::set_follow_symlinks`])
+    pub(crate) fn discover_files(
+        &self,
+        mut skipped: Option<&mut Vec<(PathBuf, SkipReason)>>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut files = Self::discover(
+            &self.magento_root,
+            &self.ignore,
+            &self.module_filter,
+            self.follow_symlinks,
+            self.include_tests,
+            self.include_docs,
+            skipped.as_deref_mut(),
+        )?;
+        // Extra roots follow the Magento root, each with its own ignore files
+        for (root, ignore) in self.extra_roots.iter().zip(&self.extra_ignore) {
+            files.extend(Self::discover(
+                Path::new(&root.path),
+                ignore,
+                &self.module_filter,
+                self.follow_symlinks,
+                self.include_tests,
+                self.include_docs,
+                skipped.as_deref_mut(),
+            )?);
+        }
+        Ok(files)
+    }
+
+    /// Walk `root` for indexable files. When `skipped` is given, paths left
+    /// out (excluded directories, oversized files, other modules) are
+    /// recorded with the reason.
+    fn discover(
+        root: &Path,
+        ignore: &IgnoreRules,
+        module_filter: &[glob::Pattern],
+        follow_symlinks: bool,
+        include_tests: bool,
+        include_docs: bool,
+        mut skipped: Option<&mut Vec<(PathBuf, SkipReason)>>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        let mut walker = SourceWalk::new(root, follow_symlinks);
+        while let Some(entry) = walker.next() {
             let entry = entry?;
+            if let Some(reason) = Self::dir_skip_reason(&entry, root, ignore, include_tests) {
+                if let Some(ref mut skipped) = skipped {
+                    skipped.push((entry.path().to_path_buf(), reason));
+                }
+                walker.skip_current_dir();
+                continue;
+            }
             if entry.file_type().is_file() {
                 let path = entry.path();
 
+                let rel = relative_path(root, path);
+                if include_tests && skips_test_file(&rel) {
+                    continue;
+                }
+                if EXCLUDE_FILES.contains(&rel.as_str()) {
+                    if let Some(ref mut skipped) = skipped {
+                        skipped.push((path.to_path_buf(), SkipReason::Secrets));
+                    }
+                    continue;
+                }
+                if ignore.matches(&rel, false) {
+                    if let Some(ref mut skipped) = skipped {
+                        skipped.push((path.to_path_buf(), SkipReason::Ignored));
+                    }
+                    continue;
+                }
+
                 // Check extension first (cheap), then file size
                 if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                    if INCLUDE_EXTENSIONS.contains(&ext) {
+                    if is_indexable(&rel, ext, include_docs) {
                         // Use entry metadata (already cached from DirEntry)
                         if let Ok(meta) = entry.metadata() {
-                            if meta.len() <= MAX_FILE_SIZE {
-                                files.push(path.to_path_buf());
+                            let reason = if meta.len() > MAX_FILE_SIZE {
+                                Some(SkipReason::TooLarge)
+                            } else if !Self::module_filter_matches(module_filter, root, path) {
+                                Some(SkipReason::OutsideModules)
+                            } else {
+                                None
+                            };
+                            match (reason, skipped.as_mut()) {
+                                (None, _) => files.push(path.to_path_buf()),
+                                (Some(reason), Some(skipped)) => skipped.push((path.to_path_buf(), reason)),
+                                (Some(_), None) => {}
                             }
                         }
                     }
@@ -629,6 +2171,9 @@ impl Indexer {
             }
         }
 
+        // Custom code first, vendor last (see PRIORITY_TIERS)
+        files.sort_by_cached_key(|f| Self::priority_tier(&relative_path(root, f)));
+
         Ok(files)
     }
 
@@ -637,91 +2182,67 @@ impl Indexer {
     /// Checks (in order, cheapest first):
     /// 1. Directory name against EXCLUDE_DIRS (O(1) per entry)
     /// 2. Relative path prefix against EXCLUDE_PATHS (for nested paths like pub/static)
-    /// 3. .magectorignore patterns (directory prefix matching)
-    pub(crate) fn should_skip_entry(
+    /// 3. Ignore rules (`.magectorignore`, `.gitignore`; see [`IgnoreRules`])
+    ///
+    /// With `include_tests`, module `Test/` directories are walked down to
+    /// `Test/Mftf` (see [`test_dir_verdict`]).
+    pub(crate) fn dir_skip_reason(
         entry: &walkdir::DirEntry,
         root: &Path,
-        ignore_patterns: &[String],
-    ) -> bool {
+        ignore: &IgnoreRules,
+        include_tests: bool,
+    ) -> Option<SkipReason> {
         if !entry.file_type().is_dir() {
-            return false;
+            return None;
         }
 
         let name = entry.file_name().to_string_lossy();
 
+        let test_verdict = if include_tests && entry.path().starts_with(root) {
+            test_dir_verdict(&relative_path(root, entry.path()), &name)
+        } else {
+            None
+        };
+        if test_verdict == Some(true) {
+            return Some(SkipReason::ExcludedDir);
+        }
+
         // 1. Fast: exact directory name match
-        if EXCLUDE_DIRS.iter().any(|&d| name == *d) {
-            return true;
+        if test_verdict.is_none() && EXCLUDE_DIRS.iter().any(|&d| name == *d) {
+            return Some(SkipReason::ExcludedDir);
         }
 
         // 2. Relative path prefix match (for paths like pub/static, dev/tools)
-        if let Ok(relative) = entry.path().strip_prefix(root) {
-            let rel_str = relative.to_string_lossy();
+        if entry.path().starts_with(root) {
+            let rel_str = relative_path(root, entry.path());
 
             // Check built-in path exclusions
             if EXCLUDE_PATHS.iter().any(|&p| rel_str == p || rel_str.starts_with(&format!("{}/", p))) {
-                return true;
-            }
-
-            // 3. .magectorignore patterns (directory prefix matching)
-            if !ignore_patterns.is_empty() {
-                for pattern in ignore_patterns {
-                    let trimmed = pattern.trim_end_matches('/');
-                    // Exact match: "some/dir" matches "some/dir"
-                    // Prefix match: "some/dir" matches "some/dir/subdir"
-                    // Name match: "dirname" matches any directory with that name
-                    if rel_str == trimmed
-                        || rel_str.starts_with(&format!("{}/", trimmed))
-                        || (!trimmed.contains('/') && name == *trimmed)
-                    {
-                        return true;
-                    }
-                }
+                return Some(SkipReason::ExcludedDir);
+            }
+
+            // 3. Ignore rules; skipping the directory covers its contents
+            if ignore.matches(&rel_str, true) {
+                return Some(SkipReason::Ignored);
             }
         }
 
-        false
+        None
     }
 
     /// Backwards-compatible check for external callers (watcher.rs).
-    /// Uses only built-in exclusions, no .magectorignore patterns.
-    pub(crate) fn should_skip_dir(entry: &walkdir::DirEntry) -> bool {
+    /// Uses only built-in exclusions, no ignore rules.
+    pub(crate) fn should_skip_dir(entry: &walkdir::DirEntry, root: &Path, include_tests: bool) -> bool {
         if entry.file_type().is_dir() {
             let name = entry.file_name().to_string_lossy();
-            return EXCLUDE_DIRS.iter().any(|&d| name == *d);
-        }
-        false
-    }
-
-    /// Load .magectorignore file from the project root.
-    /// Returns a list of directory patterns to exclude.
-    ///
-    /// Format (one pattern per line, similar to .gitignore):
-    ///   - Lines starting with # are comments
-    ///   - Empty lines are ignored
-    ///   - Trailing slashes are stripped
-    ///   - Patterns without / match directory names anywhere
-    ///   - Patterns with / match relative paths from project root
-    fn load_ignore_file(root: &Path) -> Vec<String> {
-        let ignore_path = root.join(".magectorignore");
-        match fs::read_to_string(&ignore_path) {
-            Ok(content) => {
-                let patterns: Vec<String> = content
-                    .lines()
-                    .map(|line| line.trim())
-                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
-                    .map(|line| line.trim_end_matches('/').to_string())
-                    .collect();
-                if !patterns.is_empty() {
-                    tracing::info!(
-                        "Loaded {} patterns from .magectorignore",
-                        patterns.len()
-                    );
+            if include_tests {
+                if let Some(skip) = test_dir_verdict(&relative_path(root, entry.path()), &name) {
+                    return skip;
                 }
-                patterns
             }
-            Err(_) => Vec::new(),
+            return EXCLUDE_DIRS.iter().any(|&d| name == *d);
         }
+        false
     }
 
     /// Parse a single file (no embedding, can be parallelized with thread-local AST)
@@ -729,20 +2250,32 @@ impl Indexer {
         path: &Path,
         magento_root: &Path,
         xml_analyzer: &XmlAnalyzer,
-        ast_php: bool,
-        ast_js: bool,
+        redactor: &crate::redact::Redactor,
+        config_xref: &ConfigXref,
+        ast: AstAvailability,
+        method_chunks: bool,
     ) -> Result<Option<Vec<ParsedFile>>> {
-        let content = fs::read_to_string(path).context("Failed to read file")?;
+        let AstAvailability { php: ast_php, js: ast_js } = ast;
+        let SourceText { text: mut content, lossy } = read_source(path).context("Failed to read file")?;
+        if lossy {
+            tracing::debug!("Decoded {:?} lossily (not UTF-8)", path);
+        }
+        // Secrets never reach the AST, the embedding or the stored text
+        if let std::borrow::Cow::Owned(redacted) = redactor.redact(&content) {
+            content = redacted;
+        }
 
         if content.is_empty() {
             return Ok(None);
         }
 
-        let relative_path = path
-            .strip_prefix(magento_root)
-            .unwrap_or(path)
-            .to_string_lossy()
-            .to_string();
+        let relative_path = relative_path(magento_root, path);
+        if crate::docs::is_doc_path(&relative_path) {
+            return Ok(Some(Self::parse_doc(path, relative_path, &content, lossy)));
+        }
+        if crate::patches::is_patch_path(&relative_path) {
+            return Ok(Some(vec![Self::parse_patch(path, relative_path, &content, lossy)]));
+        }
 
         let ext = path
             .extension()
@@ -758,12 +2291,12 @@ impl Indexer {
             _ => "other",
         };
 
-        let magento_type = detect_file_type(&relative_path);
+        let mut magento_type = detect_file_type(&relative_path);
         let module_info = extract_module_info(&relative_path);
         let area = detect_area(&relative_path);
 
         // Parse with thread-local AST analyzers (no mutex contention)
-        let (php_ast, js_ast, xml_meta) = match ext {
+        let (php_ast, mut js_ast, xml_meta) = match ext {
             "php" | "phtml" if ast_php => {
                 let php_meta = TL_PHP_ANALYZER.with(|cell| {
                     let mut opt = cell.borrow_mut();
@@ -781,6 +2314,16 @@ impl Indexer {
             "xml" => (None, None, Some(xml_analyzer.analyze(&content))),
             _ => (None, None, None),
         };
+        // Inline <script> blocks and require() calls in templates
+        if ext == "phtml" && ast_js {
+            let inline_js = crate::mage_init::inline_scripts(&content);
+            if !inline_js.is_empty() {
+                js_ast = TL_JS_ANALYZER.with(|cell| {
+                    let mut opt = cell.borrow_mut();
+                    opt.as_mut().map(|analyzer| analyzer.analyze(&inline_js))
+                });
+            }
+        }
 
         // Analyze Setup scripts and inline SQL in PHP files
         let mut extra_search_terms = String::new();
@@ -803,22 +2346,225 @@ impl Indexer {
                 let setup_analyzer = SetupAnalyzer::new();
                 let setup_meta = setup_analyzer.analyze(&content);
 
-                for table in &setup_meta.tables_created {
-                    extra_search_terms.push_str(&format!(
-                        " create_table {} table_created {} legacy_schema {} declarative {}",
-                        table, table, table, table.replace('_', " ")
-                    ));
-                }
-                for trigger in &setup_meta.triggers {
-                    extra_search_terms.push_str(&format!(
-                        " db_trigger {} trigger {} create_trigger {} {} {} database_trigger sql_trigger",
-                        trigger.name, trigger.name, trigger.name, trigger.event, trigger.timing
-                    ));
-                }
-                for table in &setup_meta.table_references {
-                    if !setup_meta.tables_created.contains(table) {
-                        extra_search_terms.push_str(&format!(" table_reference {} {}", table, table.replace('_', " ")));
-                    }
+                for table in &setup_meta.tables_created {
+                    extra_search_terms.push_str(&format!(
+                        " create_table {} table_created {} legacy_schema {} declarative {}",
+                        table, table, table, table.replace('_', " ")
+                    ));
+                }
+                for trigger in &setup_meta.triggers {
+                    extra_search_terms.push_str(&format!(
+                        " db_trigger {} trigger {} create_trigger {} {} {} database_trigger sql_trigger",
+                        trigger.name, trigger.name, trigger.name, trigger.event, trigger.timing
+                    ));
+                }
+                for table in &setup_meta.table_references {
+                    if !setup_meta.tables_created.contains(table) {
+                        extra_search_terms.push_str(&format!(" table_reference {} {}", table, table.replace('_', " ")));
+                    }
+                }
+            }
+        }
+
+        // Controllers: the URLs reaching them
+        let is_action = php_ast.as_ref().map_or(content.contains("function execute"), |php| php.is_controller);
+        let urls = if ext == "php" && is_action { config_xref.routes.controller_urls(&relative_path) } else { Vec::new() };
+        for url in &urls {
+            extra_search_terms.push_str(&format!(" route url {} {}", url, url.replace('/', " ")));
+        }
+
+        // Admin controllers: the menu entries that open them
+        if ext == "php" {
+            for item in config_xref.menu.items_for_controller(&relative_path) {
+                extra_search_terms.push_str(&format!(
+                    " admin menu {} menu_item {} {} {}",
+                    item.title, item.title, item.id, item.resource
+                ));
+            }
+        }
+
+        // Widget blocks and templates: the CMS widget types using them
+        let php_class = php_ast.as_ref().and_then(|php| {
+            let class = php.class_name.as_ref()?;
+            Some(match php.namespace {
+                Some(ref ns) => format!("{}\\{}", ns, class),
+                None => class.clone(),
+            })
+        });
+        let widgets = match (ext, &php_class) {
+            ("php", Some(class)) => config_xref.widgets.widgets_for_class(class),
+            ("phtml", _) => config_xref.widgets.widgets_for_template(&relative_path),
+            _ => &[],
+        };
+        for widget in widgets {
+            extra_search_terms.push_str(&format!(
+                " cms widget custom widget type {} {} widget_id {}",
+                widget.label, widget.id, widget.id
+            ));
+        }
+
+        // Payment method models, facades and checkout renderers: the
+        // methods they implement. Models are typed so payment queries rank them first.
+        let payment_methods = match (ext, &php_class) {
+            ("php", Some(class)) => config_xref.payments.methods_for_class(class),
+            ("js", _) => config_xref.payments.methods_for_renderer(&relative_path),
+            ("xml", _) if relative_path.ends_with("di.xml") => config_xref.payments.methods_for_di(&relative_path),
+            _ => Vec::new(),
+        };
+        for method in &payment_methods {
+            extra_search_terms.push_str(&format!(
+                " payment method {} {} payment_method {} implementation{}",
+                method.code,
+                method.title.as_deref().unwrap_or(""),
+                method.code,
+                if method.is_gateway == Some(true) { " gateway" } else { "" }
+            ));
+        }
+        if ext == "php" && !payment_methods.is_empty() {
+            magento_type = crate::magento::MagentoFileType::PaymentMethod;
+        }
+
+        // GraphQL resolvers: the schema fields they serve; schema files: their bindings
+        let graphql_fields: Vec<String> = match (ext, &php_class) {
+            ("php", Some(class)) => config_xref.graphql.fields_for_class(class).iter().map(|f| f.path()).collect(),
+            _ => Vec::new(),
+        };
+        for field in &graphql_fields {
+            extra_search_terms.push_str(&format!(" graphql_field {} {}", field, field.replace('.', " ")));
+        }
+        if ext == "graphqls" {
+            for field in crate::graphql::parse_schema(&content) {
+                extra_search_terms.push_str(&format!(" graphql field {} resolver {}", field.path(), field.resolver));
+            }
+        }
+
+        // Templates and JS components: what each template instantiates via
+        // x-magento-init / data-mage-init, and which templates use each component
+        let js_components = match ext {
+            "phtml" => crate::mage_init::init_components(&content),
+            _ => Vec::new(),
+        };
+        for component in &js_components {
+            extra_search_terms.push_str(&format!(
+                " js component {} x-magento-init {}",
+                component,
+                component.replace(['/', '-', '_'], " ")
+            ));
+        }
+        let component_users: Vec<&str> = match ext {
+            "js" => config_xref.js_components.templates_for_file(&relative_path),
+            _ => Vec::new(),
+        };
+        for template in &component_users {
+            extra_search_terms.push_str(&format!(" used by template {}", template));
+        }
+
+        // JS mixins: which modules a mixin wraps and which mixins wrap a
+        // module. The AST only sees the first target of a requirejs-config.js,
+        // so configs list every declaration.
+        let (mixins, mixin_targets) = match ext {
+            "js" => (
+                config_xref.js_components.mixins_for_file(&relative_path),
+                config_xref.js_components.mixin_targets_for_file(&relative_path),
+            ),
+            _ => (Vec::new(), Vec::new()),
+        };
+        for mixin in &mixins {
+            extra_search_terms.push_str(&format!(" modified_by_mixin {} mixin", mixin));
+        }
+        for target in &mixin_targets {
+            extra_search_terms.push_str(&format!(" mixin_of {} mixin extend {}", target, target.replace(['/', '-', '_'], " ")));
+        }
+        if relative_path.ends_with("requirejs-config.js") && js_ast.as_ref().is_some_and(|js| js.is_mixin) {
+            for (target, mixin, enabled) in crate::mage_init::requirejs_mixins(&content) {
+                extra_search_terms.push_str(&format!(
+                    " mixin {} of {}{}",
+                    mixin,
+                    target,
+                    if enabled { "" } else { " disabled" }
+                ));
+            }
+        }
+
+        // Carrier models: the carrier codes they collect rates for
+        let carriers = match (ext, &php_class) {
+            ("php", Some(class)) => config_xref.carriers.carriers_for_class(class),
+            _ => Vec::new(),
+        };
+        for carrier in &carriers {
+            extra_search_terms.push_str(&format!(
+                " shipping carrier {} {} {} shipping_carrier {} collectRates shipping rate",
+                carrier.code,
+                carrier.title.as_deref().unwrap_or(""),
+                carrier.name.as_deref().unwrap_or(""),
+                carrier.code
+            ));
+        }
+        if !carriers.is_empty() {
+            magento_type = crate::magento::MagentoFileType::ShippingCarrier;
+        }
+
+        // Indexer and mview action classes: the indexers they run for
+        let indexers = match php_class {
+            Some(ref class) if ext == "php" => config_xref.indexers.entries_for_class(class),
+            _ => Vec::new(),
+        };
+        for indexer in &indexers {
+            extra_search_terms.push_str(&format!(
+                " indexer {} {} indexer_id {} reindex mview {}",
+                indexer.title,
+                indexer.id,
+                indexer.id,
+                indexer.subscribed_tables.join(" ")
+            ));
+        }
+
+        // Console command classes registered in a di.xml: the `bin/magento`
+        // command they implement, recorded as "console_command name"
+        let command = match php_class {
+            Some(ref class) if ext == "php" && config_xref.console.is_registered(class) => {
+                crate::console::parse_definition(&content)
+            }
+            _ => None,
+        };
+        if let Some(ref command) = command {
+            extra_search_terms.push_str(&format!(
+                " console command bin/magento cli console_command {} {} {}",
+                command.name,
+                command.name.replace([':', '-', '_'], " "),
+                command.description.as_deref().unwrap_or("")
+            ));
+            for alias in &command.aliases {
+                extra_search_terms.push_str(&format!(" console_command {} ", alias));
+            }
+            magento_type = crate::magento::MagentoFileType::Console;
+        }
+
+        // UI component XML: data provider, collections, repositories and the
+        // controllers rendering it; provider classes: the components using them
+        let ui_chain = match ext {
+            "xml" => config_xref.ui_components.chain(&relative_path, config_xref),
+            _ => None,
+        };
+        if let Some(ref chain) = ui_chain {
+            extra_search_terms.push_str(&format!(" ui_component {} admin grid listing form", chain.name));
+            for provider in chain.data_provider.iter().chain(chain.provider_class.iter()) {
+                extra_search_terms.push_str(&format!(" ui_data_provider {} data provider", provider));
+            }
+            for collection in &chain.collections {
+                extra_search_terms.push_str(&format!(" ui_collection {} collection", collection));
+            }
+            for repository in &chain.repositories {
+                extra_search_terms.push_str(&format!(" ui_repository {} repository", repository));
+            }
+            for controller in &chain.controllers {
+                extra_search_terms.push_str(&format!(" ui_controller {} {} controller", controller.url, controller.class));
+            }
+        }
+        if let Some(ref class) = php_class {
+            if ext == "php" {
+                for name in config_xref.ui_components.components_for_provider(class) {
+                    extra_search_terms.push_str(&format!(" ui_component {} data provider", name));
                 }
             }
         }
@@ -835,18 +2581,53 @@ impl Indexer {
             search_text.push_str(&extra_search_terms);
         }
 
-        // Create embedding text (description injected later in index/index_files)
-        let embed_text = Self::create_embedding_text(
-            &content,
-            &relative_path,
-            php_ast.as_ref(),
-            js_ast.as_ref(),
-            &search_text,
-            None,
-        );
+        // Create embedding text (description injected later in index/index_files).
+        // Code and enrichment are cut to 8000 bytes together.
+        let mut code = Self::create_code_text(&content, xml_meta.as_ref());
+        let mut enrichment =
+            Self::create_enrichment_text(&relative_path, php_ast.as_ref(), js_ast.as_ref(), &search_text);
+        truncate_at_char_boundary(&mut code, MAX_EMBED_TEXT);
+        truncate_at_char_boundary(&mut enrichment, MAX_EMBED_TEXT - code.len());
+        // Served fields and component wiring go first, inside the model's token window
+        let mut lead = String::new();
+        if !graphql_fields.is_empty() {
+            lead = format!("GraphQL fields: {}\n\n{}", graphql_fields.join(", "), lead);
+        }
+        if !js_components.is_empty() {
+            lead = format!("JS components: {}\n\n{}", js_components.join(", "), lead);
+        }
+        if !component_users.is_empty() {
+            lead = format!("Used by templates: {}\n\n{}", component_users.join(", "), lead);
+        }
+        if !mixins.is_empty() {
+            lead = format!("Modified by mixins: {}\n\n{}", mixins.join(", "), lead);
+        }
+        if !mixin_targets.is_empty() {
+            lead = format!("Mixin of: {}\n\n{}", mixin_targets.join(", "), lead);
+        }
+        if !urls.is_empty() {
+            lead = format!("URL: {}\n\n{}", urls.join(", "), lead);
+        }
+        if let Some(ref chain) = ui_chain {
+            let mut parts = Vec::new();
+            if let Some(ref provider) = chain.provider_class {
+                parts.push(format!("data provider {}", provider));
+            }
+            if !chain.collections.is_empty() {
+                parts.push(format!("collections {}", chain.collections.join(", ")));
+            }
+            if !chain.repositories.is_empty() {
+                parts.push(format!("repositories {}", chain.repositories.join(", ")));
+            }
+            let urls: Vec<&str> = chain.controllers.iter().map(|c| c.url.as_str()).collect();
+            if !urls.is_empty() {
+                parts.push(format!("rendered at {}", urls.join(", ")));
+            }
+            lead = format!("UI component {}: {}\n\n{}", chain.name, parts.join("; "), lead);
+        }
 
         // Build metadata
-        let metadata = Self::build_metadata(
+        let mut metadata = Self::build_metadata(
             relative_path,
             file_type,
             magento_type,
@@ -856,8 +2637,138 @@ impl Indexer {
             js_ast,
             search_text,
         );
+        metadata.span = Some(SourceSpan::whole(&content));
+        metadata.is_generated = is_generated_code(&metadata.path, metadata.class_name.as_deref(), &content);
+        metadata.metrics = Some(FileMetrics::measure(&content, lossy, std::fs::metadata(path).ok().as_ref()));
+        metadata.urls = urls;
+
+        let file = ParsedFile { lead, code, enrichment, metadata, lossy };
+        if method_chunks && ext == "php" && ast_php {
+            return Ok(Some(Self::split_methods(file, &content)));
+        }
+        Ok(Some(vec![file]))
+    }
+
+    /// `file` as one item for the code outside methods (declarations,
+    /// constants, properties), keeping the file's lead and enrichment, and
+    /// one item per method with its doc comment. Files without methods stay
+    /// whole.
+    fn split_methods(file: ParsedFile, content: &str) -> Vec<ParsedFile> {
+        let spans = TL_PHP_ANALYZER
+            .with(|cell| cell.borrow_mut().as_mut().map(|analyzer| analyzer.member_spans(content)))
+            .unwrap_or_default();
+        if spans.is_empty() {
+            return vec![file];
+        }
+
+        let mut outside = String::new();
+        let mut at = 0;
+        let mut methods = Vec::with_capacity(spans.len());
+        for span in spans {
+            // The doc comment right above a method describes it
+            let before = content[at..span.start_byte].trim_end();
+            let start = match before.rfind("/**") {
+                Some(doc) if before.ends_with("*/") => at + doc,
+                _ => span.start_byte,
+            };
+            outside.push_str(&content[at..start]);
+            at = span.end_byte;
+
+            let name = span.name.rsplit("::").next().unwrap_or(&span.name).to_string();
+            let mut code = content[start..span.end_byte].to_string();
+            truncate_at_char_boundary(&mut code, MAX_EMBED_TEXT);
+            let mut metadata = file.metadata.clone();
+            metadata.search_text = format!("method {} {}", name, span.name.replace("::", " "));
+            metadata.method_name = Some(name.clone());
+            metadata.methods = vec![name.clone()];
+            metadata.constants = Vec::new();
+            metadata.traits = Vec::new();
+            metadata.span = Some(SourceSpan::of_range(content, start, span.end_byte));
+            methods.push(ParsedFile {
+                lead: format!("Method {} in {}\n\n", span.name, metadata.path),
+                code,
+                enrichment: format!(" method {} {}", name, metadata.path.replace(['/', '_', '-', '.'], " ")),
+                metadata,
+                lossy: file.lossy,
+            });
+        }
+        outside.push_str(&content[at..]);
+
+        let mut code = outside;
+        truncate_at_char_boundary(&mut code, MAX_EMBED_TEXT);
+        let mut enrichment = file.enrichment;
+        truncate_at_char_boundary(&mut enrichment, MAX_EMBED_TEXT - code.len());
+        let mut metadata = file.metadata;
+        metadata.method_name = None;
+        let mut items = vec![ParsedFile { lead: file.lead, code, enrichment, metadata, lossy: file.lossy }];
+        items.extend(methods);
+        items
+    }
+
+    /// One `doc` item in the `docs` collection per section of a Markdown
+    /// file, with its heading breadcrumbs
+    fn parse_doc(path: &Path, relative_path: String, content: &str, lossy: bool) -> Vec<ParsedFile> {
+        let module_info = extract_module_info(&relative_path);
+        let area = detect_area(&relative_path);
+        let file_meta = std::fs::metadata(path).ok();
+        let mut path_words = relative_path.replace(['/', '_', '-', '.'], " ");
+        // A README describes its project or module as a whole
+        let file_name = relative_path.rsplit('/').next().unwrap_or_default();
+        if file_name.to_ascii_lowercase().starts_with("readme.") {
+            path_words.push_str(" readme project overview setup");
+        }
+        crate::docs::sections(content)
+            .into_iter()
+            .filter_map(|section| {
+                let text = content[section.start..section.end].trim();
+                if text.is_empty() {
+                    return None;
+                }
+                let title = section.title();
+                let lead = format!("Documentation: {}\n{}\n\n", relative_path, title);
+                let search_text = format!("documentation docs {} {} {}", path_words, title, text);
+                let mut metadata = Self::build_metadata(
+                    relative_path.clone(),
+                    "doc",
+                    crate::magento::MagentoFileType::Documentation,
+                    module_info.clone(),
+                    area.clone(),
+                    None,
+                    None,
+                    search_text,
+                );
+                metadata.collection = Collection::Docs;
+                metadata.headings = section.headings;
+                metadata.span = Some(SourceSpan::of_range(content, section.start, section.end));
+                metadata.metrics = Some(FileMetrics::measure(content, lossy, file_meta.as_ref()));
+                Some(ParsedFile { lead, code: text.to_string(), enrichment: String::new(), metadata, lossy })
+            })
+            .collect()
+    }
 
-        Ok(Some(vec![ParsedFile { embed_text, metadata }]))
+    /// One `patch` item per patch file, naming the files it modifies
+    fn parse_patch(path: &Path, relative_path: String, content: &str, lossy: bool) -> ParsedFile {
+        let files = crate::patches::modified_files(content);
+        let module_info = files.first().and_then(|f| extract_module_info(f)).or_else(|| extract_module_info(&relative_path));
+        let lead = format!("Patch: {}\nModifies: {}\n\n", relative_path, files.join(", "));
+        let search_text = format!(
+            "patch diff fix hotfix {} modifies {} {}",
+            relative_path.replace(['/', '_', '-', '.'], " "),
+            files.join(" ").replace(['/', '_', '-', '.'], " "),
+            content
+        );
+        let mut metadata = Self::build_metadata(
+            relative_path,
+            "patch",
+            crate::magento::MagentoFileType::Patch,
+            module_info,
+            None,
+            None,
+            None,
+            search_text,
+        );
+        metadata.metrics = Some(FileMetrics::measure(content, lossy, std::fs::metadata(path).ok().as_ref()));
+        ParsedFile { lead, code: content.to_string(), enrichment: String::new(), metadata, lossy }
     }
 
     fn generate_search_text_from_ast(
@@ -883,6 +2794,20 @@ impl Indexer {
                 terms.push(method.name.clone());
                 terms.push(split_camel_case(&method.name));
             }
+            for name in &php.traits {
+                terms.push(name.rsplit('\\').next().unwrap_or(name).to_string());
+            }
+            for class in &php.anonymous_classes {
+                let types = class.extends.iter().chain(&class.implements);
+                let types: Vec<&str> = types.map(|t| t.rsplit('\\').next().unwrap_or(t)).collect();
+                terms.push(format!("anonymous class {} {} {}", class.name, types.join(" "), class.methods.join(" ")));
+            }
+            for closure in &php.closures {
+                terms.push(format!("closure {} {}", closure.name.trim_start_matches('$'), closure.parameters));
+            }
+            for constant in php.constants.iter().filter(|c| c.is_notable()) {
+                terms.push(format!("{} {}", constant.name, constant.value.as_deref().unwrap_or_default()));
+            }
             if php.is_controller {
                 // Add strong controller signals
                 terms.push("controller action execute http request response".to_string());
@@ -991,14 +2916,49 @@ impl Indexer {
                     terms.push(format!("disabled plugin {}", plugin.name));
                 }
             }
+            for vt in &xml.virtual_types {
+                terms.push(format!("virtualType {} {}", vt.name, vt.base_type));
+            }
+            for arg in &xml.arguments {
+                if arg.is_proxy() {
+                    terms.push(format!("proxy {} {}", arg.name, arg.value));
+                } else if arg.xsi_type == "object" {
+                    terms.push(arg.value.clone());
+                }
+                if arg.shared == Some(false) {
+                    terms.push(format!("non-shared {}", arg.name));
+                }
+                terms.extend(arg.items.iter().cloned());
+            }
             for event in &xml.events {
                 terms.push(event.clone());
             }
+            for widget in &xml.widgets {
+                terms.push(format!("widget {} {} {}", widget.id, widget.label, widget.class));
+                terms.push(split_camel_case(widget.class.rsplit('\\').next().unwrap_or(&widget.class)));
+                terms.extend(widget.parameters.iter().cloned());
+                terms.extend(widget.templates.iter().cloned());
+            }
+            for (kind, name) in &xml.mftf_entities {
+                terms.push(format!("mftf {} {} {}", kind, name, split_camel_case(name)));
+            }
+            for item in &xml.menu_items {
+                terms.push(format!("menu_item {} {} {}", item.title, item.id, item.resource));
+                // The controller the item opens: Controller/Adminhtml/<Controller>/<Action>
+                if let Some(key) = item.controller_action() {
+                    let (controller, action) = key.split_once('/').unwrap_or((&key, "index"));
+                    terms.push(format!("admin controller {} {}", controller.replace('_', " "), action));
+                }
+            }
+        }
+
+        if is_mftf_path(path) {
+            terms.push("mftf functional test acceptance test qa".to_string());
         }
 
         // XML file-specific enrichment
         if path.ends_with(".xml") {
-            let filename = path.split('/').last().unwrap_or("");
+            let filename = path.split('/').next_back().unwrap_or("");
 
             // Add filename multiple times for weight
             terms.push(filename.to_string());
@@ -1043,18 +3003,33 @@ impl Indexer {
                 _ if filename == "widget.xml" => {
                     terms.push("widget.xml cms widget parameter".to_string());
                 }
+                "communication.xml" | "queue_topology.xml" | "queue_consumer.xml" | "queue_publisher.xml" | "queue.xml" => {
+                    terms.push("message queue async topic consumer handler exchange binding publisher".to_string());
+                }
                 _ if filename == "crontab.xml" => {
                     terms.push("crontab.xml cron job schedule".to_string());
                 }
                 _ if filename == "email_templates.xml" => {
                     terms.push("email_templates.xml email template transactional".to_string());
+                    terms.push("order confirmation invoice shipment creditmemo email".to_string());
+                }
+                "sales.xml" => {
+                    terms.push("sales.xml totals collector quote order invoice creditmemo".to_string());
+                    terms.push("total collector grand_total subtotal sort_order".to_string());
+                }
+                "fieldset.xml" => {
+                    terms.push("fieldset.xml fieldset copy field aspect convert".to_string());
+                    terms.push("quote to order conversion copyFieldsetToTarget".to_string());
+                }
+                "pdf.xml" => {
+                    terms.push("pdf.xml pdf invoice shipment creditmemo print renderer totals".to_string());
                 }
                 _ => {}
             }
 
             // Extract root element from XML content
             if let Some(root_start) = content.find('<') {
-                if let Some(root_end) = content[root_start..].find(|c| c == ' ' || c == '>' || c == '/') {
+                if let Some(root_end) = content[root_start..].find([' ', '>', '/']) {
                     let root_tag = &content[root_start + 1..root_start + root_end];
                     if !root_tag.starts_with('?') && !root_tag.starts_with('!') {
                         terms.push(format!("xml {} configuration", root_tag));
@@ -1077,25 +3052,78 @@ impl Indexer {
         terms.join(" ")
     }
 
-    /// Create embedding text with enrichments
-    fn create_embedding_text(
-        content: &str,
-        path: &str,
-        php_ast: Option<&PhpAstMetadata>,
-        js_ast: Option<&JsAstMetadata>,
-        search_text: &str,
-        description: Option<&str>,
-    ) -> String {
+    /// Texts of `items` with their vectors and, with `meta_vectors`, their
+    /// metadata vectors
+    fn embed_parsed(
+        embedder: &mut Embedder,
+        items: &[&ParsedFile],
+        meta_vectors: bool,
+    ) -> Result<(Vec<EmbedText>, Embeddings)> {
+        let texts: Vec<EmbedText> = items.iter().map(|p| p.embed_texts(meta_vectors)).collect();
+        let embeddings = crate::migrate::embed_texts(embedder, &texts)?;
+        Ok((texts, embeddings))
+    }
+
+    /// Code part of the embedded text: XML summaries, then the content.
+    /// LLM descriptions are prepended later (index/index_files) so they
+    /// land within the 256-token ONNX window before raw content is cut off.
+    fn create_code_text(content: &str, xml_meta: Option<&crate::magento::XmlMetadata>) -> String {
         let mut text = String::with_capacity(content.len() + 2000);
 
-        // Prepend LLM description if available — places semantic terms within
-        // the 256-token ONNX window before raw content gets truncated
-        if let Some(desc) = description {
-            text.push_str("Description: ");
-            text.push_str(desc);
+        // Virtual type names go before the content so they land inside the
+        // model's token window even for long di.xml files
+        if let Some(xml) = xml_meta.filter(|x| !x.virtual_types.is_empty()) {
+            let names: Vec<String> = xml
+                .virtual_types
+                .iter()
+                .map(|vt| format!("{} ({})", vt.name, vt.base_type))
+                .collect();
+            text.push_str("Virtual types: ");
+            text.push_str(&names.join(", "));
             text.push_str("\n\n");
         }
 
+        // Same for indexer.xml / mview.xml: which indexer, which tables
+        if let Some(xml) = xml_meta.filter(|x| !x.indexers.is_empty() || !x.mviews.is_empty()) {
+            for indexer in &xml.indexers {
+                text.push_str(&format!("Indexer: {} ({}) {}\n", indexer.id, indexer.title, indexer.class));
+            }
+            for view in &xml.mviews {
+                let tables: Vec<&str> = view.subscriptions.iter().map(|(t, _)| t.as_str()).collect();
+                text.push_str(&format!("Mview: {} {} subscribed to {}\n", view.id, view.class, tables.join(", ")));
+            }
+            text.push('\n');
+        }
+
+        // Sales config: collectors in sort order, copy rules by fieldset
+        if let Some(xml) = xml_meta {
+            let mut collectors: Vec<_> = xml.total_collectors.iter().collect();
+            collectors.sort_by_key(|c| (c.section.clone(), c.sort_order.unwrap_or(i64::MAX)));
+            for c in collectors {
+                text.push_str(&format!(
+                    "Total collector: {} {} (sort_order {}) {}\n",
+                    c.section,
+                    c.code,
+                    c.sort_order.map_or_else(|| "-".to_string(), |o| o.to_string()),
+                    c.class
+                ));
+            }
+            let mut fieldsets: Vec<(&str, Vec<String>)> = Vec::new();
+            for rule in &xml.fieldset_rules {
+                let copy = format!("{}→{}", rule.field, rule.aspect);
+                match fieldsets.iter_mut().find(|(id, _)| *id == rule.fieldset) {
+                    Some((_, copies)) => copies.push(copy),
+                    None => fieldsets.push((&rule.fieldset, vec![copy])),
+                }
+            }
+            for (id, copies) in fieldsets {
+                text.push_str(&format!("Fieldset {} copies: {}\n", id, copies.join(", ")));
+            }
+            if !xml.total_collectors.is_empty() || !xml.fieldset_rules.is_empty() {
+                text.push('\n');
+            }
+        }
+
         // Add code content (truncated at char boundary)
         let content_limit = 6000;
         if content.len() > content_limit {
@@ -1108,6 +3136,18 @@ impl Indexer {
         } else {
             text.push_str(content);
         }
+        text
+    }
+
+    /// Enrichment terms embedded after the code: names, type signals, path
+    /// parts and the search text
+    fn create_enrichment_text(
+        path: &str,
+        php_ast: Option<&PhpAstMetadata>,
+        js_ast: Option<&JsAstMetadata>,
+        search_text: &str,
+    ) -> String {
+        let mut text = String::with_capacity(search_text.len() + 500);
 
         // PHP enrichment
         if let Some(php) = php_ast {
@@ -1117,16 +3157,29 @@ impl Indexer {
             if let Some(ref ns) = php.namespace {
                 text.push_str(&format!(" namespace {}", ns.replace('\\', " ")));
             }
+            // Parents are fully qualified; the short name is what queries use
             if let Some(ref ext) = php.extends {
-                text.push_str(&format!(" extends {}", ext));
+                text.push_str(&format!(" extends {}", ext.rsplit('\\').next().unwrap_or(ext)));
             }
             for impl_name in &php.implements {
-                text.push_str(&format!(" implements {}", impl_name));
+                text.push_str(&format!(" implements {}", impl_name.rsplit('\\').next().unwrap_or(impl_name)));
             }
             // Add method names with emphasis
             for method in &php.methods {
                 text.push_str(&format!(" method {}", method.name));
             }
+            for class in &php.anonymous_classes {
+                text.push_str(&format!(" anonymous class {}", class.name));
+                for name in class.extends.iter().chain(&class.implements) {
+                    text.push_str(&format!(" {}", name.rsplit('\\').next().unwrap_or(name)));
+                }
+            }
+            for closure in &php.closures {
+                text.push_str(&format!(" closure {}", closure.name.trim_start_matches('$')));
+            }
+            for constant in php.constants.iter().filter(|c| c.is_notable()) {
+                text.push_str(&format!(" const {} {}", constant.name, constant.value.as_deref().unwrap_or_default()));
+            }
             // Add type signals for better semantic matching
             if php.is_helper {
                 text.push_str(" helper helper helper utility data");
@@ -1164,19 +3217,10 @@ impl Indexer {
 
         // Add search text
         text.push_str(&format!(" {}", search_text));
-
-        // Truncate if too long (at char boundary)
-        if text.len() > 8000 {
-            let mut end = 8000;
-            while end > 0 && !text.is_char_boundary(end) {
-                end -= 1;
-            }
-            text.truncate(end);
-        }
-
         text
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn build_metadata(
         path: String,
         file_type: &str,
@@ -1194,6 +3238,8 @@ impl Indexer {
         let path_is_controller = path_lower.contains("/controller/");
         let path_is_observer = path_lower.contains("/observer/");
         let path_is_block = path_lower.contains("/block/");
+        let constants = php_ast.as_ref().map_or_else(Vec::new, |php| php.constants.clone());
+        let traits = php_ast.as_ref().map_or_else(Vec::new, |php| php.traits.clone());
 
         let (
             class_name,
@@ -1271,6 +3317,11 @@ impl Indexer {
             is_mixin,
             js_dependencies,
             search_text,
+            constants,
+            traits,
+            // Spans, summaries and the generated flag are set by the caller;
+            // links are resolved once all files are indexed
+            ..Default::default()
         }
     }
 
@@ -1278,15 +3329,33 @@ impl Indexer {
     /// Returns a list of (relative_path, vector_ids) for manifest tracking.
     pub fn index_files(&mut self, files: &[PathBuf]) -> Result<Vec<(String, Vec<usize>)>> {
         let magento_root = self.magento_root.clone();
+        let mut config_changed = true;
+        if self.config_xref.is_none() {
+            self.config_xref()?;
+        } else {
+            let changed: Vec<&PathBuf> =
+                files.iter().filter(|f| ConfigXref::is_source(&relative_path(&magento_root, f))).collect();
+            config_changed = !changed.is_empty();
+            if config_changed {
+                self.config_sources.extend(changed.into_iter().cloned());
+                self.rebuild_config_xref();
+            }
+        }
         let xml_analyzer = &self.xml_analyzer;
-        let ast_php = self.ast_available.php;
-        let ast_js = self.ast_available.js;
+        let redactor = &self.redactor;
+        let config_xref = self.config_xref.as_ref().expect("config xref scanned above");
+        let ast = self.ast_available;
+        let method_chunks = self.method_chunks;
+
+        // Files outside a partial index's module subset, or owned by the
+        // core index, are ignored
+        let files: Vec<&PathBuf> = files.iter().filter(|f| self.indexes_locally(f)).collect();
 
         // Parse files in parallel
         let mut parsed_results: Vec<_> = files
             .par_iter()
             .filter_map(|file_path| {
-                match Self::parse_file(file_path, &magento_root, xml_analyzer, ast_php, ast_js) {
+                match Self::parse_file(file_path, &magento_root, xml_analyzer, redactor, config_xref, ast, method_chunks) {
                     Ok(Some(items)) => Some(items),
                     _ => None,
                 }
@@ -1298,6 +3367,26 @@ impl Indexer {
             return Ok(Vec::new());
         }
 
+        if self.summaries {
+            for item in &mut parsed_results {
+                item.metadata.summary = Some(crate::summary::summarize(&item.metadata));
+            }
+        }
+        if self.git_times {
+            let paths: HashSet<&str> = parsed_results.iter().map(|item| item.metadata.path.as_str()).collect();
+            let times = self.commit_times(&paths.into_iter().collect::<Vec<_>>());
+            for item in &mut parsed_results {
+                item.metadata.last_commit = times.get(&item.metadata.path).copied();
+            }
+        }
+        if self.owners {
+            let paths: HashSet<&str> = parsed_results.iter().map(|item| item.metadata.path.as_str()).collect();
+            let owners = self.owners_of(&paths.into_iter().collect::<Vec<_>>());
+            for item in &mut parsed_results {
+                item.metadata.owners = owners.get(&item.metadata.path).cloned().unwrap_or_default();
+            }
+        }
+
         // Inject LLM descriptions into embedding text
         if let Some(ref desc_db_path) = self.descriptions_db {
             if desc_db_path.exists() {
@@ -1305,36 +3394,295 @@ impl Indexer {
                     for item in &mut parsed_results {
                         if let Some(desc) = desc_db.get(&item.metadata.path) {
                             let prefix = format!("Description: {}\n\n", desc.description);
-                            item.embed_text.insert_str(0, &prefix);
+                            item.lead.insert_str(0, &prefix);
                         }
                     }
                 }
             }
         }
 
-        // Embed and insert
-        let mut result = Vec::new();
+        // Embed and insert under stable IDs (path + chunk index)
+        let mut result: Vec<(String, Vec<usize>)> = Vec::new();
+        let mut chunk_of: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
         for chunk in parsed_results.chunks(self.batch_size) {
-            let texts: Vec<&str> = chunk.iter().map(|p| p.embed_text.as_str()).collect();
-            let embeddings = self.embedder.embed_batch(&texts)?;
+            let parsed: Vec<&ParsedFile> = chunk.iter().collect();
+            let (texts, (embeddings, meta_embeddings)) = Self::embed_parsed(&mut self.embedder, &parsed, self.meta_vectors)?;
+
+            let items: Vec<(usize, Vec<f32>, IndexMetadata)> = embeddings
+                .into_iter()
+                .zip(chunk.iter())
+                .map(|(emb, parsed)| {
+                    let n = chunk_of.entry(parsed.metadata.path.clone()).or_insert(0);
+                    let id = stable_id(&parsed.metadata.path, *n);
+                    *n += 1;
+                    (id, emb, parsed.metadata.clone())
+                })
+                .collect();
+            let paths: Vec<String> = items.iter().map(|(_, _, m)| m.path.clone()).collect();
+            let ids = self.vectordb.insert_batch_with_ids(items);
+            self.vectordb.insert_meta_vectors(ids.iter().copied().zip(meta_embeddings).collect());
+            for (&id, text) in ids.iter().zip(texts) {
+                self.texts.insert(id, text);
+            }
 
-            for (emb, parsed) in embeddings.into_iter().zip(chunk.iter()) {
-                let path = parsed.metadata.path.clone();
-                let id = self.vectordb.insert(&emb, parsed.metadata.clone());
+            for (path, id) in paths.into_iter().zip(ids) {
                 // Group by path
-                if let Some(entry) = result.iter_mut().find(|(p, _): &&mut (String, Vec<usize>)| p == &path) {
+                if let Some(entry) = result.iter_mut().find(|(p, _)| p == &path) {
                     entry.1.push(id);
                 } else {
                     result.push((path, vec![id]));
                 }
             }
         }
+        self.link_overrides();
+        // Only the changed files and the classes depending on them can get
+        // other links; new di.xml preferences may link any interface
+        self.unlinked_paths.extend(result.iter().map(|(path, _)| path.clone()));
+        let scope = self.link_scope();
+        self.link_traits(Some(&scope));
+        self.link_inheritance(Some(&scope));
+        self.link_implementations(if config_changed { None } else { Some(&scope) });
+        self.tag_roots();
+        self.refresh_stop_terms();
+        self.reindexed_files += files.len() as u64;
 
         Ok(result)
     }
 
+    /// Keep SONA from learning the terms that are common across the index
+    /// as it is now; called whenever indexing changes the term statistics
+    fn refresh_stop_terms(&mut self) {
+        if let Some(ref mut sona) = self.sona {
+            sona.set_stop_terms(self.vectordb.term_stats().stop_terms());
+        }
+    }
+
+    /// Link `app/code` copies of vendor classes to the file they override
+    /// ([`crate::overrides::find_overrides`]), including vendor classes in
+    /// the core index. Returns how many files override one and whether any
+    /// link changed.
+    fn link_overrides(&mut self) -> (usize, bool) {
+        let overrides = {
+            let core = self.core.iter().flat_map(|c| c.db.metadata_iter().map(|(_, meta)| meta));
+            crate::overrides::find_overrides(self.vectordb.metadata_iter().map(|(_, meta)| meta).chain(core))
+        };
+        let mut changed = false;
+        self.vectordb.update_metadata(|meta| {
+            let link = overrides.get(&meta.path);
+            if meta.overrides.as_ref() != link {
+                meta.overrides = link.cloned();
+                changed = true;
+            }
+        });
+        (overrides.len(), changed)
+    }
+
+    /// Files whose links a change to the unlinked files can alter: those
+    /// files, the classes using as a trait or extending a class they declare
+    /// or declared, and so on down, and the interfaces di.xml prefers one of
+    /// those classes for. Clears the unlinked files.
+    fn link_scope(&mut self) -> HashSet<String> {
+        let mut scope = std::mem::take(&mut self.unlinked_paths);
+        let mut changed = std::mem::take(&mut self.unlinked_classes);
+        for path in &scope {
+            changed.extend(self.vectordb.items_of(path).filter_map(|(_, meta)| crate::overrides::class_key(meta)));
+        }
+        let preferred = match self.config_xref {
+            Some(ref xref) => crate::implementations::preferred(&xref.di),
+            None => HashMap::new(),
+        };
+        // Class key → file and key of each class using or extending it
+        let mut dependents: HashMap<String, Vec<(&str, String)>> = HashMap::new();
+        for (_, meta) in self.vectordb.metadata_iter() {
+            let Some(key) = crate::overrides::class_key(meta) else { continue };
+            if meta.is_api_interface && preferred.get(&key).is_some_and(|class| changed.contains(class)) {
+                scope.insert(meta.path.clone());
+            }
+            let parent = meta.extends.as_deref().map(|p| p.trim_start_matches('\\').to_lowercase());
+            for used in meta.traits.iter().map(|t| t.to_lowercase()).chain(parent) {
+                dependents.entry(used).or_default().push((meta.path.as_str(), key.clone()));
+            }
+        }
+        let mut pending: Vec<String> = changed.into_iter().collect();
+        while let Some(class) = pending.pop() {
+            for (path, key) in dependents.remove(&class).unwrap_or_default() {
+                if scope.insert(path.to_string()) {
+                    pending.push(key);
+                }
+            }
+        }
+        scope
+    }
+
+    /// [`VectorDB::update_metadata`] over the files of `scope`, or all files
+    fn update_links(&mut self, scope: Option<&HashSet<String>>, f: impl FnMut(&mut IndexMetadata)) {
+        match scope {
+            Some(paths) => self.vectordb.update_metadata_of(paths, f),
+            None => self.vectordb.update_metadata(f),
+        }
+    }
+
+    /// Set [`IndexMetadata::trait_methods`] of every class, or those of the
+    /// files in `scope`, from the indexed traits it uses. Returns how many
+    /// files gain methods and whether any item changed.
+    fn link_traits(&mut self, scope: Option<&HashSet<String>>) -> (usize, bool) {
+        let resolved = {
+            let core = self.core.iter().flat_map(|c| c.db.metadata_iter().map(|(_, meta)| meta));
+            crate::traits::trait_methods(self.vectordb.metadata_iter().map(|(_, meta)| meta).chain(core), scope)
+        };
+        let mut changed = false;
+        self.update_links(scope, |meta| {
+            let methods = if meta.traits.is_empty() { None } else { resolved.get(&meta.path) };
+            let methods = methods.cloned().unwrap_or_default();
+            if meta.trait_methods != methods {
+                meta.trait_methods = methods;
+                changed = true;
+            }
+        });
+        (resolved.len(), changed)
+    }
+
+    /// Set [`IndexMetadata::inherited_types`] and the matching flags of every
+    /// class, or those of the files in `scope`, from its indexed ancestors.
+    /// Returns how many files inherit a type and whether any item changed.
+    fn link_inheritance(&mut self, scope: Option<&HashSet<String>>) -> (usize, bool) {
+        let resolved = {
+            let core = self.core.iter().flat_map(|c| c.db.metadata_iter().map(|(_, meta)| meta));
+            crate::inheritance::inherited_types(self.vectordb.metadata_iter().map(|(_, meta)| meta).chain(core), scope)
+        };
+        let mut changed = false;
+        self.update_links(scope, |meta| {
+            let types = resolved.get(&meta.path).cloned().unwrap_or_default();
+            changed |= crate::inheritance::apply(meta, types);
+        });
+        (resolved.len(), changed)
+    }
+
+    /// Set [`IndexMetadata::implementation`] of every API interface, or
+    /// those of the files in `scope`, from the di.xml preferences. Returns
+    /// how many interfaces have one and whether any item changed. Without
+    /// parsed config nothing changes.
+    fn link_implementations(&mut self, scope: Option<&HashSet<String>>) -> (usize, bool) {
+        let Some(ref xref) = self.config_xref else { return (0, false) };
+        let resolved = {
+            let core = self.core.iter().flat_map(|c| c.db.metadata_iter().map(|(_, meta)| meta));
+            let items = self.vectordb.metadata_iter().map(|(_, meta)| meta).chain(core);
+            crate::implementations::implementations(items, &xref.di, scope)
+        };
+        let mut changed = false;
+        self.update_links(scope, |meta| {
+            let implementation = resolved.get(&meta.path);
+            if meta.implementation.as_ref() != implementation {
+                meta.implementation = implementation.cloned();
+                changed = true;
+            }
+        });
+        (resolved.len(), changed)
+    }
+
+    /// Tag each item with the extra root its file comes from. Returns the
+    /// number of tagged items and whether any tag changed.
+    fn tag_roots(&mut self) -> (usize, bool) {
+        let roots = &self.extra_roots;
+        let (mut tagged, mut changed) = (0, false);
+        self.vectordb.update_metadata(|meta| {
+            let root = roots.iter().find(|r| r.contains(&meta.path)).map(|r| &r.name);
+            tagged += usize::from(root.is_some());
+            if meta.root.as_ref() != root {
+                meta.root = root.cloned();
+                changed = true;
+            }
+        });
+        (tagged, changed)
+    }
+
+    /// Item of `path` to group with an API interface result: the overlay's
+    /// first item of the file, else the core's
+    fn implementation_result(&self, path: &str) -> Option<crate::vectordb::SearchResult> {
+        let first = |db: &VectorDB| {
+            db.items_of(path).next().map(|(id, meta)| crate::vectordb::SearchResult::new(id, 0.0, meta.clone()))
+        };
+        first(&self.vectordb).or_else(|| self.core.as_ref().and_then(|core| first(&core.db)))
+    }
+
+    /// Last-commit times of `paths`; empty (with a warning) outside git
+    fn commit_times(&self, paths: &[&str]) -> HashMap<String, u64> {
+        crate::git::last_commit_times(&self.magento_root, paths).unwrap_or_else(|e| {
+            tracing::warn!("No git commit times: {:#}", e);
+            HashMap::new()
+        })
+    }
+
+    /// Set [`IndexMetadata::last_commit`] of every item from git, or clear
+    /// it when git times are off. Returns how many files have a time and
+    /// whether any item changed.
+    fn stamp_commit_times(&mut self) -> (usize, bool) {
+        let times = if self.git_times {
+            let paths = self.indexed_paths();
+            self.commit_times(&paths.iter().map(String::as_str).collect::<Vec<_>>())
+        } else {
+            HashMap::new()
+        };
+        let mut changed = false;
+        self.vectordb.update_metadata(|meta| {
+            let time = times.get(&meta.path).copied();
+            if meta.last_commit != time {
+                meta.last_commit = time;
+                changed = true;
+            }
+        });
+        (times.len(), changed)
+    }
+
+    /// Owners of `paths`: their CODEOWNERS entry, else their dominant git
+    /// author (with a warning outside git). Paths with neither are missing.
+    fn owners_of(&self, paths: &[&str]) -> HashMap<String, Vec<String>> {
+        let code_owners = crate::owners::CodeOwners::load(&self.magento_root);
+        let mut owners = HashMap::new();
+        let mut unmatched = Vec::new();
+        for &path in paths {
+            match code_owners.as_ref().and_then(|c| c.owners_of(path)) {
+                Some(found) if !found.is_empty() => {
+                    owners.insert(path.to_string(), found.to_vec());
+                }
+                // Explicitly unowned in CODEOWNERS
+                Some(_) => {}
+                None => unmatched.push(path),
+            }
+        }
+        match crate::git::dominant_authors(&self.magento_root, &unmatched) {
+            Ok(authors) => owners.extend(authors.into_iter().map(|(path, author)| (path, vec![author]))),
+            Err(e) => tracing::warn!("No git authors: {:#}", e),
+        }
+        owners
+    }
+
+    /// Set [`IndexMetadata::owners`] of every item, or clear them when
+    /// owners are off. Returns how many files have an owner and whether any
+    /// item changed.
+    fn assign_owners(&mut self) -> (usize, bool) {
+        let owners = if self.owners {
+            let paths = self.indexed_paths();
+            self.owners_of(&paths.iter().map(String::as_str).collect::<Vec<_>>())
+        } else {
+            HashMap::new()
+        };
+        let mut changed = false;
+        self.vectordb.update_metadata(|meta| {
+            let found = owners.get(&meta.path).map(Vec::as_slice).unwrap_or_default();
+            if meta.owners != found {
+                meta.owners = found.to_vec();
+                changed = true;
+            }
+        });
+        (owners.len(), changed)
+    }
+
     /// Remove all vectors associated with a file path (tombstone)
     pub fn remove_vectors_for_path(&mut self, path: &str) -> Vec<usize> {
+        let classes = self.vectordb.items_of(path).filter_map(|(_, meta)| crate::overrides::class_key(meta));
+        self.unlinked_classes.extend(classes);
+        self.unlinked_paths.insert(path.to_string());
         self.vectordb.remove_by_path(path)
     }
 
@@ -1348,37 +3696,307 @@ impl Indexer {
         self.vectordb.compact();
     }
 
+    /// Learn from a feedback signal; the query is re-embedded for LoRA
+    /// training, toward the chosen result and away from the results the user
+    /// skipped to reach it
+    pub fn learn_signal(&mut self, signal: &crate::sona::SonaSignal) {
+        let query = if signal.query.is_empty() { signal.original_query.as_deref().unwrap_or("") } else { &signal.query };
+        let query_emb = if !query.is_empty() { self.embed_query(query).ok() } else { None };
+        let (target, skipped) = match (&query_emb, signal.chosen_result()) {
+            (Some(_), Some(chosen)) => {
+                let mut vectors = self.path_vectors(&signal.search_result_paths[..=chosen]);
+                let target = vectors.pop().flatten();
+                (target, vectors.into_iter().flatten().collect())
+            }
+            _ => (None, Vec::new()),
+        };
+        let skipped: Vec<&[f32]> = skipped.iter().map(Vec::as_slice).collect();
+        self.sona_signals += 1;
+        if let Some(ref mut sona) = self.sona {
+            if let Some(ref qe) = query_emb {
+                // Without a chosen result in the index, use the query as its
+                // own target for self-supervised LoRA learning
+                sona.learn_with_embeddings(signal, Some(qe), Some(target.as_deref().unwrap_or(qe)), &skipped);
+            } else {
+                sona.learn(signal);
+            }
+        }
+    }
+
+    /// Vector of the first live item of each of `paths`, in order
+    fn path_vectors(&self, paths: &[String]) -> Vec<Option<Vec<f32>>> {
+        let mut first: HashMap<&str, usize> = HashMap::new();
+        for (id, meta) in self.vectordb.metadata_iter() {
+            if let Some(path) = paths.iter().find(|p| **p == meta.path) {
+                let slot = first.entry(path.as_str()).or_insert(id);
+                *slot = (*slot).min(id);
+            }
+        }
+        paths
+            .iter()
+            .map(|p| first.get(p.as_str()).and_then(|&id| self.vectordb.get(id)).map(|(v, _)| v.to_vec()))
+            .collect()
+    }
+
+    /// Save the SONA state next to the index (`<db>.sona`)
+    pub fn save_sona(&self) -> Result<()> {
+        if let (Some(sona), Some(db_path)) = (&self.sona, &self.db_path) {
+            sona.save(&db_path.with_extension("sona"))?;
+        }
+        Ok(())
+    }
+
     /// Save the index to disk
-    pub fn save(&self, path: &Path) -> Result<()> {
-        self.vectordb.save(path)
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.vectordb.save(path)?;
+        self.save_texts(path);
+        Ok(())
     }
 
     /// Crash-safe save: write to temp file, then atomic rename
-    pub fn save_atomic(&self, path: &Path) -> Result<()> {
-        self.vectordb.save_atomic(path)
+    pub fn save_atomic(&mut self, path: &Path) -> Result<()> {
+        self.vectordb.save_atomic(path)?;
+        self.save_texts(path);
+        Ok(())
+    }
+
+    /// Merge the texts embedded since the last save into the text cache of
+    /// the index at `path`; failures are logged, not fatal
+    fn save_texts(&mut self, path: &Path) {
+        if self.texts.is_empty() {
+            return;
+        }
+        match self.texts.merge_into(path, &self.vectordb) {
+            Ok(()) => self.texts = TextCache::default(),
+            Err(e) => tracing::warn!("Failed to save text cache: {:#}", e),
+        }
+    }
+
+    /// Save a copy of the index as snapshot `tag` (see [`crate::snapshot`]).
+    /// The working index keeps its own header.
+    pub fn save_snapshot(&mut self, db_path: &Path, tag: &str) -> Result<PathBuf> {
+        let path = snapshot::snapshot_path(db_path, tag)?;
+        let header = self.vectordb.header().clone();
+        self.vectordb.set_header(IndexHeader {
+            tag: tag.to_string(),
+            tagged_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            ..header.clone()
+        });
+        let saved = self.vectordb.save_atomic(&path);
+        self.vectordb.set_header(header);
+        saved?;
+        Ok(path)
     }
 
     /// Embed a query string with the retrieval prefix for bge-small-en-v1.5.
     /// The prefix improves retrieval accuracy by signaling the model that this
     /// is a search query, not a document to be indexed.
     pub fn embed_query(&mut self, query: &str) -> Result<Vec<f32>> {
-        let prefixed = format!("Represent this sentence: {}", query);
+        let prefixed = format!("{}{}", QUERY_PREFIX, query);
         self.embedder.embed(&prefixed)
     }
 
+    /// Tune the HNSW beam width on `queries` (see
+    /// [`VectorDB::tune_ef_search`]), embedded as searches would embed them
+    pub fn tune_ef_search(&mut self, queries: &[&str], k: usize, target: f32) -> Result<crate::vectordb::EfTuning> {
+        let prefixed: Vec<String> = queries.iter().map(|q| format!("{}{}", QUERY_PREFIX, q)).collect();
+        let refs: Vec<&str> = prefixed.iter().map(String::as_str).collect();
+        let mut embeddings = self.embedder.embed_batch(&refs)?;
+        if let Some(ref sona) = self.sona {
+            embeddings.iter_mut().for_each(|e| sona.adjust_query_embedding(e));
+        }
+        Ok(self.vectordb.tune_ef_search(&embeddings, k, target))
+    }
+
+    /// Candidate validation cases for `n` of the indexed files (see
+    /// [`crate::validation::generate_cases`])
+    pub fn generate_cases(&self, n: usize) -> Vec<crate::validation::TestCase> {
+        crate::validation::generate_cases(self.vectordb.metadata_iter().map(|(_, meta)| meta), n)
+    }
+
+    /// [`Self::search_weighted`] for several queries: one embedding batch,
+    /// then the searches in parallel. Results are in query order.
+    pub fn search_batch(
+        &mut self,
+        queries: &[&str],
+        k: usize,
+        weights: crate::vectordb::ScoreWeights,
+    ) -> Result<Vec<Vec<crate::vectordb::SearchResult>>> {
+        let queries: Vec<String> = queries.par_iter().map(|q| self.rewrite_query(q)).collect();
+        let prefixed: Vec<String> = queries.iter().map(|q| format!("{}{}", QUERY_PREFIX, q)).collect();
+        let refs: Vec<&str> = prefixed.iter().map(String::as_str).collect();
+        let mut embeddings = Vec::with_capacity(refs.len());
+        for batch in refs.chunks(CANCEL_CHECK_BATCH) {
+            self.cancel.check()?;
+            embeddings.extend(self.embedder.embed_batch(batch)?);
+        }
+        if let Some(ref sona) = self.sona {
+            embeddings.iter_mut().for_each(|e| sona.adjust_query_embedding(e));
+        }
+        let this = &*self;
+        embeddings
+            .par_iter()
+            .zip(queries.par_iter())
+            .map(|(embedding, query)| this.ranked_search(embedding, query, k, weights, None))
+            .collect()
+    }
+
     /// Search the index (hybrid: semantic + keyword re-ranking)
     pub fn search(&mut self, query: &str, k: usize) -> Result<Vec<crate::vectordb::SearchResult>> {
+        self.search_weighted(query, k, self.score_weights, None)
+    }
+
+    /// [`Self::search`] with the score parts weighted for this query only,
+    /// searching only items of `collection` when set
+    pub fn search_weighted(
+        &mut self,
+        query: &str,
+        k: usize,
+        weights: crate::vectordb::ScoreWeights,
+        collection: Option<crate::vectordb::Collection>,
+    ) -> Result<Vec<crate::vectordb::SearchResult>> {
+        let query = self.rewrite_query(query);
+        let query_embedding = self.embed_search_query(&query)?;
+        self.ranked_search(&query_embedding, &query, k, weights, collection)
+    }
+
+    /// [`Self::search_weighted`] reranking `pool` nearest neighbours from
+    /// each index (0 for the default of 3 × `k`), also returning those
+    /// candidates with their scores before and after reranking. Tells recall
+    /// problems (the wanted item isn't a candidate) from ranking problems
+    /// (it is, but reranking buries it).
+    pub fn search_pool(
+        &mut self,
+        query: &str,
+        k: usize,
+        weights: crate::vectordb::ScoreWeights,
+        pool: usize,
+        collection: Option<crate::vectordb::Collection>,
+    ) -> Result<(Vec<crate::vectordb::SearchResult>, Vec<crate::vectordb::Candidate>)> {
+        let query = self.rewrite_query(query);
+        let query_embedding = self.embed_search_query(&query)?;
+        self.ranked_search_pool(&query_embedding, &query, k, pool, weights, collection)
+    }
+
+    /// [`Self::search_weighted`] that should finish by `deadline`. When the
+    /// time left after embedding is less than a full search usually takes,
+    /// keyword/SONA/boost reranking is skipped and the HNSW beam narrowed.
+    /// The flag is true for such degraded results.
+    pub fn search_within(
+        &mut self,
+        query: &str,
+        k: usize,
+        weights: crate::vectordb::ScoreWeights,
+        deadline: Instant,
+        collection: Option<crate::vectordb::Collection>,
+    ) -> Result<(Vec<crate::vectordb::SearchResult>, bool)> {
+        let query = self.rewrite_query(query);
+        let query_embedding = self.embed_search_query(&query)?;
+        if Instant::now() + self.search_cost > deadline {
+            let mut results = self.vectordb.search_degraded(&query_embedding, k, collection);
+            if let Some(ref core) = self.core {
+                Self::merge_core(&mut results, core.db.search_degraded(&query_embedding, k, collection), k);
+            }
+            crate::project::mark_module_states(&self.module_states, &mut results, weights.disabled);
+            crate::implementations::group(&mut results, |path| self.implementation_result(path));
+            self.patches.mark(&mut results);
+            // Decay the estimate, so one slow search doesn't degrade all that
+            // follow: once a full search fits again it is run and measured
+            self.search_cost = self.search_cost * 3 / 4;
+            return Ok((results, true));
+        }
+        let start = Instant::now();
+        let results = self.ranked_search(&query_embedding, &query, k, weights, collection)?;
+        // Weighted towards the latest searches so the estimate follows load
+        self.search_cost = if self.search_cost.is_zero() {
+            start.elapsed()
+        } else {
+            (self.search_cost * 3 + start.elapsed()) / 4
+        };
+        Ok((results, false))
+    }
+
+    /// Query embedding with the MicroLoRA adjustment applied
+    fn embed_search_query(&mut self, query: &str) -> Result<Vec<f32>> {
+        self.cancel.check()?;
         let mut query_embedding = self.embed_query(query)?;
-        // Apply MicroLoRA adjustment before HNSW search
+        self.cancel.check()?;
         if let Some(ref sona) = self.sona {
             sona.adjust_query_embedding(&mut query_embedding);
         }
-        Ok(self.vectordb.hybrid_search(
-            &query_embedding,
+        Ok(query_embedding)
+    }
+
+    /// Hybrid search of the overlay and core for an embedded query
+    fn ranked_search(
+        &self,
+        query_embedding: &[f32],
+        query: &str,
+        k: usize,
+        weights: crate::vectordb::ScoreWeights,
+        collection: Option<crate::vectordb::Collection>,
+    ) -> Result<Vec<crate::vectordb::SearchResult>> {
+        Ok(self.ranked_search_pool(query_embedding, query, k, 0, weights, collection)?.0)
+    }
+
+    /// [`Self::ranked_search`] reranking `pool` candidates per index,
+    /// returned along with the results
+    fn ranked_search_pool(
+        &self,
+        query_embedding: &[f32],
+        query: &str,
+        k: usize,
+        pool: usize,
+        weights: crate::vectordb::ScoreWeights,
+        collection: Option<crate::vectordb::Collection>,
+    ) -> Result<(Vec<crate::vectordb::SearchResult>, Vec<crate::vectordb::Candidate>)> {
+        // Demoted or dropped results make room for the next candidates
+        let demotes = weights.disabled != 1.0 && self.module_states.values().any(|enabled| !enabled);
+        let fetch = if demotes { k * 2 } else { k };
+        let (mut results, mut candidates) = self.vectordb.hybrid_search_pool(
+            query_embedding,
             query,
-            k,
+            fetch,
+            pool,
             self.sona.as_ref(),
-        ))
+            &self.boost_rules,
+            weights,
+            collection,
+        );
+
+        if let Some(ref core) = self.core {
+            self.cancel.check()?;
+            let (core_results, core_candidates) = core.db.hybrid_search_pool(
+                query_embedding,
+                query,
+                fetch,
+                pool,
+                self.sona.as_ref(),
+                &self.boost_rules,
+                weights,
+                collection,
+            );
+            Self::merge_core(&mut results, core_results, fetch);
+            candidates.extend(core_candidates.into_iter().map(|c| crate::vectordb::Candidate { core: true, ..c }));
+            candidates.sort_by(|a, b| b.semantic.total_cmp(&a.semantic));
+        }
+        crate::project::mark_module_states(&self.module_states, &mut results, weights.disabled);
+        crate::implementations::group(&mut results, |path| self.implementation_result(path));
+        results.truncate(k);
+        self.patches.mark(&mut results);
+        Ok((results, candidates))
+    }
+
+    /// Merge core hits; a path present in both comes from the overlay
+    fn merge_core(
+        results: &mut Vec<crate::vectordb::SearchResult>,
+        core_results: Vec<crate::vectordb::SearchResult>,
+        k: usize,
+    ) {
+        let overlay: HashSet<String> = results.iter().map(|r| r.metadata.path.clone()).collect();
+        results.extend(core_results.into_iter().filter(|r| !overlay.contains(&r.metadata.path)));
+        results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        results.truncate(k);
     }
 
     /// Get index statistics
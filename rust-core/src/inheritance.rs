@@ -0,0 +1,181 @@
+//! Inherited Magento types
+//!
+//! The PHP analyzer flags a class as a controller, model, block, ... from
+//! its own declaration: the parent class and interfaces it names, its
+//! namespace, its methods. A class extending a project base controller that
+//! itself extends `Magento\Framework\App\Action\Action` names nothing of the
+//! kind. Once all files are indexed, each class's parent is looked up among
+//! the indexed classes, including those of a core index, and followed up the
+//! chain. The types an ancestor declares and the class doesn't are stored in
+//! [`IndexMetadata::inherited_types`], and the matching `is_*` flags are
+//! set. Parents that aren't indexed end the chain.
+//!
+//! Only `extends` is followed: interfaces with an `execute()` method would
+//! otherwise turn every implementation into a controller.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::overrides::class_key;
+use crate::vectordb::IndexMetadata;
+
+/// Types passed down inheritance chains, as stored in
+/// [`IndexMetadata::inherited_types`]
+pub const TYPES: [&str; 6] = ["controller", "repository", "observer", "model", "block", "resolver"];
+
+/// The `is_*` flag of a type in [`TYPES`]
+fn flag<'a>(meta: &'a mut IndexMetadata, name: &str) -> Option<&'a mut bool> {
+    Some(match name {
+        "controller" => &mut meta.is_controller,
+        "repository" => &mut meta.is_repository,
+        "observer" => &mut meta.is_observer,
+        "model" => &mut meta.is_model,
+        "block" => &mut meta.is_block,
+        "resolver" => &mut meta.is_resolver,
+        _ => return None,
+    })
+}
+
+/// Types the item has by its own declaration: flags set, minus those a
+/// previous pass inherited
+fn own_types(meta: &IndexMetadata) -> impl Iterator<Item = &'static str> + '_ {
+    let flags = [meta.is_controller, meta.is_repository, meta.is_observer, meta.is_model, meta.is_block, meta.is_resolver];
+    TYPES
+        .into_iter()
+        .zip(flags)
+        .filter(|(name, set)| *set && !meta.inherited_types.iter().any(|t| t == *name))
+        .map(|(name, _)| name)
+}
+
+/// What one class declares, merged over the items of its file
+#[derive(Default)]
+struct Declared {
+    types: BTreeSet<&'static str>,
+    parent: Option<String>,
+}
+
+/// Path of each class → the types its ancestors declare that it doesn't,
+/// in [`TYPES`] order. Classes inheriting nothing, or outside `only` when
+/// given, are left out. Chunks of one file count once.
+pub fn inherited_types<'a>(
+    items: impl IntoIterator<Item = &'a IndexMetadata>,
+    only: Option<&HashSet<String>>,
+) -> HashMap<String, Vec<String>> {
+    let mut classes: HashMap<String, Declared> = HashMap::new();
+    let mut files: HashMap<&str, Declared> = HashMap::new();
+    for meta in items {
+        let Some(key) = class_key(meta) else { continue };
+        let parent = meta.extends.as_deref().map(|p| p.trim_start_matches('\\').to_lowercase());
+        let file = only
+            .is_none_or(|paths| paths.contains(&meta.path))
+            .then(|| files.entry(meta.path.as_str()).or_default());
+        for declared in std::iter::once(classes.entry(key).or_default()).chain(file) {
+            declared.types.extend(own_types(meta));
+            if declared.parent.is_none() {
+                declared.parent.clone_from(&parent);
+            }
+        }
+    }
+
+    let mut resolved = HashMap::new();
+    for (path, class) in &files {
+        let mut types: BTreeSet<&str> = BTreeSet::new();
+        let mut seen = HashSet::new();
+        let mut parent = class.parent.as_ref();
+        while let Some(name) = parent {
+            if !seen.insert(name) {
+                break;
+            }
+            let Some(ancestor) = classes.get(name) else { break };
+            types.extend(ancestor.types.difference(&class.types).copied());
+            parent = ancestor.parent.as_ref();
+        }
+        if !types.is_empty() {
+            let types = TYPES.iter().filter(|t| types.contains(*t)).map(|t| t.to_string()).collect();
+            resolved.insert(path.to_string(), types);
+        }
+    }
+    resolved
+}
+
+/// Replace the inherited types of `meta` with `types`, clearing the flags
+/// of types it no longer inherits. Returns whether anything changed.
+pub fn apply(meta: &mut IndexMetadata, types: Vec<String>) -> bool {
+    if meta.inherited_types == types {
+        return false;
+    }
+    for name in std::mem::take(&mut meta.inherited_types) {
+        if let Some(flag) = flag(meta, &name) {
+            *flag = false;
+        }
+    }
+    for name in &types {
+        if let Some(flag) = flag(meta, name) {
+            *flag = true;
+        }
+    }
+    meta.inherited_types = types;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(path: &str, class: &str, extends: Option<&str>) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            class_name: Some(class.rsplit('\\').next().unwrap().to_string()),
+            class_type: Some("class".to_string()),
+            namespace: class.rsplit_once('\\').map(|(ns, _)| ns.to_string()),
+            extends: extends.map(str::to_string),
+            search_text: String::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_types_pass_down_chains() {
+        let action = IndexMetadata {
+            is_controller: true,
+            ..item("Framework/App/Action/Action.php", "Magento\\Framework\\App\\Action\\Action", None)
+        };
+        let base = item("Core/Controller/Base.php", "Vendor\\Core\\Base\\Page", Some("Magento\\Framework\\App\\Action\\Action"));
+        let view = item("Shop/View.php", "Vendor\\Shop\\Page\\View", Some("Vendor\\Core\\Base\\Page"));
+        let model = IndexMetadata {
+            is_model: true,
+            ..item("Shop/Thing.php", "Vendor\\Shop\\Thing", Some("Magento\\Framework\\App\\Action\\Action"))
+        };
+        // Cycles and missing parents end the chain
+        let a = item("A.php", "Vendor\\A", Some("Vendor\\B"));
+        let b = item("B.php", "Vendor\\B", Some("Vendor\\A"));
+        let orphan = item("Orphan.php", "Vendor\\Orphan", Some("Vendor\\Missing"));
+        let mut items = vec![action, base, view, model, a, b, orphan];
+
+        let resolved = inherited_types(&items, None);
+        assert_eq!(resolved["Shop/View.php"], ["controller"]);
+        assert_eq!(resolved["Core/Controller/Base.php"], ["controller"]);
+        assert_eq!(resolved["Shop/Thing.php"], ["controller"], "own model isn't repeated");
+        assert_eq!(resolved.len(), 3);
+        let only = HashSet::from(["Shop/View.php".to_string()]);
+        assert_eq!(inherited_types(&items, Some(&only)).into_keys().collect::<Vec<_>>(), ["Shop/View.php"]);
+
+        for meta in &mut items {
+            let types = resolved.get(&meta.path).cloned().unwrap_or_default();
+            apply(meta, types);
+        }
+        assert!(items[2].is_controller && items[3].is_model);
+
+        // The base stops extending Action: inherited flags go, own ones stay
+        items[1].extends = None;
+        items[3].extends = Some("Vendor\\Core\\Base\\Page".into());
+        let resolved = inherited_types(&items, None);
+        for meta in &mut items {
+            let types = resolved.get(&meta.path).cloned().unwrap_or_default();
+            apply(meta, types);
+        }
+        assert!(!items[1].is_controller && !items[2].is_controller && !items[3].is_controller);
+        assert!(items[0].is_controller && items[3].is_model);
+        assert!(items[2].inherited_types.is_empty());
+    }
+}
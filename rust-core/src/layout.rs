@@ -0,0 +1,175 @@
+//! Layout graph (`view/<area>/layout/<handle>.xml`)
+//!
+//! A layout file is named after the handle it applies to; a page renders
+//! the `default` handle, its full action name handle (`catalog_product_view`)
+//! and every handle those pull in with `<update handle="..."/>`. Themes
+//! extend module layouts from `app/design/<area>/<Vendor>/<theme>/<Module>/layout/`.
+//! [`LayoutGraph`] keeps the blocks each file declares or references, so a
+//! handle can be expanded into the blocks and templates it renders, and the
+//! UI components it places, so a component can be traced back to its page.
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::magento::detect_area;
+
+/// `<block>` declared or `<referenceBlock>` changed by a layout file
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LayoutBlock {
+    pub name: Option<String>,
+    pub class: Option<String>,
+    /// Template as written (`Magento_Catalog::product/view.phtml`)
+    pub template: Option<String>,
+    /// Whether the block is a `<referenceBlock>` to one declared elsewhere
+    pub reference: bool,
+}
+
+/// Handle file of a module or theme
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LayoutFile {
+    pub file: String,
+    /// `frontend`, `adminhtml` or `base`
+    pub area: String,
+    pub handle: String,
+    /// Handles pulled in with `<update handle="..."/>`
+    pub updates: Vec<String>,
+    pub blocks: Vec<LayoutBlock>,
+    /// UI components placed with `<uiComponent name="..."/>`
+    pub ui_components: Vec<String>,
+}
+
+/// Layout files of every module and theme
+#[derive(Debug, Clone, Default)]
+pub struct LayoutGraph {
+    files: Vec<LayoutFile>,
+}
+
+/// Whether `path` is a page layout handle file
+pub fn is_layout_xml(path: &str) -> bool {
+    (path.contains("/view/") || path.starts_with("app/design/") || path.contains("/app/design/"))
+        && path.ends_with(".xml")
+        && path.rsplit('/').nth(1) == Some("layout")
+}
+
+impl LayoutGraph {
+    /// Blocks and updates of the layout file at `file`; the handle comes from
+    /// its name and the area from its path
+    pub fn add_layout_xml(&mut self, content: &str, file: &str) {
+        let Some(handle) = file.rsplit('/').next().and_then(|name| name.strip_suffix(".xml")) else {
+            return;
+        };
+        let Some(area) = detect_area(file).filter(|a| matches!(a.as_str(), "frontend" | "adminhtml" | "base")) else {
+            return;
+        };
+        let block_re = Regex::new(r#"<(block|referenceBlock)\s+([^>]*?)/?>"#).unwrap();
+        let attr_re = Regex::new(r#"([\w:]+)="([^"]*)""#).unwrap();
+        let update_re = Regex::new(r#"<update\s+handle="([^"]+)""#).unwrap();
+        let ui_component_re = Regex::new(r#"<uiComponent\s+name="([^"]+)""#).unwrap();
+        let blocks = block_re
+            .captures_iter(content)
+            .map(|caps| {
+                let attr = |key: &str| {
+                    attr_re.captures_iter(&caps[2]).find(|a| &a[1] == key).map(|a| a[2].trim_start_matches('\\').to_string())
+                };
+                LayoutBlock {
+                    name: attr("name"),
+                    class: attr("class"),
+                    template: attr("template"),
+                    reference: &caps[1] == "referenceBlock",
+                }
+            })
+            .filter(|block| !block.reference || block.class.is_some() || block.template.is_some())
+            .collect();
+        self.files.push(LayoutFile {
+            file: file.to_string(),
+            area,
+            handle: handle.to_string(),
+            updates: update_re.captures_iter(content).map(|caps| caps[1].to_string()).collect(),
+            blocks,
+            ui_components: ui_component_re.captures_iter(content).map(|caps| caps[1].to_string()).collect(),
+        });
+    }
+
+    /// `handle` and every handle it pulls in through `<update>`, in `area`
+    pub fn expand(&self, area: &str, handle: &str) -> Vec<String> {
+        let mut handles = vec![handle.to_string()];
+        let mut next = 0;
+        while next < handles.len() {
+            for file in self.files_for(area, &handles[next].clone()) {
+                for update in &file.updates {
+                    if !handles.contains(update) {
+                        handles.push(update.clone());
+                    }
+                }
+            }
+            next += 1;
+        }
+        handles
+    }
+
+    /// Files for `handle` in `area`, including `base` ones
+    pub fn files_for<'a>(&'a self, area: &'a str, handle: &'a str) -> impl Iterator<Item = &'a LayoutFile> + 'a {
+        self.files.iter().filter(move |f| f.handle == handle && (f.area == area || f.area == "base"))
+    }
+
+    /// Files placing the UI component `name`
+    pub fn files_with_ui_component<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a LayoutFile> + 'a {
+        self.files.iter().filter(move |f| f.ui_components.iter().any(|c| c == name))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_graph() {
+        let mut graph = LayoutGraph::default();
+        graph.add_layout_xml(
+            r#"<page><update handle="catalog_product_opengraph"/>
+                <referenceContainer name="content">
+                    <block class="Magento\Catalog\Block\Product\View" name="product.info"
+                           template="Magento_Catalog::product/view/form.phtml">
+                        <block class="Magento\Catalog\Block\Product\View" name="product.info.addto" as="addto"/>
+                    </block>
+                </referenceContainer>
+                <referenceBlock name="breadcrumbs"/>
+                <uiComponent name="product_form"/>
+            </page>"#,
+            "vendor/magento/module-catalog/view/frontend/layout/catalog_product_view.xml",
+        );
+        graph.add_layout_xml(
+            r#"<page><referenceBlock name="product.info" template="Acme_Theme::form.phtml"/></page>"#,
+            "app/design/frontend/Acme/theme/Magento_Catalog/layout/catalog_product_view.xml",
+        );
+        graph.add_layout_xml(
+            r#"<page><update handle="catalog_product_view"/><block name="og" template="opengraph.phtml"/></page>"#,
+            "vendor/magento/module-catalog/view/frontend/layout/catalog_product_opengraph.xml",
+        );
+
+        let files: Vec<&LayoutFile> = graph.files_for("frontend", "catalog_product_view").collect();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].blocks.len(), 2);
+        assert_eq!(files[0].blocks[0].class.as_deref(), Some("Magento\\Catalog\\Block\\Product\\View"));
+        assert_eq!(files[0].blocks[1].name.as_deref(), Some("product.info.addto"));
+        assert_eq!(graph.files_with_ui_component("product_form").next().unwrap().file, files[0].file);
+        // Only references changing the block are kept
+        assert_eq!(files[1].blocks, [LayoutBlock {
+            name: Some("product.info".to_string()),
+            class: None,
+            template: Some("Acme_Theme::form.phtml".to_string()),
+            reference: true,
+        }]);
+        // Cycles through <update> end
+        assert_eq!(graph.expand("frontend", "catalog_product_view"), ["catalog_product_view", "catalog_product_opengraph"]);
+        assert_eq!(graph.files_for("adminhtml", "catalog_product_view").count(), 0);
+
+        assert!(is_layout_xml("vendor/magento/module-catalog/view/base/layout/default.xml"));
+        assert!(!is_layout_xml("vendor/magento/module-theme/view/frontend/page_layout/1column.xml"));
+        assert!(!is_layout_xml("vendor/magento/module-catalog/view/frontend/layout/override/x.xml"));
+    }
+}
@@ -2,10 +2,57 @@
 //!
 //! Provides semantic code search using ONNX embeddings and HNSW vector search.
 
+pub mod ab;
+pub mod aliases;
 pub mod ast;
+pub mod backup;
+pub mod codemap;
+pub mod config;
+pub mod console;
+pub mod constants;
+pub mod context;
+pub mod delta;
+pub mod di;
+pub mod docs;
+pub mod duplicates;
 pub mod embedder;
+pub mod enrichment;
+pub mod fsck;
+pub mod git;
+pub mod graphql;
+pub mod idf;
+pub mod ignore;
+pub mod implementations;
 pub mod indexer;
+pub mod inheritance;
+pub mod layout;
+pub mod mage_init;
+pub mod migrate;
 pub mod magento;
+pub mod payment;
+pub mod preflight;
+pub mod probe;
+pub mod project;
+pub mod overrides;
+pub mod owners;
+pub mod patches;
+pub mod path_guard;
+pub mod query;
+pub mod redact;
+pub mod routes;
+pub mod semdiff;
+pub mod session;
+pub mod queue;
+pub mod shipping;
+pub mod signing;
+pub mod snapshot;
+pub mod source;
+pub mod summary;
+pub mod totals;
+pub mod traits;
+pub mod trace;
+pub mod upgrade;
+pub mod ui_component;
 pub mod validation;
 pub mod vectordb;
 pub mod watcher;
@@ -18,5 +65,7 @@ pub use embedder::{Embedder, EMBEDDING_DIM};
 pub use indexer::{IndexStats, Indexer};
 pub use magento::{detect_file_type, MagentoFileType, XmlAnalyzer};
 pub use validation::{ValidationReport, Validator};
-pub use vectordb::{IndexMetadata, SearchResult, VectorDB};
+pub use vectordb::{
+    Collection, FileMetrics, IndexMetadata, ResultFilter, ResultOrder, ScoreWeights, SearchResult, SourceSpan, VectorDB,
+};
 pub use watcher::{WatcherStatus, watcher_loop};
@@ -0,0 +1,458 @@
+//! Template ↔ JS component wiring (serve `who_uses_js_component`)
+//!
+//! Templates instantiate JS components through `x-magento-init` script
+//! blocks (`{"<selector>": {"<component>": {config}}}`) and `data-mage-init`
+//! attributes (`{"<component>": {config}}`); UI component configs nested in
+//! them name further components under `"component"`. Components are
+//! RequireJS names: `Vendor_Module/js/file`, `mage/file` from `lib/web`, or
+//! an alias from a `requirejs-config.js` `map`. [`JsComponentXref`] records
+//! which templates use which component so both sides can be enriched.
+//!
+//! The same configs declare mixins (`config.mixins`), which wrap a module
+//! wherever it is loaded. The xref merges them per target so the effective
+//! module — the target after aliases, plus every enabled mixin — can be
+//! reported (serve `js_mixins`).
+
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::magento::module_names_for_path;
+
+/// Keys of a JSON-like text with their object depth (1 = top level), and
+/// the values of `"component"` keys. Tolerates PHP echoes and trailing
+/// garbage, which make real JSON parsers give up on most templates.
+fn json_keys(text: &str) -> (Vec<(String, usize)>, Vec<String>) {
+    let mut keys = Vec::new();
+    let mut components = Vec::new();
+    let mut depth = 0usize;
+    let mut last_key: Option<String> = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth = depth.saturating_sub(1),
+            '"' => {
+                let mut value = String::new();
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        '"' => break,
+                        _ => value.push(c),
+                    }
+                }
+                while chars.peek().is_some_and(|(_, c)| c.is_whitespace()) {
+                    chars.next();
+                }
+                if chars.peek().is_some_and(|(_, c)| *c == ':') {
+                    keys.push((value.clone(), depth));
+                    last_key = Some(value);
+                    continue;
+                }
+                if last_key.as_deref() == Some("component") {
+                    components.push(value);
+                }
+            }
+            _ => {}
+        }
+        if c != ':' && !c.is_whitespace() {
+            last_key = None;
+        }
+    }
+    (keys, components)
+}
+
+/// Whether `name` looks like a RequireJS module rather than a config key
+fn is_component_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(['#', '.', '[', '*'])
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/' | '.'))
+}
+
+/// JS components a template instantiates, in order of first use
+pub fn init_components(content: &str) -> Vec<String> {
+    let php_re = Regex::new(r"(?s)<\?(?:php|=)?.*?\?>").unwrap();
+    let script_re = Regex::new(r#"(?s)<script[^>]*type=["']text/x-magento-init["'][^>]*>(.*?)</script>"#).unwrap();
+    let attr_re = Regex::new(r#"(?s)data-mage-init\s*=\s*(?:'([^']*)'|"([^"]*)")"#).unwrap();
+
+    let mut found: Vec<String> = Vec::new();
+    let mut add = |name: String| {
+        if is_component_name(&name) && !found.contains(&name) {
+            found.push(name);
+        }
+    };
+    for script in script_re.captures_iter(content) {
+        let json = php_re.replace_all(&script[1], "null");
+        let (keys, components) = json_keys(&json);
+        // {"<selector>": {"<component>": ...}}
+        keys.into_iter().filter(|(_, depth)| *depth == 2).for_each(|(key, _)| add(key));
+        components.into_iter().for_each(&mut add);
+    }
+    for attr in attr_re.captures_iter(content) {
+        let raw = attr.get(1).or_else(|| attr.get(2)).map_or("", |m| m.as_str());
+        let json = php_re.replace_all(&raw.replace("&quot;", "\""), "null").into_owned();
+        let (keys, components) = json_keys(&json);
+        // {"<component>": ...}
+        keys.into_iter().filter(|(_, depth)| *depth == 1).for_each(|(key, _)| add(key));
+        components.into_iter().for_each(&mut add);
+    }
+    found
+}
+
+/// The JavaScript a template runs inline: its `<script>` blocks other than
+/// `x-magento-init` JSON, templates and external files, plus Magento 2.4
+/// `<<<script` heredocs rendered through `$secureRenderer`. PHP echoes
+/// become `null`, so the result parses as JS. Empty when there is none.
+pub fn inline_scripts(content: &str) -> String {
+    let php_re = Regex::new(r"(?s)<\?(?:php|=)?.*?\?>").unwrap();
+    let script_re = Regex::new(r"(?si)<script\b([^>]*)>(.*?)</script>").unwrap();
+    let type_re = Regex::new(r#"(?i)\btype\s*=\s*["']([^"']*)["']"#).unwrap();
+    let heredoc_re = Regex::new(r#"(?si)<<<\s*['"]?(?:script|js)['"]?[ \t]*\r?\n(.*?)\r?\n\s*(?:script|js)\b"#).unwrap();
+
+    let mut blocks: Vec<String> = Vec::new();
+    for script in script_re.captures_iter(content) {
+        let attrs = &script[1];
+        let is_js = type_re.captures(attrs).is_none_or(|t| {
+            let kind = t[1].to_lowercase();
+            kind.contains("javascript") || kind == "module"
+        });
+        if is_js && !attrs.contains("src=") {
+            blocks.push(php_re.replace_all(&script[2], "null").into_owned());
+        }
+    }
+    for heredoc in heredoc_re.captures_iter(content) {
+        blocks.push(heredoc[1].to_string());
+    }
+    blocks.retain(|b| !b.trim().is_empty());
+    blocks.join("\n;\n")
+}
+
+/// RequireJS name of the JS file at `path` (relative to the Magento root):
+/// `Vendor_Module/js/x` for module and theme files, `mage/x` for `lib/web`
+pub fn js_component_name(path: &str) -> Option<String> {
+    let (before, rest) = path.rsplit_once("/web/")?;
+    let rest = rest.strip_suffix(".js")?;
+    if before.ends_with("lib") {
+        return Some(rest.to_string());
+    }
+    // Theme override: <theme>/<Vendor_Module>/web/... (module files sit
+    // under view/<area>/web/ instead)
+    let last = before.rsplit('/').next().unwrap_or("");
+    if last.contains('_') {
+        return Some(format!("{}/{}", last, rest));
+    }
+    let module = module_names_for_path(path).into_iter().next()?;
+    Some(format!("{}/{}", module, rest))
+}
+
+/// `map` aliases of a requirejs-config.js as (alias, target)
+pub fn requirejs_aliases(content: &str) -> Vec<(String, String)> {
+    let map_re = Regex::new(r#"(?s)map\s*:\s*\{\s*['"]?\*['"]?\s*:\s*\{(.*?)\}"#).unwrap();
+    let entry_re = Regex::new(r#"['"]?([\w/.-]+)['"]?\s*:\s*['"]([\w/.-]+)['"]"#).unwrap();
+    map_re
+        .captures_iter(content)
+        .flat_map(|map| {
+            entry_re
+                .captures_iter(&map[1])
+                .map(|e| (e[1].to_string(), e[2].to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// `config.mixins` entries of a requirejs-config.js as (target, mixin, enabled)
+pub fn requirejs_mixins(content: &str) -> Vec<(String, String, bool)> {
+    let start_re = Regex::new(r#"['"]?mixins['"]?\s*:\s*\{"#).unwrap();
+    let target_re = Regex::new(r#"['"]?([\w/.-]+)['"]?\s*:\s*\{([^{}]*)\}"#).unwrap();
+    let entry_re = Regex::new(r#"['"]?([\w/.-]+)['"]?\s*:\s*(true|false)"#).unwrap();
+    let mut mixins = Vec::new();
+    for start in start_re.find_iter(content) {
+        // The block ends where its opening brace is balanced
+        let mut depth = 1;
+        let body = &content[start.end()..];
+        let end = body
+            .char_indices()
+            .find_map(|(i, c)| {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                (depth == 0).then_some(i)
+            })
+            .unwrap_or(body.len());
+        for target in target_re.captures_iter(&body[..end]) {
+            for entry in entry_re.captures_iter(&target[2]) {
+                mixins.push((target[1].to_string(), entry[1].to_string(), &entry[2] == "true"));
+            }
+        }
+    }
+    mixins
+}
+
+/// A mixin of a JS module, after merging every requirejs-config.js
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsMixin {
+    /// Target as declared (may be an alias)
+    pub target: String,
+    pub mixin: String,
+    /// False when a later config switched the mixin off
+    pub enabled: bool,
+    /// requirejs-config.js files declaring or toggling it
+    pub files: Vec<String>,
+}
+
+/// Which templates instantiate which JS components, and which mixins wrap them
+#[derive(Debug, Clone, Default)]
+pub struct JsComponentXref {
+    /// (component as written, template path)
+    uses: Vec<(String, String)>,
+    aliases: HashMap<String, String>,
+    mixins: Vec<JsMixin>,
+}
+
+impl JsComponentXref {
+    /// Record the components the template at `path` instantiates
+    pub fn add_template(&mut self, content: &str, path: &str) {
+        for component in init_components(content) {
+            self.uses.push((component, path.to_string()));
+        }
+    }
+
+    /// Record the `map` aliases and mixins of a requirejs-config.js
+    pub fn add_requirejs_config(&mut self, content: &str, file: &str) {
+        self.aliases.extend(requirejs_aliases(content));
+        for (target, mixin, enabled) in requirejs_mixins(content) {
+            match self.mixins.iter_mut().find(|m| m.target == target && m.mixin == mixin) {
+                Some(existing) => {
+                    existing.enabled = enabled;
+                    existing.files.push(file.to_string());
+                }
+                None => self.mixins.push(JsMixin { target, mixin, enabled, files: vec![file.to_string()] }),
+            }
+        }
+    }
+
+    /// `name` with aliases followed (`collapsible` → `mage/collapsible`)
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        let mut current = name;
+        for _ in 0..8 {
+            match self.aliases.get(current) {
+                Some(target) if target != current => current = target,
+                _ => break,
+            }
+        }
+        current
+    }
+
+    /// Templates instantiating `component` (a RequireJS name or alias)
+    pub fn templates_for_component(&self, component: &str) -> Vec<&str> {
+        let component = self.resolve(component.trim_end_matches(".js"));
+        let mut templates: Vec<&str> = self
+            .uses
+            .iter()
+            .filter(|(used, _)| self.resolve(used) == component)
+            .map(|(_, template)| template.as_str())
+            .collect();
+        templates.sort();
+        templates.dedup();
+        templates
+    }
+
+    /// Templates instantiating the JS file at `path`
+    pub fn templates_for_file(&self, path: &str) -> Vec<&str> {
+        match js_component_name(path) {
+            Some(name) => self.templates_for_component(&name),
+            None => Vec::new(),
+        }
+    }
+
+    /// Mixins wrapping `module` (a RequireJS name or alias), disabled ones
+    /// included, sorted by mixin name
+    pub fn mixins_for_component(&self, module: &str) -> Vec<&JsMixin> {
+        let module = self.resolve(module.trim_end_matches(".js"));
+        let mut mixins: Vec<&JsMixin> = self.mixins.iter().filter(|m| self.resolve(&m.target) == module).collect();
+        mixins.sort_by(|a, b| a.mixin.cmp(&b.mixin));
+        mixins
+    }
+
+    /// Enabled mixins wrapping the JS file at `path`
+    pub fn mixins_for_file(&self, path: &str) -> Vec<&str> {
+        match js_component_name(path) {
+            Some(name) => self
+                .mixins_for_component(&name)
+                .into_iter()
+                .filter(|m| m.enabled)
+                .map(|m| m.mixin.as_str())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Modules the JS file at `path` is an enabled mixin of (targets resolved)
+    pub fn mixin_targets_for_file(&self, path: &str) -> Vec<&str> {
+        let Some(name) = js_component_name(path) else { return Vec::new() };
+        let mut targets: Vec<&str> = self
+            .mixins
+            .iter()
+            .filter(|m| m.enabled && self.resolve(&m.mixin) == self.resolve(&name))
+            .map(|m| self.resolve(&m.target))
+            .collect();
+        targets.sort();
+        targets.dedup();
+        targets
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uses.is_empty() && self.mixins.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_components() {
+        let template = r#"
+<div id="minicart" data-mage-init='{"collapsible":{"openedState": "active", "saveState": false}}'>
+    <form data-mage-init='{"validation":<?= $block->getJsonConfig() ?>}'></form>
+</div>
+<script type="text/x-magento-init">
+{
+    "[data-block='minicart']": {
+        "Magento_Ui/js/core/app": <?= /* @noEscape */ $block->getJsLayout() ?>
+    },
+    "*": {
+        "Magento_Ui/js/core/app": {
+            "components": {
+                "minicart_content": {"component": "Magento_Checkout/js/view/minicart", "config": {"template": "Magento_Checkout/minicart/content"}}
+            }
+        },
+        "Magento_Checkout/js/sidebar": {"targetElement": "div.block.block-minicart"}
+    }
+}
+</script>"#;
+        assert_eq!(
+            init_components(template),
+            vec![
+                "Magento_Ui/js/core/app",
+                "Magento_Checkout/js/sidebar",
+                "Magento_Checkout/js/view/minicart",
+                "collapsible",
+                "validation"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inline_scripts() {
+        let template = r#"
+<script type="text/x-magento-init">{"*": {"Magento_Ui/js/core/app": {}}}</script>
+<script type="text/x-magento-template"><div><%- data.name %></div></script>
+<script src="<?= $block->getViewFileUrl('js/x.js') ?>"></script>
+<script>
+    require(['jquery', 'Magento_Customer/js/customer-data'], function ($, customerData) {
+        customerData.init(<?= (int) $block->getLifetime() ?>);
+    });
+</script>
+<?php $scriptString = <<<script
+    require(['mage/url'], function (url) { url.setBaseUrl('{$baseUrl}'); });
+script;
+?>
+<?= /* @noEscape */ $secureRenderer->renderTag('script', [], $scriptString, false) ?>"#;
+        let js = inline_scripts(template);
+        assert!(js.contains("customerData.init(null);"));
+        assert!(js.contains("require(['mage/url']"));
+        assert!(!js.contains("Magento_Ui/js/core/app"));
+        assert!(!js.contains("data.name"));
+        assert_eq!(inline_scripts("<div><?= $block->getName() ?></div>"), "");
+    }
+
+    #[test]
+    fn test_js_component_name() {
+        assert_eq!(
+            js_component_name("vendor/magento/module-checkout/view/frontend/web/js/view/minicart.js").as_deref(),
+            Some("Magento_Checkout/js/view/minicart")
+        );
+        assert_eq!(
+            js_component_name("app/code/Vendor/Cart/view/frontend/web/js/cart.js").as_deref(),
+            Some("Vendor_Cart/js/cart")
+        );
+        assert_eq!(js_component_name("lib/web/mage/collapsible.js").as_deref(), Some("mage/collapsible"));
+        assert_eq!(
+            js_component_name("app/design/frontend/Vendor/theme/Magento_Checkout/web/js/sidebar.js").as_deref(),
+            Some("Magento_Checkout/js/sidebar")
+        );
+        assert_eq!(js_component_name("app/code/Vendor/Cart/view/frontend/requirejs-config.js"), None);
+    }
+
+    #[test]
+    fn test_xref_with_aliases() {
+        let mut xref = JsComponentXref::default();
+        xref.add_requirejs_config(
+            r#"var config = { map: { '*': { collapsible: 'mage/collapsible', "sidebar": 'Magento_Checkout/js/sidebar' } } };"#,
+            "vendor/magento/module-checkout/view/frontend/requirejs-config.js",
+        );
+        xref.add_template(
+            r#"<div data-mage-init='{"collapsible":{}}'></div>"#,
+            "vendor/magento/module-checkout/view/frontend/templates/cart/minicart.phtml",
+        );
+        xref.add_template(
+            r#"<div data-mage-init='{"mage/collapsible":{}, "sidebar": {}}'></div>"#,
+            "app/code/Vendor/Cart/view/frontend/templates/list.phtml",
+        );
+        assert_eq!(xref.templates_for_component("collapsible").len(), 2);
+        assert_eq!(xref.templates_for_file("lib/web/mage/collapsible.js").len(), 2);
+        assert_eq!(
+            xref.templates_for_file("vendor/magento/module-checkout/view/frontend/web/js/sidebar.js"),
+            vec!["app/code/Vendor/Cart/view/frontend/templates/list.phtml"]
+        );
+        assert!(xref.templates_for_component("Magento_Ui/js/core/app").is_empty());
+    }
+
+    #[test]
+    fn test_mixins() {
+        let mut xref = JsComponentXref::default();
+        xref.add_requirejs_config(
+            r#"var config = {
+    map: { '*': { sidebar: 'Magento_Checkout/js/sidebar' } },
+    config: {
+        mixins: {
+            'Magento_Checkout/js/view/shipping': {
+                'Vendor_Cart/js/view/shipping-mixin': true,
+                'Other_Module/js/shipping-mixin': true
+            },
+            sidebar: { 'Vendor_Cart/js/sidebar-mixin': true }
+        }
+    }
+};"#,
+            "app/code/Vendor/Cart/view/frontend/requirejs-config.js",
+        );
+        // A later module switches one mixin off
+        xref.add_requirejs_config(
+            "var config = { config: { mixins: { 'Magento_Checkout/js/view/shipping': { 'Other_Module/js/shipping-mixin': false } } } };",
+            "app/code/Vendor/Fix/view/frontend/requirejs-config.js",
+        );
+
+        let shipping = xref.mixins_for_component("Magento_Checkout/js/view/shipping");
+        assert_eq!(shipping.len(), 2);
+        assert!(!shipping[0].enabled);
+        assert_eq!(shipping[0].files.len(), 2);
+        assert_eq!(
+            xref.mixins_for_file("vendor/magento/module-checkout/view/frontend/web/js/view/shipping.js"),
+            vec!["Vendor_Cart/js/view/shipping-mixin"]
+        );
+        // Declared against the alias, applies to the real module
+        assert_eq!(xref.mixins_for_component("Magento_Checkout/js/sidebar").len(), 1);
+        assert_eq!(
+            xref.mixin_targets_for_file("app/code/Vendor/Cart/view/frontend/web/js/sidebar-mixin.js"),
+            vec!["Magento_Checkout/js/sidebar"]
+        );
+        assert!(xref.mixin_targets_for_file("app/code/Other/Module/view/frontend/web/js/shipping-mixin.js").is_empty());
+    }
+}
@@ -1,6 +1,8 @@
 //! Magento-specific pattern detection and metadata extraction
 
 use regex::Regex;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 
 /// Magento file types
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -28,6 +30,18 @@ pub enum MagentoFileType {
     Template,
     JavaScript,
     GraphQlSchema,
+    /// sales.xml, fieldset.xml, pdf.xml or email_templates.xml
+    SalesConfig,
+    /// Model class of a payment method registered in config.xml
+    PaymentMethod,
+    /// Model class of a shipping carrier registered in config.xml
+    ShippingCarrier,
+    /// MFTF test, action group, data or page XML (`index --include-tests`)
+    MftfTest,
+    /// Markdown section (`index --include-docs`)
+    Documentation,
+    /// Patch file under `patches/` or `m2-hotfixes/`
+    Patch,
     Other,
 }
 
@@ -57,15 +71,60 @@ impl MagentoFileType {
             Self::Template => "template",
             Self::JavaScript => "javascript",
             Self::GraphQlSchema => "graphql_schema",
+            Self::SalesConfig => "sales_config",
+            Self::PaymentMethod => "payment_method",
+            Self::ShippingCarrier => "shipping_carrier",
+            Self::MftfTest => "mftf_test",
+            Self::Documentation => "documentation",
+            Self::Patch => "patch",
             Self::Other => "other",
         }
     }
 }
 
+/// Whether `path` is inside a module's `Test/Mftf` directory
+pub fn is_mftf_path(path: &str) -> bool {
+    path.starts_with("Test/Mftf/") || path.contains("/Test/Mftf/")
+}
+
+/// Classes Magento's code generator writes, by class name suffix. Each
+/// starts with a `<Kind> class for @see \Source\Class` docblock.
+const GENERATED_CLASS_KINDS: &[&str] = &["Interceptor", "Factory", "Proxy", "Extension", "ExtensionInterface"];
+
+/// Whether a file is generated code: anything under `generated/` (or the
+/// pre-2.2 `var/generation/`), or an interceptor, factory, proxy or
+/// extension attributes class written by the code generator. Hand-written
+/// factories lack the generator's docblock and are not matched.
+pub fn is_generated_code(path: &str, class_name: Option<&str>, content: &str) -> bool {
+    let in_generated_dir = |dir: &str| path.starts_with(dir) || path.contains(&format!("/{}", dir));
+    if in_generated_dir("generated/code/") || in_generated_dir("var/generation/") {
+        return true;
+    }
+    let Some(class) = class_name else {
+        return false;
+    };
+    GENERATED_CLASS_KINDS
+        .iter()
+        .any(|kind| class.ends_with(kind) && content.contains(&format!("{} class for @see \\", kind)))
+}
+
+/// Order-lifecycle config: totals collectors, fieldset copy rules, PDF
+/// renderers and email templates
+pub fn is_sales_config(path: &str) -> bool {
+    ["etc/sales.xml", "etc/fieldset.xml", "etc/pdf.xml", "etc/email_templates.xml"]
+        .iter()
+        .any(|name| path.ends_with(name))
+}
+
 /// Detect Magento file type from path
 pub fn detect_file_type(path: &str) -> MagentoFileType {
     let path_lower = path.to_lowercase();
 
+    // MFTF XML (ActionGroup/Data/Section files would otherwise look like config)
+    if is_mftf_path(path) && path_lower.ends_with(".xml") {
+        return MagentoFileType::MftfTest;
+    }
+
     // XML config files
     if path_lower.ends_with("di.xml") {
         return MagentoFileType::DiConfig;
@@ -91,6 +150,9 @@ pub fn detect_file_type(path: &str) -> MagentoFileType {
     if path_lower.contains("/layout/") && path_lower.ends_with(".xml") {
         return MagentoFileType::LayoutConfig;
     }
+    if is_sales_config(&path_lower) {
+        return MagentoFileType::SalesConfig;
+    }
 
     // PHP files by path
     if path_lower.contains("/controller/") {
@@ -189,6 +251,67 @@ pub fn extract_module_info(path: &str) -> Option<ModuleInfo> {
     None
 }
 
+/// Whether `dir` looks like a Magento 2 project root.
+///
+/// Matches an installed project (`app/etc/env.php`) or a checkout whose
+/// `composer.json` pulls in Magento and which has an `app/` tree — the latter
+/// check keeps module-level `composer.json` files (which also require
+/// `magento/framework`) from being mistaken for the root.
+pub fn is_magento_root(dir: &Path) -> bool {
+    if dir.join("app/etc/env.php").is_file() {
+        return true;
+    }
+    match std::fs::read_to_string(dir.join("composer.json")) {
+        Ok(composer) => {
+            (composer.contains("\"magento/framework\"") || composer.contains("\"magento/product-"))
+                && dir.join("app").is_dir()
+        }
+        Err(_) => false,
+    }
+}
+
+/// Walk up from `start` to the nearest Magento root
+pub fn find_magento_root(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find(|dir| is_magento_root(dir)).map(Path::to_path_buf)
+}
+
+/// Module names a path can be matched against by `--only-modules`.
+///
+/// Returns the canonical Magento name (`Magento_Checkout`) first, followed by
+/// the raw [`extract_module_info`] form when it differs — composer packages
+/// such as `vendor/magento/module-checkout` map to both `Magento_Checkout` and
+/// `magento_module-checkout`.
+pub fn module_names_for_path(path: &str) -> Vec<String> {
+    let Some(info) = extract_module_info(path) else {
+        return Vec::new();
+    };
+
+    let canonical = if path.contains("vendor/") && !path.contains("app/code/") {
+        let pascal = |s: &str| -> String {
+            s.split(['-', '_'])
+                .filter(|part| !part.is_empty())
+                .map(|part| {
+                    let mut chars = part.chars();
+                    match chars.next() {
+                        Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                        None => String::new(),
+                    }
+                })
+                .collect()
+        };
+        let name = info.name.strip_prefix("module-").unwrap_or(&info.name);
+        format!("{}_{}", pascal(&info.vendor), pascal(name))
+    } else {
+        info.full.clone()
+    };
+
+    let mut names = vec![canonical];
+    if info.full != names[0] {
+        names.push(info.full);
+    }
+    names
+}
+
 /// Detect area (frontend, adminhtml, etc.)
 pub fn detect_area(path: &str) -> Option<String> {
     if path.contains("/frontend/") {
@@ -278,7 +401,7 @@ impl PhpAnalyzer {
             || meta.methods.contains(&"execute".to_string());
 
         meta.is_repository = content.contains("RepositoryInterface")
-            || meta.class_name.as_ref().map_or(false, |n| n.contains("Repository"));
+            || meta.class_name.as_ref().is_some_and(|n| n.contains("Repository"));
 
         meta.is_plugin = self.plugin_method_re.captures_iter(content).count() > 0;
 
@@ -346,16 +469,609 @@ pub struct PluginDeclaration {
     pub sort_order: Option<i32>,
 }
 
+/// `<virtualType>` declaration from di.xml
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VirtualTypeDeclaration {
+    pub name: String,
+    /// Class (or another virtual type) it is based on
+    pub base_type: String,
+    /// `shared="false"` asks for a new instance per injection
+    pub shared: Option<bool>,
+}
+
+/// Constructor argument set on a `<type>` or `<virtualType>` in di.xml
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiArgument {
+    /// Type or virtual type the argument is set on
+    pub target: String,
+    pub name: String,
+    /// `xsi:type` (`object`, `string`, `array`, ...)
+    pub xsi_type: String,
+    /// Text value: a class name for `object` arguments. Empty for arrays.
+    pub value: String,
+    /// Classes of `object` items inside an `array` argument
+    pub items: Vec<String>,
+    /// `shared="false"` on an `object` argument injects a fresh instance
+    pub shared: Option<bool>,
+}
+
+impl DiArgument {
+    /// The injected object is a generated lazy-loading proxy
+    pub fn is_proxy(&self) -> bool {
+        self.xsi_type == "object" && self.value.ends_with("\\Proxy")
+    }
+}
+
+/// `<add>` entry from `etc/adminhtml/menu.xml`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MenuItem {
+    pub id: String,
+    pub title: String,
+    pub module: String,
+    /// Admin route (`adminhtml/cache`); absent for pure grouping items
+    pub action: Option<String>,
+    /// ACL resource guarding the item
+    pub resource: String,
+    pub parent: Option<String>,
+}
+
+impl MenuItem {
+    /// Controller and action the menu item opens, in the form produced by
+    /// [`admin_controller_action`] (`cache/index`). The front name is
+    /// dropped: it is declared in routes.xml, not in the controller path.
+    pub fn controller_action(&self) -> Option<String> {
+        let mut parts = self.action.as_deref()?.trim_matches('/').split('/').skip(1);
+        let controller = parts.next().filter(|c| !c.is_empty()).unwrap_or("index");
+        let action = parts.next().filter(|a| !a.is_empty()).unwrap_or("index");
+        Some(format!("{}/{}", controller, action).to_lowercase())
+    }
+}
+
+/// `controller/action` served by an admin controller file
+/// (`.../Controller/Adminhtml/Order/Create/Index.php` → `order_create/index`)
+pub fn admin_controller_action(path: &str) -> Option<String> {
+    let rest = path.split_once("/Controller/Adminhtml/")?.1.strip_suffix(".php")?;
+    let (controller, action) = rest.rsplit_once('/')?;
+    Some(format!("{}/{}", controller.replace('/', "_"), action).to_lowercase())
+}
+
+/// Admin menu items of every menu.xml, keyed by the controller action they
+/// open, so admin controllers can be correlated with their menu entries
+#[derive(Debug, Clone, Default)]
+pub struct AdminMenu {
+    by_action: std::collections::HashMap<String, Vec<MenuItem>>,
+}
+
+impl AdminMenu {
+    pub fn extend(&mut self, items: impl IntoIterator<Item = MenuItem>) {
+        for item in items {
+            if let Some(key) = item.controller_action() {
+                self.by_action.entry(key).or_default().push(item);
+            }
+        }
+    }
+
+    /// Menu items pointing at the admin controller file `path`
+    pub fn items_for_controller(&self, path: &str) -> &[MenuItem] {
+        admin_controller_action(path)
+            .and_then(|key| self.by_action.get(&key))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_action.is_empty()
+    }
+}
+
+/// Whether `path` is an admin menu definition
+pub fn is_menu_xml(path: &str) -> bool {
+    path.ends_with("etc/adminhtml/menu.xml")
+}
+
+/// `<widget>` declaration from `etc/widget.xml`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WidgetDeclaration {
+    pub id: String,
+    /// Block class rendering the widget
+    pub class: String,
+    pub label: String,
+    pub description: String,
+    /// Parameter names configurable in the CMS widget form
+    pub parameters: Vec<String>,
+    /// Templates offered by the `template` parameter, as written
+    /// (`Magento_CatalogWidget::product/widget/content/grid.phtml`)
+    pub templates: Vec<String>,
+}
+
+/// Template path relative to a module's `templates/` directory
+/// (`Vendor_Module::widget/list.phtml` → `widget/list.phtml`)
+fn template_key(template: &str) -> &str {
+    template.rsplit("::").next().unwrap_or(template).trim_start_matches('/')
+}
+
+/// CMS widget types of every widget.xml, by block class and by template
+#[derive(Debug, Clone, Default)]
+pub struct WidgetRegistry {
+    by_class: std::collections::HashMap<String, Vec<WidgetDeclaration>>,
+    by_template: std::collections::HashMap<String, Vec<WidgetDeclaration>>,
+}
+
+impl WidgetRegistry {
+    pub fn extend(&mut self, widgets: impl IntoIterator<Item = WidgetDeclaration>) {
+        for widget in widgets {
+            for template in &widget.templates {
+                self.by_template.entry(template_key(template).to_string()).or_default().push(widget.clone());
+            }
+            self.by_class.entry(widget.class.trim_start_matches('\\').to_string()).or_default().push(widget);
+        }
+    }
+
+    /// Widget types rendered by the block class `class` (fully qualified)
+    pub fn widgets_for_class(&self, class: &str) -> &[WidgetDeclaration] {
+        self.by_class.get(class.trim_start_matches('\\')).map_or(&[], Vec::as_slice)
+    }
+
+    /// Widget types offering the template file at `path`
+    pub fn widgets_for_template(&self, path: &str) -> &[WidgetDeclaration] {
+        path.split_once("/templates/")
+            .and_then(|(_, key)| self.by_template.get(key))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_class.is_empty()
+    }
+}
+
+/// Whether `path` is a CMS widget registry
+pub fn is_widget_xml(path: &str) -> bool {
+    path.ends_with("etc/widget.xml")
+}
+
+/// `<indexer>` from `etc/indexer.xml`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IndexerDeclaration {
+    pub id: String,
+    /// mview.xml `<view>` feeding "Update by Schedule" mode
+    pub view_id: Option<String>,
+    /// Action class running full and partial reindex
+    pub class: String,
+    pub title: String,
+    pub description: String,
+    /// Indexer whose tables this one shares (reindexed together)
+    pub shared_index: Option<String>,
+}
+
+/// `<view>` from `etc/mview.xml`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MviewDeclaration {
+    pub id: String,
+    /// Action class called with the changed entity ids
+    pub class: String,
+    pub group: String,
+    /// Subscribed tables as (table, entity column); triggers on them feed the changelog
+    pub subscriptions: Vec<(String, String)>,
+}
+
+/// An indexer joined with its materialized view
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IndexerEntry {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub action_class: Option<String>,
+    pub view_id: Option<String>,
+    pub mview_class: Option<String>,
+    pub shared_index: Option<String>,
+    /// Tables whose changes schedule a partial reindex
+    pub subscribed_tables: Vec<String>,
+    /// indexer.xml / mview.xml files declaring it
+    pub files: Vec<String>,
+}
+
+/// Magento indexers from every indexer.xml and mview.xml
+#[derive(Debug, Clone, Default)]
+pub struct IndexerRegistry {
+    indexers: Vec<(IndexerDeclaration, String)>,
+    views: Vec<(MviewDeclaration, String)>,
+    /// Action class → its entries, from [`Self::index_classes`]
+    by_class: std::collections::HashMap<String, Vec<IndexerEntry>>,
+}
+
+impl IndexerRegistry {
+    /// Add the declarations of one file (relative path `file`)
+    pub fn extend(&mut self, indexers: Vec<IndexerDeclaration>, views: Vec<MviewDeclaration>, file: &str) {
+        self.indexers.extend(indexers.into_iter().map(|i| (i, file.to_string())));
+        self.views.extend(views.into_iter().map(|v| (v, file.to_string())));
+    }
+
+    /// Indexers joined with their views, sorted by id, optionally only those
+    /// whose id, title, class or a subscribed table contains `filter`
+    /// (case-insensitive). Views without an indexer are listed on their own.
+    pub fn entries(&self, filter: Option<&str>) -> Vec<IndexerEntry> {
+        let mut entries: std::collections::BTreeMap<String, IndexerEntry> = std::collections::BTreeMap::new();
+        for (indexer, file) in &self.indexers {
+            // Later declarations (e.g. a module overriding the class) win
+            let entry = entries.entry(indexer.id.clone()).or_default();
+            entry.id = indexer.id.clone();
+            if !indexer.title.is_empty() {
+                entry.title = indexer.title.clone();
+            }
+            if !indexer.description.is_empty() {
+                entry.description = indexer.description.clone();
+            }
+            if !indexer.class.is_empty() {
+                entry.action_class = Some(indexer.class.clone());
+            }
+            entry.view_id = indexer.view_id.clone().or(entry.view_id.take());
+            entry.shared_index = indexer.shared_index.clone().or(entry.shared_index.take());
+            entry.files.push(file.clone());
+        }
+        for (view, file) in &self.views {
+            let id = entries
+                .values()
+                .find(|e| e.view_id.as_deref() == Some(view.id.as_str()))
+                .map_or_else(|| view.id.clone(), |e| e.id.clone());
+            let entry = entries.entry(id.clone()).or_insert_with(|| IndexerEntry {
+                id,
+                view_id: Some(view.id.clone()),
+                ..Default::default()
+            });
+            if !view.class.is_empty() {
+                entry.mview_class = Some(view.class.clone());
+            }
+            entry.subscribed_tables.extend(view.subscriptions.iter().map(|(table, _)| table.clone()));
+            entry.files.push(file.clone());
+        }
+        let matches = |entry: &IndexerEntry| {
+            let Some(filter) = filter.filter(|f| !f.is_empty()) else { return true };
+            let filter = filter.to_lowercase();
+            [&entry.id, &entry.title]
+                .into_iter()
+                .chain(entry.action_class.iter())
+                .chain(entry.mview_class.iter())
+                .chain(entry.subscribed_tables.iter())
+                .any(|part| part.to_lowercase().contains(&filter))
+        };
+        entries
+            .into_values()
+            .map(|mut entry| {
+                entry.subscribed_tables.sort();
+                entry.subscribed_tables.dedup();
+                entry
+            })
+            .filter(matches)
+            .collect()
+    }
+
+    /// Map each indexer and mview action class to its entries for
+    /// [`Self::entries_for_class`]; call once every file is added
+    pub fn index_classes(&mut self) {
+        let mut by_class: std::collections::HashMap<String, Vec<IndexerEntry>> = std::collections::HashMap::new();
+        for entry in self.entries(None) {
+            let mut classes: Vec<&str> =
+                [&entry.action_class, &entry.mview_class].into_iter().flatten().map(|c| c.trim_start_matches('\\')).collect();
+            classes.dedup();
+            for class in classes {
+                by_class.entry(class.to_string()).or_default().push(entry.clone());
+            }
+        }
+        self.by_class = by_class;
+    }
+
+    /// Indexers whose indexer or mview action class is `class`
+    pub fn entries_for_class(&self, class: &str) -> Vec<IndexerEntry> {
+        self.by_class.get(class.trim_start_matches('\\')).cloned().unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexers.is_empty() && self.views.is_empty()
+    }
+}
+
+/// Whether `path` declares Magento indexers or materialized views
+pub fn is_indexer_xml(path: &str) -> bool {
+    path.ends_with("etc/indexer.xml") || path.ends_with("etc/mview.xml")
+}
+
+/// Children of the first `<section>` element of a config.xml (`payment`,
+/// `carriers`, ...) as (code, field → text value); fields with child
+/// elements are skipped
+pub fn config_section_entries(content: &str, section: &str) -> Vec<(String, std::collections::HashMap<String, String>)> {
+    let comment_re = Regex::new(r"(?s)<!--.*?-->").unwrap();
+    let tag_re = Regex::new(r"<(/?)([A-Za-z0-9_:.-]+)[^>]*?(/?)>").unwrap();
+    let content = comment_re.replace_all(content, "");
+    let Some(start) = content.find(&format!("<{}>", section)) else { return Vec::new() };
+    let content = &content[start..];
+
+    let mut entries: Vec<(String, std::collections::HashMap<String, String>)> = Vec::new();
+    let mut stack: Vec<(String, usize)> = Vec::new();
+    for caps in tag_re.captures_iter(content) {
+        let whole = caps.get(0).unwrap();
+        let name = caps[2].to_string();
+        if &caps[3] == "/" {
+            // Self-closing: an entry without fields, or an empty field
+            if stack.len() == 1 {
+                entries.push((name, std::collections::HashMap::new()));
+            }
+            continue;
+        }
+        if &caps[1] != "/" {
+            if stack.len() == 1 {
+                entries.push((name.clone(), std::collections::HashMap::new()));
+            }
+            stack.push((name, whole.end()));
+            continue;
+        }
+        let Some((open, text_start)) = stack.pop() else { break };
+        if open != name {
+            break;
+        }
+        match stack.len() {
+            // Closing the section
+            0 => break,
+            // Direct child of an entry: a field with text content
+            2 => {
+                let text = content[text_start..whole.start()].trim();
+                if !text.contains('<') {
+                    if let Some((_, fields)) = entries.last_mut() {
+                        fields.insert(name, text.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Totals collector `<item>` from `etc/sales.xml`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TotalCollector {
+    /// `quote`, `order_invoice` or `order_creditmemo`
+    pub section: String,
+    pub group: String,
+    /// Total code (`subtotal`, `shipping`, `tax`, ...)
+    pub code: String,
+    /// Collector class; empty when a module only changes the sort order
+    pub class: String,
+    pub sort_order: Option<i64>,
+}
+
+/// Copy rule from `etc/fieldset.xml`: `field` is copied by `aspect`
+/// (e.g. `to_order`) of the `fieldset` (e.g. `sales_convert_quote`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FieldsetCopyRule {
+    pub scope: String,
+    pub fieldset: String,
+    pub field: String,
+    pub aspect: String,
+    /// Field written on the target when it differs from `field`
+    pub target_field: Option<String>,
+}
+
+/// Item renderer from `etc/pdf.xml`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfRenderer {
+    /// `invoice`, `shipment` or `creditmemo`
+    pub page: String,
+    pub product_type: String,
+    pub class: String,
+}
+
+/// Total line from `etc/pdf.xml`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfTotal {
+    pub name: String,
+    pub title: String,
+    pub source_field: String,
+    /// Custom total model, if any
+    pub model: Option<String>,
+    pub sort_order: Option<i64>,
+}
+
+/// `<template>` from `etc/email_templates.xml`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmailTemplate {
+    pub id: String,
+    pub label: String,
+    pub file: String,
+    pub module: String,
+    pub area: String,
+}
+
+/// Links from config files to the code they point at, gathered from every
+/// menu.xml, routes.xml, widget.xml, indexer.xml, mview.xml, payment/carrier config,
+/// GraphQL schema, template init scripts, di.xml, console command classes,
+/// layout XML, UI component XML and data provider classes so controllers (and
+/// their URLs), blocks, templates, indexer actions, payment methods, carriers,
+/// resolvers, JS components, `bin/magento` commands and UI components can be
+/// enriched with the files that reference them, a class's DI wiring can be
+/// explained and a request can be traced to the page it renders
+#[derive(Debug, Clone, Default)]
+pub struct ConfigXref {
+    pub menu: AdminMenu,
+    pub routes: crate::routes::RouteRegistry,
+    pub widgets: WidgetRegistry,
+    pub indexers: IndexerRegistry,
+    pub payments: crate::payment::PaymentRegistry,
+    pub carriers: crate::shipping::CarrierRegistry,
+    pub graphql: crate::graphql::GraphQlXref,
+    pub js_components: crate::mage_init::JsComponentXref,
+    pub di: crate::di::DiGraph,
+    pub console: crate::console::CommandRegistry,
+    pub layout: crate::layout::LayoutGraph,
+    pub ui_components: crate::ui_component::UiComponentRegistry,
+}
+
+impl ConfigXref {
+    /// Whether `path` contributes to the cross-reference
+    pub fn is_source(path: &str) -> bool {
+        is_menu_xml(path)
+            || crate::routes::is_routes_xml(path)
+            || is_widget_xml(path)
+            || is_indexer_xml(path)
+            || crate::payment::is_payment_source(path)
+            || path.ends_with(".graphqls")
+            || path.ends_with(".phtml")
+            || path.ends_with("requirejs-config.js")
+            || path.ends_with("etc/module.xml")
+            || crate::console::is_command_source(path)
+            || crate::layout::is_layout_xml(path)
+            || crate::ui_component::is_ui_component_xml(path)
+            || crate::ui_component::is_provider_source(path)
+    }
+
+    /// Read and parse the given config files under `root`; unreadable files
+    /// are skipped
+    pub fn from_files<'a>(root: &Path, paths: impl IntoIterator<Item = &'a Path>, analyzer: &XmlAnalyzer) -> Self {
+        let mut xref = Self::default();
+        let mut modules = Vec::new();
+        // Merged once the module load order is known
+        let mut requirejs_configs = Vec::new();
+        for path in paths {
+            let Ok(content) = std::fs::read_to_string(path) else { continue };
+            let file = crate::vectordb::relative_path(root, path);
+            if file.ends_with(".phtml") {
+                xref.js_components.add_template(&content, &file);
+                continue;
+            }
+            if file.ends_with("requirejs-config.js") {
+                requirejs_configs.push((file, content));
+                continue;
+            }
+            if let Some(dir) = file.strip_suffix("/etc/module.xml") {
+                modules.extend(crate::project::ModuleDecl::parse(&content, dir));
+                continue;
+            }
+            if file.ends_with(".js") {
+                xref.payments.add_renderer_js(&content, &file);
+                continue;
+            }
+            if file.ends_with(".graphqls") {
+                xref.graphql.add_schema(&content, &file);
+                continue;
+            }
+            if file.ends_with(".php") {
+                if crate::console::is_command_source(&file) {
+                    xref.console.add_php(&content, &file);
+                }
+                if crate::ui_component::is_provider_source(&file) {
+                    xref.ui_components.add_php(&content);
+                }
+                continue;
+            }
+            if crate::routes::is_routes_xml(&file) {
+                xref.routes.add_routes_xml(&content, &file);
+                continue;
+            }
+            if crate::layout::is_layout_xml(&file) {
+                xref.layout.add_layout_xml(&content, &file);
+                continue;
+            }
+            if crate::ui_component::is_ui_component_xml(&file) {
+                xref.ui_components.add_component_xml(&content, &file);
+                continue;
+            }
+            if file.ends_with("etc/config.xml") {
+                xref.payments.add_config_xml(&content, &file);
+                xref.carriers.add_config_xml(&content, &file);
+            } else if file.ends_with("etc/payment.xml") {
+                xref.payments.add_payment_xml(&content, &file);
+            }
+            let meta = analyzer.analyze(&content);
+            xref.payments.add_virtual_types(&meta.virtual_types, &file);
+            if file.ends_with("di.xml") {
+                xref.di.add(&meta, &file);
+                xref.console.add_di(&meta.arguments, &file);
+                xref.ui_components.add_di(&content);
+            }
+            xref.menu.extend(meta.menu_items);
+            xref.widgets.extend(meta.widgets);
+            xref.indexers.extend(meta.indexers, meta.mviews, &file);
+        }
+        // A later module's config toggles the mixins of the modules it loads after
+        let order = crate::project::ModuleOrder::new(root, &modules);
+        requirejs_configs.sort_by_cached_key(|(file, _)| (order.file_position(file), file.clone()));
+        for (file, content) in requirejs_configs {
+            xref.js_components.add_requirejs_config(&content, &file);
+        }
+        // Looked up for every indexed class
+        xref.indexers.index_classes();
+        xref.payments.index_classes();
+        xref.ui_components.index_providers();
+        xref
+    }
+
+    /// Find and parse every source file under `root`, skipping the
+    /// directories indexing skips
+    pub fn scan(root: &Path, analyzer: &XmlAnalyzer) -> anyhow::Result<Self> {
+        Self::scan_roots(root, &[], analyzer)
+    }
+
+    /// [`Self::scan`] over `root` and extra source roots (`index --extra-root`)
+    pub fn scan_roots(root: &Path, extra_roots: &[PathBuf], analyzer: &XmlAnalyzer) -> anyhow::Result<Self> {
+        let paths = Self::source_files(root, extra_roots)?;
+        Ok(Self::from_files(root, paths.iter().map(PathBuf::as_path), analyzer))
+    }
+
+    /// Source files under `root` and `extra_roots`, skipping the directories
+    /// indexing skips
+    pub fn source_files(root: &Path, extra_roots: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for walk_root in std::iter::once(root).chain(extra_roots.iter().map(PathBuf::as_path)) {
+            let mut walker = crate::indexer::SourceWalk::new(walk_root, false);
+            while let Some(entry) = walker.next() {
+                let entry = entry?;
+                if crate::indexer::Indexer::should_skip_dir(&entry, walk_root, false) {
+                    walker.skip_current_dir();
+                    continue;
+                }
+                if entry.file_type().is_file() && Self::is_source(&crate::vectordb::relative_path(walk_root, entry.path())) {
+                    paths.push(entry.into_path());
+                }
+            }
+        }
+        Ok(paths)
+    }
+}
+
 /// XML config analyzer
 pub struct XmlAnalyzer {
     preference_re: Regex,
     type_re: Regex,
-    type_block_re: Regex,
+    /// `<type>` / `<virtualType>` element: tag, attributes, optional body
+    di_block_re: Regex,
     plugin_in_block_re: Regex,
+    argument_re: Regex,
+    object_item_re: Regex,
+    attr_re: Regex,
     event_re: Regex,
     route_re: Regex,
     table_re: Regex,
     job_re: Regex,
+    menu_add_re: Regex,
+    widget_re: Regex,
+    widget_label_re: Regex,
+    widget_description_re: Regex,
+    widget_parameter_re: Regex,
+    widget_template_re: Regex,
+    mftf_re: Regex,
+    indexer_re: Regex,
+    view_re: Regex,
+    title_re: Regex,
+    subscription_re: Regex,
+    sales_section_re: Regex,
+    sales_group_re: Regex,
+    sales_item_re: Regex,
+    fieldset_scope_re: Regex,
+    fieldset_re: Regex,
+    fieldset_field_re: Regex,
+    fieldset_aspect_re: Regex,
+    pdf_page_re: Regex,
+    pdf_renderer_re: Regex,
+    pdf_total_re: Regex,
+    email_template_re: Regex,
 }
 
 impl XmlAnalyzer {
@@ -363,12 +1079,37 @@ impl XmlAnalyzer {
         Self {
             preference_re: Regex::new(r#"<preference\s+for="([^"]+)"\s+type="([^"]+)""#).unwrap(),
             type_re: Regex::new(r#"<type\s+name="([^"]+)""#).unwrap(),
-            type_block_re: Regex::new(r#"(?s)<type\s+name="([^"]+)"[^>]*>(.*?)</type>"#).unwrap(),
+            di_block_re: Regex::new(r#"(?s)<(type|virtualType)\s+([^>]*?)(?:/>|>(.*?)</(?:type|virtualType)>)"#).unwrap(),
             plugin_in_block_re: Regex::new(r#"<plugin\s+([^/>]*?)/?>"#).unwrap(),
+            argument_re: Regex::new(r#"(?s)<argument\s+([^>]*?)(?:/>|>(.*?)</argument>)"#).unwrap(),
+            object_item_re: Regex::new(r#"<item\s+[^>]*xsi:type="object"[^>]*>([^<]+)</item>"#).unwrap(),
+            attr_re: Regex::new(r#"([\w:]+)="([^"]*)""#).unwrap(),
             event_re: Regex::new(r#"<event\s+name="([^"]+)""#).unwrap(),
             route_re: Regex::new(r#"<route\s+url="([^"]+)"\s+method="([^"]+)""#).unwrap(),
             table_re: Regex::new(r#"<table\s+name="([^"]+)""#).unwrap(),
             job_re: Regex::new(r#"<job\s+name="([^"]+)"\s+instance="([^"]+)""#).unwrap(),
+            menu_add_re: Regex::new(r#"<add\s+([^>]*?)/?>"#).unwrap(),
+            widget_re: Regex::new(r#"(?s)<widget\s+([^>]*)>(.*?)</widget>"#).unwrap(),
+            widget_label_re: Regex::new(r#"<label[^>]*>([^<]*)</label>"#).unwrap(),
+            widget_description_re: Regex::new(r#"<description[^>]*>([^<]*)</description>"#).unwrap(),
+            widget_parameter_re: Regex::new(r#"<parameter\s+name="([^"]+)""#).unwrap(),
+            widget_template_re: Regex::new(r#"value="([^"]+\.phtml)""#).unwrap(),
+            indexer_re: Regex::new(r#"(?s)<indexer\s+([^>]*?)(?:/>|>(.*?)</indexer>)"#).unwrap(),
+            view_re: Regex::new(r#"(?s)<view\s+([^>]*?)(?:/>|>(.*?)</view>)"#).unwrap(),
+            title_re: Regex::new(r#"<title[^>]*>([^<]*)</title>"#).unwrap(),
+            subscription_re: Regex::new(r#"<table\s+([^>]*?)/?>"#).unwrap(),
+            sales_section_re: Regex::new(r#"(?s)<section\s+name="([^"]+)"[^>]*>(.*?)</section>"#).unwrap(),
+            sales_group_re: Regex::new(r#"(?s)<group\s+name="([^"]+)"[^>]*>(.*?)</group>"#).unwrap(),
+            sales_item_re: Regex::new(r#"<item\s+([^>]*?)/?>"#).unwrap(),
+            fieldset_scope_re: Regex::new(r#"(?s)<scope\s+id="([^"]+)"[^>]*>(.*?)</scope>"#).unwrap(),
+            fieldset_re: Regex::new(r#"(?s)<fieldset\s+id="([^"]+)"[^>]*>(.*?)</fieldset>"#).unwrap(),
+            fieldset_field_re: Regex::new(r#"(?s)<field\s+name="([^"]+)"[^>]*?(?:/>|>(.*?)</field>)"#).unwrap(),
+            fieldset_aspect_re: Regex::new(r#"<aspect\s+([^>]*?)/?>"#).unwrap(),
+            pdf_page_re: Regex::new(r#"(?s)<page\s+type="([^"]+)"[^>]*>(.*?)</page>"#).unwrap(),
+            pdf_renderer_re: Regex::new(r#"<renderer\s+product_type="([^"]+)"[^>]*>([^<]*)</renderer>"#).unwrap(),
+            pdf_total_re: Regex::new(r#"(?s)<total\s+name="([^"]+)"[^>]*>(.*?)</total>"#).unwrap(),
+            email_template_re: Regex::new(r#"<template\s+([^>]*?)/?>"#).unwrap(),
+            mftf_re: Regex::new(r#"<(test|actionGroup|entity|section|page|suite)\s+name="([^"]+)""#).unwrap(),
         }
     }
 
@@ -385,18 +1126,54 @@ impl XmlAnalyzer {
             meta.types.push(caps[1].to_string());
         }
 
-        // Plugins — parse <type name="TargetClass"><plugin name=".." type=".." disabled="true"/></type>
-        let attr_re = Regex::new(r#"(\w+)="([^"]*)""#).unwrap();
-        for type_caps in self.type_block_re.captures_iter(content) {
-            let target_class = type_caps[1].to_string();
-            let block_content = &type_caps[2];
+        // <type> and <virtualType> blocks: virtual types, plugins and
+        // constructor arguments. Plugins on a virtual type target its name.
+        for block in self.di_block_re.captures_iter(content) {
+            let attr = |key: &str| {
+                self.attr_re
+                    .captures_iter(&block[2])
+                    .find(|a| &a[1] == key)
+                    .map(|a| a[2].to_string())
+            };
+            let Some(target_class) = attr("name") else { continue };
+            if &block[1] == "virtualType" {
+                meta.virtual_types.push(VirtualTypeDeclaration {
+                    name: target_class.clone(),
+                    base_type: attr("type").unwrap_or_default(),
+                    shared: attr("shared").map(|v| v == "true"),
+                });
+            }
+            let Some(block_content) = block.get(3).map(|m| m.as_str()) else { continue };
+
+            for arg in self.argument_re.captures_iter(block_content) {
+                let mut argument = DiArgument { target: target_class.clone(), ..Default::default() };
+                for a in self.attr_re.captures_iter(&arg[1]) {
+                    match &a[1] {
+                        "name" => argument.name = a[2].to_string(),
+                        "xsi:type" => argument.xsi_type = a[2].to_string(),
+                        "shared" => argument.shared = Some(&a[2] == "true"),
+                        _ => {}
+                    }
+                }
+                let body = arg.get(2).map_or("", |m| m.as_str());
+                if body.contains('<') {
+                    argument.items = self.object_item_re.captures_iter(body).map(|c| c[1].trim().to_string()).collect();
+                } else {
+                    argument.value = body.trim().to_string();
+                }
+                if !argument.name.is_empty() {
+                    meta.arguments.push(argument);
+                }
+            }
+
+            // <plugin name=".." type=".." disabled="true"/>
             for plugin_caps in self.plugin_in_block_re.captures_iter(block_content) {
                 let attrs_str = &plugin_caps[1];
                 let mut decl = PluginDeclaration {
                     target_class: target_class.clone(),
                     ..Default::default()
                 };
-                for attr in attr_re.captures_iter(attrs_str) {
+                for attr in self.attr_re.captures_iter(attrs_str) {
                     match &attr[1] {
                         "name" => decl.name = attr[2].to_string(),
                         "type" => decl.plugin_class = attr[2].to_string(),
@@ -431,6 +1208,211 @@ impl XmlAnalyzer {
             meta.cron_jobs.push((caps[1].to_string(), caps[2].to_string()));
         }
 
+        // Admin menu items: <add id=".." title=".." action=".." resource=".."/>
+        if content.contains("<menu") {
+            for caps in self.menu_add_re.captures_iter(content) {
+                let mut item = MenuItem::default();
+                for attr in self.attr_re.captures_iter(&caps[1]) {
+                    let value = attr[2].to_string();
+                    match &attr[1] {
+                        "id" => item.id = value,
+                        "title" => item.title = value,
+                        "module" => item.module = value,
+                        "action" => item.action = Some(value),
+                        "resource" => item.resource = value,
+                        "parent" => item.parent = Some(value),
+                        _ => {}
+                    }
+                }
+                if !item.id.is_empty() {
+                    meta.menu_items.push(item);
+                }
+            }
+        }
+
+        // Magento indexers: <indexer id=".." view_id=".." class=".."><title/></indexer>
+        if content.contains("<indexer ") {
+            for caps in self.indexer_re.captures_iter(content) {
+                let mut indexer = IndexerDeclaration::default();
+                for attr in self.attr_re.captures_iter(&caps[1]) {
+                    let value = attr[2].to_string();
+                    match &attr[1] {
+                        "id" => indexer.id = value,
+                        "view_id" => indexer.view_id = Some(value),
+                        "class" => indexer.class = value,
+                        "shared_index" => indexer.shared_index = Some(value),
+                        _ => {}
+                    }
+                }
+                let body = caps.get(2).map_or("", |m| m.as_str());
+                let text = |re: &Regex| re.captures(body).map(|c| c[1].trim().to_string()).unwrap_or_default();
+                indexer.title = text(&self.title_re);
+                indexer.description = text(&self.widget_description_re);
+                if !indexer.id.is_empty() {
+                    meta.indexers.push(indexer);
+                }
+            }
+        }
+
+        // Materialized views: <view id=".." class=".."><subscriptions><table/></subscriptions></view>
+        if content.contains("Mview/etc/mview.xsd") || content.contains("<subscriptions") {
+            for caps in self.view_re.captures_iter(content) {
+                let mut view = MviewDeclaration::default();
+                for attr in self.attr_re.captures_iter(&caps[1]) {
+                    let value = attr[2].to_string();
+                    match &attr[1] {
+                        "id" => view.id = value,
+                        "class" => view.class = value,
+                        "group" => view.group = value,
+                        _ => {}
+                    }
+                }
+                let body = caps.get(2).map_or("", |m| m.as_str());
+                for table in self.subscription_re.captures_iter(body) {
+                    let attr = |key: &str| {
+                        self.attr_re.captures_iter(&table[1]).find(|a| &a[1] == key).map(|a| a[2].to_string())
+                    };
+                    if let Some(name) = attr("name") {
+                        view.subscriptions.push((name, attr("entity_column").unwrap_or_default()));
+                    }
+                }
+                if !view.id.is_empty() {
+                    meta.mviews.push(view);
+                }
+            }
+        }
+
+        // sales.xml: <section name="quote"><group name="totals"><item name=".." instance=".." sort_order=".."/>
+        if content.contains("<section name=") && content.contains("<group name=") {
+            for section in self.sales_section_re.captures_iter(content) {
+                for group in self.sales_group_re.captures_iter(&section[2]) {
+                    for item in self.sales_item_re.captures_iter(&group[2]) {
+                        let attr = |key: &str| {
+                            self.attr_re.captures_iter(&item[1]).find(|a| &a[1] == key).map(|a| a[2].to_string())
+                        };
+                        let Some(code) = attr("name") else { continue };
+                        meta.total_collectors.push(TotalCollector {
+                            section: section[1].to_string(),
+                            group: group[1].to_string(),
+                            code,
+                            class: attr("instance").unwrap_or_default(),
+                            sort_order: attr("sort_order").and_then(|v| v.parse().ok()),
+                        });
+                    }
+                }
+            }
+        }
+
+        // fieldset.xml: <scope id><fieldset id><field name><aspect name targetField/>
+        if content.contains("<aspect ") {
+            for scope in self.fieldset_scope_re.captures_iter(content) {
+                for fieldset in self.fieldset_re.captures_iter(&scope[2]) {
+                    for field in self.fieldset_field_re.captures_iter(&fieldset[2]) {
+                        let body = field.get(2).map_or("", |m| m.as_str());
+                        for aspect in self.fieldset_aspect_re.captures_iter(body) {
+                            let attr = |key: &str| {
+                                self.attr_re.captures_iter(&aspect[1]).find(|a| &a[1] == key).map(|a| a[2].to_string())
+                            };
+                            let Some(name) = attr("name") else { continue };
+                            meta.fieldset_rules.push(FieldsetCopyRule {
+                                scope: scope[1].to_string(),
+                                fieldset: fieldset[1].to_string(),
+                                field: field[1].to_string(),
+                                aspect: name,
+                                target_field: attr("targetField"),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        // pdf.xml: item renderers per document page, and total lines
+        if content.contains("<renderers>") || content.contains("<source_field>") {
+            for page in self.pdf_page_re.captures_iter(content) {
+                for renderer in self.pdf_renderer_re.captures_iter(&page[2]) {
+                    meta.pdf_renderers.push(PdfRenderer {
+                        page: page[1].to_string(),
+                        product_type: renderer[1].to_string(),
+                        class: renderer[2].trim().to_string(),
+                    });
+                }
+            }
+            for total in self.pdf_total_re.captures_iter(content) {
+                let body = &total[2];
+                let element = |tag: &str| {
+                    let open = format!("<{}", tag);
+                    let start = body.find(&open)?;
+                    let start = start + body[start..].find('>')? + 1;
+                    let end = start + body[start..].find('<')?;
+                    Some(body[start..end].trim().to_string())
+                };
+                meta.pdf_totals.push(PdfTotal {
+                    name: total[1].to_string(),
+                    title: element("title").unwrap_or_default(),
+                    source_field: element("source_field").unwrap_or_default(),
+                    model: element("model").filter(|m| !m.is_empty()),
+                    sort_order: element("sort_order").and_then(|v| v.parse().ok()),
+                });
+            }
+        }
+
+        // email_templates.xml: <template id label file type module area/>
+        if content.contains("<template id=") {
+            for template in self.email_template_re.captures_iter(content) {
+                let attr = |key: &str| {
+                    self.attr_re
+                        .captures_iter(&template[1])
+                        .find(|a| &a[1] == key)
+                        .map(|a| a[2].to_string())
+                        .unwrap_or_default()
+                };
+                let id = attr("id");
+                if id.is_empty() {
+                    continue;
+                }
+                meta.email_templates.push(EmailTemplate {
+                    id,
+                    label: attr("label"),
+                    file: attr("file"),
+                    module: attr("module"),
+                    area: attr("area"),
+                });
+            }
+        }
+
+        // MFTF declarations, recognized by their schema URNs
+        if content.contains("urn:magento:mftf:") {
+            for caps in self.mftf_re.captures_iter(content) {
+                meta.mftf_entities.push((caps[1].to_string(), caps[2].to_string()));
+            }
+        }
+
+        // CMS widget types: <widget id=".." class=".."><label/><parameters/></widget>
+        if content.contains("<widgets") {
+            for caps in self.widget_re.captures_iter(content) {
+                let mut widget = WidgetDeclaration::default();
+                for attr in self.attr_re.captures_iter(&caps[1]) {
+                    match &attr[1] {
+                        "id" => widget.id = attr[2].to_string(),
+                        "class" => widget.class = attr[2].to_string(),
+                        _ => {}
+                    }
+                }
+                let body = &caps[2];
+                // The first label/description belong to the widget, later
+                // ones to its parameters
+                let text = |re: &Regex| re.captures(body).map(|c| c[1].trim().to_string()).unwrap_or_default();
+                widget.label = text(&self.widget_label_re);
+                widget.description = text(&self.widget_description_re);
+                widget.parameters = self.widget_parameter_re.captures_iter(body).map(|c| c[1].to_string()).collect();
+                widget.templates = self.widget_template_re.captures_iter(body).map(|c| c[1].to_string()).collect();
+                if !widget.id.is_empty() {
+                    meta.widgets.push(widget);
+                }
+            }
+        }
+
         meta
     }
 }
@@ -446,10 +1428,55 @@ pub struct XmlMetadata {
     pub preferences: Vec<(String, String)>,
     pub types: Vec<String>,
     pub plugins: Vec<PluginDeclaration>,
+    pub virtual_types: Vec<VirtualTypeDeclaration>,
+    /// Constructor argument overrides on types and virtual types
+    pub arguments: Vec<DiArgument>,
     pub events: Vec<String>,
     pub routes: Vec<(String, String)>,
     pub tables: Vec<String>,
     pub cron_jobs: Vec<(String, String)>,
+    pub menu_items: Vec<MenuItem>,
+    pub widgets: Vec<WidgetDeclaration>,
+    /// MFTF (kind, name) pairs: tests, action groups, data entities, sections, pages
+    pub mftf_entities: Vec<(String, String)>,
+    pub indexers: Vec<IndexerDeclaration>,
+    pub mviews: Vec<MviewDeclaration>,
+    pub total_collectors: Vec<TotalCollector>,
+    pub fieldset_rules: Vec<FieldsetCopyRule>,
+    pub pdf_renderers: Vec<PdfRenderer>,
+    pub pdf_totals: Vec<PdfTotal>,
+    pub email_templates: Vec<EmailTemplate>,
+}
+
+impl XmlMetadata {
+    /// Class the object manager instantiates for `name`, following
+    /// preferences (the last declaration wins) and virtual types down to a
+    /// concrete class. `None` if neither applies to `name`.
+    pub fn resolve_preference(&self, name: &str) -> Option<String> {
+        let name = name.trim_start_matches('\\');
+        let mut current = name.to_string();
+        let mut seen = std::collections::HashSet::from([current.clone()]);
+        loop {
+            let next = self
+                .preferences
+                .iter()
+                .rev()
+                .find(|(from, _)| from.trim_start_matches('\\') == current)
+                .map(|(_, to)| to)
+                .or_else(|| {
+                    self.virtual_types
+                        .iter()
+                        .find(|vt| vt.name == current && !vt.base_type.is_empty())
+                        .map(|vt| &vt.base_type)
+                });
+            // A cycle is a config error; stop at the last class before it
+            match next.map(|n| n.trim_start_matches('\\').to_string()) {
+                Some(next) if seen.insert(next.clone()) => current = next,
+                _ => break,
+            }
+        }
+        (current != name).then_some(current)
+    }
 }
 
 /// Generate searchable text from code
@@ -512,12 +1539,54 @@ pub fn generate_search_text(
                 terms.push(format!("disabled plugin {}", plugin.name));
             }
         }
+        for vt in &xml.virtual_types {
+            terms.push(format!("virtualType {} {} {}", vt.name, split_camel_case(vt.name.rsplit('\\').next().unwrap_or(&vt.name)), vt.base_type));
+        }
+        for arg in xml.arguments.iter().filter(|a| a.is_proxy()) {
+            terms.push(format!("proxy {} {}", arg.target, arg.value));
+        }
         for event in &xml.events {
             terms.push(format!("event {}", event.replace('_', " ")));
         }
         for table in &xml.tables {
             terms.push(format!("table {}", table.replace('_', " ")));
         }
+        for item in &xml.menu_items {
+            terms.push(format!("admin menu {} {}", item.title, item.id));
+            if let Some(ref action) = item.action {
+                terms.push(format!("action {}", action));
+            }
+        }
+        for indexer in &xml.indexers {
+            terms.push(format!("indexer {} {} {} reindex", indexer.id, indexer.title, indexer.class));
+        }
+        for view in &xml.mviews {
+            terms.push(format!("mview view {} {} changelog subscription", view.id, view.class));
+            for (table, _) in &view.subscriptions {
+                terms.push(format!("table {}", table));
+            }
+        }
+        for widget in &xml.widgets {
+            terms.push(format!("cms widget {} {} {} {}", widget.id, widget.label, widget.class, widget.templates.join(" ")));
+        }
+        for collector in &xml.total_collectors {
+            terms.push(format!(
+                "total collector {} {} totals {} collect sort_order",
+                collector.section, collector.code, collector.class
+            ));
+        }
+        for rule in &xml.fieldset_rules {
+            terms.push(format!("fieldset copy {} field {} {}", rule.fieldset, rule.field, rule.aspect));
+        }
+        for renderer in &xml.pdf_renderers {
+            terms.push(format!("pdf {} renderer {} {}", renderer.page, renderer.product_type, renderer.class));
+        }
+        for total in &xml.pdf_totals {
+            terms.push(format!("pdf total {} {} {}", total.name, total.title, total.source_field));
+        }
+        for template in &xml.email_templates {
+            terms.push(format!("email template {} {} {}", template.id, template.label, template.file));
+        }
     }
 
     terms.join(" ")
@@ -660,10 +1729,10 @@ impl SetupAnalyzer {
 
             // Also extract SQL from addStatement calls (multiline)
             let stmt_re = Regex::new(r"(?s)addStatement\s*\(\s*'(.*?)'").unwrap();
+            let sql_table_re = Regex::new(r"(?i)\b(?:from|into|update|join|table)\s+`?(\w+)`?").unwrap();
             for caps in stmt_re.captures_iter(content) {
                 let sql = caps[1].to_string();
                 // Extract table names from SQL statements
-                let sql_table_re = Regex::new(r"(?i)\b(?:from|into|update|join|table)\s+`?(\w+)`?").unwrap();
                 for tcaps in sql_table_re.captures_iter(&sql) {
                     let tbl = tcaps[1].to_string();
                     if tbl != "as" && tbl != "set" && tbl != "where" && !meta.table_references.contains(&tbl) {
@@ -796,6 +1865,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_find_magento_root() {
+        let root = std::env::temp_dir().join(format!("magector_root_{}", std::process::id()));
+        let module = root.join("app/code/Vendor/Pay");
+        std::fs::create_dir_all(&module).unwrap();
+        std::fs::write(
+            root.join("composer.json"),
+            r#"{"require":{"magento/product-community-edition":"2.4.7"}}"#,
+        )
+        .unwrap();
+        // Module composer.json requires the framework but has no app/ tree
+        std::fs::write(module.join("composer.json"), r#"{"require":{"magento/framework":"*"}}"#).unwrap();
+
+        assert!(!is_magento_root(&module));
+        assert_eq!(find_magento_root(&module), Some(root.clone()));
+        assert_eq!(find_magento_root(&root), Some(root.clone()));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_module_names_for_path() {
+        assert_eq!(
+            module_names_for_path("app/code/Vendor/Payment/Model/Pay.php"),
+            vec!["Vendor_Payment"]
+        );
+        assert_eq!(
+            module_names_for_path("vendor/magento/module-checkout/Model/Session.php"),
+            vec!["Magento_Checkout", "magento_module-checkout"]
+        );
+        assert_eq!(
+            module_names_for_path("vendor/magento/module-catalog-search/etc/di.xml")[0],
+            "Magento_CatalogSearch"
+        );
+        assert_eq!(
+            module_names_for_path("lib/internal/Magento/Framework/App/Http.php"),
+            vec!["Magento_Framework"]
+        );
+        assert!(module_names_for_path("pub/index.php").is_empty());
+    }
+
     #[test]
     fn test_detect_graphql_resolver_under_model() {
         // Resolvers under Model/Resolver/ in graph-ql modules must be detected
@@ -1088,4 +2198,323 @@ mod tests {
         assert!(text.contains("disabled plugin my_plugin"),
             "Search text should indicate disabled plugin, got: {}", text);
     }
+
+    #[test]
+    fn test_xml_analyzer_virtual_types_and_arguments() {
+        let analyzer = XmlAnalyzer::new();
+        let xml = r#"<config>
+            <preference for="Vendor\Api\LoggerInterface" type="Vendor\Logger\Custom"/>
+            <virtualType name="Vendor\Logger\Custom" type="Monolog\Logger">
+                <arguments>
+                    <argument name="name" xsi:type="string">custom</argument>
+                    <argument name="handlers" xsi:type="array">
+                        <item name="system" xsi:type="object">Vendor\Logger\Handler</item>
+                    </argument>
+                </arguments>
+                <plugin name="log_context" type="Vendor\Plugin\LogContext" sortOrder="5"/>
+            </virtualType>
+            <virtualType name="Vendor\Pool" type="Vendor\Model\Pool" shared="false"/>
+            <type name="Vendor\Model\Importer">
+                <arguments>
+                    <argument name="session" xsi:type="object" shared="false">Magento\Customer\Model\Session\Proxy</argument>
+                </arguments>
+            </type>
+        </config>"#;
+        let meta = analyzer.analyze(xml);
+
+        assert_eq!(meta.virtual_types.len(), 2);
+        assert_eq!(meta.virtual_types[0].base_type, "Monolog\\Logger");
+        assert_eq!(meta.virtual_types[1].shared, Some(false));
+
+        assert_eq!(meta.plugins.len(), 1);
+        assert_eq!(meta.plugins[0].target_class, "Vendor\\Logger\\Custom");
+
+        assert_eq!(meta.arguments.len(), 3);
+        assert_eq!(meta.arguments[0].value, "custom");
+        assert_eq!(meta.arguments[1].items, vec!["Vendor\\Logger\\Handler"]);
+        let session = &meta.arguments[2];
+        assert_eq!(session.target, "Vendor\\Model\\Importer");
+        assert_eq!(session.shared, Some(false));
+        assert!(session.is_proxy());
+
+        assert_eq!(meta.resolve_preference("\\Vendor\\Api\\LoggerInterface").as_deref(), Some("Monolog\\Logger"));
+        assert_eq!(meta.resolve_preference("Vendor\\Pool").as_deref(), Some("Vendor\\Model\\Pool"));
+        assert_eq!(meta.resolve_preference("Vendor\\Model\\Importer"), None);
+
+        let text = generate_search_text("", None, Some(&meta));
+        assert!(text.contains("virtualType Vendor\\Logger\\Custom"), "got: {}", text);
+        assert!(text.contains("proxy Vendor\\Model\\Importer"), "got: {}", text);
+    }
+
+    #[test]
+    fn test_resolve_preference_cycle() {
+        let meta = XmlMetadata {
+            preferences: vec![("A".to_string(), "B".to_string())],
+            virtual_types: vec![VirtualTypeDeclaration { name: "B".to_string(), base_type: "A".to_string(), shared: None }],
+            ..Default::default()
+        };
+        assert_eq!(meta.resolve_preference("A").as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn test_menu_items_and_admin_controllers() {
+        let analyzer = XmlAnalyzer::new();
+        let xml = r#"<config><menu>
+            <add id="Magento_Backend::system_cache" title="Cache Management" translate="title" module="Magento_Backend"
+                 sortOrder="20" parent="Magento_Backend::system_tools" action="adminhtml/cache" resource="Magento_Backend::cache"/>
+            <add id="Vendor_Module::items" title="Items" module="Vendor_Module" resource="Vendor_Module::items"/>
+            <add id="Vendor_Module::item_new" title="New Item" module="Vendor_Module" parent="Vendor_Module::items"
+                 action="vendor/item_import/start/" resource="Vendor_Module::items"/>
+        </menu></config>"#;
+        let meta = analyzer.analyze(xml);
+        assert_eq!(meta.menu_items.len(), 3);
+        let cache = &meta.menu_items[0];
+        assert_eq!(cache.title, "Cache Management");
+        assert_eq!(cache.resource, "Magento_Backend::cache");
+        assert_eq!(cache.parent.as_deref(), Some("Magento_Backend::system_tools"));
+        assert_eq!(cache.controller_action().as_deref(), Some("cache/index"));
+        assert_eq!(meta.menu_items[1].controller_action(), None);
+        assert_eq!(meta.menu_items[2].controller_action().as_deref(), Some("item_import/start"));
+
+        // <add> outside a menu is not a menu item
+        assert!(analyzer.analyze(r#"<config><add id="x" title="y"/></config>"#).menu_items.is_empty());
+
+        assert_eq!(
+            admin_controller_action("app/code/Vendor/Module/Controller/Adminhtml/Item/Import/Start.php").as_deref(),
+            Some("item_import/start")
+        );
+        assert_eq!(admin_controller_action("app/code/Vendor/Module/Controller/Index/Index.php"), None);
+
+        let mut menu = AdminMenu::default();
+        menu.extend(meta.menu_items);
+        let items = menu.items_for_controller("vendor/magento/module-backend/Controller/Adminhtml/Cache/Index.php");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, "Magento_Backend::system_cache");
+        assert!(menu.items_for_controller("vendor/magento/module-backend/Controller/Adminhtml/Cache/Flush.php").is_empty());
+    }
+
+    #[test]
+    fn test_widget_registry() {
+        let analyzer = XmlAnalyzer::new();
+        let xml = r#"<widgets xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+            <widget id="products_list" class="Magento\CatalogWidget\Block\Product\ProductsList" is_email_compatible="true">
+                <label translate="true">Catalog Products List</label>
+                <description translate="true">List of Products</description>
+                <parameters>
+                    <parameter name="title" xsi:type="text" visible="true">
+                        <label translate="true">Title</label>
+                    </parameter>
+                    <parameter name="template" xsi:type="select" visible="true">
+                        <options>
+                            <option name="default" value="Magento_CatalogWidget::product/widget/content/grid.phtml" selected="true">
+                                <label translate="true">Products Grid Template</label>
+                            </option>
+                        </options>
+                    </parameter>
+                </parameters>
+            </widget>
+        </widgets>"#;
+        let meta = analyzer.analyze(xml);
+        assert_eq!(meta.widgets.len(), 1);
+        let widget = &meta.widgets[0];
+        assert_eq!(widget.id, "products_list");
+        assert_eq!(widget.label, "Catalog Products List");
+        assert_eq!(widget.description, "List of Products");
+        assert_eq!(widget.parameters, vec!["title", "template"]);
+        assert_eq!(widget.templates, vec!["Magento_CatalogWidget::product/widget/content/grid.phtml"]);
+
+        let mut registry = WidgetRegistry::default();
+        registry.extend(meta.widgets);
+        assert_eq!(registry.widgets_for_class("\\Magento\\CatalogWidget\\Block\\Product\\ProductsList").len(), 1);
+        assert_eq!(
+            registry
+                .widgets_for_template("vendor/magento/module-catalog-widget/view/frontend/templates/product/widget/content/grid.phtml")
+                .len(),
+            1
+        );
+        assert!(registry.widgets_for_template("app/design/frontend/Vendor/theme/templates/other.phtml").is_empty());
+    }
+
+    #[test]
+    fn test_indexer_registry() {
+        let analyzer = XmlAnalyzer::new();
+        let indexer_xml = r#"<config xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:noNamespaceSchemaLocation="urn:magento:framework:Indexer/etc/indexer.xsd">
+            <indexer id="catalog_product_price" view_id="catalog_product_price" class="Magento\Catalog\Model\Indexer\Product\Price">
+                <title translate="true">Product Price</title>
+                <description translate="true">Index product prices</description>
+            </indexer>
+            <indexer id="inventory" view_id="inventory" class="Magento\InventoryIndexer\Indexer\InventoryIndexer" shared_index="cataloginventory_stock"/>
+        </config>"#;
+        let mview_xml = r#"<config xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:noNamespaceSchemaLocation="urn:magento:framework:Mview/etc/mview.xsd">
+            <view id="catalog_product_price" class="Magento\Catalog\Model\Indexer\Product\Price" group="indexer">
+                <subscriptions>
+                    <table name="catalog_product_entity" entity_column="entity_id" />
+                    <table name="catalog_product_entity_decimal" entity_column="entity_id" />
+                </subscriptions>
+            </view>
+            <view id="orphan_view" class="Vendor\Module\Model\Indexer\Orphan" group="indexer">
+                <subscriptions>
+                    <table name="vendor_table" entity_column="id" />
+                </subscriptions>
+            </view>
+        </config>"#;
+        let indexers = analyzer.analyze(indexer_xml);
+        assert_eq!(indexers.indexers.len(), 2);
+        assert_eq!(indexers.indexers[0].title, "Product Price");
+        assert_eq!(indexers.indexers[0].description, "Index product prices");
+        assert_eq!(indexers.indexers[1].shared_index.as_deref(), Some("cataloginventory_stock"));
+        let views = analyzer.analyze(mview_xml);
+        assert_eq!(views.mviews.len(), 2);
+        assert_eq!(views.mviews[0].group, "indexer");
+        assert_eq!(
+            views.mviews[0].subscriptions[0],
+            ("catalog_product_entity".to_string(), "entity_id".to_string())
+        );
+
+        let mut registry = IndexerRegistry::default();
+        registry.extend(indexers.indexers, Vec::new(), "Magento/Catalog/etc/indexer.xml");
+        registry.extend(Vec::new(), views.mviews, "Magento/Catalog/etc/mview.xml");
+        let entries = registry.entries(None);
+        assert_eq!(entries.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), vec!["catalog_product_price", "inventory", "orphan_view"]);
+        let price = &entries[0];
+        assert_eq!(price.mview_class.as_deref(), Some("Magento\\Catalog\\Model\\Indexer\\Product\\Price"));
+        assert_eq!(price.subscribed_tables, vec!["catalog_product_entity", "catalog_product_entity_decimal"]);
+        assert_eq!(price.files.len(), 2);
+        assert!(entries[2].action_class.is_none());
+
+        let filtered = registry.entries(Some("PRODUCT_ENTITY_DECIMAL"));
+        assert_eq!(filtered.len(), 1);
+        registry.index_classes();
+        assert_eq!(registry.entries_for_class("\\Magento\\Catalog\\Model\\Indexer\\Product\\Price").len(), 1);
+        assert!(registry.entries_for_class("Magento\\Catalog\\Model\\Product").is_empty());
+    }
+
+    #[test]
+    fn test_sales_config() {
+        let analyzer = XmlAnalyzer::new();
+        let sales = analyzer.analyze(r#"<config xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:noNamespaceSchemaLocation="urn:magento:module:Magento_Sales:etc/sales.xsd">
+            <section name="quote">
+                <group name="totals">
+                    <item name="subtotal" instance="Magento\Quote\Model\Quote\Address\Total\Subtotal" sort_order="100"/>
+                    <item name="grand_total" instance="Magento\Quote\Model\Quote\Address\Total\Grand" sort_order="550">
+                        <renderer name="adminhtml" instance="Magento\Sales\Block\Adminhtml\Order\Create\Totals\Grandtotal"/>
+                    </item>
+                </group>
+            </section>
+            <section name="order_invoice">
+                <group name="totals">
+                    <item name="tax" sort_order="300"/>
+                </group>
+            </section>
+        </config>"#);
+        assert_eq!(sales.total_collectors.len(), 3);
+        assert_eq!(sales.total_collectors[1].code, "grand_total");
+        assert_eq!(sales.total_collectors[1].sort_order, Some(550));
+        assert_eq!(sales.total_collectors[2].section, "order_invoice");
+        assert!(sales.total_collectors[2].class.is_empty());
+
+        let fieldset = analyzer.analyze(r#"<config>
+            <scope id="global">
+                <fieldset id="sales_convert_quote">
+                    <field name="customer_email">
+                        <aspect name="to_order" />
+                    </field>
+                    <field name="entity_id">
+                        <aspect name="to_order" targetField="quote_id" />
+                    </field>
+                </fieldset>
+            </scope>
+        </config>"#);
+        assert_eq!(fieldset.fieldset_rules.len(), 2);
+        assert_eq!(fieldset.fieldset_rules[0].fieldset, "sales_convert_quote");
+        assert_eq!(fieldset.fieldset_rules[0].aspect, "to_order");
+        assert_eq!(fieldset.fieldset_rules[1].target_field.as_deref(), Some("quote_id"));
+
+        let pdf = analyzer.analyze(r#"<config>
+            <renderers>
+                <page type="invoice">
+                    <renderer product_type="default">Magento\Sales\Model\Order\Pdf\Items\Invoice\DefaultInvoice</renderer>
+                </page>
+            </renderers>
+            <totals>
+                <total name="subtotal">
+                    <title translate="true">Subtotal</title>
+                    <source_field>subtotal</source_field>
+                    <sort_order>100</sort_order>
+                </total>
+            </totals>
+        </config>"#);
+        assert_eq!(pdf.pdf_renderers[0].page, "invoice");
+        assert_eq!(pdf.pdf_renderers[0].class, "Magento\\Sales\\Model\\Order\\Pdf\\Items\\Invoice\\DefaultInvoice");
+        assert_eq!(pdf.pdf_totals[0].title, "Subtotal");
+        assert_eq!(pdf.pdf_totals[0].source_field, "subtotal");
+        assert_eq!(pdf.pdf_totals[0].sort_order, Some(100));
+
+        let email = analyzer.analyze(r#"<config>
+            <template id="sales_email_order_template" label="New Order" file="order_new.html" type="html" module="Magento_Sales" area="frontend"/>
+        </config>"#);
+        assert_eq!(email.email_templates[0].label, "New Order");
+        assert_eq!(email.email_templates[0].module, "Magento_Sales");
+
+        assert_eq!(detect_file_type("app/code/Magento/Sales/etc/pdf.xml"), MagentoFileType::SalesConfig);
+        assert_eq!(detect_file_type("app/code/Magento/Quote/etc/fieldset.xml"), MagentoFileType::SalesConfig);
+    }
+
+    #[test]
+    fn test_is_generated_code() {
+        let interceptor = "<?php\nnamespace Magento\\Catalog\\Model\\Product;\n\n/**\n * Interceptor class for @see \\Magento\\Catalog\\Model\\Product\n */\nclass Interceptor extends \\Magento\\Catalog\\Model\\Product\n{\n}\n";
+        assert!(is_generated_code("vendor/acme/build/Product/Interceptor.php", Some("Interceptor"), interceptor));
+        assert!(is_generated_code("generated/code/Magento/Catalog/Model/ProductFactory.php", Some("ProductFactory"), ""));
+
+        let factory = "/**\n * Factory class for @see \\Acme\\Foo\\Model\\Bar\n */\nclass BarFactory\n";
+        assert!(is_generated_code("app/code/Acme/Foo/Model/BarFactory.php", Some("BarFactory"), factory));
+        // Hand-written factories and classes that only mention the marker
+        let handwritten = "/**\n * Creates bars\n */\nclass BarFactory\n";
+        assert!(!is_generated_code("app/code/Acme/Foo/Model/BarFactory.php", Some("BarFactory"), handwritten));
+        assert!(!is_generated_code("app/code/Acme/Foo/Model/Bar.php", Some("Bar"), factory));
+    }
+
+    #[test]
+    fn test_mftf_files() {
+        let path = "app/code/Acme/Foo/Test/Mftf/ActionGroup/AdminOpenFooActionGroup.xml";
+        assert_eq!(detect_file_type(path), MagentoFileType::MftfTest);
+        assert_eq!(detect_file_type("app/code/Acme/Foo/etc/di.xml"), MagentoFileType::DiConfig);
+
+        let xml = r#"<actionGroups xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+                xsi:noNamespaceSchemaLocation="urn:magento:mftf:Test/etc/actionGroupSchema.xsd">
+            <actionGroup name="AdminOpenFooActionGroup">
+                <amOnPage url="{{AdminFooPage.url}}" stepKey="openPage"/>
+            </actionGroup>
+        </actionGroups>"#;
+        let meta = XmlAnalyzer::new().analyze(xml);
+        assert_eq!(meta.mftf_entities, vec![("actionGroup".to_string(), "AdminOpenFooActionGroup".to_string())]);
+    }
+
+    #[test]
+    fn test_requirejs_configs_merge_in_module_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |rel: &str, content: &str| {
+            let path = dir.path().join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+        let mixin = |enabled: bool| {
+            format!("var config = {{ config: {{ mixins: {{ 'Magento_Checkout/js/view/shipping': {{ 'Zed_Cart/js/shipping-mixin': {} }} }} }} }};", enabled)
+        };
+        // Acme_Fix sorts first by path but loads after Zed_Cart, so its switch-off wins
+        let paths = [
+            write("app/code/Acme/Fix/etc/module.xml", r#"<config><module name="Acme_Fix"><sequence><module name="Zed_Cart"/></sequence></module></config>"#),
+            write("app/code/Acme/Fix/view/frontend/requirejs-config.js", &mixin(false)),
+            write("vendor/zed/module-cart/etc/module.xml", r#"<config><module name="Zed_Cart"/></config>"#),
+            write("vendor/zed/module-cart/view/frontend/requirejs-config.js", &mixin(true)),
+        ];
+        assert!(paths.iter().all(|p| ConfigXref::is_source(&crate::vectordb::relative_path(dir.path(), p))));
+
+        let xref = ConfigXref::from_files(dir.path(), paths.iter().map(PathBuf::as_path), &XmlAnalyzer::new());
+        let mixins = xref.js_components.mixins_for_component("Magento_Checkout/js/view/shipping");
+        assert_eq!(mixins.len(), 1);
+        assert!(!mixins[0].enabled);
+        assert_eq!(mixins[0].files[1], "app/code/Acme/Fix/view/frontend/requirejs-config.js");
+    }
 }
@@ -2,20 +2,54 @@
 
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
+use std::collections::HashMap;
 use std::fs;
 use std::io::{self, BufRead, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use magector_core::{Indexer, VectorDB, Embedder, Validator, WatcherStatus, EMBEDDING_DIM};
+use magector_core::{Collection, Indexer, VectorDB, Embedder, ResultFilter, ResultOrder, Validator, WatcherStatus, EMBEDDING_DIM};
+use magector_core::backup;
+use magector_core::codemap;
+use magector_core::config::{IndexConfig, ProjectConfig};
 use magector_core::datadb::DataDb;
+use magector_core::embedder::{Pooling, DEFAULT_MODEL};
+use magector_core::delta::DeltaPatch;
+use magector_core::fsck;
+use magector_core::indexer::CancelToken;
+use magector_core::magento::{ConfigXref, IndexerEntry, XmlAnalyzer};
+use magector_core::migrate;
+use magector_core::path_guard::ReadScope;
+use magector_core::payment::PaymentMethod;
+use magector_core::console::ConsoleCommand;
+use magector_core::preflight;
+use magector_core::probe::{self, ProbeHistory};
+use magector_core::shipping::Carrier;
+use magector_core::project::ProjectInfo;
+use magector_core::session::Sessions;
+use magector_core::queue::{QueueRoute, QueueTopology};
+use magector_core::signing;
+use magector_core::snapshot;
+use magector_core::totals::{CollectorStep, TotalsConfig};
+use magector_core::vectordb::{relative_path, ExtraRoot, RootMap};
 
 const MAGENTO2_REPO: &str = "https://github.com/magento/magento2.git";
 const MAGENTO2_TAG: &str = "2.4.7"; // Latest stable version
 
+/// `serve --tune-ef` on an index without files to generate queries from
+/// uses every n-th built-in validation query
+const EF_TUNING_STRIDE: usize = 5;
+
+/// Queries `serve --tune-ef` generates from the indexed files
+const EF_TUNING_QUERIES: usize = 40;
+
+/// `serve --tune-ef` re-tunes once the index has grown past this multiple
+/// of its size at the last tuning
+const EF_RETUNE_GROWTH: f32 = 1.5;
+
 #[derive(Parser)]
 #[command(name = "magector")]
 #[command(about = "Magento source code indexer with semantic search")]
@@ -33,13 +67,14 @@ struct Cli {
 enum Commands {
     /// Index a Magento codebase
     Index {
-        /// Path to Magento root directory
+        /// Path to Magento root directory (default: nearest parent of the
+        /// current directory containing app/etc/env.php or a Magento composer.json)
         #[arg(short, long)]
-        magento_root: PathBuf,
+        magento_root: Option<PathBuf>,
 
-        /// Path to store the index database
-        #[arg(short, long, default_value = "./.magector/index.db")]
-        database: PathBuf,
+        /// Path to store the index database (default: <magento_root>/.magector/index.db)
+        #[arg(short, long)]
+        database: Option<PathBuf>,
 
         /// Path to cache embedding model
         #[arg(short = 'c', long, default_value = "./models")]
@@ -61,6 +96,120 @@ enum Commands {
         /// Without this flag, indexing auto-resumes from the previous run.
         #[arg(long)]
         force: bool,
+
+        /// Reproducible output: embed and insert items in path order so
+        /// identical sources produce identical DB bytes. Implies --force.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        deterministic: Option<bool>,
+
+        /// Re-embed only files whose content changed since the last run:
+        /// files touched without changes (branch switches, deploy copies)
+        /// are recognized by the content hashes in the manifest
+        #[arg(long, conflicts_with_all = ["force", "deterministic"])]
+        incremental: bool,
+
+        /// Index only modules matching these globs (comma-separated,
+        /// e.g. Magento_Checkout,Vendor_*). Recorded in the index header.
+        #[arg(long, value_delimiter = ',')]
+        only_modules: Vec<String>,
+
+        /// Follow symlinked directories (modman / dev setups). Cycles and
+        /// duplicate links are detected, so each module is indexed once.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        follow_symlinks: Option<bool>,
+
+        /// Also index MFTF tests, action groups and data fixtures
+        /// (`Test/Mftf/**/*.xml`). Unit and integration tests stay excluded.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        include_tests: Option<bool>,
+
+        /// Also index Markdown docs outside vendor/ (dev docs, ADRs, module
+        /// READMEs) into the `docs` collection, searchable with
+        /// `search --namespace docs`. Recorded in the index header.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        include_docs: Option<bool>,
+
+        /// Also skip what the root .gitignore ignores (.magectorignore is
+        /// always applied and can re-include with `!`, e.g. `!/vendor/`).
+        /// Recorded in the index header.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        gitignore: Option<bool>,
+
+        /// Pre-built read-only core index (e.g. vendor/magento) to layer
+        /// this index over: files it covers are not indexed locally, and
+        /// searches merge both. Recorded in the index header.
+        #[arg(long)]
+        core_index: Option<PathBuf>,
+
+        /// Also index a source tree outside the Magento root (e.g. a private
+        /// extension checkout) into the same database ([NAME=]PATH,
+        /// repeatable; NAME defaults to the directory name). Results show
+        /// the root they come from. Recorded in the index header; runs
+        /// without it keep the recorded roots.
+        #[arg(long, value_name = "[NAME=]PATH")]
+        extra_root: Vec<ExtraRoot>,
+
+        /// Drop the extra roots recorded in the index header
+        #[arg(long, conflicts_with = "extra_root")]
+        no_extra_roots: bool,
+
+        /// Also save the finished index as snapshot TAG (e.g. a release or
+        /// commit) under snapshots/ next to the database
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Pooling over token embeddings: cls, mean or max (default: the
+        /// one recorded in the index, else mean). Changing it rebuilds.
+        #[arg(long)]
+        pooling: Option<Pooling>,
+
+        /// Build the search graph from the first N dimensions of each vector
+        /// and rerank candidates with the full vector (e.g. 128; 0 = off).
+        /// For Matryoshka-trained models; default: the value in the index.
+        #[arg(long)]
+        coarse_dim: Option<usize>,
+
+        /// Two-stage search: find candidate files on a file-level graph,
+        /// then score their chunks. Recorded in the index; `--two-stage
+        /// false` turns it off again.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        two_stage: Option<bool>,
+
+        /// Store a one-line structural summary of each file (kind, class,
+        /// module, key methods) and return it with results. Makes the index
+        /// larger; recorded in the index, `--summaries false` drops them.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        summaries: Option<bool>,
+
+        /// Store when each file was last committed to git, for `search
+        /// --boost-recent`. Read with one `git log` walk per run; recorded
+        /// in the index, `--git-times false` drops them.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        git_times: Option<bool>,
+
+        /// Store each file's owners, from CODEOWNERS or else the author
+        /// with the most commits to it, for `search --owner`. Recorded in
+        /// the index, `--owners false` drops them.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        owners: Option<bool>,
+
+        /// Embed each item twice: its code, and a description built from
+        /// its metadata (path, class, methods, enrichment terms). Search
+        /// blends the two by the `metadata` weight. Takes about twice as
+        /// long to embed; changing it rebuilds the index.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        meta_vectors: Option<bool>,
+
+        /// Split PHP files into one item per method, with its name and line
+        /// range, plus one for the code outside methods. Changing it
+        /// rebuilds the index.
+        #[arg(long, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
+        method_chunks: Option<bool>,
+
+        /// Only discover files: print the breakdown and the size estimate,
+        /// check disk space, and exit without loading the model
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Search the index
@@ -83,6 +232,223 @@ enum Commands {
         /// Output format (text, json)
         #[arg(short, long, default_value = "text")]
         format: String,
+
+        /// Rewrite stored paths of an index built elsewhere
+        /// (OLD=NEW, repeatable)
+        #[arg(long)]
+        map_root: Vec<RootMap>,
+
+        /// Core index to merge into results (default: the one recorded
+        /// when the index was built)
+        #[arg(long)]
+        core_index: Option<PathBuf>,
+
+        /// Search snapshot TAG (from `index --tag`) instead of the working index
+        #[arg(long)]
+        snapshot: Option<String>,
+
+        /// Magento root whose magector.toml `boost` rules apply (default:
+        /// detected from the current directory)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+
+        /// Shell command that rewrites the query (query on stdin and in
+        /// MAGECTOR_QUERY, rewritten query on stdout)
+        #[arg(long)]
+        rewriter_cmd: Option<String>,
+
+        /// Leave generated code (interceptors, factories, proxies) out of
+        /// the results instead of ranking it below source classes
+        #[arg(long)]
+        exclude_generated: bool,
+
+        /// Leave out code from modules disabled in app/etc/config.php
+        /// instead of only marking it
+        #[arg(long)]
+        exclude_disabled: bool,
+
+        /// Leave out files with fewer non-blank lines than this, e.g. to
+        /// skip interface stubs and find the real implementation
+        #[arg(long)]
+        min_loc: Option<usize>,
+
+        /// Leave out files with more non-blank lines than this
+        #[arg(long)]
+        max_loc: Option<usize>,
+
+        /// Result order: score, or recent (last modified file first)
+        #[arg(long, default_value = "score")]
+        sort: ResultOrder,
+
+        /// Rank recently committed files slightly higher (needs an index
+        /// built with --git-times)
+        #[arg(long)]
+        boost_recent: bool,
+
+        /// Only files owned by this team or person (substring of a
+        /// CODEOWNERS owner or git author; needs an index built with
+        /// --owners)
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Only this collection: code, or docs (Markdown indexed with
+        /// --include-docs)
+        #[arg(long)]
+        namespace: Option<Collection>,
+
+        /// Mark result N (1-based) as the right answer: SONA learns from it
+        /// as from an MCP follow-up. Re-run the search with this added.
+        #[arg(long, value_name = "N")]
+        mark: Option<usize>,
+    },
+
+    /// Show how a file or class changed across index snapshots
+    History {
+        /// Relative file path or (fully qualified) class name
+        target: String,
+
+        /// Path to the working index database; snapshots are read from
+        /// snapshots/ next to it
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Class constants and enum cases whose string value contains a string
+    /// (event names, status codes, config paths)
+    #[command(name = "find-string")]
+    FindString {
+        /// String to look up
+        value: String,
+
+        /// Path to the index database; its core index is searched too
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Only values equal to the string (case-sensitive)
+        #[arg(long)]
+        exact: bool,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Message queue routes: topic → exchange → queue → consumer → handler,
+    /// from communication.xml, queue_topology.xml, queue_consumer.xml and queue.xml
+    Queues {
+        /// Only routes whose topic, queue, consumer or handler contains this
+        filter: Option<String>,
+
+        /// Path to Magento root directory (default: nearest parent of the
+        /// current directory containing a Magento installation)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Magento indexers: id → action class, mview class and subscribed
+    /// tables, from indexer.xml and mview.xml
+    Indexers {
+        /// Only indexers whose id, title, class or subscribed table contains this
+        filter: Option<String>,
+
+        /// Path to Magento root directory (default: nearest parent of the
+        /// current directory containing a Magento installation)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Payment methods: code → model class, gateway flag and checkout
+    /// renderer components, from config.xml, payment.xml, di.xml and the
+    /// checkout renderer registrations
+    Payments {
+        /// Only methods whose code, title, model or renderer contains this
+        filter: Option<String>,
+
+        /// Path to Magento root directory (default: nearest parent of the
+        /// current directory containing a Magento installation)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Shipping carriers: code → model class, title and defaults, from
+    /// config.xml
+    Carriers {
+        /// Only carriers whose code, title, name or model contains this
+        filter: Option<String>,
+
+        /// Path to Magento root directory (default: nearest parent of the
+        /// current directory containing a Magento installation)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// bin/magento commands: name → command class, from the di.xml
+    /// `commands` lists and the classes they register
+    #[command(name = "console-commands")]
+    Console {
+        /// Only commands whose name, class, description or alias contains this
+        filter: Option<String>,
+
+        /// Path to Magento root directory (default: nearest parent of the
+        /// current directory containing a Magento installation)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Totals collector chain of a sales.xml section, in the order the
+    /// collectors run
+    Totals {
+        /// Section: quote, order (collected on the quote), invoice,
+        /// creditmemo, or any section name declared in sales.xml
+        #[arg(default_value = "quote")]
+        section: String,
+
+        /// Path to Magento root directory (default: nearest parent of the
+        /// current directory containing a Magento installation)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Project settings: enabled modules from app/etc/config.php, and
+    /// deploy mode, table prefix, cache and queue config from
+    /// app/etc/env.php with credentials redacted
+    ProjectInfo {
+        /// Path to Magento root directory (default: nearest parent of the
+        /// current directory containing a Magento installation)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
     },
 
     /// Generate embedding for text (for JS integration)
@@ -91,6 +457,10 @@ enum Commands {
         #[arg(short, long)]
         text: String,
 
+        /// Pooling over token embeddings: cls, mean or max
+        #[arg(long, default_value = "mean")]
+        pooling: Pooling,
+
         /// Path to cache embedding model
         #[arg(short = 'c', long, default_value = "./models")]
         model_cache: PathBuf,
@@ -103,6 +473,73 @@ enum Commands {
         database: PathBuf,
     },
 
+    /// Dump the raw vectors of indexed files or items as JSON lines
+    Vectors {
+        /// Relative file paths whose items to dump
+        paths: Vec<String>,
+
+        /// Item ID to dump (repeatable)
+        #[arg(long = "id")]
+        ids: Vec<usize>,
+
+        /// Path to the index database
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+    },
+
+    /// Which methods of two versions of a file changed semantically and
+    /// which only cosmetically
+    Semdiff {
+        /// Old version of the file (with --rev, the working copy)
+        old: PathBuf,
+
+        /// New version of the file
+        new: Option<PathBuf>,
+
+        /// Compare the file at this git revision with its working copy
+        #[arg(long)]
+        rev: Option<String>,
+
+        /// Path to cache embedding model
+        #[arg(short = 'c', long, default_value = "./models")]
+        model_cache: PathBuf,
+
+        /// Similarity at or above which a code change counts as minor
+        #[arg(short, long, default_value = "0.97")]
+        threshold: f32,
+
+        /// Also list unchanged chunks
+        #[arg(long)]
+        all: bool,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Cluster the indexed files by vector and project them onto a plane
+    Map {
+        /// Path to the index database
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Number of clusters (default: √(files/2), 2–50)
+        #[arg(short = 'k', long, default_value = "0")]
+        clusters: usize,
+
+        /// Only files whose path starts with this (e.g. app/code/)
+        #[arg(long, default_value = "")]
+        prefix: String,
+
+        /// Output format (text, json, csv)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+
+        /// Write the map to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
     /// Run comprehensive validation against Magento 2
     Validate {
         /// Path to Magento root directory (downloads if not specified)
@@ -124,6 +561,58 @@ enum Commands {
         /// Skip re-indexing if index exists
         #[arg(short, long)]
         skip_index: bool,
+
+        /// Write a SONA feedback signal per failed case to this JSONL file
+        #[arg(long)]
+        emit_signals: Option<PathBuf>,
+
+        /// Instead of validating, write N candidate test cases sampled from
+        /// the existing index (stratified by magento_type) for curation
+        #[arg(long, value_name = "N")]
+        generate: Option<usize>,
+
+        /// YAML file for the cases written by --generate
+        #[arg(long, default_value = "./generated_cases.yaml")]
+        generate_output: PathBuf,
+
+        /// Also search paraphrases of every case (reordered words, synonym
+        /// substitutions) and report how stable the top results stay
+        #[arg(long)]
+        paraphrase: bool,
+    },
+
+    /// Compare two ranking configurations on the validation suite
+    Ab {
+        /// Ranking configuration A (magector.toml format: `boost`, `[weights]`)
+        #[arg(long)]
+        config_a: PathBuf,
+
+        /// Ranking configuration B
+        #[arg(long)]
+        config_b: PathBuf,
+
+        /// Test cases (YAML list of {id, query, category, expected_patterns});
+        /// default: the built-in validation suite
+        #[arg(short, long)]
+        tests: Option<PathBuf>,
+
+        /// Path to the index database both configurations search
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Path to cache embedding model
+        #[arg(short = 'c', long, default_value = "./models")]
+        model_cache: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Analyses of search quality
+    Analyze {
+        #[command(subcommand)]
+        analysis: Analysis,
     },
 
     /// Download Magento 2 Open Source
@@ -185,90 +674,497 @@ enum Commands {
         /// Max ONNX threads (default: half of CPU cores). Also via MAGECTOR_THREADS env var.
         #[arg(long)]
         threads: Option<usize>,
-    },
-}
 
-/// Resolve the global thread limit from (in priority order):
-///   1. Explicit `--threads` flag
-///   2. `MAGECTOR_THREADS` env var
-///   3. `OMP_NUM_THREADS` env var
-/// Returns `None` if nothing is set (callers should fall back to their own default).
-fn resolve_thread_limit(explicit: Option<usize>) -> Option<usize> {
-    explicit
-        .or_else(|| std::env::var("MAGECTOR_THREADS").ok().and_then(|v| v.parse().ok()))
-        .or_else(|| std::env::var("OMP_NUM_THREADS").ok().and_then(|v| v.parse().ok()))
-}
+        /// Rewrite stored paths of an index built elsewhere
+        /// (OLD=NEW, repeatable). Paths under the build root are rebased
+        /// onto --magento-root automatically.
+        #[arg(long)]
+        map_root: Vec<RootMap>,
 
-/// Configure the global rayon thread pool. Must be called before any parallel work
-/// happens (otherwise rayon initializes its default pool with all CPU cores).
-/// Idempotent failure: if rayon is already initialized we log a warning and continue.
-fn configure_rayon(threads: usize) {
-    let available = num_cpus::get().max(1);
-    let n = threads.max(1).min(available);
-    match rayon::ThreadPoolBuilder::new().num_threads(n).build_global() {
-        Ok(()) => tracing::info!("Rayon global pool: {} threads (available: {})", n, available),
-        Err(e) => tracing::warn!("Could not set rayon thread count to {}: {}", n, e),
-    }
-}
+        /// Core index to merge into results (default: the one recorded
+        /// when the index was built)
+        #[arg(long)]
+        core_index: Option<PathBuf>,
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+        /// Latency budget of a search in milliseconds; searches that would
+        /// overrun it skip reranking and answer with "degraded":true
+        #[arg(long)]
+        deadline_ms: Option<u64>,
 
-    // Initialize logging — always write to stderr to avoid polluting stdout (MCP/JSON)
-    let filter = if cli.verbose {
-        "debug"
-    } else {
-        "magector_core=info,warn"
-    };
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(filter))
-        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
-        .init();
+        /// Shell command that rewrites search queries (query on stdin and in
+        /// MAGECTOR_QUERY, rewritten query on stdout)
+        #[arg(long)]
+        rewriter_cmd: Option<String>,
 
-    // Configure rayon early — must happen before any par_iter() in PHASE 1.
-    // For Index/Serve we honor --threads; for other commands we fall back to env vars only.
-    let cmd_threads = match &cli.command {
-        Commands::Index { threads, .. } => *threads,
-        Commands::Serve { threads, .. } => *threads,
-        _ => None,
-    };
-    if let Some(n) = resolve_thread_limit(cmd_threads) {
-        configure_rayon(n);
-    }
+        /// Pick the HNSW beam width at startup from how stable results are
+        /// on a sample of queries, and save it in the index (once per index)
+        #[arg(long)]
+        tune_ef: bool,
 
-    match cli.command {
-        Commands::Index {
-            magento_root,
-            database,
-            model_cache,
-            descriptions_db,
-            threads,
-            batch_size,
-            force,
-        } => {
-            run_index(&magento_root, &database, &model_cache, descriptions_db.as_deref(), threads, batch_size, force)?;
-        }
+        /// Answer `get_vectors` requests with raw vectors (large responses)
+        #[arg(long)]
+        expose_vectors: bool,
 
-        Commands::Search {
-            query,
-            database,
-            model_cache,
-            limit,
+        /// Search a sample of the validation cases at startup and then every
+        /// N hours, recording accuracy in probes.jsonl (0 disables)
+        #[arg(long, default_value = "0")]
+        probe_hours: u64,
+
+        /// Validation cases per probe run
+        #[arg(long, default_value = "20")]
+        probe_cases: usize,
+    },
+
+    /// Write a patch that turns one index into another (for distributing index updates)
+    Delta {
+        /// Index the patch applies to
+        old: PathBuf,
+
+        /// Updated index
+        new: PathBuf,
+
+        /// Path to write the patch
+        #[arg(short, long, default_value = "./index.mdx")]
+        output: PathBuf,
+    },
+
+    /// Apply a patch written by `delta` to an index
+    Apply {
+        /// Patch file
+        patch: PathBuf,
+
+        /// Path to the index database to update
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Write the patched index here instead of updating it in place
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Generate an ed25519 key pair for signing indexes
+    Keygen {
+        /// Secret key file; the public key is written next to it with a .pub extension
+        #[arg(short, long, default_value = "./magector.key")]
+        output: PathBuf,
+    },
+
+    /// Sign an index file (writes a detached <file>.sig)
+    Sign {
+        /// Index database or delta patch to sign
+        file: PathBuf,
+
+        /// Secret key from `keygen`
+        #[arg(short, long)]
+        key: PathBuf,
+    },
+
+    /// Verify an index file's signature and embedded checksum
+    Verify {
+        /// Index database or delta patch to verify
+        file: PathBuf,
+
+        /// Public key from `keygen`
+        #[arg(short, long)]
+        key: PathBuf,
+
+        /// Signature file (default: <file>.sig)
+        #[arg(short, long)]
+        signature: Option<PathBuf>,
+    },
+
+    /// Check an index, its manifest and SONA file for damage
+    Fsck {
+        /// Path to the index database
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Fix what can be fixed in place; dropped files are indexed again
+        /// by the next `index` run
+        #[arg(long)]
+        repair: bool,
+
+        /// Items whose own vector is searched for to check the graph
+        #[arg(long, default_value = "1000")]
+        graph_samples: usize,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Back up an index, its manifest and SONA file, and magector.toml
+    Backup {
+        /// Path to the index database
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Magento root holding magector.toml (not backed up without it)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+
+        /// Archive to write (gzip-compressed tar)
+        #[arg(short, long, default_value = "./magector-backup.tar.zst")]
+        output: PathBuf,
+    },
+
+    /// Restore a backup written by `backup`, replacing the current state
+    Restore {
+        /// Archive written by `backup`
+        archive: PathBuf,
+
+        /// Path to the index database to restore
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Magento root to restore magector.toml to (left alone without it)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+    },
+
+    /// Re-embed an index with another model from its text cache, writing a
+    /// new database
+    Migrate {
+        /// Model to embed with: `<model>.onnx` and `<model>.tokenizer.json`
+        /// in the model cache, or the default bge-small-en-v1.5
+        #[arg(long)]
+        model: String,
+
+        /// Path to the index database to migrate
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Database to write (default: index-<model>.db next to the index)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Path to cache embedding model
+        #[arg(short = 'c', long, default_value = "./models")]
+        model_cache: PathBuf,
+
+        /// Embedding batch size
+        #[arg(long, default_value = "256")]
+        batch_size: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum Analysis {
+    /// Suggest `search_text` enrichment from failed validation cases and
+    /// zero-result queries: the query words their target files lack
+    Enrichment {
+        /// Path to the index database
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Validation report (JSON) written by `validate`
+        #[arg(short, long)]
+        report: Option<PathBuf>,
+
+        /// MCP server log with zero-result queries (default: magector.log
+        /// next to the database, if present)
+        #[arg(short, long)]
+        log: Option<PathBuf>,
+
+        /// Maximum number of suggestions
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Pairs of files in different modules that embed almost alike
+    /// (copy-pasted helpers, cloned plugins)
+    Duplicates {
+        /// Path to the index database
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Cosine similarity a pair needs
+        #[arg(short, long, default_value = "0.95")]
+        threshold: f32,
+
+        /// Only files of this type (php, xml, phtml, js, ...; empty for all)
+        #[arg(long, default_value = "php")]
+        file_type: String,
+
+        /// Maximum number of pairs
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Core classes the project customizes (overrides, subclasses,
+    /// preferences, plugins), ranked by how much they change between two
+    /// Magento versions
+    Upgrade {
+        /// Path to the project index database
+        #[arg(short, long, default_value = "./.magector/index.db")]
+        database: PathBuf,
+
+        /// Core index of the current Magento version
+        #[arg(long)]
+        from: PathBuf,
+
+        /// Core index of the target Magento version
+        #[arg(long)]
+        to: PathBuf,
+
+        /// Magento root with the project's di.xml files (default: where the
+        /// project index was built)
+        #[arg(short, long)]
+        magento_root: Option<PathBuf>,
+
+        /// Maximum number of classes
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+}
+
+/// Resolve the global thread limit from (in priority order):
+///   1. Explicit `--threads` flag
+///   2. `MAGECTOR_THREADS` env var
+///   3. `OMP_NUM_THREADS` env var
+///
+/// Returns `None` if nothing is set (callers should fall back to their own default).
+fn resolve_thread_limit(explicit: Option<usize>) -> Option<usize> {
+    explicit
+        .or_else(|| std::env::var("MAGECTOR_THREADS").ok().and_then(|v| v.parse().ok()))
+        .or_else(|| std::env::var("OMP_NUM_THREADS").ok().and_then(|v| v.parse().ok()))
+}
+
+/// Configure the global rayon thread pool. Must be called before any parallel work
+/// happens (otherwise rayon initializes its default pool with all CPU cores).
+/// Idempotent failure: if rayon is already initialized we log a warning and continue.
+fn configure_rayon(threads: usize) {
+    let available = num_cpus::get().max(1);
+    let n = threads.max(1).min(available);
+    match rayon::ThreadPoolBuilder::new().num_threads(n).build_global() {
+        Ok(()) => tracing::info!("Rayon global pool: {} threads (available: {})", n, available),
+        Err(e) => tracing::warn!("Could not set rayon thread count to {}: {}", n, e),
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // Initialize logging — always write to stderr to avoid polluting stdout (MCP/JSON)
+    let filter = if cli.verbose {
+        "debug"
+    } else {
+        "magector_core=info,warn"
+    };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new(filter))
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .init();
+
+    // Configure rayon early — must happen before any par_iter() in PHASE 1.
+    // For Index/Serve we honor --threads; for other commands we fall back to env vars only.
+    let cmd_threads = match &cli.command {
+        Commands::Index { threads, .. } => *threads,
+        Commands::Serve { threads, .. } => *threads,
+        _ => None,
+    };
+    if let Some(n) = resolve_thread_limit(cmd_threads) {
+        configure_rayon(n);
+    }
+
+    match cli.command {
+        Commands::Index {
+            magento_root,
+            database,
+            model_cache,
+            descriptions_db,
+            threads,
+            batch_size,
+            force,
+            deterministic,
+            incremental,
+            only_modules,
+            follow_symlinks,
+            include_tests,
+            include_docs,
+            gitignore,
+            core_index,
+            extra_root,
+            no_extra_roots,
+            tag,
+            pooling,
+            coarse_dim,
+            two_stage,
+            summaries,
+            git_times,
+            owners,
+            meta_vectors,
+            method_chunks,
+            dry_run,
+        } => {
+            let magento_root = match magento_root {
+                Some(root) => root,
+                None => {
+                    let cwd = std::env::current_dir()?;
+                    let root = magector_core::magento::find_magento_root(&cwd).with_context(|| {
+                        format!("No Magento root found in {} or its parents — pass --magento-root", cwd.display())
+                    })?;
+                    println!("Detected Magento root: {}", root.display());
+                    root
+                }
+            };
+            let database = database.unwrap_or_else(|| magento_root.join(".magector").join("index.db"));
+
+            // magector.toml supplies defaults; explicit flags win
+            let flags = IndexConfig {
+                only_modules,
+                batch_size,
+                deterministic,
+                follow_symlinks,
+                include_tests,
+                include_docs,
+                gitignore,
+                pooling,
+                coarse_dim,
+                two_stage,
+                summaries,
+                git_times,
+                owners,
+                meta_vectors,
+                method_chunks,
+                ..Default::default()
+            };
+            let mut config = flags.or(ProjectConfig::load(&magento_root)?.index);
+            // The file's paths are relative to the Magento root
+            let core_index = core_index.or_else(|| config.core_index.take().map(|p| magento_root.join(p)));
+            let config_roots = std::mem::take(&mut config.extra_roots);
+            let extra_roots = if no_extra_roots {
+                Some(Vec::new())
+            } else if !extra_root.is_empty() {
+                Some(extra_root)
+            } else if !config_roots.is_empty() {
+                Some(
+                    config_roots
+                        .iter()
+                        .map(|spec| {
+                            let (name, path) = spec.split_once('=').map_or((None, spec.as_str()), |(n, p)| (Some(n), p));
+                            ExtraRoot::new(name, &magento_root.join(path))
+                        })
+                        .collect(),
+                )
+            } else {
+                None
+            };
+            let options = IndexOptions {
+                magento_root,
+                database,
+                model_cache,
+                descriptions_db,
+                threads,
+                force,
+                incremental,
+                core_index,
+                extra_roots,
+                tag,
+                config,
+            };
+
+            if dry_run {
+                run_index_dry_run(&options)?;
+            } else {
+                run_index(options)?;
+            }
+        }
+
+        Commands::Search {
+            query,
+            database,
+            model_cache,
+            limit,
             format,
+            map_root,
+            core_index,
+            snapshot,
+            magento_root,
+            rewriter_cmd,
+            exclude_generated,
+            exclude_disabled,
+            min_loc,
+            max_loc,
+            sort,
+            boost_recent,
+            owner,
+            namespace,
+            mark,
         } => {
+            if mark.is_some() && snapshot.is_some() {
+                anyhow::bail!("--mark learns into the working index; search it without --snapshot");
+            }
+            let database = match snapshot {
+                Some(tag) => {
+                    let path = snapshot::snapshot_path(&database, &tag)?;
+                    if !path.exists() {
+                        anyhow::bail!("Snapshot {:?} not found at {:?}", tag, path);
+                    }
+                    path
+                }
+                None => database,
+            };
             let mut indexer = Indexer::new(&PathBuf::new(), &model_cache, &database)?;
+            indexer.map_roots(&map_root);
+            if let Some(core_index) = core_index {
+                indexer.set_core_index(&core_index)?;
+            }
+            let magento_root = magento_root.or_else(|| {
+                magector_core::magento::find_magento_root(&std::env::current_dir().ok()?)
+            });
+            if let Some(root) = magento_root {
+                indexer.apply_ranking(&ProjectConfig::load(&root)?);
+                indexer.load_module_states(&root);
+                indexer.load_patches(&root);
+            }
+            indexer.set_query_rewriter(query_rewriter(rewriter_cmd.as_deref()));
+            if exclude_generated {
+                let weights = indexer.score_weights();
+                indexer.set_score_weights(magector_core::ScoreWeights { generated: 0.0, ..weights });
+            }
+            if exclude_disabled {
+                let weights = indexer.score_weights();
+                indexer.set_score_weights(magector_core::ScoreWeights { disabled: 0.0, ..weights });
+            }
+            if boost_recent {
+                let weights = indexer.score_weights();
+                let recency = magector_core::vectordb::RECENT_BOOST_WEIGHT;
+                indexer.set_score_weights(magector_core::ScoreWeights { recency, ..weights });
+            }
 
-            let results = indexer.search(&query, limit)?;
+            let filter = ResultFilter { min_loc, max_loc, owner, namespace, order: sort };
+            let weights = indexer.score_weights();
+            let mut results = indexer.search_weighted(&query, filter.fetch_limit(limit), weights, filter.namespace)?;
+            filter.apply(&mut results, limit);
 
             if format == "json" {
                 println!("{}", serde_json::to_string_pretty(&results)?);
             } else {
                 println!("\n=== Search Results for: \"{}\" ===\n", query);
                 for (i, result) in results.iter().enumerate() {
+                    // Whole-file items start at line 1 and need no location
+                    let location = match (result.line, result.end_line) {
+                        (Some(line), Some(end)) if line > 1 => format!(":{line}-{end}"),
+                        _ => String::new(),
+                    };
                     println!(
-                        "{}. {} (score: {:.3})",
+                        "{}. {}{} (score: {:.3})",
                         i + 1,
                         result.metadata.path,
+                        location,
                         result.score
                     );
                     if let Some(ref class) = result.metadata.class_name {
@@ -277,25 +1173,323 @@ fn main() -> Result<()> {
                     if let Some(ref mtype) = result.metadata.magento_type {
                         println!("   Type: {}", mtype);
                     }
+                    if let Some(ref root) = result.metadata.root {
+                        println!("   Root: {}", root);
+                    }
+                    if let Some(ref summary) = result.metadata.summary {
+                        println!("   Summary: {}", summary);
+                    }
+                    if result.metadata.is_generated {
+                        println!("   Generated code");
+                    }
+                    if result.module_enabled == Some(false) {
+                        println!("   ⚠️  Module disabled in app/etc/config.php");
+                    }
+                    for patch in &result.patched_by {
+                        match patch.description {
+                            Some(ref description) => println!("   Patched by: {} ({})", patch.patch, description),
+                            None => println!("   Patched by: {}", patch.patch),
+                        }
+                    }
+                    if let Some(ref original) = result.metadata.overrides {
+                        println!("   Overrides: {}", original);
+                    }
+                    if let Some(ref implementation) = result.implementation {
+                        match implementation.metadata.class_name {
+                            Some(ref class) => println!("   Implementation: {} ({})", implementation.metadata.path, class),
+                            None => println!("   Implementation: {}", implementation.metadata.path),
+                        }
+                    }
+                    if !result.metadata.urls.is_empty() {
+                        println!("   URL: {}", result.metadata.urls.join(", "));
+                    }
+                    if let Some(metrics) = result.metadata.metrics {
+                        println!("   Size: {}", describe_metrics(&metrics));
+                    }
+                    if let Some(committed) = result.metadata.last_commit {
+                        println!("   Last commit: {}", days_ago(committed));
+                    }
+                    if !result.metadata.owners.is_empty() {
+                        println!("   Owners: {}", result.metadata.owners.join(", "));
+                    }
+                    if result.metadata.collection == Collection::Docs {
+                        println!("   Collection: docs");
+                    }
+                    if !result.metadata.headings.is_empty() {
+                        println!("   Section: {}", result.metadata.headings.join(" › "));
+                    }
+                    // Method chunks; whole files start at byte 0
+                    if let (Some(method), Some(span)) = (&result.metadata.method_name, result.metadata.span) {
+                        if span.start_byte > 0 {
+                            println!("   Method: {} (lines {}-{})", method, span.start_line, span.end_line);
+                        }
+                    }
                     println!();
                 }
             }
+
+            if let Some(n) = mark {
+                if n == 0 || n > results.len() {
+                    anyhow::bail!("--mark {} is out of range: {} results", n, results.len());
+                }
+                let metas: Vec<&magector_core::IndexMetadata> = results.iter().map(|r| &r.metadata).collect();
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                let signal = magector_core::sona::SonaSignal::marked(&query, &metas, n, now);
+                indexer.learn_signal(&signal);
+                indexer.save_sona()?;
+                let message = format!("Marked {}. {} as the answer ({})", n, results[n - 1].metadata.path, signal.signal_type);
+                if format == "json" {
+                    eprintln!("{}", message);
+                } else {
+                    println!("{}", message);
+                }
+            }
+        }
+
+        Commands::History { target, database, format } => {
+            let history = snapshot::file_history(&database, &target)?;
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&history)?);
+            } else {
+                print_history(&target, &history);
+            }
+        }
+
+        Commands::FindString { value, database, exact, format } => {
+            let (header, mut items) = VectorDB::read_metadata(&database)?;
+            if !header.core_index.is_empty() {
+                items.extend(VectorDB::read_metadata(Path::new(&header.core_index))?.1);
+            }
+            let found = magector_core::constants::find(&items, &value, exact);
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&found)?);
+            } else {
+                print_string_constants(&value, &found);
+            }
+        }
+
+        Commands::Queues { filter, magento_root, format } => {
+            let magento_root = magento_root_or_detect(magento_root)?;
+            let routes = QueueTopology::scan(&magento_root)?.routes(filter.as_deref());
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&routes)?);
+            } else {
+                print_queue_routes(&routes);
+            }
+        }
+
+        Commands::Indexers { filter, magento_root, format } => {
+            let magento_root = magento_root_or_detect(magento_root)?;
+            let xref = ConfigXref::scan(&magento_root, &XmlAnalyzer::new())?;
+            let entries = xref.indexers.entries(filter.as_deref());
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else {
+                print_indexers(&entries);
+            }
+        }
+
+        Commands::Payments { filter, magento_root, format } => {
+            let magento_root = magento_root_or_detect(magento_root)?;
+            let xref = ConfigXref::scan(&magento_root, &XmlAnalyzer::new())?;
+            let methods = xref.payments.methods(filter.as_deref());
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&methods)?);
+            } else {
+                print_payment_methods(&methods);
+            }
+        }
+
+        Commands::Carriers { filter, magento_root, format } => {
+            let magento_root = magento_root_or_detect(magento_root)?;
+            let xref = ConfigXref::scan(&magento_root, &XmlAnalyzer::new())?;
+            let carriers = xref.carriers.carriers(filter.as_deref());
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&carriers)?);
+            } else {
+                print_carriers(&carriers);
+            }
+        }
+
+        Commands::Console { filter, magento_root, format } => {
+            let magento_root = magento_root_or_detect(magento_root)?;
+            let xref = ConfigXref::scan(&magento_root, &XmlAnalyzer::new())?;
+            let commands = xref.console.commands(filter.as_deref());
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&commands)?);
+            } else {
+                print_console_commands(&commands);
+            }
+        }
+
+        Commands::Totals { section, magento_root, format } => {
+            let magento_root = magento_root_or_detect(magento_root)?;
+            let config = TotalsConfig::scan(&magento_root)?;
+            let Some(resolved) = config.resolve_section(&section) else {
+                anyhow::bail!(
+                    "No totals collectors declared for section '{}' (available: {})",
+                    section,
+                    config.sections().join(", ")
+                );
+            };
+            let chain = config.chain(&resolved);
+            if format == "json" {
+                let out = serde_json::json!({ "section": resolved, "collectors": chain });
+                println!("{}", serde_json::to_string_pretty(&out)?);
+            } else {
+                print_totals_chain(&section, &resolved, &chain);
+            }
+        }
+
+        Commands::ProjectInfo { magento_root, format } => {
+            let magento_root = magento_root_or_detect(magento_root)?;
+            let info = ProjectInfo::scan(&magento_root)?;
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else {
+                print_project_info(&info);
+            }
         }
 
-        Commands::Embed { text, model_cache } => {
+        Commands::Embed { text, model_cache, pooling } => {
             let mut embedder = Embedder::from_pretrained(&model_cache)?;
+            embedder.set_pooling(pooling);
             let embedding = embedder.embed(&text)?;
 
             // Output as JSON array for easy parsing
             println!("{}", serde_json::to_string(&embedding)?);
         }
 
+        Commands::Vectors { paths, ids, database } => {
+            if paths.is_empty() && ids.is_empty() {
+                anyhow::bail!("Give file paths or --id to dump vectors of");
+            }
+            let db = VectorDB::open(&database)?;
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+            let items = db.vectors_by_path(&paths).into_iter().chain(ids.iter().filter_map(|&id| db.item_vector(id)));
+            let stdout = io::stdout();
+            let mut out = io::BufWriter::new(stdout.lock());
+            let mut dumped = 0;
+            for item in items {
+                writeln!(out, "{}", serde_json::to_string(&item)?)?;
+                dumped += 1;
+            }
+            out.flush()?;
+            if dumped == 0 {
+                anyhow::bail!("No indexed items match");
+            }
+        }
+
+        Commands::Semdiff { old, new, rev, model_cache, threshold, all, format } => {
+            let (old_version, new_version) = match (&new, &rev) {
+                (Some(new), None) => (
+                    (old.display().to_string(), fs::read_to_string(&old).with_context(|| format!("Failed to read {}", old.display()))?),
+                    (new.display().to_string(), fs::read_to_string(new).with_context(|| format!("Failed to read {}", new.display()))?),
+                ),
+                (None, Some(rev)) => (
+                    (format!("{}@{}", old.display(), rev), magector_core::git::show_revision(&old, rev)?),
+                    (old.display().to_string(), fs::read_to_string(&old).with_context(|| format!("Failed to read {}", old.display()))?),
+                ),
+                _ => anyhow::bail!("Give two files, or one file and --rev"),
+            };
+            let mut embedder = Embedder::from_pretrained(&model_cache)?;
+            let path = new.as_deref().unwrap_or(&old).to_string_lossy().into_owned();
+            let mut diff = magector_core::semdiff::diff(
+                &path,
+                (&old_version.0, &old_version.1),
+                (&new_version.0, &new_version.1),
+                threshold,
+                |texts| embedder.embed_batch(texts),
+            )?;
+            if !all {
+                diff.chunks.retain(|c| c.change != magector_core::semdiff::Change::Unchanged);
+            }
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&diff)?);
+            } else {
+                print_semdiff(&diff);
+            }
+        }
+
+        Commands::Map { database, clusters, prefix, format, output } => {
+            let db = VectorDB::open(&database)?;
+            let map = codemap::build(&db, &prefix, clusters);
+            if map.points.is_empty() {
+                anyhow::bail!("No indexed files under '{}'", prefix);
+            }
+            let rendered = match format.as_str() {
+                "json" => serde_json::to_string_pretty(&map)?,
+                "csv" => map.to_csv(),
+                _ => render_code_map(&map),
+            };
+            match output {
+                Some(path) => {
+                    fs::write(&path, rendered)?;
+                    println!("✓ Wrote a map of {} files in {} clusters to {:?}", map.points.len(), map.clusters.len(), path);
+                }
+                None => print!("{}", rendered),
+            }
+        }
+
         Commands::Stats { database } => {
             let db = VectorDB::open(&database)?;
 
             println!("\n=== Index Statistics ===");
             println!("Total vectors: {}", db.len());
             println!("Embedding dim: {}", EMBEDDING_DIM);
+            println!("Pooling:       {}", db.header().pooling);
+            if !db.header().model.is_empty() && db.header().model != DEFAULT_MODEL {
+                println!("Model:         {}", db.header().model);
+            }
+            if db.header().ef_search > 0 {
+                println!("ef_search:     {} (tuned)", db.header().ef_search);
+            }
+            if db.header().coarse_dim > 0 {
+                println!("Coarse graph:  {} dims (full-vector rerank)", db.header().coarse_dim);
+            }
+            if db.header().two_stage {
+                println!("Two-stage:     file-level graph, then chunks");
+            }
+            if db.header().summaries {
+                println!("Summaries:     one line per file");
+            }
+            if db.header().git_times {
+                println!("Git times:     last commit per file");
+            }
+            if db.header().owners {
+                println!("Owners:        CODEOWNERS or git authorship");
+            }
+            if db.header().meta_vectors {
+                println!("Meta vectors:  code and metadata embedded separately");
+            }
+            if db.header().method_chunks {
+                println!("Method chunks: one item per PHP method");
+            }
+            if db.header().include_docs {
+                println!("Docs:          Markdown sections (docs collection)");
+            }
+            if db.header().gitignore {
+                println!("Gitignore:     root .gitignore applied");
+            }
+            if !db.header().stop_terms.is_empty() {
+                println!("Stop terms:    {}", db.header().stop_terms.join(", "));
+            }
+            if !db.header().aliases.is_empty() {
+                let aliases: Vec<String> =
+                    db.header().aliases.iter().map(|(alias, words)| format!("{} → {}", alias, words.join(" "))).collect();
+                println!("Aliases:       {}", aliases.join(", "));
+            }
+            if let Some(warning) = db.header().partial_warning() {
+                println!("⚠️  {}", warning);
+            }
+            let snapshots = snapshot::list_snapshots(&database)?;
+            if !snapshots.is_empty() {
+                let tags: Vec<&str> = snapshots.iter().map(|s| s.tag.as_str()).collect();
+                println!("Snapshots: {}", tags.join(", "));
+            }
         }
 
         Commands::Validate {
@@ -304,8 +1498,106 @@ fn main() -> Result<()> {
             model_cache,
             report,
             skip_index,
+            emit_signals,
+            generate,
+            generate_output,
+            paraphrase,
+        } => {
+            if let Some(n) = generate {
+                let (_, items) = VectorDB::read_metadata(&database)?;
+                let cases = magector_core::validation::generate_cases(&items, n);
+                magector_core::validation::save_cases(&cases, &generate_output)?;
+                println!("Wrote {} candidate test cases to {:?}", cases.len(), generate_output);
+            } else {
+                run_validation(magento_root, &database, &model_cache, &report, skip_index, emit_signals.as_deref(), paraphrase)?;
+            }
+        }
+
+        Commands::Ab {
+            config_a,
+            config_b,
+            tests,
+            database,
+            model_cache,
+            format,
         } => {
-            run_validation(magento_root, &database, &model_cache, &report, skip_index)?;
+            let validator = match tests {
+                Some(path) => Validator::from_file(&path)?,
+                None => Validator::new(),
+            };
+            let mut indexer = Indexer::new(&PathBuf::new(), &model_cache, &database)?;
+            let mut results = Vec::new();
+            for config in [&config_a, &config_b] {
+                indexer.apply_ranking(&ProjectConfig::load_file(config)?);
+                eprintln!("Running {} test cases with {}...", validator.test_cases().len(), config.display());
+                results.push(validator.evaluate(&mut indexer)?);
+            }
+            let report = magector_core::ab::compare(validator.test_cases(), &results[0], &results[1]);
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_ab_report(&report, &config_a, &config_b);
+            }
+        }
+
+        Commands::Analyze { analysis: Analysis::Duplicates { database, threshold, file_type, limit, format } } => {
+            let db = VectorDB::open(&database)?;
+            let mut pairs = magector_core::duplicates::find(&db, &file_type, threshold);
+            let total = pairs.len();
+            pairs.truncate(limit);
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&pairs)?);
+            } else {
+                print_duplicates(&pairs, total, threshold);
+            }
+        }
+
+        Commands::Analyze { analysis: Analysis::Upgrade { database, from, to, magento_root, limit, format } } => {
+            let project = VectorDB::open(&database)?;
+            let root = match magento_root {
+                Some(root) => root,
+                None if !project.header().root.is_empty() => PathBuf::from(&project.header().root),
+                None => magento_root_or_detect(None)?,
+            };
+            let mut report = magector_core::upgrade::impact(&project, &root, &VectorDB::open(&from)?, &VectorDB::open(&to)?)?;
+            let total = report.len();
+            report.truncate(limit);
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_upgrade_impact(&report, total);
+            }
+        }
+
+        Commands::Analyze { analysis: Analysis::Enrichment { database, report, log, limit, format } } => {
+            let failures = match report {
+                Some(ref path) => {
+                    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+                    let report: magector_core::validation::ValidationReport = serde_json::from_str(&text)
+                        .with_context(|| format!("Invalid validation report {}", path.display()))?;
+                    report.test_results
+                }
+                None => Vec::new(),
+            };
+            let log = log.or_else(|| Some(database.with_file_name("magector.log")).filter(|p| p.exists()));
+            let zero_result = match log {
+                Some(ref path) => magector_core::enrichment::zero_result_queries(
+                    &fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?,
+                ),
+                None => Vec::new(),
+            };
+            if report.is_none() && log.is_none() {
+                anyhow::bail!("Nothing to analyze: pass a validation report (--report) or a server log (--log)");
+            }
+            let (_, items) = VectorDB::read_metadata(&database)?;
+            let mut suggestions = magector_core::enrichment::suggest(&items, &failures, &zero_result);
+            suggestions.truncate(limit);
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&suggestions)?);
+            } else {
+                let failed = failures.iter().filter(|r| !r.passed).count();
+                print_enrichment_suggestions(&suggestions, failed, zero_result.len());
+            }
         }
 
         Commands::Describe {
@@ -345,29 +1637,201 @@ fn main() -> Result<()> {
             watch_interval,
             descriptions_db,
             threads,
+            map_root,
+            core_index,
+            deadline_ms,
+            rewriter_cmd,
+            tune_ef,
+            expose_vectors,
+            probe_hours,
+            probe_cases,
         } => {
-            run_serve(&database, &model_cache, magento_root, watch_interval, descriptions_db, threads)?;
+            run_serve(
+                &database,
+                &model_cache,
+                magento_root,
+                watch_interval,
+                descriptions_db,
+                threads,
+                &map_root,
+                core_index.as_deref(),
+                deadline_ms.map(Duration::from_millis),
+                rewriter_cmd.as_deref(),
+                tune_ef,
+                expose_vectors,
+                probe_hours,
+                probe_cases,
+            )?;
+        }
+
+        Commands::Delta { old, new, output } => {
+            run_delta(&old, &new, &output)?;
+        }
+
+        Commands::Apply { patch, database, output } => {
+            run_apply(&patch, &database, output.as_deref())?;
+        }
+
+        Commands::Keygen { output } => {
+            let public = signing::generate_keypair(&output)?;
+            println!("✓ Secret key: {:?} (keep private)", output);
+            println!("✓ Public key: {:?}", public);
+        }
+
+        Commands::Sign { file, key } => {
+            let sig = signing::sign_file(&file, &signing::read_signing_key(&key)?)?;
+            println!("✓ Signed {:?} -> {:?}", file, sig);
+        }
+
+        Commands::Verify { file, key, signature } => {
+            run_verify(&file, &key, signature.as_deref())?;
+        }
+
+        Commands::Fsck { database, repair, graph_samples, format } => {
+            run_fsck(&database, repair, graph_samples, &format)?;
+        }
+
+        Commands::Backup { database, magento_root, output } => {
+            let info = backup::create(&database, magento_root.as_deref(), &output)?;
+            let names: Vec<&str> = info.files.iter().map(|f| f.name.as_str()).collect();
+            println!("✓ Backed up {} ({} vectors) to {:?}", names.join(", "), info.items, output);
+            println!("  Size: {}", preflight::format_bytes(file_size(&output)));
+        }
+
+        Commands::Restore { archive, database, magento_root } => {
+            let (info, warnings) = backup::restore(&archive, &database, magento_root.as_deref())?;
+            for warning in &warnings {
+                println!("⚠️  {}", warning);
+            }
+            let names: Vec<&str> = info.files.iter().map(|f| f.name.as_str()).collect();
+            println!(
+                "✓ Restored {} ({} vectors, backed up by magector {}) to {:?}",
+                names.join(", "),
+                info.items,
+                info.version,
+                database
+            );
+        }
+
+        Commands::Migrate { model, database, output, model_cache, batch_size } => {
+            let output = output.unwrap_or_else(|| database.with_file_name(format!("index-{}.db", model)));
+            let mut embedder = Embedder::from_model(&model_cache, &model, None)?;
+            let report = migrate::migrate(&database, &output, &mut embedder, batch_size)?;
+            let from = if report.from_model.is_empty() { DEFAULT_MODEL } else { report.from_model.as_str() };
+            println!("✓ Migrated {} vectors from {} to {}: {:?}", report.items, from, report.to_model, output);
+            if report.meta_vectors > 0 {
+                println!("  Metadata vectors: {}", report.meta_vectors);
+            }
+            if report.sona {
+                println!("  SONA: pattern and term weights kept, LoRA adapter reset");
+            }
+            println!("  Search it with -d {:?}, or move it over the old index", output);
         }
     }
 
     Ok(())
 }
 
-fn run_index(
-    magento_root: &PathBuf,
-    database: &PathBuf,
-    model_cache: &PathBuf,
-    descriptions_db: Option<&std::path::Path>,
+/// `magector index` settings, flags merged over `magector.toml`
+#[derive(Default)]
+struct IndexOptions {
+    magento_root: PathBuf,
+    database: PathBuf,
+    model_cache: PathBuf,
+    descriptions_db: Option<PathBuf>,
     threads: Option<usize>,
-    batch_size: Option<usize>,
     force: bool,
-) -> Result<()> {
+    incremental: bool,
+    core_index: Option<PathBuf>,
+    /// None keeps the roots recorded in the index header
+    extra_roots: Option<Vec<ExtraRoot>>,
+    tag: Option<String>,
+    /// The `[index]` settings; `core_index` and `extra_roots` are taken out
+    /// into the fields above, resolved
+    config: IndexConfig,
+}
+
+fn run_index(options: IndexOptions) -> Result<()> {
+    let IndexOptions {
+        magento_root,
+        database,
+        model_cache,
+        descriptions_db,
+        threads,
+        force,
+        incremental,
+        core_index,
+        extra_roots,
+        tag,
+        config,
+    } = options;
+    let (magento_root, database, model_cache) = (magento_root.as_path(), database.as_path(), model_cache.as_path());
+    let batch_size = config.batch_size;
+
+    // Check the tag before a long run rather than after it
+    if let Some(tag) = tag.as_deref() {
+        let path = snapshot::snapshot_path(database, tag)?;
+        if path.exists() {
+            anyhow::bail!("Snapshot {:?} already exists at {:?} — delete it to re-tag", tag, path);
+        }
+    }
+
     tracing::info!("Starting indexer...");
 
-    let mut indexer = Indexer::with_options(magento_root, model_cache, database, threads, batch_size)?;
+    let mut indexer = match Indexer::with_options(magento_root, model_cache, database, threads, batch_size) {
+        Err(e) if force => match VectorDB::set_aside_if_unreadable(database)? {
+            Some(aside) => {
+                println!("⚠️  Existing index is unreadable ({:#}) — moved to {}, rebuilding", e, aside.display());
+                Indexer::with_options(magento_root, model_cache, database, threads, batch_size)?
+            }
+            None => return Err(e),
+        },
+        result => result?,
+    };
+    indexer.set_redaction(&ProjectConfig::load(magento_root)?.redact)?;
+    indexer.set_deterministic(config.deterministic.unwrap_or(false));
+    indexer.set_incremental(incremental);
+    indexer.set_only_modules(&config.only_modules)?;
+    let walk = config.walk_options();
+    indexer.set_follow_symlinks(walk.follow_symlinks);
+    indexer.set_include_tests(walk.include_tests);
+    indexer.set_include_docs(walk.include_docs);
+    if let Some(gitignore) = config.gitignore {
+        indexer.set_gitignore(gitignore);
+    }
+    if let Some(extra_roots) = extra_roots {
+        indexer.set_extra_roots(extra_roots)?;
+    }
+    if let Some(pooling) = config.pooling {
+        indexer.set_pooling(pooling)?;
+    }
+    if let Some(coarse_dim) = config.coarse_dim {
+        indexer.set_coarse_dim(coarse_dim)?;
+    }
+    if let Some(two_stage) = config.two_stage {
+        indexer.set_two_stage(two_stage);
+    }
+    if let Some(summaries) = config.summaries {
+        indexer.set_summaries(summaries);
+    }
+    if let Some(git_times) = config.git_times {
+        indexer.set_git_times(git_times);
+    }
+    if let Some(owners) = config.owners {
+        indexer.set_owners(owners);
+    }
+    if let Some(meta_vectors) = config.meta_vectors {
+        indexer.set_meta_vectors(meta_vectors);
+    }
+    if let Some(method_chunks) = config.method_chunks {
+        indexer.set_method_chunks(method_chunks);
+    }
+    if let Some(core_index) = core_index {
+        indexer.set_core_index(&core_index)?;
+    }
 
     // Auto-detect descriptions DB next to the main DB if not explicitly provided
-    let desc_db_path = descriptions_db.map(|p| p.to_path_buf()).unwrap_or_else(|| {
+    let desc_db_path = descriptions_db.unwrap_or_else(|| {
         database.with_file_name("sqlite.db")
     });
     if desc_db_path.exists() {
@@ -379,6 +1843,10 @@ fn run_index(
 
     tracing::info!("Saving final index to {:?}...", database);
     indexer.save_atomic(database)?;
+    if let Some(tag) = tag.as_deref() {
+        let path = indexer.save_snapshot(database, tag)?;
+        println!("📸 Snapshot {:?} saved to {:?}", tag, path);
+    }
 
     println!("Files found:    {}", stats.files_found);
     println!("Files indexed:  {}", stats.files_indexed);
@@ -389,16 +1857,539 @@ fn run_index(
     println!("Files skipped:  {}", stats.files_skipped);
     println!("Vectors created: {}", stats.vectors_created);
     println!("Errors:         {}", stats.errors);
+    println!("Non-UTF-8 files: {}", stats.lossy_decodes);
+
+    Ok(())
+}
+
+fn print_history(target: &str, history: &[snapshot::HistoryEntry]) {
+    println!("\n=== History of {} ===\n", target);
+    let width = history.iter().map(|h| h.tag.len()).max().unwrap_or(0);
+    for entry in history {
+        let state = if entry.files.is_empty() {
+            "absent".to_string()
+        } else {
+            entry.files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>().join(", ")
+        };
+        println!("{:<width$}  {}", entry.tag, state);
+        for change in &entry.changes {
+            println!("{:<width$}    {}", "", change);
+        }
+    }
+    match history.iter().find(|h| !h.files.is_empty()) {
+        Some(first) if first.tag != history[0].tag => println!("\nFirst seen in {}", first.tag),
+        Some(_) => {}
+        None => println!("\nNot found in any snapshot"),
+    }
+}
+
+fn print_queue_routes(routes: &[QueueRoute]) {
+    if routes.is_empty() {
+        println!("No message queue routes found");
+        return;
+    }
+    println!("\n=== Message Queue Routes ({}) ===\n", routes.len());
+    for route in routes {
+        let part = |p: &Option<String>| p.clone().unwrap_or_else(|| "-".to_string());
+        println!(
+            "topic {} → exchange {} → queue {} → consumer {}",
+            part(&route.topic),
+            part(&route.exchange),
+            part(&route.queue),
+            part(&route.consumer)
+        );
+        if let Some(ref connection) = route.connection {
+            println!("  connection: {}", connection);
+        }
+        for handler in &route.handlers {
+            println!("  handler:    {}", handler);
+        }
+        for file in &route.files {
+            println!("  {}", file);
+        }
+        println!();
+    }
+}
+
+/// `--magento-root`, or the nearest Magento installation above the current directory
+fn magento_root_or_detect(magento_root: Option<PathBuf>) -> Result<PathBuf> {
+    if let Some(root) = magento_root {
+        return Ok(root);
+    }
+    let cwd = std::env::current_dir()?;
+    magector_core::magento::find_magento_root(&cwd)
+        .with_context(|| format!("No Magento root found in {} or its parents — pass --magento-root", cwd.display()))
+}
+
+fn print_ab_report(report: &magector_core::ab::AbReport, config_a: &Path, config_b: &Path) {
+    let line = |name: &str, s: &magector_core::ab::AbStats| {
+        println!(
+            "  {:20} {:>3}/{:<3} → {:>3}/{:<3} ({:+6.1} pts)  +{} -{}  p={:.3}",
+            name, s.passed_a, s.total, s.passed_b, s.total, s.accuracy_delta(), s.wins, s.losses, s.p_value
+        );
+    };
+    println!("\n=== A/B: {} (A) vs {} (B) ===\n", config_a.display(), config_b.display());
+    println!("  {:20} {:>7}   {:>7}", "category", "A", "B");
+    for (category, stats) in &report.categories {
+        line(category, stats);
+    }
+    println!();
+    line("overall", &report.overall);
+    let verdict = match (report.overall.wins.cmp(&report.overall.losses), report.overall.p_value < 0.05) {
+        (std::cmp::Ordering::Equal, _) | (_, false) => "no significant difference (p >= 0.05)",
+        (std::cmp::Ordering::Greater, true) => "B is significantly better",
+        (std::cmp::Ordering::Less, true) => "A is significantly better",
+    };
+    println!("\n  {}", verdict);
+    if !report.changes.is_empty() {
+        println!("\nChanged cases:");
+        for change in &report.changes {
+            println!(
+                "  {} {} [{}] {}",
+                if change.win { "+" } else { "-" },
+                change.test_id,
+                change.category,
+                change.query
+            );
+            println!(
+                "      A: {}  B: {}",
+                change.top_a.as_deref().unwrap_or("-"),
+                change.top_b.as_deref().unwrap_or("-")
+            );
+        }
+    }
+}
+
+fn print_string_constants(value: &str, found: &[magector_core::constants::StringConstant]) {
+    println!("\n=== String constants matching {:?} ({}) ===\n", value, found.len());
+    if found.is_empty() {
+        println!("No class constant or enum case holds this string");
+        return;
+    }
+    for constant in found {
+        let kind = if constant.is_case { "case" } else { "const" };
+        println!("{} {} = '{}'", kind, constant.constant, constant.value);
+        if let Some(ref class) = constant.class {
+            println!("  {}", class);
+        }
+        println!("  {}", constant.path);
+    }
+}
+
+fn print_enrichment_suggestions(suggestions: &[magector_core::enrichment::Suggestion], failed: usize, zero_result: usize) {
+    println!("\n=== Enrichment Suggestions ({} failed cases, {} zero-result queries) ===\n", failed, zero_result);
+    if suggestions.is_empty() {
+        println!("No missing query words found");
+        return;
+    }
+    for suggestion in suggestions {
+        println!("{}", suggestion.summary());
+        let ids = if suggestion.test_ids.is_empty() { String::new() } else { format!(" ({})", suggestion.test_ids.join(", ")) };
+        println!("  queries: {}{}", suggestion.queries.join(" | "), ids);
+        for file in suggestion.files.iter().take(5) {
+            println!("  {}", file);
+        }
+        if suggestion.files.len() > 5 {
+            println!("  ... {} more", suggestion.files.len() - 5);
+        }
+        println!();
+    }
+}
+
+fn print_duplicates(pairs: &[magector_core::duplicates::DuplicatePair], total: usize, threshold: f32) {
+    println!("\n=== Near-duplicate files across modules ({} pairs at similarity ≥ {}) ===\n", total, threshold);
+    if pairs.is_empty() {
+        println!("No near-duplicates found");
+        return;
+    }
+    for pair in pairs {
+        println!("{:.3}  {} ({})", pair.similarity, pair.path_a, pair.module_a);
+        println!("       {} ({})", pair.path_b, pair.module_b);
+    }
+    if total > pairs.len() {
+        println!("\n... {} more (raise --limit or --threshold)", total - pairs.len());
+    }
+}
+
+fn print_upgrade_impact(report: &[magector_core::upgrade::UpgradeImpact], total: usize) {
+    println!("\n=== Upgrade impact ({} customized core classes) ===\n", total);
+    if report.is_empty() {
+        println!("No customized core classes found");
+        return;
+    }
+    for entry in report {
+        let change = match entry.change {
+            _ if entry.target_path.is_none() => "removed".to_string(),
+            Some(change) => format!("{:.3}", change),
+            None => "?".to_string(),
+        };
+        println!("{:<8} {}  {}", change, entry.class, entry.path);
+        if let Some(ref target) = entry.target_path.as_ref().filter(|t| **t != entry.path) {
+            println!("         moved to {}", target);
+        }
+        for customizer in &entry.customizers {
+            println!("         {:<10} {}  {}", customizer.kind.as_str(), customizer.class, customizer.path);
+        }
+    }
+    if total > report.len() {
+        println!("\n... {} more (raise --limit)", total - report.len());
+    }
+}
+
+fn print_indexers(entries: &[IndexerEntry]) {
+    if entries.is_empty() {
+        println!("No indexers found");
+        return;
+    }
+    println!("\n=== Indexers ({}) ===\n", entries.len());
+    for entry in entries {
+        if entry.title.is_empty() {
+            println!("{}", entry.id);
+        } else {
+            println!("{} — {}", entry.id, entry.title);
+        }
+        if let Some(ref class) = entry.action_class {
+            println!("  action:     {}", class);
+        }
+        if let Some(ref view) = entry.view_id {
+            println!("  view:       {}", view);
+        }
+        if let Some(ref class) = entry.mview_class {
+            println!("  mview:      {}", class);
+        }
+        if let Some(ref shared) = entry.shared_index {
+            println!("  shared:     {}", shared);
+        }
+        if !entry.subscribed_tables.is_empty() {
+            println!("  tables:     {}", entry.subscribed_tables.join(", "));
+        }
+        for file in &entry.files {
+            println!("  {}", file);
+        }
+        println!();
+    }
+}
+
+fn print_payment_methods(methods: &[PaymentMethod]) {
+    if methods.is_empty() {
+        println!("No payment methods found");
+        return;
+    }
+    println!("\n=== Payment Methods ({}) ===\n", methods.len());
+    for method in methods {
+        let mut flags = Vec::new();
+        if method.is_gateway == Some(true) {
+            flags.push("gateway".to_string());
+        }
+        if method.active == Some(true) {
+            flags.push("active by default".to_string());
+        }
+        if let Some(ref group) = method.group {
+            flags.push(format!("group {}", group));
+        }
+        match method.title {
+            Some(ref title) => print!("{} — {}", method.code, title),
+            None => print!("{}", method.code),
+        }
+        if flags.is_empty() {
+            println!();
+        } else {
+            println!(" ({})", flags.join(", "));
+        }
+        if let Some(ref model) = method.model {
+            match method.model_class {
+                Some(ref class) if class != model => println!("  model:      {} → {}", model, class),
+                _ => println!("  model:      {}", model),
+            }
+        }
+        for renderer in &method.renderers {
+            println!("  renderer:   {}", renderer);
+        }
+        for file in &method.files {
+            println!("  {}", file);
+        }
+        println!();
+    }
+}
+
+fn print_carriers(carriers: &[Carrier]) {
+    if carriers.is_empty() {
+        println!("No shipping carriers found");
+        return;
+    }
+    println!("\n=== Shipping Carriers ({}) ===\n", carriers.len());
+    for carrier in carriers {
+        let label = [carrier.title.as_deref(), carrier.name.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" / ");
+        if label.is_empty() {
+            println!("{}", carrier.code);
+        } else {
+            println!("{} — {}", carrier.code, label);
+        }
+        if let Some(ref model) = carrier.model {
+            println!("  model:      {}", model);
+        }
+        if let Some(active) = carrier.active {
+            println!("  active:     {}", if active { "yes" } else { "no" });
+        }
+        if !carrier.config.is_empty() {
+            let defaults: Vec<String> = carrier.config.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            println!("  defaults:   {}", defaults.join(", "));
+        }
+        for file in &carrier.files {
+            println!("  {}", file);
+        }
+        println!();
+    }
+}
+
+fn print_console_commands(commands: &[ConsoleCommand]) {
+    if commands.is_empty() {
+        println!("No console commands found");
+        return;
+    }
+    println!("\n=== Console Commands ({}) ===\n", commands.len());
+    for command in commands {
+        let name = command.name.as_deref().unwrap_or("(name set at runtime)");
+        match command.description {
+            Some(ref description) => println!("bin/magento {} — {}", name, description),
+            None => println!("bin/magento {}", name),
+        }
+        println!("  class:      {}", command.class);
+        if !command.aliases.is_empty() {
+            println!("  aliases:    {}", command.aliases.join(", "));
+        }
+        for file in command.file.iter().chain(&command.registered_in) {
+            println!("  {}", file);
+        }
+        println!();
+    }
+}
+
+fn print_project_info(info: &ProjectInfo) {
+    if info.files.is_empty() {
+        println!("No app/etc/config.php or app/etc/env.php found");
+        return;
+    }
+    println!("\n=== Project ({}) ===\n", info.files.join(", "));
+    if let Some(ref mode) = info.mode {
+        println!("Mode:          {}", mode);
+    }
+    if let Some(ref prefix) = info.table_prefix {
+        println!("Table prefix:  {}", if prefix.is_empty() { "(none)" } else { prefix });
+    }
+    if !info.modules.is_empty() {
+        let disabled = info.disabled_modules();
+        println!("Modules:       {} ({} enabled, {} disabled)", info.modules.len(), info.modules.len() - disabled.len(), disabled.len());
+        if !disabled.is_empty() {
+            println!("Disabled:      {}", disabled.join(", "));
+        }
+    }
+    for (label, section) in [("Cache:", &info.cache), ("Queue:", &info.queue)] {
+        if let Some(section) = section {
+            println!("{:<14} {}", label, section);
+        }
+    }
+    println!();
+}
+
+fn print_totals_chain(requested: &str, section: &str, chain: &[CollectorStep]) {
+    if requested != section {
+        println!("'{}' totals are collected in the '{}' section", requested, section);
+    }
+    println!("\n=== Totals collectors: {} ({}) ===\n", section, chain.len());
+    for (i, step) in chain.iter().enumerate() {
+        let sort_order = step.sort_order.map_or_else(|| "-".to_string(), |o| o.to_string());
+        println!(
+            "{:>2}. {} [sort_order {}] {}",
+            i + 1,
+            step.code,
+            sort_order,
+            step.class.as_deref().unwrap_or("(no class)")
+        );
+        if let Some(ref file) = step.class_file {
+            println!("      {}", file);
+        }
+        for file in &step.files {
+            println!("      declared in {}", file);
+        }
+    }
+}
+
+/// `index --dry-run`: discovery and preflight only, no model or embedding
+#[allow(clippy::too_many_arguments)]
+fn run_index_dry_run(options: &IndexOptions) -> Result<()> {
+    let (magento_root, database) = (options.magento_root.as_path(), options.database.as_path());
+    println!("Dry run: {:?}\n", magento_root);
+
+    let extra_roots = match &options.extra_roots {
+        Some(roots) => roots.clone(),
+        None => VectorDB::read_header(database).map(|h| h.extra_roots).unwrap_or_default(),
+    };
+    let plan = Indexer::plan(magento_root, &extra_roots, &options.config)?;
+    println!("Would index {} files\n", plan.files.len());
+    Indexer::print_breakdown(&plan.files);
+
+    const MAX_ROWS: usize = 30;
+    let modules = plan.module_counts(magento_root);
+    println!("By module ({}):", modules.len());
+    for (module, count) in modules.iter().take(MAX_ROWS) {
+        println!("  {:>7}  {}", count, module);
+    }
+    if modules.len() > MAX_ROWS {
+        println!("  ... and {} more", modules.len() - MAX_ROWS);
+    }
+
+    println!("\nBy type:");
+    for (file_type, count) in plan.type_counts(magento_root) {
+        println!("  {:>7}  {}", count, file_type);
+    }
 
+    if !plan.skipped.is_empty() {
+        println!("\nSkipped ({}):", plan.skipped.len());
+        const EXAMPLES: usize = 5;
+        let mut reasons: Vec<_> = plan.skipped.iter().map(|(_, r)| *r).collect();
+        reasons.sort();
+        reasons.dedup();
+        for reason in reasons {
+            let paths: Vec<&PathBuf> = plan
+                .skipped
+                .iter()
+                .filter(|(_, r)| *r == reason)
+                .map(|(p, _)| p)
+                .collect();
+            println!("  {:>7}  {}", paths.len(), reason.as_str());
+            for path in paths.iter().take(EXAMPLES) {
+                println!("             {}", path.strip_prefix(magento_root).unwrap_or(path).display());
+            }
+            if paths.len() > EXAMPLES {
+                println!("             ... and {} more", paths.len() - EXAMPLES);
+            }
+        }
+    }
+    println!();
+
+    Indexer::print_estimate(&plan.estimate);
+
+    match preflight::check_disk_space(database, &plan.estimate) {
+        preflight::SpaceCheck::Insufficient { available, required } => println!(
+            "✗ Not enough disk space: ~{} needed, {} available",
+            preflight::format_bytes(required),
+            preflight::format_bytes(available)
+        ),
+        preflight::SpaceCheck::Low { available, required } => println!(
+            "⚠️  Low disk space: ~{} needed, {} available",
+            preflight::format_bytes(required),
+            preflight::format_bytes(available)
+        ),
+        preflight::SpaceCheck::Ok => println!("✓ Disk space OK for {:?}", database),
+    }
+
+    Ok(())
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn run_delta(old: &Path, new: &Path, output: &Path) -> Result<()> {
+    let base = VectorDB::load_existing(old)?;
+    let target = VectorDB::load_existing(new)?;
+    let patch = DeltaPatch::between(&base, &target)?;
+    patch.write(output)?;
+
+    println!("\n=== Delta ===");
+    println!("Added/changed: {}", patch.upserts.len());
+    println!("Removed:       {}", patch.removals.len());
+    println!(
+        "Patch size:    {} (new index: {})",
+        preflight::format_bytes(file_size(output)),
+        preflight::format_bytes(file_size(new))
+    );
+    println!("Written to {:?}", output);
+    Ok(())
+}
+
+fn run_apply(patch_path: &Path, database: &Path, output: Option<&Path>) -> Result<()> {
+    let patch = DeltaPatch::read(patch_path)?;
+    let mut db = VectorDB::load_existing(database)?;
+    patch.apply(&mut db)
+        .with_context(|| format!("Failed to apply {}", patch_path.display()))?;
+
+    let target = output.unwrap_or(database);
+    db.save_atomic(target)?;
+    println!(
+        "✓ Applied {} added/changed, {} removed — {} vectors in {:?}",
+        patch.upserts.len(),
+        patch.removals.len(),
+        db.len(),
+        target
+    );
+    Ok(())
+}
+
+fn run_verify(file: &Path, key: &Path, signature: Option<&Path>) -> Result<()> {
+    let sig_path = signature.map(Path::to_path_buf).unwrap_or_else(|| signing::signature_path(file));
+    signing::verify_file(file, &sig_path, &signing::read_verifying_key(key)?)?;
+    println!("✓ Signature valid: {:?}", file);
+
+    // Delta patches carry their own base/target hashes, checked on apply
+    if DeltaPatch::read(file).is_ok() {
+        return Ok(());
+    }
+    let db = VectorDB::load_existing(file)?;
+    if db.header().checksum.is_empty() {
+        println!("⚠️  No embedded checksum (saved by an older version) — re-save to add one");
+    } else {
+        println!("✓ Checksum valid ({} vectors)", db.len());
+    }
+    Ok(())
+}
+
+fn run_fsck(database: &Path, repair: bool, graph_samples: usize, format: &str) -> Result<()> {
+    let report = fsck::check(database, graph_samples, repair)?;
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("\n=== Fsck {:?} ===", database);
+        println!("Items:       {}", report.integrity.items);
+        println!(
+            "Graph:       {} of {} sampled items found",
+            report.integrity.graph_checked - report.integrity.unreachable.len(),
+            report.integrity.graph_checked
+        );
+        for f in &report.findings {
+            let mark = if f.repaired { "✓ repaired" } else { "✗" };
+            println!("{} [{}] {}", mark, f.area.as_str(), f.problem);
+        }
+        if !report.reindex.is_empty() {
+            println!("\n{} files will be indexed again by the next `index` run", report.reindex.len());
+        }
+        if report.findings.is_empty() {
+            println!("✓ No problems found");
+        }
+    }
+    let unrepaired = report.unrepaired();
+    if unrepaired > 0 && repair {
+        anyhow::bail!("{} problems could not be repaired — reindex with `index --force` or restore from a backup", unrepaired);
+    }
+    if unrepaired > 0 {
+        anyhow::bail!("{} problems found — run with --repair to fix them", unrepaired);
+    }
     Ok(())
 }
 
 fn run_validation(
     magento_root: Option<PathBuf>,
     database: &PathBuf,
-    model_cache: &PathBuf,
+    model_cache: &Path,
     report_path: &PathBuf,
     skip_index: bool,
+    signals_path: Option<&Path>,
+    paraphrase: bool,
 ) -> Result<()> {
     println!("\n╔═══════════════════════════════════════════════════════════╗");
     println!("║          MAGECTOR COMPREHENSIVE VALIDATION                ║");
@@ -432,7 +2423,13 @@ fn run_validation(
     } else {
         println!("\nIndexing Magento codebase...\n");
         // Validation runs always start fresh so results are reproducible.
-        run_index(&magento_path, database, model_cache, None, None, None, true)?;
+        run_index(IndexOptions {
+            magento_root: magento_path.clone(),
+            database: database.to_path_buf(),
+            model_cache: model_cache.to_path_buf(),
+            force: true,
+            ..Default::default()
+        })?;
     }
 
     // Load indexer for search
@@ -441,11 +2438,31 @@ fn run_validation(
 
     // Run validation
     let validator = Validator::new();
-    let report = validator.run(&mut indexer)?;
+    let mut report = validator.run(&mut indexer)?;
+    if paraphrase {
+        let robustness = validator.robustness(&mut indexer)?;
+        robustness.print();
+        report.robustness = Some(robustness);
+    }
 
     // Save report
     validator.save_report(&report, report_path)?;
 
+    if let Some(path) = signals_path {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let signals = validator.failure_signals(&report.test_results, now);
+        let mut out = String::new();
+        for signal in &signals {
+            out.push_str(&serde_json::to_string(signal)?);
+            out.push('\n');
+        }
+        std::fs::write(path, out).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Wrote {} SONA signals to {:?}", signals.len(), path);
+    }
+
     // Final summary
     println!("\n╔═══════════════════════════════════════════════════════════╗");
     println!("║                    FINAL RESULTS                          ║");
@@ -454,6 +2471,9 @@ fn run_validation(
     println!("📊 Overall Accuracy: {:.1}%", report.accuracy);
     println!("✓ Tests Passed: {}/{}", report.passed, report.total_tests);
     println!("✗ Tests Failed: {}", report.failed);
+    if let Some(ref robustness) = report.robustness {
+        println!("🔀 Paraphrase Stability: {:.2}", robustness.score);
+    }
     println!("📁 Index Size: {} vectors", report.index_size);
     println!("⏱  Total Time: {} ms", report.total_time_ms);
     println!("\n📄 Full report saved to: {:?}", report_path);
@@ -473,21 +2493,65 @@ fn run_validation(
 ///
 /// Protocol (one JSON object per line):
 ///   Request:  {"command":"search","query":"...","limit":10}
+///   Request:  {"command":"search","query":"...","deadline_ms":300}
+///   Request:  {"command":"search","query":"...","candidates":200,"return_candidates":true}
+///   Request:  {"command":"search","query":"...","min_loc":20,"max_loc":400,"sort":"recent"}
+///   Request:  {"command":"search","query":"...","owner":"team-checkout"}
+///   Request:  {"command":"search","query":"...","namespace":"docs"}
+///   Request:  {"command":"search_batch","queries":["...","..."],"limit":5}
+///   Request:  {"command":"assemble_context","query":"...","budget":4000}
 ///   Request:  {"command":"stats"}
 ///   Request:  {"command":"watcher_status"}
+///   Request:  {"command":"cancel","request":7}
 ///   Response: {"ok":true,"data":...}
 ///   Error:    {"ok":false,"error":"..."}
+#[allow(clippy::too_many_arguments)]
 fn run_serve(
-    database: &PathBuf,
-    model_cache: &PathBuf,
+    database: &Path,
+    model_cache: &Path,
     magento_root: Option<PathBuf>,
     watch_interval: u64,
     descriptions_db: Option<PathBuf>,
     threads: Option<usize>,
+    map_root: &[RootMap],
+    core_index: Option<&Path>,
+    deadline: Option<Duration>,
+    rewriter_cmd: Option<&str>,
+    tune_ef: bool,
+    expose_vectors: bool,
+    probe_hours: u64,
+    probe_cases: usize,
 ) -> Result<()> {
     eprintln!("Loading model and index for serve mode...");
     let mg_root = magento_root.clone().unwrap_or_default();
     let mut indexer = Indexer::with_options(&mg_root, model_cache, database, threads, None)?;
+    if !map_root.is_empty() {
+        eprintln!("Remapped {} stored paths", indexer.map_roots(map_root));
+    }
+    if let Some(core_index) = core_index {
+        indexer.set_core_index(core_index)?;
+    }
+    if let Some(core_vectors) = indexer.core_vectors() {
+        eprintln!("Core index layered under the overlay: {} vectors", core_vectors);
+    }
+    if let Some(ref root) = magento_root {
+        let config = ProjectConfig::load(root)?;
+        if !config.boost.is_empty() {
+            eprintln!("Applying {} boost rules from {}", config.boost.len(), magector_core::config::CONFIG_FILE);
+        }
+        indexer.apply_ranking(&config);
+        indexer.set_redaction(&config.redact)?;
+        indexer.load_module_states(root);
+        indexer.load_patches(root);
+    }
+    indexer.set_search_budget(deadline);
+    if let Some(cmd) = rewriter_cmd {
+        eprintln!("Rewriting queries with: {}", cmd);
+    }
+    indexer.set_query_rewriter(query_rewriter(rewriter_cmd));
+    if tune_ef {
+        tune_ef_search(&mut indexer, database)?;
+    }
 
     // Auto-detect descriptions DB
     let desc_db_path = descriptions_db.unwrap_or_else(|| {
@@ -508,6 +2572,9 @@ fn run_serve(
     let data_db = Arc::new(Mutex::new(data_db));
     eprintln!("DataDb opened at {:?}", data_db_path);
 
+    // Ephemeral per-session boosts, never saved
+    let sessions = Mutex::new(Sessions::default());
+
     // Watcher status (shared with watcher thread)
     let watcher_status = Arc::new(Mutex::new(WatcherStatus {
         running: false,
@@ -520,7 +2587,7 @@ fn run_serve(
     if let Some(ref root) = magento_root {
         let idx = Arc::clone(&indexer);
         let root = root.clone();
-        let db = database.clone();
+        let db = database.to_path_buf();
         let interval = Duration::from_secs(watch_interval);
         let status = Arc::clone(&watcher_status);
 
@@ -539,6 +2606,34 @@ fn run_serve(
         eprintln!("File watcher enabled (interval: {}s)", watch_interval);
     }
 
+    // Probe accuracy in the background: once now as a baseline, then every
+    // --probe-hours
+    let probes = Arc::new(Mutex::new(ProbeHistory::default()));
+    if probe_hours > 0 {
+        *probes.lock().unwrap() = ProbeHistory::open(database.with_file_name(probe::PROBE_FILE));
+        let validator = Validator::with_cases(probe::sample(Validator::new().test_cases(), probe_cases));
+        eprintln!("Probing {} validation cases every {}h", validator.test_cases().len(), probe_hours);
+        let idx = Arc::clone(&indexer);
+        let history = Arc::clone(&probes);
+        let interval = Duration::from_secs(probe_hours * 3600);
+        std::thread::Builder::new()
+            .name("probe".to_string())
+            .spawn(move || loop {
+                let run = probe::run(&idx, &validator);
+                match run {
+                    Ok(run) => {
+                        eprintln!("Probe: {}/{} cases passed ({:.1}%)", run.passed, run.total, run.accuracy);
+                        if let Some(warning) = history.lock().unwrap_or_else(|e| e.into_inner()).record(run) {
+                            eprintln!("Warning: {}", warning);
+                        }
+                    }
+                    Err(e) => eprintln!("Warning: probe run failed: {}", e),
+                }
+                std::thread::sleep(interval);
+            })
+            .context("Failed to spawn probe thread")?;
+    }
+
     // Write own PID to data.db so Node.js can discover us via DB query
     {
         let ddb = data_db.lock().unwrap();
@@ -558,98 +2653,683 @@ fn run_serve(
     eprintln!("Ready. Listening on stdin for JSON queries.");
 
     // Signal readiness with a JSON line on stdout
-    let stdout = io::stdout();
-    let mut out = io::BufWriter::new(stdout.lock());
+    let out = Arc::new(Mutex::new(io::stdout()));
     let watcher_running = magento_root.is_some();
-    writeln!(
-        out,
-        r#"{{"ok":true,"ready":true,"vectors":{},"watcher":{}}}"#,
-        vectors, watcher_running
-    )?;
-    out.flush()?;
-
-    let stdin = io::stdin();
-    for line in stdin.lock().lines() {
-        let line = match line {
-            Ok(l) => l,
-            Err(_) => break,
-        };
-        let line = line.trim().to_string();
-        if line.is_empty() {
-            continue;
-        }
+    write_line(&out, &format!(r#"{{"ok":true,"ready":true,"vectors":{},"watcher":{}}}"#, vectors, watcher_running))?;
+
+    // Requests are read on their own thread so a `cancel` reaches a search
+    // that is still running; everything else is answered in order below
+    let cancels: Arc<Mutex<HashMap<String, CancelToken>>> = Arc::default();
+    let (requests, received) = std::sync::mpsc::channel::<serde_json::Result<serde_json::Value>>();
+    {
+        let out = Arc::clone(&out);
+        let cancels = Arc::clone(&cancels);
+        std::thread::Builder::new()
+            .name("stdin".to_string())
+            .spawn(move || {
+                for line in io::stdin().lock().lines() {
+                    let Ok(line) = line else { break };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let req = serde_json::from_str::<serde_json::Value>(line.trim());
+                    if let Ok(ref req) = req {
+                        if req.get("command").and_then(|v| v.as_str()) == Some("cancel") {
+                            let response = cancel_request(&cancels, req);
+                            if write_line(&out, &with_request_id(response, req.get("id"))).is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+                        if let Some(id) = req.get("id") {
+                            cancels.lock().unwrap().entry(id.to_string()).or_default();
+                        }
+                    }
+                    if requests.send(req).is_err() {
+                        break;
+                    }
+                }
+            })
+            .context("Failed to spawn stdin thread")?;
+    }
 
-        let response = match serde_json::from_str::<serde_json::Value>(&line) {
+    for req in received {
+        let response = match req {
             Ok(req) => {
-                // Catch panics to prevent serve process death
-                let indexer_ref = &indexer;
-                let watcher_ref = &watcher_status;
-                let db_ref = database;
-                let desc_db_ref = &desc_db_path_for_serve;
-                let data_db_ref = &data_db;
-                match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                    handle_serve_request(
-                        indexer_ref,
-                        watcher_ref,
-                        db_ref,
-                        desc_db_ref,
-                        data_db_ref,
+                let key = req.get("id").map(|id| id.to_string());
+                let cancel = key.as_ref().and_then(|k| cancels.lock().unwrap().get(k).cloned()).unwrap_or_default();
+                let response = if cancel.is_cancelled() {
+                    CANCELLED_RESPONSE.to_string()
+                } else {
+                    answer_serve_request(
+                        &indexer,
+                        &watcher_status,
+                        database,
+                        &desc_db_path_for_serve,
+                        &data_db,
+                        &sessions,
+                        &probes,
+                        expose_vectors,
+                        &cancel,
                         &req,
                     )
-                })) {
-                    Ok(resp) => resp,
-                    Err(_) => {
-                        eprintln!("Panic caught in request handler, serve process continues");
-                        r#"{"ok":false,"error":"Internal panic caught"}"#.to_string()
-                    }
+                };
+                if let Some(ref key) = key {
+                    cancels.lock().unwrap().remove(key);
                 }
+                // Whatever a cancelled request got done is dropped
+                let response = if cancel.is_cancelled() { CANCELLED_RESPONSE.to_string() } else { response };
+                with_request_id(response, req.get("id"))
             }
             Err(e) => format!(r#"{{"ok":false,"error":"Invalid JSON: {}"}}"#, e),
         };
-
-        writeln!(out, "{}", response)?;
-        out.flush()?;
+        write_line(&out, &response)?;
     }
 
     Ok(())
 }
 
+/// Most HNSW candidates a serve search may ask to rerank (`candidates`)
+const MAX_CANDIDATE_POOL: u64 = 5000;
+
+/// Answer of a request cancelled before it finished
+const CANCELLED_RESPONSE: &str = r#"{"ok":false,"error":"Cancelled","cancelled":true}"#;
+
+/// [`handle_serve_request`], answering a panic with an error so the serve
+/// process lives on
+#[allow(clippy::too_many_arguments)]
+fn answer_serve_request(
+    indexer: &Arc<Mutex<Indexer>>,
+    watcher_status: &Arc<Mutex<WatcherStatus>>,
+    db_path: &Path,
+    desc_db_path: &Path,
+    data_db: &Arc<Mutex<DataDb>>,
+    sessions: &Mutex<Sessions>,
+    probes: &Mutex<ProbeHistory>,
+    expose_vectors: bool,
+    cancel: &CancelToken,
+    req: &serde_json::Value,
+) -> String {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        handle_serve_request(
+            indexer,
+            watcher_status,
+            db_path,
+            desc_db_path,
+            data_db,
+            sessions,
+            probes,
+            expose_vectors,
+            cancel,
+            req,
+        )
+    })) {
+        Ok(resp) => resp,
+        Err(_) => {
+            eprintln!("Panic caught in request handler, serve process continues");
+            r#"{"ok":false,"error":"Internal panic caught"}"#.to_string()
+        }
+    }
+}
+
+/// Serve `cancel`: flag the request `request` (its `id`) as cancelled, if it
+/// is queued or running
+fn cancel_request(cancels: &Mutex<HashMap<String, CancelToken>>, req: &serde_json::Value) -> String {
+    let Some(id) = req.get("request") else {
+        return r#"{"ok":false,"error":"Missing 'request' field"}"#.to_string();
+    };
+    match cancels.lock().unwrap().get(&id.to_string()) {
+        Some(token) => {
+            token.cancel();
+            r#"{"ok":true,"data":{"cancelled":true}}"#.to_string()
+        }
+        None => r#"{"ok":true,"data":{"cancelled":false}}"#.to_string(),
+    }
+}
+
+/// `response` with the `id` of its request, so answers can be matched to
+/// requests when a `cancel` is answered out of order
+fn with_request_id(response: String, id: Option<&serde_json::Value>) -> String {
+    match (id, response.strip_prefix('{')) {
+        (Some(id), Some(rest)) => format!(r#"{{"id":{},{}"#, id, rest),
+        _ => response,
+    }
+}
+
+fn write_line(out: &Mutex<io::Stdout>, line: &str) -> io::Result<()> {
+    let mut out = out.lock().unwrap_or_else(|e| e.into_inner());
+    writeln!(out, "{}", line)?;
+    out.flush()
+}
+
+/// Every [`EF_TUNING_STRIDE`]-th validation query that expects a match
+fn sample_queries(validator: &Validator) -> Vec<&str> {
+    validator
+        .test_cases()
+        .iter()
+        .filter(|case| case.max_score.is_none())
+        .step_by(EF_TUNING_STRIDE)
+        .map(|case| case.query.as_str())
+        .collect()
+}
+
+/// `serve --tune-ef`: tune the HNSW beam on queries generated from the
+/// indexed files unless the index records a beam tuned at about its current
+/// size, and save the choice in the header
+fn tune_ef_search(indexer: &mut Indexer, database: &Path) -> Result<()> {
+    let header = indexer.index_header();
+    let (tuned, tuned_items) = (header.ef_search, header.ef_tuned_items);
+    let items = indexer.stats().vectors_created;
+    if tuned > 0 && (items as f32) <= tuned_items as f32 * EF_RETUNE_GROWTH {
+        eprintln!("HNSW ef_search: {} (tuned)", tuned);
+        return Ok(());
+    }
+    let cases = indexer.generate_cases(EF_TUNING_QUERIES);
+    let validator = Validator::new();
+    let queries: Vec<&str> = if cases.is_empty() {
+        sample_queries(&validator)
+    } else {
+        cases.iter().map(|case| case.query.as_str()).collect()
+    };
+    let tuning = indexer.tune_ef_search(&queries, 10, magector_core::vectordb::EF_STABILITY_TARGET)?;
+    let overlaps: Vec<String> =
+        tuning.overlaps.iter().map(|(ef, overlap)| format!("ef={} {:.1}%", ef, overlap * 100.0)).collect();
+    eprintln!(
+        "HNSW ef_search tuned to {} on {} queries ({})",
+        tuning.ef_search,
+        tuning.queries,
+        overlaps.join(", ")
+    );
+    VectorDB::update_saved_header(database, |header| {
+        header.ef_search = tuning.ef_search;
+        header.ef_tuned_items = items;
+    })
+}
+
+fn print_semdiff(diff: &magector_core::semdiff::SemanticDiff) {
+    use magector_core::semdiff::Change;
+    println!("\n=== Semantic diff: {} → {} ===\n", diff.old, diff.new);
+    if diff.chunks.is_empty() {
+        println!("No changes");
+        return;
+    }
+    for chunk in &diff.chunks {
+        let lines = match (chunk.old_lines, chunk.new_lines) {
+            (_, Some((start, end))) | (Some((start, end)), None) => format!("lines {}-{}", start, end),
+            (None, None) => String::new(),
+        };
+        let similarity = chunk.similarity.map(|s| format!("  similarity {:.3}", s)).unwrap_or_default();
+        let name = match &chunk.old_name {
+            Some(old_name) => format!("{} → {}", old_name, chunk.name),
+            None => chunk.name.clone(),
+        };
+        println!("{:<10} {}  ({}){}", chunk.change.as_str(), name, lines, similarity);
+    }
+    let counts: Vec<String> = [Change::Semantic, Change::Renamed, Change::Added, Change::Removed, Change::Minor, Change::Cosmetic]
+        .iter()
+        .filter(|&&c| diff.count(c) > 0)
+        .map(|&c| format!("{} {}", diff.count(c), c.as_str()))
+        .collect();
+    println!("\n{} (threshold {})", counts.join(", "), diff.threshold);
+}
+
+/// Text form of `magector map`: clusters by size, then the strays
+fn render_code_map(map: &codemap::CodeMap) -> String {
+    let mut out = format!("\n=== Code map: {} files, {} clusters ===\n", map.points.len(), map.clusters.len());
+    let mut clusters: Vec<&codemap::MapCluster> = map.clusters.iter().filter(|c| c.size > 0).collect();
+    clusters.sort_by_key(|c| std::cmp::Reverse(c.size));
+    for c in clusters {
+        let groups: Vec<String> = c.groups.iter().map(|(g, n)| format!("{} {}", g, n)).collect();
+        out.push_str(&format!(
+            "  #{:<3} {:<40} {:>5} files  {:>3.0}%  ({})\n",
+            c.id,
+            c.label,
+            c.size,
+            c.share * 100.0,
+            groups.join(", ")
+        ));
+    }
+    let strays: Vec<&codemap::MapPoint> = map.strays().collect();
+    if !strays.is_empty() {
+        out.push_str(&format!("\nFiles among another module's code ({}):\n", strays.len()));
+        for p in strays {
+            out.push_str(&format!("  {}  ({} in cluster of {})\n", p.path, p.group, map.clusters[p.cluster].label));
+        }
+    }
+    out
+}
+
+/// `--rewriter-cmd` as a query rewriter
+fn query_rewriter(cmd: Option<&str>) -> Option<Box<dyn magector_core::query::QueryRewriter>> {
+    cmd.map(|cmd| Box::new(magector_core::query::CommandRewriter::new(cmd)) as Box<dyn magector_core::query::QueryRewriter>)
+}
+
+/// `today`, `yesterday` or `3 days ago` for a Unix time
+fn days_ago(time: u64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    match now.saturating_sub(time) / 86_400 {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        days => format!("{} days ago", days),
+    }
+}
+
+/// `120 lines, 4.2 KB, modified 3 days ago`
+fn describe_metrics(metrics: &magector_core::FileMetrics) -> String {
+    let mut text = format!("{} lines, {}", metrics.loc, preflight::format_bytes(metrics.bytes));
+    if let Some(modified) = metrics.modified {
+        text.push_str(&format!(", modified {}", days_ago(modified)));
+    }
+    if metrics.lossy {
+        text.push_str(", not UTF-8");
+    }
+    text
+}
+
+/// `min_loc`, `max_loc`, `owner`, `namespace` and `sort` of a search request
+fn request_filter(req: &serde_json::Value) -> std::result::Result<ResultFilter, String> {
+    let invalid = |field: &str, e: String| {
+        serde_json::json!({"ok": false, "error": format!("Invalid '{}': {}", field, e)}).to_string()
+    };
+    let lines = |field: &str| match req.get(field) {
+        None => Ok(None),
+        Some(v) => v.as_u64().map(|n| Some(n as usize)).ok_or_else(|| invalid(field, "expected a line count".into())),
+    };
+    let order = match req.get("sort") {
+        None => ResultOrder::default(),
+        Some(v) => v.as_str().unwrap_or_default().parse().map_err(|e| invalid("sort", e))?,
+    };
+    let owner = match req.get("owner") {
+        None => None,
+        Some(v) => Some(v.as_str().map(str::to_string).ok_or_else(|| invalid("owner", "expected a string".into()))?),
+    };
+    let namespace = match req.get("namespace") {
+        None => None,
+        Some(v) => Some(v.as_str().unwrap_or_default().parse().map_err(|e| invalid("namespace", e))?),
+    };
+    Ok(ResultFilter { min_loc: lines("min_loc")?, max_loc: lines("max_loc")?, owner, namespace, order })
+}
+
+/// Optional per-request ranking experiment: `"weights": {"semantic":0.7,"keyword":0.3,"sona":0.0}`.
+/// The error is a ready serve response.
+fn request_weights(req: &serde_json::Value) -> std::result::Result<Option<magector_core::ScoreWeights>, String> {
+    let Some(w) = req.get("weights") else {
+        return Ok(None);
+    };
+    serde_json::from_value::<magector_core::ScoreWeights>(w.clone())
+        .map_err(anyhow::Error::from)
+        .and_then(|w| w.validate().map(|_| Some(w)))
+        .map_err(|e| serde_json::json!({"ok": false, "error": format!("Invalid 'weights': {}", e)}).to_string())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn handle_serve_request(
     indexer: &Arc<Mutex<Indexer>>,
     watcher_status: &Arc<Mutex<WatcherStatus>>,
-    db_path: &PathBuf,
-    desc_db_path: &PathBuf,
+    db_path: &Path,
+    desc_db_path: &Path,
     data_db: &Arc<Mutex<DataDb>>,
+    sessions: &Mutex<Sessions>,
+    probes: &Mutex<ProbeHistory>,
+    expose_vectors: bool,
+    cancel: &CancelToken,
     req: &serde_json::Value,
 ) -> String {
     let command = req.get("command").and_then(|v| v.as_str()).unwrap_or("");
 
     match command {
         "search" => {
+            let started = Instant::now();
             let query = match req.get("query").and_then(|v| v.as_str()) {
                 Some(q) => q,
                 None => return r#"{"ok":false,"error":"Missing 'query' field"}"#.to_string(),
             };
             let limit = req.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+            let weights = match request_weights(req) {
+                Ok(w) => w,
+                Err(e) => return e,
+            };
+            let filter = match request_filter(req) {
+                Ok(f) => f,
+                Err(e) => return e,
+            };
+            let fetch = filter.fetch_limit(limit);
+            // Ranking diagnostics: how many HNSW candidates get reranked,
+            // and whether to return them with their scores
+            let pool = match req.get("candidates") {
+                None => 0,
+                Some(v) => match v.as_u64() {
+                    Some(n @ 1..=MAX_CANDIDATE_POOL) => n as usize,
+                    _ => {
+                        return format!(
+                            r#"{{"ok":false,"error":"'candidates' must be between 1 and {}"}}"#,
+                            MAX_CANDIDATE_POOL
+                        )
+                    }
+                },
+            };
+            let return_candidates = req.get("return_candidates").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            let mut guard = indexer.lock().unwrap();
+            let mut idx = guard.with_cancel_token(cancel.clone());
+            let weights = weights.unwrap_or_else(|| idx.score_weights());
+            // Per-request budget overrides --deadline-ms; 0 turns it off
+            let budget = match req.get("deadline_ms").and_then(|v| v.as_u64()) {
+                Some(0) => None,
+                Some(ms) => Some(Duration::from_millis(ms)),
+                None => idx.search_budget(),
+            };
 
-            let mut idx = indexer.lock().unwrap();
-
-            let mut results = match idx.search(query, limit) {
+            // The budget counts from the request, so waiting for the lock uses it up too.
+            // Diagnostic searches always rerank in full.
+            let mut candidates = None;
+            let (searched, degraded) = match budget {
+                _ if pool > 0 || return_candidates => match idx.search_pool(query, fetch, weights, pool, filter.namespace) {
+                    Ok((r, c)) => {
+                        candidates = Some(c).filter(|_| return_candidates);
+                        (Ok(r), None)
+                    }
+                    Err(e) => (Err(e), None),
+                },
+                Some(budget) => match idx.search_within(query, fetch, weights, started + budget, filter.namespace) {
+                    Ok((r, degraded)) => (Ok(r), Some(degraded)),
+                    Err(e) => (Err(e), None),
+                },
+                None => (idx.search_weighted(query, fetch, weights, filter.namespace), None),
+            };
+            let mut results = match searched {
                 Ok(r) => r,
                 Err(e) => return format!(r#"{{"ok":false,"error":"Search error: {}"}}"#, e),
             };
 
-            results.truncate(limit);
+            // Mild preference for the modules this session has opened files in.
+            // Candidates keep their pre-boost, pre-filter score and rank.
+            if let Some(session) = req.get("session").and_then(|v| v.as_str()) {
+                sessions.lock().unwrap().boost(session, &mut results);
+            }
+            filter.apply(&mut results, limit);
+
+            if let Some(candidates) = candidates {
+                return match (serde_json::to_string(&results), serde_json::to_string(&candidates)) {
+                    (Ok(json), Ok(candidates)) => {
+                        format!(r#"{{"ok":true,"data":{},"candidates":{}}}"#, json, candidates)
+                    }
+                    (Err(e), _) | (_, Err(e)) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+                };
+            }
+            match (serde_json::to_string(&results), degraded) {
+                (Ok(json), Some(degraded)) => format!(r#"{{"ok":true,"data":{},"degraded":{}}}"#, json, degraded),
+                (Ok(json), None) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                (Err(e), _) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+            }
+        }
+        "search_batch" => {
+            let queries: Vec<&str> = match req.get("queries").and_then(|v| v.as_array()) {
+                Some(qs) if !qs.is_empty() => match qs.iter().map(|q| q.as_str()).collect::<Option<Vec<_>>>() {
+                    Some(qs) => qs,
+                    None => return r#"{"ok":false,"error":"'queries' must be an array of strings"}"#.to_string(),
+                },
+                _ => return r#"{"ok":false,"error":"Missing 'queries' field"}"#.to_string(),
+            };
+            let limit = req.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+            let weights = match request_weights(req) {
+                Ok(w) => w,
+                Err(e) => return e,
+            };
 
+            let mut guard = indexer.lock().unwrap();
+            let mut idx = guard.with_cancel_token(cancel.clone());
+            let weights = weights.unwrap_or_else(|| idx.score_weights());
+            let results = match idx.search_batch(&queries, limit, weights) {
+                Ok(r) => r,
+                Err(e) => return format!(r#"{{"ok":false,"error":"Search error: {}"}}"#, e),
+            };
             match serde_json::to_string(&results) {
                 Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
                 Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
             }
         }
+        "assemble_context" => {
+            // Search hits plus their di.xml/layout wiring, sized to a token budget
+            let query = match req.get("query").and_then(|v| v.as_str()) {
+                Some(q) => q,
+                None => return r#"{"ok":false,"error":"Missing 'query' field"}"#.to_string(),
+            };
+            let budget = match req.get("budget").and_then(|v| v.as_u64()) {
+                Some(0) => return r#"{"ok":false,"error":"'budget' must be a positive token count"}"#.to_string(),
+                Some(b) => b as usize,
+                None => 4000,
+            };
+            let mut guard = indexer.lock().unwrap();
+            let mut idx = guard.with_cancel_token(cancel.clone());
+            match magector_core::context::assemble(&mut idx, query, budget) {
+                Ok(pack) => match serde_json::to_string(&pack) {
+                    Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                    Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+                },
+                Err(e) => format!(r#"{{"ok":false,"error":"Search error: {}"}}"#, e),
+            }
+        }
         "stats" => {
             let idx = indexer.lock().unwrap();
             let stats = idx.stats();
-            format!(r#"{{"ok":true,"data":{{"vectors":{}}}}}"#, stats.vectors_created)
+            let only_modules = serde_json::to_string(&idx.index_header().only_modules).unwrap_or_else(|_| "[]".into());
+            let probes = serde_json::to_string(&probes.lock().unwrap().trend()).unwrap_or_else(|_| "null".into());
+            format!(
+                r#"{{"ok":true,"data":{{"vectors":{},"partial":{},"only_modules":{},"building":{},"core_vectors":{},"pooling":"{}","coarse_dim":{},"two_stage":{},"ef_search":{},"probes":{}}}}}"#,
+                stats.vectors_created,
+                idx.index_header().is_partial(),
+                only_modules,
+                idx.index_header().building,
+                idx.core_vectors().map_or("null".to_string(), |n| n.to_string()),
+                idx.index_header().pooling,
+                idx.index_header().coarse_dim,
+                idx.index_header().two_stage,
+                idx.index_header().ef_search,
+                probes
+            )
+        }
+        "queues" => {
+            let root = indexer.lock().unwrap().magento_root().to_path_buf();
+            let filter = req.get("filter").and_then(|v| v.as_str());
+            match QueueTopology::scan(&root) {
+                Ok(topology) => match serde_json::to_string(&topology.routes(filter)) {
+                    Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                    Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+                },
+                Err(e) => format!(r#"{{"ok":false,"error":"Queue scan error: {}"}}"#, e),
+            }
+        }
+        "indexers" => {
+            let root = indexer.lock().unwrap().magento_root().to_path_buf();
+            let filter = req.get("filter").and_then(|v| v.as_str());
+            match ConfigXref::scan(&root, &XmlAnalyzer::new()) {
+                Ok(xref) => match serde_json::to_string(&xref.indexers.entries(filter)) {
+                    Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                    Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+                },
+                Err(e) => format!(r#"{{"ok":false,"error":"Indexer scan error: {}"}}"#, e),
+            }
+        }
+        "payments" => {
+            let root = indexer.lock().unwrap().magento_root().to_path_buf();
+            let filter = req.get("filter").and_then(|v| v.as_str());
+            match ConfigXref::scan(&root, &XmlAnalyzer::new()) {
+                Ok(xref) => match serde_json::to_string(&xref.payments.methods(filter)) {
+                    Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                    Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+                },
+                Err(e) => format!(r#"{{"ok":false,"error":"Payment scan error: {}"}}"#, e),
+            }
+        }
+        "carriers" => {
+            let root = indexer.lock().unwrap().magento_root().to_path_buf();
+            let filter = req.get("filter").and_then(|v| v.as_str());
+            match ConfigXref::scan(&root, &XmlAnalyzer::new()) {
+                Ok(xref) => match serde_json::to_string(&xref.carriers.carriers(filter)) {
+                    Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                    Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+                },
+                Err(e) => format!(r#"{{"ok":false,"error":"Carrier scan error: {}"}}"#, e),
+            }
+        }
+        "console_commands" => {
+            let root = indexer.lock().unwrap().magento_root().to_path_buf();
+            let filter = req.get("filter").and_then(|v| v.as_str());
+            match ConfigXref::scan(&root, &XmlAnalyzer::new()) {
+                Ok(xref) => match serde_json::to_string(&xref.console.commands(filter)) {
+                    Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                    Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+                },
+                Err(e) => format!(r#"{{"ok":false,"error":"Console command scan error: {}"}}"#, e),
+            }
+        }
+        "graphql_field" => {
+            // "Query.products", or a bare field name to match it on any type
+            let field = match req.get("field").and_then(|v| v.as_str()) {
+                Some(f) => f,
+                None => return r#"{"ok":false,"error":"Missing 'field' field"}"#.to_string(),
+            };
+            let mut idx = indexer.lock().unwrap();
+            match idx.config_xref() {
+                Ok(xref) => match serde_json::to_string(&xref.graphql.lookup(field)) {
+                    Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                    Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+                },
+                Err(e) => format!(r#"{{"ok":false,"error":"Schema scan error: {}"}}"#, e),
+            }
+        }
+        "get_vectors" => {
+            if !expose_vectors {
+                return r#"{"ok":false,"error":"Vector retrieval is off — start serve with --expose-vectors"}"#.to_string();
+            }
+            let paths: Vec<&str> = req
+                .get("paths")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+                .unwrap_or_default();
+            let ids: Vec<usize> = req
+                .get("ids")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|v| v.as_u64().map(|id| id as usize)).collect())
+                .unwrap_or_default();
+            if paths.is_empty() && ids.is_empty() {
+                return r#"{"ok":false,"error":"Missing 'paths' or 'ids' field"}"#.to_string();
+            }
+            let items = indexer.lock().unwrap().item_vectors(&paths, &ids);
+            match serde_json::to_string(&items) {
+                Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+            }
+        }
+        "who_uses_js_component" => {
+            // RequireJS name or alias ("Magento_Checkout/js/view/minicart", "collapsible")
+            let component = match req.get("component").and_then(|v| v.as_str()) {
+                Some(c) => c,
+                None => return r#"{"ok":false,"error":"Missing 'component' field"}"#.to_string(),
+            };
+            let mut idx = indexer.lock().unwrap();
+            match idx.config_xref() {
+                Ok(xref) => {
+                    let data = serde_json::json!({
+                        "component": xref.js_components.resolve(component),
+                        "templates": xref.js_components.templates_for_component(component),
+                    });
+                    format!(r#"{{"ok":true,"data":{}}}"#, data)
+                }
+                Err(e) => format!(r#"{{"ok":false,"error":"Template scan error: {}"}}"#, e),
+            }
+        }
+        "js_mixins" => {
+            // Effective JS module: the target after requirejs-config aliases
+            // and every mixin declared for it
+            let module = match req.get("module").and_then(|v| v.as_str()) {
+                Some(m) => m,
+                None => return r#"{"ok":false,"error":"Missing 'module' field"}"#.to_string(),
+            };
+            let mut idx = indexer.lock().unwrap();
+            match idx.config_xref() {
+                Ok(xref) => {
+                    let data = serde_json::json!({
+                        "module": xref.js_components.resolve(module),
+                        "mixins": xref.js_components.mixins_for_component(module),
+                    });
+                    format!(r#"{{"ok":true,"data":{}}}"#, data)
+                }
+                Err(e) => format!(r#"{{"ok":false,"error":"requirejs-config scan error: {}"}}"#, e),
+            }
+        }
+        "explain_di" => {
+            // Preferences, plugins, constructor injections and di.xml files of a class
+            let class = match req.get("class").and_then(|v| v.as_str()) {
+                Some(c) if !c.is_empty() => c,
+                _ => return r#"{"ok":false,"error":"Missing 'class' field"}"#.to_string(),
+            };
+            let mut idx = indexer.lock().unwrap();
+            // The constructor comes from the class source when it is indexed
+            let source_file = idx.class_file(class);
+            let constructor = source_file
+                .as_ref()
+                .and_then(|file| ReadScope::new(idx.magento_root()).ok()?.with_extra_roots(&idx.extra_root_paths()).resolve(file).ok())
+                .and_then(|path| fs::read_to_string(path).ok())
+                .and_then(|content| {
+                    let mut analyzer = magector_core::PhpAstAnalyzer::new().ok()?;
+                    Some(magector_core::di::constructor_params(&analyzer.analyze(&content)))
+                })
+                .unwrap_or_default();
+            match idx.config_xref() {
+                Ok(xref) => {
+                    let mut data = serde_json::json!(xref.di.explain(class, &constructor));
+                    data["source_file"] = serde_json::json!(source_file);
+                    format!(r#"{{"ok":true,"data":{}}}"#, data)
+                }
+                Err(e) => format!(r#"{{"ok":false,"error":"di.xml scan error: {}"}}"#, e),
+            }
+        }
+        "trace_request" => {
+            // URL path → route, controller, layout handles, blocks and templates
+            let url = match req.get("url").and_then(|v| v.as_str()) {
+                Some(u) if !u.is_empty() => u,
+                _ => return r#"{"ok":false,"error":"Missing 'url' field"}"#.to_string(),
+            };
+            let mut idx = indexer.lock().unwrap();
+            match idx.trace_request(url) {
+                Ok(trace) => match serde_json::to_string(&trace) {
+                    Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                    Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+                },
+                Err(e) => format!(r#"{{"ok":false,"error":{}}}"#, serde_json::Value::from(format!("{:#}", e))),
+            }
+        }
+        "project_info" => {
+            let root = indexer.lock().unwrap().magento_root().to_path_buf();
+            match ProjectInfo::scan(&root) {
+                Ok(info) => match serde_json::to_string(&info) {
+                    Ok(json) => format!(r#"{{"ok":true,"data":{}}}"#, json),
+                    Err(e) => format!(r#"{{"ok":false,"error":"Serialize error: {}"}}"#, e),
+                },
+                Err(e) => format!(r#"{{"ok":false,"error":{}}}"#, serde_json::Value::from(format!("Project scan error: {:#}", e))),
+            }
+        }
+        "totals" => {
+            let root = indexer.lock().unwrap().magento_root().to_path_buf();
+            let section = req.get("section").and_then(|v| v.as_str()).unwrap_or("quote");
+            match TotalsConfig::scan(&root) {
+                Ok(config) => match config.resolve_section(section) {
+                    Some(resolved) => {
+                        let data = serde_json::json!({ "section": resolved, "collectors": config.chain(&resolved) });
+                        format!(r#"{{"ok":true,"data":{}}}"#, data)
+                    }
+                    None => format!(
+                        r#"{{"ok":false,"error":{}}}"#,
+                        serde_json::Value::from(format!("No totals collectors declared for section '{}'", section))
+                    ),
+                },
+                Err(e) => format!(r#"{{"ok":false,"error":"Totals scan error: {}"}}"#, e),
+            }
         }
         "watcher_status" => {
             let s = watcher_status.lock().unwrap();
@@ -668,33 +3348,33 @@ fn handle_serve_request(
             }
             let mut idx = indexer.lock().unwrap();
             for signal in &signals {
-                // Re-embed the query for LoRA training
-                let query = if signal.query.is_empty() {
-                    signal.original_query.as_deref().unwrap_or("")
-                } else {
-                    &signal.query
-                };
-                let query_emb = if !query.is_empty() {
-                    idx.embed_query(query).ok()
-                } else {
-                    None
-                };
-                if let Some(ref mut sona) = idx.sona {
-                    if let Some(ref qe) = query_emb {
-                        // Use query as its own target for self-supervised LoRA learning
-                        sona.learn_with_embeddings(signal, Some(qe), Some(qe));
-                    } else {
-                        sona.learn(signal);
-                    }
-                }
-            }
-            if let Some(ref sona) = idx.sona {
-                let sona_path = db_path.with_extension("sona");
-                let _ = sona.save(&sona_path);
+                idx.learn_signal(signal);
             }
+            let _ = idx.save_sona();
             format!(r#"{{"ok":true,"data":{{"learned":{}}}}}"#, signals.len())
         }
 
+        "session_open" => {
+            let (session, path) = match (
+                req.get("session").and_then(|v| v.as_str()),
+                req.get("path").and_then(|v| v.as_str()),
+            ) {
+                (Some(s), Some(p)) => (s, p),
+                _ => return r#"{"ok":false,"error":"Missing 'session' or 'path' field"}"#.to_string(),
+            };
+            let modules = sessions.lock().unwrap().open_file(session, path);
+            format!(r#"{{"ok":true,"data":{{"modules":{}}}}}"#, serde_json::to_string(&modules).unwrap_or_else(|_| "[]".into()))
+        }
+
+        "session_end" => {
+            let session = match req.get("session").and_then(|v| v.as_str()) {
+                Some(s) => s,
+                None => return r#"{"ok":false,"error":"Missing 'session' field"}"#.to_string(),
+            };
+            let ended = sessions.lock().unwrap().end(session);
+            format!(r#"{{"ok":true,"data":{{"ended":{}}}}}"#, ended)
+        }
+
         "sona_status" => {
             let idx = indexer.lock().unwrap();
             let patterns = idx.sona.as_ref()
@@ -705,11 +3385,22 @@ fn handle_serve_request(
                 .map(|s| s.learned.term_adjustments.len()).unwrap_or(0);
             let global_count = idx.sona.as_ref()
                 .map(|s| s.learned.global_count).unwrap_or(0);
-            format!(r#"{{"ok":true,"data":{{"learned_patterns":{},"total_observations":{},"term_patterns":{},"global_observations":{}}}}}"#, patterns, observations, term_patterns, global_count)
+            let limits = idx.sona.as_ref().map(|s| s.limits()).unwrap_or_default();
+            let evicted = idx.sona.as_ref().map(|s| s.evicted()).unwrap_or(0);
+            format!(
+                r#"{{"ok":true,"data":{{"learned_patterns":{},"total_observations":{},"term_patterns":{},"global_observations":{},"max_patterns":{},"max_terms":{},"evicted":{}}}}}"#,
+                patterns, observations, term_patterns, global_count, limits.max_patterns, limits.max_terms, evicted
+            )
         }
 
         "describe" => {
-            let mg_root = req.get("magento_root").and_then(|v| v.as_str()).unwrap_or("");
+            let root = indexer.lock().unwrap().magento_root().to_path_buf();
+            let mg_root = match served_root(req, &root) {
+                Ok(r) => r,
+                Err(resp) => return resp,
+            };
+            let mg_root = mg_root.to_string_lossy();
+            let mg_root = mg_root.as_ref();
             let output = req.get("output").and_then(|v| v.as_str())
                 .map(|s| s.to_string())
                 .unwrap_or_else(|| desc_db_path.to_string_lossy().to_string());
@@ -719,10 +3410,6 @@ fn handle_serve_request(
                 .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok());
             let model = req.get("model").and_then(|v| v.as_str());
 
-            if mg_root.is_empty() {
-                return r#"{"ok":false,"error":"Missing 'magento_root' field"}"#.to_string();
-            }
-
             let api_key = match api_key {
                 Some(k) => k,
                 None => return r#"{"ok":false,"error":"No API key. Set ANTHROPIC_API_KEY env var."}"#.to_string(),
@@ -809,13 +3496,15 @@ fn handle_serve_request(
         }
 
         "enrich" => {
-            let mg_root = match req.get("magento_root").and_then(|v| v.as_str()) {
-                Some(r) if !r.is_empty() => r,
-                _ => return r#"{"ok":false,"error":"Missing 'magento_root' field"}"#.to_string(),
+            let root = indexer.lock().unwrap().magento_root().to_path_buf();
+            let (mg_root, scope) = match served_scope(req, &root, &[]) {
+                Ok(s) => s,
+                Err(resp) => return resp,
             };
+            let mg_root = mg_root.as_path();
 
             // Scan vendor/**/*.php for method chains
-            let pattern = format!("{}/vendor/**/*.php", mg_root);
+            let pattern = format!("{}/vendor/**/*.php", mg_root.display());
             let php_files: Vec<_> = match glob::glob(&pattern) {
                 Ok(paths) => paths.filter_map(|p| p.ok()).collect(),
                 Err(e) => return format!(r#"{{"ok":false,"error":"Glob error: {}"}}"#, e),
@@ -837,7 +3526,6 @@ fn handle_serve_request(
 
             let mut scanned: usize = 0;
             let mut chains: usize = 0;
-            let mg_root_prefix = format!("{}/", mg_root);
 
             let ddb = data_db.lock().unwrap();
             if let Err(e) = ddb.begin() {
@@ -848,7 +3536,7 @@ fn handle_serve_request(
                 return format!(r#"{{"ok":false,"error":"Clear failed: {}"}}"#, e);
             }
 
-            for php_file in &php_files {
+            for php_file in php_files.iter().filter(|f| scope.contains(f)) {
                 let content = match std::fs::read_to_string(php_file) {
                     Ok(c) => c,
                     Err(_) => continue,
@@ -857,10 +3545,7 @@ fn handle_serve_request(
                     continue;
                 }
 
-                let rel_path = php_file.to_string_lossy()
-                    .strip_prefix(&mg_root_prefix)
-                    .unwrap_or(&php_file.to_string_lossy())
-                    .to_string();
+                let rel_path = relative_path(mg_root, php_file);
 
                 let lines: Vec<&str> = content.lines().collect();
 
@@ -1069,10 +3754,6 @@ fn handle_serve_request(
 
         "ast_query" => {
             let pattern_name = req.get("pattern").and_then(|v| v.as_str()).unwrap_or("");
-            let mg_root = match req.get("magento_root").and_then(|v| v.as_str()) {
-                Some(r) if !r.is_empty() => r,
-                _ => return r#"{"ok":false,"error":"Missing 'magento_root' field"}"#.to_string(),
-            };
             let search_path = req.get("path").and_then(|v| v.as_str()).unwrap_or(".");
             let limit = req.get("limit").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
 
@@ -1085,8 +3766,14 @@ fn handle_serve_request(
                 ),
             };
 
-            let root = std::path::Path::new(mg_root);
-            let target = root.join(search_path);
+            let (root, extra_roots) = {
+                let idx = indexer.lock().unwrap();
+                (idx.magento_root().to_path_buf(), idx.extra_root_paths())
+            };
+            let (mg_root, scope, target) = match scoped_target(req, &root, search_path, &extra_roots) {
+                Ok(t) => t,
+                Err(resp) => return resp,
+            };
             let glob_pattern = format!("{}/**/*.php", target.display());
 
             let php_files: Vec<_> = match glob::glob(&glob_pattern) {
@@ -1100,19 +3787,17 @@ fn handle_serve_request(
             };
 
             let is_setter_pattern = pattern_name == "dataobject-set-null";
-            let mg_root_prefix = format!("{}/", mg_root);
+            let redactor = project_redactor(&mg_root);
             let mut all_results: Vec<serde_json::Value> = Vec::new();
 
-            'outer: for php_file in &php_files {
+            'outer: for php_file in php_files.iter().filter(|f| scope.contains(f)) {
                 let content = match std::fs::read_to_string(php_file) {
                     Ok(c) => c,
                     Err(_) => continue,
                 };
+                let content = redactor.redact(&content);
 
-                let rel_path = php_file.to_string_lossy()
-                    .strip_prefix(&mg_root_prefix)
-                    .unwrap_or(&php_file.to_string_lossy())
-                    .to_string();
+                let rel_path = relative_path(&mg_root, php_file);
 
                 let matches = match analyzer.run_query(&content, query_source) {
                     Ok(m) => m,
@@ -1157,13 +3842,78 @@ fn handle_serve_request(
 
         // ─── Grep: in-process text search ─────────────────────────────────
         "grep" => {
-            handle_grep_command(req)
+            let (root, extra_roots) = {
+                let idx = indexer.lock().unwrap();
+                (idx.magento_root().to_path_buf(), idx.extra_root_paths())
+            };
+            handle_grep_command(req, &root, &extra_roots)
         }
 
         _ => format!(r#"{{"ok":false,"error":"Unknown command: {}"}}"#, command),
     }
 }
 
+/// The served Magento root, canonical, for a request that reads files. A
+/// request's `magento_root` may only repeat it: files are never read from,
+/// nor redaction configured by, a root the client picks.
+fn served_root(req: &serde_json::Value, root: &Path) -> std::result::Result<PathBuf, String> {
+    let error = |msg: String| serde_json::json!({"ok": false, "error": msg}).to_string();
+    if root.as_os_str().is_empty() {
+        return Err(error("serve was started without --magento-root".to_string()));
+    }
+    let root = root
+        .canonicalize()
+        .map_err(|e| error(format!("Magento root does not exist: {} ({})", root.display(), e)))?;
+    match req.get("magento_root").and_then(|v| v.as_str()) {
+        Some(requested) if !requested.is_empty() && Path::new(requested).canonicalize().ok().as_ref() != Some(&root) => {
+            Err(error(format!("magento_root {} is not the served root {}", requested, root.display())))
+        }
+        _ => Ok(root),
+    }
+}
+
+/// [`served_root`] and the scope its files are read in, which also takes
+/// in the index's `extra_roots`
+fn served_scope(
+    req: &serde_json::Value,
+    root: &Path,
+    extra_roots: &[PathBuf],
+) -> std::result::Result<(PathBuf, ReadScope), String> {
+    let root = served_root(req, root)?;
+    let scope = ReadScope::new(&root)
+        .map_err(|e| serde_json::json!({"ok": false, "error": format!("{:#}", e)}).to_string())?
+        .with_extra_roots(extra_roots);
+    Ok((root, scope))
+}
+
+/// [`served_scope`] plus the request `path` under the root. Fails (with a
+/// ready serve response) when the path does not exist or resolves outside
+/// the roots, e.g. via `..` or a symlink.
+fn scoped_target(
+    req: &serde_json::Value,
+    root: &Path,
+    search_path: &str,
+    extra_roots: &[PathBuf],
+) -> std::result::Result<(PathBuf, ReadScope, PathBuf), String> {
+    let (root, scope) = served_scope(req, root, extra_roots)?;
+    scope
+        .resolve(search_path)
+        .map_err(|e| serde_json::json!({"ok": false, "error": format!("{:#}", e)}).to_string())?;
+    let target = root.join(search_path);
+    Ok((root, scope, target))
+}
+
+/// Redaction patterns (`[redact]`) of the project at `mg_root`; the
+/// built-in ones when its magector.toml is invalid
+fn project_redactor(mg_root: &Path) -> magector_core::redact::Redactor {
+    ProjectConfig::load(mg_root)
+        .and_then(|config| magector_core::redact::Redactor::new(&config.redact))
+        .unwrap_or_else(|e| {
+            tracing::warn!("Using the built-in redaction patterns: {:#}", e);
+            Default::default()
+        })
+}
+
 /// Expand brace patterns like `*.{php,xml}` into multiple glob patterns.
 /// Returns the original pattern in a vec if no braces are found.
 fn expand_brace_pattern(pattern: &str) -> Vec<String> {
@@ -1207,7 +3957,7 @@ fn glob_match_simple(text: &str, pattern: &str) -> bool {
             p = &p[1..];
             continue;
         }
-        if !t.is_empty() && !p.is_empty() && (p[0] == b'?' || p[0].to_ascii_lowercase() == t[0].to_ascii_lowercase()) {
+        if !t.is_empty() && !p.is_empty() && (p[0] == b'?' || p[0].eq_ignore_ascii_case(&t[0])) {
             t = &t[1..];
             p = &p[1..];
             continue;
@@ -1226,17 +3976,13 @@ fn glob_match_simple(text: &str, pattern: &str) -> bool {
 }
 
 /// Handle the "grep" serve command: in-process text search using regex + walkdir.
-fn handle_grep_command(req: &serde_json::Value) -> String {
+fn handle_grep_command(req: &serde_json::Value, root: &Path, extra_roots: &[PathBuf]) -> String {
     use walkdir::WalkDir;
 
     let pattern_str = match req.get("pattern").and_then(|v| v.as_str()) {
         Some(p) if !p.is_empty() => p,
         _ => return r#"{"ok":false,"error":"Missing or empty 'pattern' field"}"#.to_string(),
     };
-    let mg_root = match req.get("magento_root").and_then(|v| v.as_str()) {
-        Some(r) if !r.is_empty() => r,
-        _ => return r#"{"ok":false,"error":"Missing 'magento_root' field"}"#.to_string(),
-    };
     let search_path = req.get("path").and_then(|v| v.as_str()).unwrap_or(".");
     let include = req.get("include").and_then(|v| v.as_str()).unwrap_or("*.php");
     let context_lines = req.get("context").and_then(|v| v.as_u64()).unwrap_or(4) as usize;
@@ -1262,16 +4008,11 @@ fn handle_grep_command(req: &serde_json::Value) -> String {
         }
     };
 
-    let root = std::path::Path::new(mg_root);
-    let target = root.join(search_path);
-    if !target.exists() {
-        return format!(
-            r#"{{"ok":false,"error":"Search path does not exist: {}"}}"#,
-            target.display()
-        );
-    }
-
-    let mg_root_prefix = format!("{}/", mg_root);
+    let (mg_root, scope, target) = match scoped_target(req, root, search_path, extra_roots) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+    let redactor = project_redactor(&mg_root);
     let mut matches_output: Vec<serde_json::Value> = Vec::new();
     let mut matched_files: Vec<String> = Vec::new();
     let mut hit_limit = false;
@@ -1291,7 +4032,7 @@ fn handle_grep_command(req: &serde_json::Value) -> String {
         };
 
         // Filter by include pattern
-        if !matches_include_pattern(file_name, include) {
+        if !matches_include_pattern(file_name, include) || !scope.contains(file_path) {
             continue;
         }
 
@@ -1300,6 +4041,8 @@ fn handle_grep_command(req: &serde_json::Value) -> String {
             Ok(c) => c,
             Err(_) => continue, // Skip binary / unreadable files
         };
+        // Matched and returned without secrets
+        let content = redactor.redact(&content);
 
         let lines: Vec<&str> = content.lines().collect();
         let mut file_has_match = false;
@@ -1317,11 +4060,7 @@ fn handle_grep_command(req: &serde_json::Value) -> String {
         }
 
         // Relative path for output
-        let rel_path = file_path
-            .to_string_lossy()
-            .strip_prefix(&mg_root_prefix)
-            .unwrap_or(&file_path.to_string_lossy())
-            .to_string();
+        let rel_path = relative_path(&mg_root, file_path);
 
         if files_only {
             matched_files.push(rel_path);
@@ -1350,6 +4089,7 @@ fn handle_grep_command(req: &serde_json::Value) -> String {
             match_line_indices.iter().copied().collect();
 
         for (range_start, range_end) in &ranges {
+            #[allow(clippy::needless_range_loop)]
             for i in *range_start..=*range_end {
                 let is_match_line = match_set.contains(&i);
                 matches_output.push(serde_json::json!({
@@ -1581,7 +4321,7 @@ class Helper
     fn test_grep_basic_match() {
         let dir = setup_test_dir();
         let req = make_grep_request(&dir, serde_json::json!({}));
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, dir.path(), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
 
         assert_eq!(resp["ok"], true, "Response: {}", response_str);
@@ -1608,7 +4348,7 @@ class Helper
     fn test_grep_context_lines() {
         let dir = setup_test_dir();
         let req = make_grep_request(&dir, serde_json::json!({"context": 1}));
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, dir.path(), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
 
         assert_eq!(resp["ok"], true);
@@ -1625,7 +4365,7 @@ class Helper
     fn test_grep_files_only() {
         let dir = setup_test_dir();
         let req = make_grep_request(&dir, serde_json::json!({"files_only": true}));
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, dir.path(), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
 
         assert_eq!(resp["ok"], true);
@@ -1645,7 +4385,7 @@ class Helper
     fn test_grep_include_filter_xml() {
         let dir = setup_test_dir();
         let req = make_grep_request(&dir, serde_json::json!({"include": "*.xml"}));
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, dir.path(), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
 
         assert_eq!(resp["ok"], true);
@@ -1670,7 +4410,7 @@ class Helper
             &dir,
             serde_json::json!({"include": "*.{php,xml}", "files_only": true}),
         );
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, dir.path(), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
 
         assert_eq!(resp["ok"], true);
@@ -1686,7 +4426,7 @@ class Helper
             &dir,
             serde_json::json!({"pattern": "setcouponcode", "ignore_case": true, "files_only": true}),
         );
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, dir.path(), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
 
         assert_eq!(resp["ok"], true);
@@ -1704,7 +4444,7 @@ class Helper
             &dir,
             serde_json::json!({"max_results": 3, "context": 0}),
         );
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, dir.path(), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
 
         assert_eq!(resp["ok"], true);
@@ -1722,7 +4462,7 @@ class Helper
             &dir,
             serde_json::json!({"pattern": "thisWillNeverMatchAnything12345"}),
         );
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, dir.path(), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
 
         assert_eq!(resp["ok"], true);
@@ -1733,19 +4473,40 @@ class Helper
     #[test]
     fn test_grep_missing_pattern() {
         let req = serde_json::json!({"command": "grep", "magento_root": "/tmp"});
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, Path::new("/tmp"), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
         assert_eq!(resp["ok"], false);
         assert!(resp["error"].as_str().unwrap().contains("pattern"));
     }
 
     #[test]
-    fn test_grep_missing_magento_root() {
-        let req = serde_json::json!({"command": "grep", "pattern": "test"});
-        let response_str = handle_grep_command(&req);
+    fn test_grep_without_served_root() {
+        let req = serde_json::json!({"command": "grep", "pattern": "test", "magento_root": "/tmp"});
+        let response_str = handle_grep_command(&req, Path::new(""), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
         assert_eq!(resp["ok"], false);
-        assert!(resp["error"].as_str().unwrap().contains("magento_root"));
+        assert!(resp["error"].as_str().unwrap().contains("--magento-root"));
+    }
+
+    #[test]
+    fn test_grep_pins_served_root() {
+        let dir = setup_test_dir();
+
+        // A request root other than the served one is refused
+        let outside = TempDir::new().unwrap();
+        for root in ["/", &*outside.path().to_string_lossy()] {
+            let req = make_grep_request(&dir, serde_json::json!({"magento_root": root, "path": "."}));
+            let resp: serde_json::Value = serde_json::from_str(&handle_grep_command(&req, dir.path(), &[])).unwrap();
+            assert_eq!(resp["ok"], false, "{} is not the served root", root);
+            assert!(resp["error"].as_str().unwrap().contains("not the served root"));
+        }
+
+        // Omitting it searches the served root
+        let mut req = make_grep_request(&dir, serde_json::json!({"files_only": true}));
+        req.as_object_mut().unwrap().remove("magento_root");
+        let resp: serde_json::Value = serde_json::from_str(&handle_grep_command(&req, dir.path(), &[])).unwrap();
+        assert_eq!(resp["ok"], true);
+        assert_eq!(resp["data"]["files"].as_array().unwrap().len(), 2);
     }
 
     #[test]
@@ -1755,7 +4516,7 @@ class Helper
             "pattern": "[invalid(regex",
             "magento_root": "/tmp",
         });
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, Path::new("/tmp"), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
         assert_eq!(resp["ok"], false);
         assert!(resp["error"].as_str().unwrap().contains("regex"));
@@ -1768,7 +4529,7 @@ class Helper
             &dir,
             serde_json::json!({"files_only": true}),
         );
-        let response_str = handle_grep_command(&req);
+        let response_str = handle_grep_command(&req, dir.path(), &[]);
         let resp: serde_json::Value = serde_json::from_str(&response_str).unwrap();
 
         assert_eq!(resp["ok"], true);
@@ -1781,6 +4542,60 @@ class Helper
         }
     }
 
+    #[test]
+    fn test_grep_redacts_secrets() {
+        let dir = setup_test_dir();
+        fs::write(
+            dir.path().join("vendor/acme/module-cart/Config.php"),
+            "<?php\nreturn [\n    'api_key' => 'sk_live_hunter2',\n];\n",
+        )
+        .unwrap();
+        let req = make_grep_request(&dir, serde_json::json!({"pattern": "api_key", "context": 0}));
+        let response_str = handle_grep_command(&req, dir.path(), &[]);
+        assert!(!response_str.contains("hunter2"), "{}", response_str);
+        assert!(response_str.contains("'api_key' => '[REDACTED]'"), "{}", response_str);
+
+        // The secret itself is not searchable
+        let req = make_grep_request(&dir, serde_json::json!({"pattern": "hunter2"}));
+        let resp: serde_json::Value = serde_json::from_str(&handle_grep_command(&req, dir.path(), &[])).unwrap();
+        assert!(resp["data"]["matches"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_grep_rejects_traversal() {
+        let dir = setup_test_dir();
+        for path in ["..", "vendor/../../", "/etc"] {
+            let req = make_grep_request(&dir, serde_json::json!({"path": path}));
+            let resp: serde_json::Value = serde_json::from_str(&handle_grep_command(&req, dir.path(), &[])).unwrap();
+            assert_eq!(resp["ok"], false, "{} escapes the root", path);
+            assert!(resp["error"].as_str().unwrap().contains("outside magento_root"));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_grep_rejects_symlink_escape() {
+        let dir = setup_test_dir();
+        let outside = TempDir::new().unwrap();
+        fs::write(outside.path().join("Secret.php"), "<?php $cart->setCouponCode('leak');\n").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("vendor/linked")).unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("Secret.php"),
+            dir.path().join("vendor/acme/module-cart/Secret.php"),
+        )
+        .unwrap();
+
+        let req = make_grep_request(&dir, serde_json::json!({"path": "vendor/linked"}));
+        let resp: serde_json::Value = serde_json::from_str(&handle_grep_command(&req, dir.path(), &[])).unwrap();
+        assert_eq!(resp["ok"], false);
+
+        let req = make_grep_request(&dir, serde_json::json!({"files_only": true}));
+        let resp: serde_json::Value = serde_json::from_str(&handle_grep_command(&req, dir.path(), &[])).unwrap();
+        let files = resp["data"]["files"].as_array().unwrap();
+        assert!(!files.is_empty());
+        assert!(files.iter().all(|f| !f.as_str().unwrap().contains("Secret")));
+    }
+
     // Helper function tests
     #[test]
     fn test_expand_brace_pattern() {
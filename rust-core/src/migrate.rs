@@ -0,0 +1,332 @@
+//! Embedding model migration (`magector migrate`)
+//!
+//! Indexing keeps the text each vector was embedded from in a sidecar next
+//! to the index (`index.texts`, keyed by item ID). Switching to another
+//! model then needs no new parse of the codebase: `migrate` embeds the
+//! cached texts with the new model and writes a new database with the same
+//! items under the same stable IDs, the same metadata and header, and
+//! copies of the manifest and text cache. The SONA file keeps its learned
+//! pattern and term weights; its LoRA adapter and EWC state live in the old
+//! model's embedding space and start over.
+
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::embedder::Embedder;
+use crate::sona::{EwcRegularizer, MicroLoRA, SonaEngine};
+use crate::vectordb::{IndexHeader, VectorDB};
+use crate::watcher::FileManifest;
+
+/// Version byte of the text cache file
+const TEXTS_VERSION: u8 = 1;
+
+/// Text an item's vectors were embedded from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmbedText {
+    /// Text of the item's vector
+    pub text: String,
+    /// Text of its metadata vector (`index --meta-vectors`)
+    pub meta: Option<String>,
+}
+
+/// Embedded texts by item ID, persisted next to the index
+#[derive(Debug, Clone, Default)]
+pub struct TextCache {
+    texts: HashMap<usize, EmbedText>,
+}
+
+impl TextCache {
+    /// Derive the text cache path from the index DB path.
+    /// e.g. `.magector/index.db` → `.magector/index.texts`
+    pub fn sidecar_path(db_path: &Path) -> PathBuf {
+        db_path.with_extension("texts")
+    }
+
+    /// Load the cache at `path`; empty if there is none
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to open {}", path.display())),
+        };
+        let mut reader = GzDecoder::new(BufReader::new(file));
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).with_context(|| format!("Corrupted text cache {}", path.display()))?;
+        match bytes.split_first() {
+            Some((&TEXTS_VERSION, payload)) => {
+                let (texts, _) = bincode::serde::decode_from_slice(payload, bincode::config::standard())
+                    .with_context(|| format!("Corrupted text cache {}", path.display()))?;
+                Ok(Self { texts })
+            }
+            Some((version, _)) => bail!("Text cache {} has unknown version {}", path.display(), version),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Write the cache to `path` (temp file, then rename)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("texts.tmp");
+        {
+            let mut writer = GzEncoder::new(BufWriter::new(File::create(&tmp)?), Compression::fast());
+            writer.write_all(&[TEXTS_VERSION])?;
+            writer.write_all(&bincode::serde::encode_to_vec(&self.texts, bincode::config::standard())?)?;
+            writer.finish()?.flush()?;
+        }
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Merge `self` into the cache of the index at `db_path`, keeping only
+    /// the live items of `db`
+    pub fn merge_into(&self, db_path: &Path, db: &VectorDB) -> Result<()> {
+        let path = Self::sidecar_path(db_path);
+        // A cache that can't be read is replaced; its items are only missed
+        // by the next migration
+        let mut cache = Self::load(&path).unwrap_or_else(|e| {
+            tracing::warn!("{:#} — starting a new text cache", e);
+            Self::default()
+        });
+        cache.texts.extend(self.texts.iter().map(|(&id, text)| (id, text.clone())));
+        cache.texts.retain(|&id, _| db.get(id).is_some());
+        cache.save(&path)
+    }
+
+    pub fn insert(&mut self, id: usize, text: EmbedText) {
+        self.texts.insert(id, text);
+    }
+
+    pub fn get(&self, id: usize) -> Option<&EmbedText> {
+        self.texts.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.texts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty()
+    }
+}
+
+/// Vectors of a batch of texts and their metadata vectors
+pub(crate) type Embeddings = (Vec<Vec<f32>>, Vec<Vec<f32>>);
+
+/// Vectors of `texts`, and their metadata vectors when they have metadata
+/// texts (all of an index's items do, or none)
+pub(crate) fn embed_texts(embedder: &mut Embedder, texts: &[EmbedText]) -> Result<Embeddings> {
+    embed_with(texts, |batch| embedder.embed_batch(batch))
+}
+
+fn embed_with(
+    texts: &[EmbedText],
+    mut embed: impl FnMut(&[&str]) -> Result<Vec<Vec<f32>>>,
+) -> Result<Embeddings> {
+    let main: Vec<&str> = texts.iter().map(|t| t.text.as_str()).collect();
+    let meta: Vec<&str> = texts.iter().filter_map(|t| t.meta.as_deref()).collect();
+    let vectors = embed(&main)?;
+    let meta_vectors = if meta.is_empty() { Vec::new() } else { embed(&meta)? };
+    Ok((vectors, meta_vectors))
+}
+
+/// Outcome of a migration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrateReport {
+    /// Model the source index was embedded with
+    pub from_model: String,
+    pub to_model: String,
+    /// Items re-embedded
+    pub items: usize,
+    /// Metadata vectors re-embedded
+    pub meta_vectors: usize,
+    /// The SONA file was carried over
+    pub sona: bool,
+}
+
+/// Re-embed the index at `db_path` with `embedder` from its text cache and
+/// write the result, with its manifest, text cache and SONA file, to
+/// `output`. Fails before writing anything if an item has no cached text.
+pub fn migrate(db_path: &Path, output: &Path, embedder: &mut Embedder, batch_size: usize) -> Result<MigrateReport> {
+    let model = embedder.model().to_string();
+    embedder.set_pooling(VectorDB::read_header(db_path)?.pooling);
+    migrate_with(db_path, output, &model, batch_size, |batch| embedder.embed_batch(batch))
+}
+
+fn migrate_with(
+    db_path: &Path,
+    output: &Path,
+    model: &str,
+    batch_size: usize,
+    mut embed: impl FnMut(&[&str]) -> Result<Vec<Vec<f32>>>,
+) -> Result<MigrateReport> {
+    if std::path::absolute(db_path)? == std::path::absolute(output)? {
+        bail!("Migrate writes a new database — choose an output other than {}", db_path.display());
+    }
+    let source = VectorDB::load_existing(db_path)?;
+    let cache = TextCache::load(&TextCache::sidecar_path(db_path))?;
+
+    let mut ids: Vec<usize> = source.metadata_iter().map(|(id, _)| id).collect();
+    ids.sort_unstable();
+    let missing: Vec<&str> =
+        ids.iter().filter(|&&id| cache.get(id).is_none()).map(|&id| source.get(id).unwrap().1.path.as_str()).collect();
+    if !missing.is_empty() {
+        bail!(
+            "{} of {} items have no cached text (first: {}) — run `index --force` once to fill the text cache",
+            missing.len(),
+            ids.len(),
+            missing[0]
+        );
+    }
+
+    let header = source.header();
+    let mut db = VectorDB::with_capacity(ids.len());
+    db.set_header(IndexHeader { model: model.to_string(), checksum: String::new(), ..header.clone() });
+    let mut meta_vectors = 0;
+    for batch in ids.chunks(batch_size.max(1)) {
+        let texts: Vec<EmbedText> = batch.iter().map(|&id| cache.get(id).unwrap().clone()).collect();
+        let (vectors, metas) = embed_with(&texts, &mut embed)?;
+        if vectors.len() != batch.len() {
+            bail!("Model {} returned {} vectors for {} texts", model, vectors.len(), batch.len());
+        }
+        let items = batch
+            .iter()
+            .zip(vectors)
+            .map(|(&id, vector)| (id, vector, source.get(id).unwrap().1.clone()))
+            .collect();
+        let assigned = db.insert_batch_with_ids(items);
+        let with_meta = assigned.into_iter().zip(&texts).filter(|(_, t)| t.meta.is_some()).map(|(id, _)| id);
+        meta_vectors += metas.len();
+        db.insert_meta_vectors(with_meta.zip(metas).collect());
+    }
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    db.save_atomic(output)?;
+    cache.merge_into(output, &db)?;
+    let manifest = FileManifest::sidecar_path(db_path);
+    if manifest.exists() {
+        fs::copy(&manifest, FileManifest::sidecar_path(output))?;
+    }
+    let sona_path = db_path.with_extension("sona");
+    let sona = sona_path.exists();
+    if sona {
+        let mut engine = SonaEngine::open(&sona_path)?;
+        engine.lora = MicroLoRA::default();
+        engine.ewc = EwcRegularizer::default();
+        engine.save(&output.with_extension("sona"))?;
+    }
+
+    Ok(MigrateReport {
+        from_model: header.model.clone(),
+        to_model: model.to_string(),
+        items: db.len(),
+        meta_vectors,
+        sona,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::EMBEDDING_DIM;
+    use crate::vectordb::stable_id;
+    use crate::IndexMetadata;
+
+    fn meta(path: &str) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            search_text: format!("search {}", path),
+            ..Default::default()
+        }
+    }
+
+    /// A "model" whose vectors depend on the text's length
+    fn fake_embed(texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts
+            .iter()
+            .map(|t| {
+                let mut v = vec![0.01; EMBEDDING_DIM];
+                v[t.len() % EMBEDDING_DIM] = 1.0;
+                v
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_text_cache_roundtrip_and_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("index.db");
+        let mut db = VectorDB::new();
+        let a = db.insert(&vec![0.1; EMBEDDING_DIM], meta("a.php"));
+        let b = db.insert(&vec![0.2; EMBEDDING_DIM], meta("b.php"));
+
+        let mut first = TextCache::default();
+        first.insert(a, EmbedText { text: "class A".to_string(), meta: None });
+        first.insert(b, EmbedText { text: "class B".to_string(), meta: None });
+        first.merge_into(&db_path, &db).unwrap();
+
+        // b.php is deleted and a.php changes
+        db.remove_by_path("b.php");
+        let mut second = TextCache::default();
+        second.insert(a, EmbedText { text: "class A2".to_string(), meta: None });
+        second.merge_into(&db_path, &db).unwrap();
+
+        let cache = TextCache::load(&TextCache::sidecar_path(&db_path)).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(a).unwrap().text, "class A2");
+        assert!(TextCache::load(&dir.path().join("none.texts")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_migrate_keeps_ids_metadata_and_sona_terms() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("index.db");
+        let mut db = VectorDB::new();
+        db.set_header(IndexHeader { meta_vectors: true, summaries: true, ..Default::default() });
+        let mut cache = TextCache::default();
+        for (n, path) in ["a.php", "b.php", "c.php"].iter().enumerate() {
+            let id = db.insert(&vec![0.1 + n as f32; EMBEDDING_DIM], meta(path));
+            cache.insert(id, EmbedText { text: format!("code of {}", path), meta: Some(format!("meta {}", path)) });
+        }
+        db.save(&db_path).unwrap();
+        cache.merge_into(&db_path, &db).unwrap();
+        let mut sona = SonaEngine::new();
+        sona.learned.term_counts.insert("price".to_string(), 3);
+        sona.save(&db_path.with_extension("sona")).unwrap();
+
+        assert!(migrate_with(&db_path, &db_path, "other-model", 2, fake_embed).is_err());
+
+        let output = dir.path().join("migrated/index.db");
+        let report = migrate_with(&db_path, &output, "other-model", 2, fake_embed).unwrap();
+        assert_eq!(report.items, 3);
+        assert_eq!(report.meta_vectors, 3);
+        assert_eq!(report.to_model, "other-model");
+        assert!(report.sona);
+
+        let migrated = VectorDB::load_existing(&output).unwrap();
+        assert_eq!(migrated.header().model, "other-model");
+        assert!(migrated.header().meta_vectors && migrated.header().summaries);
+        let id = stable_id("b.php", 0);
+        let (vector, metadata) = migrated.get(id).unwrap();
+        assert_eq!(metadata, db.get(id).unwrap().1);
+        assert_eq!(vector, fake_embed(&["code of b.php"]).unwrap()[0].as_slice());
+        assert!(migrated.meta_vector(id).is_some());
+        assert_eq!(TextCache::load(&TextCache::sidecar_path(&output)).unwrap().len(), 3);
+        let sona = SonaEngine::open(&output.with_extension("sona")).unwrap();
+        assert_eq!(sona.learned.term_counts["price"], 3);
+
+        // Items indexed before texts were cached can't be migrated
+        db.insert(&vec![0.5; EMBEDDING_DIM], meta("d.php"));
+        db.save(&db_path).unwrap();
+        let err = migrate_with(&db_path, &dir.path().join("other.db"), "other-model", 2, fake_embed).unwrap_err();
+        assert!(err.to_string().contains("d.php"), "{err}");
+    }
+}
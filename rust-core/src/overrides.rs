@@ -0,0 +1,133 @@
+//! Old-school class overrides: `app/code` copies of vendor classes
+//!
+//! Projects sometimes copy a core class into `app/code`, either under the
+//! same namespace (the autoloader finds `app/code` first) or with the vendor
+//! segment renamed and a preference pointing at the copy. Both files are
+//! indexed and look alike in results, so each copy is linked to the vendor
+//! file it overrides ([`IndexMetadata::overrides`]).
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::vectordb::IndexMetadata;
+
+/// Share of method names a renamed copy must have in common with the vendor
+/// class (Jaccard index)
+const MIN_METHOD_OVERLAP: f64 = 0.8;
+
+/// Whether `path` belongs to a Composer package
+pub fn is_vendor_path(path: &str) -> bool {
+    path.starts_with("vendor/") || path.contains("/vendor/")
+}
+
+/// Lowercase fully qualified class name
+pub(crate) fn class_key(meta: &IndexMetadata) -> Option<String> {
+    let class = meta.class_name.as_deref()?;
+    Some(match meta.namespace.as_deref() {
+        Some(ns) => format!("{}\\{}", ns, class).to_lowercase(),
+        None => class.to_lowercase(),
+    })
+}
+
+/// [`class_key`] without the vendor segment: `Magento\Catalog\Model\Product`
+/// and `Acme\Catalog\Model\Product` share `catalog\model\product`
+fn renamed_key(meta: &IndexMetadata) -> Option<String> {
+    let (_, rest) = meta.namespace.as_deref()?.split_once('\\')?;
+    Some(format!("{}\\{}", rest, meta.class_name.as_deref()?).to_lowercase())
+}
+
+fn method_overlap(a: &[String], b: &[String]) -> f64 {
+    let a: HashSet<&String> = a.iter().collect();
+    let b: HashSet<&String> = b.iter().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Map each non-vendor file declaring a copy of a vendor class to that
+/// vendor file. A copy has the same fully qualified name, or the same name
+/// apart from the vendor segment and at least [`MIN_METHOD_OVERLAP`] of its
+/// methods. Chunks of one file count once.
+pub fn find_overrides<'a>(items: impl IntoIterator<Item = &'a IndexMetadata>) -> HashMap<String, String> {
+    // By path, so the same candidates always win
+    let mut vendor: BTreeMap<&str, &IndexMetadata> = BTreeMap::new();
+    let mut local: BTreeMap<&str, &IndexMetadata> = BTreeMap::new();
+    for meta in items {
+        if meta.class_name.is_none() {
+            continue;
+        }
+        let side = if is_vendor_path(&meta.path) { &mut vendor } else { &mut local };
+        side.entry(meta.path.as_str()).or_insert(meta);
+    }
+
+    let mut by_class: HashMap<String, &IndexMetadata> = HashMap::new();
+    let mut by_renamed: HashMap<String, Vec<&IndexMetadata>> = HashMap::new();
+    for meta in vendor.values() {
+        if let Some(key) = class_key(meta) {
+            by_class.entry(key).or_insert(meta);
+        }
+        if let Some(key) = renamed_key(meta) {
+            by_renamed.entry(key).or_default().push(meta);
+        }
+    }
+
+    let mut overrides = HashMap::new();
+    for (path, meta) in local {
+        let same_name = class_key(meta).and_then(|key| by_class.get(&key).copied());
+        let renamed = || {
+            let candidates = by_renamed.get(&renamed_key(meta)?)?;
+            candidates
+                .iter()
+                .find(|v| !meta.methods.is_empty() && method_overlap(&meta.methods, &v.methods) >= MIN_METHOD_OVERLAP)
+                .copied()
+        };
+        if let Some(original) = same_name.or_else(renamed) {
+            overrides.insert(path.to_string(), original.path.clone());
+        }
+    }
+    overrides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(path: &str, namespace: &str, name: &str, methods: &[&str]) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            class_name: Some(name.to_string()),
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+            namespace: Some(namespace.to_string()),
+            search_text: String::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_overrides() {
+        let core = "vendor/magento/module-catalog/Model/Product.php";
+        let methods = ["getPrice", "getName", "getSku", "save", "load"];
+        let items = [
+            class(core, "Magento\\Catalog\\Model", "Product", &methods),
+            class("vendor/magento/module-sales/Model/Order.php", "Magento\\Sales\\Model", "Order", &["place"]),
+            // Same namespace, copied into app/code
+            class("app/code/Magento/Catalog/Model/Product.php", "Magento\\Catalog\\Model", "Product", &["getPrice"]),
+            // Renamed vendor segment, same methods plus one
+            class(
+                "app/code/Acme/Catalog/Model/Product.php",
+                "Acme\\Catalog\\Model",
+                "Product",
+                &["getPrice", "getName", "getSku", "save", "load", "getAcmeFlag"],
+            ),
+            // Renamed, but an unrelated class
+            class("app/code/Acme/Sales/Model/Order.php", "Acme\\Sales\\Model", "Order", &["export", "place"]),
+        ];
+        let overrides = find_overrides(&items);
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides["app/code/Magento/Catalog/Model/Product.php"], core);
+        assert_eq!(overrides["app/code/Acme/Catalog/Model/Product.php"], core);
+        assert!(!overrides.contains_key("app/code/Acme/Sales/Model/Order.php"));
+    }
+}
@@ -0,0 +1,120 @@
+//! Code ownership (`index --owners`)
+//!
+//! A file's owners come from the project's CODEOWNERS file when a rule
+//! matches it, else from git: the author with the most commits touching the
+//! file ([`crate::git::dominant_authors`]). Stored in
+//! [`IndexMetadata::owners`](crate::vectordb::IndexMetadata::owners) so
+//! results can be routed or filtered by team (`search --owner`).
+
+use glob::{MatchOptions, Pattern};
+use std::path::Path;
+
+/// Where GitHub and GitLab look for the file, in their order
+const CODEOWNERS_PATHS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS", ".gitlab/CODEOWNERS"];
+
+const MATCH_OPTIONS: MatchOptions =
+    MatchOptions { case_sensitive: true, require_literal_separator: true, require_literal_leading_dot: false };
+
+/// One CODEOWNERS line: the patterns it matches and who owns those files
+#[derive(Debug, Clone)]
+struct OwnerRule {
+    patterns: Vec<Pattern>,
+    owners: Vec<String>,
+}
+
+/// Parsed CODEOWNERS rules; the last matching rule wins
+#[derive(Debug, Clone, Default)]
+pub struct CodeOwners {
+    rules: Vec<OwnerRule>,
+}
+
+impl CodeOwners {
+    /// The first CODEOWNERS file found under `root`, `None` without one
+    pub fn load(root: &Path) -> Option<Self> {
+        CODEOWNERS_PATHS.iter().find_map(|p| std::fs::read_to_string(root.join(p)).ok()).map(|text| Self::parse(&text))
+    }
+
+    /// Parse CODEOWNERS text. Patterns follow gitignore rules: one without
+    /// an inner `/` matches at any depth, a leading `/` anchors it to the
+    /// root, and a directory covers everything beneath it. GitLab section
+    /// headers and unparsable patterns are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut rules = Vec::new();
+        for line in text.lines() {
+            let line = line.split(" #").next().unwrap_or("").trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') || line.starts_with("^[") {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else {
+                continue;
+            };
+            let pattern = pattern.trim_end_matches('/');
+            let anchored = pattern.starts_with('/') || pattern.contains('/');
+            let base = match pattern.trim_start_matches('/') {
+                "" | "*" => "**".to_string(),
+                p if anchored => p.to_string(),
+                p => format!("**/{}", p),
+            };
+            let patterns: Vec<Pattern> =
+                [base.clone(), format!("{}/**", base)].iter().filter_map(|p| Pattern::new(p).ok()).collect();
+            if patterns.is_empty() {
+                continue;
+            }
+            rules.push(OwnerRule { patterns, owners: fields.map(str::to_string).collect() });
+        }
+        Self { rules }
+    }
+
+    /// Owners of `path` (relative, `/`-separated) by the last matching
+    /// rule. `None` when no rule matches; a matching rule without owners
+    /// gives an empty slice.
+    pub fn owners_of(&self, path: &str) -> Option<&[String]> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.patterns.iter().any(|p| p.matches_with(path, MATCH_OPTIONS)))
+            .map(|rule| rule.owners.as_slice())
+    }
+}
+
+/// Whether any of `owners` contains `wanted`, ignoring case and a leading
+/// `@`: `team-checkout` matches `@acme/team-checkout`
+pub fn owned_by(owners: &[String], wanted: &str) -> bool {
+    let wanted = wanted.trim_start_matches('@').to_lowercase();
+    owners.iter().any(|owner| owner.to_lowercase().contains(&wanted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_owners() {
+        let owners = CodeOwners::parse(
+            "# Default owners\n\
+             *                          @acme/platform\n\
+             *.js                       @acme/frontend\n\
+             /app/code/Acme/Checkout/   @acme/team-checkout @jane\n\
+             app/code/Acme/Checkout/etc/di.xml\n\
+             [Docs]\n\
+             docs/                      @acme/docs # written by hand\n",
+        );
+        let of = |path: &str| owners.owners_of(path).map(|o| o.join(" "));
+        assert_eq!(of("app/code/Acme/Cart/Model/Cart.php").as_deref(), Some("@acme/platform"));
+        assert_eq!(of("app/code/Acme/Cart/view/frontend/web/js/cart.js").as_deref(), Some("@acme/frontend"));
+        assert_eq!(
+            of("app/code/Acme/Checkout/Model/Session.php").as_deref(),
+            Some("@acme/team-checkout @jane")
+        );
+        // Explicitly unowned
+        assert_eq!(of("app/code/Acme/Checkout/etc/di.xml").as_deref(), Some(""));
+        assert_eq!(of("docs/index.md").as_deref(), Some("@acme/docs"));
+        assert!(CodeOwners::parse("").owners_of("a.php").is_none());
+
+        let team = ["@acme/team-checkout".to_string(), "@jane".to_string()];
+        assert!(owned_by(&team, "team-checkout"));
+        assert!(owned_by(&team, "@Jane"));
+        assert!(!owned_by(&team, "team-catalog"));
+    }
+}
@@ -0,0 +1,292 @@
+//! Patch awareness
+//!
+//! Projects change core and third-party packages with patch files, either
+//! declared for `cweagans/composer-patches` (`extra.patches` in
+//! `composer.json`, or the file named by `extra.patches-file`) or dropped
+//! into `patches/` or `m2-hotfixes/` for deploy tooling to apply. Patch
+//! files under those directories are indexed as `patch` items naming the
+//! files they modify. [`PatchRegistry`] links each modified file back to
+//! its patches, so a search hit on a patched vendor file says so.
+//!
+//! Paths in a composer-patches patch are relative to its package and are
+//! mapped to `vendor/<package>/`; paths in other patches are relative to
+//! the Magento root. Remote (URL) patches are not fetched.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::vectordb::SearchResult;
+
+/// Directories holding patch files, relative to the Magento root
+pub const PATCH_DIRS: &[&str] = &["patches", "m2-hotfixes"];
+
+/// Extensions of patch files
+pub const PATCH_EXTENSIONS: &[&str] = &["patch", "diff"];
+
+/// A patch modifying a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchRef {
+    /// Patch file relative to the Magento root
+    pub patch: String,
+    /// Description from the composer-patches declaration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Modified file → patches, for one project
+#[derive(Debug, Clone, Default)]
+pub struct PatchRegistry {
+    by_file: BTreeMap<String, Vec<PatchRef>>,
+}
+
+/// A patch declared in composer.json
+struct Declared {
+    package: String,
+    description: String,
+    patch: String,
+}
+
+/// Whether `path` is a patch file in one of the [`PATCH_DIRS`]
+pub fn is_patch_path(path: &str) -> bool {
+    PATCH_DIRS.iter().any(|dir| path.strip_prefix(dir).is_some_and(|rest| rest.starts_with('/')))
+        && path
+            .rsplit_once('.')
+            .is_some_and(|(_, ext)| PATCH_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Path of a `---`/`+++` header without its `a/`/`b/` prefix or trailing
+/// timestamp; `None` for `/dev/null`
+fn header_path(field: &str) -> Option<&str> {
+    let path = field.split('\t').next()?.trim();
+    let path = path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")).unwrap_or(path);
+    (!path.is_empty() && path != "/dev/null").then_some(path)
+}
+
+/// Files a unified diff modifies, in order of appearance. A deleted file is
+/// named by its `---` header.
+pub fn modified_files(diff: &str) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    let mut old: Option<&str> = None;
+    for line in diff.lines() {
+        if let Some(field) = line.strip_prefix("--- ") {
+            old = Some(field);
+            continue;
+        }
+        // A `+++` header always follows its `---` one
+        if let (Some(field), Some(old)) = (line.strip_prefix("+++ "), old) {
+            if let Some(path) = header_path(field).or_else(|| header_path(old)) {
+                if !files.iter().any(|f| f == path) {
+                    files.push(path.to_string());
+                }
+            }
+        }
+        old = None;
+    }
+    files
+}
+
+/// Patch sources per package from an `extra.patches` object. Both the
+/// `{"description": "file"}` and the `[{"label": ..., "source": ...}]` forms
+/// are read.
+fn collect_declared(patches: &Value, declared: &mut Vec<Declared>) {
+    let Some(packages) = patches.as_object() else {
+        return;
+    };
+    let source = |value: &Value| {
+        value.as_str().or_else(|| value["source"].as_str()).or_else(|| value["url"].as_str()).map(str::to_string)
+    };
+    for (package, list) in packages {
+        let entries: Vec<(String, Option<String>)> = match list {
+            Value::Object(map) => map.iter().map(|(description, value)| (description.clone(), source(value))).collect(),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| {
+                    let label = item["label"].as_str().or_else(|| item["description"].as_str()).unwrap_or_default();
+                    (label.to_string(), source(item))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        for (description, patch) in entries {
+            if let Some(patch) = patch {
+                let patch = patch.trim_start_matches("./").to_string();
+                declared.push(Declared { package: package.clone(), description, patch });
+            }
+        }
+    }
+}
+
+/// Patches declared in `composer.json` and its `patches-file`
+fn composer_patches(root: &Path) -> Result<Vec<Declared>> {
+    let read_json = |rel: &str| -> Result<Option<Value>> {
+        let path = root.join(rel);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", rel))?;
+        serde_json::from_str(&text).map(Some).with_context(|| format!("Invalid JSON in {}", rel))
+    };
+    let mut declared = Vec::new();
+    let Some(composer) = read_json("composer.json")? else {
+        return Ok(declared);
+    };
+    collect_declared(&composer["extra"]["patches"], &mut declared);
+    if let Some(file) = composer["extra"]["patches-file"].as_str() {
+        if let Some(patches_file) = read_json(file)? {
+            collect_declared(&patches_file["patches"], &mut declared);
+        }
+    }
+    Ok(declared)
+}
+
+impl PatchRegistry {
+    /// Link the files modified by every patch of the project at `root`.
+    /// Unreadable patch files are logged and skipped.
+    pub fn scan(root: &Path) -> Result<Self> {
+        let mut registry = Self::default();
+        let mut seen = BTreeSet::new();
+        for Declared { package, description, patch } in composer_patches(root)? {
+            if patch.contains("://") || !seen.insert(patch.clone()) {
+                continue;
+            }
+            let diff = match std::fs::read_to_string(root.join(&patch)) {
+                Ok(diff) => diff,
+                Err(e) => {
+                    tracing::warn!("Skipping patch {} declared for {}: {}", patch, package, e);
+                    continue;
+                }
+            };
+            let description = (!description.is_empty()).then_some(description);
+            for file in modified_files(&diff) {
+                let file = if file.starts_with("vendor/") { file } else { format!("vendor/{}/{}", package, file) };
+                registry.add(file, PatchRef { patch: patch.clone(), description: description.clone() });
+            }
+        }
+        for dir in PATCH_DIRS {
+            for entry in WalkDir::new(root.join(dir)).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+                let rel = crate::vectordb::relative_path(root, entry.path());
+                if !entry.file_type().is_file() || !is_patch_path(&rel) || !seen.insert(rel.clone()) {
+                    continue;
+                }
+                match std::fs::read_to_string(entry.path()) {
+                    Ok(diff) => {
+                        for file in modified_files(&diff) {
+                            registry.add(file, PatchRef { patch: rel.clone(), description: None });
+                        }
+                    }
+                    Err(e) => tracing::warn!("Skipping patch {}: {}", rel, e),
+                }
+            }
+        }
+        Ok(registry)
+    }
+
+    fn add(&mut self, file: String, patch: PatchRef) {
+        let patches = self.by_file.entry(file).or_default();
+        if !patches.contains(&patch) {
+            patches.push(patch);
+        }
+    }
+
+    /// Patches modifying the file at `path` (relative to the Magento root)
+    pub fn patches_for(&self, path: &str) -> &[PatchRef] {
+        self.by_file.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    /// Number of patched files
+    pub fn len(&self) -> usize {
+        self.by_file.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_file.is_empty()
+    }
+
+    /// Set [`SearchResult::patched_by`] for every result
+    pub fn mark(&self, results: &mut [SearchResult]) {
+        if self.is_empty() {
+            return;
+        }
+        for result in results {
+            result.patched_by = self.patches_for(&result.metadata.path).to_vec();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/Model/Product.php b/Model/Product.php\n\
+                        index 1111111..2222222 100644\n\
+                        --- a/Model/Product.php\n\
+                        +++ b/Model/Product.php\n\
+                        @@ -1,3 +1,3 @@\n\
+                        --- not a header\n\
+                        -old\n\
+                        +new\n\
+                        --- a/Model/Old.php\t2024-01-01 00:00:00\n\
+                        +++ /dev/null\n\
+                        @@ -1 +0,0 @@\n\
+                        -gone\n";
+
+    #[test]
+    fn test_modified_files() {
+        assert_eq!(modified_files(DIFF), ["Model/Product.php", "Model/Old.php"]);
+        assert!(is_patch_path("patches/composer/fix-price.patch"));
+        assert!(is_patch_path("m2-hotfixes/MDVA-123.DIFF"));
+        assert!(!is_patch_path("patchesx/fix.patch"));
+        assert!(!is_patch_path("patches/README.md"));
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("patches/composer")).unwrap();
+        std::fs::create_dir_all(root.join("m2-hotfixes")).unwrap();
+        std::fs::write(root.join("patches/composer/fix-price.patch"), DIFF).unwrap();
+        std::fs::write(
+            root.join("m2-hotfixes/MDVA-1.patch"),
+            "--- a/vendor/magento/module-catalog/Model/Product.php\n\
+             +++ b/vendor/magento/module-catalog/Model/Product.php\n@@ -1 +1 @@\n-a\n+b\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("composer.json"),
+            r#"{"extra": {"patches": {"magento/module-catalog": {"Fix price rounding": "./patches/composer/fix-price.patch",
+                "Remote": "https://example.com/x.patch"}},
+                "patches-file": "composer.patches.json"}}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("composer.patches.json"),
+            r#"{"patches": {"acme/module-cart": [{"label": "Missing", "source": "patches/missing.patch"}]}}"#,
+        )
+        .unwrap();
+
+        let registry = PatchRegistry::scan(root).unwrap();
+        assert_eq!(registry.len(), 2);
+        let patches = registry.patches_for("vendor/magento/module-catalog/Model/Product.php");
+        assert_eq!(
+            patches,
+            [
+                PatchRef {
+                    patch: "patches/composer/fix-price.patch".to_string(),
+                    description: Some("Fix price rounding".to_string())
+                },
+                PatchRef { patch: "m2-hotfixes/MDVA-1.patch".to_string(), description: None },
+            ]
+        );
+        assert_eq!(registry.patches_for("vendor/magento/module-catalog/Model/Old.php").len(), 1);
+        assert!(registry.patches_for("vendor/magento/module-catalog/Model/Category.php").is_empty());
+
+        // No composer.json, no patches
+        let empty = tempfile::tempdir().unwrap();
+        assert!(PatchRegistry::scan(empty.path()).unwrap().is_empty());
+    }
+}
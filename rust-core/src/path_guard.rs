@@ -0,0 +1,119 @@
+//! Read scope for serve commands that return file content
+//!
+//! `grep`, `ast_query` and `explain_di` read files named by the request or
+//! the index. A path is only read once it canonicalizes to somewhere inside
+//! the Magento root or one of the index's extra roots, so neither `..`
+//! segments nor a symlink pointing out of the project can make them return
+//! host files.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// The canonical roots that file reads must stay within
+#[derive(Debug, Clone)]
+pub struct ReadScope {
+    root: PathBuf,
+    /// Extra roots (`index --extra-root`) whose files are indexed too
+    extra: Vec<PathBuf>,
+}
+
+impl ReadScope {
+    /// Scope reads to `root`, which must exist
+    pub fn new(root: &Path) -> Result<Self> {
+        let root = root.canonicalize().with_context(|| format!("Magento root does not exist: {}", root.display()))?;
+        Ok(Self { root, extra: Vec::new() })
+    }
+
+    /// Also allow reads under `roots`; ones that don't exist are left out
+    pub fn with_extra_roots(mut self, roots: &[PathBuf]) -> Self {
+        self.extra = roots.iter().filter_map(|r| r.canonicalize().ok()).collect();
+        self
+    }
+
+    fn allows(&self, path: &Path) -> bool {
+        path.starts_with(&self.root) || self.extra.iter().any(|r| path.starts_with(r))
+    }
+
+    /// Resolve `rel` (relative to the root, or absolute) to its canonical
+    /// path. Fails when it does not exist or lies outside the roots.
+    pub fn resolve(&self, rel: &str) -> Result<PathBuf> {
+        let joined = self.root.join(rel);
+        let path = joined.canonicalize().with_context(|| format!("Path does not exist: {}", rel))?;
+        if !self.allows(&path) {
+            anyhow::bail!("Path is outside magento_root: {}", rel);
+        }
+        Ok(path)
+    }
+
+    /// Whether `path` exists and canonicalizes to somewhere inside the roots
+    pub fn contains(&self, path: &Path) -> bool {
+        path.canonicalize().is_ok_and(|p| self.allows(&p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_resolve_stays_in_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("magento");
+        fs::create_dir_all(root.join("app/code/Acme")).unwrap();
+        fs::write(root.join("app/code/Acme/a.php"), "<?php").unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        let scope = ReadScope::new(&root).unwrap();
+
+        assert!(scope.resolve(".").is_ok());
+        assert!(scope.resolve("app/code/Acme/../Acme/a.php").is_ok());
+        assert!(scope.contains(&root.join("app/code/Acme/a.php")));
+
+        let err = scope.resolve("../secret.txt").unwrap_err().to_string();
+        assert!(err.contains("outside"), "{}", err);
+        assert!(scope.resolve("app/../../secret.txt").is_err());
+        assert!(scope.resolve(&dir.path().join("secret.txt").to_string_lossy()).is_err());
+        assert!(!scope.contains(&root.join("../secret.txt")));
+        assert!(scope.resolve("app/missing.php").unwrap_err().to_string().contains("does not exist"));
+    }
+
+    #[test]
+    fn test_extra_roots_readable() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("magento");
+        let extra = dir.path().join("acme-payments");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(extra.join("Model")).unwrap();
+        fs::write(extra.join("Model/Pay.php"), "<?php").unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        let file = extra.join("Model/Pay.php").to_string_lossy().into_owned();
+
+        assert!(ReadScope::new(&root).unwrap().resolve(&file).is_err());
+        let scope = ReadScope::new(&root).unwrap().with_extra_roots(&[extra.clone(), dir.path().join("missing")]);
+        assert!(scope.resolve(&file).is_ok());
+        assert!(scope.contains(&extra.join("Model/../Model/Pay.php")));
+        assert!(scope.resolve(&extra.join("../secret.txt").to_string_lossy()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escape() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("magento");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("id_rsa"), "key").unwrap();
+        fs::write(root.join("app/a.php"), "<?php").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("app/linked")).unwrap();
+        std::os::unix::fs::symlink(outside.join("id_rsa"), root.join("app/key.php")).unwrap();
+        std::os::unix::fs::symlink(root.join("app/a.php"), root.join("app/alias.php")).unwrap();
+        let scope = ReadScope::new(&root).unwrap();
+
+        assert!(scope.resolve("app/linked").is_err());
+        assert!(scope.resolve("app/linked/id_rsa").is_err());
+        assert!(!scope.contains(&root.join("app/key.php")));
+        // Links that stay inside the root are fine
+        assert!(scope.contains(&root.join("app/alias.php")));
+    }
+}
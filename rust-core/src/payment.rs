@@ -0,0 +1,322 @@
+//! Payment method registry (`magector payments`, serve `payments`)
+//!
+//! A payment method is spread over several files: `etc/config.xml` declares
+//! it under `<default><payment><code>` (model, title, `is_gateway`, ...),
+//! `etc/payment.xml` lists it among the methods, gateway methods point their
+//! model at a facade virtualType in `etc/di.xml`, and checkout renders it
+//! with a JS component registered via `rendererList.push({type, component})`.
+//! [`PaymentRegistry::methods`] joins them per method code. The indexer uses
+//! the registry to tag method implementations, so payment queries rank them
+//! above files that merely mention the code.
+
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::magento::VirtualTypeDeclaration;
+
+/// A payment method joined from every file declaring it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PaymentMethod {
+    pub code: String,
+    pub title: Option<String>,
+    /// `<model>` as written: a class or a facade virtualType
+    pub model: Option<String>,
+    /// Class behind `model` after following virtualTypes
+    pub model_class: Option<String>,
+    pub is_gateway: Option<bool>,
+    pub active: Option<bool>,
+    /// Payment group (`offline`, ...)
+    pub group: Option<String>,
+    /// Checkout JS renderer components
+    pub renderers: Vec<String>,
+    /// Files declaring the method, its facade or its renderers
+    pub files: Vec<String>,
+}
+
+impl PaymentMethod {
+    fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+        [Some(&self.code), self.title.as_ref(), self.model.as_ref(), self.model_class.as_ref()]
+            .into_iter()
+            .flatten()
+            .chain(self.renderers.iter())
+            .any(|part| part.to_lowercase().contains(&filter))
+    }
+}
+
+/// Payment configuration gathered from config.xml, payment.xml, di.xml and
+/// checkout renderer registrations
+#[derive(Debug, Clone, Default)]
+pub struct PaymentRegistry {
+    methods: BTreeMap<String, PaymentMethod>,
+    /// virtualType name → (base type, declaring file)
+    virtual_types: HashMap<String, (String, String)>,
+    /// Implementing class → its methods, from [`Self::index_classes`]
+    by_class: HashMap<String, Vec<PaymentMethod>>,
+}
+
+/// Whether `path` feeds the payment registry
+pub fn is_payment_source(path: &str) -> bool {
+    path.ends_with("etc/config.xml")
+        || path.ends_with("etc/payment.xml")
+        || path.ends_with("/di.xml")
+        || (path.ends_with(".js") && path.contains("/web/js/view/payment/"))
+}
+
+impl PaymentRegistry {
+    fn method(&mut self, code: &str) -> &mut PaymentMethod {
+        self.methods.entry(code.to_string()).or_insert_with(|| PaymentMethod {
+            code: code.to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// Methods declared under `<default><payment>` in a config.xml
+    pub fn add_config_xml(&mut self, content: &str, file: &str) {
+        let flag = |v: &str| v == "1" || v == "true";
+        for (code, fields) in crate::magento::config_section_entries(content, "payment") {
+            let method = self.method(&code);
+            if let Some(model) = fields.get("model").filter(|m| !m.is_empty()) {
+                method.model = Some(model.trim_start_matches('\\').to_string());
+            }
+            if let Some(title) = fields.get("title").filter(|t| !t.is_empty()) {
+                method.title = Some(title.clone());
+            }
+            if let Some(group) = fields.get("group").filter(|g| !g.is_empty()) {
+                method.group = Some(group.clone());
+            }
+            if let Some(v) = fields.get("is_gateway") {
+                method.is_gateway = Some(flag(v));
+            }
+            if let Some(v) = fields.get("active") {
+                method.active = Some(flag(v));
+            }
+            method.files.push(file.to_string());
+        }
+    }
+
+    /// Methods listed under `<methods>` in a payment.xml
+    pub fn add_payment_xml(&mut self, content: &str, file: &str) {
+        let method_re = Regex::new(r#"<method\s+name="([^"]+)""#).unwrap();
+        for caps in method_re.captures_iter(content) {
+            self.method(&caps[1]).files.push(file.to_string());
+        }
+    }
+
+    /// virtualTypes of a di.xml, for resolving facade models
+    pub fn add_virtual_types(&mut self, virtual_types: &[VirtualTypeDeclaration], file: &str) {
+        for vt in virtual_types.iter().filter(|vt| !vt.base_type.is_empty()) {
+            self.virtual_types
+                .insert(vt.name.trim_start_matches('\\').to_string(), (vt.base_type.trim_start_matches('\\').to_string(), file.to_string()));
+        }
+    }
+
+    /// Checkout renderer registrations: `rendererList.push({type, component})`
+    pub fn add_renderer_js(&mut self, content: &str, file: &str) {
+        let push_re = Regex::new(r"rendererList\.push\(\s*\{([^}]*)\}").unwrap();
+        let prop_re = Regex::new(r#"(type|component)\s*:\s*['"]([^'"]+)['"]"#).unwrap();
+        for push in push_re.captures_iter(content) {
+            let mut code = None;
+            let mut component = None;
+            for prop in prop_re.captures_iter(&push[1]) {
+                match &prop[1] {
+                    "type" => code = Some(prop[2].to_string()),
+                    _ => component = Some(prop[2].to_string()),
+                }
+            }
+            if let (Some(code), Some(component)) = (code, component) {
+                let method = self.method(&code);
+                if !method.renderers.contains(&component) {
+                    method.renderers.push(component);
+                }
+                method.files.push(file.to_string());
+            }
+        }
+    }
+
+    /// Follow virtualTypes from `name` to a class; the declaring di.xml
+    /// files are returned alongside
+    fn resolve(&self, name: &str) -> (String, Vec<String>) {
+        let mut current = name.to_string();
+        let mut seen = std::collections::HashSet::from([current.clone()]);
+        let mut files = Vec::new();
+        while let Some((base, file)) = self.virtual_types.get(&current) {
+            // A cycle is a config error; stop at the last type before it
+            if !seen.insert(base.clone()) {
+                break;
+            }
+            files.push(file.clone());
+            current = base.clone();
+        }
+        (current, files)
+    }
+
+    /// `method` with its model resolved and its files collected
+    fn joined(&self, method: &PaymentMethod) -> PaymentMethod {
+        let mut method = method.clone();
+        if let Some(ref model) = method.model {
+            let (class, files) = self.resolve(model);
+            method.model_class = Some(class);
+            method.files.extend(files);
+        }
+        method.files.sort();
+        method.files.dedup();
+        method
+    }
+
+    /// Joined methods with a model or a renderer that satisfy `keep`
+    fn select(&self, keep: impl Fn(&PaymentMethod) -> bool) -> Vec<PaymentMethod> {
+        self.methods
+            .values()
+            .filter(|m| m.model.is_some() || !m.renderers.is_empty())
+            .filter(|m| keep(m))
+            .map(|m| self.joined(m))
+            .collect()
+    }
+
+    /// Methods with a model or a renderer, sorted by code, optionally only
+    /// those whose code, title, model or renderer contains `filter`
+    /// (case-insensitive). Codes that only appear in payment.xml or as
+    /// unrelated config keys are left out.
+    pub fn methods(&self, filter: Option<&str>) -> Vec<PaymentMethod> {
+        self.select(|_| true)
+            .into_iter()
+            .filter(|m| filter.filter(|f| !f.is_empty()).is_none_or(|f| m.matches(f)))
+            .collect()
+    }
+
+    /// Map the classes implementing each method to it for
+    /// [`Self::methods_for_class`]; call once every file is added
+    pub fn index_classes(&mut self) {
+        let mut by_class: HashMap<String, Vec<PaymentMethod>> = HashMap::new();
+        for method in self.select(|m| m.model.is_some()) {
+            let Some(ref model) = method.model else { continue };
+            let (resolved, _) = self.resolve(model);
+            let mut classes = vec![model.trim_start_matches('\\')];
+            if !is_generic_adapter(&resolved) {
+                classes.push(resolved.trim_start_matches('\\'));
+            }
+            classes.dedup();
+            for class in classes {
+                by_class.entry(class.to_string()).or_default().push(method.clone());
+            }
+        }
+        self.by_class = by_class;
+    }
+
+    /// Methods implemented by `class`: their model, or the class behind a
+    /// facade unless that is the shared gateway adapter
+    pub fn methods_for_class(&self, class: &str) -> Vec<PaymentMethod> {
+        self.by_class.get(class.trim_start_matches('\\')).cloned().unwrap_or_default()
+    }
+
+    /// Methods whose checkout renderer component is the JS file at `path`
+    /// (`Vendor_Module/js/view/payment/method-renderer/x` ↔
+    /// `.../web/js/view/payment/method-renderer/x.js`)
+    pub fn methods_for_renderer(&self, path: &str) -> Vec<PaymentMethod> {
+        self.select(|m| {
+            m.renderers.iter().any(|component| {
+                component
+                    .split_once('/')
+                    .is_some_and(|(_, rest)| path.ends_with(&format!("/web/{}.js", rest)))
+            })
+        })
+    }
+
+    /// Methods whose facade virtualType is declared in the di.xml at `path`
+    pub fn methods_for_di(&self, path: &str) -> Vec<PaymentMethod> {
+        self.select(|m| {
+            m.model
+                .as_ref()
+                .and_then(|model| self.virtual_types.get(model))
+                .is_some_and(|(_, file)| file == path)
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+}
+
+/// Gateway facades all resolve to the same adapter; it implements no
+/// method in particular
+fn is_generic_adapter(class: &str) -> bool {
+    class == "Magento\\Payment\\Model\\Method\\Adapter"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payment_registry() {
+        let mut registry = PaymentRegistry::default();
+        registry.add_config_xml(
+            r#"<config>
+                <default>
+                    <payment>
+                        <checkmo>
+                            <active>1</active>
+                            <model>Magento\OfflinePayments\Model\Checkmo</model>
+                            <!-- <model>Old\Model</model> -->
+                            <title>Check / Money order</title>
+                            <group>offline</group>
+                        </checkmo>
+                        <braintree>
+                            <model>BraintreeFacade</model>
+                            <is_gateway>1</is_gateway>
+                            <paymentInfoKeys>cc_type,avsPostalCodeResponseCode</paymentInfoKeys>
+                        </braintree>
+                    </payment>
+                    <carriers><flatrate><model>Magento\OfflineShipping\Model\Carrier\Flatrate</model></flatrate></carriers>
+                </default>
+            </config>"#,
+            "Magento/OfflinePayments/etc/config.xml",
+        );
+        registry.add_virtual_types(
+            &[VirtualTypeDeclaration {
+                name: "BraintreeFacade".to_string(),
+                base_type: "Magento\\Payment\\Model\\Method\\Adapter".to_string(),
+                shared: None,
+            }],
+            "PayPal/Braintree/etc/di.xml",
+        );
+        registry.add_renderer_js(
+            r#"define(['uiComponent', 'Magento_Checkout/js/model/payment/renderer-list'], function (Component, rendererList) {
+                rendererList.push(
+                    {
+                        type: 'checkmo',
+                        component: 'Magento_OfflinePayments/js/view/payment/method-renderer/checkmo-method'
+                    }
+                );
+            });"#,
+            "Magento/OfflinePayments/view/frontend/web/js/view/payment/offline-payments.js",
+        );
+
+        let methods = registry.methods(None);
+        assert_eq!(methods.iter().map(|m| m.code.as_str()).collect::<Vec<_>>(), vec!["braintree", "checkmo"]);
+        let checkmo = &methods[1];
+        assert_eq!(checkmo.model.as_deref(), Some("Magento\\OfflinePayments\\Model\\Checkmo"));
+        assert_eq!(checkmo.title.as_deref(), Some("Check / Money order"));
+        assert_eq!(checkmo.active, Some(true));
+        assert_eq!(checkmo.group.as_deref(), Some("offline"));
+        assert_eq!(checkmo.renderers, vec!["Magento_OfflinePayments/js/view/payment/method-renderer/checkmo-method"]);
+        let braintree = &methods[0];
+        assert_eq!(braintree.is_gateway, Some(true));
+        assert_eq!(braintree.model_class.as_deref(), Some("Magento\\Payment\\Model\\Method\\Adapter"));
+        assert!(braintree.files.contains(&"PayPal/Braintree/etc/di.xml".to_string()));
+
+        assert_eq!(registry.methods(Some("money")).len(), 1);
+        registry.index_classes();
+        assert_eq!(registry.methods_for_class("\\Magento\\OfflinePayments\\Model\\Checkmo").len(), 1);
+        assert!(registry.methods_for_class("Magento\\Payment\\Model\\Method\\Adapter").is_empty());
+        assert_eq!(
+            registry
+                .methods_for_renderer("vendor/magento/module-offline-payments/view/frontend/web/js/view/payment/method-renderer/checkmo-method.js")
+                .len(),
+            1
+        );
+        assert_eq!(registry.methods_for_di("PayPal/Braintree/etc/di.xml")[0].code, "braintree");
+    }
+}
@@ -0,0 +1,131 @@
+//! Index size estimation and disk space preflight
+
+use std::path::Path;
+
+use crate::embedder::EMBEDDING_DIM;
+
+/// Rough on-disk bytes per vector beyond the embedding itself: metadata,
+/// search text and map keys. Deliberately generous so the preflight errs
+/// on the safe side.
+const METADATA_BYTES_PER_VECTOR: u64 = 2560;
+
+/// Estimated index size for a set of files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexEstimate {
+    /// Vectors after indexing (one per file, plus any kept from a resume)
+    pub vectors: usize,
+    /// Size of the saved DB file
+    pub db_bytes: u64,
+}
+
+impl IndexEstimate {
+    pub fn for_vectors(vectors: usize) -> Self {
+        let per_vector = (EMBEDDING_DIM * std::mem::size_of::<f32>()) as u64 + METADATA_BYTES_PER_VECTOR;
+        Self {
+            vectors,
+            db_bytes: vectors as u64 * per_vector,
+        }
+    }
+
+    /// Free space needed: the atomic save writes a full temp copy before
+    /// replacing the previous DB, so two copies coexist briefly.
+    pub fn required_bytes(&self) -> u64 {
+        self.db_bytes.saturating_mul(2)
+    }
+}
+
+/// Outcome of [`check_disk_space`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpaceCheck {
+    /// Enough room, or free space could not be determined
+    Ok,
+    /// Fits, but with less than 2x headroom
+    Low { available: u64, required: u64 },
+    /// The index will not fit
+    Insufficient { available: u64, required: u64 },
+}
+
+/// Compare the estimate against free space on the filesystem holding `db_path`.
+pub fn check_disk_space(db_path: &Path, estimate: &IndexEstimate) -> SpaceCheck {
+    let Some(available) = available_space(db_path) else {
+        return SpaceCheck::Ok;
+    };
+    let required = estimate.required_bytes();
+    if available < required {
+        SpaceCheck::Insufficient { available, required }
+    } else if available < required.saturating_mul(2) {
+        SpaceCheck::Low { available, required }
+    } else {
+        SpaceCheck::Ok
+    }
+}
+
+/// Free bytes for unprivileged users on the filesystem holding `path`.
+/// The path itself may not exist yet — its nearest existing ancestor is used.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = path
+        .ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.exists())
+        .unwrap_or(Path::new("."));
+    let c_path = std::ffi::CString::new(existing.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is NUL-terminated and stat is a valid out-pointer.
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Human-readable byte count (`1.5 GB`)
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_and_format() {
+        let estimate = IndexEstimate::for_vectors(1000);
+        assert_eq!(estimate.db_bytes, 1000 * (384 * 4 + METADATA_BYTES_PER_VECTOR));
+        assert_eq!(estimate.required_bytes(), estimate.db_bytes * 2);
+
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_disk_space_check() {
+        let missing = std::env::temp_dir().join("magector_preflight/not/yet/index.db");
+        assert!(available_space(&missing).is_some());
+
+        let tiny = IndexEstimate::for_vectors(1);
+        assert_eq!(check_disk_space(&missing, &tiny), SpaceCheck::Ok);
+
+        let huge = IndexEstimate { vectors: 0, db_bytes: u64::MAX / 4 };
+        assert!(matches!(check_disk_space(&missing, &huge), SpaceCheck::Insufficient { .. }));
+    }
+}
@@ -0,0 +1,220 @@
+//! Background validation in serve mode (`serve --probe-hours`)
+//!
+//! A small, fixed sample of the validation cases is searched when serve
+//! starts and then every few hours. Each run is appended to `probes.jsonl`
+//! next to the index, so accuracy can be followed over time and across
+//! restarts. A run that scores below the one before it is reported with the
+//! cases that started failing and the files re-indexed and SONA signals
+//! learned in between; serve `stats` shows the trend.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::validation::{TestCase, Validator};
+use crate::Indexer;
+
+/// Cases a probe run searches by default
+pub const DEFAULT_PROBE_CASES: usize = 20;
+
+/// Probe history file, next to the index
+pub const PROBE_FILE: &str = "probes.jsonl";
+
+/// Runs kept in memory
+const KEPT_RUNS: usize = 100;
+
+/// Latest runs listed by [`ProbeHistory::trend`]
+const TREND_RUNS: usize = 10;
+
+/// Outcome of one probe run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeRun {
+    /// Unix seconds
+    pub at: u64,
+    pub passed: usize,
+    pub total: usize,
+    pub accuracy: f32,
+    /// Vectors in the index at the time
+    pub vectors: usize,
+    /// Files re-indexed since serve started
+    pub reindexed_files: u64,
+    /// SONA signals learned since serve started
+    pub sona_signals: u64,
+    /// IDs of the failed cases
+    pub failed: Vec<String>,
+}
+
+/// Accuracy over the recorded runs (serve `stats`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProbeTrend {
+    pub runs: usize,
+    pub first: Option<f32>,
+    pub latest: Option<f32>,
+    /// Latest minus first accuracy
+    pub change: Option<f32>,
+    /// Latest runs, oldest first
+    pub recent: Vec<ProbeRun>,
+}
+
+/// `n` cases spread evenly over `cases`, in suite order
+pub fn sample(cases: &[TestCase], n: usize) -> Vec<TestCase> {
+    if n == 0 || cases.is_empty() {
+        return Vec::new();
+    }
+    cases.iter().step_by(cases.len().div_ceil(n)).cloned().collect()
+}
+
+/// Search the cases of `validator` and summarize the outcome. The indexer
+/// is locked per case, so requests and the watcher run in between.
+pub fn run(indexer: &Mutex<Indexer>, validator: &Validator) -> Result<ProbeRun> {
+    let lock = || indexer.lock().unwrap_or_else(|e| e.into_inner());
+    let results = validator
+        .test_cases()
+        .iter()
+        .map(|case| validator.evaluate_case(&mut lock(), case))
+        .collect::<Result<Vec<_>>>()?;
+    let indexer = lock();
+    let passed = results.iter().filter(|r| r.passed).count();
+    Ok(ProbeRun {
+        at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        passed,
+        total: results.len(),
+        accuracy: if results.is_empty() { 0.0 } else { passed as f32 / results.len() as f32 * 100.0 },
+        vectors: indexer.stats().vectors_created,
+        reindexed_files: indexer.reindexed_files(),
+        sona_signals: indexer.sona_signals(),
+        failed: results.iter().filter(|r| !r.passed).map(|r| r.test_id.clone()).collect(),
+    })
+}
+
+/// Probe runs, loaded from and appended to a history file
+#[derive(Debug, Default)]
+pub struct ProbeHistory {
+    path: Option<PathBuf>,
+    runs: VecDeque<ProbeRun>,
+    /// Runs loaded from the file rather than recorded by this process
+    loaded: usize,
+}
+
+impl ProbeHistory {
+    /// History kept in `path`, with its earlier runs loaded
+    pub fn open(path: PathBuf) -> Self {
+        let mut runs: VecDeque<ProbeRun> = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        while runs.len() > KEPT_RUNS {
+            runs.pop_front();
+        }
+        let loaded = runs.len();
+        Self { path: Some(path), runs, loaded }
+    }
+
+    /// Record a run and append it to the file. Returns a warning when its
+    /// accuracy is below the previous run's.
+    pub fn record(&mut self, run: ProbeRun) -> Option<String> {
+        let warning = self.runs.back().filter(|prev| run.accuracy < prev.accuracy).map(|prev| {
+            let newly_failed: Vec<&str> =
+                run.failed.iter().filter(|id| !prev.failed.contains(id)).map(String::as_str).collect();
+            // The counters restart with serve
+            let since = if self.loaded == self.runs.len() {
+                "since the previous serve session".to_string()
+            } else {
+                format!(
+                    "after {} re-indexed files and {} SONA signals",
+                    run.reindexed_files - prev.reindexed_files.min(run.reindexed_files),
+                    run.sona_signals - prev.sona_signals.min(run.sona_signals)
+                )
+            };
+            format!(
+                "Probe accuracy fell from {:.1}% to {:.1}% {} (newly failing: {})",
+                prev.accuracy,
+                run.accuracy,
+                since,
+                if newly_failed.is_empty() { "none".to_string() } else { newly_failed.join(", ") }
+            )
+        });
+        if let Some(ref path) = self.path {
+            let appended = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", serde_json::to_string(&run).unwrap_or_default()));
+            if let Err(e) = appended {
+                tracing::warn!("Failed to append probe run to {}: {}", path.display(), e);
+            }
+        }
+        self.runs.push_back(run);
+        if self.runs.len() > KEPT_RUNS {
+            self.runs.pop_front();
+            self.loaded = self.loaded.saturating_sub(1);
+        }
+        warning
+    }
+
+    pub fn trend(&self) -> ProbeTrend {
+        let first = self.runs.front().map(|r| r.accuracy);
+        let latest = self.runs.back().map(|r| r.accuracy);
+        ProbeTrend {
+            runs: self.runs.len(),
+            first,
+            latest,
+            change: first.zip(latest).map(|(first, latest)| latest - first),
+            recent: self.runs.iter().skip(self.runs.len().saturating_sub(TREND_RUNS)).cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(accuracy: f32, reindexed_files: u64, failed: &[&str]) -> ProbeRun {
+        ProbeRun {
+            at: 0,
+            passed: 0,
+            total: 20,
+            accuracy,
+            vectors: 100,
+            reindexed_files,
+            sona_signals: 0,
+            failed: failed.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_history_warns_on_drop_and_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROBE_FILE);
+
+        let mut history = ProbeHistory::open(path.clone());
+        assert!(history.record(probe(95.0, 0, &["a"])).is_none());
+        assert!(history.record(probe(95.0, 3, &["a"])).is_none());
+        let warning = history.record(probe(90.0, 10, &["a", "b"])).unwrap();
+        assert!(warning.contains("95.0% to 90.0%"), "{}", warning);
+        assert!(warning.contains("after 7 re-indexed files"), "{}", warning);
+        assert!(warning.contains("newly failing: b"), "{}", warning);
+
+        let mut reopened = ProbeHistory::open(path);
+        let trend = reopened.trend();
+        assert_eq!((trend.runs, trend.first, trend.latest, trend.change), (3, Some(95.0), Some(90.0), Some(-5.0)));
+        // The counters of the previous serve session aren't comparable
+        let warning = reopened.record(probe(85.0, 1, &["a", "b", "c"])).unwrap();
+        assert!(warning.contains("since the previous serve session"), "{}", warning);
+    }
+
+    #[test]
+    fn test_sample_spreads_cases() {
+        let cases: Vec<TestCase> = Validator::new().test_cases().to_vec();
+        let sampled = sample(&cases, DEFAULT_PROBE_CASES);
+        assert!(!sampled.is_empty() && sampled.len() <= DEFAULT_PROBE_CASES);
+        assert_eq!(sampled[0].id, cases[0].id);
+        assert!(sample(&cases, 0).is_empty());
+    }
+}
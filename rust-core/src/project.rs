@@ -0,0 +1,612 @@
+//! Project configuration (`magector project-info`, serve `project_info`)
+//!
+//! `app/etc/config.php` lists every installed module with its enabled flag,
+//! and `app/etc/env.php` holds the deployment settings. Both are PHP files
+//! returning a nested array literal, read here without running PHP. env.php
+//! also holds database credentials and the crypt key, so only the deploy
+//! mode, the table prefix and the `cache` and `queue` sections are kept,
+//! with every value under a credential-like key replaced by [`REDACTED`].
+//! env.php itself is never indexed.
+//!
+//! Search marks results from modules config.php disables, which can never
+//! run on the project, and weights them with
+//! [`ScoreWeights::disabled`](crate::vectordb::ScoreWeights::disabled).
+//!
+//! config.php also fixes the module load order: `setup:upgrade` writes the
+//! modules in the order it resolved from every `etc/module.xml`
+//! `<sequence>`. Config merged across modules (sales.xml totals,
+//! requirejs-config.js mixins) lets later modules win, so [`ModuleOrder`]
+//! replays it.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use crate::magento::module_names_for_path;
+use crate::redact::REDACTED;
+use crate::vectordb::SearchResult;
+
+/// Module registry, relative to the Magento root
+pub const CONFIG_PHP: &str = "app/etc/config.php";
+
+/// Deployment configuration, relative to the Magento root
+pub const ENV_PHP: &str = "app/etc/env.php";
+
+/// Key fragments whose values are credentials
+const SECRET_KEY_PARTS: &[&str] = &["password", "passwd", "secret", "token", "api_key", "apikey", "crypt", "private"];
+
+/// Keys that are credentials as a whole
+const SECRET_KEYS: &[&str] = &["user", "username", "key", "auth"];
+
+/// Project-level settings from config.php and a sanitized env.php
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProjectInfo {
+    /// Module name → enabled, for every module config.php lists
+    pub modules: BTreeMap<String, bool>,
+    /// Deploy mode (`MAGE_MODE`: default, developer, production)
+    pub mode: Option<String>,
+    /// Prefix of every database table (`db.table_prefix`)
+    pub table_prefix: Option<String>,
+    /// `cache` section of env.php, credentials redacted
+    pub cache: Option<serde_json::Value>,
+    /// `queue` section of env.php, credentials redacted
+    pub queue: Option<serde_json::Value>,
+    /// Files read, relative to the Magento root
+    pub files: Vec<String>,
+}
+
+impl ProjectInfo {
+    /// Read config.php and env.php under `root`. Missing files leave their
+    /// fields empty; unreadable ones are an error.
+    pub fn scan(root: &Path) -> Result<Self> {
+        let mut info = Self::default();
+        if let Some(config) = read_php_return(root, CONFIG_PHP)? {
+            info.modules = config
+                .get("modules")
+                .map(|modules| modules.entries().filter_map(|(name, flag)| Some((name?.to_string(), flag.truthy()))).collect())
+                .unwrap_or_default();
+            info.files.push(CONFIG_PHP.to_string());
+        }
+        if let Some(env) = read_php_return(root, ENV_PHP)? {
+            info.mode = env.get("MAGE_MODE").and_then(PhpValue::as_str).map(str::to_string);
+            info.table_prefix =
+                env.get("db").and_then(|db| db.get("table_prefix")).and_then(PhpValue::as_str).map(str::to_string);
+            info.cache = env.get("cache").map(|cache| cache.to_json(false));
+            info.queue = env.get("queue").map(|queue| queue.to_json(false));
+            info.files.push(ENV_PHP.to_string());
+        }
+        Ok(info)
+    }
+
+    /// Whether `module` (`Vendor_Module`) is enabled; `None` when config.php
+    /// does not list it
+    pub fn is_enabled(&self, module: &str) -> Option<bool> {
+        self.modules.get(module).copied()
+    }
+
+    /// Modules config.php lists as disabled
+    pub fn disabled_modules(&self) -> Vec<&str> {
+        self.modules.iter().filter(|(_, enabled)| !**enabled).map(|(name, _)| name.as_str()).collect()
+    }
+}
+
+/// A module as `etc/module.xml` declares it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleDecl {
+    /// `Vendor_Module`
+    pub name: String,
+    /// Module directory, relative to the Magento root
+    pub dir: String,
+    /// Modules it loads after (`<sequence>`)
+    pub sequence: Vec<String>,
+}
+
+impl ModuleDecl {
+    /// Parse the module.xml of the module in `dir`
+    pub fn parse(content: &str, dir: &str) -> Option<Self> {
+        let module_re = Regex::new(r#"<module\s+name="([^"]+)""#).unwrap();
+        let mut names = module_re.captures_iter(content).map(|c| c[1].to_string());
+        let name = names.next()?;
+        Some(Self { name, dir: dir.to_string(), sequence: names.collect() })
+    }
+}
+
+/// Module load order: config.php's when it lists the module, else the
+/// module.xml `<sequence>` declarations resolved here (dependencies first,
+/// ties by name), after every listed module
+#[derive(Debug, Clone, Default)]
+pub struct ModuleOrder {
+    position: HashMap<String, usize>,
+    /// (module directory, module), longest directory first
+    dirs: Vec<(String, String)>,
+}
+
+impl ModuleOrder {
+    /// Order of the `declared` modules of the project at `root`
+    pub fn new(root: &Path, declared: &[ModuleDecl]) -> Self {
+        let listed: Vec<String> = read_php_return(root, CONFIG_PHP)
+            .ok()
+            .flatten()
+            .and_then(|config| Some(config.get("modules")?.entries().filter_map(|(name, _)| name.map(str::to_string)).collect()))
+            .unwrap_or_default();
+        Self::from_parts(listed, declared)
+    }
+
+    fn from_parts(listed: Vec<String>, declared: &[ModuleDecl]) -> Self {
+        let mut order = Self::default();
+        for name in listed.into_iter().chain(Self::resolve(declared)) {
+            let next = order.position.len();
+            order.position.entry(name).or_insert(next);
+        }
+        order.dirs = declared.iter().map(|d| (d.dir.clone(), d.name.clone())).collect();
+        order.dirs.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+        order
+    }
+
+    /// `declared` in dependency order
+    fn resolve(declared: &[ModuleDecl]) -> Vec<String> {
+        fn visit<'a>(
+            name: &'a str,
+            by_name: &HashMap<&'a str, &'a ModuleDecl>,
+            seen: &mut HashSet<&'a str>,
+            out: &mut Vec<String>,
+        ) {
+            let Some(decl) = by_name.get(name) else { return };
+            if !seen.insert(name) {
+                return;
+            }
+            let mut deps: Vec<&str> = decl.sequence.iter().map(String::as_str).collect();
+            deps.sort();
+            for dep in deps {
+                visit(dep, by_name, seen, out);
+            }
+            out.push(name.to_string());
+        }
+        let by_name: HashMap<&str, &ModuleDecl> = declared.iter().map(|d| (d.name.as_str(), d)).collect();
+        let mut names: Vec<&str> = by_name.keys().copied().collect();
+        names.sort();
+        let mut seen = HashSet::new();
+        let mut out = Vec::new();
+        for name in names {
+            visit(name, &by_name, &mut seen, &mut out);
+        }
+        out
+    }
+
+    /// Load position of the module holding `file` (relative to the root);
+    /// files outside every declared module, such as themes, come last
+    pub fn file_position(&self, file: &str) -> usize {
+        self.dirs
+            .iter()
+            .find(|(dir, _)| file.strip_prefix(dir.as_str()).is_some_and(|rest| rest.starts_with('/')))
+            .and_then(|(_, name)| self.position.get(name).copied())
+            .unwrap_or(usize::MAX)
+    }
+}
+
+/// Set [`SearchResult::module_enabled`] from `states` (module → enabled)
+/// and multiply the score of results from disabled modules by `weight`;
+/// 0 drops them. Results stay sorted by score.
+pub fn mark_module_states(states: &BTreeMap<String, bool>, results: &mut Vec<SearchResult>, weight: f32) {
+    if states.is_empty() {
+        return;
+    }
+    for result in results.iter_mut() {
+        result.module_enabled =
+            module_names_for_path(&result.metadata.path).iter().find_map(|name| states.get(name).copied());
+        if result.module_enabled == Some(false) {
+            result.score *= weight;
+        }
+    }
+    if weight != 1.0 {
+        results.retain(|r| r.module_enabled != Some(false) || weight > 0.0);
+        results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    }
+}
+
+/// Whether the value under `key` is a credential
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SECRET_KEYS.contains(&key.as_str()) || SECRET_KEY_PARTS.iter().any(|part| key.contains(part))
+}
+
+/// The array a PHP file returns, `None` when the file does not exist
+fn read_php_return(root: &Path, rel: &str) -> Result<Option<PhpValue>> {
+    let path = root.join(rel);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let source = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", rel))?;
+    parse_php_return(&source).map(Some).with_context(|| format!("No array literal returned by {}", rel))
+}
+
+/// A PHP literal as found in config.php and env.php
+#[derive(Debug, Clone, PartialEq)]
+enum PhpValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Null,
+    /// Entries in order; keys as written, `None` for list entries
+    Array(Vec<(Option<String>, PhpValue)>),
+    /// Constant or expression, as written
+    Other(String),
+}
+
+impl PhpValue {
+    fn get(&self, key: &str) -> Option<&PhpValue> {
+        self.entries().find(|(k, _)| *k == Some(key)).map(|(_, v)| v)
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (Option<&str>, &PhpValue)> {
+        let entries = match self {
+            PhpValue::Array(entries) => entries.as_slice(),
+            _ => &[],
+        };
+        entries.iter().map(|(k, v)| (k.as_deref(), v))
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            PhpValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// PHP truthiness of a scalar (`1`, `true`, `'1'`)
+    fn truthy(&self) -> bool {
+        match self {
+            PhpValue::Int(n) => *n != 0,
+            PhpValue::Float(n) => *n != 0.0,
+            PhpValue::Bool(b) => *b,
+            PhpValue::Str(s) => !s.is_empty() && s != "0",
+            PhpValue::Array(entries) => !entries.is_empty(),
+            PhpValue::Null | PhpValue::Other(_) => false,
+        }
+    }
+
+    /// JSON form; everything under a credential-like key (or below one,
+    /// with `secret`) becomes [`REDACTED`]
+    fn to_json(&self, secret: bool) -> serde_json::Value {
+        use serde_json::Value;
+        match self {
+            PhpValue::Array(entries) if entries.iter().all(|(k, _)| k.is_none()) => {
+                Value::Array(entries.iter().map(|(_, v)| v.to_json(secret)).collect())
+            }
+            PhpValue::Array(entries) => Value::Object(
+                entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (k, v))| {
+                        let key = k.clone().unwrap_or_else(|| i.to_string());
+                        let secret = secret || is_secret_key(&key);
+                        (key, v.to_json(secret))
+                    })
+                    .collect(),
+            ),
+            _ if secret => Value::from(REDACTED),
+            PhpValue::Str(s) | PhpValue::Other(s) => Value::from(s.as_str()),
+            PhpValue::Int(n) => Value::from(*n),
+            PhpValue::Float(n) => Value::from(*n),
+            PhpValue::Bool(b) => Value::from(*b),
+            PhpValue::Null => Value::Null,
+        }
+    }
+}
+
+/// Parse the literal after the first `return` in a PHP file
+fn parse_php_return(source: &str) -> Option<PhpValue> {
+    let start = source.find("return")? + "return".len();
+    let mut parser = PhpParser { src: source.as_bytes(), pos: start };
+    let value = parser.value()?;
+    matches!(value, PhpValue::Array(_)).then_some(value)
+}
+
+/// Recursive descent over PHP array literals (`[...]` and `array(...)`)
+struct PhpParser<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl PhpParser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos).copied()
+    }
+
+    fn rest(&self) -> &[u8] {
+        &self.src[self.pos.min(self.src.len())..]
+    }
+
+    /// Skip whitespace and comments
+    fn skip_space(&mut self) {
+        loop {
+            while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+                self.pos += 1;
+            }
+            if self.rest().starts_with(b"//") || self.rest().starts_with(b"#") {
+                while self.peek().is_some_and(|b| b != b'\n') {
+                    self.pos += 1;
+                }
+            } else if self.rest().starts_with(b"/*") {
+                let end = self.rest()[2..].windows(2).position(|w| w == b"*/").map_or(self.src.len(), |p| self.pos + p + 4);
+                self.pos = end;
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn value(&mut self) -> Option<PhpValue> {
+        self.skip_space();
+        match self.peek()? {
+            b'[' => {
+                self.pos += 1;
+                self.array(b']')
+            }
+            quote @ (b'\'' | b'"') => self.string(quote).map(PhpValue::Str),
+            b'-' | b'0'..=b'9' => Some(self.number()),
+            _ => {
+                let word = self.word();
+                if word.is_empty() {
+                    return None;
+                }
+                self.skip_space();
+                if word.eq_ignore_ascii_case("array") && self.peek() == Some(b'(') {
+                    self.pos += 1;
+                    return self.array(b')');
+                }
+                Some(match word.to_ascii_lowercase().as_str() {
+                    "true" => PhpValue::Bool(true),
+                    "false" => PhpValue::Bool(false),
+                    "null" => PhpValue::Null,
+                    _ => {
+                        // A call such as getenv('X'): keep it as written
+                        let start = self.pos;
+                        if self.peek() == Some(b'(') {
+                            self.skip_balanced();
+                        }
+                        PhpValue::Other(format!("{}{}", word, String::from_utf8_lossy(&self.src[start..self.pos])))
+                    }
+                })
+            }
+        }
+    }
+
+    fn array(&mut self, close: u8) -> Option<PhpValue> {
+        let mut entries = Vec::new();
+        loop {
+            self.skip_space();
+            if self.peek()? == close {
+                self.pos += 1;
+                return Some(PhpValue::Array(entries));
+            }
+            let first = self.value()?;
+            self.skip_space();
+            if self.rest().starts_with(b"=>") {
+                self.pos += 2;
+                let key = match first {
+                    PhpValue::Str(s) | PhpValue::Other(s) => s,
+                    PhpValue::Int(n) => n.to_string(),
+                    other => format!("{:?}", other),
+                };
+                entries.push((Some(key), self.value()?));
+            } else {
+                entries.push((None, first));
+            }
+            self.skip_space();
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b if b == close => {}
+                _ => return None,
+            }
+        }
+    }
+
+    fn string(&mut self, quote: u8) -> Option<String> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let b = self.peek()?;
+            self.pos += 1;
+            match b {
+                b'\\' => {
+                    let next = self.peek()?;
+                    self.pos += 1;
+                    match (quote, next) {
+                        (_, b'\\') => out.push(b'\\'),
+                        (_, n) if n == quote => out.push(n),
+                        (b'"', b'n') => out.push(b'\n'),
+                        (b'"', b't') => out.push(b'\t'),
+                        (b'"', b'$') => out.push(b'$'),
+                        (_, n) => out.extend([b'\\', n]),
+                    }
+                }
+                b if b == quote => return Some(String::from_utf8_lossy(&out).into_owned()),
+                b => out.push(b),
+            }
+        }
+    }
+
+    fn number(&mut self) -> PhpValue {
+        let start = self.pos;
+        self.pos += 1;
+        while self.peek().is_some_and(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'_') {
+            self.pos += 1;
+        }
+        let text = String::from_utf8_lossy(&self.src[start..self.pos]).replace('_', "");
+        text.parse()
+            .map(PhpValue::Int)
+            .or_else(|_| text.parse().map(PhpValue::Float))
+            .unwrap_or(PhpValue::Other(text))
+    }
+
+    /// Identifier, constant or class constant (`\Foo\Bar::BAZ`)
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'\\' | b':' | b'$')) {
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.src[start..self.pos]).into_owned()
+    }
+
+    /// Skip a parenthesized group, strings included
+    fn skip_balanced(&mut self) {
+        let mut depth = 0;
+        while let Some(b) = self.peek() {
+            match b {
+                b'\'' | b'"' => {
+                    self.string(b);
+                    continue;
+                }
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.pos += 1;
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("app/etc")).unwrap();
+        fs::write(
+            dir.path().join(CONFIG_PHP),
+            "<?php\nreturn [\n    'modules' => [\n        'Magento_Store' => 1,\n        'Magento_Msrp' => 0,\n\
+             'Acme_Cart' => true,\n    ],\n    'system' => ['default' => ['web' => ['url' => ['use_store' => '1']]]],\n];\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join(ENV_PHP),
+            "<?php\n// deployment\nreturn array(\n  'MAGE_MODE' => 'production',\n  'crypt' => ['key' => 'abc123'],\n\
+             'db' => ['table_prefix' => 'mg_', 'connection' => ['default' => ['password' => 'hunter2']]],\n\
+             'cache' => ['frontend' => ['default' => ['backend' => 'Magento\\\\Framework\\\\Cache\\\\Backend\\\\Redis',\n\
+             'backend_options' => ['server' => 'redis', 'password' => 's3cret', 'database' => '0']]]],\n\
+             'queue' => ['amqp' => ['host' => 'rabbit', 'user' => 'guest', 'password' => getenv('AMQP_PASS')],\n\
+             'consumers_wait_for_messages' => 0],\n  /* no install date */\n);\n",
+        )
+        .unwrap();
+
+        let info = ProjectInfo::scan(dir.path()).unwrap();
+        assert_eq!(info.is_enabled("Magento_Store"), Some(true));
+        assert_eq!(info.is_enabled("Acme_Cart"), Some(true));
+        assert_eq!(info.is_enabled("Acme_Unknown"), None);
+        assert_eq!(info.disabled_modules(), ["Magento_Msrp"]);
+        assert_eq!(info.mode.as_deref(), Some("production"));
+        assert_eq!(info.table_prefix.as_deref(), Some("mg_"));
+        assert_eq!(info.files, [CONFIG_PHP, ENV_PHP]);
+
+        let cache = info.cache.unwrap();
+        let default = &cache["frontend"]["default"];
+        assert_eq!(default["backend"], "Magento\\Framework\\Cache\\Backend\\Redis");
+        assert_eq!(default["backend_options"]["server"], "redis");
+        assert_eq!(default["backend_options"]["password"], REDACTED);
+        let queue = info.queue.unwrap();
+        assert_eq!(queue["amqp"]["host"], "rabbit");
+        assert_eq!(queue["amqp"]["user"], REDACTED);
+        assert_eq!(queue["amqp"]["password"], REDACTED);
+        assert_eq!(queue["consumers_wait_for_messages"], 0);
+
+        // Nothing outside the kept sections is exposed
+        let json = serde_json::to_string(&ProjectInfo::scan(dir.path()).unwrap()).unwrap();
+        assert!(!json.contains("hunter2") && !json.contains("abc123") && !json.contains("s3cret"));
+    }
+
+    #[test]
+    fn test_module_order() {
+        let decl = |name: &str, dir: &str, sequence: &[&str]| ModuleDecl {
+            name: name.to_string(),
+            dir: dir.to_string(),
+            sequence: sequence.iter().map(|s| s.to_string()).collect(),
+        };
+        let parsed = ModuleDecl::parse(
+            "<config><module name=\"Acme_Fee\"><sequence><module name=\"Magento_Quote\"/></sequence></module></config>",
+            "app/code/Acme/Fee",
+        );
+        assert_eq!(parsed, Some(decl("Acme_Fee", "app/code/Acme/Fee", &["Magento_Quote"])));
+
+        let declared = [
+            decl("Acme_Fee", "app/code/Acme/Fee", &["Magento_Quote"]),
+            decl("Magento_Quote", "vendor/magento/module-quote", &["Magento_Store"]),
+            decl("Magento_Store", "vendor/magento/module-store", &[]),
+            decl("Acme_Base", "app/code/Acme/Base", &[]),
+        ];
+        // No config.php: sequences resolved, ties by name
+        let order = ModuleOrder::from_parts(Vec::new(), &declared);
+        let files = ["app/code/Acme/Fee/etc/sales.xml", "vendor/magento/module-quote/etc/sales.xml", "app/code/Acme/Base/etc/sales.xml"];
+        let positions: Vec<usize> = files.iter().map(|f| order.file_position(f)).collect();
+        assert_eq!(positions, [3, 2, 0]);
+        assert_eq!(order.file_position("app/design/frontend/Acme/theme/requirejs-config.js"), usize::MAX);
+        assert_eq!(order.file_position("app/code/Acme/FeeExtra/etc/sales.xml"), usize::MAX);
+
+        // config.php order wins; unlisted modules follow it
+        let order = ModuleOrder::from_parts(vec!["Magento_Store".into(), "Acme_Fee".into(), "Magento_Quote".into()], &declared);
+        let positions: Vec<usize> = files.iter().map(|f| order.file_position(f)).collect();
+        assert_eq!(positions, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_mark_module_states() {
+        let result = |path: &str, score: f32| {
+            let metadata = crate::vectordb::IndexMetadata {
+                path: path.to_string(),
+                file_type: "php".to_string(),
+                search_text: String::new(),
+                ..Default::default()
+            };
+            SearchResult::new(0, score, metadata)
+        };
+        let results = || {
+            vec![
+                result("vendor/magento/module-msrp/Model/Config.php", 0.9),
+                result("app/code/Acme/Cart/Model/Cart.php", 0.8),
+                result("lib/internal/Magento/Framework/App/Http.php", 0.7),
+            ]
+        };
+        let states = BTreeMap::from([("Magento_Msrp".to_string(), false), ("Acme_Cart".to_string(), true)]);
+
+        let mut marked = results();
+        mark_module_states(&states, &mut marked, 1.0);
+        let enabled: Vec<_> = marked.iter().map(|r| r.module_enabled).collect();
+        assert_eq!(enabled, [Some(false), Some(true), None]);
+        assert_eq!(marked[0].score, 0.9);
+        let json = serde_json::to_value(&marked).unwrap();
+        assert_eq!(json[0]["module_enabled"], false);
+        assert!(json[2].get("module_enabled").is_none());
+
+        let mut demoted = results();
+        mark_module_states(&states, &mut demoted, 0.5);
+        assert_eq!(demoted[0].metadata.path, "app/code/Acme/Cart/Model/Cart.php");
+        assert_eq!(demoted.len(), 3);
+
+        let mut excluded = results();
+        mark_module_states(&states, &mut excluded, 0.0);
+        assert!(excluded.iter().all(|r| r.module_enabled != Some(false)));
+        assert_eq!(excluded.len(), 2);
+    }
+
+    #[test]
+    fn test_missing_and_malformed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(ProjectInfo::scan(dir.path()).unwrap(), ProjectInfo::default());
+
+        fs::create_dir_all(dir.path().join("app/etc")).unwrap();
+        fs::write(dir.path().join(CONFIG_PHP), "<?php\nreturn ['modules' => ['Magento_Store' => 1,\n").unwrap();
+        assert!(ProjectInfo::scan(dir.path()).unwrap_err().to_string().contains(CONFIG_PHP));
+    }
+}
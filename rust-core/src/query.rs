@@ -0,0 +1,125 @@
+//! Query preprocessing before a search
+//!
+//! A [`QueryRewriter`] turns the question a developer typed into the text
+//! that is embedded and matched, e.g. an LLM expanding "where do prices get
+//! calculated" into Magento terms like "price indexer final price model".
+//! [`CommandRewriter`] plugs in any external program (`--rewriter-cmd`):
+//! the query goes to its stdin (and `MAGECTOR_QUERY`), the rewritten query
+//! is read from its stdout.
+
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long [`CommandRewriter`] waits for the command by default
+pub const DEFAULT_REWRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Rewrites a search query. The indexer searches with the original query
+/// when this fails.
+pub trait QueryRewriter: Send + Sync {
+    fn rewrite(&self, query: &str) -> Result<String>;
+}
+
+impl<F> QueryRewriter for F
+where
+    F: Fn(&str) -> Result<String> + Send + Sync,
+{
+    fn rewrite(&self, query: &str) -> Result<String> {
+        self(query)
+    }
+}
+
+/// Rewrites queries with a shell command
+#[derive(Debug, Clone)]
+pub struct CommandRewriter {
+    command: String,
+    timeout: Duration,
+}
+
+impl CommandRewriter {
+    pub fn new(command: &str) -> Self {
+        Self { command: command.to_string(), timeout: DEFAULT_REWRITE_TIMEOUT }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn shell(&self) -> Command {
+        if cfg!(windows) {
+            let mut cmd = Command::new("cmd");
+            cmd.args(["/C", &self.command]);
+            cmd
+        } else {
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", &self.command]);
+            cmd
+        }
+    }
+}
+
+impl QueryRewriter for CommandRewriter {
+    fn rewrite(&self, query: &str) -> Result<String> {
+        let mut child = self
+            .shell()
+            .env("MAGECTOR_QUERY", query)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("Failed to run rewriter {:?}", self.command))?;
+
+        // A command that only reads MAGECTOR_QUERY may exit before stdin is written
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(query.as_bytes());
+        }
+        let mut stdout = child.stdout.take().context("Rewriter stdout not captured")?;
+        let reader = std::thread::spawn(move || {
+            let mut out = String::new();
+            stdout.read_to_string(&mut out).map(|_| out)
+        });
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if start.elapsed() > self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                anyhow::bail!("Rewriter {:?} timed out after {:?}", self.command, self.timeout);
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        };
+        let out = reader.join().map_err(|_| anyhow::anyhow!("Rewriter output reader panicked"))??;
+
+        if !status.success() {
+            anyhow::bail!("Rewriter {:?} failed: {}", self.command, status);
+        }
+        let rewritten = out.trim();
+        if rewritten.is_empty() {
+            anyhow::bail!("Rewriter {:?} printed no query", self.command);
+        }
+        Ok(rewritten.to_string())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_rewriter() {
+        let rewriter = CommandRewriter::new("sed 's/^/magento /'");
+        assert_eq!(rewriter.rewrite("price calculation").unwrap(), "magento price calculation");
+        let from_env = CommandRewriter::new("echo \"$MAGECTOR_QUERY plugin\"");
+        assert_eq!(from_env.rewrite("checkout").unwrap(), "checkout plugin");
+
+        assert!(CommandRewriter::new("exit 3").rewrite("q").is_err());
+        assert!(CommandRewriter::new("cat > /dev/null").rewrite("q").is_err());
+        let slow = CommandRewriter::new("sleep 5").with_timeout(Duration::from_millis(50));
+        assert!(slow.rewrite("q").is_err());
+    }
+}
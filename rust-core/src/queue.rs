@@ -0,0 +1,465 @@
+//! Message queue topology (`magector queues`, serve `queues`)
+//!
+//! Magento spreads a queue route over several files: `communication.xml`
+//! declares topics and their synchronous handlers, `queue_topology.xml` binds
+//! topics to queues through exchanges, `queue_consumer.xml` attaches consumers
+//! (and their handlers) to queues, and `queue_publisher.xml` picks the
+//! connection a topic is published on. The legacy `queue.xml` declares a
+//! whole route per `<broker>`. [`QueueTopology::routes`] joins them into
+//! topic → exchange → queue → consumer → handler chains.
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::indexer::{Indexer, SourceWalk};
+use crate::vectordb::relative_path;
+
+/// Queue config files, by name
+pub const QUEUE_FILES: &[&str] = &[
+    "communication.xml",
+    "queue_topology.xml",
+    "queue_consumer.xml",
+    "queue_publisher.xml",
+    "queue.xml",
+];
+
+/// `<topic>` from communication.xml
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Topic {
+    pub name: String,
+    /// Request data type (class, interface or scalar type)
+    pub request: Option<String>,
+    /// `<handler type method>` as `Class::method`
+    pub handlers: Vec<String>,
+    pub file: String,
+}
+
+/// `<binding>` inside an `<exchange>` in queue_topology.xml
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Binding {
+    pub exchange: String,
+    pub connection: String,
+    /// Topic pattern; AMQP wildcards `*` (one word) and `#` (any words)
+    pub topic: String,
+    pub queue: String,
+    pub file: String,
+}
+
+/// `<consumer>` from queue_consumer.xml
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Consumer {
+    pub name: String,
+    pub queue: String,
+    pub connection: String,
+    /// `Class::method`; when absent, the topic handlers from communication.xml run
+    pub handler: Option<String>,
+    pub consumer_instance: Option<String>,
+    pub file: String,
+}
+
+/// `<publisher>` from queue_publisher.xml
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Publisher {
+    pub topic: String,
+    pub connection: String,
+    pub exchange: String,
+    pub file: String,
+}
+
+/// One topic → queue → consumer chain; parts unknown from config are `None`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QueueRoute {
+    pub topic: Option<String>,
+    pub exchange: Option<String>,
+    pub connection: Option<String>,
+    pub queue: Option<String>,
+    pub consumer: Option<String>,
+    /// Classes that process the message, as `Class::method`
+    pub handlers: Vec<String>,
+    /// Files the route was assembled from
+    pub files: Vec<String>,
+}
+
+impl QueueRoute {
+    fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+        [&self.topic, &self.exchange, &self.queue, &self.consumer]
+            .into_iter()
+            .flatten()
+            .chain(&self.handlers)
+            .any(|part| part.to_lowercase().contains(&filter))
+    }
+}
+
+/// Parsed queue config of a codebase
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueueTopology {
+    pub topics: Vec<Topic>,
+    pub bindings: Vec<Binding>,
+    pub consumers: Vec<Consumer>,
+    pub publishers: Vec<Publisher>,
+    /// Complete routes from legacy queue.xml `<broker>` declarations
+    pub legacy_routes: Vec<QueueRoute>,
+}
+
+struct QueueParser {
+    attr_re: Regex,
+    topic_re: Regex,
+    handler_re: Regex,
+    exchange_re: Regex,
+    binding_re: Regex,
+    consumer_re: Regex,
+    publisher_re: Regex,
+    connection_re: Regex,
+    broker_re: Regex,
+    queue_re: Regex,
+}
+
+impl QueueParser {
+    fn new() -> Self {
+        Self {
+            attr_re: Regex::new(r#"([\w:]+)="([^"]*)""#).unwrap(),
+            topic_re: Regex::new(r#"(?s)<topic\s+([^>]*?)(?:/>|>(.*?)</topic>)"#).unwrap(),
+            handler_re: Regex::new(r#"<handler\s+([^>]*?)/?>"#).unwrap(),
+            exchange_re: Regex::new(r#"(?s)<exchange\s+([^>]*?)(?:/>|>(.*?)</exchange>)"#).unwrap(),
+            binding_re: Regex::new(r#"<binding\s+([^>]*?)/?>"#).unwrap(),
+            consumer_re: Regex::new(r#"<consumer\s+([^>]*?)/?>"#).unwrap(),
+            publisher_re: Regex::new(r#"(?s)<publisher\s+([^>]*?)(?:/>|>(.*?)</publisher>)"#).unwrap(),
+            connection_re: Regex::new(r#"<connection\s+([^>]*?)/?>"#).unwrap(),
+            broker_re: Regex::new(r#"(?s)<broker\s+([^>]*?)(?:/>|>(.*?)</broker>)"#).unwrap(),
+            queue_re: Regex::new(r#"<queue\s+([^>]*?)/?>"#).unwrap(),
+        }
+    }
+
+    fn attrs(&self, text: &str) -> std::collections::HashMap<String, String> {
+        self.attr_re
+            .captures_iter(text)
+            .map(|c| (c[1].to_string(), c[2].to_string()))
+            .collect()
+    }
+
+    fn parse(&self, file_name: &str, content: &str, file: &str, topology: &mut QueueTopology) {
+        match file_name {
+            "communication.xml" => {
+                for caps in self.topic_re.captures_iter(content) {
+                    let attrs = self.attrs(&caps[1]);
+                    let Some(name) = attrs.get("name") else { continue };
+                    let body = caps.get(2).map_or("", |m| m.as_str());
+                    let mut handlers: Vec<String> = self
+                        .handler_re
+                        .captures_iter(body)
+                        .filter_map(|h| {
+                            let h = self.attrs(&h[1]);
+                            let class = h.get("type")?;
+                            Some(format!("{}::{}", class, h.get("method").map_or("execute", String::as_str)))
+                        })
+                        .collect();
+                    // Synchronous topics may point at a service method instead
+                    if let Some(schema) = attrs.get("schema").filter(|s| s.contains("::")) {
+                        handlers.push(schema.clone());
+                    }
+                    topology.topics.push(Topic {
+                        name: name.clone(),
+                        request: attrs.get("request").cloned(),
+                        handlers,
+                        file: file.to_string(),
+                    });
+                }
+            }
+            "queue_topology.xml" => {
+                for caps in self.exchange_re.captures_iter(content) {
+                    let exchange = self.attrs(&caps[1]);
+                    let body = caps.get(2).map_or("", |m| m.as_str());
+                    for binding in self.binding_re.captures_iter(body) {
+                        let binding = self.attrs(&binding[1]);
+                        if binding.get("destinationType").is_some_and(|t| t != "queue") {
+                            continue;
+                        }
+                        topology.bindings.push(Binding {
+                            exchange: exchange.get("name").cloned().unwrap_or_default(),
+                            connection: exchange.get("connection").cloned().unwrap_or_default(),
+                            topic: binding.get("topic").cloned().unwrap_or_default(),
+                            queue: binding.get("destination").cloned().unwrap_or_default(),
+                            file: file.to_string(),
+                        });
+                    }
+                }
+            }
+            "queue_consumer.xml" => {
+                for caps in self.consumer_re.captures_iter(content) {
+                    let attrs = self.attrs(&caps[1]);
+                    let Some(name) = attrs.get("name") else { continue };
+                    topology.consumers.push(Consumer {
+                        name: name.clone(),
+                        queue: attrs.get("queue").cloned().unwrap_or_default(),
+                        connection: attrs.get("connection").cloned().unwrap_or_default(),
+                        handler: attrs.get("handler").cloned(),
+                        consumer_instance: attrs.get("consumerInstance").cloned(),
+                        file: file.to_string(),
+                    });
+                }
+            }
+            "queue_publisher.xml" => {
+                for caps in self.publisher_re.captures_iter(content) {
+                    let attrs = self.attrs(&caps[1]);
+                    let Some(topic) = attrs.get("topic") else { continue };
+                    let body = caps.get(2).map_or("", |m| m.as_str());
+                    // The enabled connection wins; without one, the default is db
+                    let connection = self
+                        .connection_re
+                        .captures_iter(body)
+                        .map(|c| self.attrs(&c[1]))
+                        .find(|c| c.get("disabled").is_none_or(|d| d != "true"));
+                    topology.publishers.push(Publisher {
+                        topic: topic.clone(),
+                        connection: connection
+                            .as_ref()
+                            .and_then(|c| c.get("name").cloned())
+                            .unwrap_or_else(|| "db".to_string()),
+                        exchange: connection
+                            .as_ref()
+                            .and_then(|c| c.get("exchange").cloned())
+                            .unwrap_or_else(|| "magento".to_string()),
+                        file: file.to_string(),
+                    });
+                }
+            }
+            "queue.xml" => {
+                for caps in self.broker_re.captures_iter(content) {
+                    let broker = self.attrs(&caps[1]);
+                    let body = caps.get(2).map_or("", |m| m.as_str());
+                    for queue in self.queue_re.captures_iter(body) {
+                        let queue = self.attrs(&queue[1]);
+                        topology.legacy_routes.push(QueueRoute {
+                            topic: broker.get("topic").cloned(),
+                            exchange: broker.get("exchange").cloned(),
+                            connection: broker.get("type").cloned(),
+                            queue: queue.get("name").cloned(),
+                            consumer: queue.get("consumer").cloned(),
+                            handlers: queue.get("handler").cloned().into_iter().collect(),
+                            files: vec![file.to_string()],
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// AMQP topic match: `*` is exactly one word, `#` zero or more
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    fn matches(pattern: &[&str], topic: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => topic.is_empty(),
+            Some((&"#", rest)) => (0..=topic.len()).any(|skip| matches(rest, &topic[skip..])),
+            Some((&word, rest)) => topic
+                .split_first()
+                .is_some_and(|(t, topic_rest)| (word == "*" || word == *t) && matches(rest, topic_rest)),
+        }
+    }
+    let pattern: Vec<&str> = pattern.split('.').collect();
+    let topic: Vec<&str> = topic.split('.').collect();
+    matches(&pattern, &topic)
+}
+
+impl QueueTopology {
+    /// Parse one queue config file; `file_name` selects the schema
+    pub fn add_file(&mut self, file_name: &str, content: &str, file: &str) {
+        QueueParser::new().parse(file_name, content, file, self);
+    }
+
+    /// Parse every queue config file under `root`, skipping the directories
+    /// indexing skips (tests, generated code, ...)
+    pub fn scan(root: &Path) -> Result<Self> {
+        let parser = QueueParser::new();
+        let mut topology = Self::default();
+        let mut walker = SourceWalk::new(root, false);
+        while let Some(entry) = walker.next() {
+            let entry = entry?;
+            if Indexer::should_skip_dir(&entry, root, false) {
+                walker.skip_current_dir();
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy();
+            if !entry.file_type().is_file() || !QUEUE_FILES.contains(&name.as_ref()) {
+                continue;
+            }
+            // Only module config: etc/ directories
+            let path = entry.path();
+            if path.parent().and_then(|p| p.file_name()).is_none_or(|p| p != "etc") {
+                continue;
+            }
+            if let Ok(content) = std::fs::read_to_string(path) {
+                parser.parse(&name, &content, &relative_path(root, path), &mut topology);
+            }
+        }
+        Ok(topology)
+    }
+
+    /// All routes, optionally only those where a topic, exchange, queue,
+    /// consumer or handler contains `filter` (case-insensitive)
+    pub fn routes(&self, filter: Option<&str>) -> Vec<QueueRoute> {
+        let mut routes = self.legacy_routes.clone();
+        let mut routed_topics = std::collections::HashSet::new();
+        let mut routed_consumers = std::collections::HashSet::new();
+
+        let topic_handlers = |name: &str| -> (Vec<String>, Vec<String>) {
+            self.topics
+                .iter()
+                .filter(|t| t.name == name)
+                .fold((Vec::new(), Vec::new()), |(mut handlers, mut files), t| {
+                    handlers.extend(t.handlers.iter().cloned());
+                    files.push(t.file.clone());
+                    (handlers, files)
+                })
+        };
+
+        for binding in &self.bindings {
+            // Concrete topics the binding pattern covers; the pattern itself if none are declared
+            let mut topics: Vec<String> = self
+                .topics
+                .iter()
+                .filter(|t| topic_matches(&binding.topic, &t.name))
+                .map(|t| t.name.clone())
+                .collect();
+            topics.sort();
+            topics.dedup();
+            if topics.is_empty() {
+                topics.push(binding.topic.clone());
+            }
+            let consumers: Vec<&Consumer> = self.consumers.iter().filter(|c| c.queue == binding.queue).collect();
+
+            for topic in topics {
+                routed_topics.insert(topic.clone());
+                let (handlers, topic_files) = topic_handlers(&topic);
+                let base = QueueRoute {
+                    topic: Some(topic),
+                    exchange: Some(binding.exchange.clone()),
+                    connection: Some(binding.connection.clone()).filter(|c| !c.is_empty()),
+                    queue: Some(binding.queue.clone()),
+                    consumer: None,
+                    handlers: handlers.clone(),
+                    files: topic_files.into_iter().chain([binding.file.clone()]).collect(),
+                };
+                if consumers.is_empty() {
+                    routes.push(base);
+                    continue;
+                }
+                for consumer in &consumers {
+                    routed_consumers.insert(consumer.name.clone());
+                    let mut route = base.clone();
+                    route.consumer = Some(consumer.name.clone());
+                    if let Some(ref handler) = consumer.handler {
+                        route.handlers = vec![handler.clone()];
+                    }
+                    route.files.push(consumer.file.clone());
+                    routes.push(route);
+                }
+            }
+        }
+
+        // Topics without a queue binding (synchronous RPC or misconfigured)
+        for topic in &self.topics {
+            if routed_topics.insert(topic.name.clone()) {
+                let (handlers, files) = topic_handlers(&topic.name);
+                routes.push(QueueRoute {
+                    topic: Some(topic.name.clone()),
+                    handlers,
+                    files,
+                    ..Default::default()
+                });
+            }
+        }
+        // Consumers on queues no binding feeds
+        for consumer in &self.consumers {
+            if routed_consumers.insert(consumer.name.clone()) {
+                routes.push(QueueRoute {
+                    queue: Some(consumer.queue.clone()),
+                    connection: Some(consumer.connection.clone()).filter(|c| !c.is_empty()),
+                    consumer: Some(consumer.name.clone()),
+                    handlers: consumer.handler.iter().cloned().collect(),
+                    files: vec![consumer.file.clone()],
+                    ..Default::default()
+                });
+            }
+        }
+
+        for route in &mut routes {
+            route.files.sort();
+            route.files.dedup();
+        }
+        match filter {
+            Some(filter) if !filter.is_empty() => routes.into_iter().filter(|r| r.matches(filter)).collect(),
+            _ => routes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_matches() {
+        assert!(topic_matches("product_action_attribute.update", "product_action_attribute.update"));
+        assert!(topic_matches("async.#", "async.magento.catalog.save"));
+        assert!(topic_matches("#", "anything.at.all"));
+        assert!(topic_matches("sales.*.created", "sales.order.created"));
+        assert!(!topic_matches("sales.*.created", "sales.order.invoice.created"));
+        assert!(!topic_matches("export", "export.product"));
+    }
+
+    #[test]
+    fn test_queue_routes() {
+        let mut topology = QueueTopology::default();
+        topology.add_file(
+            "communication.xml",
+            r#"<config>
+                <topic name="exportProcessor" request="Magento\ImportExport\Api\Data\ExportInfoInterface">
+                    <handler name="exportProcessor" type="Magento\ImportExport\Model\Export\Consumer" method="process"/>
+                </topic>
+                <topic name="customer.sync" request="string" response="string" schema="Vendor\Api\SyncInterface::sync"/>
+            </config>"#,
+            "app/code/Magento/ImportExport/etc/communication.xml",
+        );
+        topology.add_file(
+            "queue_topology.xml",
+            r#"<config>
+                <exchange name="magento-db" type="topic" connection="db">
+                    <binding id="exportProcessor" topic="exportProcessor" destinationType="queue" destination="export"/>
+                </exchange>
+            </config>"#,
+            "app/code/Magento/ImportExport/etc/queue_topology.xml",
+        );
+        topology.add_file(
+            "queue_consumer.xml",
+            r#"<config>
+                <consumer name="exportProcessor" queue="export" connection="db" maxMessages="100"
+                          consumerInstance="Magento\Framework\MessageQueue\Consumer"
+                          handler="Magento\ImportExport\Model\Export\Consumer::process"/>
+                <consumer name="orphan" queue="nowhere" connection="db"/>
+            </config>"#,
+            "app/code/Magento/ImportExport/etc/queue_consumer.xml",
+        );
+
+        let routes = topology.routes(None);
+        assert_eq!(routes.len(), 3, "{:#?}", routes);
+        let export = &routes[0];
+        assert_eq!(export.topic.as_deref(), Some("exportProcessor"));
+        assert_eq!(export.exchange.as_deref(), Some("magento-db"));
+        assert_eq!(export.queue.as_deref(), Some("export"));
+        assert_eq!(export.consumer.as_deref(), Some("exportProcessor"));
+        assert_eq!(export.handlers, vec!["Magento\\ImportExport\\Model\\Export\\Consumer::process"]);
+        assert_eq!(export.files.len(), 3);
+
+        assert_eq!(routes[1].handlers, vec!["Vendor\\Api\\SyncInterface::sync"]);
+        assert_eq!(routes[2].consumer.as_deref(), Some("orphan"));
+
+        let filtered = topology.routes(Some("EXPORT"));
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].consumer.as_deref(), Some("exportProcessor"));
+    }
+}
@@ -0,0 +1,260 @@
+//! Controller routes (`etc/frontend/routes.xml`, `etc/adminhtml/routes.xml`)
+//!
+//! A route gives a front name to one or more modules; a request for
+//! `/frontName/controller/action` runs `Controller/Controller/Action.php` in
+//! the first of those modules that has it. [`RouteRegistry`] turns that
+//! around: for a controller file it returns the URLs that reach it, and
+//! [`RouteRegistry::resolve_url`] goes the other way. Admin
+//! URLs are given under the default `/admin` area path, which a project can
+//! change in env.php (`backend/frontName`).
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::magento::{admin_controller_action, module_names_for_path};
+
+/// Area path admin URLs are given under
+pub const ADMIN_PATH: &str = "admin";
+
+/// Route, controller and action a URL path dispatches to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RouteMatch {
+    pub route: Route,
+    /// Controller segment (`product_compare`), `index` when absent
+    pub controller: String,
+    /// Action segment, `index` when absent
+    pub action: String,
+}
+
+impl RouteMatch {
+    /// Layout handle of the page (`catalog_product_view`)
+    pub fn full_action_name(&self) -> String {
+        format!("{}_{}_{}", self.route.id, self.controller, self.action).to_lowercase()
+    }
+
+    /// URL of the action (`/catalog/product/view`, `/admin/sales/order/index`)
+    pub fn url(&self) -> String {
+        let prefix = if self.route.area == "adminhtml" { format!("/{}", ADMIN_PATH) } else { String::new() };
+        format!("{}/{}/{}/{}", prefix, self.route.front_name, self.controller, self.action)
+    }
+
+    /// Controller class the route's `module` would serve the request with
+    /// (`Magento_Catalog` → `Magento\Catalog\Controller\Product\View`)
+    pub fn controller_class(&self, module: &str) -> String {
+        let ucfirst = |s: &str| {
+            let mut chars = s.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+        };
+        let mut parts: Vec<String> = module.split('_').map(str::to_string).collect();
+        parts.push("Controller".to_string());
+        if self.route.area == "adminhtml" {
+            parts.push("Adminhtml".to_string());
+        }
+        parts.extend(self.controller.split('_').map(ucfirst));
+        parts.push(ucfirst(&self.action));
+        parts.join("\\")
+    }
+}
+
+/// `<route>` of a routes.xml, merged across every file declaring it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Route {
+    /// `frontend` or `adminhtml`
+    pub area: String,
+    pub id: String,
+    pub front_name: String,
+    /// Modules whose controllers the route serves, as declared
+    /// (`Magento_Catalog`)
+    pub modules: Vec<String>,
+    /// routes.xml files declaring or extending the route
+    pub files: Vec<String>,
+}
+
+/// Routes of every routes.xml
+#[derive(Debug, Clone, Default)]
+pub struct RouteRegistry {
+    routes: Vec<Route>,
+}
+
+/// Whether `path` is a routes.xml
+pub fn is_routes_xml(path: &str) -> bool {
+    path.ends_with("etc/frontend/routes.xml") || path.ends_with("etc/adminhtml/routes.xml")
+}
+
+/// Area, controller and action served by a controller file
+/// (`Controller/Product/Compare/Add.php` → `frontend`, `product_compare`,
+/// `add`); `None` outside a `Controller/<Name>/` directory
+pub fn controller_action(path: &str) -> Option<(&'static str, String, String)> {
+    if let Some(key) = admin_controller_action(path) {
+        let (controller, action) = key.split_once('/')?;
+        return Some(("adminhtml", controller.to_string(), action.to_string()));
+    }
+    let rest = path.split_once("/Controller/")?.1.strip_suffix(".php")?;
+    let (controller, action) = rest.rsplit_once('/')?;
+    Some(("frontend", controller.replace('/', "_").to_lowercase(), action.to_lowercase()))
+}
+
+impl RouteRegistry {
+    /// Routes declared in a routes.xml; the area comes from its path
+    pub fn add_routes_xml(&mut self, content: &str, file: &str) {
+        let area = if file.ends_with("etc/adminhtml/routes.xml") { "adminhtml" } else { "frontend" };
+        let route_re = Regex::new(r#"(?s)<route\s+([^>]*?)(?:/>|>(.*?)</route>)"#).unwrap();
+        let attr_re = Regex::new(r#"([\w:]+)="([^"]*)""#).unwrap();
+        let module_re = Regex::new(r#"<module\s+[^>]*?name="([^"]+)""#).unwrap();
+        for caps in route_re.captures_iter(content) {
+            let attr = |key: &str| attr_re.captures_iter(&caps[1]).find(|a| &a[1] == key).map(|a| a[2].to_string());
+            let Some(id) = attr("id") else { continue };
+            let index = match self.routes.iter().position(|r| r.area == area && r.id == id) {
+                Some(index) => index,
+                None => {
+                    self.routes.push(Route { area: area.to_string(), id: id.clone(), ..Default::default() });
+                    self.routes.len() - 1
+                }
+            };
+            let route = &mut self.routes[index];
+            if let Some(front_name) = attr("frontName") {
+                route.front_name = front_name;
+            }
+            for module in module_re.captures_iter(caps.get(2).map_or("", |m| m.as_str())) {
+                if !route.modules.iter().any(|m| m == &module[1]) {
+                    route.modules.push(module[1].to_string());
+                }
+            }
+            if !route.files.iter().any(|f| f == file) {
+                route.files.push(file.to_string());
+            }
+        }
+    }
+
+    /// Routes of `area` serving controllers of the module at `path`
+    fn routes_for_module<'a>(&'a self, area: &'a str, path: &str) -> impl Iterator<Item = &'a Route> + 'a {
+        let names = module_names_for_path(path);
+        self.routes.iter().filter(move |route| {
+            route.area == area
+                && !route.front_name.is_empty()
+                && route.modules.iter().any(|m| names.iter().any(|n| n.eq_ignore_ascii_case(m)))
+        })
+    }
+
+    /// URLs reaching the controller file at `path`
+    /// (`/catalog/product/view`, `/admin/sales/order_create/index`)
+    pub fn controller_urls(&self, path: &str) -> Vec<String> {
+        let Some((area, controller, action)) = controller_action(path) else {
+            return Vec::new();
+        };
+        let prefix = if area == "adminhtml" { format!("/{}", ADMIN_PATH) } else { String::new() };
+        let mut urls: Vec<String> = self
+            .routes_for_module(area, path)
+            .map(|route| format!("{}/{}/{}/{}", prefix, route.front_name, controller, action))
+            .collect();
+        urls.dedup();
+        urls
+    }
+
+    /// Route a URL path (`/catalog/product/view/id/5`,
+    /// `/admin/sales/order/index`) dispatches to through the standard
+    /// router. Query strings and trailing parameters are ignored; URL
+    /// rewrites and CMS pages are not resolved.
+    pub fn resolve_url(&self, url: &str) -> Option<RouteMatch> {
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        let mut segments = path.split('/').filter(|s| !s.is_empty());
+        let mut front_name = segments.next()?;
+        let mut area = "frontend";
+        if front_name == ADMIN_PATH {
+            area = "adminhtml";
+            front_name = segments.next().unwrap_or(ADMIN_PATH);
+        }
+        let route = self.routes.iter().find(|r| r.area == area && r.front_name.eq_ignore_ascii_case(front_name))?;
+        Some(RouteMatch {
+            route: route.clone(),
+            controller: segments.next().unwrap_or("index").to_lowercase(),
+            action: segments.next().unwrap_or("index").to_lowercase(),
+        })
+    }
+
+    /// Actions of `area` whose full action name is the layout handle
+    /// `handle` (`sales_order_create_index`); one per route whose id
+    /// prefixes the handle
+    pub fn resolve_handle(&self, area: &str, handle: &str) -> Vec<RouteMatch> {
+        self.routes
+            .iter()
+            .filter(|route| route.area == area && !route.front_name.is_empty())
+            .filter_map(|route| {
+                let rest = handle.strip_prefix(route.id.as_str())?.strip_prefix('_')?;
+                let (controller, action) = rest.rsplit_once('_')?;
+                Some(RouteMatch { route: route.clone(), controller: controller.to_string(), action: action.to_string() })
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_controller_urls() {
+        let mut registry = RouteRegistry::default();
+        registry.add_routes_xml(
+            r#"<config><router id="standard">
+                <route id="catalog" frontName="catalog"><module name="Magento_Catalog" /></route>
+            </router></config>"#,
+            "vendor/magento/module-catalog/etc/frontend/routes.xml",
+        );
+        registry.add_routes_xml(
+            r#"<config><router id="admin">
+                <route id="catalog" frontName="catalog">
+                    <module name="Magento_Catalog" before="Magento_Backend" />
+                </route>
+            </router></config>"#,
+            "vendor/magento/module-catalog/etc/adminhtml/routes.xml",
+        );
+        // Another module adding controllers to the route
+        registry.add_routes_xml(
+            r#"<config><router id="standard">
+                <route id="catalog"><module name="Acme_Compare" before="Magento_Catalog"/></route>
+            </router></config>"#,
+            "app/code/Acme/Compare/etc/frontend/routes.xml",
+        );
+
+        assert_eq!(
+            registry.controller_urls("vendor/magento/module-catalog/Controller/Product/View.php"),
+            ["/catalog/product/view"]
+        );
+        assert_eq!(
+            registry.controller_urls("vendor/magento/module-catalog/Controller/Adminhtml/Product/Attribute/Edit.php"),
+            ["/admin/catalog/product_attribute/edit"]
+        );
+        assert_eq!(
+            registry.controller_urls("app/code/Acme/Compare/Controller/Product/Compare/Add.php"),
+            ["/catalog/product_compare/add"]
+        );
+        // Abstract base controllers sit directly in Controller/
+        assert!(registry.controller_urls("vendor/magento/module-catalog/Controller/Product.php").is_empty());
+        assert!(registry.controller_urls("vendor/magento/module-checkout/Controller/Cart/Add.php").is_empty());
+        assert_eq!(registry.routes[0].files.len(), 2);
+
+        let page = registry.resolve_url("/catalog/product_compare/add/product/5?uenc=x").unwrap();
+        assert_eq!(page.full_action_name(), "catalog_product_compare_add");
+        assert_eq!(page.controller_class("Acme_Compare"), "Acme\\Compare\\Controller\\Product\\Compare\\Add");
+        let admin = registry.resolve_url("/admin/catalog/product/edit/id/1/").unwrap();
+        assert_eq!(admin.route.area, "adminhtml");
+        assert_eq!(
+            admin.controller_class("Magento_Catalog"),
+            "Magento\\Catalog\\Controller\\Adminhtml\\Product\\Edit"
+        );
+        let index = registry.resolve_url("catalog").unwrap();
+        assert_eq!((index.controller.as_str(), index.action.as_str()), ("index", "index"));
+        assert!(registry.resolve_url("/checkout/cart/add").is_none());
+        assert!(registry.resolve_url("/").is_none());
+
+        let actions = registry.resolve_handle("adminhtml", "catalog_product_attribute_edit");
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].url(), "/admin/catalog/product_attribute/edit");
+        assert!(registry.resolve_handle("adminhtml", "catalog_product").is_empty());
+    }
+}
@@ -0,0 +1,404 @@
+//! Semantic diff of two files (`magector semdiff`)
+//!
+//! A textual diff of a vendor file across an upgrade mixes reformatting,
+//! reworded comments and real changes. `semdiff` splits both versions into
+//! chunks (each PHP method, plus everything outside methods; other files
+//! are one chunk) and pairs them by name. A pair whose code is the same once
+//! comments and whitespace are dropped changed only cosmetically. Other
+//! pairs are embedded: below the similarity threshold the change is
+//! semantic, at or above it minor (renamed variables, small tweaks).
+//! Chunks only in the new file are matched against those only in the old
+//! one, and a close enough match counts as a rename.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::ast::PhpAstAnalyzer;
+
+/// Default similarity at or above which a code change counts as minor
+pub const DEFAULT_THRESHOLD: f32 = 0.97;
+
+/// Name of the chunk with the code of a PHP file outside its methods
+pub const OUTSIDE_METHODS: &str = "(outside methods)";
+
+/// Name of the single chunk of a file that isn't split
+pub const WHOLE_FILE: &str = "(file)";
+
+/// A named part of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub name: String,
+    pub text: String,
+    /// 1-based first and last line
+    pub lines: (usize, usize),
+}
+
+/// How a chunk changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Unchanged,
+    /// Only comments or whitespace differ
+    Cosmetic,
+    /// Code differs, but embeds at or above the threshold
+    Minor,
+    /// Code differs and embeds below the threshold
+    Semantic,
+    /// Only in the new file, and like a chunk only in the old one
+    Renamed,
+    Added,
+    Removed,
+}
+
+impl Change {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Change::Unchanged => "unchanged",
+            Change::Cosmetic => "cosmetic",
+            Change::Minor => "minor",
+            Change::Semantic => "semantic",
+            Change::Renamed => "renamed",
+            Change::Added => "added",
+            Change::Removed => "removed",
+        }
+    }
+}
+
+/// Outcome for one chunk
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChunkDiff {
+    pub name: String,
+    pub change: Change,
+    /// Name in the old file of a renamed chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_name: Option<String>,
+    /// Cosine similarity of the two versions, when they were embedded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_lines: Option<(usize, usize)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_lines: Option<(usize, usize)>,
+}
+
+/// Outcome of [`diff`]
+#[derive(Debug, Clone, Serialize)]
+pub struct SemanticDiff {
+    pub old: String,
+    pub new: String,
+    pub threshold: f32,
+    /// Semantic changes first (least similar first), then renames, additions,
+    /// removals, minor and cosmetic changes, and unchanged chunks
+    pub chunks: Vec<ChunkDiff>,
+}
+
+impl SemanticDiff {
+    /// Number of chunks with `change`
+    pub fn count(&self, change: Change) -> usize {
+        self.chunks.iter().filter(|c| c.change == change).count()
+    }
+}
+
+/// Split `source` into chunks; `path` decides how
+pub fn chunks(path: &str, source: &str) -> Result<Vec<Chunk>> {
+    let lines = source.lines().count().max(1);
+    if !path.ends_with(".php") {
+        return Ok(vec![Chunk { name: WHOLE_FILE.to_string(), text: source.to_string(), lines: (1, lines) }]);
+    }
+    let mut analyzer = PhpAstAnalyzer::new().map_err(|e| anyhow::anyhow!("PHP parser init failed: {}", e))?;
+    let spans = analyzer.member_spans(source);
+    let mut outside = String::new();
+    let mut at = 0;
+    let mut chunks = Vec::new();
+    for span in spans {
+        outside.push_str(&source[at..span.start_byte]);
+        at = span.end_byte;
+        // A name declared twice (e.g. in two classes of one file) keeps both
+        let name = match chunks.iter().filter(|c: &&Chunk| c.name == span.name).count() {
+            0 => span.name,
+            n => format!("{}#{}", span.name, n + 1),
+        };
+        let text = source[span.start_byte..span.end_byte].to_string();
+        chunks.push(Chunk { name, text, lines: (span.line, span.end_line) });
+    }
+    outside.push_str(&source[at..]);
+    chunks.insert(0, Chunk { name: OUTSIDE_METHODS.to_string(), text: outside, lines: (1, lines) });
+    Ok(chunks)
+}
+
+/// Code of `text` without comments and whitespace, for telling cosmetic
+/// changes apart. PHP and JS comments are dropped outside string literals;
+/// other files only lose their whitespace.
+pub fn normalized(path: &str, text: &str) -> String {
+    let php = path.ends_with(".php");
+    let code = php || path.ends_with(".js");
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut quote: Option<char> = None;
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            out.push(c);
+            if c == '\\' {
+                out.extend(chars.next());
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        if code {
+            match (c, chars.peek().copied()) {
+                ('\'' | '"' | '`', _) => {
+                    quote = Some(c);
+                    out.push(c);
+                    continue;
+                }
+                // `#[...]` is a PHP attribute, not a comment
+                ('/', Some('/')) | ('#', _) if c == '/' || (php && chars.peek() != Some(&'[')) => {
+                    for next in chars.by_ref() {
+                        if next == '\n' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                ('/', Some('*')) => {
+                    chars.next();
+                    let mut last = ' ';
+                    for next in chars.by_ref() {
+                        if last == '*' && next == '/' {
+                            break;
+                        }
+                        last = next;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        if !c.is_whitespace() {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Compare two versions of the file at `path`, each given as
+/// `(label, source)`. `embed` returns a vector per text.
+pub fn diff(
+    path: &str,
+    old: (&str, &str),
+    new: (&str, &str),
+    threshold: f32,
+    mut embed: impl FnMut(&[&str]) -> Result<Vec<Vec<f32>>>,
+) -> Result<SemanticDiff> {
+    let (old_label, old_source) = old;
+    let (new_label, new_source) = new;
+    let old_chunks = chunks(path, old_source)?;
+    let new_chunks = chunks(path, new_source)?;
+
+    let mut results = Vec::new();
+    // Pairs of (old, new) chunk indexes whose code changed
+    let mut changed: Vec<(usize, usize)> = Vec::new();
+    let mut added: Vec<usize> = Vec::new();
+    for (n, chunk) in new_chunks.iter().enumerate() {
+        match old_chunks.iter().position(|o| o.name == chunk.name) {
+            Some(o) if old_chunks[o].text == chunk.text => results.push(pair_diff(&old_chunks[o], chunk, Change::Unchanged, None)),
+            Some(o) if normalized(path, &old_chunks[o].text) == normalized(path, &chunk.text) => {
+                results.push(pair_diff(&old_chunks[o], chunk, Change::Cosmetic, None))
+            }
+            Some(o) => changed.push((o, n)),
+            None => added.push(n),
+        }
+    }
+    let mut removed: Vec<usize> =
+        (0..old_chunks.len()).filter(|&o| !new_chunks.iter().any(|n| n.name == old_chunks[o].name)).collect();
+
+    // One embedding batch: changed pairs, then added, then removed chunks
+    let mut texts: Vec<&str> = Vec::new();
+    for &(o, n) in &changed {
+        texts.push(&old_chunks[o].text);
+        texts.push(&new_chunks[n].text);
+    }
+    texts.extend(added.iter().map(|&n| new_chunks[n].text.as_str()));
+    texts.extend(removed.iter().map(|&o| old_chunks[o].text.as_str()));
+    let vectors = if texts.is_empty() { Vec::new() } else { embed(&texts)? };
+    if vectors.len() != texts.len() {
+        bail!("Embedding returned {} vectors for {} chunks", vectors.len(), texts.len());
+    }
+
+    for (i, &(o, n)) in changed.iter().enumerate() {
+        let similarity = cosine(&vectors[2 * i], &vectors[2 * i + 1]);
+        let change = if similarity >= threshold { Change::Minor } else { Change::Semantic };
+        results.push(pair_diff(&old_chunks[o], &new_chunks[n], change, Some(similarity)));
+    }
+    let added_vectors = &vectors[2 * changed.len()..2 * changed.len() + added.len()];
+    let removed_vectors = &vectors[2 * changed.len() + added.len()..];
+    let mut renamed_from: Vec<Option<usize>> = vec![None; removed.len()];
+    for (a, &n) in added.iter().enumerate() {
+        let best = (0..removed.len())
+            .filter(|&r| renamed_from[r].is_none())
+            .map(|r| (r, cosine(&added_vectors[a], &removed_vectors[r])))
+            .max_by(|x, y| x.1.total_cmp(&y.1))
+            .filter(|(_, similarity)| *similarity >= threshold);
+        let chunk = &new_chunks[n];
+        match best {
+            Some((r, similarity)) => {
+                renamed_from[r] = Some(n);
+                let old = &old_chunks[removed[r]];
+                results.push(ChunkDiff {
+                    old_name: Some(old.name.clone()),
+                    ..pair_diff(old, chunk, Change::Renamed, Some(similarity))
+                });
+            }
+            None => results.push(ChunkDiff {
+                name: chunk.name.clone(),
+                change: Change::Added,
+                old_name: None,
+                similarity: None,
+                old_lines: None,
+                new_lines: Some(chunk.lines),
+            }),
+        }
+    }
+    let mut kept = renamed_from.iter().map(Option::is_none);
+    removed.retain(|_| kept.next().unwrap_or(true));
+    for o in removed {
+        let chunk = &old_chunks[o];
+        results.push(ChunkDiff {
+            name: chunk.name.clone(),
+            change: Change::Removed,
+            old_name: None,
+            similarity: None,
+            old_lines: Some(chunk.lines),
+            new_lines: None,
+        });
+    }
+
+    let rank = |c: &ChunkDiff| match c.change {
+        Change::Semantic => 0,
+        Change::Renamed => 1,
+        Change::Added => 2,
+        Change::Removed => 3,
+        Change::Minor => 4,
+        Change::Cosmetic => 5,
+        Change::Unchanged => 6,
+    };
+    results.sort_by(|a, b| {
+        rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.similarity.unwrap_or(1.0).total_cmp(&b.similarity.unwrap_or(1.0)))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(SemanticDiff { old: old_label.to_string(), new: new_label.to_string(), threshold, chunks: results })
+}
+
+fn pair_diff(old: &Chunk, new: &Chunk, change: Change, similarity: Option<f32>) -> ChunkDiff {
+    ChunkDiff {
+        name: new.name.clone(),
+        change,
+        old_name: None,
+        similarity,
+        old_lines: Some(old.lines),
+        new_lines: Some(new.lines),
+    }
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    match norm(a) * norm(b) {
+        n if n > 0.0 => dot / n,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = r#"<?php
+class Cart
+{
+    // Sum of the item prices
+    public function total()
+    {
+        return array_sum($this->prices);
+    }
+
+    public function count() { return count($this->items); }
+
+    public function legacyTax($rate)
+    {
+        return $this->total() * $rate;
+    }
+
+    public function clear()
+    {
+        $this->items = [];
+    }
+}
+"#;
+
+    const NEW: &str = r#"<?php
+class Cart
+{
+    /** Sum of all item prices */
+    public function total()
+    {
+        return array_sum( $this->prices );
+    }
+
+    public function count() { return count($this->items) + 1; }
+
+    public function taxFor($rate)
+    {
+        return $this->total() * $rate;
+    }
+
+    public function url() { return 'http://example.com'; }
+}
+"#;
+
+    /// Texts embed alike when they share their last 20 characters
+    fn fake_embed(texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts
+            .iter()
+            .map(|t| {
+                let tail: u32 = t.chars().rev().take(20).map(|c| c as u32).sum();
+                let mut v = vec![0.0f32; 64];
+                v[tail as usize % 64] = 1.0;
+                v
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_semantic_diff() {
+        let diff = diff("Model/Cart.php", ("old", OLD), ("new", NEW), DEFAULT_THRESHOLD, fake_embed).unwrap();
+        let change = |name: &str| diff.chunks.iter().find(|c| c.name == name).map(|c| c.change);
+        assert_eq!(change(OUTSIDE_METHODS), Some(Change::Cosmetic));
+        assert_eq!(change("Cart::total"), Some(Change::Cosmetic));
+        assert_eq!(change("Cart::count"), Some(Change::Semantic));
+        assert_eq!(change("Cart::taxFor"), Some(Change::Renamed));
+        assert_eq!(change("Cart::url"), Some(Change::Added));
+        assert_eq!(change("Cart::clear"), Some(Change::Removed));
+        assert_eq!(change("Cart::legacyTax"), None);
+        let renamed = diff.chunks.iter().find(|c| c.change == Change::Renamed).unwrap();
+        assert_eq!(renamed.old_name.as_deref(), Some("Cart::legacyTax"));
+        assert_eq!((renamed.old_lines, renamed.new_lines), (Some((12, 15)), Some((12, 15))));
+        assert_eq!(diff.chunks[0].name, "Cart::count");
+        assert_eq!(diff.count(Change::Cosmetic), 2);
+
+        let same = super::diff("a.xml", ("a", "<a>\n  <b/>\n</a>"), ("b", "<a><b/></a>"), 0.9, fake_embed).unwrap();
+        assert_eq!(same.chunks.len(), 1);
+        assert_eq!((same.chunks[0].name.as_str(), same.chunks[0].change), (WHOLE_FILE, Change::Cosmetic));
+    }
+
+    #[test]
+    fn test_normalized_keeps_strings() {
+        assert_eq!(normalized("a.php", "$a = 1; // one\n# two\n$b /* x */ = 2;"), "$a=1;$b=2;");
+        assert_ne!(normalized("a.php", "$u = 'http://a';"), normalized("a.php", "$u = 'http://b';"));
+        assert_eq!(normalized("a.php", "#[Attr]\nclass A {}"), "#[Attr]classA{}");
+        assert_eq!(normalized("a.php", r#"$s = "it\"s // not a comment";"#), r#"$s="it\"s // not a comment";"#);
+    }
+}
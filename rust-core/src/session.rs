@@ -0,0 +1,138 @@
+//! Session-scoped search boosts (serve `session_open` / `session_end`)
+//!
+//! Within one MCP session the files a user opens say what they are working
+//! on: after reading `Magento_Checkout`'s payment information model, "save
+//! payment" most likely means the checkout one. Serve remembers the modules
+//! of the last few files a session opened and nudges results from them up
+//! by [`SESSION_BOOST`] in that session's searches. Unlike SONA learning
+//! nothing is saved: the boosts live in memory, go away when the session
+//! ends and lapse after [`SESSION_TTL`] without use.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::magento::extract_module_info;
+use crate::vectordb::SearchResult;
+
+/// Score added to results from a module the session opened a file in
+pub const SESSION_BOOST: f32 = 0.05;
+
+/// Modules remembered per session, most recently opened first
+const MAX_MODULES: usize = 5;
+
+/// Idle time after which a session that never ended is dropped
+pub const SESSION_TTL: Duration = Duration::from_secs(2 * 60 * 60);
+
+#[derive(Debug)]
+struct Session {
+    modules: VecDeque<String>,
+    touched: Instant,
+}
+
+/// Open sessions by id
+#[derive(Debug, Default)]
+pub struct Sessions {
+    sessions: HashMap<String, Session>,
+}
+
+impl Sessions {
+    /// Record that `session` opened `path`; returns the session's modules,
+    /// most recent first. Paths outside a module leave them as they are.
+    pub fn open_file(&mut self, session: &str, path: &str) -> Vec<String> {
+        self.prune();
+        let entry = self.sessions.entry(session.to_string()).or_insert_with(|| Session {
+            modules: VecDeque::new(),
+            touched: Instant::now(),
+        });
+        entry.touched = Instant::now();
+        if let Some(module) = extract_module_info(path).map(|m| m.full) {
+            entry.modules.retain(|m| m != &module);
+            entry.modules.push_front(module);
+            entry.modules.truncate(MAX_MODULES);
+        }
+        entry.modules.iter().cloned().collect()
+    }
+
+    /// Forget `session`; false when it was not open
+    pub fn end(&mut self, session: &str) -> bool {
+        self.sessions.remove(session).is_some()
+    }
+
+    /// Modules `session` opened files in, most recent first
+    pub fn modules(&self, session: &str) -> Vec<String> {
+        self.sessions.get(session).map(|s| s.modules.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Raise results from `session`'s modules by [`SESSION_BOOST`] and
+    /// re-sort; returns how many were raised
+    pub fn boost(&mut self, session: &str, results: &mut [SearchResult]) -> usize {
+        self.prune();
+        let Some(entry) = self.sessions.get_mut(session) else { return 0 };
+        entry.touched = Instant::now();
+        let mut boosted = 0;
+        for result in results.iter_mut() {
+            if result.metadata.module.as_ref().is_some_and(|m| entry.modules.contains(m)) {
+                result.score += SESSION_BOOST;
+                boosted += 1;
+            }
+        }
+        if boosted > 0 {
+            results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        }
+        boosted
+    }
+
+    /// Drop sessions idle for longer than [`SESSION_TTL`]
+    fn prune(&mut self) {
+        self.sessions.retain(|_, s| s.touched.elapsed() < SESSION_TTL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IndexMetadata;
+
+    fn result(id: usize, score: f32, module: &str) -> SearchResult {
+        SearchResult::new(
+            id,
+            score,
+            IndexMetadata {
+                path: format!("app/code/{}/File{}.php", module.replace('_', "/"), id),
+                file_type: "php".to_string(),
+                module: Some(module.to_string()),
+                search_text: String::new(),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_session_boost() {
+        let mut sessions = Sessions::default();
+        let modules = sessions.open_file("s1", "app/code/Magento/Checkout/Model/PaymentInformationManagement.php");
+        assert_eq!(modules, ["Magento_Checkout"]);
+        // Files outside a module change nothing
+        assert_eq!(sessions.open_file("s1", "app/etc/di.xml"), ["Magento_Checkout"]);
+
+        let mut results = vec![result(1, 0.70, "Magento_Quote"), result(2, 0.67, "Magento_Checkout")];
+        assert_eq!(sessions.boost("s1", &mut results), 1);
+        assert_eq!(results[0].id, 2);
+        assert!((results[0].score - 0.72).abs() < 1e-6);
+
+        // Other sessions are unaffected
+        let mut other = vec![result(1, 0.70, "Magento_Quote"), result(2, 0.67, "Magento_Checkout")];
+        assert_eq!(sessions.boost("s2", &mut other), 0);
+        assert_eq!(other[0].id, 1);
+
+        // Only the last few modules are kept
+        for name in ["A", "B", "C", "D", "E"] {
+            sessions.open_file("s1", &format!("app/code/Acme/{}/Model/X.php", name));
+        }
+        assert_eq!(sessions.modules("s1"), ["Acme_E", "Acme_D", "Acme_C", "Acme_B", "Acme_A"]);
+
+        assert!(sessions.end("s1"));
+        assert!(!sessions.end("s1"));
+        assert!(sessions.modules("s1").is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+//! Shipping carrier registry (`magector carriers`, serve `carriers`)
+//!
+//! Carriers are declared in `etc/config.xml` under `<default><carriers><code>`
+//! with the model class that implements `collectRates()`, plus the title,
+//! method name and defaults shown in the admin. Modules override single
+//! fields of a carrier they don't own (`active`, `price`, ...), so the
+//! registry merges every declaration per code. The indexer uses it to tag
+//! carrier models, so shipping queries rank them above the blocks and
+//! templates that display rates.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A carrier joined from every config.xml declaring it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Carrier {
+    pub code: String,
+    /// Carrier title shown at checkout (`<title>`)
+    pub title: Option<String>,
+    /// Method name shown next to the rate (`<name>`)
+    pub name: Option<String>,
+    /// Class collecting the rates
+    pub model: Option<String>,
+    pub active: Option<bool>,
+    pub sort_order: Option<i64>,
+    /// Other scalar defaults (`price`, `type`, `handling_type`, ...)
+    pub config: BTreeMap<String, String>,
+    pub files: Vec<String>,
+}
+
+/// Carriers from every config.xml, by code
+#[derive(Debug, Clone, Default)]
+pub struct CarrierRegistry {
+    carriers: BTreeMap<String, Carrier>,
+}
+
+impl CarrierRegistry {
+    /// Carriers declared under `<default><carriers>` in a config.xml
+    pub fn add_config_xml(&mut self, content: &str, file: &str) {
+        for (code, fields) in crate::magento::config_section_entries(content, "carriers") {
+            let carrier = self.carriers.entry(code.clone()).or_insert_with(|| Carrier {
+                code,
+                ..Default::default()
+            });
+            for (field, value) in fields {
+                match field.as_str() {
+                    "model" if !value.is_empty() => carrier.model = Some(value.trim_start_matches('\\').to_string()),
+                    "title" if !value.is_empty() => carrier.title = Some(value),
+                    "name" if !value.is_empty() => carrier.name = Some(value),
+                    "active" => carrier.active = Some(value == "1" || value == "true"),
+                    "sort_order" => carrier.sort_order = value.parse().ok(),
+                    _ => {
+                        carrier.config.insert(field, value);
+                    }
+                }
+            }
+            carrier.files.push(file.to_string());
+        }
+    }
+
+    /// Carriers with a model, sorted by code, optionally only those whose
+    /// code, title, name or model contains `filter` (case-insensitive)
+    pub fn carriers(&self, filter: Option<&str>) -> Vec<Carrier> {
+        let filter = filter.filter(|f| !f.is_empty()).map(str::to_lowercase);
+        self.carriers
+            .values()
+            .filter(|c| c.model.is_some())
+            .filter(|c| {
+                let Some(ref filter) = filter else { return true };
+                [Some(&c.code), c.title.as_ref(), c.name.as_ref(), c.model.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .any(|part| part.to_lowercase().contains(filter))
+            })
+            .map(|c| {
+                let mut carrier = c.clone();
+                carrier.files.sort();
+                carrier.files.dedup();
+                carrier
+            })
+            .collect()
+    }
+
+    /// Carriers whose model is `class`
+    pub fn carriers_for_class(&self, class: &str) -> Vec<&Carrier> {
+        let class = class.trim_start_matches('\\');
+        self.carriers.values().filter(|c| c.model.as_deref() == Some(class)).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.carriers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_carrier_registry() {
+        let mut registry = CarrierRegistry::default();
+        registry.add_config_xml(
+            r#"<config>
+                <default>
+                    <carriers>
+                        <flatrate>
+                            <active>1</active>
+                            <sallowspecific>0</sallowspecific>
+                            <model>Magento\OfflineShipping\Model\Carrier\Flatrate</model>
+                            <name>Fixed</name>
+                            <price>5.00</price>
+                            <title>Flat Rate</title>
+                            <type>I</type>
+                        </flatrate>
+                    </carriers>
+                </default>
+            </config>"#,
+            "Magento/OfflineShipping/etc/config.xml",
+        );
+        // Another module only switches it off
+        registry.add_config_xml(
+            "<config><default><carriers><flatrate><active>0</active></flatrate><orphan><active>1</active></orphan></carriers></default></config>",
+            "Vendor/Shipping/etc/config.xml",
+        );
+
+        let carriers = registry.carriers(None);
+        assert_eq!(carriers.len(), 1);
+        let flatrate = &carriers[0];
+        assert_eq!(flatrate.model.as_deref(), Some("Magento\\OfflineShipping\\Model\\Carrier\\Flatrate"));
+        assert_eq!(flatrate.title.as_deref(), Some("Flat Rate"));
+        assert_eq!(flatrate.name.as_deref(), Some("Fixed"));
+        assert_eq!(flatrate.active, Some(false));
+        assert_eq!(flatrate.config.get("price").map(String::as_str), Some("5.00"));
+        assert_eq!(flatrate.files.len(), 2);
+
+        assert_eq!(registry.carriers(Some("FLAT RATE")).len(), 1);
+        assert!(registry.carriers(Some("ups")).is_empty());
+        assert_eq!(registry.carriers_for_class("\\Magento\\OfflineShipping\\Model\\Carrier\\Flatrate").len(), 1);
+    }
+}
@@ -0,0 +1,139 @@
+//! Ed25519 signatures for distributed index files (`magector sign` / `verify`)
+//!
+//! Signatures are detached: `index.db.sig` next to the DB holds a signature
+//! over the SHA-256 of the file. Keys and signatures are stored as hex text
+//! so they can be pasted into CI secrets and config files.
+
+use anyhow::{bail, Context, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Prefix of the signed message, so an index signature can't be replayed
+/// as a signature over anything else
+const SIGNING_CONTEXT: &[u8] = b"magector-index-v1\0";
+
+/// Detached signature path for a file (`index.db` → `index.db.sig`)
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+/// Generate a key pair, writing the secret key to `secret_path` (owner-only
+/// on Unix) and the public key to `secret_path` with a `.pub` extension.
+/// Returns the public key path.
+pub fn generate_keypair(secret_path: &Path) -> Result<PathBuf> {
+    let mut seed = [0u8; 32];
+    getrandom::getrandom(&mut seed).map_err(|e| anyhow::anyhow!("No system randomness: {e}"))?;
+    let key = SigningKey::from_bytes(&seed);
+
+    let public_path = secret_path.with_extension("pub");
+    write_secret(secret_path, &to_hex(key.as_bytes()))?;
+    fs::write(&public_path, to_hex(key.verifying_key().as_bytes()) + "\n")
+        .with_context(|| format!("Failed to write {}", public_path.display()))?;
+    Ok(public_path)
+}
+
+/// Create the secret key file, failing if it exists; owner-only on Unix
+fn write_secret(path: &Path, hex: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    writeln!(file, "{hex}")?;
+    Ok(())
+}
+
+/// Read a secret key written by [`generate_keypair`]
+pub fn read_signing_key(path: &Path) -> Result<SigningKey> {
+    let bytes = read_hex_file::<32>(path, "secret key")?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// Read a public key written by [`generate_keypair`]
+pub fn read_verifying_key(path: &Path) -> Result<VerifyingKey> {
+    let bytes = read_hex_file::<32>(path, "public key")?;
+    VerifyingKey::from_bytes(&bytes).with_context(|| format!("Invalid public key in {}", path.display()))
+}
+
+/// Sign `path` and write the detached signature next to it.
+/// Returns the signature path.
+pub fn sign_file(path: &Path, key: &SigningKey) -> Result<PathBuf> {
+    let signature = key.sign(&signed_message(path)?);
+    let sig_path = signature_path(path);
+    fs::write(&sig_path, to_hex(&signature.to_bytes()) + "\n")
+        .with_context(|| format!("Failed to write {}", sig_path.display()))?;
+    Ok(sig_path)
+}
+
+/// Check the detached signature at `sig_path` against `path`
+pub fn verify_file(path: &Path, sig_path: &Path, key: &VerifyingKey) -> Result<()> {
+    let signature = Signature::from_bytes(&read_hex_file::<64>(sig_path, "signature")?);
+    key.verify(&signed_message(path)?, &signature)
+        .map_err(|_| anyhow::anyhow!("Signature check failed: {} was modified or signed with another key", path.display()))
+}
+
+fn signed_message(path: &Path) -> Result<Vec<u8>> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    let mut message = SIGNING_CONTEXT.to_vec();
+    message.extend_from_slice(&hasher.finalize());
+    Ok(message)
+}
+
+fn read_hex_file<const N: usize>(path: &Path, what: &str) -> Result<[u8; N]> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {what} {}", path.display()))?;
+    match from_hex(text.trim()) {
+        Some(bytes) if bytes.len() == N => Ok(bytes.try_into().expect("length checked")),
+        _ => bail!("{} is not a valid {what} ({} hex characters expected)", path.display(), N * 2),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("magector.key");
+        let public = generate_keypair(&secret).unwrap();
+        let db = dir.path().join("index.db");
+        fs::write(&db, b"index bytes").unwrap();
+
+        let sig = sign_file(&db, &read_signing_key(&secret).unwrap()).unwrap();
+        assert_eq!(sig, dir.path().join("index.db.sig"));
+        let key = read_verifying_key(&public).unwrap();
+        verify_file(&db, &sig, &key).unwrap();
+
+        fs::write(&db, b"tampered bytes").unwrap();
+        assert!(verify_file(&db, &sig, &key).is_err());
+
+        // Keys are never overwritten
+        assert!(generate_keypair(&secret).is_err());
+    }
+}
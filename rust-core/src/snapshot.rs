@@ -0,0 +1,242 @@
+//! Tagged index snapshots (`index --tag`, `search --snapshot`, `history`)
+//!
+//! A snapshot is a full copy of the index saved as `snapshots/<tag>.db` next
+//! to the working DB, with the tag and time recorded in its header. Any
+//! snapshot can be searched directly, and [`file_history`] follows a file or
+//! class across all of them to find the release that introduced or changed it.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::vectordb::{IndexMetadata, VectorDB};
+
+/// Directory holding snapshots, next to the working DB
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Label of the working index in [`file_history`]
+pub const CURRENT_LABEL: &str = "current";
+
+/// Snapshot directory for the working DB at `db_path`
+pub fn snapshot_dir(db_path: &Path) -> PathBuf {
+    db_path.parent().unwrap_or(Path::new(".")).join(SNAPSHOT_DIR)
+}
+
+/// Path of snapshot `tag`. Tags are used as file names, so they are limited
+/// to letters, digits, `.`, `-` and `_` (`v2.4.6`, `2024-06-release`, a commit hash).
+pub fn snapshot_path(db_path: &Path, tag: &str) -> Result<PathBuf> {
+    let valid = !tag.is_empty()
+        && !tag.starts_with('.')
+        && tag.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    if !valid || tag == CURRENT_LABEL {
+        bail!("Invalid snapshot tag {:?}: use letters, digits, '.', '-' or '_'", tag);
+    }
+    Ok(snapshot_dir(db_path).join(format!("{tag}.db")))
+}
+
+/// A saved snapshot
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotInfo {
+    pub tag: String,
+    /// Unix seconds
+    pub tagged_at: u64,
+    pub path: PathBuf,
+}
+
+/// Snapshots of the working DB at `db_path`, oldest first
+pub fn list_snapshots(db_path: &Path) -> Result<Vec<SnapshotInfo>> {
+    let dir = snapshot_dir(db_path);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("db") {
+            continue;
+        }
+        let header = match VectorDB::read_header(&path) {
+            Ok(header) => header,
+            Err(e) => {
+                tracing::warn!("Skipping unreadable snapshot {:?}: {e:#}", path);
+                continue;
+            }
+        };
+        let tag = if header.tag.is_empty() {
+            path.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+        } else {
+            header.tag
+        };
+        snapshots.push(SnapshotInfo { tag, tagged_at: header.tagged_at, path });
+    }
+    snapshots.sort_by(|a, b| a.tagged_at.cmp(&b.tagged_at).then_with(|| a.tag.cmp(&b.tag)));
+    Ok(snapshots)
+}
+
+/// One file as indexed in a snapshot, merged over its chunks
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileVersion {
+    pub path: String,
+    /// Fully qualified class name, if any
+    pub class: Option<String>,
+    pub magento_type: Option<String>,
+    pub module: Option<String>,
+    pub extends: Option<String>,
+    pub implements: Vec<String>,
+    pub methods: BTreeSet<String>,
+}
+
+/// Matches for the history target in one snapshot
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub tag: String,
+    pub tagged_at: u64,
+    pub files: Vec<FileVersion>,
+    /// Differences from the previous entry (`added`, `removed`, `methods +x -y`, ...)
+    pub changes: Vec<String>,
+}
+
+/// Follow `target` — a relative path or a (fully qualified) class name —
+/// across all snapshots, oldest first, then the working index.
+pub fn file_history(db_path: &Path, target: &str) -> Result<Vec<HistoryEntry>> {
+    let mut sources: Vec<(String, u64, PathBuf)> = list_snapshots(db_path)?
+        .into_iter()
+        .map(|s| (s.tag, s.tagged_at, s.path))
+        .collect();
+    if db_path.exists() {
+        sources.push((CURRENT_LABEL.to_string(), 0, db_path.to_path_buf()));
+    }
+    if sources.is_empty() {
+        bail!("No index or snapshots found at {}", db_path.display());
+    }
+
+    let mut history: Vec<HistoryEntry> = Vec::with_capacity(sources.len());
+    for (tag, tagged_at, path) in sources {
+        let (_, items) = VectorDB::read_metadata(&path)?;
+        let files = file_versions(items.iter().filter(|m| matches_target(m, target)));
+        let changes = diff_versions(history.last().map(|h| h.files.as_slice()), &files);
+        history.push(HistoryEntry { tag, tagged_at, files, changes });
+    }
+    Ok(history)
+}
+
+fn qualified_class(meta: &IndexMetadata) -> Option<String> {
+    let class = meta.class_name.as_ref()?;
+    Some(match &meta.namespace {
+        Some(ns) if !ns.is_empty() => format!("{ns}\\{class}"),
+        _ => class.clone(),
+    })
+}
+
+fn matches_target(meta: &IndexMetadata, target: &str) -> bool {
+    let target = target.trim_start_matches('\\');
+    meta.path == target
+        || meta.class_name.as_deref() == Some(target)
+        || qualified_class(meta).as_deref() == Some(target)
+}
+
+/// Merge chunk items into one [`FileVersion`] per path
+fn file_versions<'a>(items: impl Iterator<Item = &'a IndexMetadata>) -> Vec<FileVersion> {
+    let mut by_path: BTreeMap<&str, FileVersion> = BTreeMap::new();
+    for meta in items {
+        let version = by_path.entry(&meta.path).or_insert_with(|| FileVersion {
+            path: meta.path.clone(),
+            class: qualified_class(meta),
+            magento_type: meta.magento_type.clone(),
+            module: meta.module.clone(),
+            extends: meta.extends.clone(),
+            implements: meta.implements.clone(),
+            methods: BTreeSet::new(),
+        });
+        version.methods.extend(meta.methods.iter().cloned());
+        version.methods.extend(meta.method_name.iter().cloned());
+    }
+    by_path.into_values().collect()
+}
+
+fn diff_versions(previous: Option<&[FileVersion]>, current: &[FileVersion]) -> Vec<String> {
+    let Some(previous) = previous else {
+        return if current.is_empty() { Vec::new() } else { vec!["present".to_string()] };
+    };
+    let mut changes = Vec::new();
+    for file in current {
+        let Some(old) = previous.iter().find(|p| p.path == file.path) else {
+            changes.push(format!("added {}", file.path));
+            continue;
+        };
+        let added: Vec<&str> = file.methods.difference(&old.methods).map(String::as_str).collect();
+        let removed: Vec<&str> = old.methods.difference(&file.methods).map(String::as_str).collect();
+        if !added.is_empty() || !removed.is_empty() {
+            let mut parts = Vec::new();
+            parts.extend(added.iter().map(|m| format!("+{m}")));
+            parts.extend(removed.iter().map(|m| format!("-{m}")));
+            changes.push(format!("methods {}", parts.join(" ")));
+        }
+        if file.extends != old.extends {
+            changes.push(format!(
+                "extends {} → {}",
+                old.extends.as_deref().unwrap_or("-"),
+                file.extends.as_deref().unwrap_or("-")
+            ));
+        }
+        if file.implements != old.implements {
+            changes.push(format!("implements {}", file.implements.join(", ")));
+        }
+        if file.class != old.class {
+            changes.push(format!("class {}", file.class.as_deref().unwrap_or("-")));
+        }
+    }
+    for old in previous {
+        if !current.iter().any(|f| f.path == old.path) {
+            changes.push(format!("removed {}", old.path));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(path: &str, methods: &[&str]) -> FileVersion {
+        FileVersion {
+            path: path.to_string(),
+            class: Some("Vendor\\Module\\Model\\Cart".to_string()),
+            magento_type: Some("model".to_string()),
+            module: Some("Vendor_Module".to_string()),
+            extends: None,
+            implements: Vec::new(),
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_tags() {
+        let db = Path::new("/srv/.magector/index.db");
+        assert_eq!(
+            snapshot_path(db, "v2.4.6").unwrap(),
+            Path::new("/srv/.magector/snapshots/v2.4.6.db")
+        );
+        assert!(snapshot_path(db, "../escape").is_err());
+        assert!(snapshot_path(db, "").is_err());
+        assert!(snapshot_path(db, CURRENT_LABEL).is_err());
+    }
+
+    #[test]
+    fn test_diff_versions() {
+        let a = version("app/code/Vendor/Module/Model/Cart.php", &["load", "save"]);
+        assert!(diff_versions(None, &[]).is_empty());
+        assert_eq!(diff_versions(None, std::slice::from_ref(&a)), vec!["present"]);
+        assert_eq!(diff_versions(Some(&[]), std::slice::from_ref(&a)), vec![format!("added {}", a.path)]);
+        assert!(diff_versions(Some(std::slice::from_ref(&a)), std::slice::from_ref(&a)).is_empty());
+
+        let mut b = version(&a.path, &["load", "validate"]);
+        b.extends = Some("AbstractModel".to_string());
+        assert_eq!(
+            diff_versions(Some(std::slice::from_ref(&a)), std::slice::from_ref(&b)),
+            vec!["methods +validate -save", "extends - → AbstractModel"]
+        );
+        assert_eq!(diff_versions(Some(&[b]), &[]), vec![format!("removed {}", a.path)]);
+    }
+}
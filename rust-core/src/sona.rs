@@ -6,7 +6,8 @@
 //! similar queries in the future.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
 use std::path::Path;
 
 use crate::embedder::EMBEDDING_DIM;
@@ -28,6 +29,67 @@ const MIN_LORA_SIMILARITY: f32 = 0.90;
 /// EWC regularization strength
 const EWC_LAMBDA: f32 = 2000.0;
 
+/// LoRA learning rate for a step away from a skipped result, relative to a
+/// step toward the chosen one
+const NEGATIVE_LORA_FACTOR: f32 = 0.2;
+
+/// Size caps of the learned weights (`[sona]` in `magector.toml`)
+///
+/// Query patterns and terms past a cap are evicted lowest observation count
+/// first, a tenth of the cap at a time, so a `.sona` file stays small and
+/// lookups fast after months of feedback.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SonaLimits {
+    /// Query patterns with their own adjustments
+    pub max_patterns: usize,
+    /// Terms with their own adjustments
+    pub max_terms: usize,
+}
+
+impl Default for SonaLimits {
+    fn default() -> Self {
+        Self { max_patterns: 5000, max_terms: 2000 }
+    }
+}
+
+impl SonaLimits {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.max_patterns == 0 || self.max_terms == 0 {
+            anyhow::bail!("sona limits must be positive, got max_patterns = {}, max_terms = {}", self.max_patterns, self.max_terms);
+        }
+        Ok(())
+    }
+}
+
+/// Remove the keys of `adjustments` and `counts` with the fewest
+/// observations (ties by key) until at most `keep` are left, sparing
+/// `protect`; returns how many were removed
+fn evict_lowest<K: Clone + Ord + Hash>(
+    adjustments: &mut HashMap<K, HashMap<String, f32>>,
+    counts: &mut HashMap<K, u32>,
+    keep: usize,
+    protect: &[K],
+) -> usize {
+    if adjustments.len() <= keep && counts.len() <= keep {
+        return 0;
+    }
+    let mut keys: Vec<K> = adjustments.keys().chain(counts.keys()).cloned().collect();
+    keys.sort();
+    keys.dedup();
+    if keys.len() <= keep {
+        return 0;
+    }
+    let excess = keys.len() - keep;
+    keys.retain(|k| !protect.contains(k));
+    keys.sort_by_key(|k| counts.get(k).copied().unwrap_or(0));
+    for key in keys.iter().take(excess) {
+        adjustments.remove(key);
+        counts.remove(key);
+    }
+    excess.min(keys.len())
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SonaSignal {
     #[serde(rename = "type")]
@@ -50,6 +112,59 @@ pub struct SonaSignal {
     pub original_result_paths: Option<Vec<String>>,
 }
 
+impl SonaSignal {
+    /// Signal for a search whose result `marked` (1-based) the user chose as
+    /// the answer (`search --mark`): the follow-up an MCP client would have
+    /// made to reach a file like it
+    pub fn marked(query: &str, results: &[&IndexMetadata], marked: usize, timestamp: u64) -> Self {
+        let meta = results[marked - 1];
+        let (signal_type, tool) = refinement_for_result(meta);
+        SonaSignal {
+            signal_type: signal_type.to_string(),
+            query: query.to_string(),
+            timestamp,
+            search_result_paths: results.iter().map(|r| r.path.clone()).collect(),
+            followed_tool: Some(tool.to_string()),
+            followed_args: Some(serde_json::json!({ "path": meta.path, "rank": marked })),
+            original_query: None,
+            refined_query: None,
+            original_result_paths: None,
+        }
+    }
+
+    /// Index into `search_result_paths` of the result the user went on to:
+    /// `followed_args.rank` (1-based, from `search --mark`) or the result at
+    /// `followed_args.path`. The results above it were skipped.
+    pub fn chosen_result(&self) -> Option<usize> {
+        let args = self.followed_args.as_ref()?;
+        let index = match args.get("rank").and_then(|r| r.as_u64()) {
+            Some(rank) => (rank as usize).checked_sub(1)?,
+            None => {
+                let path = args.get("path")?.as_str()?;
+                self.search_result_paths.iter().position(|p| p == path)?
+            }
+        };
+        (index < self.search_result_paths.len()).then_some(index)
+    }
+}
+
+/// Signal type and MCP tool of the follow-up that finds a file like `meta`
+pub fn refinement_for_result(meta: &IndexMetadata) -> (&'static str, &'static str) {
+    if meta.is_plugin {
+        ("refinement_to_plugin", "magento_find_plugin")
+    } else if meta.is_observer {
+        ("refinement_to_observer", "magento_find_observer")
+    } else if meta.is_controller {
+        ("refinement_to_controller", "magento_find_controller")
+    } else if meta.is_block {
+        ("refinement_to_block", "magento_find_block")
+    } else if meta.file_type == "xml" || meta.magento_type.as_deref() == Some("di_config") {
+        ("refinement_to_config", "magento_find_config")
+    } else {
+        ("refinement_to_class", "magento_find_class")
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Default, Debug)]
 pub struct LearnedWeights {
     /// pattern_hash → (feature_name → delta_weight)
@@ -129,6 +244,7 @@ impl MicroLoRA {
     }
 
     /// Apply LoRA transformation: embedding' = embedding + B × (A × embedding)
+    #[allow(clippy::needless_range_loop)]
     pub fn forward(&self, embedding: &[f32]) -> Vec<f32> {
         if embedding.len() != EMBEDDING_DIM || !self.is_valid() {
             // Corrupted LoRA or wrong embedding size — return unchanged
@@ -136,7 +252,7 @@ impl MicroLoRA {
         }
 
         // hidden = A × embedding (LORA_RANK-dim)
-        let mut hidden = vec![0.0f32; LORA_RANK];
+        let mut hidden = [0.0f32; LORA_RANK];
         for r in 0..LORA_RANK {
             let mut sum = 0.0f32;
             let row_start = r * EMBEDDING_DIM;
@@ -172,7 +288,27 @@ impl MicroLoRA {
         // Decay learning rate with update count
         self.update_count += 1;
         let lr = LORA_LR / (1.0 + 0.005 * self.update_count as f32);
+        self.step(query_emb, target_emb, lr);
+    }
+
+    /// Update LoRA weights from a result the user skipped (hard negative)
+    ///
+    /// The step of [`Self::update_from_signal`] reversed, at
+    /// [`NEGATIVE_LORA_FACTOR`] of its rate: the query embedding moves away
+    /// from the skipped result's direction. Does not count as an update for
+    /// learning rate decay.
+    pub fn update_from_negative(&mut self, query_emb: &[f32], skipped_emb: &[f32]) {
+        if query_emb.len() != EMBEDDING_DIM || skipped_emb.len() != EMBEDDING_DIM || !self.is_valid() {
+            return;
+        }
+        let lr = LORA_LR / (1.0 + 0.005 * self.update_count as f32);
+        self.step(query_emb, skipped_emb, -lr * NEGATIVE_LORA_FACTOR);
+    }
 
+    /// Move the LoRA output for `query_emb` toward `target_emb` at rate `lr`
+    /// (away from it when `lr` is negative)
+    #[allow(clippy::needless_range_loop)]
+    fn step(&mut self, query_emb: &[f32], target_emb: &[f32], lr: f32) {
         // Compute desired delta = target - query (direction to move)
         let mut delta = vec![0.0f32; EMBEDDING_DIM];
         for i in 0..EMBEDDING_DIM {
@@ -181,7 +317,7 @@ impl MicroLoRA {
 
         // Approximate gradient update for B matrix
         // hidden = A × query_emb
-        let mut hidden = vec![0.0f32; LORA_RANK];
+        let mut hidden = [0.0f32; LORA_RANK];
         for r in 0..LORA_RANK {
             let row_start = r * EMBEDDING_DIM;
             for c in 0..EMBEDDING_DIM {
@@ -252,6 +388,7 @@ impl EwcRegularizer {
     }
 
     /// Update Fisher information and star weights from current LoRA state
+    #[allow(clippy::needless_range_loop)]
     pub fn update_fisher(&mut self, lora: &MicroLoRA) {
         // Guard against corrupted state: if dimensions don't match, reset
         // to defaults instead of panicking. This can happen if the LoRA was
@@ -296,6 +433,7 @@ impl EwcRegularizer {
     }
 
     /// Compute EWC penalty for current weights vs star weights
+    #[allow(clippy::needless_range_loop)]
     pub fn penalty(&self, lora: &MicroLoRA) -> f32 {
         if self.update_count == 0 || !self.is_valid() || !lora.is_valid() {
             return 0.0;
@@ -351,6 +489,13 @@ pub struct SonaEngine {
     pub learned: LearnedWeights,
     pub lora: MicroLoRA,
     pub ewc: EwcRegularizer,
+    /// Stop terms of the index (see [`crate::idf`]); not learned per term.
+    /// Set by the indexer, not persisted.
+    stop_terms: HashSet<String>,
+    /// Size caps of `learned`; not persisted
+    limits: SonaLimits,
+    /// Patterns and terms evicted since the engine was opened
+    evicted: usize,
 }
 
 /// Persisted SONA state (V2 with LoRA + EWC)
@@ -364,12 +509,21 @@ struct SonaStateV2 {
 /// Version byte for V2 SONA files
 const SONA_VERSION_V2: u8 = 2;
 
+impl Default for SonaEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SonaEngine {
     pub fn new() -> Self {
         Self {
             learned: LearnedWeights::default(),
             lora: MicroLoRA::default(),
             ewc: EwcRegularizer::default(),
+            stop_terms: HashSet::new(),
+            limits: SonaLimits::default(),
+            evicted: 0,
         }
     }
 
@@ -420,6 +574,9 @@ impl SonaEngine {
                         learned: state.learned,
                         lora,
                         ewc,
+                        stop_terms: HashSet::new(),
+                        limits: SonaLimits::default(),
+                        evicted: 0,
                     });
                 }
                 Err(e) => {
@@ -435,6 +592,9 @@ impl SonaEngine {
                 learned,
                 lora: MicroLoRA::default(),
                 ewc: EwcRegularizer::default(),
+                stop_terms: HashSet::new(),
+                limits: SonaLimits::default(),
+                evicted: 0,
             }),
             Err(e) => {
                 tracing::warn!("SONA V1 deserialization failed: {} — resetting", e);
@@ -455,6 +615,51 @@ impl SonaEngine {
         Ok(())
     }
 
+    /// Problems with the SONA file at `path` that [`Self::open`] would
+    /// silently recover from by dropping state (`magector fsck`); empty when
+    /// it reads back whole
+    pub fn check_file(path: &Path) -> Vec<String> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) => return vec![format!("unreadable: {}", e)],
+        };
+        if bytes.is_empty() {
+            return Vec::new();
+        }
+        if bytes[0] != SONA_VERSION_V2 {
+            return match bincode::serde::decode_from_slice::<LearnedWeights, _>(&bytes, bincode::config::standard()) {
+                Ok(_) => Vec::new(),
+                Err(e) => vec![format!("not a readable SONA file ({}): all learning would be lost", e)],
+            };
+        }
+        let state = match bincode::serde::decode_from_slice::<SonaStateV2, _>(&bytes[1..], bincode::config::standard()) {
+            Ok((state, _)) => state,
+            Err(e) => return vec![format!("not a readable SONA file ({}): all learning would be lost", e)],
+        };
+        let mut problems = Vec::new();
+        if !state.lora.is_valid() {
+            problems.push(format!(
+                "LoRA weights have the wrong size (a={}, b={}, expected {} each)",
+                state.lora.a.len(),
+                state.lora.b.len(),
+                MicroLoRA::A_SIZE
+            ));
+        } else if !state.lora.a.iter().chain(&state.lora.b).all(|x| x.is_finite()) {
+            problems.push("LoRA weights contain NaN or infinite values".to_string());
+        }
+        if !state.ewc.is_valid() {
+            problems.push(format!(
+                "EWC state has the wrong size (fisher={}, star_weights={}, expected {})",
+                state.ewc.fisher.len(),
+                state.ewc.star_weights.len(),
+                EwcRegularizer::EXPECTED_SIZE
+            ));
+        } else if !state.ewc.fisher.iter().chain(&state.ewc.star_weights).all(|x| x.is_finite()) {
+            problems.push("EWC state contains NaN or infinite values".to_string());
+        }
+        problems
+    }
+
     /// FNV-1a hash of normalized, sorted query terms (3+ chars)
     fn pattern_hash(query: &str) -> u64 {
         let lower = query.to_lowercase();
@@ -488,6 +693,40 @@ impl SonaEngine {
         terms
     }
 
+    /// Words too common in the index to learn from on their own
+    pub fn set_stop_terms(&mut self, terms: impl IntoIterator<Item = String>) {
+        self.stop_terms = terms.into_iter().collect();
+    }
+
+    /// Size caps of the learned weights; a state over them is trimmed now
+    pub fn set_limits(&mut self, limits: SonaLimits) {
+        self.limits = limits;
+        self.evict(limits.max_patterns, limits.max_terms, None, &[]);
+    }
+
+    pub fn limits(&self) -> SonaLimits {
+        self.limits
+    }
+
+    /// Patterns and terms evicted since the engine was opened
+    pub fn evicted(&self) -> usize {
+        self.evicted
+    }
+
+    /// Trim `learned` to `max_patterns` patterns and `max_terms` terms,
+    /// keeping `pattern` and `terms`
+    fn evict(&mut self, max_patterns: usize, max_terms: usize, pattern: Option<u64>, terms: &[String]) {
+        let learned = &mut self.learned;
+        self.evicted += evict_lowest(&mut learned.adjustments, &mut learned.counts, max_patterns, pattern.as_slice());
+        self.evicted += evict_lowest(&mut learned.term_adjustments, &mut learned.term_counts, max_terms, terms);
+    }
+
+    /// Query terms adjusted per term: [`Self::normalize_terms`] without
+    /// stop terms
+    fn learned_terms(&self, query: &str) -> Vec<String> {
+        Self::normalize_terms(query).into_iter().filter(|t| !self.stop_terms.contains(t)).collect()
+    }
+
     /// Apply a feature adjustment map to metadata, returning the total delta.
     fn apply_features(adj: &HashMap<String, f32>, meta: &IndexMetadata) -> f32 {
         let mut delta = 0.0f32;
@@ -542,6 +781,23 @@ impl SonaEngine {
 
         // 1. Per-query-hash learning (strongest, existing behavior)
         let pattern = Self::pattern_hash(query);
+        let terms = self.learned_terms(query);
+
+        // Make room for a new pattern and terms, a tenth of each cap at a time
+        let SonaLimits { max_patterns, max_terms } = self.limits;
+        let new_pattern = !self.learned.adjustments.contains_key(&pattern);
+        let new_terms = terms.iter().filter(|t| !self.learned.term_adjustments.contains_key(*t)).count();
+        let keep_patterns = if new_pattern && self.learned.adjustments.len() >= max_patterns {
+            max_patterns - (max_patterns / 10).max(1)
+        } else {
+            max_patterns
+        };
+        let keep_terms = if self.learned.term_adjustments.len() + new_terms > max_terms {
+            max_terms.saturating_sub((max_terms / 10).max(new_terms))
+        } else {
+            max_terms
+        };
+        self.evict(keep_patterns, keep_terms, Some(pattern), &terms);
         let count = self.learned.counts.entry(pattern).or_insert(0);
         *count += 1;
         let lr = BASE_LR / (1.0 + (*count as f32) * 0.1);
@@ -563,7 +819,6 @@ impl SonaEngine {
         *gw = (*gw + global_lr).min(MAX_ADJUSTMENT);
 
         // 3. Per-term learning (medium strength)
-        let terms = Self::normalize_terms(query);
         let term_lr = lr * 0.5;
         for term in &terms {
             let tc = self.learned.term_counts.entry(term.clone()).or_insert(0);
@@ -615,7 +870,7 @@ impl SonaEngine {
         }
 
         // 2. Term-level matching (medium strength)
-        let terms = Self::normalize_terms(query);
+        let terms = self.learned_terms(query);
         let mut term_sum = 0.0f32;
         let mut term_count = 0u32;
         for term in &terms {
@@ -640,12 +895,12 @@ impl SonaEngine {
     ///
     /// Called before HNSW search to adapt the embedding based on learned patterns.
     /// Modifies the embedding in-place.
-    pub fn adjust_query_embedding(&self, embedding: &mut Vec<f32>) {
+    pub fn adjust_query_embedding(&self, embedding: &mut [f32]) {
         if embedding.len() != EMBEDDING_DIM || !self.lora.is_valid() {
             return;
         }
 
-        let original = embedding.clone();
+        let original = embedding.to_vec();
         let adjusted = self.lora.forward(embedding);
 
         // Check cosine similarity between original and adjusted
@@ -677,23 +932,34 @@ impl SonaEngine {
     /// Learn from feedback with LoRA + EWC update
     ///
     /// If query/target embeddings are provided, also updates the MicroLoRA adapter
-    /// with EWC regularization to prevent forgetting.
+    /// with EWC regularization to prevent forgetting. `skipped_embs` are the
+    /// embeddings of results listed above the chosen one
+    /// ([`SonaSignal::chosen_result`]); each moves the adapter a little away
+    /// from that result before the EWC step.
     pub fn learn_with_embeddings(
         &mut self,
         signal: &SonaSignal,
         query_emb: Option<&[f32]>,
         target_emb: Option<&[f32]>,
+        skipped_embs: &[&[f32]],
     ) {
         // Standard pattern learning
         self.learn(signal);
 
         // LoRA update if embeddings available
-        if let (Some(q), Some(t)) = (query_emb, target_emb) {
-            if q.len() == EMBEDDING_DIM && t.len() == EMBEDDING_DIM {
-                self.lora.update_from_signal(q, t);
-                self.ewc.regularize(&mut self.lora);
-                self.ewc.update_fisher(&self.lora);
-            }
+        let Some(q) = query_emb.filter(|q| q.len() == EMBEDDING_DIM) else { return };
+        let mut updated = false;
+        if let Some(t) = target_emb.filter(|t| t.len() == EMBEDDING_DIM) {
+            self.lora.update_from_signal(q, t);
+            updated = true;
+        }
+        for skipped in skipped_embs.iter().filter(|s| s.len() == EMBEDDING_DIM) {
+            self.lora.update_from_negative(q, skipped);
+            updated = true;
+        }
+        if updated {
+            self.ewc.regularize(&mut self.lora);
+            self.ewc.update_fisher(&self.lora);
         }
     }
 }
@@ -706,29 +972,13 @@ mod tests {
         IndexMetadata {
             path: String::new(),
             file_type: "php".to_string(),
-            magento_type: None,
-            class_name: None,
-            class_type: None,
-            method_name: None,
-            methods: vec![],
-            namespace: None,
-            module: None,
-            area: None,
-            extends: None,
             implements: vec![],
             is_controller,
             is_repository: false,
             is_plugin,
             is_observer,
-            is_model: false,
-            is_block: false,
-            is_resolver: false,
-            is_api_interface: false,
-            is_ui_component: false,
-            is_widget: false,
-            is_mixin: false,
-            js_dependencies: vec![],
             search_text: String::new(),
+            ..Default::default()
         }
     }
 
@@ -769,6 +1019,52 @@ mod tests {
         assert_eq!(adj_plain, 0.0, "Non-plugin should get zero adjustment");
     }
 
+    #[test]
+    fn test_stop_terms_not_learned() {
+        let mut engine = SonaEngine::new();
+        engine.set_stop_terms(["magento".to_string(), "model".to_string()]);
+        let signal = SonaSignal {
+            signal_type: "refinement_to_plugin".to_string(),
+            query: "Magento model wishlist".to_string(),
+            timestamp: 0,
+            search_result_paths: vec![],
+            followed_tool: None,
+            followed_args: None,
+            original_query: None,
+            refined_query: None,
+            original_result_paths: None,
+        };
+        engine.learn(&signal);
+
+        let mut terms: Vec<&String> = engine.learned.term_adjustments.keys().collect();
+        terms.sort();
+        assert_eq!(terms, ["wishlist"]);
+        // The exact query still matches its own pattern
+        assert_eq!(engine.learned.adjustments.len(), 1);
+        // Another query sharing only stop terms gets the global bias alone
+        let meta_plugin = make_meta(true, false, false);
+        let global = engine.score_adjustment("catalog", &meta_plugin);
+        assert!(global > 0.0);
+        assert_eq!(engine.score_adjustment("magento model", &meta_plugin), global);
+        assert!(engine.score_adjustment("wishlist", &meta_plugin) > global);
+    }
+
+    #[test]
+    fn test_marked_result_signal() {
+        let plugin = make_meta(true, false, false);
+        let controller = make_meta(false, false, true);
+        let results = [&controller, &plugin];
+        let signal = SonaSignal::marked("cart totals", &results, 2, 7);
+        assert_eq!(signal.signal_type, "refinement_to_plugin");
+        assert_eq!(signal.followed_tool.as_deref(), Some("magento_find_plugin"));
+        assert_eq!(signal.search_result_paths.len(), 2);
+
+        let mut engine = SonaEngine::new();
+        engine.learn(&signal);
+        assert!(engine.score_adjustment("cart totals", &plugin) > engine.score_adjustment("cart totals", &controller));
+        assert_eq!(refinement_for_result(&controller).0, "refinement_to_controller");
+    }
+
     #[test]
     fn test_adjustment_capped() {
         let mut engine = SonaEngine::new();
@@ -974,9 +1270,9 @@ mod tests {
 
         // At least some weights should be closer to star_weights than the perturbed values
         let mut closer_count = 0;
-        for i in 0..lora.a.len().min(ewc.star_weights.len()) {
-            let dist_before = (perturbed_a[i] + 0.1 - ewc.star_weights[i]).abs();
-            let dist_after = (lora.a[i] - ewc.star_weights[i]).abs();
+        for ((&after, &perturbed), &star) in lora.a.iter().zip(&perturbed_a).zip(&ewc.star_weights) {
+            let dist_before = (perturbed + 0.1 - star).abs();
+            let dist_after = (after - star).abs();
             if dist_after < dist_before {
                 closer_count += 1;
             }
@@ -1072,8 +1368,10 @@ mod tests {
         let mut bad_lora = MicroLoRA::default();
         bad_lora.a.truncate(44);
         bad_lora.b.truncate(44);
-        let mut ewc = EwcRegularizer::default();
-        ewc.update_count = 1; // so is_valid() dimensions matter
+        let ewc = EwcRegularizer {
+            update_count: 1, // so is_valid() dimensions matter
+            ..Default::default()
+        };
 
         let state = SonaStateV2 {
             learned: LearnedWeights::default(),
@@ -1170,7 +1468,7 @@ mod tests {
         let target_emb = vec![0.2f32; EMBEDDING_DIM];
         let a_before = engine.lora.a.clone();
 
-        engine.learn_with_embeddings(&signal, Some(&query_emb), Some(&target_emb));
+        engine.learn_with_embeddings(&signal, Some(&query_emb), Some(&target_emb), &[]);
 
         // LoRA weights should have changed
         assert!(engine.lora.a.iter().zip(a_before.iter()).any(|(a, b)| (a - b).abs() > 1e-10));
@@ -1180,4 +1478,84 @@ mod tests {
         let meta = make_meta(true, false, false);
         assert!(engine.score_adjustment("checkout cart totals", &meta) > 0.0);
     }
+
+    #[test]
+    fn test_hard_negatives_from_skipped_results() {
+        let paths: Vec<String> = ["a.php", "b.php", "c.php", "d.php", "e.php"].iter().map(|p| p.to_string()).collect();
+        let mut signal = SonaSignal {
+            signal_type: "refinement_to_class".to_string(),
+            query: "stock reservation".to_string(),
+            timestamp: 0,
+            search_result_paths: paths,
+            followed_tool: Some("magento_find_class".to_string()),
+            followed_args: Some(serde_json::json!({ "path": "e.php" })),
+            original_query: None,
+            refined_query: None,
+            original_result_paths: None,
+        };
+        assert_eq!(signal.chosen_result(), Some(4));
+        signal.followed_args = Some(serde_json::json!({ "path": "e.php", "rank": 2 }));
+        assert_eq!(signal.chosen_result(), Some(1));
+        signal.followed_args = Some(serde_json::json!({ "className": "Stock" }));
+        assert_eq!(signal.chosen_result(), None);
+
+        let query = vec![0.1f32; EMBEDDING_DIM];
+        let mut skipped = vec![0.1f32; EMBEDDING_DIM];
+        for x in skipped.iter_mut().take(EMBEDDING_DIM / 2) {
+            *x = 0.3;
+        }
+        let mut engine = SonaEngine::new();
+        let before = engine.lora.forward(&query);
+        for _ in 0..20 {
+            engine.learn_with_embeddings(&signal, Some(&query), None, &[&skipped]);
+        }
+        let after = engine.lora.forward(&query);
+        // The adjusted query moved against the direction of the skipped result
+        let toward: f32 = after
+            .iter()
+            .zip(&before)
+            .zip(skipped.iter().zip(&query))
+            .map(|((a, b), (s, q))| (a - b) * (s - q))
+            .sum();
+        assert!(toward < 0.0, "{toward}");
+        // Negative steps are EWC-protected but do not decay the learning rate
+        assert!(engine.ewc.update_count > 0);
+        assert_eq!(engine.lora.update_count, 0);
+    }
+
+    #[test]
+    fn test_learned_state_limits() {
+        let signal = |query: &str| SonaSignal {
+            signal_type: "refinement_to_plugin".to_string(),
+            query: query.to_string(),
+            timestamp: 0,
+            search_result_paths: vec![],
+            followed_tool: None,
+            followed_args: None,
+            original_query: None,
+            refined_query: None,
+            original_result_paths: None,
+        };
+        let mut engine = SonaEngine::new();
+        engine.set_limits(SonaLimits { max_patterns: 10, max_terms: 20 });
+        // A pattern seen often survives eviction
+        for _ in 0..3 {
+            engine.learn(&signal("checkout totals"));
+        }
+        for i in 0..30 {
+            engine.learn(&signal(&format!("query{:02} word{:02}", i, i)));
+        }
+        assert!(engine.learned.adjustments.len() <= 10 && engine.learned.counts.len() <= 10);
+        assert!(engine.learned.term_adjustments.len() <= 20 && engine.learned.term_counts.len() <= 20);
+        assert!(engine.evicted() > 0);
+        let meta = make_meta(true, false, false);
+        assert!(engine.score_adjustment("totals checkout", &meta) > engine.score_adjustment("query05 word05", &meta));
+        // The latest pattern is kept
+        assert!(engine.learned.counts.contains_key(&SonaEngine::pattern_hash("query29 word29")));
+
+        // Tightening the caps trims right away
+        engine.set_limits(SonaLimits { max_patterns: 2, max_terms: 2 });
+        assert_eq!(engine.learned.adjustments.len(), 2);
+        assert_eq!(engine.learned.term_counts.len(), 2);
+    }
 }
@@ -0,0 +1,72 @@
+//! Source file decoding: BOM handling and legacy (non-UTF-8) encodings
+
+use encoding_rs::{Encoding, WINDOWS_1252};
+use std::io;
+use std::path::Path;
+
+/// Leading bytes scanned for NUL when deciding whether a file is binary
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+/// Decoded file contents
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceText {
+    pub text: String,
+    /// True when the bytes were not valid UTF-8 and had to be converted
+    /// (legacy single-byte fallback or replacement characters)
+    pub lossy: bool,
+}
+
+/// Read and decode a source file, see [`decode_source`].
+pub fn read_source(path: &Path) -> io::Result<SourceText> {
+    decode_source(std::fs::read(path)?)
+}
+
+/// Decode file bytes to text.
+///
+/// A BOM (UTF-8, UTF-16LE/BE) selects the encoding and is stripped. Without
+/// one, valid UTF-8 is taken as is; anything else is decoded as Windows-1252,
+/// the WHATWG superset of ISO-8859-1 used by legacy Magento templates.
+/// Files with NUL bytes near the start are rejected as binary
+/// (`ErrorKind::InvalidData`).
+pub fn decode_source(bytes: Vec<u8>) -> io::Result<SourceText> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(&bytes) {
+        let (text, lossy) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return Ok(SourceText { text: text.into_owned(), lossy });
+    }
+
+    if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "binary file"));
+    }
+
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(SourceText { text, lossy: false }),
+        Err(e) => {
+            let (text, _) = WINDOWS_1252.decode_without_bom_handling(e.as_bytes());
+            Ok(SourceText { text: text.into_owned(), lossy: true })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_source() {
+        let plain = decode_source(b"<?php echo 1;".to_vec()).unwrap();
+        assert_eq!(plain, SourceText { text: "<?php echo 1;".into(), lossy: false });
+
+        let bom = decode_source(b"\xEF\xBB\xBF<?php".to_vec()).unwrap();
+        assert_eq!(bom, SourceText { text: "<?php".into(), lossy: false });
+
+        let utf16 = decode_source(vec![0xFF, 0xFE, b'o', 0, b'k', 0]).unwrap();
+        assert_eq!(utf16.text, "ok");
+
+        // ISO-8859-1 "Größe"
+        let latin1 = decode_source(b"Gr\xF6\xDFe".to_vec()).unwrap();
+        assert_eq!(latin1, SourceText { text: "Größe".into(), lossy: true });
+
+        let binary = decode_source(b"\x89PNG\r\n\x1a\n\0\0".to_vec()).unwrap_err();
+        assert_eq!(binary.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,171 @@
+//! One-line structural file summaries (`index --summaries`)
+//!
+//! Built from the metadata alone: what kind of file it is, the class and the
+//! words in its name, where it sits, what it extends, and its key methods.
+//! Stored in [`IndexMetadata::summary`] and returned with search results, so
+//! an LLM client can tell what a hit is without fetching its content.
+
+use crate::magento::split_camel_case;
+use crate::vectordb::IndexMetadata;
+
+/// Methods listed per summary
+const MAX_METHODS: usize = 6;
+
+/// Interfaces or JS dependencies listed per summary
+const MAX_LISTED: usize = 3;
+
+/// What the file is according to its type flags
+fn flagged_kind(meta: &IndexMetadata) -> Option<&'static str> {
+    let flagged = [
+        (meta.is_generated, "Generated"),
+        (meta.is_controller, "Controller"),
+        (meta.is_plugin, "Plugin"),
+        (meta.is_observer, "Observer"),
+        (meta.is_resolver, "GraphQL resolver"),
+        (meta.is_repository, "Repository"),
+        (meta.is_api_interface, "Service contract"),
+        (meta.is_block, "Block"),
+        (meta.is_model, "Model"),
+        (meta.is_ui_component, "UI component"),
+        (meta.is_widget, "jQuery widget"),
+        (meta.is_mixin, "JS mixin"),
+    ];
+    flagged.iter().find(|(flag, _)| *flag).map(|(_, kind)| *kind)
+}
+
+/// Magento type in words (`di config`), `None` when unknown
+fn type_words(meta: &IndexMetadata) -> Option<String> {
+    meta.magento_type.as_deref().filter(|t| *t != "other").map(|t| t.replace('_', " "))
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// `a, b, c (+2 more)`
+fn listing(items: &[String], max: usize) -> String {
+    let mut text = items.iter().take(max).cloned().collect::<Vec<_>>().join(", ");
+    if items.len() > max {
+        text.push_str(&format!(" (+{} more)", items.len() - max));
+    }
+    text
+}
+
+/// Last segment of a `\`-separated class name
+fn short_name(fqcn: &str) -> &str {
+    fqcn.rsplit('\\').next().unwrap_or(fqcn)
+}
+
+/// One-line summary of an indexed file, e.g. `Plugin class ProductSave
+/// (product save) in Magento_Catalog; methods: beforeSave, afterSave` or
+/// `di.xml (di config) in Magento_Sales`
+pub fn summarize(meta: &IndexMetadata) -> String {
+    let kind = flagged_kind(meta).map(str::to_string);
+    let mut summary = match &meta.class_name {
+        Some(class) => {
+            let kind = kind.or_else(|| type_words(meta).map(|t| capitalize(&t)));
+            let class_type = meta.class_type.as_deref().unwrap_or("class");
+            let mut text = match kind {
+                Some(kind) => format!("{} {} {}", kind, class_type, class),
+                None => format!("{} {}", capitalize(class_type), class),
+            };
+            let words = split_camel_case(class);
+            if words.contains(' ') {
+                text.push_str(&format!(" ({})", words));
+            }
+            text
+        }
+        None => {
+            let file = meta.path.rsplit('/').next().unwrap_or(&meta.path);
+            match (kind, type_words(meta)) {
+                (Some(kind), _) => format!("{} {}", kind, file),
+                (None, Some(words)) => format!("{} ({})", file, words),
+                (None, None) => file.to_string(),
+            }
+        }
+    };
+    if let Some(module) = &meta.module {
+        summary.push_str(&format!(" in {}", module));
+    }
+    if let Some(area) = &meta.area {
+        summary.push_str(&format!(" ({})", area));
+    }
+
+    let mut parts = Vec::new();
+    if let Some(parent) = &meta.extends {
+        parts.push(format!("extends {}", short_name(parent)));
+    }
+    if !meta.implements.is_empty() {
+        let names: Vec<String> = meta.implements.iter().map(|i| short_name(i).to_string()).collect();
+        parts.push(format!("implements {}", listing(&names, MAX_LISTED)));
+    }
+    // Constructors and magic methods say nothing about the purpose
+    let methods: Vec<String> = meta.methods.iter().filter(|m| !m.starts_with("__")).cloned().collect();
+    if !methods.is_empty() {
+        parts.push(format!("methods: {}", listing(&methods, MAX_METHODS)));
+    }
+    if !meta.js_dependencies.is_empty() {
+        parts.push(format!("depends on {}", listing(&meta.js_dependencies, MAX_LISTED)));
+    }
+    for part in parts {
+        summary.push_str("; ");
+        summary.push_str(&part);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(path: &str) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            search_text: String::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let plugin = IndexMetadata {
+            magento_type: Some("plugin".to_string()),
+            class_name: Some("ProductSavePlugin".to_string()),
+            methods: ["__construct", "beforeSave", "afterSave"].map(String::from).to_vec(),
+            module: Some("Magento_Catalog".to_string()),
+            area: Some("adminhtml".to_string()),
+            implements: vec!["Magento\\Framework\\ObjectManager\\NoninterceptableInterface".to_string()],
+            is_plugin: true,
+            ..meta("app/code/Magento/Catalog/Plugin/ProductSavePlugin.php")
+        };
+        assert_eq!(
+            summarize(&plugin),
+            "Plugin class ProductSavePlugin (product save plugin) in Magento_Catalog (adminhtml); \
+             implements NoninterceptableInterface; methods: beforeSave, afterSave"
+        );
+
+        let methods: Vec<String> = (0..8).map(|i| format!("get{}", i)).collect();
+        let model = IndexMetadata {
+            class_name: Some("Product".to_string()),
+            extends: Some("Magento\\Framework\\Model\\AbstractModel".to_string()),
+            methods,
+            is_model: true,
+            ..meta("Model/Product.php")
+        };
+        assert_eq!(
+            summarize(&model),
+            "Model class Product; extends AbstractModel; methods: get0, get1, get2, get3, get4, get5 (+2 more)"
+        );
+
+        let di = IndexMetadata {
+            file_type: "xml".to_string(),
+            magento_type: Some("di_config".to_string()),
+            module: Some("Magento_Sales".to_string()),
+            ..meta("app/code/Magento/Sales/etc/di.xml")
+        };
+        assert_eq!(summarize(&di), "di.xml (di config) in Magento_Sales");
+        assert_eq!(summarize(&meta("lib/web/x.php")), "x.php");
+    }
+}
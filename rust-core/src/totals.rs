@@ -0,0 +1,248 @@
+//! Totals collector chains (`magector totals`, serve `totals`)
+//!
+//! Each module's `etc/sales.xml` adds `<item>`s to the `totals` group of a
+//! section (`quote`, `order_invoice`, `order_creditmemo`); Magento merges
+//! them by name and runs the collectors in `sort_order`. A later module may
+//! redeclare an item only to move it; files are merged in module load
+//! order. [`TotalsConfig::chain`] replays the merge and links each collector class to its file through the module
+//! directories declared by `etc/module.xml`.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::indexer::{Indexer, SourceWalk};
+use crate::magento::{TotalCollector, XmlAnalyzer};
+use crate::project::{ModuleDecl, ModuleOrder};
+use crate::vectordb::relative_path;
+
+/// One collector in a section's chain, after merging every sales.xml
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CollectorStep {
+    pub code: String,
+    pub class: Option<String>,
+    pub sort_order: Option<i64>,
+    /// Source file of `class`, relative to the Magento root
+    pub class_file: Option<String>,
+    /// sales.xml files declaring or moving the collector
+    pub files: Vec<String>,
+}
+
+/// Totals collectors of every sales.xml, by section
+#[derive(Debug, Clone, Default)]
+pub struct TotalsConfig {
+    collectors: Vec<(TotalCollector, String)>,
+    /// Module namespace prefix (`Magento\Quote\`) → module directory, relative to the root
+    modules: HashMap<String, String>,
+}
+
+/// Section names accepted besides the literal ones
+const SECTION_ALIASES: &[(&str, &str)] = &[
+    ("invoice", "order_invoice"),
+    ("creditmemo", "order_creditmemo"),
+    // Orders keep the totals collected on the quote (copied by fieldset.xml)
+    ("order", "quote"),
+];
+
+impl TotalsConfig {
+    /// Add the collectors of one sales.xml (relative path `file`)
+    pub fn add(&mut self, collectors: Vec<TotalCollector>, file: &str) {
+        self.collectors.extend(collectors.into_iter().map(|c| (c, file.to_string())));
+    }
+
+    /// Record that classes under `Vendor\Module\` live in `dir`
+    pub fn add_module(&mut self, module: &str, dir: &str) {
+        if let Some((vendor, name)) = module.split_once('_') {
+            self.modules.insert(format!("{}\\{}\\", vendor, name), dir.to_string());
+        }
+    }
+
+    /// Parse every sales.xml and module.xml under `root`, skipping the
+    /// directories indexing skips
+    pub fn scan(root: &Path) -> Result<Self> {
+        let analyzer = XmlAnalyzer::new();
+        let mut sales_files: Vec<String> = Vec::new();
+        let mut modules: Vec<ModuleDecl> = Vec::new();
+        let mut config = Self::default();
+        let mut walker = SourceWalk::new(root, false);
+        while let Some(entry) = walker.next() {
+            let entry = entry?;
+            if Indexer::should_skip_dir(&entry, root, false) {
+                walker.skip_current_dir();
+                continue;
+            }
+            let path = entry.path();
+            if !entry.file_type().is_file() || path.parent().and_then(|p| p.file_name()).is_none_or(|p| p != "etc") {
+                continue;
+            }
+            match entry.file_name().to_str() {
+                Some("sales.xml") => sales_files.push(relative_path(root, path)),
+                Some("module.xml") => {
+                    let Ok(content) = std::fs::read_to_string(path) else { continue };
+                    let Some(dir) = path.parent().and_then(Path::parent) else { continue };
+                    if let Some(module) = ModuleDecl::parse(&content, &relative_path(root, dir)) {
+                        config.add_module(&module.name, &module.dir);
+                        modules.push(module);
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Magento merges sales.xml in module load order, so a module's
+        // redeclaration wins over the modules it loads after
+        let order = ModuleOrder::new(root, &modules);
+        sales_files.sort_by_cached_key(|file| (order.file_position(file), file.clone()));
+        for file in sales_files {
+            if let Ok(content) = std::fs::read_to_string(root.join(&file)) {
+                config.add(analyzer.analyze(&content).total_collectors, &file);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Sections with at least one collector
+    pub fn sections(&self) -> Vec<String> {
+        let mut sections: Vec<String> = self.collectors.iter().map(|(c, _)| c.section.clone()).collect();
+        sections.sort();
+        sections.dedup();
+        sections
+    }
+
+    /// Declared section for `name`: the section itself, or its alias
+    /// (`invoice` → `order_invoice`, `order` → `quote`)
+    pub fn resolve_section(&self, name: &str) -> Option<String> {
+        let sections = self.sections();
+        if sections.iter().any(|s| s == name) {
+            return Some(name.to_string());
+        }
+        SECTION_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == name)
+            .map(|(_, section)| section.to_string())
+            .filter(|section| sections.contains(section))
+    }
+
+    /// Collectors of `section`'s totals group in run order; collectors
+    /// without a sort order run last
+    pub fn chain(&self, section: &str) -> Vec<CollectorStep> {
+        let mut steps: Vec<CollectorStep> = Vec::new();
+        for (collector, file) in self.collectors.iter().filter(|(c, _)| c.section == section && c.group == "totals") {
+            let index = match steps.iter().position(|s| s.code == collector.code) {
+                Some(index) => index,
+                None => {
+                    steps.push(CollectorStep { code: collector.code.clone(), ..Default::default() });
+                    steps.len() - 1
+                }
+            };
+            let step = &mut steps[index];
+            if !collector.class.is_empty() {
+                step.class = Some(collector.class.trim_start_matches('\\').to_string());
+            }
+            step.sort_order = collector.sort_order.or(step.sort_order);
+            step.files.push(file.clone());
+        }
+        for step in &mut steps {
+            step.class_file = step.class.as_deref().and_then(|class| self.class_file(class));
+        }
+        steps.sort_by(|a, b| {
+            a.sort_order
+                .unwrap_or(i64::MAX)
+                .cmp(&b.sort_order.unwrap_or(i64::MAX))
+                .then_with(|| a.code.cmp(&b.code))
+        });
+        steps
+    }
+
+    /// Expected file of `class` inside its module directory
+    fn class_file(&self, class: &str) -> Option<String> {
+        self.modules.iter().find_map(|(namespace, dir)| {
+            let rest = class.strip_prefix(namespace.as_str())?;
+            Some(format!("{}/{}.php", dir, rest.replace('\\', "/")))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collector(section: &str, code: &str, class: &str, sort_order: Option<i64>) -> TotalCollector {
+        TotalCollector {
+            section: section.to_string(),
+            group: "totals".to_string(),
+            code: code.to_string(),
+            class: class.to_string(),
+            sort_order,
+        }
+    }
+
+    #[test]
+    fn test_chain_merges_and_orders() {
+        let mut config = TotalsConfig::default();
+        config.add_module("Magento_Quote", "vendor/magento/module-quote");
+        config.add(
+            vec![
+                collector("quote", "grand_total", "Magento\\Quote\\Model\\Quote\\Address\\Total\\Grand", Some(550)),
+                collector("quote", "subtotal", "Magento\\Quote\\Model\\Quote\\Address\\Total\\Subtotal", Some(100)),
+                collector("order_invoice", "subtotal", "Magento\\Sales\\Model\\Order\\Invoice\\Total\\Subtotal", Some(50)),
+            ],
+            "vendor/magento/module-quote/etc/sales.xml",
+        );
+        config.add(
+            vec![
+                collector("quote", "shipping", "Magento\\Quote\\Model\\Quote\\Address\\Total\\Shipping", Some(350)),
+                // Moves grand_total without redeclaring its class
+                collector("quote", "grand_total", "", Some(700)),
+                collector("quote", "custom", "\\Vendor\\Fee\\Model\\Total\\Fee", None),
+            ],
+            "app/code/Vendor/Fee/etc/sales.xml",
+        );
+
+        let chain = config.chain("quote");
+        let codes: Vec<&str> = chain.iter().map(|s| s.code.as_str()).collect();
+        assert_eq!(codes, vec!["subtotal", "shipping", "grand_total", "custom"]);
+        let grand = &chain[2];
+        assert_eq!(grand.sort_order, Some(700));
+        assert_eq!(grand.class.as_deref(), Some("Magento\\Quote\\Model\\Quote\\Address\\Total\\Grand"));
+        assert_eq!(
+            grand.class_file.as_deref(),
+            Some("vendor/magento/module-quote/Model/Quote/Address/Total/Grand.php")
+        );
+        assert_eq!(grand.files.len(), 2);
+        assert_eq!(chain[3].class.as_deref(), Some("Vendor\\Fee\\Model\\Total\\Fee"));
+        assert!(chain[3].class_file.is_none());
+
+        assert_eq!(config.sections(), vec!["order_invoice", "quote"]);
+        assert_eq!(config.resolve_section("invoice").as_deref(), Some("order_invoice"));
+        assert_eq!(config.resolve_section("order").as_deref(), Some("quote"));
+        assert_eq!(config.resolve_section("creditmemo"), None);
+    }
+
+    #[test]
+    fn test_scan_merges_in_module_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |rel: &str, content: &str| {
+            let path = dir.path().join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        let sales = |class: &str, sort_order: u32| {
+            format!(
+                "<config><section name=\"quote\"><group name=\"totals\"><item name=\"fee\" instance=\"{}\" sort_order=\"{}\"/></group></section></config>",
+                class, sort_order
+            )
+        };
+        // The app/code module sorts first by path but loads after the vendor one
+        write("app/code/Acme/Fee/etc/module.xml", "<config><module name=\"Acme_Fee\"><sequence><module name=\"Zed_Fee\"/></sequence></module></config>");
+        write("app/code/Acme/Fee/etc/sales.xml", &sales("Acme\\Fee\\Total", 900));
+        write("vendor/zed/module-fee/etc/module.xml", "<config><module name=\"Zed_Fee\"/></config>");
+        write("vendor/zed/module-fee/etc/sales.xml", &sales("Zed\\Fee\\Total", 100));
+
+        let config = TotalsConfig::scan(dir.path()).unwrap();
+        let chain = config.chain("quote");
+        assert_eq!(chain[0].sort_order, Some(900));
+        assert_eq!(chain[0].class.as_deref(), Some("Acme\\Fee\\Total"));
+        assert_eq!(chain[0].files, ["vendor/zed/module-fee/etc/sales.xml", "app/code/Acme/Fee/etc/sales.xml"]);
+    }
+}
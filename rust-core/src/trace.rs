@@ -0,0 +1,241 @@
+//! Request tracing (serve `trace_request`)
+//!
+//! Follows a URL path through the standard router to the page it renders:
+//! the route from routes.xml, the controller class each module of the route
+//! would serve it with, the layout handles the page loads and the blocks and
+//! templates those handles declare. Classes and templates are resolved to
+//! files through the index. The `default` handle every page loads and
+//! handles a controller adds at runtime (`catalog_product_view_type_simple`)
+//! are left out.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::magento::{module_names_for_path, ConfigXref};
+use crate::routes::Route;
+
+/// Indexed files and the classes they declare
+#[derive(Debug, Clone, Default)]
+pub struct FileIndex {
+    classes: HashMap<String, String>,
+    paths: BTreeSet<String>,
+}
+
+/// Controller class a module of the route would serve the request with
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ControllerTrace {
+    pub module: String,
+    pub class: String,
+    /// Indexed file declaring the class; `None` when the module has no such
+    /// controller
+    pub file: Option<String>,
+}
+
+/// Block of a layout file with its class and template resolved to files
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockTrace {
+    pub name: Option<String>,
+    pub class: Option<String>,
+    pub class_file: Option<String>,
+    pub template: Option<String>,
+    /// Template files, theme overrides first
+    pub template_files: Vec<String>,
+    pub reference: bool,
+}
+
+/// Layout file loaded for one of the page's handles
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayoutTrace {
+    pub file: String,
+    pub handle: String,
+    pub blocks: Vec<BlockTrace>,
+}
+
+/// Everything a URL path dispatches to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RequestTrace {
+    pub url: String,
+    pub route: Route,
+    pub controller: String,
+    pub action: String,
+    /// Controller classes in the order of the route's modules; the first
+    /// one with a file handles the request
+    pub controllers: Vec<ControllerTrace>,
+    /// Full action name handle and the handles it pulls in
+    pub handles: Vec<String>,
+    pub layouts: Vec<LayoutTrace>,
+}
+
+/// Module a `Vendor\Module\...` class belongs to (`Vendor_Module`)
+fn class_module(class: &str) -> Option<String> {
+    let mut parts = class.trim_start_matches('\\').split('\\');
+    Some(format!("{}_{}", parts.next()?, parts.next()?))
+}
+
+impl FileIndex {
+    /// Index of `(path, fully qualified class)` pairs, one or more per file
+    pub fn new(files: impl IntoIterator<Item = (String, Option<String>)>) -> Self {
+        let mut index = Self::default();
+        for (path, class) in files {
+            if let Some(class) = class {
+                index.classes.entry(class).or_insert_with(|| path.clone());
+            }
+            index.paths.insert(path);
+        }
+        index
+    }
+
+    pub fn class_file(&self, class: &str) -> Option<String> {
+        self.classes.get(class.trim_start_matches('\\')).cloned()
+    }
+
+    /// Files of `template` (`Magento_Catalog::product/view.phtml`, or a
+    /// path in `module`) in `area`: theme overrides, then the module's own
+    /// `view/<area>/` and `view/base/` templates
+    pub fn template_files(&self, template: &str, module: Option<&str>, area: &str) -> Vec<String> {
+        let (module, rel) = match template.split_once("::") {
+            Some((module, rel)) => (module.to_string(), rel),
+            None => match module {
+                Some(module) => (module.to_string(), template),
+                None => return Vec::new(),
+            },
+        };
+        let theme_suffix = format!("/{}/templates/{}", module, rel);
+        let theme_prefix = format!("app/design/{}/", area);
+        let module_dirs = [format!("/view/{}/templates/{}", area, rel), format!("/view/base/templates/{}", rel)];
+        let (mut themes, modules): (Vec<String>, Vec<String>) = self
+            .paths
+            .iter()
+            .filter(|p| {
+                (p.starts_with(&theme_prefix) && p.ends_with(&theme_suffix))
+                    || (module_dirs.iter().any(|dir| p.ends_with(dir.as_str()))
+                        && module_names_for_path(p).iter().any(|n| n.eq_ignore_ascii_case(&module)))
+            })
+            .cloned()
+            .partition(|p| p.starts_with("app/design/"));
+        themes.extend(modules);
+        themes
+    }
+}
+
+/// Trace the URL path `url` through the routes, controllers and layouts of
+/// `xref`, resolving classes and templates with `index`
+pub fn trace_request(xref: &ConfigXref, index: &FileIndex, url: &str) -> Result<RequestTrace> {
+    let Some(target) = xref.routes.resolve_url(url) else {
+        bail!("No route matches '{}' (URL rewrites and CMS pages are not resolved)", url);
+    };
+    let area = target.route.area.as_str();
+    let controllers = target
+        .route
+        .modules
+        .iter()
+        .map(|module| {
+            let class = target.controller_class(module);
+            ControllerTrace { module: module.clone(), file: index.class_file(&class), class }
+        })
+        .collect();
+    let handles = xref.layout.expand(area, &target.full_action_name());
+    let mut layouts = Vec::new();
+    for handle in &handles {
+        for file in xref.layout.files_for(area, handle) {
+            let file_module = module_names_for_path(&file.file).into_iter().next();
+            let blocks = file
+                .blocks
+                .iter()
+                .map(|block| {
+                    let module = block.class.as_deref().and_then(class_module).or_else(|| file_module.clone());
+                    BlockTrace {
+                        name: block.name.clone(),
+                        class: block.class.clone(),
+                        class_file: block.class.as_deref().and_then(|class| index.class_file(class)),
+                        template: block.template.clone(),
+                        template_files: block
+                            .template
+                            .as_deref()
+                            .map(|template| index.template_files(template, module.as_deref(), area))
+                            .unwrap_or_default(),
+                        reference: block.reference,
+                    }
+                })
+                .collect();
+            layouts.push(LayoutTrace { file: file.file.clone(), handle: handle.clone(), blocks });
+        }
+    }
+    Ok(RequestTrace {
+        url: url.to_string(),
+        route: target.route.clone(),
+        controller: target.controller.clone(),
+        action: target.action.clone(),
+        controllers,
+        handles,
+        layouts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_request() {
+        let mut xref = ConfigXref::default();
+        xref.routes.add_routes_xml(
+            r#"<config><router id="standard">
+                <route id="checkout" frontName="checkout">
+                    <module name="Acme_Checkout" before="Magento_Checkout"/>
+                    <module name="Magento_Checkout"/>
+                </route>
+            </router></config>"#,
+            "vendor/magento/module-checkout/etc/frontend/routes.xml",
+        );
+        xref.layout.add_layout_xml(
+            r#"<page><update handle="checkout_cart_item_renderers"/>
+                <block class="Magento\Checkout\Block\Cart" name="checkout.cart" template="Magento_Checkout::cart.phtml"/>
+            </page>"#,
+            "vendor/magento/module-checkout/view/frontend/layout/checkout_cart_index.xml",
+        );
+        xref.layout.add_layout_xml(
+            r#"<page><block class="Magento\Checkout\Block\Cart\Item\Renderer" name="renderer" template="cart/item/default.phtml"/></page>"#,
+            "vendor/magento/module-checkout/view/frontend/layout/checkout_cart_item_renderers.xml",
+        );
+        let index = FileIndex::new([
+            (
+                "vendor/magento/module-checkout/Controller/Cart/Index.php".to_string(),
+                Some("Magento\\Checkout\\Controller\\Cart\\Index".to_string()),
+            ),
+            (
+                "vendor/magento/module-checkout/Block/Cart.php".to_string(),
+                Some("Magento\\Checkout\\Block\\Cart".to_string()),
+            ),
+            ("vendor/magento/module-checkout/view/frontend/templates/cart.phtml".to_string(), None),
+            ("vendor/magento/module-checkout/view/frontend/templates/cart/item/default.phtml".to_string(), None),
+            ("app/design/frontend/Acme/theme/Magento_Checkout/templates/cart.phtml".to_string(), None),
+            ("vendor/magento/module-catalog/view/frontend/templates/cart.phtml".to_string(), None),
+        ]);
+
+        let trace = trace_request(&xref, &index, "/checkout/cart/").unwrap();
+        assert_eq!((trace.controller.as_str(), trace.action.as_str()), ("cart", "index"));
+        assert_eq!(trace.controllers.len(), 2);
+        assert_eq!(trace.controllers[0].file, None);
+        assert_eq!(trace.controllers[1].file.as_deref(), Some("vendor/magento/module-checkout/Controller/Cart/Index.php"));
+        assert_eq!(trace.handles, ["checkout_cart_index", "checkout_cart_item_renderers"]);
+        assert_eq!(trace.layouts.len(), 2);
+        let cart = &trace.layouts[0].blocks[0];
+        assert_eq!(cart.class_file.as_deref(), Some("vendor/magento/module-checkout/Block/Cart.php"));
+        assert_eq!(
+            cart.template_files,
+            [
+                "app/design/frontend/Acme/theme/Magento_Checkout/templates/cart.phtml",
+                "vendor/magento/module-checkout/view/frontend/templates/cart.phtml",
+            ]
+        );
+        // A template without a module prefix is looked up in the block's module
+        assert_eq!(
+            trace.layouts[1].blocks[0].template_files,
+            ["vendor/magento/module-checkout/view/frontend/templates/cart/item/default.phtml"]
+        );
+
+        assert!(trace_request(&xref, &index, "/men.html").is_err());
+    }
+}
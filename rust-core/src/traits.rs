@@ -0,0 +1,115 @@
+//! Trait resolution
+//!
+//! A class using traits has more methods than it declares: a query naming a
+//! method from `LoggerTrait` should find the classes that use it. The PHP
+//! analyzer records the fully qualified traits of each class
+//! ([`IndexMetadata::traits`]). Once all files are indexed, those names are
+//! looked up among the indexed traits, including those of a core index, and
+//! the methods of each trait and of the traits it uses in turn are stored in
+//! [`IndexMetadata::trait_methods`] of the using class. Methods the class
+//! declares itself are left out; traits that aren't indexed add nothing.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::overrides::class_key;
+use crate::vectordb::IndexMetadata;
+
+/// What one declaration contributes, merged over the items of its file
+#[derive(Default)]
+struct Declared<'a> {
+    methods: BTreeSet<&'a str>,
+    traits: BTreeSet<String>,
+}
+
+/// Path of each file using traits → the methods its traits add, sorted.
+/// Files whose traits add nothing, or outside `only` when given, are left
+/// out. Chunks of one file count once.
+pub fn trait_methods<'a>(
+    items: impl IntoIterator<Item = &'a IndexMetadata>,
+    only: Option<&HashSet<String>>,
+) -> HashMap<String, Vec<String>> {
+    let mut traits: HashMap<String, Declared> = HashMap::new();
+    let mut users: HashMap<&str, Declared> = HashMap::new();
+    for meta in items {
+        if meta.class_name.is_none() {
+            continue;
+        }
+        let is_trait = meta.class_type.as_deref() == Some("trait");
+        let declared = match class_key(meta) {
+            Some(key) if is_trait => traits.entry(key).or_default(),
+            _ if only.is_some_and(|paths| !paths.contains(&meta.path)) => continue,
+            _ => users.entry(meta.path.as_str()).or_default(),
+        };
+        declared.methods.extend(meta.methods.iter().map(String::as_str));
+        declared.traits.extend(meta.traits.iter().map(|t| t.to_lowercase()));
+    }
+
+    let mut resolved = HashMap::new();
+    for (path, class) in users {
+        if class.traits.is_empty() {
+            continue;
+        }
+        let mut methods = BTreeSet::new();
+        let mut seen = HashSet::new();
+        let mut pending: Vec<&String> = class.traits.iter().collect();
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name) {
+                continue;
+            }
+            let Some(used) = traits.get(name) else { continue };
+            methods.extend(used.methods.iter().filter(|m| !class.methods.contains(*m)));
+            pending.extend(&used.traits);
+        }
+        if !methods.is_empty() {
+            resolved.insert(path.to_string(), methods.into_iter().map(str::to_string).collect());
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(path: &str, class: &str, class_type: &str, methods: &[&str], traits: &[&str]) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            class_name: Some(class.rsplit('\\').next().unwrap().to_string()),
+            class_type: Some(class_type.to_string()),
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+            namespace: class.rsplit_once('\\').map(|(ns, _)| ns.to_string()),
+            search_text: String::new(),
+            traits: traits.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_trait_methods_merge_transitively() {
+        let items = vec![
+            item("Traits/Logger.php", "Vendor\\Traits\\Logger", "trait", &["log", "debug"], &["Vendor\\Traits\\Formats"]),
+            item("Traits/Formats.php", "Vendor\\Traits\\Formats", "trait", &["format"], &[]),
+            // Cycles end
+            item("Traits/A.php", "Vendor\\Traits\\A", "trait", &["a"], &["Vendor\\Traits\\B"]),
+            item("Traits/B.php", "Vendor\\Traits\\B", "trait", &["b"], &["Vendor\\Traits\\A"]),
+            item("Model/Order.php", "Vendor\\Sales\\Model\\Order", "class", &["place", "debug"], &["vendor\\traits\\logger"]),
+            // A method chunk of the same class
+            item("Model/Order.php", "Vendor\\Sales\\Model\\Order", "class", &["format"], &[]),
+            item("Model/Cycle.php", "Vendor\\Cycle", "class", &[], &["Vendor\\Traits\\A", "Vendor\\Missing\\Trait"]),
+            item("Model/Plain.php", "Vendor\\Plain", "class", &["run"], &["Vendor\\Missing\\Trait"]),
+        ];
+
+        let resolved = trait_methods(&items, None);
+        assert_eq!(resolved["Model/Order.php"], ["log"], "own debug() and format() win");
+        assert_eq!(resolved["Model/Cycle.php"], ["a", "b"]);
+        assert!(!resolved.contains_key("Model/Plain.php"));
+        assert!(!resolved.contains_key("Traits/Logger.php"));
+
+        // Traits outside the scope still resolve for the files inside it
+        let only = HashSet::from(["Model/Cycle.php".to_string()]);
+        let resolved = trait_methods(&items, Some(&only));
+        assert_eq!(resolved.keys().collect::<Vec<_>>(), ["Model/Cycle.php"]);
+        assert_eq!(resolved["Model/Cycle.php"], ["a", "b"]);
+    }
+}
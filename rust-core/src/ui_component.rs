@@ -0,0 +1,392 @@
+//! Admin UI components (`view/adminhtml/ui_component/<name>.xml`)
+//!
+//! A listing or form names its data provider class in the component XML,
+//! often a virtual type of a generic provider. Grids built on the framework
+//! `DataProvider` get their collection from the `collections` argument of
+//! the UI `CollectionFactory` in di.xml, keyed by the data source name;
+//! custom providers inject a collection factory or repositories. The page
+//! showing the component places it with `<uiComponent>` in a layout handle,
+//! which names the controller action. [`UiComponentRegistry::chain`] joins
+//! those, so a grid question finds the whole path from controller to data.
+
+use regex::Regex;
+use std::collections::HashMap;
+
+use crate::magento::{module_names_for_path, ConfigXref};
+
+/// Type whose `collections` argument maps grid data sources to collections
+pub const GRID_COLLECTION_FACTORY: &str = "Magento\\Framework\\View\\Element\\UiComponent\\DataProvider\\CollectionFactory";
+
+/// Data provider declared by a component XML
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UiComponentDefinition {
+    /// `<dataSource name>`
+    pub data_source: Option<String>,
+    /// Provider class or virtual type
+    pub data_provider: Option<String>,
+    /// Provider `name`, the key of grid collections
+    pub provider_name: Option<String>,
+}
+
+/// Controller action rendering a component
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiComponentController {
+    pub url: String,
+    pub class: String,
+    /// Layout file placing the component
+    pub layout: String,
+}
+
+/// Component → data provider → collections and repositories → controllers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UiComponentChain {
+    pub name: String,
+    /// Provider as declared (may be a virtual type)
+    pub data_provider: Option<String>,
+    /// Provider class with virtual types resolved
+    pub provider_class: Option<String>,
+    pub collections: Vec<String>,
+    pub repositories: Vec<String>,
+    pub controllers: Vec<UiComponentController>,
+}
+
+/// Component XMLs, grid collections and the dependencies of provider classes
+#[derive(Debug, Clone, Default)]
+pub struct UiComponentRegistry {
+    /// Component file → definition
+    components: HashMap<String, UiComponentDefinition>,
+    /// Data source name → collection class
+    grid_collections: HashMap<String, String>,
+    /// Provider class → (collections, repositories) its constructor injects
+    providers: HashMap<String, (Vec<String>, Vec<String>)>,
+    /// Data provider → names of the components declaring it, from
+    /// [`Self::index_providers`]
+    by_provider: HashMap<String, Vec<String>>,
+}
+
+/// Whether `path` is a UI component definition
+pub fn is_ui_component_xml(path: &str) -> bool {
+    path.ends_with(".xml") && path.rsplit('/').nth(1) == Some("ui_component")
+}
+
+/// Whether the PHP file at `path` may be a data provider class
+pub fn is_provider_source(path: &str) -> bool {
+    path.ends_with(".php") && path.contains("DataProvider")
+}
+
+/// Component name of the definition at `path` (its file name)
+fn component_name(path: &str) -> Option<&str> {
+    path.rsplit('/').next()?.strip_suffix(".xml")
+}
+
+/// Parse the data source and provider of a component XML; both the
+/// `<dataProvider class>` form and the older `configurableObject` argument
+/// are read
+pub fn parse_component(content: &str) -> UiComponentDefinition {
+    let data_source_re = Regex::new(r#"<dataSource\s+[^>]*?name="([^"]+)""#).unwrap();
+    let provider_re = Regex::new(r#"<dataProvider\s+([^>]*?)/?>"#).unwrap();
+    let attr_re = Regex::new(r#"([\w:]+)="([^"]*)""#).unwrap();
+    let legacy_re = Regex::new(
+        r#"(?s)<argument\s+name="dataProvider"\s+xsi:type="configurableObject"\s*>(.*?)<argument\s+name="data""#,
+    )
+    .unwrap();
+    let string_arg_re = Regex::new(r#"<argument\s+name="(class|name)"\s+xsi:type="string"\s*>\s*([^<]+?)\s*</argument>"#).unwrap();
+
+    let mut definition = UiComponentDefinition {
+        data_source: data_source_re.captures(content).map(|c| c[1].to_string()),
+        ..Default::default()
+    };
+    if let Some(caps) = provider_re.captures(content) {
+        let attr = |key: &str| attr_re.captures_iter(&caps[1]).find(|a| &a[1] == key).map(|a| a[2].to_string());
+        definition.data_provider = attr("class");
+        definition.provider_name = attr("name");
+    } else if let Some(caps) = legacy_re.captures(content) {
+        for arg in string_arg_re.captures_iter(&caps[1]) {
+            match &arg[1] {
+                "class" => definition.data_provider = Some(arg[2].to_string()),
+                _ => definition.provider_name = Some(arg[2].to_string()),
+            }
+        }
+    }
+    definition.data_provider = definition.data_provider.map(|c| c.trim_start_matches('\\').to_string());
+    definition
+}
+
+/// Collection and repository types a class's constructor injects, resolved
+/// through its namespace and `use` statements (`CollectionFactory` types
+/// give the collection they create)
+pub fn constructor_dependencies(content: &str) -> (Vec<String>, Vec<String>) {
+    let namespace_re = Regex::new(r"(?m)^\s*namespace\s+([\w\\]+)").unwrap();
+    let use_re = Regex::new(r"(?m)^\s*use\s+\\?([\w\\]+)(?:\s+as\s+(\w+))?\s*;").unwrap();
+    let constructor_re = Regex::new(r"(?s)function\s+__construct\s*\((.*?)\)\s*[{:]").unwrap();
+    let param_re = Regex::new(r"(?:^|,)\s*(?:(?:private|protected|public|readonly)\s+)*\??(\\?[A-Za-z_][\w\\]*)\s+\$").unwrap();
+
+    let namespace = namespace_re.captures(content).map(|c| c[1].to_string());
+    let uses: HashMap<String, String> = use_re
+        .captures_iter(content)
+        .map(|c| {
+            let alias = c.get(2).map_or_else(|| c[1].rsplit('\\').next().unwrap_or_default(), |a| a.as_str());
+            (alias.to_string(), c[1].to_string())
+        })
+        .collect();
+    let resolve = |hint: &str| -> String {
+        if let Some(fqcn) = hint.strip_prefix('\\') {
+            return fqcn.to_string();
+        }
+        let (first, rest) = hint.split_once('\\').map_or((hint, None), |(f, r)| (f, Some(r)));
+        let base = match (uses.get(first), &namespace) {
+            (Some(imported), _) => imported.clone(),
+            (None, Some(ns)) => format!("{}\\{}", ns, first),
+            (None, None) => first.to_string(),
+        };
+        rest.map_or(base.clone(), |rest| format!("{}\\{}", base, rest))
+    };
+
+    let (mut collections, mut repositories) = (Vec::new(), Vec::new());
+    let Some(params) = constructor_re.captures(content) else {
+        return (collections, repositories);
+    };
+    for caps in param_re.captures_iter(&params[1]) {
+        let class = resolve(&caps[1]);
+        let short = class.rsplit('\\').next().unwrap_or_default();
+        if short.ends_with("CollectionFactory") {
+            collections.push(class.strip_suffix("Factory").unwrap_or(&class).to_string());
+        } else if short.ends_with("Collection") {
+            collections.push(class);
+        } else if short.contains("Repository") {
+            repositories.push(class);
+        }
+    }
+    (collections, repositories)
+}
+
+impl UiComponentRegistry {
+    pub fn add_component_xml(&mut self, content: &str, file: &str) {
+        self.components.insert(file.to_string(), parse_component(content));
+    }
+
+    /// Grid collections from the `collections` argument of the
+    /// [`GRID_COLLECTION_FACTORY`] in a di.xml
+    pub fn add_di(&mut self, content: &str) {
+        let type_re = Regex::new(r#"(?s)<type\s+name="\\?([^"]+)"\s*>(.*?)</type>"#).unwrap();
+        let collections_re = Regex::new(r#"(?s)<argument\s+name="collections"[^>]*>(.*?)</argument>"#).unwrap();
+        let item_re = Regex::new(r#"<item\s+name="([^"]+)"[^>]*>\s*\\?([^<]+?)\s*</item>"#).unwrap();
+        for block in type_re.captures_iter(content).filter(|c| &c[1] == GRID_COLLECTION_FACTORY) {
+            for argument in collections_re.captures_iter(&block[2]) {
+                for item in item_re.captures_iter(&argument[1]) {
+                    self.grid_collections.insert(item[1].to_string(), item[2].to_string());
+                }
+            }
+        }
+    }
+
+    /// Collections and repositories injected into a provider class
+    pub fn add_php(&mut self, content: &str) {
+        let class_re = Regex::new(r"(?m)^\s*(?:(?:abstract|final|readonly)\s+)*class\s+(\w+)").unwrap();
+        let namespace_re = Regex::new(r"(?m)^\s*namespace\s+([\w\\]+)").unwrap();
+        let Some(class) = class_re.captures(content).map(|c| c[1].to_string()) else { return };
+        let class = match namespace_re.captures(content) {
+            Some(ns) => format!("{}\\{}", &ns[1], class),
+            None => class,
+        };
+        let dependencies = constructor_dependencies(content);
+        if !dependencies.0.is_empty() || !dependencies.1.is_empty() {
+            self.providers.insert(class, dependencies);
+        }
+    }
+
+    /// Chain of the component defined at `path`, `None` for other files.
+    /// Controllers come from the layout handles placing the component; of a
+    /// route serving several modules, only the module of the layout file is
+    /// assumed to handle it when it is one of them.
+    pub fn chain(&self, path: &str, xref: &ConfigXref) -> Option<UiComponentChain> {
+        let definition = self.components.get(path)?;
+        let name = component_name(path)?;
+        let provider_class = definition.data_provider.as_deref().map(|p| xref.di.resolve_virtual_type(p));
+        let mut collections = Vec::new();
+        let mut repositories = Vec::new();
+        if let Some((injected, repos)) = provider_class.as_ref().and_then(|c| self.providers.get(c)) {
+            collections.extend(injected.iter().cloned());
+            repositories.extend(repos.iter().cloned());
+        }
+        let source = definition.provider_name.as_ref().or(definition.data_source.as_ref());
+        if let Some(collection) = source.and_then(|s| self.grid_collections.get(s)) {
+            if !collections.contains(collection) {
+                collections.push(collection.clone());
+            }
+        }
+
+        let mut controllers = Vec::new();
+        for layout in xref.layout.files_with_ui_component(name) {
+            let area = if layout.area == "base" { "adminhtml" } else { layout.area.as_str() };
+            let layout_modules = module_names_for_path(&layout.file);
+            for action in xref.routes.resolve_handle(area, &layout.handle) {
+                let own: Vec<&String> = action
+                    .route
+                    .modules
+                    .iter()
+                    .filter(|m| layout_modules.iter().any(|n| n.eq_ignore_ascii_case(m)))
+                    .collect();
+                let modules = if own.is_empty() { action.route.modules.iter().collect() } else { own };
+                for module in modules {
+                    controllers.push(UiComponentController {
+                        url: action.url(),
+                        class: action.controller_class(module),
+                        layout: layout.file.clone(),
+                    });
+                }
+            }
+        }
+
+        Some(UiComponentChain {
+            name: name.to_string(),
+            data_provider: definition.data_provider.clone(),
+            provider_class,
+            collections,
+            repositories,
+            controllers,
+        })
+    }
+
+    /// Map each data provider to the components declaring it for
+    /// [`Self::components_for_provider`]; call once every file is added
+    pub fn index_providers(&mut self) {
+        let mut by_provider: HashMap<String, Vec<String>> = HashMap::new();
+        for (path, definition) in &self.components {
+            if let (Some(provider), Some(name)) = (&definition.data_provider, component_name(path)) {
+                by_provider.entry(provider.clone()).or_default().push(name.to_string());
+            }
+        }
+        for names in by_provider.values_mut() {
+            names.sort();
+            names.dedup();
+        }
+        self.by_provider = by_provider;
+    }
+
+    /// Names of the components declaring `class` as their data provider.
+    /// Providers reached through a virtual type are left out: that is
+    /// usually the framework `DataProvider`, shared by every grid.
+    pub fn components_for_provider(&self, class: &str) -> Vec<String> {
+        self.by_provider.get(class.trim_start_matches('\\')).cloned().unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magento::XmlAnalyzer;
+
+    #[test]
+    fn test_parse_component() {
+        let listing = parse_component(
+            r#"<listing><dataSource name="sales_order_grid_data_source" component="Magento_Ui/js/grid/provider">
+                <dataProvider class="Magento\Framework\View\Element\UiComponent\DataProvider\DataProvider"
+                              name="sales_order_grid_data_source"><settings/></dataProvider>
+            </dataSource></listing>"#,
+        );
+        assert_eq!(listing.data_source.as_deref(), Some("sales_order_grid_data_source"));
+        assert_eq!(
+            listing.data_provider.as_deref(),
+            Some("Magento\\Framework\\View\\Element\\UiComponent\\DataProvider\\DataProvider")
+        );
+        let legacy = parse_component(
+            r#"<form><dataSource name="cms_block_form_data_source">
+                <argument name="dataProvider" xsi:type="configurableObject">
+                    <argument name="class" xsi:type="string">Magento\Cms\Model\Block\DataProvider</argument>
+                    <argument name="name" xsi:type="string">cms_block_form_data_source</argument>
+                    <argument name="data" xsi:type="array"/>
+                </argument>
+            </dataSource></form>"#,
+        );
+        assert_eq!(legacy.data_provider.as_deref(), Some("Magento\\Cms\\Model\\Block\\DataProvider"));
+        assert_eq!(legacy.provider_name.as_deref(), Some("cms_block_form_data_source"));
+
+        let (collections, repositories) = constructor_dependencies(
+            r#"<?php
+namespace Magento\Cms\Model\Block;
+
+use Magento\Cms\Model\ResourceModel\Block\CollectionFactory;
+use Magento\Cms\Api\BlockRepositoryInterface as Repository;
+
+class DataProvider extends \Magento\Ui\DataProvider\ModifierPoolDataProvider
+{
+    public function __construct(
+        $name,
+        CollectionFactory $blockCollectionFactory,
+        private readonly Repository $repository,
+        ?\Magento\Framework\App\Request\DataPersistorInterface $dataPersistor,
+        array $data = []
+    ) {
+    }
+}"#,
+        );
+        assert_eq!(collections, ["Magento\\Cms\\Model\\ResourceModel\\Block\\Collection"]);
+        assert_eq!(repositories, ["Magento\\Cms\\Api\\BlockRepositoryInterface"]);
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut xref = ConfigXref::default();
+        let di = r#"<config>
+            <virtualType name="SalesOrderGridDataProvider" type="Magento\Framework\View\Element\UiComponent\DataProvider\DataProvider"/>
+            <type name="Magento\Framework\View\Element\UiComponent\DataProvider\CollectionFactory">
+                <arguments>
+                    <argument name="collections" xsi:type="array">
+                        <item name="sales_order_grid_data_source" xsi:type="string">Magento\Sales\Model\ResourceModel\Order\Grid\Collection</item>
+                    </argument>
+                </arguments>
+            </type>
+        </config>"#;
+        xref.di.add(&XmlAnalyzer::new().analyze(di), "vendor/magento/module-sales/etc/di.xml");
+        xref.ui_components.add_di(di);
+        xref.ui_components.add_component_xml(
+            r#"<listing><dataSource name="sales_order_grid_data_source">
+                <dataProvider class="SalesOrderGridDataProvider" name="sales_order_grid_data_source"/>
+            </dataSource></listing>"#,
+            "vendor/magento/module-sales/view/adminhtml/ui_component/sales_order_grid.xml",
+        );
+        xref.routes.add_routes_xml(
+            r#"<config><router id="admin"><route id="sales" frontName="sales">
+                <module name="Magento_Sales"/><module name="Magento_Paypal" before="Magento_Sales"/>
+            </route></router></config>"#,
+            "vendor/magento/module-sales/etc/adminhtml/routes.xml",
+        );
+        xref.layout.add_layout_xml(
+            r#"<page><referenceContainer name="content"><uiComponent name="sales_order_grid"/></referenceContainer></page>"#,
+            "vendor/magento/module-sales/view/adminhtml/layout/sales_order_index.xml",
+        );
+
+        let chain = xref
+            .ui_components
+            .chain("vendor/magento/module-sales/view/adminhtml/ui_component/sales_order_grid.xml", &xref)
+            .unwrap();
+        assert_eq!(chain.name, "sales_order_grid");
+        assert_eq!(
+            chain.provider_class.as_deref(),
+            Some("Magento\\Framework\\View\\Element\\UiComponent\\DataProvider\\DataProvider")
+        );
+        assert_eq!(chain.collections, ["Magento\\Sales\\Model\\ResourceModel\\Order\\Grid\\Collection"]);
+        assert!(chain.repositories.is_empty());
+        assert_eq!(
+            chain.controllers,
+            [UiComponentController {
+                url: "/admin/sales/order/index".to_string(),
+                class: "Magento\\Sales\\Controller\\Adminhtml\\Order\\Index".to_string(),
+                layout: "vendor/magento/module-sales/view/adminhtml/layout/sales_order_index.xml".to_string(),
+            }]
+        );
+        xref.ui_components.index_providers();
+        assert_eq!(
+            xref.ui_components.components_for_provider("SalesOrderGridDataProvider"),
+            ["sales_order_grid"]
+        );
+        assert!(xref.ui_components.chain("vendor/magento/module-sales/etc/di.xml", &xref).is_none());
+        assert!(is_ui_component_xml("vendor/magento/module-sales/view/adminhtml/ui_component/sales_order_grid.xml"));
+        assert!(!is_ui_component_xml("vendor/magento/module-sales/view/adminhtml/layout/sales_order_index.xml"));
+    }
+}
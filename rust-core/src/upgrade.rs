@@ -0,0 +1,376 @@
+//! Upgrade impact (`magector analyze upgrade`)
+//!
+//! Planning a Magento upgrade starts with which of the project's
+//! customizations sit on core code that changed. The project index (usually
+//! an overlay over a core index) yields the core classes the project
+//! customizes: copies of them ([`IndexMetadata::overrides`]), subclasses,
+//! and preferences and plugins in its own di.xml files. Each class is looked
+//! up in two core indexes, one per Magento version, and ranked by how far
+//! its file vector (the normalized mean of its items') moved between them.
+//! Classes gone from the target version come first.
+//!
+//! [`IndexMetadata::overrides`]: crate::vectordb::IndexMetadata::overrides
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::codemap::file_vectors;
+use crate::embedder::DEFAULT_MODEL;
+use crate::magento::{ConfigXref, XmlAnalyzer};
+use crate::overrides::class_key;
+use crate::vectordb::{IndexMetadata, VectorDB};
+
+/// How the project customizes a core class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Customization {
+    /// A copy of the class in project code
+    Override,
+    /// A project class extends it
+    Extends,
+    /// A project di.xml prefers another type for it
+    Preference,
+    /// A project di.xml plugs into it
+    Plugin,
+}
+
+impl Customization {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Customization::Override => "override",
+            Customization::Extends => "extends",
+            Customization::Preference => "preference",
+            Customization::Plugin => "plugin",
+        }
+    }
+}
+
+/// One customization of a core class
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Customizer {
+    pub kind: Customization,
+    /// Project class doing it: the copy, the subclass, the preferred type or
+    /// the plugin
+    pub class: String,
+    /// File declaring it: the class itself, or the di.xml
+    pub path: String,
+}
+
+/// A customized core class and how much it changed between two versions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UpgradeImpact {
+    pub class: String,
+    /// File in the current version's core index
+    pub path: String,
+    /// File in the target version's core index; none when the class is gone
+    pub target_path: Option<String>,
+    /// 1 − cosine similarity of the class's file vectors in the two
+    /// versions; none when the class is gone
+    pub change: Option<f32>,
+    pub customizers: Vec<Customizer>,
+}
+
+/// Customized core classes of the project index `project` (sources under
+/// `root`), looked up in the core index of the current version `from` and of
+/// the target version `to`: classes gone from `to` first, then by change,
+/// largest first. Classes `from` lacks are left out.
+pub fn impact(project: &VectorDB, root: &Path, from: &VectorDB, to: &VectorDB) -> Result<Vec<UpgradeImpact>> {
+    let space = |db: &VectorDB| {
+        let header = db.header();
+        let model = if header.model.is_empty() { DEFAULT_MODEL.to_string() } else { header.model.clone() };
+        format!("{}, {} pooling", model, header.pooling)
+    };
+    if space(from) != space(to) {
+        bail!(
+            "The core indexes were embedded differently ({} vs {}); migrate one with `magector migrate`",
+            space(from),
+            space(to)
+        );
+    }
+
+    let current = CoreClasses::new(from);
+    let customized = customizations(project, root, &current);
+
+    let target = CoreClasses::new(to);
+    let found: Vec<(&String, &CoreClass, Option<&CoreClass>)> = customized
+        .keys()
+        .filter_map(|key| Some((key, current.by_key.get(key)?, target.by_key.get(key))))
+        .collect();
+    let from_paths: HashSet<&str> = found.iter().map(|(_, (_, path), _)| path.as_str()).collect();
+    let to_paths: HashSet<&str> = found.iter().filter_map(|(_, _, t)| Some(t.as_ref()?.1.as_str())).collect();
+    let from_vectors = vectors_of(from, &from_paths);
+    let to_vectors = vectors_of(to, &to_paths);
+
+    let mut report: Vec<UpgradeImpact> = found
+        .into_iter()
+        .map(|(key, (class, path), target)| {
+            let target_path = target.map(|(_, p)| p.clone());
+            let change = target_path.as_ref().and_then(|target_path| {
+                let (a, b) = (from_vectors.get(path.as_str())?, to_vectors.get(target_path.as_str())?);
+                Some((1.0 - a.iter().zip(b.iter()).map(|(x, y)| x * y).sum::<f32>()).max(0.0))
+            });
+            UpgradeImpact {
+                class: class.clone(),
+                path: path.clone(),
+                target_path,
+                change,
+                customizers: customized[key].iter().cloned().collect(),
+            }
+        })
+        .collect();
+    report.sort_by(|a, b| {
+        let rank = |i: &UpgradeImpact| if i.target_path.is_none() { f32::INFINITY } else { i.change.unwrap_or(0.0) };
+        rank(b).total_cmp(&rank(a)).then_with(|| a.class.cmp(&b.class))
+    });
+    Ok(report)
+}
+
+/// FQCN as written and path of a class in a core index
+type CoreClass = (String, String);
+
+/// The classes of a core index
+struct CoreClasses<'a> {
+    /// Lowercase FQCN → class; the first path wins
+    by_key: HashMap<String, CoreClass>,
+    /// Path → lowercase FQCN
+    by_path: HashMap<&'a str, String>,
+    /// Lowercase short name → lowercase FQCNs
+    by_name: HashMap<String, Vec<String>>,
+}
+
+impl<'a> CoreClasses<'a> {
+    fn new(db: &'a VectorDB) -> Self {
+        let mut sorted: BTreeMap<&str, &IndexMetadata> = BTreeMap::new();
+        for (_, meta) in db.metadata_iter() {
+            sorted.entry(meta.path.as_str()).or_insert(meta);
+        }
+        let mut classes = CoreClasses { by_key: HashMap::new(), by_path: HashMap::new(), by_name: HashMap::new() };
+        for (path, meta) in sorted {
+            let Some(key) = class_key(meta) else { continue };
+            classes.by_path.insert(path, key.clone());
+            if classes.by_key.contains_key(&key) {
+                continue;
+            }
+            let name = meta.class_name.clone().unwrap_or_default();
+            let written = match meta.namespace.as_deref() {
+                Some(ns) => format!("{}\\{}", ns, name),
+                None => name.clone(),
+            };
+            classes.by_name.entry(name.to_lowercase()).or_default().push(key.clone());
+            classes.by_key.insert(key, (written, path.to_string()));
+        }
+        classes
+    }
+
+    /// Key of a class named `name` in a file of `namespace`. Imports aren't
+    /// indexed, so an unqualified name not in the file's namespace is taken
+    /// when exactly one core class has it.
+    fn resolve(&self, name: &str, namespace: Option<&str>) -> Option<String> {
+        if let Some(fqcn) = name.strip_prefix('\\') {
+            return Some(fqcn.to_lowercase()).filter(|key| self.by_key.contains_key(key));
+        }
+        let relative = match namespace {
+            Some(ns) => format!("{}\\{}", ns, name).to_lowercase(),
+            None => name.to_lowercase(),
+        };
+        if self.by_key.contains_key(&relative) {
+            return Some(relative);
+        }
+        let absolute = name.to_lowercase();
+        if self.by_key.contains_key(&absolute) {
+            return Some(absolute);
+        }
+        match self.by_name.get(&absolute).map(Vec::as_slice) {
+            Some([only]) if !name.contains('\\') => Some(only.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Core classes (by key) the project customizes, with how. Project files
+/// are those `current` doesn't cover.
+fn customizations(project: &VectorDB, root: &Path, current: &CoreClasses) -> BTreeMap<String, BTreeSet<Customizer>> {
+    let mut customized: BTreeMap<String, BTreeSet<Customizer>> = BTreeMap::new();
+    let mut di_files: BTreeSet<&str> = BTreeSet::new();
+    let mut add = |key: String, kind, class: String, path: &str| {
+        customized.entry(key).or_default().insert(Customizer { kind, class, path: path.to_string() });
+    };
+    for (_, meta) in project.metadata_iter() {
+        if current.by_path.contains_key(meta.path.as_str()) || meta.is_generated {
+            continue;
+        }
+        if meta.path.ends_with("di.xml") {
+            di_files.insert(meta.path.as_str());
+        }
+        let Some(class) = meta.class_name.as_deref() else { continue };
+        let written = match meta.namespace.as_deref() {
+            Some(ns) => format!("{}\\{}", ns, class),
+            None => class.to_string(),
+        };
+        if let Some(ref original) = meta.overrides {
+            let key = current.by_path.get(original.as_str()).cloned().or_else(|| class_key(meta));
+            if let Some(key) = key.filter(|key| current.by_key.contains_key(key)) {
+                add(key, Customization::Override, written.clone(), &meta.path);
+            }
+        }
+        if let Some(parent) = meta.extends.as_deref() {
+            if let Some(key) = current.resolve(parent.trim(), meta.namespace.as_deref()) {
+                add(key, Customization::Extends, written, &meta.path);
+            }
+        }
+    }
+
+    let paths: Vec<PathBuf> = di_files.iter().map(|file| root.join(file)).collect();
+    let xref = ConfigXref::from_files(root, paths.iter().map(PathBuf::as_path), &XmlAnalyzer::new());
+    for preference in xref.di.preferences() {
+        if let Some(key) = current.resolve(&format!("\\{}", preference.for_type), None) {
+            add(key, Customization::Preference, preference.type_name.clone(), &preference.file);
+        }
+    }
+    for (plugin, file) in xref.di.plugins() {
+        let target = format!("\\{}", plugin.target_class.trim().trim_start_matches('\\'));
+        if let Some(key) = current.resolve(&target, None).filter(|_| !plugin.disabled) {
+            add(key, Customization::Plugin, plugin.plugin_class.trim_start_matches('\\').to_string(), file);
+        }
+    }
+    customized
+}
+
+/// File vectors of `paths`
+fn vectors_of(db: &VectorDB, paths: &HashSet<&str>) -> HashMap<String, Vec<f32>> {
+    file_vectors(db, |meta| paths.contains(meta.path.as_str()))
+        .into_iter()
+        .map(|(path, _, vector)| (path, vector))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::EMBEDDING_DIM;
+
+    fn meta(path: &str, namespace: Option<&str>, class: Option<&str>) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: if path.ends_with(".xml") { "xml" } else { "php" }.to_string(),
+            class_name: class.map(str::to_string),
+            namespace: namespace.map(str::to_string),
+            search_text: format!("search {}", path),
+            ..Default::default()
+        }
+    }
+
+    /// Unit vector along `axis`, tilted towards the next axis by `tilt`
+    fn vector(axis: usize, tilt: f32) -> Vec<f32> {
+        let mut v = vec![0.0f32; EMBEDDING_DIM];
+        v[axis] = 1.0;
+        v[axis + 1] = tilt;
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        v.into_iter().map(|x| x / norm).collect()
+    }
+
+    const CART: &str = "vendor/magento/module-checkout/Model/Cart.php";
+    const PRODUCT: &str = "vendor/magento/module-catalog/Model/Product.php";
+    const SESSION: &str = "vendor/magento/module-checkout/Model/Session.php";
+    const LEGACY: &str = "vendor/magento/module-checkout/Model/Legacy.php";
+
+    fn core(tilts: &[(&str, &str, f32)]) -> VectorDB {
+        let mut db = VectorDB::new();
+        db.insert_batch(
+            tilts
+                .iter()
+                .enumerate()
+                .map(|(i, (path, class, tilt))| {
+                    let (namespace, class) = class.rsplit_once('\\').unwrap();
+                    (vector(i * 2, *tilt), meta(path, Some(namespace), Some(class)))
+                })
+                .collect(),
+        );
+        db
+    }
+
+    #[test]
+    fn test_ranks_customized_classes_by_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let di = "app/code/Acme/Checkout/etc/di.xml";
+        std::fs::create_dir_all(dir.path().join("app/code/Acme/Checkout/etc")).unwrap();
+        std::fs::write(
+            dir.path().join(di),
+            r#"<config>
+    <preference for="Magento\Checkout\Model\Session" type="Acme\Checkout\Model\Session"/>
+    <type name="Magento\Checkout\Model\Legacy">
+        <plugin name="acme_legacy" type="Acme\Checkout\Plugin\Legacy"/>
+    </type>
+    <type name="Magento\Catalog\Model\Product">
+        <plugin name="off" type="Acme\Checkout\Plugin\Off" disabled="true"/>
+    </type>
+</config>"#,
+        )
+        .unwrap();
+
+        let from = core(&[
+            (CART, "Magento\\Checkout\\Model\\Cart", 0.0),
+            (PRODUCT, "Magento\\Catalog\\Model\\Product", 0.0),
+            (SESSION, "Magento\\Checkout\\Model\\Session", 0.0),
+            (LEGACY, "Magento\\Checkout\\Model\\Legacy", 0.0),
+        ]);
+        // Cart changed a lot, Session a little, Product not at all, Legacy is gone
+        let to = core(&[
+            (CART, "Magento\\Checkout\\Model\\Cart", 1.0),
+            (PRODUCT, "Magento\\Catalog\\Model\\Product", 0.0),
+            (SESSION, "Magento\\Checkout\\Model\\Session", 0.1),
+        ]);
+
+        let mut project = VectorDB::new();
+        project.insert_batch(vec![
+            (
+                vector(100, 0.0),
+                IndexMetadata {
+                    overrides: Some(CART.to_string()),
+                    ..meta("app/code/Magento/Checkout/Model/Cart.php", Some("Magento\\Checkout\\Model"), Some("Cart"))
+                },
+            ),
+            (
+                vector(102, 0.0),
+                IndexMetadata {
+                    extends: Some("\\Magento\\Catalog\\Model\\Product".to_string()),
+                    ..meta("app/code/Acme/Catalog/Model/Product.php", Some("Acme\\Catalog\\Model"), Some("Product"))
+                },
+            ),
+            (vector(104, 0.0), meta(di, None, None)),
+            // Core files in the project index aren't customizations
+            (
+                vector(106, 0.0),
+                IndexMetadata { extends: Some("Cart".to_string()), ..meta(SESSION, Some("Magento\\Checkout\\Model"), Some("Session")) },
+            ),
+        ]);
+
+        let report = impact(&project, dir.path(), &from, &to).unwrap();
+        let classes: Vec<&str> = report.iter().map(|i| i.class.as_str()).collect();
+        assert_eq!(
+            classes,
+            [
+                "Magento\\Checkout\\Model\\Legacy",
+                "Magento\\Checkout\\Model\\Cart",
+                "Magento\\Checkout\\Model\\Session",
+                "Magento\\Catalog\\Model\\Product"
+            ]
+        );
+        assert_eq!((report[0].target_path.as_deref(), report[0].change), (None, None));
+        assert_eq!(report[0].customizers[0].kind, Customization::Plugin);
+        assert_eq!(report[0].customizers[0].class, "Acme\\Checkout\\Plugin\\Legacy");
+        assert_eq!(report[1].customizers[0].kind, Customization::Override);
+        assert!(report[1].change.unwrap() > report[2].change.unwrap());
+        assert_eq!(report[2].customizers[0].kind, Customization::Preference);
+        assert_eq!(report[2].customizers[0].path, di);
+        assert!(report[3].change.unwrap() < 1e-5);
+        assert_eq!(report[3].customizers.len(), 1, "the disabled plugin is left out");
+        assert_eq!(report[3].customizers[0].kind, Customization::Extends);
+
+        let mut other = core(&[(CART, "Magento\\Checkout\\Model\\Cart", 0.0)]);
+        other.set_header(crate::vectordb::IndexHeader { model: "other-model".to_string(), ..other.header().clone() });
+        assert!(impact(&project, dir.path(), &from, &other).is_err());
+    }
+}
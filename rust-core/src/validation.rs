@@ -5,12 +5,14 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::Path;
 use std::time::Instant;
 
-use crate::Indexer;
+use crate::magento::split_camel_case;
+use crate::sona::SonaSignal;
+use crate::{Indexer, IndexMetadata};
 
 /// A single validation test case
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,8 +21,15 @@ pub struct TestCase {
     pub query: String,
     pub category: String,
     pub expected_patterns: Vec<String>,      // Patterns that SHOULD match
+    #[serde(default)]
     pub unexpected_patterns: Vec<String>,    // Patterns that should NOT match
+    #[serde(default)]
     pub min_score: f32,                       // Minimum expected score
+    /// Negative case: the top score must stay below this, for queries the
+    /// codebase has no answer to (WordPress hooks, Shopify Liquid)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_score: Option<f32>,
+    #[serde(default)]
     pub description: String,
 }
 
@@ -59,6 +68,9 @@ pub struct ValidationReport {
     pub recommendations: Vec<String>,
     pub total_time_ms: u64,
     pub index_size: usize,
+    /// Paraphrase stability (`validate --paraphrase`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub robustness: Option<RobustnessReport>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -81,6 +93,93 @@ impl Validator {
         }
     }
 
+    /// Create validator with the test cases of a YAML file: a list of
+    /// `{id, query, category, expected_patterns}` entries, optionally with
+    /// `unexpected_patterns`, `min_score`, `max_score` and `description`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let test_cases: Vec<TestCase> =
+            serde_yaml::from_str(&text).with_context(|| format!("Invalid test cases in {}", path.display()))?;
+        if test_cases.is_empty() {
+            anyhow::bail!("No test cases in {}", path.display());
+        }
+        Ok(Self { test_cases })
+    }
+
+    /// Create validator with the given test cases
+    pub fn with_cases(test_cases: Vec<TestCase>) -> Self {
+        Self { test_cases }
+    }
+
+    pub fn test_cases(&self) -> &[TestCase] {
+        &self.test_cases
+    }
+
+    /// Run every test case without printing, in test case order
+    pub fn evaluate(&self, indexer: &mut Indexer) -> Result<Vec<TestResult>> {
+        self.test_cases.iter().map(|test| self.evaluate_case(indexer, test)).collect()
+    }
+
+    /// Run one test case without printing
+    pub fn evaluate_case(&self, indexer: &mut Indexer, test: &TestCase) -> Result<TestResult> {
+        let test_start = Instant::now();
+        let search_results = indexer.search(&test.query, 20)?;
+        Ok(self.analyze_results(test, &search_results, test_start.elapsed().as_millis() as u64))
+    }
+
+    /// Synthetic SONA feedback for every failed result, as if the user had
+    /// followed the search with the tool that finds what it missed
+    pub fn failure_signals(&self, results: &[TestResult], timestamp: u64) -> Vec<SonaSignal> {
+        results
+            .iter()
+            .filter(|r| !r.passed)
+            .filter_map(|result| {
+                // A too confident negative case has nothing to refine to
+                let case = self.test_cases.iter().find(|c| c.id == result.test_id && c.max_score.is_none())?;
+                let (signal_type, tool) = refinement_for(case, result);
+                Some(SonaSignal {
+                    signal_type: signal_type.to_string(),
+                    query: case.query.clone(),
+                    timestamp,
+                    search_result_paths: result.top_results.iter().map(|r| r.path.clone()).collect(),
+                    followed_tool: Some(tool.to_string()),
+                    followed_args: Some(serde_json::json!({
+                        "test_id": case.id,
+                        "expected": result.missed_expected,
+                    })),
+                    original_query: None,
+                    refined_query: None,
+                    original_result_paths: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Search the paraphrases of every case ([`paraphrases`]) and compare
+    /// their top results with those of the case's own query
+    pub fn robustness(&self, indexer: &mut Indexer) -> Result<RobustnessReport> {
+        let mut checks = Vec::new();
+        // Negative cases have no top results worth keeping
+        for test in self.test_cases.iter().filter(|t| t.max_score.is_none()) {
+            let variants = paraphrases(&test.query);
+            if variants.is_empty() {
+                continue;
+            }
+            let original = top_paths(&indexer.search(&test.query, 20)?);
+            for paraphrase in variants {
+                let top = top_paths(&indexer.search(&paraphrase, 20)?);
+                checks.push(ParaphraseCheck {
+                    test_id: test.id.clone(),
+                    category: test.category.clone(),
+                    query: test.query.clone(),
+                    stability: stability(&original, &top),
+                    paraphrase,
+                });
+            }
+        }
+        Ok(RobustnessReport::from_checks(checks))
+    }
+
     /// Get comprehensive test cases (90+ cases)
     fn get_comprehensive_test_cases() -> Vec<TestCase> {
         let mut cases = Vec::new();
@@ -96,6 +195,7 @@ impl Validator {
                 expected_patterns: expected.iter().map(|s| s.to_string()).collect(),
                 unexpected_patterns: unexpected.iter().map(|s| s.to_string()).collect(),
                 min_score,
+                max_score: None,
                 description: desc.to_string(),
             });
         };
@@ -2951,6 +3051,29 @@ impl Validator {
             &[],
             0.3, "Structured data on product pages");
 
+        // ==================== NEGATIVE TESTS ====================
+        // Other platforms' concepts: nothing in Magento answers these, so no
+        // result should look like a confident match
+        let mut add_negative = |query: &str, max_score: f32, desc: &str| {
+            cases.push(TestCase {
+                id: format!("NEG{:03}", cases.iter().filter(|c| c.max_score.is_some()).count() + 1),
+                query: query.to_string(),
+                category: "negative".to_string(),
+                expected_patterns: Vec::new(),
+                unexpected_patterns: Vec::new(),
+                min_score: 0.0,
+                max_score: Some(max_score),
+                description: desc.to_string(),
+            });
+        };
+
+        add_negative("wordpress add_action hook in functions.php theme", 0.6,
+            "WordPress theme hooks");
+        add_negative("shopify liquid section schema for storefront theme", 0.6,
+            "Shopify Liquid sections");
+        add_negative("woocommerce cart fragments ajax refresh", 0.6,
+            "WooCommerce cart fragments");
+
         cases
     }
 
@@ -2966,6 +3089,11 @@ impl Validator {
         println!("{}", "═".repeat(60).bright_blue());
         println!("\nRunning {} test cases...\n", total.to_string().cyan());
 
+        let partial_warning = indexer.index_header().partial_warning();
+        if let Some(ref warning) = partial_warning {
+            println!("{} {}\n", "⚠".yellow(), warning.yellow());
+        }
+
         for (i, test) in self.test_cases.iter().enumerate() {
             let test_start = Instant::now();
 
@@ -3005,6 +3133,9 @@ impl Validator {
                 if !result.matched_unexpected.is_empty() {
                     println!("        {} Unexpected: {:?}", "→".yellow(), result.matched_unexpected);
                 }
+                if let Some(max) = test.max_score.filter(|&max| result.score >= max) {
+                    println!("        {} Too confident: {:.3} (max {:.3})", "→".yellow(), result.score, max);
+                }
                 if !result.top_results.is_empty() {
                     println!("        {} Top result: {}", "→".yellow(), result.top_results[0].path);
                 }
@@ -3024,7 +3155,10 @@ impl Validator {
         }
 
         // Generate recommendations
-        let recommendations = self.generate_recommendations(&results, &categories);
+        let mut recommendations = self.generate_recommendations(&results, &categories);
+        if let Some(warning) = partial_warning {
+            recommendations.insert(0, warning);
+        }
 
         let report = ValidationReport {
             total_tests: total,
@@ -3036,6 +3170,7 @@ impl Validator {
             recommendations,
             total_time_ms: start_time.elapsed().as_millis() as u64,
             index_size: indexer.stats().vectors_created,
+            robustness: None,
         };
 
         // Print summary
@@ -3097,15 +3232,20 @@ impl Validator {
 
         let passed = expected_ratio >= 0.5
             && matched_unexpected.is_empty()
-            && score >= test.min_score;
+            && score >= test.min_score
+            && test.max_score.is_none_or(|max| score < max);
 
+        let bounds = match test.max_score {
+            Some(max) => format!("min: {:.3}, max: {:.3}", test.min_score, max),
+            None => format!("min: {:.3}", test.min_score),
+        };
         let details = format!(
-            "Expected: {}/{}, Unexpected: {}, Score: {:.3} (min: {:.3})",
+            "Expected: {}/{}, Unexpected: {}, Score: {:.3} ({})",
             matched_expected.len(),
             test.expected_patterns.len(),
             matched_unexpected.len(),
             score,
-            test.min_score
+            bounds
         );
 
         TestResult {
@@ -3149,7 +3289,7 @@ impl Validator {
             }
 
             let mut sorted_failures: Vec<_> = failure_patterns.into_iter().collect();
-            sorted_failures.sort_by(|a, b| b.1.cmp(&a.1));
+            sorted_failures.sort_by_key(|b| std::cmp::Reverse(b.1));
 
             for (pattern, count) in sorted_failures.iter().take(5) {
                 if *count > 2 {
@@ -3255,3 +3395,466 @@ impl Default for Validator {
         Self::new()
     }
 }
+
+/// The refinement a user would make after `result` missed what `case`
+/// expects, judged from the category and the missed patterns: signal type
+/// and the MCP tool that produces it
+fn refinement_for(case: &TestCase, result: &TestResult) -> (&'static str, &'static str) {
+    let missed = if result.missed_expected.is_empty() { &case.expected_patterns } else { &result.missed_expected };
+    let haystack = format!("{} {}", case.category, missed.join(" ")).to_lowercase();
+    if haystack.contains("plugin") {
+        ("refinement_to_plugin", "magento_find_plugin")
+    } else if haystack.contains("observer") {
+        ("refinement_to_observer", "magento_find_observer")
+    } else if haystack.contains("controller") {
+        ("refinement_to_controller", "magento_find_controller")
+    } else if haystack.contains("block") {
+        ("refinement_to_block", "magento_find_block")
+    } else if haystack.contains(".xml") || haystack.contains("config") {
+        ("refinement_to_config", "magento_find_config")
+    } else {
+        ("refinement_to_class", "magento_find_class")
+    }
+}
+
+/// Candidate test cases for `n` indexed files, for a human to curate.
+///
+/// Files are grouped by magento_type and the types take turns, so a rare type
+/// gets as many cases as a common one until it runs out of files. Within a
+/// type the picks are spread evenly over the files in path order, which keeps
+/// the output stable for the same index. The query is built from the file's
+/// module, namespace, class name and type; the expected pattern is its path.
+pub fn generate_cases<'a>(items: impl IntoIterator<Item = &'a IndexMetadata>, n: usize) -> Vec<TestCase> {
+    let mut by_type: BTreeMap<&str, BTreeMap<&str, &IndexMetadata>> = BTreeMap::new();
+    for meta in items {
+        let mtype = meta.magento_type.as_deref().unwrap_or(&meta.file_type);
+        by_type.entry(mtype).or_default().entry(&meta.path).or_insert(meta);
+    }
+
+    let mut quotas: Vec<usize> = vec![0; by_type.len()];
+    let mut left = n;
+    while left > 0 {
+        let before = left;
+        for (quota, files) in quotas.iter_mut().zip(by_type.values()) {
+            if left > 0 && *quota < files.len() {
+                *quota += 1;
+                left -= 1;
+            }
+        }
+        if left == before {
+            break;
+        }
+    }
+
+    let mut cases = Vec::new();
+    for ((mtype, files), quota) in by_type.iter().zip(quotas) {
+        let files: Vec<&IndexMetadata> = files.values().copied().collect();
+        for i in 0..quota {
+            let meta = files[i * files.len() / quota];
+            cases.push(TestCase {
+                id: format!("GEN{:03}", cases.len() + 1),
+                query: generated_query(meta, mtype),
+                category: mtype.to_string(),
+                expected_patterns: vec![meta.path.clone()],
+                unexpected_patterns: Vec::new(),
+                min_score: 0.0,
+                max_score: None,
+                description: format!("Generated from {}", meta.path),
+            });
+        }
+    }
+    cases
+}
+
+/// Lowercase words describing a file: module, namespace below the vendor,
+/// class name (or the last path components) and type
+fn generated_query(meta: &IndexMetadata, mtype: &str) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if let Some(module) = &meta.module {
+        parts.push(module.rsplit('_').next().unwrap_or(module).to_string());
+    }
+    match (&meta.namespace, &meta.class_name) {
+        (namespace, Some(class)) => {
+            if let Some(ns) = namespace {
+                parts.extend(ns.split('\\').skip(2).map(str::to_string));
+            }
+            parts.push(class.clone());
+        }
+        (_, None) => {
+            let stem = meta.path.rsplit_once('.').map_or(meta.path.as_str(), |(stem, _)| stem);
+            let components: Vec<&str> = stem.split('/').collect();
+            let tail = &components[components.len().saturating_sub(3)..];
+            parts.extend(tail.iter().map(|c| c.to_string()));
+        }
+    }
+    parts.push(mtype.to_string());
+
+    let mut words: Vec<String> = Vec::new();
+    for part in &parts {
+        for word in split_camel_case(part).split(|c: char| !c.is_alphanumeric()) {
+            if !word.is_empty() && !words.iter().any(|w| w == word) {
+                words.push(word.to_string());
+            }
+        }
+    }
+    words.join(" ")
+}
+
+/// Write test cases as YAML, in the format [`Validator::from_file`] reads
+pub fn save_cases(cases: &[TestCase], path: &Path) -> Result<()> {
+    let yaml = serde_yaml::to_string(cases)?;
+    fs::write(path, yaml).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Magento domain synonyms, shared with the MCP server's query expansion
+const SYNONYMS_JSON: &str = include_str!("../../config/synonyms.json");
+
+/// Substitutions for paraphrases: each word and the first synonym the MCP
+/// server's query expansion adds for it
+static PARAPHRASE_SYNONYMS: std::sync::LazyLock<HashMap<String, String>> = std::sync::LazyLock::new(|| {
+    let synonyms: HashMap<String, Vec<String>> =
+        serde_json::from_str(SYNONYMS_JSON).expect("config/synonyms.json is valid");
+    synonyms
+        .into_iter()
+        .filter_map(|(word, list)| Some((word, list.into_iter().next()?)))
+        .collect()
+});
+
+/// Paraphrases per query
+const MAX_PARAPHRASES: usize = 4;
+
+/// Top results compared between a query and its paraphrases
+const STABLE_TOP: usize = 5;
+
+/// Paraphrases keeping fewer of the top results than this are brittle
+const BRITTLE_STABILITY: f32 = 0.4;
+
+/// Simple paraphrases of a query a user could as well have typed: the
+/// words reversed, the first word moved to the end, and one dictionary
+/// synonym substituted at a time
+pub fn paraphrases(query: &str) -> Vec<String> {
+    let words: Vec<&str> = query.split_whitespace().collect();
+    let mut variants: Vec<String> = Vec::new();
+    if words.len() >= 2 {
+        variants.push(words.iter().rev().copied().collect::<Vec<_>>().join(" "));
+    }
+    if words.len() >= 3 {
+        variants.push(words[1..].iter().chain(&words[..1]).copied().collect::<Vec<_>>().join(" "));
+    }
+    for (i, word) in words.iter().enumerate() {
+        let key = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        if let Some(synonym) = PARAPHRASE_SYNONYMS.get(&key) {
+            let mut substituted = words.clone();
+            substituted[i] = synonym;
+            variants.push(substituted.join(" "));
+        }
+    }
+    let mut seen = vec![query.to_lowercase()];
+    variants.retain(|v| {
+        let lower = v.to_lowercase();
+        !seen.contains(&lower) && {
+            seen.push(lower);
+            true
+        }
+    });
+    variants.truncate(MAX_PARAPHRASES);
+    variants
+}
+
+/// Distinct paths of the first [`STABLE_TOP`] files of a result list
+fn top_paths(results: &[crate::SearchResult]) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for r in results {
+        if paths.len() == STABLE_TOP {
+            break;
+        }
+        if !paths.contains(&r.metadata.path) {
+            paths.push(r.metadata.path.clone());
+        }
+    }
+    paths
+}
+
+/// Share of `original`'s top paths a paraphrase's top paths keep (1 when
+/// the original found nothing)
+fn stability(original: &[String], paraphrase: &[String]) -> f32 {
+    if original.is_empty() {
+        return 1.0;
+    }
+    original.iter().filter(|p| paraphrase.contains(p)).count() as f32 / original.len() as f32
+}
+
+/// One paraphrase of a test case and how much of its top results it kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParaphraseCheck {
+    pub test_id: String,
+    pub category: String,
+    pub query: String,
+    pub paraphrase: String,
+    /// Share (0–1) of the query's top results among the paraphrase's
+    pub stability: f32,
+}
+
+/// Paraphrase robustness of a validation suite
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RobustnessReport {
+    /// Mean stability over every paraphrase
+    pub score: f32,
+    /// Mean stability per test category
+    pub categories: BTreeMap<String, f32>,
+    /// Paraphrases below the brittleness threshold, least stable first
+    pub brittle: Vec<ParaphraseCheck>,
+    /// Every paraphrase checked
+    pub checks: Vec<ParaphraseCheck>,
+}
+
+impl RobustnessReport {
+    pub fn from_checks(checks: Vec<ParaphraseCheck>) -> Self {
+        let mean = |values: &[f32]| if values.is_empty() { 1.0 } else { values.iter().sum::<f32>() / values.len() as f32 };
+        let mut by_category: BTreeMap<String, Vec<f32>> = BTreeMap::new();
+        for check in &checks {
+            by_category.entry(check.category.clone()).or_default().push(check.stability);
+        }
+        let all: Vec<f32> = checks.iter().map(|c| c.stability).collect();
+        let mut brittle: Vec<ParaphraseCheck> = checks.iter().filter(|c| c.stability < BRITTLE_STABILITY).cloned().collect();
+        brittle.sort_by(|a, b| a.stability.partial_cmp(&b.stability).unwrap_or(std::cmp::Ordering::Equal));
+        Self {
+            score: mean(&all),
+            categories: by_category.into_iter().map(|(category, values)| (category, mean(&values))).collect(),
+            brittle,
+            checks,
+        }
+    }
+
+    pub fn print(&self) {
+        println!("\n{}", "Paraphrase Robustness:".bold());
+        println!("  {} paraphrases, mean stability {:.2}", self.checks.len().to_string().cyan(), self.score);
+        let mut categories: Vec<_> = self.categories.iter().collect();
+        categories.sort_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal));
+        for (category, score) in categories {
+            let score_str = format!("{:.2}", score);
+            let colored = if *score >= 0.8 {
+                score_str.bright_green()
+            } else if *score >= 0.5 {
+                score_str.yellow()
+            } else {
+                score_str.red()
+            };
+            println!("  {:20} {}", category.cyan(), colored);
+        }
+        if !self.brittle.is_empty() {
+            println!("\n  {} brittle paraphrases (least stable first):", self.brittle.len());
+            for check in self.brittle.iter().take(10) {
+                println!(
+                    "  {} {} \"{}\" → \"{}\" ({:.2})",
+                    "✗".red(),
+                    check.test_id.yellow(),
+                    check.query,
+                    check.paraphrase,
+                    check.stability
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(id: &str, category: &str, expected: &[&str]) -> TestCase {
+        TestCase {
+            id: id.to_string(),
+            query: format!("query {}", id),
+            category: category.to_string(),
+            expected_patterns: expected.iter().map(|s| s.to_string()).collect(),
+            unexpected_patterns: Vec::new(),
+            min_score: 0.0,
+            max_score: None,
+            description: String::new(),
+        }
+    }
+
+    fn result(id: &str, passed: bool, missed: &[&str]) -> TestResult {
+        TestResult {
+            test_id: id.to_string(),
+            query: String::new(),
+            passed,
+            score: 0.0,
+            matched_expected: Vec::new(),
+            missed_expected: missed.iter().map(|s| s.to_string()).collect(),
+            matched_unexpected: Vec::new(),
+            top_results: vec![SearchResultSummary {
+                path: "Model/Other.php".to_string(),
+                score: 0.5,
+                magento_type: None,
+                class_name: None,
+            }],
+            execution_time_ms: 0,
+            details: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_failure_signals() {
+        let validator = Validator {
+            test_cases: vec![
+                case("T1", "plugin", &["Plugin"]),
+                case("T2", "checkout", &["etc/di.xml"]),
+                case("T3", "model", &["Model/Product"]),
+                case("T4", "model", &["Model/Quote"]),
+            ],
+        };
+        let results = [
+            result("T1", false, &["Plugin"]),
+            result("T2", false, &["etc/di.xml"]),
+            result("T3", true, &[]),
+            result("T4", false, &["Model/Quote"]),
+        ];
+        let signals = validator.failure_signals(&results, 42);
+        let types: Vec<&str> = signals.iter().map(|s| s.signal_type.as_str()).collect();
+        assert_eq!(types, ["refinement_to_plugin", "refinement_to_config", "refinement_to_class"]);
+        assert_eq!(signals[0].query, "query T1");
+        assert_eq!(signals[0].timestamp, 42);
+        assert_eq!(signals[0].search_result_paths, ["Model/Other.php"]);
+        assert_eq!(signals[1].followed_tool.as_deref(), Some("magento_find_config"));
+        assert_eq!(signals[2].followed_args.as_ref().unwrap()["expected"][0], "Model/Quote");
+    }
+
+    fn meta(path: &str, mtype: &str, class: Option<&str>) -> IndexMetadata {
+        IndexMetadata {
+            path: path.to_string(),
+            file_type: "php".to_string(),
+            magento_type: Some(mtype.to_string()),
+            class_name: class.map(str::to_string),
+            namespace: class.map(|_| "Magento\\Catalog\\Model\\Product".to_string()),
+            module: Some("Magento_Catalog".to_string()),
+            search_text: String::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_generate_cases() {
+        let mut items: Vec<IndexMetadata> = (0..6)
+            .map(|i| meta(&format!("Model/Product/Type{}.php", i), "model", Some(&format!("PriceType{}", i))))
+            .collect();
+        // A second chunk of the same file is one candidate
+        items.push(items[0].clone());
+        items.push(meta("etc/frontend/di.xml", "di_config", None));
+
+        let cases = generate_cases(&items, 4);
+        assert_eq!(cases.len(), 4);
+        let categories: Vec<&str> = cases.iter().map(|c| c.category.as_str()).collect();
+        assert_eq!(categories, ["di_config", "model", "model", "model"]);
+        assert_eq!(cases[0].query, "catalog etc frontend di config");
+        assert_eq!(cases[0].expected_patterns, ["etc/frontend/di.xml"]);
+        assert_eq!(cases[1].query, "catalog model product price type0");
+        let picked: Vec<&str> = cases[1..].iter().map(|c| c.expected_patterns[0].as_str()).collect();
+        assert_eq!(picked, ["Model/Product/Type0.php", "Model/Product/Type2.php", "Model/Product/Type4.php"]);
+        assert_eq!(cases[3].id, "GEN004");
+
+        assert_eq!(generate_cases(&items, 100).len(), 7);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("generated.yaml");
+        save_cases(&cases, &path).unwrap();
+        assert_eq!(Validator::from_file(&path).unwrap().test_cases().len(), 4);
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cases.yaml");
+        fs::write(
+            &path,
+            r#"
+- id: CUSTOM001
+  query: vendor fee total collector
+  category: totals
+  expected_patterns: [Fee, Total]
+- id: CUSTOM002
+  query: checkout agreements plugin
+  category: plugin
+  expected_patterns: ["Plugin"]
+  unexpected_patterns: ["Test/"]
+  min_score: 0.4
+"#,
+        )
+        .unwrap();
+        let validator = Validator::from_file(&path).unwrap();
+        let cases = validator.test_cases();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].expected_patterns, vec!["Fee", "Total"]);
+        assert!(cases[0].unexpected_patterns.is_empty());
+        assert_eq!(cases[1].min_score, 0.4);
+
+        fs::write(&path, "[]").unwrap();
+        assert!(Validator::from_file(&path).is_err());
+        fs::write(&path, "- id: X\n  query: q\n").unwrap();
+        assert!(Validator::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_paraphrase_robustness() {
+        assert_eq!(
+            paraphrases("product price indexer"),
+            [
+                "indexer price product",
+                "price indexer product",
+                "catalog product price indexer",
+                "product price reindex",
+            ]
+        );
+        // Nothing to reorder or substitute
+        assert!(paraphrases("wishlist").is_empty());
+        assert_eq!(paraphrases("checkout"), ["cart"]);
+
+        let original: Vec<String> = ["a", "b", "c", "d"].iter().map(|p| p.to_string()).collect();
+        assert_eq!(stability(&original, &original[1..]), 0.75);
+        assert_eq!(stability(&[], &original), 1.0);
+
+        let check = |id: &str, category: &str, stability: f32| ParaphraseCheck {
+            test_id: id.to_string(),
+            category: category.to_string(),
+            query: format!("query {}", id),
+            paraphrase: format!("{} query", id),
+            stability,
+        };
+        let report = RobustnessReport::from_checks(vec![
+            check("T1", "plugin", 1.0),
+            check("T1", "plugin", 0.6),
+            check("T2", "cron", 0.2),
+            check("T3", "cron", 0.0),
+        ]);
+        assert!((report.score - 0.45).abs() < 1e-6);
+        assert!((report.categories["plugin"] - 0.8).abs() < 1e-6);
+        assert!((report.categories["cron"] - 0.1).abs() < 1e-6);
+        let brittle: Vec<&str> = report.brittle.iter().map(|c| c.test_id.as_str()).collect();
+        assert_eq!(brittle, ["T3", "T2"]);
+    }
+
+    #[test]
+    fn test_negative_cases() {
+        let negative = TestCase {
+            max_score: Some(0.6),
+            ..case("NEG001", "negative", &[])
+        };
+        let validator = Validator { test_cases: vec![negative.clone()] };
+        let hits = |score: f32| vec![crate::SearchResult::new(1, score, meta("Model/Theme.php", "model", Some("Theme")))];
+
+        let quiet = validator.analyze_results(&negative, &hits(0.42), 0);
+        assert!(quiet.passed, "{}", quiet.details);
+        assert!(quiet.details.ends_with("(min: 0.000, max: 0.600)"));
+        let confident = validator.analyze_results(&negative, &hits(0.71), 0);
+        assert!(!confident.passed);
+        assert!(validator.analyze_results(&negative, &[], 0).passed);
+        // Nothing to learn from a too confident answer
+        assert!(validator.failure_signals(&[confident], 0).is_empty());
+
+        assert!(Validator::new().test_cases().iter().any(|c| c.max_score.is_some() && c.expected_patterns.is_empty()));
+        let yaml = "- id: NEG\n  query: shopify liquid\n  category: negative\n  expected_patterns: []\n  max_score: 0.5\n";
+        let parsed: Vec<TestCase> = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(parsed[0].max_score, Some(0.5));
+    }
+}
@@ -4,13 +4,16 @@
 
 use anyhow::{Context, Result};
 use hnsw_rs::prelude::*;
-use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::BufWriter;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::embedder::EMBEDDING_DIM;
+use crate::embedder::{Pooling, EMBEDDING_DIM};
+use crate::idf::TermStats;
 
 /// Default HNSW parameters
 const HNSW_M: usize = 32;             // max connections per node
@@ -18,6 +21,33 @@ const HNSW_MAX_LAYER: usize = 16;
 const HNSW_EF_CONSTRUCTION: usize = 200;
 const HNSW_MIN_CAPACITY: usize = 1_000;
 
+/// Candidates fetched per result from a coarse (truncated) graph before
+/// reranking with the full vectors
+const COARSE_OVERSAMPLE: usize = 4;
+
+/// Files taken from the file-level graph per requested result in two-stage
+/// search; all their chunks are then scored
+const FILE_CANDIDATES_PER_RESULT: usize = 2;
+
+/// HNSW beam widths [`VectorDB::tune_ef_search`] chooses from, narrowest
+/// first; the last one is the reference the others are compared with
+pub const EF_SEARCH_STEPS: [usize; 5] = [32, 48, 64, 96, 128];
+
+/// Mean share of the reference beam's candidates a tuned beam must find
+pub const EF_STABILITY_TARGET: f32 = 0.95;
+
+/// Largest difference of a vector's length from 1 that still counts as
+/// normalized
+const NORM_TOLERANCE: f32 = 1e-3;
+
+/// Neighbours fetched when checking that the graph finds an item from its
+/// own vector
+const GRAPH_CHECK_K: usize = 10;
+
+/// Share of checked items the graph may miss before it counts as damaged:
+/// HNSW search is approximate, so a few misses are normal
+const UNREACHABLE_TOLERANCE: f32 = 0.01;
+
 /// Check whether a vector is safe for cosine distance computation.
 /// Rejects NaN, Inf, and zero vectors — these produce NaN distances
 /// that corrupt the HNSW graph structure.
@@ -32,8 +62,22 @@ fn is_valid_vector(v: &[f32]) -> bool {
     norm_sq > 1e-12
 }
 
+/// Cosine distance matching `DistCosine` (1 - cosine similarity)
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut na, mut nb) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+    }
+    if na <= 0.0 || nb <= 0.0 {
+        return 1.0;
+    }
+    1.0 - dot / (na.sqrt() * nb.sqrt())
+}
+
 /// Metadata associated with each indexed item
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct IndexMetadata {
     pub path: String,
     pub file_type: String,
@@ -61,6 +105,354 @@ pub struct IndexMetadata {
     pub is_mixin: bool,
     pub js_dependencies: Vec<String>,
     pub search_text: String,
+    /// Where the item sits in its file; `None` for items indexed before
+    /// spans were recorded
+    pub span: Option<SourceSpan>,
+    /// One-line structural summary of the file (`index --summaries`)
+    pub summary: Option<String>,
+    /// Code written by Magento's code generator (interceptors, factories,
+    /// proxies) or found under `generated/`; ranked below source classes
+    pub is_generated: bool,
+    /// Vendor file of the class this file is a copy of (see
+    /// [`crate::overrides`]); set for `app/code` overrides only
+    pub overrides: Option<String>,
+    /// Size and age of the file; `None` for items indexed before metrics
+    /// were recorded
+    pub metrics: Option<FileMetrics>,
+    /// Time of the last git commit touching the file, in seconds since the
+    /// Unix epoch (`index --git-times`)
+    pub last_commit: Option<u64>,
+    /// Who owns the file: its CODEOWNERS entry, else its most frequent git
+    /// author (`index --owners`)
+    pub owners: Vec<String>,
+    /// Collection the item belongs to: source code, or project
+    /// documentation indexed with `index --include-docs`
+    pub collection: Collection,
+    /// Heading breadcrumbs of a documentation section, outermost first;
+    /// empty for code
+    pub headings: Vec<String>,
+    /// URLs reaching a controller action, from routes.xml
+    /// (`/catalog/product/view`); empty for anything else
+    pub urls: Vec<String>,
+    /// Constants and enum cases the file declares, with their values
+    pub constants: Vec<ClassConstant>,
+    /// Fully qualified names of the traits the class uses
+    pub traits: Vec<String>,
+    /// Methods the class gets from its traits (and their traits), resolved
+    /// against the indexed traits after parsing; see
+    /// [`crate::traits::trait_methods`]
+    pub trait_methods: Vec<String>,
+    /// Magento types (`controller`, `model`, ...) the class gets from an
+    /// ancestor rather than its own declaration, set after parsing; the
+    /// matching `is_*` flags are set too. See
+    /// [`crate::inheritance::inherited_types`]
+    pub inherited_types: Vec<String>,
+    /// File of the class di.xml prefers for an API interface, set after
+    /// parsing (see [`crate::implementations`])
+    pub implementation: Option<String>,
+    /// Name of the extra source root the file was indexed from
+    /// (`index --extra-root`); `None` for the Magento root
+    pub root: Option<String>,
+}
+
+/// A class constant or enum case and its value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassConstant {
+    pub name: String,
+    /// Value as written, string literals without their quotes; `None` for
+    /// enum cases without a value
+    pub value: Option<String>,
+    /// The value is a string literal
+    pub is_string: bool,
+    /// An enum case rather than a `const`
+    pub is_case: bool,
+}
+
+impl ClassConstant {
+    /// A string value worth searching for: event names, status codes and
+    /// config paths rather than separators or single letters
+    pub fn is_notable(&self) -> bool {
+        self.is_string
+            && self.value.as_deref().is_some_and(|v| v.len() >= 3 && v.chars().any(|c| c.is_alphabetic()))
+    }
+}
+
+/// Location of an indexed item in its source file. Lines are 1-based and
+/// inclusive; bytes are a half-open range into the decoded UTF-8 text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceSpan {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+impl SourceSpan {
+    /// Span of `source[start_byte..end_byte]`
+    pub fn of_range(source: &str, start_byte: usize, end_byte: usize) -> Self {
+        let end_byte = end_byte.min(source.len());
+        let start_byte = start_byte.min(end_byte);
+        let line_at = |byte: usize| source.as_bytes()[..byte].iter().filter(|&&b| b == b'\n').count() + 1;
+        // A trailing newline belongs to the last line, not a new one
+        let last = if end_byte > start_byte && source.as_bytes()[end_byte - 1] == b'\n' { end_byte - 1 } else { end_byte };
+        Self { start_line: line_at(start_byte), end_line: line_at(last), start_byte, end_byte }
+    }
+
+    /// Span of a whole file
+    pub fn whole(source: &str) -> Self {
+        Self::of_range(source, 0, source.len())
+    }
+}
+
+/// Size, age and encoding of an indexed file, taken when it was parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMetrics {
+    /// Non-blank lines
+    pub loc: usize,
+    /// Size on disk
+    pub bytes: u64,
+    /// Last modification, in seconds since the Unix epoch
+    pub modified: Option<u64>,
+    /// The file is not UTF-8 and was decoded from a legacy encoding
+    pub lossy: bool,
+}
+
+impl FileMetrics {
+    /// Metrics of a decoded file; size and mtime come from `file` when given
+    pub fn measure(text: &str, lossy: bool, file: Option<&fs::Metadata>) -> Self {
+        Self {
+            loc: text.lines().filter(|line| !line.trim().is_empty()).count(),
+            bytes: file.map_or(text.len() as u64, fs::Metadata::len),
+            modified: file
+                .and_then(|f| f.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+            lossy,
+        }
+    }
+}
+
+/// [`IndexMetadata`] as stored in V1 and V2 files
+#[derive(Deserialize, Serialize)]
+struct LegacyMetadata {
+    path: String,
+    file_type: String,
+    magento_type: Option<String>,
+    class_name: Option<String>,
+    class_type: Option<String>,
+    method_name: Option<String>,
+    methods: Vec<String>,
+    namespace: Option<String>,
+    module: Option<String>,
+    area: Option<String>,
+    extends: Option<String>,
+    implements: Vec<String>,
+    is_controller: bool,
+    is_repository: bool,
+    is_plugin: bool,
+    is_observer: bool,
+    is_model: bool,
+    is_block: bool,
+    is_resolver: bool,
+    is_api_interface: bool,
+    is_ui_component: bool,
+    is_widget: bool,
+    is_mixin: bool,
+    js_dependencies: Vec<String>,
+    search_text: String,
+}
+
+impl From<LegacyMetadata> for IndexMetadata {
+    fn from(m: LegacyMetadata) -> Self {
+        Self {
+            path: m.path,
+            file_type: m.file_type,
+            magento_type: m.magento_type,
+            class_name: m.class_name,
+            class_type: m.class_type,
+            method_name: m.method_name,
+            methods: m.methods,
+            namespace: m.namespace,
+            module: m.module,
+            area: m.area,
+            extends: m.extends,
+            implements: m.implements,
+            is_controller: m.is_controller,
+            is_repository: m.is_repository,
+            is_plugin: m.is_plugin,
+            is_observer: m.is_observer,
+            is_model: m.is_model,
+            is_block: m.is_block,
+            is_resolver: m.is_resolver,
+            is_api_interface: m.is_api_interface,
+            is_ui_component: m.is_ui_component,
+            is_widget: m.is_widget,
+            is_mixin: m.is_mixin,
+            js_dependencies: m.js_dependencies,
+            search_text: m.search_text,
+            ..Default::default()
+        }
+    }
+}
+
+fn upgrade_metadata<M: Into<IndexMetadata>>(metadata: HashMap<usize, M>) -> HashMap<usize, IndexMetadata> {
+    metadata.into_iter().map(|(id, meta)| (id, meta.into())).collect()
+}
+
+/// Index-level metadata persisted alongside the vectors.
+///
+/// Stored as JSON inside the bincode payload so new fields can be added with
+/// `#[serde(default)]` without another on-disk format bump.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexHeader {
+    /// Module globs the index was restricted to (`index --only-modules`).
+    /// Empty means the whole codebase was indexed.
+    #[serde(default)]
+    pub only_modules: Vec<String>,
+    /// Set while an index run is still writing checkpoints; cleared on the
+    /// final save. A DB with this flag covers only the files embedded so far.
+    #[serde(default)]
+    pub building: bool,
+    /// Symlinked directories and files were followed (`index --follow-symlinks`)
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// MFTF XML under module `Test/Mftf` was indexed (`index --include-tests`)
+    #[serde(default)]
+    pub include_tests: bool,
+    /// Markdown documentation was indexed into the `docs` collection
+    /// (`index --include-docs`)
+    #[serde(default)]
+    pub include_docs: bool,
+    /// The root `.gitignore` was applied besides `.magectorignore`
+    /// (`index --gitignore`)
+    #[serde(default)]
+    pub gitignore: bool,
+    /// Absolute Magento root the index was built from (`/` separators).
+    /// Stored paths are relative to it; see [`VectorDB::remap_paths`].
+    #[serde(default)]
+    pub root: String,
+    /// Read-only core index searched alongside this one (`--core-index`);
+    /// empty for a standalone index
+    #[serde(default)]
+    pub core_index: String,
+    /// Source trees indexed besides the root (`index --extra-root`)
+    #[serde(default)]
+    pub extra_roots: Vec<ExtraRoot>,
+    /// [`VectorDB::content_hash`] written on save and verified on load;
+    /// empty for indexes saved before checksums were recorded
+    #[serde(default)]
+    pub checksum: String,
+    /// Snapshot label (`index --tag`); empty for the working index
+    #[serde(default)]
+    pub tag: String,
+    /// When the snapshot was tagged (Unix seconds)
+    #[serde(default)]
+    pub tagged_at: u64,
+    /// Pooling the vectors were embedded with (`index --pooling`); queries
+    /// must use the same. Indexes from before the setting used mean pooling.
+    #[serde(default)]
+    pub pooling: Pooling,
+    /// Embedding model the vectors come from (`magector migrate`); empty
+    /// for indexes from before the setting, which used
+    /// [`crate::embedder::DEFAULT_MODEL`]
+    #[serde(default)]
+    pub model: String,
+    /// Leading dimensions the HNSW graph is built from (`index --coarse-dim`);
+    /// candidates are reranked with the full vectors. 0 uses full vectors.
+    #[serde(default)]
+    pub coarse_dim: usize,
+    /// Two-stage retrieval (`index --two-stage`): a file-level graph of chunk
+    /// centroids picks candidate files, then their chunks are scored
+    #[serde(default)]
+    pub two_stage: bool,
+    /// Items carry a structural summary (`index --summaries`)
+    #[serde(default)]
+    pub summaries: bool,
+    /// Items carry their last git commit time (`index --git-times`)
+    #[serde(default)]
+    pub git_times: bool,
+    /// Items carry their owners (`index --owners`)
+    #[serde(default)]
+    pub owners: bool,
+    /// Items have a second vector embedded from their metadata text
+    /// (`index --meta-vectors`), blended in by the `metadata` score weight
+    #[serde(default)]
+    pub meta_vectors: bool,
+    /// PHP files are split into one item per method plus one for the code
+    /// outside methods (`index --method-chunks`)
+    #[serde(default)]
+    pub method_chunks: bool,
+    /// HNSW beam width picked by [`VectorDB::tune_ef_search`] (`serve
+    /// --tune-ef`); 0 derives it from the number of candidates fetched
+    #[serde(default)]
+    pub ef_search: usize,
+    /// Live items when `ef_search` was tuned; serve re-tunes once the index
+    /// has grown well past it
+    #[serde(default)]
+    pub ef_tuned_items: usize,
+    /// Words in more than 30% of the items when the index was saved (see
+    /// [`crate::idf`]), left out of keyword scoring and SONA term learning
+    #[serde(default)]
+    pub stop_terms: Vec<String>,
+    /// Project aliases mined from class names when the index was built
+    /// (see [`crate::aliases`]): alias → words queries using it are
+    /// expanded with
+    #[serde(default)]
+    pub aliases: BTreeMap<String, Vec<String>>,
+}
+
+impl IndexHeader {
+    /// Whether the index covers only a subset of the codebase
+    pub fn is_partial(&self) -> bool {
+        !self.only_modules.is_empty() || self.building
+    }
+
+    /// Human-readable warning for partial indexes, if any
+    pub fn partial_warning(&self) -> Option<String> {
+        let mut warnings = Vec::new();
+        if !self.only_modules.is_empty() {
+            warnings.push(format!(
+                "Partial index: only modules matching {} were indexed — results from other modules are missing",
+                self.only_modules.join(",")
+            ));
+        }
+        if self.building {
+            warnings.push(
+                "Index build in progress: app/code and app/design are indexed first, vendor code may be missing"
+                    .to_string(),
+            );
+        }
+        if warnings.is_empty() {
+            None
+        } else {
+            Some(warnings.join("; "))
+        }
+    }
+}
+
+/// Raw vectors of an item, for use outside Magector (clustering, plots)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemVector {
+    pub id: usize,
+    pub path: String,
+    /// Method of a method-level chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    pub vector: Vec<f32>,
+    /// Metadata vector (`index --meta-vectors`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta_vector: Option<Vec<f32>>,
+}
+
+/// Outcome of [`VectorDB::tune_ef_search`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EfTuning {
+    /// Beam width chosen
+    pub ef_search: usize,
+    /// Mean overlap with the reference beam of each narrower beam tried
+    pub overlaps: Vec<(usize, f32)>,
+    /// Queries measured
+    pub queries: usize,
 }
 
 /// Search result
@@ -69,12 +461,282 @@ pub struct SearchResult {
     pub id: usize,
     pub score: f32,
     pub metadata: IndexMetadata,
+    /// First and last line of the hit (from `metadata.span`), so editors
+    /// can jump to the method rather than the top of the file
+    #[serde(default)]
+    pub line: Option<usize>,
+    #[serde(default)]
+    pub end_line: Option<usize>,
+    /// Whether the result's module is enabled in `app/etc/config.php`;
+    /// `None` when the module is not listed or config.php was not read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module_enabled: Option<bool>,
+    /// Patches modifying the result's file ([`crate::patches`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patched_by: Vec<crate::patches::PatchRef>,
+    /// For an API interface, the class di.xml prefers for it, returned with
+    /// the interface instead of as a result of its own
+    /// ([`crate::implementations::group`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub implementation: Option<Box<SearchResult>>,
+}
+
+impl SearchResult {
+    pub fn new(id: usize, score: f32, metadata: IndexMetadata) -> Self {
+        let span = metadata.span;
+        Self {
+            id,
+            score,
+            metadata,
+            line: span.map(|s| s.start_line),
+            end_line: span.map(|s| s.end_line),
+            module_enabled: None,
+            patched_by: Vec::new(),
+            implementation: None,
+        }
+    }
+}
+
+/// A nearest neighbour reranked by a hybrid search
+/// ([`VectorDB::hybrid_search_pool`])
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Candidate {
+    pub id: usize,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Similarity to the query before reranking
+    pub semantic: f32,
+    /// Score after keyword, SONA, boost and recency reranking. Serve's
+    /// session boost and result filters come later and are not reflected.
+    pub score: f32,
+    /// Position after reranking within its index, 0 first, in the same
+    /// pre-filter order as `score`
+    pub rank: usize,
+    /// From the core index layered under the searched one
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub core: bool,
+}
+
+/// Collection (search namespace) an item belongs to inside one
+/// [`VectorDB`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Collection {
+    /// Source code and configuration
+    #[default]
+    Code,
+    /// Project documentation ([`crate::docs`])
+    Docs,
+}
+
+impl Collection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Collection::Code => "code",
+            Collection::Docs => "docs",
+        }
+    }
+}
+
+impl std::fmt::Display for Collection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Collection {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "code" => Ok(Collection::Code),
+            "docs" => Ok(Collection::Docs),
+            _ => Err(format!("unknown namespace {:?} (expected code or docs)", s)),
+        }
+    }
+}
+
+/// How search results are ordered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResultOrder {
+    /// Best match first
+    #[default]
+    Score,
+    /// Most recently modified file first, then by score
+    Recent,
+}
+
+impl ResultOrder {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResultOrder::Score => "score",
+            ResultOrder::Recent => "recent",
+        }
+    }
+}
+
+impl std::fmt::Display for ResultOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ResultOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "score" => Ok(ResultOrder::Score),
+            "recent" => Ok(ResultOrder::Recent),
+            _ => Err(format!("unknown sort order {:?} (expected score or recent)", s)),
+        }
+    }
+}
+
+/// Hits fetched per result wanted when a size filter will drop some of them
+const SIZE_FILTER_OVERFETCH: usize = 4;
+
+/// Hits fetched per result wanted when only a small slice is kept (one
+/// owner's files)
+const NARROW_FILTER_OVERFETCH: usize = 10;
+
+/// Filters and order applied to ranked results (`search --min-loc
+/// --max-loc --owner --namespace --sort`). Items indexed without
+/// [`FileMetrics`] pass the size filter and sort last by recency; items
+/// without owners never pass the owner filter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResultFilter {
+    /// Drop files with fewer non-blank lines than this (interface stubs)
+    pub min_loc: Option<usize>,
+    /// Drop files with more non-blank lines than this
+    pub max_loc: Option<usize>,
+    /// Keep only files one of whose owners contains this
+    /// ([`crate::owners::owned_by`])
+    pub owner: Option<String>,
+    /// Keep only items of this collection; all of them when unset. Search
+    /// selects candidates within it, so it needs no overfetch.
+    pub namespace: Option<Collection>,
+    pub order: ResultOrder,
+}
+
+impl ResultFilter {
+    /// Hits to search for so that `limit` remain after filtering
+    pub fn fetch_limit(&self, limit: usize) -> usize {
+        if self.owner.is_some() {
+            limit.saturating_mul(NARROW_FILTER_OVERFETCH)
+        } else if self.min_loc.is_some() || self.max_loc.is_some() {
+            limit.saturating_mul(SIZE_FILTER_OVERFETCH)
+        } else {
+            limit
+        }
+    }
+
+    /// Filter and reorder `results` (best score first) and keep `limit`
+    pub fn apply(&self, results: &mut Vec<SearchResult>, limit: usize) {
+        let (min, max) = (self.min_loc.unwrap_or(0), self.max_loc.unwrap_or(usize::MAX));
+        results.retain(|r| r.metadata.metrics.is_none_or(|m| (min..=max).contains(&m.loc)));
+        if let Some(owner) = &self.owner {
+            results.retain(|r| crate::owners::owned_by(&r.metadata.owners, owner));
+        }
+        if let Some(namespace) = self.namespace {
+            results.retain(|r| r.metadata.collection == namespace);
+        }
+        results.truncate(limit);
+        if self.order == ResultOrder::Recent {
+            // Stable, so equally recent files keep their score order
+            results.sort_by_key(|r| std::cmp::Reverse(r.metadata.metrics.and_then(|m| m.modified)));
+        }
+    }
+}
+
+/// Multipliers for the parts of a hybrid-search score: cosine similarity,
+/// keyword/type bonus, SONA adjustment and git recency, plus two for the
+/// whole score of generated code and of code in disabled modules, the share
+/// of the metadata vector in the cosine similarity and the strength of IDF
+/// keyword weighting. The default (recency 0, generated code 0.5, metadata
+/// 0.3, the rest 1.0) is the built-in ranking; serve requests may override
+/// it per query.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoreWeights {
+    pub semantic: f32,
+    pub keyword: f32,
+    pub sona: f32,
+    /// Applied to items flagged [`IndexMetadata::is_generated`] so they rank
+    /// below their source classes; 0 leaves them out of results
+    pub generated: f32,
+    /// Bonus for files with a recent [`IndexMetadata::last_commit`], halved
+    /// every [`RECENCY_HALF_LIFE_DAYS`]; 0 turns it off
+    pub recency: f32,
+    /// Applied to results from modules disabled in `app/etc/config.php`
+    /// (see [`SearchResult::module_enabled`]); 0 leaves them out of results
+    pub disabled: f32,
+    /// Share (0–1) of the metadata vector's similarity in the semantic
+    /// score of items that have one (`index --meta-vectors`); the code
+    /// vector's similarity makes up the rest. 0 searches code vectors only.
+    pub metadata: f32,
+    /// Strength (0–1) of IDF weighting of keyword matches: 1 scales each
+    /// query term's keyword bonus by [`TermStats::weight`], so words most
+    /// items mention add little; 0 weighs every term alike
+    pub idf: f32,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self { semantic: 1.0, keyword: 1.0, sona: 1.0, generated: 0.5, recency: 0.0, disabled: 1.0, metadata: 0.3, idf: 1.0 }
+    }
+}
+
+/// `recency` weight of `search --boost-recent`: a file committed today
+/// gains about as much as one matching query term in its path
+pub const RECENT_BOOST_WEIGHT: f32 = 0.1;
+
+/// Age at which a commit earns half the recency bonus
+pub const RECENCY_HALF_LIFE_DAYS: f32 = 14.0;
+
+/// Recency bonus (1 for a commit now, towards 0 for old ones) of a file
+/// last committed at `last_commit`, both in Unix seconds
+fn recency_bonus(last_commit: Option<u64>, now: u64) -> f32 {
+    let Some(time) = last_commit else {
+        return 0.0;
+    };
+    let age_days = now.saturating_sub(time) as f32 / 86_400.0;
+    0.5f32.powf(age_days / RECENCY_HALF_LIFE_DAYS)
+}
+
+impl ScoreWeights {
+    /// Weights must be finite and non-negative, `metadata` and `idf` at most 1
+    pub fn validate(&self) -> Result<()> {
+        let weights = [
+            ("semantic", self.semantic),
+            ("keyword", self.keyword),
+            ("sona", self.sona),
+            ("generated", self.generated),
+            ("recency", self.recency),
+            ("disabled", self.disabled),
+            ("metadata", self.metadata),
+            ("idf", self.idf),
+        ];
+        for (name, weight) in weights {
+            if !weight.is_finite() || weight < 0.0 {
+                anyhow::bail!("weight '{}' must be a non-negative number, got {}", name, weight);
+            }
+        }
+        if self.metadata > 1.0 {
+            anyhow::bail!("weight 'metadata' is a share and must be at most 1, got {}", self.metadata);
+        }
+        if self.idf > 1.0 {
+            anyhow::bail!("weight 'idf' is a strength and must be at most 1, got {}", self.idf);
+        }
+        Ok(())
+    }
 }
 
 /// Persisted state V1 — legacy format (no tombstones)
 #[derive(Serialize, Deserialize)]
-struct PersistedState {
-    metadata: HashMap<usize, IndexMetadata>,
+struct PersistedState<M = IndexMetadata> {
+    metadata: HashMap<usize, M>,
     vectors: HashMap<usize, Vec<f32>>,
     next_id: usize,
 }
@@ -82,22 +744,292 @@ struct PersistedState {
 /// Version tag written before V2 payloads
 const PERSIST_VERSION_V2: u8 = 3;
 
+/// Version tag written before V3 payloads
+const PERSIST_VERSION_V3: u8 = 4;
+
 /// Persisted state V2 — includes tombstone set
 #[derive(Serialize, Deserialize)]
-struct PersistedStateV2 {
+struct PersistedStateV2<M = IndexMetadata> {
+    metadata: HashMap<usize, M>,
+    vectors: HashMap<usize, Vec<f32>>,
+    next_id: usize,
+    tombstones: HashSet<usize>,
+}
+
+/// Persisted state V3 — V2 plus the JSON-encoded [`IndexHeader`] and the
+/// metadata vectors of items that have one.
+///
+/// Maps and sets are written in key order so that identical contents always
+/// produce identical bytes (HashMap iteration order is randomized per process).
+/// Readers still decode them into hash collections.
+#[derive(Serialize, Deserialize)]
+struct PersistedStateV3 {
+    header: String,
+    #[serde(serialize_with = "serialize_sorted_map")]
     metadata: HashMap<usize, IndexMetadata>,
+    #[serde(serialize_with = "serialize_sorted_map")]
     vectors: HashMap<usize, Vec<f32>>,
     next_id: usize,
+    #[serde(serialize_with = "serialize_sorted_set")]
     tombstones: HashSet<usize>,
+    #[serde(serialize_with = "serialize_sorted_map")]
+    meta_vectors: HashMap<usize, Vec<f32>>,
+}
+
+fn serialize_sorted_map<S: Serializer, V: Serialize>(
+    map: &HashMap<usize, V>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+fn serialize_sorted_set<S: Serializer>(
+    set: &HashSet<usize>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    set.iter().collect::<BTreeSet<_>>().serialize(serializer)
+}
+
+/// See [`VectorDB::content_hash`]
+fn hash_items<M: Serialize>(
+    metadata: &HashMap<usize, M>,
+    vectors: &HashMap<usize, Vec<f32>>,
+    meta_vectors: &HashMap<usize, Vec<f32>>,
+    tombstones: &HashSet<usize>,
+) -> String {
+    let mut ids: Vec<usize> = metadata.keys().filter(|id| !tombstones.contains(id)).copied().collect();
+    ids.sort_unstable();
+    let mut hasher = Sha256::new();
+    for id in ids {
+        let Some(vector) = vectors.get(&id) else { continue };
+        hasher.update((id as u64).to_le_bytes());
+        for x in vector {
+            hasher.update(x.to_le_bytes());
+        }
+        if let Ok(bytes) = bincode::serde::encode_to_vec(&metadata[&id], bincode::config::standard()) {
+            hasher.update(&bytes);
+        }
+        // Items without a metadata vector hash as they did before there were any
+        for x in meta_vectors.get(&id).into_iter().flatten() {
+            hasher.update(x.to_le_bytes());
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Mask applied to stable IDs: 53 bits keeps them exact when they pass
+/// through JSON numbers on the Node.js side.
+const STABLE_ID_MASK: u64 = (1 << 53) - 1;
+
+/// Derive a stable vector ID from a file path and chunk index (FNV-1a).
+///
+/// The same `(path, chunk)` pair always maps to the same ID, independent of
+/// insertion order. Collisions are resolved at insert time by probing.
+pub fn stable_id(path: &str, chunk: usize) -> usize {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in path.bytes().chain([0xff]).chain((chunk as u64).to_le_bytes()) {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    (h & STABLE_ID_MASK) as usize
+}
+
+/// Root-relative path with `/` separators on every OS. This is the form
+/// stored in `IndexMetadata::path`, manifests and reports, and the form all
+/// path patterns (`app/code/`, `/etc/di.xml`, ...) are written against.
+/// Paths outside the root (files of an [`ExtraRoot`]) stay absolute.
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    if relative.is_absolute() {
+        return normalize_separators(&relative.to_string_lossy());
+    }
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    parts.join("/")
+}
+
+/// Convert Windows `\` separators in a stored or user-supplied path to `/`
+pub fn normalize_separators(path: &str) -> String {
+    if path.contains('\\') {
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    }
+}
+
+/// Root prefix rewrite applied when opening an index built elsewhere
+/// (`--map-root /old/path=/new/path`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootMap {
+    pub from: String,
+    pub to: String,
+}
+
+impl std::str::FromStr for RootMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let (from, to) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected OLD=NEW, got '{}'", s))?;
+        let clean = |p: &str| normalize_separators(p).trim_end_matches('/').to_string();
+        let (from, to) = (clean(from), clean(to));
+        if from.is_empty() {
+            return Err(format!("empty source root in '{}'", s));
+        }
+        Ok(Self { from, to })
+    }
+}
+
+/// Source tree indexed into the same database as the Magento root
+/// (`index --extra-root [NAME=]PATH`). Its files are stored by absolute
+/// path and tagged with the name in [`IndexMetadata::root`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtraRoot {
+    pub name: String,
+    /// Absolute path with `/` separators
+    pub path: String,
+}
+
+impl ExtraRoot {
+    /// Name the root from its directory when none is given
+    pub fn new(name: Option<&str>, path: &Path) -> Self {
+        // Lexically, so `../extensions/pay` doesn't look like it's under the root
+        let mut absolute = PathBuf::new();
+        for component in std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()).components() {
+            match component {
+                std::path::Component::ParentDir => {
+                    absolute.pop();
+                }
+                std::path::Component::CurDir => {}
+                component => absolute.push(component),
+            }
+        }
+        let path = normalize_separators(&absolute.to_string_lossy()).trim_end_matches('/').to_string();
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => path.rsplit('/').next().unwrap_or_default().to_string(),
+        };
+        Self { name, path }
+    }
+
+    /// Whether a stored path belongs to this root
+    pub fn contains(&self, path: &str) -> bool {
+        path_under(path, &self.path)
+    }
+}
+
+impl std::str::FromStr for ExtraRoot {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let (name, path) = match s.split_once('=') {
+            Some((name, path)) => (Some(name.trim()), path),
+            None => (None, s),
+        };
+        if name.is_some_and(str::is_empty) {
+            return Err(format!("empty root name in '{}'", s));
+        }
+        let root = Self::new(name, Path::new(path));
+        if path.trim().is_empty() || root.name.is_empty() {
+            return Err(format!("expected [NAME=]PATH, got '{}'", s));
+        }
+        Ok(root)
+    }
+}
+
+/// Whether `path` is `prefix` itself or lies below it
+fn path_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Problems found by [`VectorDB::check_integrity`], as item IDs in
+/// ascending order
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IntegrityReport {
+    /// Live items
+    pub items: usize,
+    /// Vectors or metadata vectors stored without metadata
+    pub orphan_vectors: Vec<usize>,
+    /// Live items without a vector
+    pub missing_vectors: Vec<usize>,
+    /// Items whose vector has the wrong size, NaN or infinite values, or is
+    /// all zeros. Loading tombstones them, so they can't be found.
+    pub invalid_vectors: Vec<usize>,
+    /// Live items whose vector is not unit length
+    pub unnormalized: Vec<usize>,
+    /// Live items whose own vector was searched for
+    pub graph_checked: usize,
+    /// Checked items the search did not find
+    pub unreachable: Vec<usize>,
+}
+
+impl IntegrityReport {
+    /// Whether the graph missed more than [`UNREACHABLE_TOLERANCE`] of the
+    /// checked items
+    pub fn graph_damaged(&self) -> bool {
+        self.unreachable.len() as f32 > self.graph_checked as f32 * UNREACHABLE_TOLERANCE
+    }
+
+    /// Whether nothing needs repairing
+    pub fn is_clean(&self) -> bool {
+        self.orphan_vectors.is_empty()
+            && self.missing_vectors.is_empty()
+            && self.invalid_vectors.is_empty()
+            && self.unnormalized.is_empty()
+            && !self.graph_damaged()
+    }
 }
 
 /// Vector database for semantic code search
+///
+/// Vector IDs are stable: each item is keyed by [`stable_id`] of its path and
+/// chunk index, so the same chunk keeps its ID across reindexes and clients
+/// can cache references to results.
 pub struct VectorDB {
     hnsw: Hnsw<'static, f32, DistCosine>,
     metadata: HashMap<usize, IndexMetadata>,
     vectors: HashMap<usize, Vec<f32>>,
+    /// One past the highest ID in use. Kept for on-disk compatibility with
+    /// the positional IDs written by older versions.
     next_id: usize,
     tombstones: HashSet<usize>,
+    /// Second vector of items embedded from their metadata text
+    /// (`index --meta-vectors`); see [`ScoreWeights::metadata`]
+    meta_vectors: HashMap<usize, Vec<f32>>,
+    /// Graph of `meta_vectors`, built when there are any. Like the main
+    /// graph it keeps outdated points of re-inserted IDs, so its candidates
+    /// are always scored from `meta_vectors`. Not persisted.
+    meta_hnsw: Option<Hnsw<'static, f32, DistCosine>>,
+    /// IDs re-inserted after being tombstoned. HNSW can't delete points, so
+    /// the graph still holds their old vectors until the next compaction;
+    /// search rescores these from `vectors`. Not persisted (HNSW is rebuilt
+    /// on load).
+    stale: HashSet<usize>,
+    header: IndexHeader,
+    /// Document frequencies of the live items' search text words, for IDF
+    /// keyword weighting; derived on load, not persisted
+    term_stats: TermStats,
+    /// Live item IDs of each file, derived like `term_stats`
+    paths: HashMap<String, BTreeSet<usize>>,
+    /// Derived from the chunks when `header.two_stage` is set; not persisted
+    file_level: Option<FileLevel>,
+}
+
+/// File-level graph for two-stage search. Each point is the centroid of a
+/// file's chunk vectors; when a file's chunks change it gets a new point and
+/// the old one is ignored until the next rebuild.
+struct FileLevel {
+    hnsw: Hnsw<'static, f32, DistCosine>,
+    /// File path of each graph point
+    points: Vec<String>,
+    /// Current point of each file
+    current: HashMap<String, usize>,
+    /// Chunk IDs per file; may include tombstoned IDs
+    chunks: HashMap<String, Vec<usize>>,
 }
 
 fn make_hnsw(capacity: usize) -> Hnsw<'static, f32, DistCosine> {
@@ -110,6 +1042,68 @@ fn make_hnsw(capacity: usize) -> Hnsw<'static, f32, DistCosine> {
     )
 }
 
+/// Insert points into the graph, truncated to their first `coarse_dim`
+/// dimensions when set. `DistCosine` normalizes, so prefixes need no
+/// rescaling; a prefix that is all zeros can't be placed and is left out.
+fn hnsw_insert(hnsw: &Hnsw<'static, f32, DistCosine>, data: &[(&Vec<f32>, usize)], coarse_dim: usize) {
+    if coarse_dim == 0 {
+        hnsw.parallel_insert(data);
+        return;
+    }
+    let prefixes: Vec<(&[f32], usize)> = data
+        .iter()
+        .map(|(vec, id)| (&vec[..coarse_dim.min(vec.len())], *id))
+        .filter(|(prefix, _)| is_valid_vector(prefix))
+        .collect();
+    if prefixes.len() < data.len() {
+        tracing::warn!("Coarse graph: {} vectors have an empty prefix and are not searchable", data.len() - prefixes.len());
+    }
+    hnsw.parallel_insert_slice(&prefixes);
+}
+
+/// Add a centroid point to `files` for each of `paths` from its live
+/// chunks; centroids are summed in parallel
+fn add_centroids(
+    files: &mut FileLevel,
+    paths: Vec<String>,
+    vectors: &HashMap<usize, Vec<f32>>,
+    tombstones: &HashSet<usize>,
+    coarse_dim: usize,
+) {
+    let chunks = &files.chunks;
+    let sums: Vec<(String, Vec<f32>)> = paths
+        .into_par_iter()
+        .map(|path| {
+            let mut sum = vec![0.0f32; EMBEDDING_DIM];
+            for id in chunks.get(&path).into_iter().flatten() {
+                if tombstones.contains(id) {
+                    continue;
+                }
+                if let Some(vec) = vectors.get(id) {
+                    sum.iter_mut().zip(vec).for_each(|(s, x)| *s += x);
+                }
+            }
+            (path, sum)
+        })
+        .collect();
+    let mut centroids: Vec<(Vec<f32>, usize)> = Vec::with_capacity(sums.len());
+    for (path, sum) in sums {
+        // `DistCosine` normalizes, so the sum stands in for the mean
+        if is_valid_vector(&sum) {
+            let point = files.points.len();
+            files.points.push(path.clone());
+            files.current.insert(path, point);
+            centroids.push((sum, point));
+        } else {
+            files.current.remove(&path);
+        }
+    }
+    let data: Vec<(&Vec<f32>, usize)> = centroids.iter().map(|(vec, point)| (vec, *point)).collect();
+    if !data.is_empty() {
+        hnsw_insert(&files.hnsw, &data, coarse_dim);
+    }
+}
+
 impl VectorDB {
     /// Create a new empty vector database
     pub fn new() -> Self {
@@ -119,6 +1113,13 @@ impl VectorDB {
             vectors: HashMap::new(),
             next_id: 0,
             tombstones: HashSet::new(),
+            meta_vectors: HashMap::new(),
+            meta_hnsw: None,
+            stale: HashSet::new(),
+            term_stats: TermStats::default(),
+            paths: HashMap::new(),
+            header: IndexHeader::default(),
+            file_level: None,
         }
     }
 
@@ -130,6 +1131,13 @@ impl VectorDB {
             vectors: HashMap::with_capacity(capacity),
             next_id: 0,
             tombstones: HashSet::new(),
+            meta_vectors: HashMap::new(),
+            meta_hnsw: None,
+            stale: HashSet::new(),
+            term_stats: TermStats::default(),
+            paths: HashMap::new(),
+            header: IndexHeader::default(),
+            file_level: None,
         }
     }
 
@@ -177,18 +1185,159 @@ impl VectorDB {
         Ok(Self::new())
     }
 
-    /// Load database from a bincode file (V2 with tombstones, V1 fallback).
-    /// Returns `Err` with `FormatChanged` context if the schema is incompatible.
+    /// Load an existing database without [`Self::open`]'s recovery: a
+    /// missing, incompatible or corrupted file is an error and is left in
+    /// place. Used for index artifacts (`delta`, `apply`, `verify`).
+    pub fn load_existing(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            anyhow::bail!("Index not found: {}", path.display());
+        }
+        Self::load(path)
+    }
+
+    /// Move an index that can't be loaded (corrupted, checksum mismatch)
+    /// to `<path>.corrupt`, so a rebuild starts fresh without destroying
+    /// it. Returns the new location; `None` when the file is missing or
+    /// loads fine.
+    pub fn set_aside_if_unreadable(path: &Path) -> Result<Option<PathBuf>> {
+        if !path.exists() || Self::load(path).is_ok() {
+            return Ok(None);
+        }
+        let mut aside = path.as_os_str().to_owned();
+        aside.push(".corrupt");
+        let aside = PathBuf::from(aside);
+        fs::rename(path, &aside).with_context(|| format!("Failed to move {} aside", path.display()))?;
+        Ok(Some(aside))
+    }
+
+    /// Read only the header of a saved index, without loading the items.
+    /// Files older than the V3 format have no header and get the default.
+    pub fn read_header(path: &Path) -> Result<IndexHeader> {
+        let mut reader = std::io::BufReader::new(File::open(path).with_context(|| format!("Failed to open {}", path.display()))?);
+        let mut version = [0u8; 1];
+        if std::io::Read::read(&mut reader, &mut version)? == 0
+            || version[0] != PERSIST_VERSION_V3
+        {
+            return Ok(IndexHeader::default());
+        }
+        // The header is the first field of the V3 payload
+        let header: String = bincode::serde::decode_from_std_read(&mut reader, bincode::config::standard())
+            .context("Failed to read index header")?;
+        Ok(serde_json::from_str(&header)?)
+    }
+
+    /// Read the header and live item metadata of a saved V3 index without
+    /// building the HNSW graph. The checksum is verified as on load.
+    pub fn read_metadata(path: &Path) -> Result<(IndexHeader, Vec<IndexMetadata>)> {
+        let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let Some((header, state)) = Self::decode_v3(&bytes)? else {
+            anyhow::bail!("{} predates the V3 index format — re-save it first", path.display());
+        };
+        if !header.checksum.is_empty()
+            && hash_items(&state.metadata, &state.vectors, &state.meta_vectors, &state.tombstones) != header.checksum
+        {
+            anyhow::bail!("Index checksum mismatch in {}: the file is corrupted or was modified", path.display());
+        }
+        let tombstones = state.tombstones;
+        let mut metadata = state.metadata;
+        metadata.retain(|id, _| !tombstones.contains(id));
+        let mut items: Vec<IndexMetadata> = metadata.into_values().map(|mut meta| {
+            meta.path = normalize_separators(&meta.path);
+            meta
+        }).collect();
+        items.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok((header, items))
+    }
+
+    /// Load database from disk, see [`Self::decode`]. Stored paths from
+    /// indexes built on Windows before paths were normalized get `/`
+    /// separators so they match path patterns.
     fn load(path: &Path) -> Result<Self> {
+        let (db, checksum) = Self::load_unverified(path)?;
+        checksum.with_context(|| format!("Refusing to load {}", path.display()))?;
+        Ok(db)
+    }
+
+    /// [`Self::load_existing`] that keeps a file whose contents don't match
+    /// its checksum, returning the outcome of the check with it. For `fsck`,
+    /// which reports the mismatch along with whatever else is wrong.
+    pub fn load_unverified(path: &Path) -> Result<(Self, Result<()>)> {
+        if !path.exists() {
+            anyhow::bail!("Index not found: {}", path.display());
+        }
+        let mut db = Self::decode(path)?;
+        let checksum = db.verify_checksum();
+        let mut normalized = false;
+        for meta in db.metadata.values_mut() {
+            if meta.path.contains('\\') {
+                meta.path = normalize_separators(&meta.path);
+                normalized = true;
+            }
+        }
+        db.build_lookups();
+        if normalized {
+            db.build_file_level();
+        }
+        Ok((db, checksum))
+    }
+
+    /// Decode a V3 file into its state and header. `None` for older formats.
+    fn decode_v3(bytes: &[u8]) -> Result<Option<(IndexHeader, PersistedStateV3)>> {
+        if bytes.first() != Some(&PERSIST_VERSION_V3) {
+            return Ok(None);
+        }
+        let state = match bincode::serde::decode_from_slice::<PersistedStateV3, _>(&bytes[1..], bincode::config::standard()) {
+            Ok((state, _)) => state,
+            Err(e) => {
+                tracing::warn!("V3 database format incompatible: {e}");
+                return Err(anyhow::anyhow!("Database format changed (schema mismatch). Re-index required."))
+                    .context("FormatChanged");
+            }
+        };
+        let header = serde_json::from_str(&state.header).unwrap_or_else(|e| {
+            tracing::warn!("Unreadable index header ({e}) — using defaults");
+            IndexHeader::default()
+        });
+        Ok(Some((header, state)))
+    }
+
+    /// Decode a bincode file (V3 with header, V2 with tombstones, V1
+    /// fallback). Returns `Err` with `FormatChanged` context if the schema
+    /// is incompatible.
+    fn decode(path: &Path) -> Result<Self> {
         let bytes = fs::read(path).context("Failed to read database")?;
         if bytes.is_empty() {
             return Ok(Self::new());
         }
 
-        // Try V2 first: first byte == PERSIST_VERSION_V2
+        if let Some((header, state)) = Self::decode_v3(&bytes)? {
+            return Self::from_state_v2(
+                PersistedStateV2 {
+                    metadata: state.metadata,
+                    vectors: state.vectors,
+                    next_id: state.next_id,
+                    tombstones: state.tombstones,
+                },
+                header,
+                state.meta_vectors,
+            );
+        }
+
+        // Then V2: first byte == PERSIST_VERSION_V2
         if bytes[0] == PERSIST_VERSION_V2 {
-            match bincode::serde::decode_from_slice::<PersistedStateV2, _>(&bytes[1..], bincode::config::standard()) {
-                Ok((state, _)) => return Self::from_state_v2(state),
+            match bincode::serde::decode_from_slice::<PersistedStateV2<LegacyMetadata>, _>(&bytes[1..], bincode::config::standard()) {
+                Ok((state, _)) => {
+                    return Self::from_state_v2(
+                        PersistedStateV2 {
+                            metadata: upgrade_metadata(state.metadata),
+                            vectors: state.vectors,
+                            next_id: state.next_id,
+                            tombstones: state.tombstones,
+                        },
+                        IndexHeader::default(),
+                        HashMap::new(),
+                    )
+                }
                 Err(e) => {
                     tracing::warn!("V2 database format incompatible: {e}");
                     return Err(anyhow::anyhow!("Database format changed (schema mismatch). Re-index required."))
@@ -198,8 +1347,12 @@ impl VectorDB {
         }
 
         // Fallback: V1 (no version byte)
-        match bincode::serde::decode_from_slice::<PersistedState, _>(&bytes, bincode::config::standard()) {
-            Ok((state, _)) => Self::from_state(state),
+        match bincode::serde::decode_from_slice::<PersistedState<LegacyMetadata>, _>(&bytes, bincode::config::standard()) {
+            Ok((state, _)) => Self::from_state(PersistedState {
+                metadata: upgrade_metadata(state.metadata),
+                vectors: state.vectors,
+                next_id: state.next_id,
+            }),
             Err(e) => {
                 tracing::warn!("V1 database format incompatible: {e}");
                 Err(anyhow::anyhow!("Database format changed (schema mismatch). Re-index required."))
@@ -222,10 +1375,13 @@ impl VectorDB {
             return true;
         }
 
-        if bytes[0] == PERSIST_VERSION_V2 {
-            bincode::serde::decode_from_slice::<PersistedStateV2, _>(&bytes[1..], bincode::config::standard()).is_ok()
-        } else {
-            bincode::serde::decode_from_slice::<PersistedState, _>(&bytes, bincode::config::standard()).is_ok()
+        let config = bincode::config::standard();
+        match bytes[0] {
+            PERSIST_VERSION_V3 => bincode::serde::decode_from_slice::<PersistedStateV3, _>(&bytes[1..], config).is_ok(),
+            PERSIST_VERSION_V2 => {
+                bincode::serde::decode_from_slice::<PersistedStateV2<LegacyMetadata>, _>(&bytes[1..], config).is_ok()
+            }
+            _ => bincode::serde::decode_from_slice::<PersistedState<LegacyMetadata>, _>(&bytes, config).is_ok(),
         }
     }
 
@@ -259,60 +1415,77 @@ impl VectorDB {
             vectors: state.vectors,
             next_id: state.next_id,
             tombstones,
+            meta_vectors: HashMap::new(),
+            meta_hnsw: None,
+            stale: HashSet::new(),
+            term_stats: TermStats::default(),
+            paths: HashMap::new(),
+            header: IndexHeader::default(),
+            file_level: None,
         })
     }
 
-    /// Rebuild HNSW from persisted V2 state (skip tombstoned vectors)
-    fn from_state_v2(state: PersistedStateV2) -> Result<Self> {
-        let live_count = state.vectors.len().saturating_sub(state.tombstones.len());
-        let capacity = live_count.max(HNSW_MIN_CAPACITY);
-        let hnsw = make_hnsw(capacity);
-
+    /// Rebuild the graphs from persisted V2 state (skip tombstoned vectors)
+    fn from_state_v2(
+        state: PersistedStateV2,
+        header: IndexHeader,
+        meta_vectors: HashMap<usize, Vec<f32>>,
+    ) -> Result<Self> {
         // Only insert non-tombstoned AND valid vectors
         let mut tombstones = state.tombstones;
-        let data: Vec<(&Vec<f32>, usize)> = state.vectors.iter()
-            .filter(|(id, vec)| {
-                if tombstones.contains(id) {
-                    return false;
-                }
-                if !is_valid_vector(vec) {
-                    tracing::warn!("V2 load: tombstoning invalid vector id={}", id);
-                    tombstones.insert(**id);
-                    return false;
-                }
-                true
-            })
-            .map(|(&id, vec)| (vec, id))
-            .collect();
-        hnsw.parallel_insert(&data);
+        for (&id, vec) in &state.vectors {
+            if !tombstones.contains(&id) && !is_valid_vector(vec) {
+                tracing::warn!("V2 load: tombstoning invalid vector id={}", id);
+                tombstones.insert(id);
+            }
+        }
 
-        Ok(Self {
-            hnsw,
+        let mut db = Self {
+            hnsw: make_hnsw(HNSW_MIN_CAPACITY),
             metadata: state.metadata,
             vectors: state.vectors,
             next_id: state.next_id,
             tombstones,
-        })
+            meta_vectors,
+            meta_hnsw: None,
+            stale: HashSet::new(),
+            term_stats: TermStats::default(),
+            paths: HashMap::new(),
+            header,
+            file_level: None,
+        };
+        db.rebuild_hnsw();
+        Ok(db)
     }
 
-    /// Save database to disk (V2 bincode format with tombstones)
-    pub fn save(&self, path: &Path) -> Result<()> {
-        fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
-
-        let state = PersistedStateV2 {
+    /// Write the version byte and V3 payload
+    fn write_state<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
+        let header = IndexHeader {
+            checksum: self.content_hash(),
+            stop_terms: self.term_stats.stop_terms(),
+            ..self.header.clone()
+        };
+        let state = PersistedStateV3 {
+            header: serde_json::to_string(&header)?,
             metadata: self.metadata.clone(),
             vectors: self.vectors.clone(),
             next_id: self.next_id,
             tombstones: self.tombstones.clone(),
+            meta_vectors: self.meta_vectors.clone(),
         };
+        writer.write_all(&[PERSIST_VERSION_V3])?;
+        bincode::serde::encode_into_std_write(&state, writer, bincode::config::standard())
+            .context("Failed to serialize database")?;
+        Ok(())
+    }
+
+    /// Save database to disk (V3 bincode format with header and tombstones)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
 
         let file = File::create(path)?;
         let mut writer = BufWriter::with_capacity(1 << 20, file);
-        // Write version byte, then V2 payload
-        use std::io::Write;
-        writer.write_all(&[PERSIST_VERSION_V2])?;
-        bincode::serde::encode_into_std_write(&state, &mut writer, bincode::config::standard())
-            .context("Failed to serialize database")?;
+        self.write_state(&mut writer)?;
 
         // Clean up legacy files from old versions
         for ext in &["bin", "json"] {
@@ -332,20 +1505,11 @@ impl VectorDB {
 
         let tmp_path = path.with_extension("db.tmp");
 
-        let state = PersistedStateV2 {
-            metadata: self.metadata.clone(),
-            vectors: self.vectors.clone(),
-            next_id: self.next_id,
-            tombstones: self.tombstones.clone(),
-        };
-
         {
             let file = File::create(&tmp_path)?;
             let mut writer = BufWriter::with_capacity(1 << 20, file);
+            self.write_state(&mut writer)?;
             use std::io::Write;
-            writer.write_all(&[PERSIST_VERSION_V2])?;
-            bincode::serde::encode_into_std_write(&state, &mut writer, bincode::config::standard())
-                .context("Failed to serialize database")?;
             writer.flush()?;
         }
 
@@ -356,55 +1520,285 @@ impl VectorDB {
         Ok(())
     }
 
-    /// Insert a vector with metadata.
-    /// Returns None if the vector is invalid (NaN/Inf/zero).
-    pub fn insert(&mut self, vector: &[f32], metadata: IndexMetadata) -> usize {
-        assert_eq!(vector.len(), EMBEDDING_DIM);
+    /// Apply `update` to the header of the V3 index saved at `path`. The
+    /// items are copied through as stored rather than re-encoded, and the
+    /// checksum stays valid since it covers only the items. Its temp file is
+    /// not the one [`save_atomic`](Self::save_atomic) writes, so a save
+    /// racing the rewrite cannot have its half-written file renamed in.
+    pub fn update_saved_header(path: &Path, update: impl FnOnce(&mut IndexHeader)) -> Result<()> {
+        use std::io::{Read, Write};
+        let config = bincode::config::standard();
+        let mut reader = std::io::BufReader::new(File::open(path).with_context(|| format!("Failed to open {}", path.display()))?);
+        let mut version = [0u8; 1];
+        reader.read_exact(&mut version)?;
+        if version[0] != PERSIST_VERSION_V3 {
+            anyhow::bail!("{} predates the V3 index format — re-save it first", path.display());
+        }
+        let stored: String =
+            bincode::serde::decode_from_std_read(&mut reader, config).context("Failed to read index header")?;
+        let mut header: IndexHeader = serde_json::from_str(&stored)?;
+        update(&mut header);
 
-        if !is_valid_vector(vector) {
-            tracing::warn!("Skipping invalid vector for {}: NaN/Inf/zero", metadata.path);
-            // Still assign an ID and store metadata (for stats accuracy),
-            // but tombstone it immediately so it's excluded from search.
-            let id = self.next_id;
-            self.next_id += 1;
-            self.metadata.insert(id, metadata);
-            self.tombstones.insert(id);
-            return id;
+        let tmp_path = path.with_extension("db.header.tmp");
+        {
+            let mut writer = BufWriter::with_capacity(1 << 20, File::create(&tmp_path)?);
+            writer.write_all(&version)?;
+            bincode::serde::encode_into_std_write(serde_json::to_string(&header)?, &mut writer, config)
+                .context("Failed to serialize index header")?;
+            std::io::copy(&mut reader, &mut writer)?;
+            writer.flush()?;
         }
+        fs::rename(&tmp_path, path).context("Failed to atomically rename temp DB")?;
+        Ok(())
+    }
 
-        let id = self.next_id;
-        self.next_id += 1;
+    /// Rewrite stored paths for an index opened away from where it was
+    /// built: the first matching [`RootMap`] replaces a path's prefix, then
+    /// paths under `root` are made relative to it. Returns the number of
+    /// paths changed.
+    pub fn remap_paths(&mut self, maps: &[RootMap], root: &Path) -> usize {
+        let root = normalize_separators(&root.to_string_lossy()).trim_end_matches('/').to_string();
+        let mut changed = 0;
+        for meta in self.metadata.values_mut() {
+            let mut path = match maps.iter().find(|m| path_under(&meta.path, &m.from)) {
+                Some(m) => format!("{}{}", m.to, &meta.path[m.from.len()..]),
+                None => meta.path.clone(),
+            };
+            if !root.is_empty() && path.len() > root.len() && path_under(&path, &root) {
+                path = path[root.len() + 1..].to_string();
+            }
+            if path != meta.path {
+                meta.path = path;
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.build_file_level();
+        }
+        changed
+    }
 
-        let vec = vector.to_vec();
-        self.hnsw.insert((&vec, id));
-        self.vectors.insert(id, vec);
-        self.metadata.insert(id, metadata);
+    /// Compare the contents against the checksum recorded in the header.
+    /// Indexes saved without a checksum pass.
+    pub fn verify_checksum(&self) -> Result<()> {
+        if self.header.checksum.is_empty() {
+            return Ok(());
+        }
+        let actual = self.content_hash();
+        if actual != self.header.checksum {
+            anyhow::bail!(
+                "Index checksum mismatch (expected {}, got {}): the file is corrupted or was modified",
+                &self.header.checksum[..self.header.checksum.len().min(12)],
+                &actual[..12]
+            );
+        }
+        Ok(())
+    }
 
-        id
+    /// Index-level metadata (module subset, ...)
+    pub fn header(&self) -> &IndexHeader {
+        &self.header
     }
 
-    /// Batch insert vectors with metadata (uses parallel HNSW insert).
-    /// Invalid vectors (NaN/Inf/zero) are silently skipped from HNSW insertion.
-    pub fn insert_batch(&mut self, items: Vec<(Vec<f32>, IndexMetadata)>) {
-        if items.is_empty() {
+    /// Replace the index header; persisted on the next save. A different
+    /// `coarse_dim` rebuilds the graph.
+    pub fn set_header(&mut self, header: IndexHeader) {
+        let (coarse_dim, two_stage) = (header.coarse_dim, header.two_stage);
+        self.header = IndexHeader { coarse_dim: self.header.coarse_dim, two_stage: self.header.two_stage, ..header };
+        self.set_coarse_dim(coarse_dim);
+        self.set_two_stage(two_stage);
+    }
+
+    /// Search in two stages: a file-level graph of chunk centroids finds
+    /// candidate files, then every live chunk of those files is scored.
+    /// Keeps latency flat when files are split into many chunks.
+    pub fn set_two_stage(&mut self, enabled: bool) {
+        if enabled == self.header.two_stage {
+            return;
+        }
+        self.header.two_stage = enabled;
+        self.build_file_level();
+    }
+
+    /// Build the HNSW graph from the first `dim` dimensions of each vector
+    /// (0 = full vectors) and rerank candidates with the full vectors.
+    ///
+    /// Meant for Matryoshka-trained models, whose leading dimensions form a
+    /// usable embedding on their own; with other models recall drops further
+    /// as `dim` shrinks. Rebuilds the graph if the setting changes.
+    pub fn set_coarse_dim(&mut self, dim: usize) {
+        let dim = if dim >= EMBEDDING_DIM { 0 } else { dim };
+        if dim == self.header.coarse_dim {
             return;
         }
+        self.header.coarse_dim = dim;
+        // An empty graph keeps its capacity hint
+        if !self.vectors.is_empty() {
+            self.rebuild_hnsw();
+        }
+    }
+
+    /// Rebuild the graphs from the live vectors (load, compaction). The
+    /// main, metadata-vector and file-level graphs are built side by side,
+    /// each inserting its points from all threads.
+    fn rebuild_hnsw(&mut self) {
+        let start = std::time::Instant::now();
+        let (hnsw, (meta_hnsw, file_level)) = rayon::join(
+            || self.main_graph(),
+            || rayon::join(|| self.meta_graph(), || self.file_level_graph()),
+        );
+        self.hnsw = hnsw;
+        self.meta_hnsw = meta_hnsw;
+        self.file_level = file_level;
+        self.stale.clear();
+        tracing::debug!("Built graphs of {} vectors in {:.2}s", self.len(), start.elapsed().as_secs_f64());
+    }
+
+    /// Graph of the live vectors
+    fn main_graph(&self) -> Hnsw<'static, f32, DistCosine> {
+        let hnsw = make_hnsw(self.vectors.len());
+        let data: Vec<(&Vec<f32>, usize)> = self
+            .vectors
+            .iter()
+            .filter(|(id, _)| !self.tombstones.contains(id))
+            .map(|(&id, vec)| (vec, id))
+            .collect();
+        if !data.is_empty() {
+            hnsw_insert(&hnsw, &data, self.header.coarse_dim);
+        }
+        hnsw
+    }
+
+    /// Count the search text words of every live item
+    /// Derive `term_stats` and `paths` from the live items
+    fn build_lookups(&mut self) {
+        let mut stats = TermStats::default();
+        let mut paths: HashMap<String, BTreeSet<usize>> = HashMap::new();
+        for (id, meta) in self.metadata_iter() {
+            stats.add(&meta.search_text);
+            paths.entry(meta.path.clone()).or_default().insert(id);
+        }
+        self.term_stats = stats;
+        self.paths = paths;
+    }
+
+    /// Document frequencies of search text words over the live items
+    pub fn term_stats(&self) -> &TermStats {
+        &self.term_stats
+    }
+
+    /// Graph of the live metadata vectors; `None` when there are none
+    fn meta_graph(&self) -> Option<Hnsw<'static, f32, DistCosine>> {
+        let data: Vec<(&Vec<f32>, usize)> = self
+            .meta_vectors
+            .iter()
+            .filter(|(id, vec)| !self.tombstones.contains(id) && is_valid_vector(vec))
+            .map(|(&id, vec)| (vec, id))
+            .collect();
+        (!data.is_empty()).then(|| {
+            let hnsw = make_hnsw(data.len());
+            hnsw.parallel_insert(&data);
+            hnsw
+        })
+    }
+
+    /// Rebuild the file-level graph from the live chunks, or drop it when
+    /// two-stage search is off
+    fn build_file_level(&mut self) {
+        self.file_level = self.file_level_graph();
+    }
+
+    /// File-level graph of the live chunks; `None` when two-stage search is
+    /// off
+    fn file_level_graph(&self) -> Option<FileLevel> {
+        if !self.header.two_stage {
+            return None;
+        }
+        let mut chunks: HashMap<String, Vec<usize>> = HashMap::new();
+        for (id, meta) in self.metadata_iter() {
+            chunks.entry(meta.path.clone()).or_default().push(id);
+        }
+        let paths: Vec<String> = chunks.keys().cloned().collect();
+        let mut files = FileLevel {
+            hnsw: make_hnsw(chunks.len()),
+            points: Vec::new(),
+            current: HashMap::new(),
+            chunks,
+        };
+        add_centroids(&mut files, paths, &self.vectors, &self.tombstones, self.header.coarse_dim);
+        Some(files)
+    }
+
+    /// Add a centroid point for each of `paths` from its current chunks
+    fn update_file_level(&mut self, paths: Vec<String>) {
+        if let Some(files) = self.file_level.as_mut() {
+            add_centroids(files, paths, &self.vectors, &self.tombstones, self.header.coarse_dim);
+        }
+    }
+
+    /// Insert a vector with metadata under the stable ID of `(path, 0)`.
+    /// Invalid vectors (NaN/Inf/zero) still get an ID and metadata (for stats
+    /// accuracy) but are tombstoned immediately so they're excluded from search.
+    pub fn insert(&mut self, vector: &[f32], metadata: IndexMetadata) -> usize {
+        assert_eq!(vector.len(), EMBEDDING_DIM);
+        let id = stable_id(&metadata.path, 0);
+        self.insert_batch_with_ids(vec![(id, vector.to_vec(), metadata)])[0]
+    }
+
+    /// Batch insert vectors with metadata (uses parallel HNSW insert).
+    ///
+    /// IDs are derived from each item's path and its position among items
+    /// with the same path in this batch, so all chunks of a file should be
+    /// inserted together — otherwise use [`Self::insert_batch_with_ids`].
+    /// Invalid vectors (NaN/Inf/zero) are silently skipped from HNSW insertion.
+    pub fn insert_batch(&mut self, items: Vec<(Vec<f32>, IndexMetadata)>) -> Vec<usize> {
+        let mut chunks: HashMap<String, usize> = HashMap::new();
+        let items = items
+            .into_iter()
+            .map(|(vec, meta)| {
+                let chunk = chunks.entry(meta.path.clone()).or_insert(0);
+                let id = stable_id(&meta.path, *chunk);
+                *chunk += 1;
+                (id, vec, meta)
+            })
+            .collect();
+        self.insert_batch_with_ids(items)
+    }
 
-        let start_id = self.next_id;
+    /// Batch insert vectors under caller-supplied IDs (see [`stable_id`]).
+    ///
+    /// A tombstoned ID is reclaimed in place, which is what happens when a
+    /// modified file is re-indexed. If an ID is held by a live item, the next
+    /// free ID is used instead, so the result is deterministic for a given
+    /// insertion order. Returns the IDs actually assigned, in input order.
+    pub fn insert_batch_with_ids(&mut self, items: Vec<(usize, Vec<f32>, IndexMetadata)>) -> Vec<usize> {
+        let mut assigned = Vec::with_capacity(items.len());
         let mut skipped = 0usize;
 
-        // Assign IDs and store metadata + vectors, filtering invalid ones
-        for (i, (vec, meta)) in items.iter().enumerate() {
-            let id = start_id + i;
-            if !is_valid_vector(vec) {
+        for (id, vec, meta) in items {
+            let mut id = id;
+            while self.metadata.contains_key(&id) {
+                if self.tombstones.remove(&id) {
+                    if self.vectors.remove(&id).is_some() {
+                        self.stale.insert(id);
+                    }
+                    break;
+                }
+                id = ((id as u64 + 1) & STABLE_ID_MASK) as usize;
+            }
+            self.meta_vectors.remove(&id);
+            if !is_valid_vector(&vec) {
                 tracing::warn!("Skipping invalid vector for {}: NaN/Inf/zero", meta.path);
-                self.metadata.insert(id, meta.clone());
                 self.tombstones.insert(id);
                 skipped += 1;
             } else {
-                self.vectors.insert(id, vec.clone());
-                self.metadata.insert(id, meta.clone());
+                self.vectors.insert(id, vec);
+                self.term_stats.add(&meta.search_text);
+                self.paths.entry(meta.path.clone()).or_default().insert(id);
             }
+            self.metadata.insert(id, meta);
+            self.next_id = self.next_id.max(id + 1);
+            assigned.push(id);
         }
 
         if skipped > 0 {
@@ -412,39 +1806,259 @@ impl VectorDB {
         }
 
         // Build references for parallel HNSW insert (only valid vectors)
-        let data: Vec<(&Vec<f32>, usize)> = (0..items.len())
-            .filter_map(|i| {
-                let id = start_id + i;
-                self.vectors.get(&id).map(|vec| (vec, id))
+        let data: Vec<(&Vec<f32>, usize)> = assigned
+            .iter()
+            .filter_map(|id| self.vectors.get(id).map(|vec| (vec, *id)))
+            .collect();
+        if !data.is_empty() {
+            hnsw_insert(&self.hnsw, &data, self.header.coarse_dim);
+        }
+
+        if let Some(files) = self.file_level.as_mut() {
+            let mut changed = Vec::new();
+            for &id in &assigned {
+                let path = &self.metadata[&id].path;
+                let ids = files.chunks.entry(path.clone()).or_default();
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+                if !changed.contains(path) {
+                    changed.push(path.clone());
+                }
+            }
+            self.update_file_level(changed);
+        }
+        assigned
+    }
+
+    /// Give items inserted under `ids` (see [`Self::insert_batch_with_ids`])
+    /// a second vector embedded from their metadata text. Inserting an item
+    /// again drops its metadata vector, so these follow every insert. IDs
+    /// without a live item and invalid vectors are skipped.
+    pub fn insert_meta_vectors(&mut self, items: Vec<(usize, Vec<f32>)>) {
+        let mut inserted = Vec::with_capacity(items.len());
+        for (id, vec) in items {
+            if self.get(id).is_some() && is_valid_vector(&vec) {
+                self.meta_vectors.insert(id, vec);
+                inserted.push(id);
+            }
+        }
+        if inserted.is_empty() {
+            return;
+        }
+        let data: Vec<(&Vec<f32>, usize)> = inserted.iter().map(|id| (&self.meta_vectors[id], *id)).collect();
+        let hnsw = self.meta_hnsw.get_or_insert_with(|| make_hnsw(self.vectors.len()));
+        hnsw.parallel_insert(&data);
+    }
+
+    /// Metadata vector of a live item, if it has one
+    pub fn meta_vector(&self, id: usize) -> Option<&[f32]> {
+        self.get(id)?;
+        self.meta_vectors.get(&id).map(Vec::as_slice)
+    }
+
+    /// Whether item `id` belongs to `collection` (any, when unset)
+    fn in_collection(&self, id: usize, collection: Option<Collection>) -> bool {
+        collection.is_none_or(|c| self.metadata.get(&id).is_some_and(|m| m.collection == c))
+    }
+
+    /// Add live items whose metadata vector is among the `fetch` nearest to
+    /// `query` to `found`, with the distance of their code vector
+    fn add_meta_neighbours(
+        &self,
+        query: &[f32],
+        fetch: usize,
+        ef_search: usize,
+        collection: Option<Collection>,
+        found: &mut Vec<(usize, f32)>,
+    ) {
+        let Some(hnsw) = self.meta_hnsw.as_ref() else {
+            return;
+        };
+        let in_collection = |id: &DataId| self.in_collection(*id, collection);
+        let filter = collection.map(|_| &in_collection as &dyn FilterT);
+        let mut seen: HashSet<usize> = found.iter().map(|(id, _)| *id).collect();
+        for n in hnsw.search_filter(query, fetch, ef_search, filter) {
+            if self.tombstones.contains(&n.d_id) || !seen.insert(n.d_id) {
+                continue;
+            }
+            if let Some(vec) = self.vectors.get(&n.d_id) {
+                found.push((n.d_id, cosine_distance(query, vec)));
+            }
+        }
+    }
+
+    /// Query HNSW and return live `(id, distance)` pairs in ascending distance.
+    ///
+    /// Drops tombstoned IDs, rescores reclaimed IDs against their current
+    /// vector (the graph may still hold an outdated point for them), and
+    /// keeps a single entry per ID. With a coarse graph, oversamples on the
+    /// vector prefix and reranks every candidate with the full vector. With
+    /// a `collection`, the graph search only collects that collection's
+    /// items, so a small one isn't crowded out of the `fetch` nearest.
+    fn live_neighbours(
+        &self,
+        query: &[f32],
+        fetch: usize,
+        ef_search: usize,
+        collection: Option<Collection>,
+    ) -> Vec<(usize, f32)> {
+        if let Some(files) = self.file_level.as_ref() {
+            return self.two_stage_neighbours(files, query, fetch, ef_search, collection);
+        }
+        let in_collection = |id: &DataId| self.in_collection(*id, collection);
+        let filter = collection.map(|_| &in_collection as &dyn FilterT);
+        let coarse_dim = self.header.coarse_dim;
+        if coarse_dim > 0 {
+            let candidates = fetch * COARSE_OVERSAMPLE;
+            let mut seen = HashSet::new();
+            let mut found: Vec<(usize, f32)> = self
+                .hnsw
+                .search_filter(&query[..coarse_dim], candidates, ef_search.max(candidates), filter)
+                .into_iter()
+                .filter(|n| !self.tombstones.contains(&n.d_id) && seen.insert(n.d_id))
+                .filter_map(|n| Some((n.d_id, cosine_distance(query, self.vectors.get(&n.d_id)?))))
+                .collect();
+            found.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+            found.truncate(fetch);
+            return found;
+        }
+
+        let mut seen = HashSet::new();
+        let mut found: Vec<(usize, f32)> = self
+            .hnsw
+            .search_filter(query, fetch, ef_search, filter)
+            .into_iter()
+            .filter(|n| !self.tombstones.contains(&n.d_id))
+            .filter_map(|n| {
+                if !seen.insert(n.d_id) {
+                    return None;
+                }
+                if self.stale.contains(&n.d_id) {
+                    let vec = self.vectors.get(&n.d_id)?;
+                    return Some((n.d_id, cosine_distance(query, vec)));
+                }
+                Some((n.d_id, n.distance))
             })
             .collect();
+        if !self.stale.is_empty() {
+            found.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        }
+        found
+    }
 
-        if !data.is_empty() {
-            self.hnsw.parallel_insert(&data);
+    /// Two-stage variant of [`Self::live_neighbours`]: candidate files from
+    /// the file-level graph, then all their live chunks scored exactly
+    fn two_stage_neighbours(
+        &self,
+        files: &FileLevel,
+        query: &[f32],
+        fetch: usize,
+        ef_search: usize,
+        collection: Option<Collection>,
+    ) -> Vec<(usize, f32)> {
+        let wanted = fetch * FILE_CANDIDATES_PER_RESULT;
+        let file_query = match self.header.coarse_dim {
+            0 => query,
+            dim => &query[..dim],
+        };
+        // A file qualifies when any of its chunks is in the collection
+        let file_in_collection = |id: &DataId| {
+            files.points.get(*id).and_then(|path| files.chunks.get(path)).is_some_and(|chunks| {
+                chunks.iter().any(|&chunk| self.in_collection(chunk, collection))
+            })
+        };
+        let filter = collection.map(|_| &file_in_collection as &dyn FilterT);
+        let mut seen = HashSet::new();
+        let mut found: Vec<(usize, f32)> = Vec::new();
+        for n in files.hnsw.search_filter(file_query, wanted, ef_search.max(wanted), filter) {
+            let path = &files.points[n.d_id];
+            if files.current.get(path) != Some(&n.d_id) {
+                continue;
+            }
+            for &id in &files.chunks[path] {
+                if self.tombstones.contains(&id) || !self.in_collection(id, collection) || !seen.insert(id) {
+                    continue;
+                }
+                if let Some(vec) = self.vectors.get(&id) {
+                    found.push((id, cosine_distance(query, vec)));
+                }
+            }
+        }
+        found.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        found.truncate(fetch);
+        found
+    }
+
+    /// HNSW beam width for `fetch` candidates: the tuned one from the header
+    /// (never narrower than `fetch`), else `default`
+    fn beam(&self, fetch: usize, default: usize) -> usize {
+        match self.header.ef_search {
+            0 => default,
+            ef => ef.max(fetch),
+        }
+    }
+
+    /// Pick the narrowest beam of [`EF_SEARCH_STEPS`] whose hybrid-search
+    /// candidates for `queries` overlap those of the widest by at least
+    /// `target` on average, and record it in the header. Saving the index
+    /// persists the choice; an index rebuilt from scratch starts untuned.
+    pub fn tune_ef_search(&mut self, queries: &[Vec<f32>], k: usize, target: f32) -> EfTuning {
+        let fetch = k * 3;
+        let reference = EF_SEARCH_STEPS[EF_SEARCH_STEPS.len() - 1];
+        let reference_ids: Vec<HashSet<usize>> = queries
+            .par_iter()
+            .map(|q| self.live_neighbours(q, fetch, reference.max(fetch), None).into_iter().map(|(id, _)| id).collect())
+            .collect();
+        let mut overlaps = Vec::new();
+        let mut ef_search = reference;
+        for &ef in &EF_SEARCH_STEPS[..EF_SEARCH_STEPS.len() - 1] {
+            let shares: Vec<f32> = queries
+                .par_iter()
+                .zip(&reference_ids)
+                .filter(|(_, expected)| !expected.is_empty())
+                .map(|(q, expected)| {
+                    let found = self.live_neighbours(q, fetch, ef.max(fetch), None);
+                    found.iter().filter(|(id, _)| expected.contains(id)).count() as f32 / expected.len() as f32
+                })
+                .collect();
+            let overlap = if shares.is_empty() { 1.0 } else { shares.iter().sum::<f32>() / shares.len() as f32 };
+            overlaps.push((ef, overlap));
+            if overlap >= target {
+                ef_search = ef;
+                break;
+            }
         }
-        self.next_id = start_id + items.len();
+        self.header.ef_search = ef_search;
+        self.header.ef_tuned_items = self.len();
+        EfTuning { ef_search, overlaps, queries: queries.len() }
     }
 
     /// Search for similar vectors (pure semantic), filtering tombstoned IDs
     pub fn search(&self, query: &[f32], k: usize) -> Vec<SearchResult> {
+        self.semantic_search(query, k, false, None)
+    }
+
+    /// [`Self::search`] with the HNSW beam no wider than the candidates
+    /// fetched: the cheapest search, for requests about to miss a deadline.
+    /// No keyword, SONA or boost reranking. Only items of `collection`
+    /// when set.
+    pub fn search_degraded(&self, query: &[f32], k: usize, collection: Option<Collection>) -> Vec<SearchResult> {
+        self.semantic_search(query, k, true, collection)
+    }
+
+    fn semantic_search(&self, query: &[f32], k: usize, narrow: bool, collection: Option<Collection>) -> Vec<SearchResult> {
         assert_eq!(query.len(), EMBEDDING_DIM);
 
         // Fetch extra candidates to compensate for tombstoned entries
         let extra = if self.tombstones.is_empty() { 0 } else { self.tombstones.len().min(k) };
-        let fetch = k + extra;
-        let ef_search = (fetch * 2).max(50);
-        let results = self.hnsw.search(query, fetch, ef_search);
+        let fetch = k + extra + self.stale.len().min(k);
+        let ef_search = if narrow { fetch } else { self.beam(fetch, (fetch * 2).max(50)) };
 
-        results
+        self.live_neighbours(query, fetch, ef_search, collection)
             .into_iter()
-            .filter(|n| !self.tombstones.contains(&n.d_id))
-            .filter_map(|n| {
-                let id = n.d_id;
-                self.metadata.get(&id).map(|meta| SearchResult {
-                    id,
-                    score: 1.0 - n.distance,
-                    metadata: meta.clone(),
-                })
+            .filter_map(|(id, distance)| {
+                self.metadata.get(&id).map(|meta| SearchResult::new(id, 1.0 - distance, meta.clone()))
             })
             .take(k)
             .collect()
@@ -455,24 +2069,58 @@ impl VectorDB {
     /// Fetches extra candidates from HNSW, then boosts scores based on
     /// keyword matches in path and search_text. This significantly improves
     /// accuracy for type-specific queries (helper, plugin, di.xml, setup, etc.)
+    /// `weights` scale the score parts; project `boosts` from magector.toml
+    /// scale the final scores.
     pub fn hybrid_search(
         &self,
         query: &[f32],
         query_text: &str,
         k: usize,
         sona: Option<&crate::sona::SonaEngine>,
+        boosts: &[crate::config::BoostRule],
+        weights: ScoreWeights,
     ) -> Vec<SearchResult> {
+        self.hybrid_search_pool(query, query_text, k, 0, sona, boosts, weights, None).0
+    }
+
+    /// [`Self::hybrid_search`] reranking `pool` nearest neighbours (at least
+    /// `k`; 0 for the default of 3 × `k`), also returning the candidates
+    /// with their scores before and after reranking, most similar first.
+    /// With a `collection`, candidates are the nearest items of it alone.
+    #[allow(clippy::too_many_arguments)]
+    pub fn hybrid_search_pool(
+        &self,
+        query: &[f32],
+        query_text: &str,
+        k: usize,
+        pool: usize,
+        sona: Option<&crate::sona::SonaEngine>,
+        boosts: &[crate::config::BoostRule],
+        weights: ScoreWeights,
+        collection: Option<Collection>,
+    ) -> (Vec<SearchResult>, Vec<Candidate>) {
         assert_eq!(query.len(), EMBEDDING_DIM);
 
         // Fetch 3x candidates for re-ranking (plus tombstone headroom)
         let extra = if self.tombstones.is_empty() { 0 } else { self.tombstones.len().min(k) };
-        let candidates = k * 3 + extra;
-        let ef_search = (candidates * 2).max(64);
-        let results = self.hnsw.search(query, candidates, ef_search);
+        let pool = if pool == 0 { k * 3 } else { pool.max(k) };
+        let candidates = pool + extra + self.stale.len().min(k);
+        let ef_search = self.beam(candidates, (candidates * 2).max(64));
+        let mut results = self.live_neighbours(query, candidates, ef_search, collection);
+        // Items whose description matches but whose code doesn't are
+        // candidates too
+        if weights.metadata > 0.0 {
+            self.add_meta_neighbours(query, candidates, ef_search, collection, &mut results);
+        }
 
         // Lowercase query terms for matching
         let query_lower = query_text.to_lowercase();
         let query_terms: Vec<&str> = query_lower.split_whitespace().collect();
+        // Matches on words most items mention count for less
+        let term_weights: Vec<f32> = query_terms
+            .iter()
+            .map(|term| 1.0 - weights.idf * (1.0 - self.term_stats.weight(term)))
+            .collect();
 
         // Detect specific file/type patterns in query for strong boosting
         let wants_di_xml = query_lower.contains("di.xml");
@@ -484,14 +2132,34 @@ impl VectorDB {
         let wants_observer = query_terms.contains(&"observer");
         let wants_resolver = query_terms.contains(&"resolver");
         let wants_graphql = query_terms.contains(&"graphql");
-
-        let mut scored: Vec<SearchResult> = results
+        let wants_payment = query_terms.contains(&"payment");
+        let wants_carrier = query_terms.iter().any(|t| matches!(*t, "shipping" | "carrier" | "carriers"));
+        // `bin/magento indexer:reindex`: command names match the class's
+        // recorded "console_command" terms
+        let command_names: Vec<String> = query_terms
+            .iter()
+            .map(|t| t.trim_matches(|c: char| !c.is_ascii_alphanumeric()))
+            .filter(|t| crate::console::is_command_name(t))
+            .map(|t| format!("console_command {} ", t))
+            .collect();
+        let wants_console = !command_names.is_empty()
+            || query_terms.iter().any(|t| matches!(*t, "bin/magento" | "console" | "cli"));
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut scored: Vec<(SearchResult, f32)> = results
             .into_iter()
-            .filter(|n| !self.tombstones.contains(&n.d_id))
-            .filter_map(|n| {
-                let id = n.d_id;
-                self.metadata.get(&id).map(|meta| {
-                    let semantic_score = 1.0 - n.distance;
+            .filter_map(|(id, distance)| {
+                self.metadata.get(&id).filter(|meta| !meta.is_generated || weights.generated > 0.0).map(|meta| {
+                    let semantic_score = match self.meta_vectors.get(&id).filter(|_| weights.metadata > 0.0) {
+                        Some(meta_vec) => {
+                            (1.0 - weights.metadata) * (1.0 - distance)
+                                + weights.metadata * (1.0 - cosine_distance(query, meta_vec))
+                        }
+                        None => 1.0 - distance,
+                    };
 
                     // Compute keyword bonus from path and search_text
                     let path_lower = meta.path.to_lowercase();
@@ -500,23 +2168,25 @@ impl VectorDB {
                     let mut keyword_bonus: f32 = 0.0;
                     let mut matched_terms = 0u32;
 
-                    for term in &query_terms {
-                        if term.len() < 3 { continue; }
+                    for (term, term_weight) in query_terms.iter().zip(&term_weights) {
+                        if term.len() < 3 || self.term_stats.is_stop_term(term) { continue; }
 
                         // Path match is strongest signal
                         if path_lower.contains(term) {
-                            keyword_bonus += 0.08;
+                            keyword_bonus += 0.08 * term_weight;
                             matched_terms += 1;
                         }
-                        // Search text match
-                        if search_lower.contains(term) {
-                            keyword_bonus += 0.03;
+                        // Search text match, or a method the class gets from a trait
+                        if search_lower.contains(term)
+                            || meta.trait_methods.iter().any(|m| m.to_lowercase().contains(term))
+                        {
+                            keyword_bonus += 0.03 * term_weight;
                             matched_terms += 1;
                         }
                         // Class name match
                         if let Some(ref cn) = meta.class_name {
                             if cn.to_lowercase().contains(term) {
-                                keyword_bonus += 0.06;
+                                keyword_bonus += 0.06 * term_weight;
                                 matched_terms += 1;
                             }
                         }
@@ -524,7 +2194,7 @@ impl VectorDB {
                         if let Some(ref mt) = meta.magento_type {
                             let mt_lower = mt.to_lowercase();
                             if mt_lower.contains(term) || term.replace('.', "_") == mt_lower {
-                                keyword_bonus += 0.10;
+                                keyword_bonus += 0.10 * term_weight;
                                 matched_terms += 1;
                             }
                         }
@@ -559,6 +2229,18 @@ impl VectorDB {
                     if wants_graphql && (mtype == "graphql_resolver" || mtype == "graphql_schema" || path_lower.contains("graph-ql") || path_lower.contains("graphql")) {
                         keyword_bonus += 0.10;
                     }
+                    if wants_payment && mtype == "payment_method" {
+                        keyword_bonus += 0.15;
+                    }
+                    if wants_carrier && mtype == "shipping_carrier" {
+                        keyword_bonus += 0.15;
+                    }
+                    if wants_console && mtype == "console" {
+                        keyword_bonus += 0.15;
+                    }
+                    if command_names.iter().any(|name| search_lower.contains(name.as_str())) {
+                        keyword_bonus += 0.25;
+                    }
 
                     // Multi-term bonus: reward results matching many query terms
                     if matched_terms >= 3 {
@@ -579,26 +2261,58 @@ impl VectorDB {
                     // Cap keyword bonus to avoid overwhelming semantic score
                     let keyword_bonus = keyword_bonus.min(0.45);
                     let sona_adj = sona.map(|s| s.score_adjustment(query_text, meta)).unwrap_or(0.0);
-                    let final_score = semantic_score + keyword_bonus + sona_adj;
-
-                    SearchResult {
-                        id,
-                        score: final_score,
-                        metadata: meta.clone(),
-                    }
+                    let boost: f32 = boosts
+                        .iter()
+                        .filter(|rule| rule.matches(&meta.path, meta.magento_type.as_deref(), &query_lower))
+                        .map(|rule| rule.factor)
+                        .product();
+                    let generated = if meta.is_generated { weights.generated } else { 1.0 };
+                    let recency = if weights.recency > 0.0 { recency_bonus(meta.last_commit, now) } else { 0.0 };
+                    let final_score = (weights.semantic * semantic_score
+                        + weights.keyword * keyword_bonus
+                        + weights.sona * sona_adj
+                        + weights.recency * recency)
+                        * boost
+                        * generated;
+
+                    (SearchResult::new(id, final_score, meta.clone()), semantic_score)
                 })
             })
             .collect();
 
         // Sort by final score descending and take top k
-        scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        scored.sort_by(|a, b| b.0.score.partial_cmp(&a.0.score).unwrap_or(std::cmp::Ordering::Equal));
+        let mut candidates: Vec<Candidate> = scored
+            .iter()
+            .enumerate()
+            .map(|(rank, (result, semantic))| Candidate {
+                id: result.id,
+                path: result.metadata.path.clone(),
+                method: result.metadata.method_name.clone(),
+                semantic: *semantic,
+                score: result.score,
+                rank,
+                core: false,
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.semantic.total_cmp(&a.semantic));
         scored.truncate(k);
-        scored
+        (scored.into_iter().map(|(result, _)| result).collect(), candidates)
     }
 
     /// Mark a vector ID as tombstoned (soft-delete)
     pub fn tombstone(&mut self, id: usize) {
-        self.tombstones.insert(id);
+        if self.tombstones.insert(id) {
+            if let Some(meta) = self.metadata.get(&id) {
+                self.term_stats.remove(&meta.search_text);
+                if let Some(ids) = self.paths.get_mut(&meta.path) {
+                    ids.remove(&id);
+                    if ids.is_empty() {
+                        self.paths.remove(&meta.path);
+                    }
+                }
+            }
+        }
     }
 
     /// Remove all vectors whose metadata path matches the given path.
@@ -609,23 +2323,23 @@ impl VectorDB {
             .map(|(&id, _)| id)
             .collect();
         for &id in &ids {
-            self.tombstones.insert(id);
+            self.tombstone(id);
         }
         ids
     }
 
-    /// Ratio of tombstoned entries to total vectors (0.0 – 1.0)
+    /// Ratio of dead HNSW points (tombstoned or reclaimed) to total vectors
     pub fn tombstone_ratio(&self) -> f64 {
         if self.vectors.is_empty() {
             return 0.0;
         }
-        self.tombstones.len() as f64 / self.vectors.len() as f64
+        (self.tombstones.len() + self.stale.len()) as f64 / self.vectors.len() as f64
     }
 
     /// Compact: rebuild HNSW and purge tombstoned entries from all maps.
     /// This reclaims memory and restores search performance.
     pub fn compact(&mut self) {
-        if self.tombstones.is_empty() {
+        if self.tombstones.is_empty() && self.stale.is_empty() {
             return;
         }
 
@@ -633,19 +2347,131 @@ impl VectorDB {
         for &id in &self.tombstones {
             self.metadata.remove(&id);
             self.vectors.remove(&id);
+            self.meta_vectors.remove(&id);
         }
 
-        // Rebuild HNSW from live vectors
-        let capacity = self.vectors.len().max(HNSW_MIN_CAPACITY);
-        self.hnsw = make_hnsw(capacity);
-        let data: Vec<(&Vec<f32>, usize)> = self.vectors.iter()
-            .map(|(&id, vec)| (vec, id))
+        self.tombstones.clear();
+        self.rebuild_hnsw();
+    }
+
+    /// Check the stored items for damage (`magector fsck`): vectors and
+    /// metadata without each other, invalid and non-normalized vectors, and
+    /// whether a search for the vector of each of up to `graph_samples` live
+    /// items, spread over the index, finds that item (or an identical one).
+    pub fn check_integrity(&self, graph_samples: usize) -> IntegrityReport {
+        let mut report = IntegrityReport { items: self.len(), ..Default::default() };
+        report.orphan_vectors = self
+            .vectors
+            .keys()
+            .chain(self.meta_vectors.keys())
+            .filter(|id| !self.metadata.contains_key(id))
+            .copied()
             .collect();
-        if !data.is_empty() {
-            self.hnsw.parallel_insert(&data);
+        report.orphan_vectors.sort_unstable();
+        report.orphan_vectors.dedup();
+        let valid = |vec: &[f32]| vec.len() == EMBEDDING_DIM && is_valid_vector(vec);
+        report.invalid_vectors = self
+            .vectors
+            .iter()
+            .filter(|(id, vec)| self.metadata.contains_key(id) && !valid(vec))
+            .map(|(&id, _)| id)
+            .collect();
+        report.invalid_vectors.sort_unstable();
+
+        let mut live: Vec<usize> = self.metadata_iter().map(|(id, _)| id).collect();
+        live.sort_unstable();
+        let mut searchable = Vec::with_capacity(live.len());
+        for id in live {
+            match self.vectors.get(&id) {
+                None => report.missing_vectors.push(id),
+                Some(vec) if valid(vec) => {
+                    let norm = vec.iter().map(|x| x * x).sum::<f32>().sqrt();
+                    if (norm - 1.0).abs() > NORM_TOLERANCE {
+                        report.unnormalized.push(id);
+                    }
+                    searchable.push(id);
+                }
+                Some(_) => {}
+            }
         }
 
-        self.tombstones.clear();
+        if graph_samples > 0 && !searchable.is_empty() {
+            let step = (searchable.len() / graph_samples).max(1);
+            for &id in searchable.iter().step_by(step).take(graph_samples) {
+                report.graph_checked += 1;
+                let found = self.live_neighbours(&self.vectors[&id], GRAPH_CHECK_K, (GRAPH_CHECK_K * 2).max(50), None);
+                if !found.iter().any(|&(other, distance)| other == id || distance <= 1e-6) {
+                    report.unreachable.push(id);
+                }
+            }
+        }
+        report
+    }
+
+    /// Fix what [`Self::check_integrity`] found: drop orphaned vectors and
+    /// every item of a file with a missing or invalid vector, normalize
+    /// vectors, and rebuild the graphs. Returns the dropped files, which
+    /// need indexing again.
+    pub fn repair(&mut self, report: &IntegrityReport) -> Vec<String> {
+        for id in &report.orphan_vectors {
+            self.vectors.remove(id);
+            self.meta_vectors.remove(id);
+        }
+        let dropped: BTreeSet<String> = report
+            .missing_vectors
+            .iter()
+            .chain(&report.invalid_vectors)
+            .filter_map(|id| self.metadata.get(id).map(|meta| meta.path.clone()))
+            .collect();
+        let ids: Vec<usize> =
+            self.metadata.iter().filter(|(_, meta)| dropped.contains(&meta.path)).map(|(&id, _)| id).collect();
+        for id in ids {
+            self.metadata.remove(&id);
+            self.vectors.remove(&id);
+            self.meta_vectors.remove(&id);
+            self.tombstones.remove(&id);
+        }
+        for id in &report.unnormalized {
+            if let Some(vec) = self.vectors.get_mut(id) {
+                let norm = vec.iter().map(|x| x * x).sum::<f32>().sqrt();
+                if norm > 0.0 {
+                    vec.iter_mut().for_each(|x| *x /= norm);
+                }
+            }
+        }
+        self.rebuild_hnsw();
+        self.build_lookups();
+        dropped.into_iter().collect()
+    }
+
+    /// Change the metadata of every stored item in place. Vectors and
+    /// graphs are untouched, so `f` must not change paths.
+    pub fn update_metadata(&mut self, mut f: impl FnMut(&mut IndexMetadata)) {
+        for meta in self.metadata.values_mut() {
+            f(meta);
+        }
+        self.build_lookups();
+    }
+
+    /// [`Self::update_metadata`] for the live items of `paths` only
+    pub fn update_metadata_of<'a>(
+        &mut self,
+        paths: impl IntoIterator<Item = &'a String>,
+        mut f: impl FnMut(&mut IndexMetadata),
+    ) {
+        for path in paths {
+            for id in self.paths.get(path).into_iter().flatten() {
+                let Some(meta) = self.metadata.get_mut(id) else { continue };
+                self.term_stats.remove(&meta.search_text);
+                f(meta);
+                self.term_stats.add(&meta.search_text);
+            }
+        }
+    }
+
+    /// Live items of `path`, by ID
+    pub fn items_of(&self, path: &str) -> impl Iterator<Item = (usize, &IndexMetadata)> {
+        self.paths.get(path).into_iter().flatten().filter_map(|&id| self.metadata.get(&id).map(|meta| (id, meta)))
     }
 
     /// Iterate over `(id, metadata)` pairs for all non-tombstoned vectors.
@@ -657,6 +2483,66 @@ impl VectorDB {
             .map(|(&id, meta)| (id, meta))
     }
 
+    /// Group live vector IDs by file path (IDs sorted per path).
+    pub fn ids_by_path(&self) -> HashMap<String, Vec<usize>> {
+        let mut map: HashMap<String, Vec<usize>> = HashMap::new();
+        for (id, meta) in self.metadata_iter() {
+            map.entry(meta.path.clone()).or_default().push(id);
+        }
+        for ids in map.values_mut() {
+            ids.sort_unstable();
+        }
+        map
+    }
+
+    /// Vector of a live item
+    pub fn get_vector(&self, id: usize) -> Option<&[f32]> {
+        self.get(id).map(|(vector, _)| vector)
+    }
+
+    /// Vectors of the live items of `paths`, in the order given and by ID
+    /// within a file
+    pub fn vectors_by_path(&self, paths: &[&str]) -> Vec<ItemVector> {
+        let wanted: HashSet<&str> = paths.iter().copied().collect();
+        let mut ids: Vec<(usize, usize)> = self
+            .metadata_iter()
+            .filter(|(_, meta)| wanted.contains(meta.path.as_str()))
+            .map(|(id, meta)| (paths.iter().position(|p| *p == meta.path).unwrap_or(0), id))
+            .collect();
+        ids.sort_unstable();
+        ids.into_iter().filter_map(|(_, id)| self.item_vector(id)).collect()
+    }
+
+    /// [`ItemVector`] of a live item
+    pub fn item_vector(&self, id: usize) -> Option<ItemVector> {
+        let (vector, meta) = self.get(id)?;
+        Some(ItemVector {
+            id,
+            path: meta.path.clone(),
+            method: meta.method_name.clone(),
+            vector: vector.to_vec(),
+            meta_vector: self.meta_vectors.get(&id).cloned(),
+        })
+    }
+
+    /// Vector and metadata of a live item
+    pub fn get(&self, id: usize) -> Option<(&[f32], &IndexMetadata)> {
+        if self.tombstones.contains(&id) {
+            return None;
+        }
+        Some((self.vectors.get(&id)?.as_slice(), self.metadata.get(&id)?))
+    }
+
+    /// SHA-256 over the live items in ID order (ID, vector, metadata,
+    /// metadata vector).
+    ///
+    /// Independent of tombstones, HNSW layout and the header, so two DBs with
+    /// the same searchable contents hash the same. Stored as the header
+    /// checksum and used to match delta patches to their base.
+    pub fn content_hash(&self) -> String {
+        hash_items(&self.metadata, &self.vectors, &self.meta_vectors, &self.tombstones)
+    }
+
     /// Get total number of live (non-tombstoned) vectors
     pub fn len(&self) -> usize {
         self.metadata.len().saturating_sub(self.tombstones.len())
@@ -672,8 +2558,15 @@ impl VectorDB {
         self.hnsw = make_hnsw(HNSW_MIN_CAPACITY);
         self.metadata.clear();
         self.vectors.clear();
+        self.meta_vectors.clear();
+        self.meta_hnsw = None;
         self.tombstones.clear();
+        self.stale.clear();
+        self.term_stats = TermStats::default();
+        self.paths.clear();
         self.next_id = 0;
+        self.header = IndexHeader::default();
+        self.file_level = None;
     }
 }
 
@@ -695,30 +2588,8 @@ mod tests {
         let metadata = IndexMetadata {
             path: "test.php".to_string(),
             file_type: "php".to_string(),
-            magento_type: None,
-            class_name: None,
-            class_type: None,
-            method_name: None,
-            methods: Vec::new(),
-            namespace: None,
-            module: None,
-            area: None,
-            extends: None,
-            implements: Vec::new(),
-            is_controller: false,
-            is_repository: false,
-            is_plugin: false,
-            is_observer: false,
-            is_model: false,
-            is_block: false,
-            is_resolver: false,
-            is_api_interface: false,
-            is_ui_component: false,
-            is_widget: false,
-            is_mixin: false,
-            js_dependencies: Vec::new(),
             search_text: "test".to_string(),
-
+            ..Default::default()
         };
 
         db.insert(&vector, metadata);
@@ -732,30 +2603,8 @@ mod tests {
         IndexMetadata {
             path: path.to_string(),
             file_type: "php".to_string(),
-            magento_type: None,
-            class_name: None,
-            class_type: None,
-            method_name: None,
-            methods: Vec::new(),
-            namespace: None,
-            module: None,
-            area: None,
-            extends: None,
-            implements: Vec::new(),
-            is_controller: false,
-            is_repository: false,
-            is_plugin: false,
-            is_observer: false,
-            is_model: false,
-            is_block: false,
-            is_resolver: false,
-            is_api_interface: false,
-            is_ui_component: false,
-            is_widget: false,
-            is_mixin: false,
-            js_dependencies: Vec::new(),
             search_text: "test".to_string(),
-
+            ..Default::default()
         }
     }
 
@@ -770,7 +2619,7 @@ mod tests {
 
         // Before tombstone: both found
         let results = db.search(&v1, 10);
-        assert!(results.len() >= 1);
+        assert!(!results.is_empty());
 
         // Tombstone id1
         db.tombstone(id1);
@@ -780,6 +2629,24 @@ mod tests {
         assert!(results.iter().all(|r| r.id != id1));
     }
 
+    #[test]
+    fn test_search_degraded() {
+        let mut db = VectorDB::new();
+        for i in 0..20 {
+            let mut v = vec![0.0f32; EMBEDDING_DIM];
+            v[i] = 1.0;
+            db.insert(&v, make_test_meta(&format!("file{}.php", i)));
+        }
+        let mut query = vec![0.0f32; EMBEDDING_DIM];
+        query[3] = 1.0;
+
+        let results = db.search_degraded(&query, 5, None);
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].metadata.path, "file3.php");
+        // Not reranked: the score is the cosine similarity alone
+        assert!((results[0].score - 1.0).abs() < 1e-5);
+    }
+
     #[test]
     fn test_remove_by_path() {
         let mut db = VectorDB::new();
@@ -805,7 +2672,420 @@ mod tests {
         db.compact();
         assert_eq!(db.tombstones.len(), 0);
         assert_eq!(db.vectors.len(), 1);
-        assert!(db.metadata.contains_key(&(id + 1))); // "new.php" still there
+        assert!(db.metadata.contains_key(&stable_id("new.php", 0))); // "new.php" still there
+    }
+
+    #[test]
+    fn test_compact_and_load_rebuild_every_graph() {
+        let one_hot = |i: usize| {
+            let mut v = vec![0.0f32; EMBEDDING_DIM];
+            v[i % EMBEDDING_DIM] = 1.0;
+            v
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("index.db");
+        let mut db = VectorDB::new();
+        db.set_two_stage(true);
+        let ids = db.insert_batch((0..300).map(|i| (one_hot(i), make_test_meta(&format!("f{}.php", i / 2)))).collect());
+        db.insert_meta_vectors(ids.iter().map(|&id| (id, one_hot(id))).collect());
+        for i in 0..100 {
+            db.remove_by_path(&format!("f{}.php", i));
+        }
+
+        db.compact();
+        let check = |db: &VectorDB| {
+            assert_eq!(db.len(), 100);
+            let report = db.check_integrity(1000);
+            assert!(report.is_clean(), "{report:?}");
+            assert_eq!(db.meta_hnsw.as_ref().unwrap().get_nb_point(), 100);
+            assert_eq!(db.file_level.as_ref().unwrap().current.len(), 50);
+            assert_eq!(db.search(&one_hot(250), 1)[0].metadata.path, "f125.php");
+        };
+        check(&db);
+        db.save(&db_path).unwrap();
+        check(&VectorDB::open(&db_path).unwrap());
+    }
+
+    #[test]
+    fn test_check_integrity_and_repair() {
+        let one_hot = |i: usize| {
+            let mut v = vec![0.0f32; EMBEDDING_DIM];
+            v[i] = 1.0;
+            v
+        };
+        let mut db = VectorDB::new();
+        for i in 0..20 {
+            db.insert(&one_hot(i), make_test_meta(&format!("file{}.php", i)));
+        }
+        let report = db.check_integrity(100);
+        assert!(report.is_clean(), "{report:?}");
+        assert_eq!((report.items, report.graph_checked), (20, 20));
+
+        let id = |i: usize| stable_id(&format!("file{}.php", i), 0);
+        db.vectors.insert(7, one_hot(30));
+        db.vectors.remove(&id(1));
+        db.vectors.get_mut(&id(2)).unwrap()[5] = f32::NAN;
+        db.vectors.get_mut(&id(3)).unwrap()[3] = 2.0;
+        // In the maps but never added to the graph
+        db.metadata.insert(id(40), make_test_meta("file40.php"));
+        db.vectors.insert(id(40), one_hot(40));
+
+        let report = db.check_integrity(100);
+        assert_eq!(report.orphan_vectors, [7]);
+        assert_eq!(report.missing_vectors, [id(1)]);
+        assert_eq!(report.invalid_vectors, [id(2)]);
+        assert_eq!(report.unnormalized, [id(3)]);
+        assert_eq!(report.unreachable, [id(40)]);
+        assert!(report.graph_damaged() && !report.is_clean());
+
+        assert_eq!(db.repair(&report), ["file1.php", "file2.php"]);
+        let report = db.check_integrity(100);
+        assert!(report.is_clean(), "{report:?}");
+        assert_eq!(report.items, 19);
+        assert_eq!(db.search(&one_hot(40), 1)[0].id, id(40));
+    }
+
+    #[test]
+    fn test_hybrid_search_boost_rules() {
+        let mut near = vec![0.05f32; EMBEDDING_DIM];
+        near[10] = 1.0;
+        let mut far = near.clone();
+        far[11] = 0.3;
+        let mut db = VectorDB::new();
+        db.insert_batch(vec![
+            (near.clone(), make_test_meta("vendor/magento/module-cart/Cart.php")),
+            (far, make_test_meta("app/code/MyVendor/Cart/Cart.php")),
+        ]);
+        let top = |boosts: &[crate::config::BoostRule]| {
+            db.hybrid_search(&near, "xy", 2, None, boosts, ScoreWeights::default())[0].metadata.path.clone()
+        };
+        assert_eq!(top(&[]), "vendor/magento/module-cart/Cart.php");
+
+        let rules = crate::config::ProjectConfig::parse(r#"boost = [{ path_prefix = "app/code/MyVendor", factor = 1.5 }]"#)
+            .unwrap()
+            .boost;
+        assert_eq!(top(&rules), "app/code/MyVendor/Cart/Cart.php");
+    }
+
+    #[test]
+    fn test_hybrid_search_weights() {
+        let mut near = vec![0.05f32; EMBEDDING_DIM];
+        near[10] = 1.0;
+        let mut close = near.clone();
+        close[11] = 0.2;
+        let mut db = VectorDB::new();
+        db.insert_batch(vec![
+            (near.clone(), make_test_meta("Model/Cart.php")),
+            (close, make_test_meta("Model/Checkout.php")),
+        ]);
+        let top = |weights: ScoreWeights| db.hybrid_search(&near, "checkout", 2, None, &[], weights)[0].metadata.path.clone();
+        // The path match outweighs the small semantic gap...
+        assert_eq!(top(ScoreWeights::default()), "Model/Checkout.php");
+        // ...unless keyword matches are switched off for the request
+        assert_eq!(top(ScoreWeights { keyword: 0.0, ..Default::default() }), "Model/Cart.php");
+
+        assert!(ScoreWeights { sona: -1.0, ..Default::default() }.validate().is_err());
+        let parsed: ScoreWeights = serde_json::from_str(r#"{"semantic":0.7,"keyword":0.3}"#).unwrap();
+        assert_eq!(parsed, ScoreWeights { semantic: 0.7, keyword: 0.3, sona: 1.0, generated: 0.5, recency: 0.0, disabled: 1.0, metadata: 0.3, idf: 1.0 });
+        assert!(serde_json::from_str::<ScoreWeights>(r#"{"semantics":1}"#).is_err());
+    }
+
+    #[test]
+    fn test_hybrid_search_pool_returns_candidates() {
+        let mut db = VectorDB::new();
+        db.insert_batch(
+            (0..12)
+                .map(|i| {
+                    let mut v = vec![0.0f32; EMBEDDING_DIM];
+                    v[10] = 1.0;
+                    v[11] = i as f32 * 0.3;
+                    (v, make_test_meta(&format!("Model/Item{}.php", i)))
+                })
+                .collect(),
+        );
+        let mut query = vec![0.0f32; EMBEDDING_DIM];
+        query[10] = 1.0;
+
+        let (results, candidates) = db.hybrid_search_pool(&query, "item", 2, 0, None, &[], ScoreWeights::default(), None);
+        assert_eq!(results.len(), 2);
+        assert_eq!(candidates.len(), 6, "three times k by default");
+        assert!(candidates.windows(2).all(|w| w[0].semantic >= w[1].semantic));
+        assert_eq!(candidates[0].path, "Model/Item0.php");
+        assert!(candidates.iter().any(|c| c.rank == 0 && c.path == results[0].metadata.path));
+
+        let (_, candidates) = db.hybrid_search_pool(&query, "item", 2, 10, None, &[], ScoreWeights::default(), None);
+        assert_eq!(candidates.len(), 10);
+    }
+
+    #[test]
+    fn test_hybrid_search_pool_within_collection() {
+        let mut db = VectorDB::new();
+        // Fewer items than HNSW_M, so the graph links every pair
+        let mut items: Vec<(Vec<f32>, IndexMetadata)> = (0..20)
+            .map(|i| {
+                let mut v = vec![0.0f32; EMBEDDING_DIM];
+                v[10] = 1.0;
+                v[11] = i as f32 * 0.01;
+                (v, make_test_meta(&format!("Model/Item{}.php", i)))
+            })
+            .collect();
+        let mut v = vec![0.0f32; EMBEDDING_DIM];
+        v[10] = 1.0;
+        v[12] = 0.5;
+        let mut doc = make_test_meta("docs/checkout.md");
+        doc.collection = Collection::Docs;
+        items.push((v, doc));
+        db.insert_batch(items);
+        let mut query = vec![0.0f32; EMBEDDING_DIM];
+        query[10] = 1.0;
+
+        // Every code item is nearer than the document
+        let (results, _) = db.hybrid_search_pool(&query, "", 3, 0, None, &[], ScoreWeights::default(), None);
+        assert!(results.iter().all(|r| r.metadata.collection == Collection::Code));
+
+        let (results, candidates) =
+            db.hybrid_search_pool(&query, "", 3, 0, None, &[], ScoreWeights::default(), Some(Collection::Docs));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].metadata.path, "docs/checkout.md");
+        assert_eq!(candidates.len(), 1);
+        let degraded = db.search_degraded(&query, 3, Some(Collection::Docs));
+        assert_eq!(degraded.len(), 1);
+        assert_eq!(degraded[0].metadata.path, "docs/checkout.md");
+    }
+
+    #[test]
+    fn test_tune_ef_search_picks_narrowest_stable_beam() {
+        // Spread-out unit vectors from a sine pattern
+        let vector = |i: usize| {
+            let v: Vec<f32> = (0..EMBEDDING_DIM).map(|d| ((i * 31 + d * 7) as f32 * 0.37).sin()).collect();
+            let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+            v.into_iter().map(|x| x / norm).collect::<Vec<f32>>()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.db");
+        let mut db = VectorDB::new();
+        db.insert_batch((0..400).map(|i| (vector(i), make_test_meta(&format!("f{}.php", i)))).collect());
+        let queries: Vec<Vec<f32>> = (1000..1020).map(vector).collect();
+
+        let strict = db.tune_ef_search(&queries, 10, 1.1);
+        assert_eq!(strict.ef_search, 128);
+        assert_eq!(strict.overlaps.len(), EF_SEARCH_STEPS.len() - 1);
+        assert_eq!(strict.queries, 20);
+
+        let tuning = db.tune_ef_search(&queries, 10, 0.0);
+        assert_eq!(tuning.ef_search, 32);
+        assert_eq!(tuning.overlaps.len(), 1);
+        assert_eq!(db.header().ef_search, 32);
+        // The beam never gets narrower than the candidates fetched
+        assert_eq!(db.beam(100, 200), 100);
+        assert_eq!(db.hybrid_search(&vector(5), "", 1, None, &[], ScoreWeights::default())[0].metadata.path, "f5.php");
+
+        assert_eq!(db.header().ef_tuned_items, 400);
+        db.save(&path).unwrap();
+        assert_eq!(VectorDB::load_existing(&path).unwrap().header().ef_search, 32);
+
+        // Rewriting only the header keeps the items and their checksum
+        VectorDB::update_saved_header(&path, |header| header.ef_search = 48).unwrap();
+        let loaded = VectorDB::load_existing(&path).unwrap();
+        assert_eq!((loaded.header().ef_search, loaded.header().ef_tuned_items), (48, 400));
+        assert_eq!(loaded.len(), 400);
+        assert_eq!(loaded.content_hash(), db.content_hash());
+        assert!(!path.with_extension("db.header.tmp").exists());
+    }
+
+    #[test]
+    fn test_vectors_by_path() {
+        let vector = |x: f32| {
+            let mut v = vec![0.05f32; EMBEDDING_DIM];
+            v[0] = x;
+            v
+        };
+        let mut db = VectorDB::new();
+        let a = db.insert(&vector(0.1), make_test_meta("a.php"));
+        let b = db.insert_batch(vec![
+            (vector(0.2), IndexMetadata { method_name: Some("run".into()), ..make_test_meta("b.php") }),
+            (vector(0.3), make_test_meta("b.php")),
+        ]);
+        db.insert_meta_vectors(vec![(a, vector(0.9))]);
+
+        assert_eq!(db.get_vector(a), Some(vector(0.1).as_slice()));
+        let items = db.vectors_by_path(&["b.php", "a.php", "none.php"]);
+        let paths: Vec<&str> = items.iter().map(|item| item.path.as_str()).collect();
+        assert_eq!(paths, ["b.php", "b.php", "a.php"]);
+        assert_eq!(items[2].meta_vector, Some(vector(0.9)));
+        let json = serde_json::to_value(&items[0]).unwrap();
+        assert!(json.get("meta_vector").is_none());
+        assert_eq!(json["vector"].as_array().unwrap().len(), EMBEDDING_DIM);
+        assert!(items.iter().any(|item| item.method.as_deref() == Some("run")));
+
+        db.remove_by_path("b.php");
+        assert!(db.get_vector(b[0]).is_none());
+        assert!(db.vectors_by_path(&["b.php"]).is_empty());
+    }
+
+    #[test]
+    fn test_idf_keyword_weighting() {
+        let mut near = vec![0.05f32; EMBEDDING_DIM];
+        near[10] = 1.0;
+        let mut far = vec![0.05f32; EMBEDDING_DIM];
+        far[200] = 1.0;
+        let mut items = vec![
+            (near.clone(), IndexMetadata { search_text: "magento".into(), ..make_test_meta("Magento/Cart.php") }),
+            (near.clone(), IndexMetadata { search_text: "magento wishlist".into(), ..make_test_meta("Model/Item.php") }),
+        ];
+        for i in 0..10 {
+            let meta = IndexMetadata { search_text: "magento".into(), ..make_test_meta(&format!("Block/B{}.php", i)) };
+            items.push((far.clone(), meta));
+        }
+        let mut db = VectorDB::new();
+        db.insert_batch(items);
+        assert_eq!(db.term_stats().df("magento"), 12);
+
+        let top = |db: &VectorDB, weights: ScoreWeights| {
+            db.hybrid_search(&near, "magento wishlist", 2, None, &[], weights)[0].metadata.path.clone()
+        };
+        // "magento" is in every item, so the rarer "wishlist" match decides...
+        assert_eq!(top(&db, ScoreWeights::default()), "Model/Item.php");
+        // ...while unweighted, the path match on "magento" wins
+        assert_eq!(top(&db, ScoreWeights { idf: 0.0, ..Default::default() }), "Magento/Cart.php");
+
+        db.remove_by_path("Block/B0.php");
+        assert_eq!(db.term_stats().df("magento"), 11);
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("idf.db");
+        db.save(&db_path).unwrap();
+        assert_eq!(VectorDB::open(&db_path).unwrap().term_stats().df("magento"), 11);
+    }
+
+    #[test]
+    fn test_items_of_path() {
+        let mut db = VectorDB::new();
+        let vector = |i: usize| {
+            let mut v = vec![0.01f32; EMBEDDING_DIM];
+            v[i] = 1.0;
+            v
+        };
+        db.insert_batch(vec![
+            (vector(1), make_test_meta("a.php")),
+            (vector(2), IndexMetadata { search_text: "first".into(), ..make_test_meta("b.php") }),
+            (vector(3), IndexMetadata { search_text: "second".into(), ..make_test_meta("b.php") }),
+        ]);
+        let ids: Vec<usize> = db.items_of("b.php").map(|(id, _)| id).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids[0] < ids[1]);
+
+        db.update_metadata_of(&["b.php".to_string()], |meta| meta.search_text = "linked".into());
+        assert!(db.items_of("b.php").all(|(_, meta)| meta.search_text == "linked"));
+        assert_eq!(db.term_stats().df("linked"), 2);
+        assert_eq!(db.term_stats().df("first"), 0);
+        assert_ne!(db.items_of("a.php").next().unwrap().1.search_text, "linked");
+
+        db.remove_by_path("b.php");
+        assert_eq!(db.items_of("b.php").count(), 0);
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("paths.db");
+        db.save(&db_path).unwrap();
+        assert_eq!(VectorDB::open(&db_path).unwrap().items_of("a.php").count(), 1);
+    }
+
+    #[test]
+    fn test_stop_terms_skip_keyword_scoring() {
+        let mut near = vec![0.05f32; EMBEDDING_DIM];
+        near[10] = 1.0;
+        let mut far = vec![0.05f32; EMBEDDING_DIM];
+        far[200] = 1.0;
+        let mut items = vec![(near.clone(), IndexMetadata { search_text: "magento".into(), ..make_test_meta("Magento/Cart.php") })];
+        for i in 0..120 {
+            let meta = IndexMetadata { search_text: "magento block".into(), ..make_test_meta(&format!("Block/B{}.php", i)) };
+            items.push((far.clone(), meta));
+        }
+        let mut db = VectorDB::new();
+        db.insert_batch(items);
+        assert!(db.term_stats().is_stop_term("magento"));
+
+        // Even unweighted by IDF, a stop term earns no keyword bonus
+        let score = |query: &str| {
+            db.hybrid_search(&near, query, 1, None, &[], ScoreWeights { idf: 0.0, ..Default::default() })[0].score
+        };
+        assert_eq!(score("magento"), score("unrelated"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("stop.db");
+        db.save(&db_path).unwrap();
+        assert_eq!(VectorDB::open(&db_path).unwrap().header().stop_terms, ["block", "magento"]);
+    }
+
+    #[test]
+    fn test_generated_code_ranks_below_source() {
+        let mut near = vec![0.05f32; EMBEDDING_DIM];
+        near[10] = 1.0;
+        let mut close = near.clone();
+        close[11] = 0.3;
+        let mut db = VectorDB::new();
+        db.insert_batch(vec![
+            (near.clone(), IndexMetadata { is_generated: true, ..make_test_meta("generated/code/Acme/Cart/Interceptor.php") }),
+            (close, make_test_meta("app/code/Acme/Cart.php")),
+        ]);
+        let paths = |weights: ScoreWeights| -> Vec<String> {
+            db.hybrid_search(&near, "cart", 2, None, &[], weights).into_iter().map(|r| r.metadata.path).collect()
+        };
+        assert_eq!(paths(ScoreWeights::default())[0], "app/code/Acme/Cart.php");
+        assert_eq!(paths(ScoreWeights { generated: 1.0, keyword: 0.0, ..Default::default() })[0], "generated/code/Acme/Cart/Interceptor.php");
+        assert_eq!(paths(ScoreWeights { generated: 0.0, ..Default::default() }), vec!["app/code/Acme/Cart.php"]);
+    }
+
+    #[test]
+    fn test_two_stage_search() {
+        let chunk = |hot: usize| {
+            let mut v = vec![0.05f32; EMBEDDING_DIM];
+            v[hot] = 1.0;
+            v
+        };
+        let mut db = VectorDB::new();
+        db.set_two_stage(true);
+        // a.php has two chunks; the second one is what the query hits
+        db.insert_batch(vec![
+            (chunk(10), make_test_meta("a.php")),
+            (chunk(20), make_test_meta("a.php")),
+            (chunk(30), make_test_meta("b.php")),
+        ]);
+        let results = db.search(&chunk(20), 1);
+        assert_eq!(results[0].id, stable_id("a.php", 1));
+        assert!((results[0].score - 1.0).abs() < 1e-5);
+
+        // A re-indexed file gets a fresh centroid
+        db.remove_by_path("b.php");
+        db.insert_batch(vec![(chunk(40), make_test_meta("b.php"))]);
+        assert_eq!(db.search(&chunk(40), 1)[0].metadata.path, "b.php");
+        assert_eq!(db.search(&chunk(30), 3).len(), 3);
+
+        db.set_two_stage(false);
+        assert_eq!(db.search(&chunk(20), 1)[0].id, stable_id("a.php", 1));
+    }
+
+    #[test]
+    fn test_coarse_search_reranks_full_vectors() {
+        // Same 128-dim prefix, different tails: only the rerank tells them apart
+        let mut a = vec![0.1f32; EMBEDDING_DIM];
+        let mut b = a.clone();
+        a[200] = 1.0;
+        b[300] = 1.0;
+        let mut db = VectorDB::new();
+        db.insert(&a, make_test_meta("a.php"));
+        db.insert(&b, make_test_meta("b.php"));
+        db.set_coarse_dim(128);
+
+        let results = db.search(&b, 2);
+        assert_eq!(results[0].metadata.path, "b.php");
+        assert!((results[0].score - 1.0).abs() < 1e-5);
+        assert_eq!(results[1].metadata.path, "a.php");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("coarse.db");
+        db.save(&path).unwrap();
+        let db = VectorDB::open(&path).unwrap();
+        assert_eq!(db.header().coarse_dim, 128);
+        assert_eq!(db.search(&a, 1)[0].metadata.path, "a.php");
     }
 
     #[test]
@@ -814,23 +3094,194 @@ mod tests {
         let _ = fs::create_dir_all(&dir);
         let db_path = dir.join("test_v2.db");
 
-        {
+        let id = {
             let mut db = VectorDB::new();
             let v = vec![0.1f32; EMBEDDING_DIM];
             let id = db.insert(&v, make_test_meta("a.php"));
             db.insert(&v, make_test_meta("b.php"));
             db.tombstone(id);
             db.save(&db_path).unwrap();
-        }
+            id
+        };
 
         // Reload and verify tombstone persisted
         let db = VectorDB::open(&db_path).unwrap();
-        assert!(db.tombstones.contains(&0));
+        assert!(db.tombstones.contains(&id));
         assert_eq!(db.len(), 1); // b.php live
 
         let _ = fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn test_header_roundtrip_and_v2_compat() {
+        let dir = std::env::temp_dir().join("magector_test_header");
+        let _ = fs::create_dir_all(&dir);
+        let db_path = dir.join("header.db");
+        let v = vec![0.1f32; EMBEDDING_DIM];
+
+        let mut db = VectorDB::new();
+        db.insert(&v, make_test_meta("app/code/Vendor/Pay/Model/A.php"));
+        db.set_header(IndexHeader { only_modules: vec!["Vendor_*".to_string()], ..Default::default() });
+        db.save(&db_path).unwrap();
+
+        let loaded = VectorDB::open(&db_path).unwrap();
+        assert!(loaded.header().is_partial());
+        assert_eq!(loaded.header().only_modules, vec!["Vendor_*"]);
+        assert!(loaded.header().partial_warning().unwrap().contains("Vendor_*"));
+
+        // Checkpoints of a running build are flagged as partial too
+        let building = IndexHeader { building: true, ..Default::default() };
+        assert!(building.is_partial());
+        assert!(building.partial_warning().unwrap().contains("in progress"));
+
+        // V2 files (no header) still load as full indexes
+        let state = PersistedStateV2 {
+            metadata: legacy_metadata(&db.metadata),
+            vectors: db.vectors.clone(),
+            next_id: db.next_id,
+            tombstones: HashSet::new(),
+        };
+        let mut bytes = vec![PERSIST_VERSION_V2];
+        bytes.extend(bincode::serde::encode_to_vec(&state, bincode::config::standard()).unwrap());
+        fs::write(&db_path, bytes).unwrap();
+
+        let legacy = VectorDB::open(&db_path).unwrap();
+        assert_eq!(legacy.len(), 1);
+        assert!(!legacy.header().is_partial());
+        assert!(legacy.header().partial_warning().is_none());
+        legacy.save(&db_path).unwrap();
+        assert_eq!(fs::read(&db_path).unwrap()[0], PERSIST_VERSION_V3);
+        assert_eq!(VectorDB::read_metadata(&db_path).unwrap().1.len(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn legacy_metadata(metadata: &HashMap<usize, IndexMetadata>) -> HashMap<usize, LegacyMetadata> {
+        metadata
+            .iter()
+            .map(|(&id, meta)| (id, serde_json::from_value(serde_json::to_value(meta).unwrap()).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_meta_vectors_blend_into_semantic_score() {
+        let mut code = vec![0.05f32; EMBEDDING_DIM];
+        code[10] = 1.0;
+        let mut other = code.clone();
+        other[11] = 0.4;
+        let mut query = vec![0.05f32; EMBEDDING_DIM];
+        query[20] = 1.0;
+        let mut db = VectorDB::new();
+        let ids = db.insert_batch(vec![
+            (code.clone(), make_test_meta("Model/Cart.php")),
+            (other, make_test_meta("Model/Quote.php")),
+        ]);
+        // Only Quote.php is described like the query
+        db.insert_meta_vectors(vec![(ids[0], code.clone()), (ids[1], query.clone())]);
+        let top = |weights: ScoreWeights| db.hybrid_search(&code, "", 2, None, &[], weights)[0].metadata.path.clone();
+        assert_eq!(top(ScoreWeights { metadata: 0.0, ..Default::default() }), "Model/Cart.php");
+        let scores = db.hybrid_search(&query, "", 2, None, &[], ScoreWeights { metadata: 1.0, ..Default::default() });
+        assert_eq!(scores[0].metadata.path, "Model/Quote.php");
+        assert!((scores[0].score - 1.0).abs() < 1e-4);
+
+        // Re-indexing an item drops its metadata vector
+        db.remove_by_path("Model/Cart.php");
+        db.insert_batch(vec![(code, make_test_meta("Model/Cart.php"))]);
+        assert_eq!(db.meta_vector(ids[0]), None);
+        assert!(ScoreWeights { metadata: 1.5, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_recent_commits_boost() {
+        let mut near = vec![0.05f32; EMBEDDING_DIM];
+        near[10] = 1.0;
+        let mut close = near.clone();
+        close[11] = 0.3;
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        let mut db = VectorDB::new();
+        db.insert_batch(vec![
+            (near.clone(), IndexMetadata { last_commit: Some(now - 400 * 86_400), ..make_test_meta("Model/Old.php") }),
+            (close, IndexMetadata { last_commit: Some(now - 86_400), ..make_test_meta("Model/Changed.php") }),
+        ]);
+        let top = |weights: ScoreWeights| db.hybrid_search(&near, "cart", 2, None, &[], weights)[0].metadata.path.clone();
+        assert_eq!(top(ScoreWeights::default()), "Model/Old.php");
+        assert_eq!(top(ScoreWeights { recency: RECENT_BOOST_WEIGHT, ..Default::default() }), "Model/Changed.php");
+
+        assert_eq!(recency_bonus(None, now), 0.0);
+        let half = recency_bonus(Some(now - 14 * 86_400), now);
+        assert!((half - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_result_filter() {
+        let result = |path: &str, score: f32, loc: usize, modified: u64| {
+            let metrics = FileMetrics { loc, bytes: 0, modified: Some(modified), lossy: false };
+            SearchResult::new(0, score, IndexMetadata { metrics: Some(metrics), ..make_test_meta(path) })
+        };
+        let ranked = || {
+            vec![
+                result("Api/ProductInterface.php", 0.9, 12, 100),
+                result("Model/Product.php", 0.8, 900, 300),
+                result("Model/Old.php", 0.7, 40, 50),
+                SearchResult::new(0, 0.6, make_test_meta("unmeasured.php")),
+                result("Model/Recent.php", 0.5, 60, 300),
+            ]
+        };
+        let paths = |results: Vec<SearchResult>| -> Vec<String> { results.into_iter().map(|r| r.metadata.path).collect() };
+
+        let mut results = ranked();
+        ResultFilter { max_loc: Some(100), ..Default::default() }.apply(&mut results, 3);
+        assert_eq!(paths(results), ["Api/ProductInterface.php", "Model/Old.php", "unmeasured.php"]);
+
+        let mut results = ranked();
+        ResultFilter { min_loc: Some(20), max_loc: Some(100), ..Default::default() }.apply(&mut results, 5);
+        assert_eq!(paths(results), ["Model/Old.php", "unmeasured.php", "Model/Recent.php"]);
+
+        let mut results = ranked();
+        ResultFilter { order: ResultOrder::Recent, ..Default::default() }.apply(&mut results, 5);
+        assert_eq!(
+            paths(results),
+            ["Model/Product.php", "Model/Recent.php", "Api/ProductInterface.php", "Model/Old.php", "unmeasured.php"]
+        );
+
+        assert_eq!("Recent".parse::<ResultOrder>(), Ok(ResultOrder::Recent));
+        assert!("newest".parse::<ResultOrder>().is_err());
+        assert_eq!(ResultFilter { max_loc: Some(10), ..Default::default() }.fetch_limit(5), 20);
+
+        let mut results = ranked();
+        results[1].metadata.owners = vec!["@acme/team-checkout".to_string()];
+        ResultFilter { owner: Some("team-checkout".to_string()), ..Default::default() }.apply(&mut results, 5);
+        assert_eq!(paths(results), ["Model/Product.php"]);
+
+        let mut results = ranked();
+        results[2].metadata.collection = Collection::Docs;
+        let docs = ResultFilter { namespace: Some(Collection::Docs), ..Default::default() };
+        assert_eq!(docs.fetch_limit(5), 5);
+        docs.apply(&mut results, 5);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].metadata.collection, Collection::Docs);
+        assert_eq!("DOCS".parse::<Collection>(), Ok(Collection::Docs));
+        assert!("wiki".parse::<Collection>().is_err());
+    }
+
+    #[test]
+    fn test_source_span() {
+        let source = "<?php\nclass A\n{\n    public function run()\n    {\n    }\n}\n";
+        let whole = SourceSpan::whole(source);
+        assert_eq!((whole.start_line, whole.end_line), (1, 7));
+        assert_eq!((whole.start_byte, whole.end_byte), (0, source.len()));
+
+        let start = source.find("public").unwrap();
+        let end = source.rfind("    }").unwrap() + 5;
+        let method = SourceSpan::of_range(source, start, end);
+        assert_eq!((method.start_line, method.end_line), (4, 6));
+
+        let mut meta = make_test_meta("a.php");
+        meta.span = Some(method);
+        let result = SearchResult::new(1, 0.9, meta);
+        assert_eq!((result.line, result.end_line), (Some(4), Some(6)));
+    }
+
     #[test]
     fn test_batch_insert() {
         let mut db = VectorDB::with_capacity(10);
@@ -843,30 +3294,8 @@ mod tests {
                 let meta = IndexMetadata {
                     path: format!("test_{}.php", i),
                     file_type: "php".to_string(),
-                    magento_type: None,
-                    class_name: None,
-                    class_type: None,
-                    method_name: None,
-                    methods: Vec::new(),
-                    namespace: None,
-                    module: None,
-                    area: None,
-                    extends: None,
-                    implements: Vec::new(),
-                    is_controller: false,
-                    is_repository: false,
-                    is_plugin: false,
-                    is_observer: false,
-                    is_model: false,
-                    is_block: false,
-                    is_resolver: false,
-                    is_api_interface: false,
-                    is_ui_component: false,
-                    is_widget: false,
-                    is_mixin: false,
-                    js_dependencies: Vec::new(),
                     search_text: format!("test {}", i),
-        
+                    ..Default::default()
                 };
                 (vec, meta)
             })
@@ -911,4 +3340,181 @@ mod tests {
 
         let _ = fs::remove_dir_all(&dir);
     }
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("index.db");
+        let mut vector = vec![0.1f32; EMBEDDING_DIM];
+        vector[0] = 0.9;
+        let mut db = VectorDB::new();
+        db.insert(&vector, make_test_meta("a.php"));
+        db.save(&db_path).unwrap();
+
+        let loaded = VectorDB::open(&db_path).unwrap();
+        assert_eq!(loaded.header().checksum, db.content_hash());
+
+        // Flip one vector component: still decodes, but the checksum fails
+        let mut bytes = fs::read(&db_path).unwrap();
+        let needle = 0.9f32.to_le_bytes();
+        let at = bytes.windows(4).position(|w| w == needle).unwrap();
+        bytes[at..at + 4].copy_from_slice(&0.8f32.to_le_bytes());
+        fs::write(&db_path, &bytes).unwrap();
+        let err = VectorDB::open(&db_path).err().unwrap();
+        assert!(format!("{err:#}").contains("checksum mismatch"));
+        assert!(db_path.exists(), "a corrupted index must not be deleted");
+
+        // `index --force` moves it aside and starts over
+        let aside = VectorDB::set_aside_if_unreadable(&db_path).unwrap().unwrap();
+        assert_eq!(aside, dir.path().join("index.db.corrupt"));
+        assert_eq!(fs::read(&aside).unwrap(), bytes);
+        assert!(VectorDB::open(&db_path).unwrap().is_empty());
+        db.save(&db_path).unwrap();
+        assert_eq!(VectorDB::set_aside_if_unreadable(&db_path).unwrap(), None);
+        assert!(db_path.exists());
+    }
+
+    #[test]
+    fn test_read_header_and_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("v1.db");
+        let mut db = VectorDB::new();
+        db.insert_batch(vec![
+            (vec![0.1f32; EMBEDDING_DIM], make_test_meta("b.php")),
+            (vec![0.2f32; EMBEDDING_DIM], make_test_meta("a.php")),
+        ]);
+        db.remove_by_path("b.php");
+        db.set_header(IndexHeader { tag: "v1".into(), tagged_at: 42, ..Default::default() });
+        db.save(&db_path).unwrap();
+
+        let header = VectorDB::read_header(&db_path).unwrap();
+        assert_eq!((header.tag.as_str(), header.tagged_at), ("v1", 42));
+        let (_, items) = VectorDB::read_metadata(&db_path).unwrap();
+        let paths: Vec<&str> = items.iter().map(|m| m.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.php"]);
+    }
+
+    #[test]
+    fn test_paths_use_forward_slashes() {
+        let root = Path::new("/srv/magento");
+        let file = root.join("app").join("code").join("Acme").join("Foo.php");
+        assert_eq!(relative_path(root, &file), "app/code/Acme/Foo.php");
+        assert_eq!(normalize_separators("app\\code\\Acme\\Foo.php"), "app/code/Acme/Foo.php");
+        assert_eq!(relative_path(root, Path::new("/srv/private/Acme/Foo.php")), "/srv/private/Acme/Foo.php");
+
+        // Indexes written on Windows are normalized on load
+        let dir = std::env::temp_dir().join("magector_test_separators");
+        let _ = fs::create_dir_all(&dir);
+        let db_path = dir.join("win.db");
+        let mut db = VectorDB::new();
+        db.insert(&vec![0.1f32; EMBEDDING_DIM], make_test_meta("app\\code\\Acme\\Foo.php"));
+        db.save(&db_path).unwrap();
+        let loaded = VectorDB::open(&db_path).unwrap();
+        assert!(loaded.ids_by_path().contains_key("app/code/Acme/Foo.php"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_extra_root_parsing() {
+        let root: ExtraRoot = "/srv/extensions/acme-pay/".parse().unwrap();
+        assert_eq!(root, ExtraRoot { name: "acme-pay".into(), path: "/srv/extensions/acme-pay".into() });
+        let root: ExtraRoot = "pay=/srv/extensions/acme-pay".parse().unwrap();
+        assert_eq!(root.name, "pay");
+        assert!(root.contains("/srv/extensions/acme-pay/Model/Pay.php"));
+        assert!(!root.contains("/srv/extensions/acme-pay-old/Model/Pay.php"));
+        assert_eq!(ExtraRoot::new(None, Path::new("/srv/magento/../extensions/pay")).path, "/srv/extensions/pay");
+        assert!("=/srv/x".parse::<ExtraRoot>().is_err());
+        assert!("pay=".parse::<ExtraRoot>().is_err());
+    }
+
+    #[test]
+    fn test_remap_paths() {
+        let map: RootMap = "/home/ci/shop/=/srv/shop".parse().unwrap();
+        assert_eq!(map, RootMap { from: "/home/ci/shop".into(), to: "/srv/shop".into() });
+        assert!("no-separator".parse::<RootMap>().is_err());
+
+        let mut db = VectorDB::new();
+        for path in ["app/code/A.php", "/home/ci/shop/vendor/B.php", "/home/ci/shopping/C.php", "/opt/D.php"] {
+            db.insert(&vec![0.1f32; EMBEDDING_DIM], make_test_meta(path));
+        }
+        assert_eq!(db.remap_paths(&[map], Path::new("/srv/shop")), 1);
+        let mut paths: Vec<String> = db.ids_by_path().into_keys().collect();
+        paths.sort();
+        assert_eq!(paths, ["/home/ci/shopping/C.php", "/opt/D.php", "app/code/A.php", "vendor/B.php"]);
+    }
+
+    #[test]
+    fn test_stable_id_independent_of_order() {
+        assert_eq!(stable_id("a.php", 0), stable_id("a.php", 0));
+        assert_ne!(stable_id("a.php", 0), stable_id("a.php", 1));
+        assert_ne!(stable_id("a.php", 0), stable_id("b.php", 0));
+        assert!((stable_id("a.php", 0) as u64) <= STABLE_ID_MASK);
+
+        let item = |path: &str, x: f32| {
+            let mut v = vec![0.1f32; EMBEDDING_DIM];
+            v[0] = x;
+            (stable_id(path, 0), v, make_test_meta(path))
+        };
+
+        let dir = std::env::temp_dir().join("magector_test_deterministic");
+        let _ = fs::create_dir_all(&dir);
+        let (p1, p2) = (dir.join("one.db"), dir.join("two.db"));
+
+        let mut db1 = VectorDB::new();
+        db1.insert_batch_with_ids(vec![item("a.php", 0.3), item("b.php", 0.5)]);
+        db1.save(&p1).unwrap();
+
+        let mut db2 = VectorDB::new();
+        db2.insert_batch_with_ids(vec![item("b.php", 0.5)]);
+        db2.insert_batch_with_ids(vec![item("a.php", 0.3)]);
+        db2.save(&p2).unwrap();
+
+        assert_eq!(fs::read(&p1).unwrap(), fs::read(&p2).unwrap());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_insert_with_ids_probes_on_collision() {
+        let mut db = VectorDB::new();
+        let v = vec![0.1f32; EMBEDDING_DIM];
+        let ids = db.insert_batch_with_ids(vec![
+            (42, v.clone(), make_test_meta("a.php")),
+            (42, v, make_test_meta("b.php")),
+        ]);
+        assert_eq!(ids, vec![42, 43]);
+        assert_eq!(db.len(), 2);
+    }
+
+    #[test]
+    fn test_reindex_keeps_stable_ids() {
+        let mut db = VectorDB::new();
+        let mut old = vec![0.1f32; EMBEDDING_DIM];
+        old[0] = 0.9;
+        let ids = db.insert_batch(vec![
+            (old.clone(), make_test_meta("a.php")),
+            (old, make_test_meta("a.php")),
+        ]);
+        assert_eq!(ids, vec![stable_id("a.php", 0), stable_id("a.php", 1)]);
+
+        // Simulate a modified file: tombstone, then re-insert new content
+        db.remove_by_path("a.php");
+        let fresh = vec![0.2f32; EMBEDDING_DIM];
+        let again = db.insert_batch(vec![
+            (fresh.clone(), make_test_meta("a.php")),
+            (fresh.clone(), make_test_meta("a.php")),
+        ]);
+        assert_eq!(again, ids);
+        assert_eq!(db.len(), 2);
+
+        // Outdated graph points are rescored from the current vectors and
+        // each ID is reported once
+        let results = db.search(&fresh, 10);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.score > 0.99));
+
+        db.compact();
+        assert_eq!(db.search(&fresh, 10).len(), 2);
+    }
 }
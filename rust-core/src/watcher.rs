@@ -5,13 +5,14 @@
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::{Duration, SystemTime};
-use walkdir::WalkDir;
 
-use crate::indexer::{Indexer, INCLUDE_EXTENSIONS, MAX_FILE_SIZE};
+use crate::indexer::{is_indexable, skips_test_file, Indexer, SourceWalk, WalkOptions, EXCLUDE_FILES, MAX_FILE_SIZE};
+use crate::vectordb::{normalize_separators, relative_path};
 
 /// Lock a mutex, recovering from poisoning instead of propagating the panic.
 ///
@@ -40,6 +41,36 @@ pub struct FileRecord {
     pub mtime: SystemTime,
     pub size: u64,
     pub vector_ids: Vec<usize>,
+    /// SHA-256 of the content, hex; empty when unknown. Only `index
+    /// --incremental` reads and records hashes.
+    pub hash: String,
+}
+
+impl FileRecord {
+    /// Record of the file at `path` as it is on disk now, without a hash
+    pub fn of(path: &Path, vector_ids: Vec<usize>) -> Option<Self> {
+        let meta = std::fs::metadata(path).ok()?;
+        Some(Self {
+            mtime: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            size: meta.len(),
+            vector_ids,
+            hash: String::new(),
+        })
+    }
+
+    /// [`Self::of`] with the content hash
+    pub fn hashed(path: &Path, vector_ids: Vec<usize>) -> Option<Self> {
+        let record = Self::of(path, vector_ids)?;
+        Some(Self { hash: content_hash(path).unwrap_or_default(), ..record })
+    }
+}
+
+/// SHA-256 of a file's content, hex
+pub fn content_hash(path: &Path) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).ok()?;
+    Some(format!("{:x}", hasher.finalize()))
 }
 
 /// Manifest of all indexed files and their metadata
@@ -48,6 +79,36 @@ pub struct FileManifest {
     pub files: HashMap<String, FileRecord>,
 }
 
+/// Leads manifests with content hashes; older manifests start with the
+/// bincode length of their file map
+const MANIFEST_MAGIC: &[u8; 8] = b"MGCTMF\x00\x02";
+
+/// Manifest record before content hashes were recorded
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+struct FileRecordV1 {
+    mtime: SystemTime,
+    size: u64,
+    vector_ids: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+struct FileManifestV1 {
+    files: HashMap<String, FileRecordV1>,
+}
+
+impl From<FileManifestV1> for FileManifest {
+    fn from(legacy: FileManifestV1) -> Self {
+        let files = legacy
+            .files
+            .into_iter()
+            .map(|(path, r)| (path, FileRecord { mtime: r.mtime, size: r.size, vector_ids: r.vector_ids, hash: String::new() }))
+            .collect();
+        Self { files }
+    }
+}
+
 /// Set of changes detected in a scan
 #[derive(Debug, Default)]
 pub struct ChangeSet {
@@ -77,14 +138,26 @@ impl FileManifest {
     /// Returns None if the file doesn't exist or can't be parsed.
     pub fn load(path: &Path) -> Option<Self> {
         let data = std::fs::read(path).ok()?;
-        bincode::serde::decode_from_slice(&data, bincode::config::standard())
-            .map(|(val, _)| val)
-            .ok()
+        let config = bincode::config::standard();
+        let manifest: Self = match data.strip_prefix(MANIFEST_MAGIC) {
+            Some(body) => bincode::serde::decode_from_slice(body, config).map(|(val, _)| val).ok()?,
+            None => bincode::serde::decode_from_slice::<FileManifestV1, _>(&data, config)
+                .map(|(val, _)| val.into())
+                .ok()?,
+        };
+        // Manifests written on Windows before paths were normalized
+        let files = manifest
+            .files
+            .into_iter()
+            .map(|(path, record)| (normalize_separators(&path), record))
+            .collect();
+        Some(Self { files })
     }
 
     /// Save manifest to a sidecar file next to the index DB.
     pub fn save(&self, path: &Path) -> Result<()> {
-        let data = bincode::serde::encode_to_vec(self, bincode::config::standard())?;
+        let mut data = MANIFEST_MAGIC.to_vec();
+        data.extend(bincode::serde::encode_to_vec(self, bincode::config::standard())?);
         // Atomic write: write to temp, then rename
         let tmp = path.with_extension("manifest.tmp");
         std::fs::write(&tmp, &data)?;
@@ -99,52 +172,59 @@ impl FileManifest {
     }
 
     /// Build initial manifest from the current index metadata.
-    /// This scans the filesystem to populate mtime/size for files already in the index.
+    /// This scans the filesystem to populate mtime/size for files already in
+    /// the index; content hashes are left to [`Self::record_missing_hashes`].
     /// Only includes files that are in `indexed_paths` (have vectors in the DB).
-    pub fn from_existing_index(magento_root: &Path, indexed_paths: &std::collections::HashSet<String>) -> Self {
+    /// Extra roots (`index --extra-root`) are walked after the Magento root.
+    pub fn from_existing_index(
+        magento_root: &Path,
+        extra_roots: &[PathBuf],
+        indexed_paths: &std::collections::HashSet<String>,
+        walk: WalkOptions,
+    ) -> Self {
         let mut manifest = Self::new();
         // Walk the filesystem and record current mtimes for files we'd index
-        let walker = WalkDir::new(magento_root)
-            .follow_links(false)
-            .into_iter()
-            .filter_entry(|e| !Indexer::should_skip_dir(e));
-
-        for entry in walker.flatten() {
-            if !entry.file_type().is_file() {
-                continue;
-            }
-            let path = entry.path();
-            let ext = match path.extension().and_then(|e| e.to_str()) {
-                Some(e) => e,
-                None => continue,
-            };
-            if !INCLUDE_EXTENSIONS.contains(&ext) {
-                continue;
-            }
-            if let Ok(meta) = entry.metadata() {
-                if meta.len() > MAX_FILE_SIZE {
+        for root in std::iter::once(magento_root).chain(extra_roots.iter().map(PathBuf::as_path)) {
+            let mut walker = SourceWalk::new(root, walk.follow_symlinks);
+            while let Some(entry) = walker.next() {
+                let Ok(entry) = entry else { continue };
+                if Indexer::should_skip_dir(&entry, root, walk.include_tests) {
+                    walker.skip_current_dir();
+                    continue;
+                }
+                if !entry.file_type().is_file() {
                     continue;
                 }
-                let relative = path
-                    .strip_prefix(magento_root)
-                    .unwrap_or(path)
-                    .to_string_lossy()
-                    .to_string();
-
-                // Only include files that actually have vectors in the DB
-                if !indexed_paths.contains(&relative) {
+                let path = entry.path();
+                let ext = match path.extension().and_then(|e| e.to_str()) {
+                    Some(e) => e,
+                    None => continue,
+                };
+                let relative = relative_path(magento_root, path);
+                if !is_indexable(&relative, ext, walk.include_docs) {
                     continue;
                 }
+                if let Ok(meta) = entry.metadata() {
+                    if meta.len() > MAX_FILE_SIZE {
+                        continue;
+                    }
+
+                    // Only include files that actually have vectors in the DB
+                    if !indexed_paths.contains(&relative) {
+                        continue;
+                    }
 
-                let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
-                manifest.files.insert(
-                    relative,
-                    FileRecord {
-                        mtime,
-                        size: meta.len(),
-                        vector_ids: Vec::new(), // IDs unknown for pre-existing index
-                    },
-                );
+                    let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    manifest.files.insert(
+                        relative,
+                        FileRecord {
+                            mtime,
+                            size: meta.len(),
+                            vector_ids: Vec::new(), // filled in by set_vector_ids
+                            hash: String::new(),
+                        },
+                    );
+                }
             }
         }
 
@@ -152,53 +232,66 @@ impl FileManifest {
     }
 
     /// Scan the filesystem and detect changes against the manifest
-    pub fn detect_changes(&self, magento_root: &Path) -> Result<ChangeSet> {
+    pub fn detect_changes(&self, magento_root: &Path, walk: WalkOptions) -> Result<ChangeSet> {
+        self.detect_changes_in(magento_root, &[], walk)
+    }
+
+    /// [`Self::detect_changes`] over the Magento root and extra roots
+    /// (`index --extra-root`)
+    pub fn detect_changes_in(
+        &self,
+        magento_root: &Path,
+        extra_roots: &[PathBuf],
+        walk: WalkOptions,
+    ) -> Result<ChangeSet> {
         let mut changes = ChangeSet::default();
         let mut seen = std::collections::HashSet::new();
 
-        let walker = WalkDir::new(magento_root)
-            .follow_links(false)
-            .into_iter()
-            .filter_entry(|e| !Indexer::should_skip_dir(e));
-
-        for entry in walker.flatten() {
-            if !entry.file_type().is_file() {
-                continue;
-            }
-            let path = entry.path();
-            let ext = match path.extension().and_then(|e| e.to_str()) {
-                Some(e) => e,
-                None => continue,
-            };
-            if !INCLUDE_EXTENSIONS.contains(&ext) {
-                continue;
-            }
-            let meta = match entry.metadata() {
-                Ok(m) => m,
-                Err(_) => continue,
-            };
-            if meta.len() > MAX_FILE_SIZE {
-                continue;
-            }
+        for root in std::iter::once(magento_root).chain(extra_roots.iter().map(PathBuf::as_path)) {
+            let mut walker = SourceWalk::new(root, walk.follow_symlinks);
+            while let Some(entry) = walker.next() {
+                let Ok(entry) = entry else { continue };
+                if Indexer::should_skip_dir(&entry, root, walk.include_tests) {
+                    walker.skip_current_dir();
+                    continue;
+                }
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let path = entry.path();
+                let ext = match path.extension().and_then(|e| e.to_str()) {
+                    Some(e) => e,
+                    None => continue,
+                };
+                let relative = relative_path(magento_root, path);
+                if !is_indexable(&relative, ext, walk.include_docs) {
+                    continue;
+                }
+                let meta = match entry.metadata() {
+                    Ok(m) => m,
+                    Err(_) => continue,
+                };
+                if meta.len() > MAX_FILE_SIZE {
+                    continue;
+                }
 
-            let relative = path
-                .strip_prefix(magento_root)
-                .unwrap_or(path)
-                .to_string_lossy()
-                .to_string();
+                if (walk.include_tests && skips_test_file(&relative)) || EXCLUDE_FILES.contains(&relative.as_str()) {
+                    continue;
+                }
 
-            seen.insert(relative.clone());
+                seen.insert(relative.clone());
 
-            match self.files.get(&relative) {
-                None => {
-                    // New file
-                    changes.added.push(path.to_path_buf());
-                }
-                Some(record) => {
-                    // Check if modified (mtime or size changed)
-                    let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
-                    if mtime != record.mtime || meta.len() != record.size {
-                        changes.modified.push(path.to_path_buf());
+                match self.files.get(&relative) {
+                    None => {
+                        // New file
+                        changes.added.push(path.to_path_buf());
+                    }
+                    Some(record) => {
+                        // Check if modified (mtime or size changed)
+                        let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                        if mtime != record.mtime || meta.len() != record.size {
+                            changes.modified.push(path.to_path_buf());
+                        }
                     }
                 }
             }
@@ -214,6 +307,54 @@ impl FileManifest {
         Ok(changes)
     }
 
+    /// Take the files of `changes.modified` whose content still hashes to
+    /// the recorded value (touched by a checkout, copy or deploy, but not
+    /// changed) out of it, refreshing their mtime and size. Files without a
+    /// recorded hash stay modified. Returns how many were taken out.
+    pub fn settle_unchanged(&mut self, magento_root: &Path, changes: &mut ChangeSet) -> usize {
+        let before = changes.modified.len();
+        changes.modified.retain(|path| {
+            let relative = relative_path(magento_root, path);
+            let Some(record) = self.files.get_mut(&relative) else { return true };
+            if record.hash.is_empty() {
+                return true;
+            }
+            match FileRecord::hashed(path, Vec::new()) {
+                Some(now) if now.hash == record.hash => {
+                    record.mtime = now.mtime;
+                    record.size = now.size;
+                    false
+                }
+                _ => true,
+            }
+        });
+        before - changes.modified.len()
+    }
+
+    /// Hash the tracked files without a recorded hash that `changes` leaves
+    /// alone: their content is still what was indexed, so a later
+    /// [`Self::settle_unchanged`] can compare against it. Each file is
+    /// hashed once. Returns how many were hashed.
+    pub fn record_missing_hashes(&mut self, magento_root: &Path, changes: &ChangeSet) -> usize {
+        let changed: std::collections::HashSet<String> = changes
+            .modified
+            .iter()
+            .map(|path| relative_path(magento_root, path))
+            .chain(changes.deleted.iter().cloned())
+            .collect();
+        let mut hashed = 0;
+        for (relative, record) in self.files.iter_mut() {
+            if !record.hash.is_empty() || changed.contains(relative) {
+                continue;
+            }
+            if let Some(hash) = content_hash(&magento_root.join(relative)) {
+                record.hash = hash;
+                hashed += 1;
+            }
+        }
+        hashed
+    }
+
     /// Update manifest after indexing new/modified files
     pub fn apply_indexed(
         &mut self,
@@ -222,21 +363,21 @@ impl FileManifest {
     ) {
         for (rel_path, vector_ids) in indexed {
             let abs_path = magento_root.join(rel_path);
-            let (mtime, size) = match std::fs::metadata(&abs_path) {
-                Ok(m) => (
-                    m.modified().unwrap_or(SystemTime::UNIX_EPOCH),
-                    m.len(),
-                ),
-                Err(_) => (SystemTime::UNIX_EPOCH, 0),
-            };
-            self.files.insert(
-                rel_path.clone(),
-                FileRecord {
-                    mtime,
-                    size,
-                    vector_ids: vector_ids.clone(),
-                },
-            );
+            let record = FileRecord::of(&abs_path, vector_ids.clone()).unwrap_or_else(|| FileRecord {
+                mtime: SystemTime::UNIX_EPOCH,
+                size: 0,
+                vector_ids: vector_ids.clone(),
+                hash: String::new(),
+            });
+            self.files.insert(rel_path.clone(), record);
+        }
+    }
+
+    /// Record the stable vector IDs of each tracked file.
+    /// Files without vectors keep an empty list.
+    pub fn set_vector_ids(&mut self, ids_by_path: &HashMap<String, Vec<usize>>) {
+        for (path, record) in self.files.iter_mut() {
+            record.vector_ids = ids_by_path.get(path).cloned().unwrap_or_default();
         }
     }
 
@@ -277,12 +418,29 @@ pub fn watcher_loop(
         interval.as_secs()
     );
 
-    // Build initial manifest
-    let mut manifest = {
+    // Build initial manifest, walking the tree the way the index was built
+    let walk;
+    let extra_roots;
+    let paths;
+    let vector_ids;
+    {
         let idx = lock_recover(&indexer, "indexer");
-        let paths = idx.indexed_paths();
-        FileManifest::from_existing_index(&magento_root, &paths)
-    };
+        // A checkpoint of a running index build: every file not embedded yet
+        // would look "added". The build itself finishes the job, and serve
+        // is restarted on the completed index.
+        if idx.index_header().building {
+            tracing::info!("Index build in progress — file watcher disabled for this checkpoint");
+            lock_recover(&status, "status").running = false;
+            return;
+        }
+        paths = idx.indexed_paths();
+        vector_ids = idx.vector_ids_by_path();
+        walk = idx.walk_options();
+        extra_roots = idx.extra_root_paths();
+    }
+    // The walk runs unlocked, so searches are served meanwhile
+    let mut manifest = FileManifest::from_existing_index(&magento_root, &extra_roots, &paths, walk);
+    manifest.set_vector_ids(&vector_ids);
 
     {
         let mut s = lock_recover(&status, "status");
@@ -295,7 +453,7 @@ pub fn watcher_loop(
         std::thread::sleep(interval);
 
         // Detect changes
-        let changes = match manifest.detect_changes(&magento_root) {
+        let mut changes = match manifest.detect_changes_in(&magento_root, &extra_roots, walk) {
             Ok(c) => c,
             Err(e) => {
                 tracing::warn!("Watcher scan error: {}", e);
@@ -303,6 +461,13 @@ pub fn watcher_loop(
             }
         };
 
+        // A partial index only tracks its own module subset, and an overlay
+        // leaves files covered by its core index alone
+        {
+            let idx = lock_recover(&indexer, "indexer");
+            changes.added.retain(|f| idx.indexes_locally(f));
+        }
+
         if changes.is_empty() {
             continue;
         }
@@ -321,11 +486,7 @@ pub fn watcher_loop(
 
         // 1. Tombstone modified and deleted files
         for path in &changes.modified {
-            let relative = path
-                .strip_prefix(&magento_root)
-                .unwrap_or(path)
-                .to_string_lossy()
-                .to_string();
+            let relative = relative_path(&magento_root, path);
             idx.remove_vectors_for_path(&relative);
         }
         for path in &changes.deleted {
@@ -436,10 +597,11 @@ mod tests {
                 mtime: meta.modified().unwrap(),
                 size: meta.len(),
                 vector_ids: vec![0],
+                hash: String::new(),
             },
         );
 
-        let changes = manifest.detect_changes(&dir).unwrap();
+        let changes = manifest.detect_changes(&dir, WalkOptions::default()).unwrap();
         assert!(
             changes.is_empty(),
             "Expected no changes but got: added={}, modified={}, deleted={}",
@@ -456,7 +618,7 @@ mod tests {
         fs::write(&php, "<?php echo 'new';").unwrap();
 
         let manifest = FileManifest::new();
-        let changes = manifest.detect_changes(&dir).unwrap();
+        let changes = manifest.detect_changes(&dir, WalkOptions::default()).unwrap();
         assert_eq!(changes.added.len(), 1);
         assert!(changes.modified.is_empty());
         assert!(changes.deleted.is_empty());
@@ -464,6 +626,114 @@ mod tests {
         let _ = fs::remove_dir_all(&dir);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_detect_follows_symlinks_once() {
+        let dir = make_temp_dir();
+        let module = dir.join("modules/Acme_Foo");
+        fs::create_dir_all(&module).unwrap();
+        fs::write(module.join("Foo.php"), "<?php class Foo {}").unwrap();
+        fs::create_dir_all(dir.join("app/code/Acme")).unwrap();
+        std::os::unix::fs::symlink(&module, dir.join("app/code/Acme/Foo")).unwrap();
+        // Cycle back to the root
+        std::os::unix::fs::symlink(&dir, module.join("loop")).unwrap();
+
+        let manifest = FileManifest::new();
+        assert_eq!(manifest.detect_changes(&dir, WalkOptions::default()).unwrap().added.len(), 1);
+
+        let changes = manifest.detect_changes(&dir, WalkOptions { follow_symlinks: true, ..Default::default() }).unwrap();
+        assert_eq!(changes.added.len(), 1, "linked module must be indexed once: {:?}", changes.added);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_detect_include_tests_only_mftf() {
+        let dir = make_temp_dir();
+        let module = dir.join("app/code/Acme/Foo");
+        for (file, body) in [
+            ("Model/Foo.php", "<?php class Foo {}"),
+            ("Test/Unit/FooTest.php", "<?php class FooTest {}"),
+            ("Test/Mftf/ActionGroup/AdminOpenFooActionGroup.xml", "<actionGroups/>"),
+            ("Test/Mftf/Test/AdminFooTest.xml", "<tests/>"),
+            ("Test/Mftf/Helper/FooHelper.php", "<?php class FooHelper {}"),
+        ] {
+            let path = module.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, body).unwrap();
+        }
+
+        let manifest = FileManifest::new();
+        assert_eq!(manifest.detect_changes(&dir, WalkOptions::default()).unwrap().added.len(), 1);
+
+        let mut added: Vec<String> = manifest
+            .detect_changes(&dir, WalkOptions { include_tests: true, ..Default::default() })
+            .unwrap()
+            .added
+            .iter()
+            .map(|p| relative_path(&module, p))
+            .collect();
+        added.sort();
+        assert_eq!(
+            added,
+            vec!["Model/Foo.php", "Test/Mftf/ActionGroup/AdminOpenFooActionGroup.xml", "Test/Mftf/Test/AdminFooTest.xml"]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_detect_include_docs() {
+        let dir = make_temp_dir();
+        for (file, body) in [
+            ("app/code/Acme/Foo/Model/Foo.php", "<?php class Foo {}"),
+            ("app/code/Acme/Foo/README.md", "# Foo"),
+            ("docs/adr/0001-queues.md", "# Queues"),
+            ("vendor/acme/lib/README.md", "# Lib"),
+        ] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, body).unwrap();
+        }
+
+        let manifest = FileManifest::new();
+        assert_eq!(manifest.detect_changes(&dir, WalkOptions::default()).unwrap().added.len(), 1);
+
+        let mut added: Vec<String> = manifest
+            .detect_changes(&dir, WalkOptions { include_docs: true, ..Default::default() })
+            .unwrap()
+            .added
+            .iter()
+            .map(|p| relative_path(&dir, p))
+            .collect();
+        added.sort();
+        assert_eq!(added, vec!["app/code/Acme/Foo/Model/Foo.php", "app/code/Acme/Foo/README.md", "docs/adr/0001-queues.md"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_detect_patches() {
+        let dir = make_temp_dir();
+        for file in ["patches/composer/fix-price.patch", "m2-hotfixes/MDVA-1.diff", "app/code/Acme/Foo/fix.patch"] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "--- a/x.php\n+++ b/x.php\n").unwrap();
+        }
+
+        let mut added: Vec<String> = FileManifest::new()
+            .detect_changes(&dir, WalkOptions::default())
+            .unwrap()
+            .added
+            .iter()
+            .map(|p| relative_path(&dir, p))
+            .collect();
+        added.sort();
+        assert_eq!(added, vec!["m2-hotfixes/MDVA-1.diff", "patches/composer/fix-price.patch"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_detect_modified_file() {
         let dir = make_temp_dir();
@@ -477,10 +747,11 @@ mod tests {
                 mtime: SystemTime::UNIX_EPOCH,
                 size: 0,
                 vector_ids: vec![0],
+                hash: String::new(),
             },
         );
 
-        let changes = manifest.detect_changes(&dir).unwrap();
+        let changes = manifest.detect_changes(&dir, WalkOptions::default()).unwrap();
         assert!(changes.added.is_empty());
         assert_eq!(changes.modified.len(), 1);
 
@@ -497,10 +768,11 @@ mod tests {
                 mtime: SystemTime::UNIX_EPOCH,
                 size: 100,
                 vector_ids: vec![0],
+                hash: String::new(),
             },
         );
 
-        let changes = manifest.detect_changes(&dir).unwrap();
+        let changes = manifest.detect_changes(&dir, WalkOptions::default()).unwrap();
         assert!(changes.added.is_empty());
         assert!(changes.modified.is_empty());
         assert_eq!(changes.deleted.len(), 1);
@@ -521,6 +793,7 @@ mod tests {
                 mtime: SystemTime::UNIX_EPOCH + Duration::from_secs(1700000000),
                 size: 4096,
                 vector_ids: vec![10, 11, 12],
+                hash: "ab12".to_string(),
             },
         );
         manifest.files.insert(
@@ -529,6 +802,7 @@ mod tests {
                 mtime: SystemTime::UNIX_EPOCH + Duration::from_secs(1600000000),
                 size: 2048,
                 vector_ids: vec![20],
+                hash: String::new(),
             },
         );
 
@@ -543,6 +817,7 @@ mod tests {
         let foo = loaded.files.get("app/code/Vendor/Module/Model/Foo.php").unwrap();
         assert_eq!(foo.size, 4096);
         assert_eq!(foo.vector_ids, vec![10, 11, 12]);
+        assert_eq!(foo.hash, "ab12");
 
         let di = loaded.files.get("vendor/magento/module-catalog/etc/di.xml").unwrap();
         assert_eq!(di.size, 2048);
@@ -550,6 +825,64 @@ mod tests {
         let _ = fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn test_settle_unchanged_by_content_hash() {
+        let dir = make_temp_dir();
+        fs::write(dir.join("touched.php"), "<?php echo 'same';").unwrap();
+        fs::write(dir.join("edited.php"), "<?php echo 'v2';").unwrap();
+        fs::write(dir.join("legacy.php"), "<?php echo 'old';").unwrap();
+
+        let mut manifest = FileManifest::new();
+        for (file, hash) in [
+            ("touched.php", content_hash(&dir.join("touched.php")).unwrap()),
+            ("edited.php", "0".repeat(64)),
+            ("legacy.php", String::new()),
+        ] {
+            let record = FileRecord { mtime: SystemTime::UNIX_EPOCH, size: 0, vector_ids: vec![0], hash };
+            manifest.files.insert(file.to_string(), record);
+        }
+
+        let mut changes = manifest.detect_changes(&dir, WalkOptions::default()).unwrap();
+        assert_eq!(changes.modified.len(), 3);
+        assert_eq!(manifest.settle_unchanged(&dir, &mut changes), 1);
+        let mut modified: Vec<String> = changes.modified.iter().map(|p| relative_path(&dir, p)).collect();
+        modified.sort();
+        assert_eq!(modified, ["edited.php", "legacy.php"]);
+        // The refreshed record no longer looks modified
+        changes = manifest.detect_changes(&dir, WalkOptions::default()).unwrap();
+        assert_eq!(changes.modified.len(), 2);
+
+        // Files left alone get the hash they lack; changed ones don't
+        fs::write(dir.join("new.php"), "<?php echo 'new';").unwrap();
+        manifest.files.insert("new.php".to_string(), FileRecord::of(&dir.join("new.php"), vec![1]).unwrap());
+        assert!(manifest.files["new.php"].hash.is_empty());
+        assert_eq!(manifest.record_missing_hashes(&dir, &changes), 1);
+        assert_eq!(manifest.files["new.php"].hash, content_hash(&dir.join("new.php")).unwrap());
+        assert!(manifest.files["legacy.php"].hash.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_manifest_without_hashes() {
+        let dir = make_temp_dir();
+        let manifest_path = dir.join("legacy.manifest");
+        let legacy = FileManifestV1 {
+            files: HashMap::from([(
+                "app/code/Vendor/Module/Model/Foo.php".to_string(),
+                FileRecordV1 { mtime: SystemTime::UNIX_EPOCH, size: 10, vector_ids: vec![7] },
+            )]),
+        };
+        fs::write(&manifest_path, bincode::serde::encode_to_vec(&legacy, bincode::config::standard()).unwrap())
+            .unwrap();
+
+        let loaded = FileManifest::load(&manifest_path).unwrap();
+        let foo = &loaded.files["app/code/Vendor/Module/Model/Foo.php"];
+        assert_eq!((foo.size, foo.vector_ids.as_slice(), foo.hash.as_str()), (10, [7].as_slice(), ""));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_sidecar_path() {
         let db_path = PathBuf::from("/data/.magector/index.db");
@@ -10,7 +10,7 @@ import { existsSync, mkdirSync } from 'fs';
 import path from 'path';
 import { resolveBinary } from './binary.js';
 import { ensureModels, resolveModels } from './model.js';
-import { init, setup } from './init.js';
+import { init, setup, findMagentoRoot } from './init.js';
 import { checkForUpdate } from './update.js';
 import { createRequire } from 'module';
 const __cliPkg = createRequire(import.meta.url)('../package.json');
@@ -23,18 +23,79 @@ function showHelp() {
 Magector — Semantic code search for Magento 2
 
 Usage:
-  npx magector init [path]       Full setup: index + IDE config
+  npx magector init [path]       Full setup: config, model, index + IDE config
   npx magector index [path]      Index (or re-index) Magento codebase
   npx magector search <query>    Search indexed code
   npx magector describe [path]   Generate LLM descriptions for di.xml files
   npx magector mcp               Start MCP server (for Claude Code / Cursor)
   npx magector stats             Show index statistics
+  npx magector history <target>  Show how a file or class changed across
+                                 snapshots (index --tag)
+  npx magector find-string <value>
+                                 Class constants and enum cases holding a
+                                 string (--exact for equal values only)
+  npx magector queues [filter]   Message queue routes: topic → queue →
+                                 consumer → handler
+  npx magector indexers [filter] Indexers: id → action class, mview and
+                                 subscribed tables
+  npx magector totals [section]  Totals collectors in run order (quote,
+                                 order, invoice, creditmemo)
+  npx magector payments [filter] Payment methods: code → model, gateway
+                                 flag, checkout renderer
+  npx magector carriers [filter] Shipping carriers: code → model, title
+  npx magector console-commands [filter]
+                                 bin/magento commands: name → class
+  npx magector project-info      Enabled modules (config.php), deploy mode,
+                                 cache and queue config (env.php, redacted)
+  npx magector ab --config-a a.toml --config-b b.toml [--tests cases.yaml]
+                                 Compare two ranking configurations on the
+                                 validation suite (win/loss, significance)
+  npx magector analyze enrichment [--report validation_report.json]
+                                 Suggest search_text enrichment from failed
+                                 validation cases and zero-result queries
+                                 in .magector/magector.log
+  npx magector analyze duplicates [--threshold 0.95] [--file-type php]
+                                 Near-duplicate files in different modules
+                                 (copy-pasted helpers, cloned plugins)
+  npx magector analyze upgrade --from <core.db> --to <core.db>
+                                 Customized core classes ranked by how much
+                                 they change between two Magento versions
+  npx magector delta <old> <new> Write a patch from one index to another
+  npx magector apply <patch>     Apply a delta patch to the index
+  npx magector keygen            Generate an ed25519 key pair for signing
+  npx magector sign [file]       Sign an index or patch (writes <file>.sig)
+  npx magector verify [file]     Check a signature and the embedded checksum
+  npx magector fsck [--repair]   Check the index, its manifest and SONA file
+                                 for damage; --repair fixes it in place
+  npx magector backup [-o file]  Back up the index, manifest, SONA file and
+                                 magector.toml (.tar.zst)
+  npx magector restore <file>    Restore a backup, replacing the current state
+  npx magector migrate --model <name> [-o file]
+                                 Re-embed the index with another model from
+                                 its text cache into a new database
+  npx magector vectors <path...> [--id <n>]
+                                 Dump raw vectors of indexed files or items
+                                 as JSON lines
+  npx magector map [-k n] [--prefix dir] [-f json|csv] [-o file]
+                                 Cluster indexed files into a 2D code map
+                                 labelled by module; lists misplaced files
+  npx magector semdiff <old> <new> | <file> --rev <rev> [--all] [-f json]
+                                 Which methods changed semantically and
+                                 which only cosmetically
   npx magector setup [path]      IDE setup only (no indexing)
   npx magector help              Show this help
 
 Search options:
   -l, --limit <n>      Number of search results (default: 10)
   -f, --format <fmt>   Output format: text, json (default: text)
+  --snapshot <tag>     Search a tagged snapshot instead of the working index
+  --mark <n>           Mark result n as the right answer so SONA learns
+                       from it (re-run the same search with this added)
+
+Init options:
+  --no-index           Bootstrap the project (magector.toml, .magector/,
+                       .gitignore, model check, IDE config) without running
+                       the first index.
 
 Index options:
   --threads <n>        Max ONNX/rayon threads (default: half of CPU cores).
@@ -45,10 +106,55 @@ Index options:
   --force              Discard any existing index and rebuild from scratch.
                        Without --force, indexing auto-resumes from the last
                        incremental save (written every ~50 batches).
+  --deterministic      Reproducible output for CI artifact caching: items
+                       are inserted in path order with stable IDs, so
+                       identical sources give identical DB bytes.
+                       Implies --force.
+  --incremental        Re-embed only files whose content changed: files
+                       touched without changes (branch switches, deploy
+                       copies) keep their vectors, by content hash.
+  --dry-run            Discover files and print the breakdown, estimated
+                       index size and disk space check — no embedding.
+  --only-modules <list> Index only modules matching these comma-separated
+                       globs (e.g. Magento_Checkout,Vendor_*). Search and
+                       validation warn that the index is partial.
+  --follow-symlinks    Follow symlinked module directories (modman/dev
+                       setups); each module is indexed once.
+  --include-tests      Also index MFTF tests, action groups and data
+                       fixtures (Test/Mftf/**/*.xml). Unit and
+                       integration tests stay excluded.
+  --core-index <path>  Layer the project index over a pre-built read-only
+                       core index: files it covers are not indexed locally
+                       and searches merge both.
+  --tag <label>        Also save the finished index as a snapshot (e.g.
+                       v2.4.6 or a commit hash) for search --snapshot and
+                       history.
+  --pooling <mode>     Token pooling: cls, mean or max (default: mean).
+                       Recorded in the index; changing it rebuilds.
+  --coarse-dim <n>     Search a graph of the first n vector dimensions and
+                       rerank with the full vector (e.g. 128; 0 = off).
+  --two-stage          Search candidate files first, then their chunks
+                       (--no-two-stage turns it off). Recorded in the index.
+
+Delta options:
+  -o, --output <path>  Patch file to write (default: ./index.mdx)
+
+Apply options:
+  -d, --database <path> Index to patch (default: the project index)
+  -o, --output <path>  Write the patched index here instead of in place
+
+Keygen/sign/verify options:
+  -o, --output <path>  Secret key file for keygen (default: ./magector.key;
+                       the public key is written next to it as .pub)
+  -k, --key <path>     Secret key (sign) or public key (verify)
+  -s, --signature <path> Signature file for verify (default: <file>.sig)
 
 Environment Variables:
-  MAGENTO_ROOT             Path to Magento installation (default: cwd)
-  MAGECTOR_DB              Path to index database (default: ./.magector/index.db)
+  MAGENTO_ROOT             Path to Magento installation (default: nearest
+                           parent of cwd with app/etc/env.php or a Magento
+                           composer.json)
+  MAGECTOR_DB              Path to index database
+                           (default: <magento root>/.magector/index.db)
   MAGECTOR_BIN             Path to magector-core binary
   MAGECTOR_MODELS          Path to ONNX model directory
   MAGECTOR_THREADS         Max threads (overridden by --threads)
@@ -62,15 +168,31 @@ Examples:
   npx magector search "checkout controller" -l 20
   npx magector index
   npx magector index --threads 4 --batch-size 128
+  npx magector index --only-modules Magento_Checkout,Vendor_*
+  npx magector index --tag v2.4.7
+  npx magector history "Magento\\Quote\\Model\\QuoteRepository"
+  npx magector find-string sales_order_place_after
+  npx magector queues export
   MAGECTOR_INDEX_TIMEOUT=28800000 npx magector index   # 8h timeout
+  npx magector delta core-2.4.6.db core-2.4.7.db -o core-2.4.7.mdx
+  npx magector apply core-2.4.7.mdx -d /shared/magector/core.db
+  npx magector sign core-2.4.7.mdx -k release.key
   npx magector mcp
 `);
 }
 
-function getConfig() {
+/**
+ * Resolve the Magento root and DB path. Without MAGENTO_ROOT (or an explicit
+ * target), the root is the nearest parent of cwd that looks like a Magento
+ * project; the DB defaults to <root>/.magector/index.db.
+ */
+function getConfig(targetPath) {
+  const magentoRoot = path.resolve(
+    targetPath || process.env.MAGENTO_ROOT || findMagentoRoot(process.cwd()) || process.cwd()
+  );
   return {
-    dbPath: process.env.MAGECTOR_DB || './.magector/index.db',
-    magentoRoot: process.env.MAGENTO_ROOT || process.cwd()
+    dbPath: process.env.MAGECTOR_DB || path.join(magentoRoot, '.magector', 'index.db'),
+    magentoRoot
   };
 }
 
@@ -85,6 +207,36 @@ function parseArgs(argv) {
       opts.verbose = true;
     } else if (argv[i] === '--force') {
       opts.force = true;
+    } else if (argv[i] === '--deterministic') {
+      opts.deterministic = true;
+    } else if (argv[i] === '--incremental') {
+      opts.incremental = true;
+    } else if (argv[i] === '--dry-run') {
+      opts.dryRun = true;
+    } else if (argv[i] === '--follow-symlinks') {
+      opts.followSymlinks = true;
+    } else if (argv[i] === '--include-tests') {
+      opts.includeTests = true;
+    } else if (argv[i] === '--core-index') {
+      opts.coreIndex = argv[++i];
+    } else if (argv[i] === '--tag') {
+      opts.tag = argv[++i];
+    } else if (argv[i] === '--pooling') {
+      opts.pooling = argv[++i];
+    } else if (argv[i] === '--coarse-dim') {
+      opts.coarseDim = argv[++i];
+    } else if (argv[i] === '--two-stage') {
+      opts.twoStage = true;
+    } else if (argv[i] === '--no-two-stage') {
+      opts.twoStage = false;
+    } else if (argv[i] === '--snapshot') {
+      opts.snapshot = argv[++i];
+    } else if (argv[i] === '--mark') {
+      opts.mark = argv[++i];
+    } else if (argv[i] === '--no-index') {
+      opts.noIndex = true;
+    } else if (argv[i] === '--only-modules') {
+      opts.onlyModules = argv[++i];
     } else if (argv[i] === '--threads') {
       opts.threads = argv[++i];
     } else if (argv[i] === '--batch-size') {
@@ -95,8 +247,8 @@ function parseArgs(argv) {
 }
 
 async function runIndex(targetPath, opts = {}) {
-  const config = getConfig();
-  const root = targetPath || config.magentoRoot;
+  const config = getConfig(targetPath);
+  const root = config.magentoRoot;
   const binary = resolveBinary();
   const modelPath = await ensureModels();
 
@@ -132,6 +284,39 @@ async function runIndex(targetPath, opts = {}) {
     if (opts.force) {
       indexArgs.push('--force');
     }
+    if (opts.deterministic) {
+      indexArgs.push('--deterministic');
+    }
+    if (opts.incremental) {
+      indexArgs.push('--incremental');
+    }
+    if (opts.onlyModules) {
+      indexArgs.push('--only-modules', String(opts.onlyModules));
+    }
+    if (opts.dryRun) {
+      indexArgs.push('--dry-run');
+    }
+    if (opts.followSymlinks) {
+      indexArgs.push('--follow-symlinks');
+    }
+    if (opts.includeTests) {
+      indexArgs.push('--include-tests');
+    }
+    if (opts.coreIndex) {
+      indexArgs.push('--core-index', path.resolve(opts.coreIndex));
+    }
+    if (opts.tag) {
+      indexArgs.push('--tag', String(opts.tag));
+    }
+    if (opts.pooling) {
+      indexArgs.push('--pooling', String(opts.pooling));
+    }
+    if (opts.coarseDim !== undefined) {
+      indexArgs.push('--coarse-dim', String(opts.coarseDim));
+    }
+    if (opts.twoStage !== undefined) {
+      indexArgs.push(`--two-stage=${opts.twoStage}`);
+    }
     // Pass descriptions DB if it exists
     const descDbPath = path.resolve(root, '.magector', 'sqlite.db');
     if (existsSync(descDbPath)) {
@@ -178,6 +363,12 @@ function runSearch(query, opts = {}) {
     '-l', String(opts.limit || 10),
     '-f', opts.format || 'text'
   ];
+  if (opts.snapshot) {
+    searchArgs.push('--snapshot', String(opts.snapshot));
+  }
+  if (opts.mark) {
+    searchArgs.push('--mark', String(opts.mark));
+  }
 
   try {
     const output = execFileSync(binary, searchArgs, {
@@ -207,9 +398,401 @@ function runStats() {
   }
 }
 
-async function runDescribe(targetPath) {
+/**
+ * Split delta/apply/sign/verify arguments into positional paths and the
+ * -o/--output, -d/--database, -k/--key and -s/--signature values.
+ */
+function parseArtifactArgs(argv) {
+  const positional = [];
+  const opts = {};
+  for (let i = 0; i < argv.length; i++) {
+    if (argv[i] === '-o' || argv[i] === '--output') {
+      opts.output = argv[++i];
+    } else if (argv[i] === '-d' || argv[i] === '--database') {
+      opts.database = argv[++i];
+    } else if (argv[i] === '-k' || argv[i] === '--key') {
+      opts.key = argv[++i];
+    } else if (argv[i] === '-s' || argv[i] === '--signature') {
+      opts.signature = argv[++i];
+    } else if (!argv[i].startsWith('-')) {
+      positional.push(argv[i]);
+    }
+  }
+  return { positional, opts };
+}
+
+function runArtifactCommand(coreArgs, label) {
+  try {
+    execFileSync(resolveBinary(), coreArgs, { stdio: 'inherit' });
+  } catch (err) {
+    if (err.status) {
+      process.exit(err.status);
+    }
+    console.error(`${label} error: ${err.message}`);
+    process.exit(1);
+  }
+}
+
+function runDelta(argv) {
+  const { positional, opts } = parseArtifactArgs(argv);
+  if (positional.length !== 2) {
+    console.error('Usage: npx magector delta <old.db> <new.db> -o <patch.mdx>');
+    process.exit(1);
+  }
+  const deltaArgs = ['delta', path.resolve(positional[0]), path.resolve(positional[1])];
+  if (opts.output) deltaArgs.push('-o', path.resolve(opts.output));
+  runArtifactCommand(deltaArgs, 'Delta');
+}
+
+function runApply(argv) {
+  const { positional, opts } = parseArtifactArgs(argv);
+  if (positional.length !== 1) {
+    console.error('Usage: npx magector apply <patch.mdx> [-d <index.db>] [-o <output.db>]');
+    process.exit(1);
+  }
+  const applyArgs = [
+    'apply', path.resolve(positional[0]),
+    '-d', path.resolve(opts.database || getConfig().dbPath)
+  ];
+  if (opts.output) applyArgs.push('-o', path.resolve(opts.output));
+  runArtifactCommand(applyArgs, 'Apply');
+}
+
+function runKeygen(argv) {
+  const { opts } = parseArtifactArgs(argv);
+  runArtifactCommand(['keygen', '-o', path.resolve(opts.output || 'magector.key')], 'Keygen');
+}
+
+function runSign(argv) {
+  const { positional, opts } = parseArtifactArgs(argv);
+  if (!opts.key) {
+    console.error('Usage: npx magector sign [file] -k <secret.key>');
+    process.exit(1);
+  }
+  const file = path.resolve(positional[0] || getConfig().dbPath);
+  runArtifactCommand(['sign', file, '-k', path.resolve(opts.key)], 'Sign');
+}
+
+function runVerify(argv) {
+  const { positional, opts } = parseArtifactArgs(argv);
+  if (!opts.key) {
+    console.error('Usage: npx magector verify [file] -k <public.pub> [-s <file.sig>]');
+    process.exit(1);
+  }
+  const file = path.resolve(positional[0] || getConfig().dbPath);
+  const verifyArgs = ['verify', file, '-k', path.resolve(opts.key)];
+  if (opts.signature) verifyArgs.push('-s', path.resolve(opts.signature));
+  runArtifactCommand(verifyArgs, 'Verify');
+}
+
+function runFsck(argv) {
+  const opts = parseArgs(argv);
+  const fsckArgs = ['fsck', '-d', path.resolve(getConfig().dbPath), '-f', opts.format || 'text'];
+  if (argv.includes('--repair')) fsckArgs.push('--repair');
+  runArtifactCommand(fsckArgs, 'Fsck');
+}
+
+function runBackup(argv) {
+  const { opts } = parseArtifactArgs(argv);
+  const config = getConfig();
+  runArtifactCommand([
+    'backup',
+    '-d', path.resolve(opts.database || config.dbPath),
+    '-m', config.magentoRoot,
+    '-o', path.resolve(opts.output || 'magector-backup.tar.zst')
+  ], 'Backup');
+}
+
+function runRestore(argv) {
+  const { positional, opts } = parseArtifactArgs(argv);
+  if (positional.length !== 1) {
+    console.error('Usage: npx magector restore <backup.tar.zst> [-d <index.db>]');
+    process.exit(1);
+  }
   const config = getConfig();
-  const root = targetPath || config.magentoRoot;
+  runArtifactCommand([
+    'restore', path.resolve(positional[0]),
+    '-d', path.resolve(opts.database || config.dbPath),
+    '-m', config.magentoRoot
+  ], 'Restore');
+}
+
+function runMigrate(argv) {
+  const { opts } = parseArtifactArgs(argv);
+  const modelIdx = argv.indexOf('--model');
+  const model = modelIdx >= 0 ? argv[modelIdx + 1] : null;
+  if (!model) {
+    console.error('Usage: npx magector migrate --model <name> [-o <new.db>]');
+    process.exit(1);
+  }
+  const modelPath = resolveModels();
+  if (!modelPath) {
+    console.error('ONNX model not found. Run `npx magector init` or `npx magector index` first.');
+    process.exit(1);
+  }
+  const migrateArgs = [
+    'migrate',
+    '--model', model,
+    '-d', path.resolve(opts.database || getConfig().dbPath),
+    '-c', modelPath
+  ];
+  if (opts.output) migrateArgs.push('-o', path.resolve(opts.output));
+  runArtifactCommand(migrateArgs, 'Migrate');
+}
+
+function runVectors(argv) {
+  const { positional, opts } = parseArtifactArgs(argv);
+  const ids = argv.filter((a, i) => argv[i - 1] === '--id');
+  const paths = positional.filter((p) => !ids.includes(p));
+  if (paths.length === 0 && ids.length === 0) {
+    console.error('Usage: npx magector vectors <path...> [--id <n>]');
+    process.exit(1);
+  }
+  const vectorArgs = ['vectors', ...paths, '-d', path.resolve(opts.database || getConfig().dbPath)];
+  for (const id of ids) vectorArgs.push('--id', id);
+  runArtifactCommand(vectorArgs, 'Vectors');
+}
+
+function runMap(argv) {
+  const { opts } = parseArtifactArgs(argv);
+  const value = (...flags) => {
+    const i = argv.findIndex((a) => flags.includes(a));
+    return i >= 0 ? argv[i + 1] : null;
+  };
+  const mapArgs = ['map', '-d', path.resolve(opts.database || getConfig().dbPath)];
+  const clusters = value('-k', '--clusters');
+  if (clusters) mapArgs.push('-k', clusters);
+  const prefix = value('--prefix');
+  if (prefix) mapArgs.push('--prefix', prefix);
+  mapArgs.push('-f', value('-f', '--format') || 'text');
+  if (opts.output) mapArgs.push('-o', path.resolve(opts.output));
+  runArtifactCommand(mapArgs, 'Map');
+}
+
+function runSemdiff(argv) {
+  const value = (...flags) => {
+    const i = argv.findIndex((a) => flags.includes(a));
+    return i >= 0 ? argv[i + 1] : null;
+  };
+  const valued = ['--rev', '-t', '--threshold', '-f', '--format'];
+  const files = argv.filter((a, i) => !a.startsWith('-') && !valued.includes(argv[i - 1]));
+  const rev = value('--rev');
+  if (files.length === 0 || (files.length === 1 && !rev)) {
+    console.error('Usage: npx magector semdiff <old> <new> | <file> --rev <rev> [-t 0.97] [--all] [-f json]');
+    process.exit(1);
+  }
+  const modelPath = resolveModels();
+  if (!modelPath) {
+    console.error('ONNX model not found. Run `npx magector init` or `npx magector index` first.');
+    process.exit(1);
+  }
+  const semdiffArgs = ['semdiff', ...files.slice(0, 2).map((f) => path.resolve(f)), '-c', modelPath];
+  if (rev) semdiffArgs.push('--rev', rev);
+  const threshold = value('-t', '--threshold');
+  if (threshold) semdiffArgs.push('-t', threshold);
+  if (argv.includes('--all')) semdiffArgs.push('--all');
+  semdiffArgs.push('-f', value('-f', '--format') || 'text');
+  runArtifactCommand(semdiffArgs, 'Semdiff');
+}
+
+function runFindString(argv) {
+  const opts = parseArgs(argv);
+  const value = argv.find((a, i) => !a.startsWith('-') && !['-f', '--format'].includes(argv[i - 1]));
+  if (!value) {
+    console.error('Usage: npx magector find-string <value> [--exact] [-f json]');
+    process.exit(1);
+  }
+  const findArgs = [
+    'find-string', value,
+    '-d', path.resolve(getConfig().dbPath),
+    '-f', opts.format || 'text'
+  ];
+  if (argv.includes('--exact')) findArgs.push('--exact');
+  runArtifactCommand(findArgs, 'Find string');
+}
+
+function runHistory(argv) {
+  const opts = parseArgs(argv);
+  const target = argv.find((a, i) => !a.startsWith('-') && !['-f', '--format'].includes(argv[i - 1]));
+  if (!target) {
+    console.error('Usage: npx magector history <path|class> [-f json]');
+    process.exit(1);
+  }
+  const historyArgs = [
+    'history', target,
+    '-d', path.resolve(getConfig().dbPath),
+    '-f', opts.format || 'text'
+  ];
+  runArtifactCommand(historyArgs, 'History');
+}
+
+function runQueues(argv) {
+  const opts = parseArgs(argv);
+  const filter = argv.find((a, i) => !a.startsWith('-') && !['-f', '--format'].includes(argv[i - 1]));
+  const queueArgs = [
+    'queues',
+    '-m', getConfig().magentoRoot,
+    '-f', opts.format || 'text'
+  ];
+  if (filter) queueArgs.push(filter);
+  runArtifactCommand(queueArgs, 'Queues');
+}
+
+function runIndexers(argv) {
+  const opts = parseArgs(argv);
+  const filter = argv.find((a, i) => !a.startsWith('-') && !['-f', '--format'].includes(argv[i - 1]));
+  const indexerArgs = [
+    'indexers',
+    '-m', getConfig().magentoRoot,
+    '-f', opts.format || 'text'
+  ];
+  if (filter) indexerArgs.push(filter);
+  runArtifactCommand(indexerArgs, 'Indexers');
+}
+
+function runPayments(argv) {
+  const opts = parseArgs(argv);
+  const filter = argv.find((a, i) => !a.startsWith('-') && !['-f', '--format'].includes(argv[i - 1]));
+  const paymentArgs = [
+    'payments',
+    '-m', getConfig().magentoRoot,
+    '-f', opts.format || 'text'
+  ];
+  if (filter) paymentArgs.push(filter);
+  runArtifactCommand(paymentArgs, 'Payments');
+}
+
+function runCarriers(argv) {
+  const opts = parseArgs(argv);
+  const filter = argv.find((a, i) => !a.startsWith('-') && !['-f', '--format'].includes(argv[i - 1]));
+  const carrierArgs = [
+    'carriers',
+    '-m', getConfig().magentoRoot,
+    '-f', opts.format || 'text'
+  ];
+  if (filter) carrierArgs.push(filter);
+  runArtifactCommand(carrierArgs, 'Carriers');
+}
+
+function runConsoleCommands(argv) {
+  const opts = parseArgs(argv);
+  const filter = argv.find((a, i) => !a.startsWith('-') && !['-f', '--format'].includes(argv[i - 1]));
+  const commandArgs = [
+    'console-commands',
+    '-m', getConfig().magentoRoot,
+    '-f', opts.format || 'text'
+  ];
+  if (filter) commandArgs.push(filter);
+  runArtifactCommand(commandArgs, 'Console commands');
+}
+
+function runProjectInfo(argv) {
+  const opts = parseArgs(argv);
+  runArtifactCommand(['project-info', '-m', getConfig().magentoRoot, '-f', opts.format || 'text'], 'Project info');
+}
+
+function runAb(argv) {
+  const opts = parseArgs(argv);
+  const flag = (name) => {
+    const i = argv.indexOf(name);
+    return i >= 0 ? argv[i + 1] : undefined;
+  };
+  const configA = flag('--config-a');
+  const configB = flag('--config-b');
+  if (!configA || !configB) {
+    console.error('Usage: npx magector ab --config-a a.toml --config-b b.toml [--tests cases.yaml] [-f json]');
+    process.exit(1);
+  }
+  const modelPath = resolveModels();
+  if (!modelPath) {
+    console.error('ONNX model not found. Run `npx magector init` or `npx magector index` first.');
+    process.exit(1);
+  }
+  const abArgs = [
+    'ab',
+    '--config-a', path.resolve(configA),
+    '--config-b', path.resolve(configB),
+    '-d', path.resolve(getConfig().dbPath),
+    '-c', modelPath,
+    '-f', opts.format || 'text'
+  ];
+  const tests = flag('--tests');
+  if (tests) abArgs.push('--tests', path.resolve(tests));
+  runArtifactCommand(abArgs, 'A/B');
+}
+
+function runAnalyze(argv) {
+  const opts = parseArgs(argv);
+  const flag = (name) => {
+    const i = argv.indexOf(name);
+    return i >= 0 ? argv[i + 1] : undefined;
+  };
+  if (argv[0] === 'duplicates') {
+    const duplicateArgs = [
+      'analyze', 'duplicates',
+      '-d', path.resolve(getConfig().dbPath),
+      '-f', opts.format || 'text'
+    ];
+    const threshold = flag('--threshold');
+    if (threshold) duplicateArgs.push('--threshold', threshold);
+    const fileType = flag('--file-type');
+    if (fileType !== undefined) duplicateArgs.push('--file-type', fileType);
+    if (opts.limit) duplicateArgs.push('-n', String(opts.limit));
+    runArtifactCommand(duplicateArgs, 'Analyze');
+    return;
+  }
+  if (argv[0] === 'upgrade') {
+    const from = flag('--from');
+    const to = flag('--to');
+    if (!from || !to) {
+      console.error('Usage: npx magector analyze upgrade --from <core-current.db> --to <core-target.db> [-l n] [-f json]');
+      process.exit(1);
+    }
+    const upgradeArgs = [
+      'analyze', 'upgrade',
+      '-d', path.resolve(getConfig().dbPath),
+      '--from', path.resolve(from),
+      '--to', path.resolve(to),
+      '-f', opts.format || 'text'
+    ];
+    if (opts.limit) upgradeArgs.push('-n', String(opts.limit));
+    runArtifactCommand(upgradeArgs, 'Analyze');
+    return;
+  }
+  if (argv[0] !== 'enrichment') {
+    console.error('Usage: npx magector analyze enrichment [--report validation_report.json] [--log magector.log] [-l n] [-f json]');
+    console.error('       npx magector analyze duplicates [--threshold 0.95] [--file-type php] [-l n] [-f json]');
+    console.error('       npx magector analyze upgrade --from <core-current.db> --to <core-target.db> [-l n] [-f json]');
+    process.exit(1);
+  }
+  const analyzeArgs = [
+    'analyze', 'enrichment',
+    '-d', path.resolve(getConfig().dbPath),
+    '-f', opts.format || 'text'
+  ];
+  const report = flag('--report');
+  if (report) analyzeArgs.push('--report', path.resolve(report));
+  const log = flag('--log');
+  if (log) analyzeArgs.push('--log', path.resolve(log));
+  if (opts.limit) analyzeArgs.push('-n', String(opts.limit));
+  runArtifactCommand(analyzeArgs, 'Analyze');
+}
+
+function runTotals(argv) {
+  const opts = parseArgs(argv);
+  const section = argv.find((a, i) => !a.startsWith('-') && !['-f', '--format'].includes(argv[i - 1]));
+  const totalsArgs = [
+    'totals',
+    '-m', getConfig().magentoRoot,
+    '-f', opts.format || 'text'
+  ];
+  if (section) totalsArgs.push(section);
+  runArtifactCommand(totalsArgs, 'Totals');
+}
+
+async function runDescribe(targetPath) {
+  const config = getConfig(targetPath);
+  const root = config.magentoRoot;
   const binary = resolveBinary();
   const opts = parseArgs(args.slice(1));
   mkdirSync(path.resolve(root, '.magector'), { recursive: true });
@@ -264,7 +847,7 @@ async function main() {
       const indexOpts = parseArgs(indexArgv);
       let targetPath = undefined;
       for (let i = 0; i < indexArgv.length; i++) {
-        if (indexArgv[i] === '--threads' || indexArgv[i] === '--batch-size') {
+        if (['--threads', '--batch-size', '--only-modules', '--core-index', '--tag', '--pooling', '--coarse-dim'].includes(indexArgv[i])) {
           i++; // skip the flag's value
         } else if (indexArgv[i].startsWith('-')) {
           // skip boolean flags like --force, --verbose
@@ -283,7 +866,8 @@ async function main() {
       const queryParts = [];
       for (let i = 0; i < searchArgv.length; i++) {
         if (searchArgv[i] === '-l' || searchArgv[i] === '--limit' ||
-            searchArgv[i] === '-f' || searchArgv[i] === '--format') {
+            searchArgv[i] === '-f' || searchArgv[i] === '--format' ||
+            searchArgv[i] === '--snapshot' || searchArgv[i] === '--mark') {
           i++; // skip the flag's value
         } else if (searchArgv[i].startsWith('-')) {
           // skip boolean flags like -v, --verbose
@@ -313,6 +897,98 @@ async function main() {
       runStats();
       break;
 
+    case 'delta':
+      runDelta(args.slice(1));
+      break;
+
+    case 'apply':
+      runApply(args.slice(1));
+      break;
+
+    case 'history':
+      runHistory(args.slice(1));
+      break;
+
+    case 'find-string':
+      runFindString(args.slice(1));
+      break;
+
+    case 'vectors':
+      runVectors(args.slice(1));
+      break;
+
+    case 'map':
+      runMap(args.slice(1));
+      break;
+
+    case 'semdiff':
+      runSemdiff(args.slice(1));
+      break;
+
+    case 'queues':
+      runQueues(args.slice(1));
+      break;
+
+    case 'indexers':
+      runIndexers(args.slice(1));
+      break;
+
+    case 'ab':
+      runAb(args.slice(1));
+      break;
+
+    case 'analyze':
+      runAnalyze(args.slice(1));
+      break;
+
+    case 'totals':
+      runTotals(args.slice(1));
+      break;
+
+    case 'payments':
+      runPayments(args.slice(1));
+      break;
+
+    case 'carriers':
+      runCarriers(args.slice(1));
+      break;
+
+    case 'console-commands':
+      runConsoleCommands(args.slice(1));
+      break;
+
+    case 'project-info':
+      runProjectInfo(args.slice(1));
+      break;
+
+    case 'keygen':
+      runKeygen(args.slice(1));
+      break;
+
+    case 'sign':
+      runSign(args.slice(1));
+      break;
+
+    case 'verify':
+      runVerify(args.slice(1));
+      break;
+
+    case 'fsck':
+      runFsck(args.slice(1));
+      break;
+
+    case 'backup':
+      runBackup(args.slice(1));
+      break;
+
+    case 'restore':
+      runRestore(args.slice(1));
+      break;
+
+    case 'migrate':
+      runMigrate(args.slice(1));
+      break;
+
     case 'setup':
       await setup(args[1]);
       break;
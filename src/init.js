@@ -11,6 +11,7 @@ import { resolveBinary } from './binary.js';
 import { ensureModels } from './model.js';
 import { CURSOR_RULES_MDC } from './templates/cursor-rules-mdc.js';
 import { CLAUDE_MD } from './templates/claude-md.js';
+import { MAGECTOR_TOML } from './templates/magector-toml.js';
 
 /**
  * Prompt the user for input. Returns empty string if stdin is not a TTY.
@@ -49,6 +50,37 @@ function isMagentoProject(projectPath) {
   return false;
 }
 
+/**
+ * Walk up from `startPath` to the nearest Magento 2 root.
+ *
+ * Stricter than isMagentoProject(): a composer.json only counts when it pulls
+ * in magento/framework or a magento/product-* metapackage *and* sits next to
+ * an app/ tree, so module-level composer.json files are skipped.
+ * Returns null when no root is found.
+ */
+export function findMagentoRoot(startPath) {
+  let dir = path.resolve(startPath || process.cwd());
+  for (;;) {
+    if (existsSync(path.join(dir, 'app', 'etc', 'env.php'))) {
+      return dir;
+    }
+    const composerPath = path.join(dir, 'composer.json');
+    if (existsSync(composerPath) && existsSync(path.join(dir, 'app'))) {
+      try {
+        const content = readFileSync(composerPath, 'utf-8');
+        if (content.includes('"magento/framework"') || content.includes('"magento/product-')) {
+          return dir;
+        }
+      } catch {
+        // ignore read errors
+      }
+    }
+    const parent = path.dirname(dir);
+    if (parent === dir) return null;
+    dir = parent;
+  }
+}
+
 /**
  * Detect which IDEs are present.
  * Returns { cursor: boolean, claude: boolean }
@@ -196,16 +228,89 @@ function updateGitignore(projectPath) {
   return true;
 }
 
+/**
+ * Write a starter magector.toml unless the project already has one.
+ */
+function writeStarterConfig(projectPath) {
+  const configPath = path.join(projectPath, 'magector.toml');
+  if (existsSync(configPath)) return false;
+  writeFileSync(configPath, MAGECTOR_TOML);
+  return true;
+}
+
+/**
+ * Check that the binary can load the model by embedding a short string.
+ * Returns an error message, or null when the model works.
+ */
+function validateModel(binary, modelPath) {
+  try {
+    const output = execFileSync(binary, ['embed', '-t', 'magento', '-c', modelPath], {
+      encoding: 'utf-8', timeout: 60000, stdio: ['pipe', 'pipe', 'pipe']
+    });
+    const vector = JSON.parse(output.trim());
+    return Array.isArray(vector) && vector.length > 0 ? null : 'empty embedding';
+  } catch (err) {
+    return (err.stderr || err.message || String(err)).trim();
+  }
+}
+
+/**
+ * Run the first index; exits the process on failure.
+ */
+function runFirstIndex(binary, projectPath, dbPath, modelPath, opts) {
+  console.log('\nIndexing codebase...');
+  // Default 4 hours — generous enough for ~80K-file enterprise codebases under
+  // CPU constraint. Override via MAGECTOR_INDEX_TIMEOUT (milliseconds).
+  const initTimeout = parseInt(process.env.MAGECTOR_INDEX_TIMEOUT, 10) || 14400000;
+  try {
+    const indexArgs = [
+      'index',
+      '-m', projectPath,
+      '-d', dbPath,
+      '-c', modelPath
+    ];
+    if (opts.threads != null) {
+      indexArgs.push('--threads', String(opts.threads));
+    }
+    if (opts.batchSize != null) {
+      indexArgs.push('--batch-size', String(opts.batchSize));
+    }
+    if (opts.force) {
+      indexArgs.push('--force');
+    }
+    execFileSync(binary, indexArgs, { timeout: initTimeout, stdio: 'inherit' });
+  } catch (err) {
+    if (err.status) {
+      console.error('Indexing failed.');
+      process.exit(err.status);
+    }
+    if (err.message && err.message.includes('ETIMEDOUT')) {
+      console.error(
+        `Indexing timed out after ${initTimeout / 1000}s.\n` +
+        `For large codebases or CPU-constrained environments, increase the timeout:\n` +
+        `  MAGECTOR_INDEX_TIMEOUT=28800000 npx magector init ${projectPath}    # 8 hours\n` +
+        `Or reduce CPU usage:\n` +
+        `  npx magector init ${projectPath} --threads 2`
+      );
+    } else {
+      console.error(`Indexing error: ${err.message}`);
+    }
+    process.exit(1);
+  }
+}
+
 /**
  * Main init function.
  *
- * @param {string} projectPath  - Magento root (defaults to cwd).
+ * @param {string} projectPath  - Magento root (defaults to the nearest parent
+ *                                of cwd that looks like a Magento project).
  * @param {object} [opts]
  * @param {number|string} [opts.threads]   - Forwarded as `--threads` to magector-core.
  * @param {number|string} [opts.batchSize] - Forwarded as `--batch-size`.
+ * @param {boolean} [opts.noIndex]         - Skip the first index run.
  */
 export async function init(projectPath, opts = {}) {
-  projectPath = path.resolve(projectPath || process.cwd());
+  projectPath = path.resolve(projectPath || findMagentoRoot(process.cwd()) || process.cwd());
   mkdirSync(path.join(projectPath, '.magector'), { recursive: true });
   const dbPath = path.join(projectPath, '.magector', 'index.db');
 
@@ -245,47 +350,28 @@ export async function init(projectPath, opts = {}) {
     process.exit(1);
   }
   console.log(`  Models: ${modelPath}`);
+  const modelError = validateModel(binary, modelPath);
+  if (modelError) {
+    console.error(`Error: model failed to load: ${modelError}`);
+    process.exit(1);
+  }
+  console.log('  Model check: OK');
+
+  // Starter config (kept if present) and .gitignore entry before indexing,
+  // so an interrupted first index still leaves a usable project setup.
+  if (writeStarterConfig(projectPath)) {
+    console.log('  Wrote magector.toml');
+  }
+  if (updateGitignore(projectPath)) {
+    console.log('  Updated .gitignore with .magector/');
+  }
 
   // 4. Run indexing
-  console.log('\nIndexing codebase...');
   const startTime = Date.now();
-  // Default 4 hours — generous enough for ~80K-file enterprise codebases under
-  // CPU constraint. Override via MAGECTOR_INDEX_TIMEOUT (milliseconds).
-  const initTimeout = parseInt(process.env.MAGECTOR_INDEX_TIMEOUT, 10) || 14400000;
-  try {
-    const indexArgs = [
-      'index',
-      '-m', projectPath,
-      '-d', dbPath,
-      '-c', modelPath
-    ];
-    if (opts.threads != null) {
-      indexArgs.push('--threads', String(opts.threads));
-    }
-    if (opts.batchSize != null) {
-      indexArgs.push('--batch-size', String(opts.batchSize));
-    }
-    if (opts.force) {
-      indexArgs.push('--force');
-    }
-    execFileSync(binary, indexArgs, { timeout: initTimeout, stdio: 'inherit' });
-  } catch (err) {
-    if (err.status) {
-      console.error('Indexing failed.');
-      process.exit(err.status);
-    }
-    if (err.message && err.message.includes('ETIMEDOUT')) {
-      console.error(
-        `Indexing timed out after ${initTimeout / 1000}s.\n` +
-        `For large codebases or CPU-constrained environments, increase the timeout:\n` +
-        `  MAGECTOR_INDEX_TIMEOUT=28800000 npx magector init ${projectPath}    # 8 hours\n` +
-        `Or reduce CPU usage:\n` +
-        `  npx magector init ${projectPath} --threads 2`
-      );
-    } else {
-      console.error(`Indexing error: ${err.message}`);
-    }
-    process.exit(1);
+  if (opts.noIndex) {
+    console.log('\nSkipping indexing (--no-index). Run `npx magector index` when ready.');
+  } else {
+    runFirstIndex(binary, projectPath, dbPath, modelPath, opts);
   }
   const elapsed = ((Date.now() - startTime) / 1000).toFixed(1);
 
@@ -317,13 +403,7 @@ export async function init(projectPath, opts = {}) {
   const rulesFiles = writeRules(projectPath, ides);
   rulesFiles.forEach(f => console.log(`  ${f}`));
 
-  // 9. Update .gitignore
-  const giUpdated = updateGitignore(projectPath);
-  if (giUpdated) {
-    console.log('\nUpdated .gitignore with .magector/');
-  }
-
-  // 10. Get stats and print summary
+  // 9. Get stats and print summary
   let vectorCount = '?';
   try {
     const statsOutput = execFileSync(binary, ['stats', '-d', dbPath], {
@@ -337,7 +417,11 @@ export async function init(projectPath, opts = {}) {
 
   console.log(`\n${'='.repeat(50)}`);
   console.log(`Setup complete!`);
-  console.log(`  Indexed ${vectorCount} vectors in ${elapsed}s`);
+  if (opts.noIndex) {
+    console.log(`  Index: not built yet — run \`npx magector index\``);
+  } else {
+    console.log(`  Indexed ${vectorCount} vectors in ${elapsed}s`);
+  }
   console.log(`  Configured for: ${ideNames.join(' + ')}`);
   console.log(`  Database: ${dbPath}`);
   console.log(`\nTest it:`);
@@ -17,8 +17,9 @@ import {
 import { execFileSync, spawn } from 'child_process';
 import { createInterface } from 'readline';
 import { createServer as createNetServer, createConnection } from 'net';
-import { existsSync, statSync, unlinkSync, copyFileSync, renameSync, appendFileSync, writeFileSync, readFileSync, readdirSync, mkdirSync, openSync, closeSync, chmodSync, constants as fsConstants } from 'fs';
+import { existsSync, statSync, unlinkSync, copyFileSync, renameSync, appendFileSync, writeFileSync, readFileSync, readdirSync, mkdirSync, openSync, closeSync, chmodSync, realpathSync, constants as fsConstants } from 'fs';
 import { stat } from 'fs/promises';
+import { randomUUID } from 'crypto';
 import { glob } from 'glob';
 import path from 'path';
 import {
@@ -231,6 +232,16 @@ function safePath(root, rel) {
   if (joined !== rootAbs && !joined.startsWith(rootAbs + path.sep)) {
     return null;
   }
+  // A symlink inside the root may still point out of it
+  if (existsSync(joined)) {
+    try {
+      const real = realpathSync(joined);
+      const realRoot = realpathSync(rootAbs);
+      if (real !== realRoot && !real.startsWith(realRoot + path.sep)) return null;
+    } catch {
+      return null;
+    }
+  }
   return joined;
 }
 
@@ -246,6 +257,32 @@ function safeRelPath(root, rel) {
   return r === '' ? '.' : r;
 }
 
+// ─── Secret Redaction ───────────────────────────────────────────
+// Mirrors the built-in patterns of rust-core/src/redact.rs for content this
+// server reads itself (magento_read, the external grep fallback). Custom
+// `[redact] patterns` from magector.toml apply to the index and serve grep.
+const SECRET_KEY = '[a-z0-9_.-]*(?:password|passwd|secret|api_?key|access_?token|auth_?token|private_?key|client_?secret|crypt_?key)';
+const SECRET_PATTERNS = [
+  new RegExp(`(['"]${SECRET_KEY}['"]\\s*=>\\s*['"])([^'"]+)(['"])`, 'gi'),
+  /(['"]crypt['"]\s*=>\s*(?:\[|array\s*\()\s*['"]key['"]\s*=>\s*['"])([^'"]+)(['"])/gi,
+  new RegExp(`(<${SECRET_KEY}\\b[^>/]*>)([^<]+)(</)`, 'gi'),
+  new RegExp(`(\\b${SECRET_KEY}\\s*[:=]\\s*['"])([^'"\\s]{6,})(['"])`, 'gi'),
+  /()(-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----)()/g,
+  /()(\b(?:AKIA|ASIA)[0-9A-Z]{16}\b)()/g,
+];
+
+/**
+ * Replace credentials in file content with [REDACTED], keeping newlines so
+ * line numbers stay put.
+ */
+function redactSecrets(text) {
+  let out = text;
+  for (const re of SECRET_PATTERNS) {
+    out = out.replace(re, (_, pre, secret, post) => `${pre}[REDACTED]${'\n'.repeat((secret.match(/\n/g) || []).length)}${post}`);
+  }
+  return out;
+}
+
 /**
  * Expand brace patterns in include globs for GNU grep compatibility.
  * GNU grep --include does NOT support brace expansion (that's a shell feature).
@@ -491,6 +528,8 @@ let reindexPhase = 0;      // 0=init, 1=AST, 2=embeddings, 3=HNSW
 let reindexTotalFiles = 0;
 let reindexItemsToEmbed = 0;
 let reindexPhase2Start = null;
+// True while serve answers from the priority checkpoint of a first-time build
+let reindexServingPartial = false;
 
 /**
  * Check if the database file is compatible with the current binary.
@@ -631,6 +670,7 @@ function startBackgroundReindex() {
   reindexTotalFiles = 0;
   reindexItemsToEmbed = 0;
   reindexPhase2Start = null;
+  reindexServingPartial = false;
 
   const hadExistingDb = existsSync(config.dbPath);
   logToFile('WARN', `Starting background re-index to temp path. Old DB ${hadExistingDb ? 'preserved for queries' : 'not found'}.`);
@@ -670,10 +710,18 @@ function startBackgroundReindex() {
     if (text.includes('PHASE 1') || text.includes('AST analyzer')) reindexPhase = 1;
     if (text.includes('PHASE 2') || text.includes('semantic embedding') || text.includes('Generating semantic')) {
       if (reindexPhase < 2) { reindexPhase = 2; reindexPhase2Start = Date.now(); }
+      // Parsing and embedding overlap, so the item count isn't printed up front;
+      // one item per file is a close enough estimate for the ETA.
+      if (reindexItemsToEmbed === 0) reindexItemsToEmbed = reindexTotalFiles;
     }
     if (text.includes('PHASE 3') || text.includes('Building HNSW') || text.includes('HNSW')) reindexPhase = 3;
     const em = text.match(/Items to embed: (\d[\d,]+)/);
     if (em) reindexItemsToEmbed = parseInt(em[1].replace(/,/g, ''), 10);
+    // First-time build: the indexer checkpoints after app/code + app/design.
+    // Serve that partial index right away instead of waiting for vendor/.
+    if (!hadExistingDb && !reindexServingPartial && text.includes('Priority files indexed')) {
+      publishPartialIndex(tempDbPath);
+    }
   };
   indexStdout.on('line', (line) => {
     const text = line.replace(/\x1b\[[0-9;]*m/g, '').trim();
@@ -690,10 +738,13 @@ function startBackgroundReindex() {
     reindexStartTime = null;
     reindexPhase = 0;
     removeReindexPidFile();
+    const servedPartial = reindexServingPartial;
+    reindexServingPartial = false;
     if (code === 0) {
       // Atomic swap: old → .bak, new → current
       try {
-        if (existsSync(config.dbPath)) {
+        // A served checkpoint copy is not worth backing up — just replace it
+        if (!servedPartial && existsSync(config.dbPath)) {
           const backupPath = config.dbPath + '.bak';
           if (existsSync(backupPath)) { try { unlinkSync(backupPath); } catch {} }
           renameSync(config.dbPath, backupPath);
@@ -726,6 +777,28 @@ function startBackgroundReindex() {
   });
 }
 
+/**
+ * Copy the indexer's priority checkpoint into place and (re)start serve on it,
+ * so a first-time build answers queries for project code within minutes.
+ * The completed index replaces the copy when the build exits.
+ */
+function publishPartialIndex(tempDbPath) {
+  const stagingPath = config.dbPath + '.partial';
+  try {
+    copyFileSync(tempDbPath, stagingPath);
+    renameSync(stagingPath, config.dbPath);
+  } catch (e) {
+    logToFile('WARN', `Could not publish partial index: ${e.message}`);
+    try { if (existsSync(stagingPath)) unlinkSync(stagingPath); } catch {}
+    return;
+  }
+  reindexServingPartial = true;
+  logToFile('INFO', 'Priority checkpoint published — serving partial index while vendor/ is indexed.');
+  if (serveProcess) serveProcess.kill();
+  searchCache.clear();
+  startServeProcess();
+}
+
 /**
  * Query cache: avoid re-embedding identical queries.
  * Keyed by "query|limit", capped at 200 entries (LRU eviction).
@@ -847,6 +920,30 @@ function startServeProcess() {
     if (threads) {
       args.push('--threads', threads);
     }
+    // Core index override; by default serve reopens the one recorded in the overlay
+    if (process.env.MAGECTOR_CORE_INDEX) {
+      args.push('--core-index', process.env.MAGECTOR_CORE_INDEX);
+    }
+    // Per-search latency budget; late searches skip reranking
+    if (process.env.MAGECTOR_SEARCH_DEADLINE_MS) {
+      args.push('--deadline-ms', process.env.MAGECTOR_SEARCH_DEADLINE_MS);
+    }
+    // External query rewriter (e.g. an LLM expanding questions into Magento terms)
+    if (process.env.MAGECTOR_REWRITER_CMD) {
+      args.push('--rewriter-cmd', process.env.MAGECTOR_REWRITER_CMD);
+    }
+    // Beam width measured on the index instead of the built-in default
+    if (process.env.MAGECTOR_TUNE_EF === '1') {
+      args.push('--tune-ef');
+    }
+    // Periodic accuracy probe, trend reported by stats
+    if (process.env.MAGECTOR_PROBE_HOURS) {
+      args.push('--probe-hours', process.env.MAGECTOR_PROBE_HOURS);
+    }
+    // Root remapping for an index built on another machine (OLD=NEW, PATH-style list)
+    for (const map of (process.env.MAGECTOR_MAP_ROOT || '').split(path.delimiter).filter(Boolean)) {
+      args.push('--map-root', map);
+    }
     logToFile('INFO', `Starting serve process: ${config.rustBinary} ${args.join(' ')}`);
     const proc = spawn(config.rustBinary, args,
       { stdio: ['pipe', 'pipe', 'pipe'], env: rustEnv });
@@ -888,7 +985,16 @@ function startServeProcess() {
         return;
       }
 
-      // Route response to pending request by order (FIFO)
+      // Route response to its request by id; answers without one (older
+      // serve binaries) go to the oldest pending request
+      if (parsed.id !== undefined) {
+        const resolver = servePending.get(parsed.id);
+        if (resolver) {
+          servePending.delete(parsed.id);
+          resolver.resolve(parsed);
+        }
+        return;
+      }
       if (servePending.size > 0) {
         const [id, resolver] = servePending.entries().next().value;
         servePending.delete(id);
@@ -1027,6 +1133,25 @@ function tryConnectSocket() {
 // Global reference to serveQuery implementation (local or socket)
 let globalServeQuery = null;
 
+/**
+ * Serve session of this MCP server: files it reads mildly lift their modules
+ * in its later searches. Kept in serve's memory only, never learned.
+ */
+const SESSION_ID = randomUUID();
+let sessionModules = '[]';
+
+function sessionOpenFile(relPath) {
+  const queryFn = globalServeQuery || ((serveProcess && serveReady) ? serveQuery : null);
+  if (!queryFn) return;
+  queryFn('session_open', { session: SESSION_ID, path: relPath }).then(resp => {
+    const modules = JSON.stringify(resp.ok && resp.data ? resp.data.modules : []);
+    if (modules !== sessionModules) {
+      sessionModules = modules;
+      searchCache.clear(); // cached rankings predate the new boost
+    }
+  }).catch(() => {});
+}
+
 function serveQuery(command, params = {}, timeoutMs = 30000) {
   if (!serveProcess || !serveReady) {
     logToFile('WARN', `serveQuery(${command}): serve process not ready — returning error`);
@@ -1038,12 +1163,17 @@ function serveQuery(command, params = {}, timeoutMs = 30000) {
     const timer = setTimeout(() => {
       servePending.delete(id);
       logToFile('ERR', `[${id}] ← ${command} TIMEOUT after ${timeoutMs}ms`);
+      // Free the serve worker instead of letting it finish unwanted work;
+      // the answer carries an id nobody waits for and is dropped
+      if (serveProcess) {
+        serveProcess.stdin.write(JSON.stringify({ command: 'cancel', request: id, id: serveNextId++ }) + '\n');
+      }
       reject(new Error('Serve query timeout'));
     }, timeoutMs);
     servePending.set(id, {
       resolve: (v) => { clearTimeout(timer); resolve(v); }
     });
-    const msg = JSON.stringify({ command, ...params });
+    const msg = JSON.stringify({ command, ...params, id });
     serveProcess.stdin.write(msg + '\n');
   });
 }
@@ -1075,7 +1205,7 @@ async function rustSearchAsync(query, limit = 10) {
   const queryFn = globalServeQuery || ((serveProcess && serveReady) ? serveQuery : null);
   if (queryFn) {
     try {
-      const resp = await queryFn('search', { query, limit });
+      const resp = await queryFn('search', { query, limit, session: SESSION_ID });
       if (resp.ok && Array.isArray(resp.data) && resp.data.length > 0) {
         cacheSet(cacheKey, resp.data);
         return resp.data;
@@ -1210,6 +1340,29 @@ async function analyzeComplexity(paths) {
 
 // ─── Result formatting helpers ──────────────────────────────────
 
+/**
+ * UI component chain recorded at index time: "ui_component name" on component
+ * XMLs and their provider classes, plus "ui_data_provider", "ui_collection",
+ * "ui_repository" and "ui_controller <url> <class>" on component XMLs.
+ * Returns null for other results.
+ */
+function uiComponentChain(searchText) {
+  const all = (re) => [...new Set([...searchText.matchAll(re)].map(m => m[1]))];
+  const components = all(/ui_component (\w+)/g);
+  if (components.length === 0) return null;
+  const controllers = new Map();
+  for (const m of searchText.matchAll(/ui_controller (\S+) ([\w\\]+)/g)) {
+    controllers.set(`${m[1]} ${m[2]}`, { url: m[1], class: m[2] });
+  }
+  return {
+    components,
+    dataProviders: all(/ui_data_provider ([\w\\]+)/g),
+    collections: all(/ui_collection ([\w\\]+)/g),
+    repositories: all(/ui_repository ([\w\\]+)/g),
+    controllers: [...controllers.values()]
+  };
+}
+
 function normalizeResult(r) {
   const meta = r.metadata || r;
   return {
@@ -1220,6 +1373,10 @@ function normalizeResult(r) {
     className: meta.class_name || meta.className,
     methodName: meta.method_name || meta.methodName,
     methods: meta.methods || [],
+    // Methods the class gets from the traits it uses
+    traitMethods: meta.trait_methods || [],
+    // Magento types (controller, model, ...) the class gets from an ancestor
+    inheritedTypes: meta.inherited_types || [],
     namespace: meta.namespace,
     searchText: meta.search_text || meta.searchText || '',
     isPlugin: meta.is_plugin || meta.isPlugin,
@@ -1229,8 +1386,45 @@ function normalizeResult(r) {
     isResolver: meta.is_resolver || meta.isResolver,
     isModel: meta.is_model || meta.isModel,
     isBlock: meta.is_block || meta.isBlock,
+    isGenerated: meta.is_generated || meta.isGenerated,
     area: meta.area,
+    // Extra root (index --extra-root) the file was indexed from
+    root: meta.root || null,
+    line: r.line ?? meta.span?.start_line ?? null,
+    endLine: r.end_line ?? meta.span?.end_line ?? null,
     description: descriptionMap[meta.path]?.description || null,
+    // One-line structural summary, stored when indexed with --summaries
+    summary: meta.summary || null,
+    // Vendor file this app/code copy of a class overrides
+    overrides: meta.overrides || null,
+    // Class di.xml prefers for an API interface, returned with the interface
+    implementation: r.implementation ? normalizeResult(r.implementation) : null,
+    // URLs reaching a controller action, from routes.xml ("/catalog/product/view")
+    urls: meta.urls || [],
+    // Non-blank lines, size on disk and mtime (Unix seconds) when indexed
+    loc: meta.metrics?.loc ?? null,
+    bytes: meta.metrics?.bytes ?? null,
+    modified: meta.metrics?.modified ?? null,
+    // Last git commit touching the file (Unix seconds), indexed with --git-times
+    lastCommit: meta.last_commit ?? null,
+    // CODEOWNERS owners or dominant git author, indexed with --owners
+    owners: meta.owners || [],
+    // "docs" for Markdown sections indexed with --include-docs
+    collection: meta.collection || 'code',
+    // Heading breadcrumbs of a doc section, outermost first
+    headings: meta.headings || [],
+    // false when app/etc/config.php disables the result's module
+    moduleEnabled: r.module_enabled ?? null,
+    // Patches (composer-patches or patches/, m2-hotfixes/) modifying the file
+    patchedBy: r.patched_by || [],
+    // bin/magento commands a class implements, recorded at index time as "console_command name"
+    consoleCommands: [...new Set([...(meta.search_text || '').matchAll(/console_command ([\w:.-]+)/g)].map(m => m[1]))],
+    // Schema fields a resolver serves, recorded at index time as "graphql_field Type.field"
+    graphqlFields: [...new Set([...(meta.search_text || '').matchAll(/graphql_field (\w+\.\w+)/g)].map(m => m[1]))],
+    // requirejs-config mixins wrapping a JS module, recorded as "modified_by_mixin Vendor_Module/js/x"
+    mixins: [...new Set([...(meta.search_text || '').matchAll(/modified_by_mixin ([\w/.-]+)/g)].map(m => m[1]))],
+    // Data provider, collections, repositories and controllers of a UI component
+    uiComponent: uiComponentChain(meta.search_text || ''),
     score: r.score
   };
 }
@@ -1270,7 +1464,7 @@ function extractPluginMethods(filePath) {
 function readMethodSnippet(filePath, methodName, maxLines = 15) {
   const absPath = filePath.startsWith('/') ? filePath : path.join(config.magentoRoot, filePath);
   let content;
-  try { content = readFileSync(absPath, 'utf-8'); } catch { return null; }
+  try { content = redactSecrets(readFileSync(absPath, 'utf-8')); } catch { return null; }
   const lines = content.split('\n');
   for (let i = 0; i < lines.length; i++) {
     if (lines[i].includes(`function ${methodName}(`)) {
@@ -1292,7 +1486,7 @@ function readMethodSnippet(filePath, methodName, maxLines = 15) {
 function readFullMethodBody(filePath, methodName, maxLines = 60) {
   const absPath = filePath.startsWith('/') ? filePath : path.join(config.magentoRoot, filePath);
   let content;
-  try { content = readFileSync(absPath, 'utf-8'); } catch { return null; }
+  try { content = redactSecrets(readFileSync(absPath, 'utf-8')); } catch { return null; }
   const lines = content.split('\n');
   for (let i = 0; i < lines.length; i++) {
     if (lines[i].includes(`function ${methodName}(`)) {
@@ -2025,6 +2219,11 @@ function formatSearchResults(results) {
       score: r.score ? parseFloat(r.score.toFixed(3)) : null,
       path: r.path || 'unknown',
     };
+    // Whole-file hits start at line 1 and carry no useful location
+    if (r.line > 1) {
+      entry.line = r.line;
+      entry.endLine = r.endLine;
+    }
     if (r.module) entry.module = r.module;
     if (r.className) entry.className = r.className;
     if (r.namespace) entry.namespace = r.namespace;
@@ -2033,7 +2232,28 @@ function formatSearchResults(results) {
     if (r.magentoType) entry.magentoType = r.magentoType;
     if (r.type) entry.fileType = r.type;
     if (r.area && r.area !== 'global') entry.area = r.area;
+    if (r.root) entry.root = r.root;
+    if (r.summary) entry.summary = r.summary;
+    if (r.overrides) entry.overrides = r.overrides;
+    if (r.implementation) {
+      const impl = r.implementation;
+      entry.implementation = { path: impl.path, className: impl.className, namespace: impl.namespace };
+      if (impl.methods && impl.methods.length > 0) entry.implementation.methods = impl.methods;
+    }
+    if (r.urls && r.urls.length > 0) entry.urls = r.urls;
+    if (r.loc != null) entry.loc = r.loc;
+    if (r.modified != null) entry.modified = new Date(r.modified * 1000).toISOString().slice(0, 10);
+    if (r.lastCommit != null) entry.lastCommit = new Date(r.lastCommit * 1000).toISOString().slice(0, 10);
+    if (r.owners && r.owners.length > 0) entry.owners = r.owners;
+    if (r.collection === 'docs') entry.collection = 'docs';
+    if (r.moduleEnabled === false) entry.module_enabled = false;
+    if (r.patchedBy && r.patchedBy.length > 0) entry.patchedBy = r.patchedBy;
+    if (r.headings && r.headings.length > 0) entry.section = r.headings.join(' › ');
     if (r.description) entry.description = r.description;
+    if (r.consoleCommands && r.consoleCommands.length > 0) entry.consoleCommands = r.consoleCommands;
+    if (r.graphqlFields && r.graphqlFields.length > 0) entry.graphqlFields = r.graphqlFields;
+    if (r.mixins && r.mixins.length > 0) entry.mixins = r.mixins;
+    if (r.uiComponent) entry.uiComponent = r.uiComponent;
 
     // Badges — concise role indicators
     const badges = [];
@@ -2044,6 +2264,7 @@ function formatSearchResults(results) {
     if (r.isResolver) badges.push('graphql-resolver');
     if (r.isModel) badges.push('model');
     if (r.isBlock) badges.push('block');
+    if (r.isGenerated) badges.push('generated');
     if (badges.length > 0) entry.badges = badges;
 
     // Only include verbose content (snippet, codePreview) for top-ranked results
@@ -2604,7 +2825,7 @@ async function profilePerformance(subsystem, threshold = 0) {
   if (phpFiles.length > 0) {
     try {
       const absPaths = phpFiles
-        .map(r => path.join(config.magentoRoot, r.path))
+        .map(r => (r.path.startsWith('/') ? r.path : path.join(config.magentoRoot, r.path)))
         .filter(p => existsSync(p));
       if (absPaths.length > 0) {
         complexityData = await analyzeComplexity(absPaths);
@@ -2614,7 +2835,7 @@ async function profilePerformance(subsystem, threshold = 0) {
 
   // Merge complexity into results
   const filesWithComplexity = allFiles.map(r => {
-    const absPath = path.join(config.magentoRoot, r.path || '');
+    const absPath = r.path?.startsWith('/') ? r.path : path.join(config.magentoRoot, r.path || '');
     const cx = complexityData.find(c => c.file === absPath);
     return {
       ...r,
@@ -2682,25 +2903,8 @@ function hybridRerank(results, query, bm25Weight = 0.3) {
 // ─── Query Expansion ────────────────────────────────────────────
 // Expand queries with Magento domain synonyms for better recall.
 
-const MAGENTO_SYNONYMS = {
-  plugin: ['interceptor', 'around method', 'before after'],
-  preference: ['di override', 'rewrite', 'di.xml for type'],
-  observer: ['event listener', 'event handler', 'events.xml'],
-  model: ['entity', 'resource model'],
-  block: ['view block', 'template block'],
-  controller: ['action class', 'execute method', 'route handler'],
-  cron: ['scheduled task', 'crontab.xml'],
-  api: ['rest endpoint', 'webapi', 'service contract'],
-  layout: ['xml layout', 'handle', 'container', 'reference block'],
-  checkout: ['cart', 'quote', 'totals collection'],
-  order: ['sales order', 'order placement', 'order submit'],
-  product: ['catalog product', 'product entity'],
-  customer: ['customer entity', 'customer account'],
-  indexer: ['reindex', 'flat table', 'price index'],
-  payment: ['payment method', 'payment gateway', 'payment information'],
-  shipping: ['carrier', 'shipping method', 'delivery'],
-  stock: ['inventory', 'salable quantity', 'source item'],
-};
+// Shared with the Rust core's paraphrase generator (`validate --paraphrase`)
+const MAGENTO_SYNONYMS = createRequire(import.meta.url)('../config/synonyms.json');
 
 function expandQuery(query) {
   const terms = query.toLowerCase().split(/\s+/);
@@ -2899,7 +3103,7 @@ async function analyzeImpact(className) {
   // Check PHP files for direct references
   const escapedShort = shortName.replace(/[.*+?^${}()|[\]\\]/g, '\\$&');
   for (const r of relatedPaths.slice(0, 40)) {
-    const absPath = path.join(root, r.path);
+    const absPath = r.path.startsWith('/') ? r.path : path.join(root, r.path);
     if (!existsSync(absPath) || !r.path.endsWith('.php')) continue;
     let content;
     try { content = readFileSync(absPath, 'utf-8'); } catch { continue; }
@@ -3058,7 +3262,7 @@ async function findTests(className, methodName) {
     const rawArr = Array.isArray(rawSearch) ? rawSearch : [];
     for (const r of rawArr.map(normalizeResult)) {
       if (r.path?.toLowerCase().includes('test')) {
-        testFiles.add(path.join(root, r.path));
+        testFiles.add(r.path.startsWith('/') ? r.path : path.join(root, r.path));
       }
     }
   } catch { /* index may not be ready */ }
@@ -3746,10 +3950,30 @@ async function traceCallChain(startClass, startMethod, maxDepth = 3) {
 
   // Resolve DI preference for an interface
   const prefCache = new Map();
+  let virtualTypeBases = null;
   async function resolvePreference(interfaceName) {
     if (prefCache.has(interfaceName)) return prefCache.get(interfaceName);
     const shortName = interfaceName.split('\\').pop();
     const diXmlFiles = await glob('**/etc/di.xml', { cwd: root, absolute: true, nodir: true });
+    if (!virtualTypeBases) {
+      virtualTypeBases = new Map();
+      for (const diFile of diXmlFiles) {
+        let content;
+        try { content = readFileSync(diFile, 'utf-8'); } catch { continue; }
+        const vtRegex = /<virtualType\s+name="([^"]+)"[^>]*type="([^"]+)"/g;
+        let vm;
+        while ((vm = vtRegex.exec(content)) !== null) virtualTypeBases.set(vm[1], vm[2]);
+      }
+    }
+    // A preference may point at a virtualType; follow it to the real class
+    const followVirtualTypes = (type) => {
+      const seen = new Set([type]);
+      while (virtualTypeBases.has(type) && !seen.has(virtualTypeBases.get(type))) {
+        type = virtualTypeBases.get(type);
+        seen.add(type);
+      }
+      return type;
+    };
     for (const diFile of diXmlFiles) {
       let content;
       try { content = readFileSync(diFile, 'utf-8'); } catch { continue; }
@@ -3757,13 +3981,15 @@ async function traceCallChain(startClass, startMethod, maxDepth = 3) {
       let m;
       while ((m = prefRegex.exec(content)) !== null) {
         if (m[1] === interfaceName || m[1].endsWith('\\' + shortName)) {
-          prefCache.set(interfaceName, m[2]);
-          return m[2];
+          const resolved = followVirtualTypes(m[2]);
+          prefCache.set(interfaceName, resolved);
+          return resolved;
         }
       }
     }
-    prefCache.set(interfaceName, null);
-    return null;
+    const resolved = virtualTypeBases.has(interfaceName) ? followVirtualTypes(interfaceName) : null;
+    prefCache.set(interfaceName, resolved);
+    return resolved;
   }
 
   async function traceMethod(className, methodName, depth) {
@@ -4018,7 +4244,7 @@ async function traceConfig(configPath, keyword) {
           const hasGroup = parts.length < 3 || content.includes(`id="${parts[1]}"`);
           if (!hasSection || !hasGroup) continue;
 
-          const relPath = path.relative(root, f);
+          const relPath = path.relative(root, f).split(path.sep).join('/');
           const entry = { file: relPath, configPath: cp };
 
           // Extract field details
@@ -4054,7 +4280,7 @@ async function traceConfig(configPath, keyword) {
       for (const f of phpFiles.slice(0, 20)) {
         try {
           const content = readFileSync(f, 'utf-8');
-          const relPath = path.relative(root, f);
+          const relPath = path.relative(root, f).split(path.sep).join('/');
           const entry = { file: relPath };
 
           // Extract class name
@@ -4244,6 +4470,33 @@ server.setRequestHandler(ListToolsRequestSchema, async () => ({
             description: 'Precise mode for debugging: disables query expansion AND applies strict post-filtering — only returns results where the file content contains at least one query keyword. Use for specific debugging queries like "gift card subtotal infinite loop". Default: false.',
             default: false
           },
+          minLoc: {
+            type: 'number',
+            description: 'Leave out files with fewer non-blank lines than this — skips interface stubs and thin wrappers when you want the real implementation. Example: 30.'
+          },
+          maxLoc: {
+            type: 'number',
+            description: 'Leave out files with more non-blank lines than this, e.g. to skip huge legacy classes.'
+          },
+          owner: {
+            type: 'string',
+            description: 'Only return files owned by this team or person: a case-insensitive substring of a CODEOWNERS owner or git author, leading @ optional. Needs an index built with --owners. Example: "team-checkout".'
+          },
+          excludeDisabled: {
+            type: 'boolean',
+            description: 'Leave out code from modules disabled in app/etc/config.php. Without it such results are kept and marked "module_enabled": false, since that code can never run on this project.'
+          },
+          namespace: {
+            type: 'string',
+            enum: ['code', 'docs'],
+            description: 'Only return code, or only project documentation (Markdown dev docs, ADRs and module READMEs, one result per section). Docs need an index built with --include-docs; without this, both are returned.'
+          },
+          sort: {
+            type: 'string',
+            enum: ['score', 'recent'],
+            description: 'Order of the returned results: "score" (best match first, default) or "recent" (most recently modified file first) — useful when debugging something that broke recently.',
+            default: 'score'
+          },
         },
         required: ['query']
       }
@@ -4454,6 +4707,53 @@ server.setRequestHandler(ListToolsRequestSchema, async () => ({
         required: ['jobName']
       }
     },
+    {
+      name: 'magento_find_queue',
+      description: 'Trace message queue routes: topic → exchange → queue → consumer → handler class, joined from communication.xml, queue_topology.xml, queue_consumer.xml and legacy queue.xml. Use for "which consumer handles async product export" style questions. Reads config directly, no index needed.',
+      inputSchema: {
+        type: 'object',
+        properties: {
+          filter: {
+            type: 'string',
+            description: 'Substring of a topic, exchange, queue, consumer or handler class. Examples: "export", "async.operations", "product_action_attribute", "inventory". Omit to list every route.'
+          }
+        }
+      }
+    },
+    {
+      name: 'magento_project_info',
+      description: 'Project-level settings: which modules are enabled or disabled in app/etc/config.php, plus the deploy mode, table prefix, cache backends and queue connections from app/etc/env.php with credentials redacted. Use to check whether a module in a search result can run at all, or which cache/queue backend the project uses. Reads config directly, no index needed.',
+      inputSchema: {
+        type: 'object',
+        properties: {}
+      }
+    },
+    {
+      name: 'magento_find_console_command',
+      description: 'Map bin/magento commands to the classes implementing them: command name → class, description, aliases and the di.xml registering it in the CommandListInterface "commands" list. Use for "which class implements bin/magento indexer:reindex" or "where is setup:upgrade defined" style questions. Reads config and command classes directly, no index needed.',
+      inputSchema: {
+        type: 'object',
+        properties: {
+          filter: {
+            type: 'string',
+            description: 'Substring of a command name, class, description or alias. Examples: "indexer:reindex", "cache", "setup:", "Acme". Omit to list every command.'
+          }
+        }
+      }
+    },
+    {
+      name: 'magento_find_indexer',
+      description: 'Map Magento indexers to their code: indexer id → action class, mview class and the tables whose changes trigger a partial reindex, joined from indexer.xml and mview.xml. Use for "what reindexes when catalog_product_entity changes" or "slow catalogsearch_fulltext reindex" style questions. Reads config directly, no index needed.',
+      inputSchema: {
+        type: 'object',
+        properties: {
+          filter: {
+            type: 'string',
+            description: 'Substring of an indexer id, title, action class or subscribed table. Examples: "price", "catalogsearch_fulltext", "cataloginventory_stock_item", "Inventory". Omit to list every indexer.'
+          }
+        }
+      }
+    },
     {
       name: 'magento_find_graphql',
       description: 'Find GraphQL schema definitions (.graphqls), types, queries, mutations, and resolver PHP classes. See also: magento_find_class (resolver implementation), magento_find_method (resolver execute method).',
@@ -5097,8 +5397,10 @@ function getReindexWarning() {
       etaStr = 'est. ~5–10 min remaining';
     }
 
-    return `> ⏳ **Re-indexing in progress** — ${elapsedStr} elapsed, ${phaseStr}. ${etaStr}.\n` +
-           `> Results below use the **previous index** — valid, but may miss recently added files.\n\n`;
+    const sourceStr = reindexServingPartial
+      ? `> Results below use a **partial index** (app/code and app/design) — vendor code is still being indexed.\n\n`
+      : `> Results below use the **previous index** — valid, but may miss recently added files.\n\n`;
+    return `> ⏳ **Re-indexing in progress** — ${elapsedStr} elapsed, ${phaseStr}. ${etaStr}.\n` + sourceStr;
   }
 
   // Secondary instance: detect via PID file (no phase info available)
@@ -5165,7 +5467,7 @@ const _callToolHandler = async (request) => {
         const precise = args.precise === true;
         const searchQuery = (args.expand !== false && !precise) ? expandQuery(args.query) : args.query;
         // When moduleFilter is present, fetch more results so post-filtering has enough candidates
-        const fetchLimit = args.moduleFilter
+        const fetchLimit = (args.moduleFilter || args.minLoc || args.maxLoc || args.owner || args.namespace)
           ? Math.max(args.limit || 10, 200)
           : Math.max(args.limit || 10, precise ? 60 : 30);
         const raw = await rustSearchAsync(searchQuery, fetchLimit);
@@ -5182,6 +5484,10 @@ const _callToolHandler = async (request) => {
             return queryTerms.some(term => haystack.includes(term));
           });
         }
+        // Read back by `magector analyze enrichment`
+        if (results.length === 0) {
+          logToFile('INFO', `search: query returned 0 results for "${args.query}"`);
+        }
         // Apply module filter if specified
         if (args.moduleFilter) {
           results = filterByModule(results, args.moduleFilter);
@@ -5189,10 +5495,32 @@ const _callToolHandler = async (request) => {
         if (args.excludeModuleFilter) {
           results = excludeByModule(results, args.excludeModuleFilter);
         }
+        // Files indexed before metrics were recorded always pass
+        if (args.minLoc || args.maxLoc) {
+          const min = args.minLoc || 0;
+          const max = args.maxLoc || Infinity;
+          results = results.filter(r => r.loc == null || (r.loc >= min && r.loc <= max));
+        }
+        // Files without owners never match
+        if (args.owner) {
+          const wanted = args.owner.replace(/^@/, '').toLowerCase();
+          results = results.filter(r => r.owners.some(o => o.toLowerCase().includes(wanted)));
+        }
+        if (args.namespace) {
+          results = results.filter(r => r.collection === args.namespace);
+        }
+        if (args.excludeDisabled) {
+          results = results.filter(r => r.moduleEnabled !== false);
+        }
+        let top = results.slice(0, args.limit || 5);
+        if (args.sort === 'recent') {
+          // Stable sort: equally recent files keep their rank
+          top = top.sort((a, b) => (b.modified ?? -1) - (a.modified ?? -1));
+        }
         // SONA: record search with results for follow-up tracking
         sessionTracker.recordToolCall(name, args || {}, arr);
         const reindexWarn = getReindexWarning();
-        const searchOutput = formatSearchResults(results.slice(0, args.limit || 5));
+        const searchOutput = formatSearchResults(top);
         return {
           content: [{
             type: 'text',
@@ -5665,6 +5993,11 @@ const _callToolHandler = async (request) => {
           results = results.filter(r => r.area === args.area || r.path?.includes(`/${args.area}/`));
         }
 
+        // Controllers whose routes.xml URL is the requested route come first
+        const url = `/${args.route.replace(/^\/+|\/+$/g, '')}`.toLowerCase();
+        const servesRoute = (r) => (r.urls || []).some(u => u === url || u.endsWith(`/admin${url}`));
+        results.sort((a, b) => servesRoute(b) - servesRoute(a));
+
         return {
           content: [{
             type: 'text',
@@ -5705,6 +6038,89 @@ const _callToolHandler = async (request) => {
         };
       }
 
+      case 'magento_find_queue': {
+        const queueArgs = ['queues', '-m', config.magentoRoot, '-f', 'json'];
+        if (args.filter) queueArgs.push(args.filter);
+        const routes = JSON.parse(execFileSync(config.rustBinary, queueArgs, {
+          encoding: 'utf-8', timeout: 60000, stdio: ['pipe', 'pipe', 'pipe'], env: rustEnv
+        }));
+        if (routes.length === 0) {
+          return { content: [{ type: 'text', text: `No message queue routes found${args.filter ? ` matching "${args.filter}"` : ''}.` }] };
+        }
+        let text = `## Message Queue Routes (${routes.length})\n\n`;
+        for (const r of routes.slice(0, 50)) {
+          text += `- **${r.topic || '-'}** → exchange ${r.exchange || '-'} → queue ${r.queue || '-'} → consumer ${r.consumer || '-'}`;
+          if (r.connection) text += ` (${r.connection})`;
+          text += '\n';
+          for (const h of r.handlers) text += `  - handler: \`${h}\`\n`;
+          for (const f of r.files) text += `  - ${f}\n`;
+        }
+        if (routes.length > 50) text += `\n… ${routes.length - 50} more; narrow the filter.\n`;
+        return { content: [{ type: 'text', text }] };
+      }
+
+      case 'magento_project_info': {
+        const info = JSON.parse(execFileSync(config.rustBinary, ['project-info', '-m', config.magentoRoot, '-f', 'json'], {
+          encoding: 'utf-8', timeout: 60000, stdio: ['pipe', 'pipe', 'pipe'], env: rustEnv
+        }));
+        if (info.files.length === 0) {
+          return { content: [{ type: 'text', text: 'No app/etc/config.php or app/etc/env.php found.' }] };
+        }
+        const modules = Object.entries(info.modules);
+        const disabled = modules.filter(([, enabled]) => !enabled).map(([name]) => name);
+        let text = `## Project (${info.files.join(', ')})\n\n`;
+        if (info.mode) text += `- mode: ${info.mode}\n`;
+        if (info.table_prefix != null) text += `- table prefix: ${info.table_prefix || '(none)'}\n`;
+        if (modules.length) text += `- modules: ${modules.length} (${modules.length - disabled.length} enabled, ${disabled.length} disabled)\n`;
+        if (disabled.length) text += `- disabled: ${disabled.join(', ')}\n`;
+        if (info.cache) text += `\n### Cache\n\n\`\`\`json\n${JSON.stringify(info.cache, null, 2)}\n\`\`\`\n`;
+        if (info.queue) text += `\n### Queue\n\n\`\`\`json\n${JSON.stringify(info.queue, null, 2)}\n\`\`\`\n`;
+        return { content: [{ type: 'text', text }] };
+      }
+
+      case 'magento_find_console_command': {
+        const commandArgs = ['console-commands', '-m', config.magentoRoot, '-f', 'json'];
+        if (args.filter) commandArgs.push(args.filter);
+        const commands = JSON.parse(execFileSync(config.rustBinary, commandArgs, {
+          encoding: 'utf-8', timeout: 60000, stdio: ['pipe', 'pipe', 'pipe'], env: rustEnv
+        }));
+        if (commands.length === 0) {
+          return { content: [{ type: 'text', text: `No console commands found${args.filter ? ` matching "${args.filter}"` : ''}.` }] };
+        }
+        let text = `## Console Commands (${commands.length})\n\n`;
+        for (const c of commands.slice(0, 50)) {
+          text += `- **bin/magento ${c.name || '(name set at runtime)'}**${c.description ? ` — ${c.description}` : ''}\n`;
+          text += `  - class: \`${c.class}\`\n`;
+          if (c.aliases.length) text += `  - aliases: ${c.aliases.join(', ')}\n`;
+          if (c.file) text += `  - ${c.file}\n`;
+          for (const f of c.registered_in) text += `  - registered in ${f}\n`;
+        }
+        if (commands.length > 50) text += `\n… ${commands.length - 50} more; narrow the filter.\n`;
+        return { content: [{ type: 'text', text }] };
+      }
+
+      case 'magento_find_indexer': {
+        const indexerArgs = ['indexers', '-m', config.magentoRoot, '-f', 'json'];
+        if (args.filter) indexerArgs.push(args.filter);
+        const indexers = JSON.parse(execFileSync(config.rustBinary, indexerArgs, {
+          encoding: 'utf-8', timeout: 60000, stdio: ['pipe', 'pipe', 'pipe'], env: rustEnv
+        }));
+        if (indexers.length === 0) {
+          return { content: [{ type: 'text', text: `No indexers found${args.filter ? ` matching "${args.filter}"` : ''}.` }] };
+        }
+        let text = `## Indexers (${indexers.length})\n\n`;
+        for (const ix of indexers.slice(0, 50)) {
+          text += `- **${ix.id}**${ix.title ? ` — ${ix.title}` : ''}\n`;
+          if (ix.action_class) text += `  - action: \`${ix.action_class}\`\n`;
+          if (ix.mview_class) text += `  - mview: \`${ix.mview_class}\` (view ${ix.view_id})\n`;
+          if (ix.shared_index) text += `  - shares tables with: ${ix.shared_index}\n`;
+          if (ix.subscribed_tables.length) text += `  - subscribed tables: ${ix.subscribed_tables.join(', ')}\n`;
+          for (const f of ix.files) text += `  - ${f}\n`;
+        }
+        if (indexers.length > 50) text += `\n… ${indexers.length - 50} more; narrow the filter.\n`;
+        return { content: [{ type: 'text', text }] };
+      }
+
       case 'magento_find_graphql': {
         let query = `graphql ${args.query}`;
         if (args.schemaType) query += ` ${args.schemaType}`;
@@ -7009,7 +7425,7 @@ const _callToolHandler = async (request) => {
                 }
                 for (const r of res.slice(0, 5)) {
                   if (r.path?.endsWith('.php') && !r.fullMethodBody) {
-                    const body = readFullMethodBody(path.join(config.magentoRoot, r.path), a.methodName);
+                    const body = readFullMethodBody(r.path.startsWith('/') ? r.path : path.join(config.magentoRoot, r.path), a.methodName);
                     if (body) r.fullMethodBody = body;
                   }
                 }
@@ -7090,7 +7506,7 @@ const _callToolHandler = async (request) => {
                   break;
                 }
                 let fileContent;
-                try { fileContent = readFileSync(filePath, 'utf-8'); } catch {
+                try { fileContent = redactSecrets(readFileSync(filePath, 'utf-8')); } catch {
                   text = `File not found: ${a.path}`;
                   break;
                 }
@@ -7103,8 +7519,10 @@ const _callToolHandler = async (request) => {
                     if (fLines[i].includes(`function ${a.methodName}(`)) { mLine = i + 1; break; }
                   }
                   text = body.split('\n').map((line, i) => `${mLine + i}\t${line}`).join('\n');
+                  sessionOpenFile(a.path);
                   break;
                 }
+                sessionOpenFile(a.path);
                 const allLines = fileContent.split('\n');
                 const s = Math.max((a.startLine || 1) - 1, 0);
                 const e = a.endLine ? Math.min(a.endLine, allLines.length) : allLines.length;
@@ -7173,7 +7591,7 @@ const _callToolHandler = async (request) => {
                   try {
                     out = execFileSync('grep', gArgs, { cwd: config.magentoRoot, encoding: 'utf-8', timeout: 15000, maxBuffer: 5 * 1024 * 1024, stdio: ['pipe', 'pipe', 'pipe'] });
                   } catch (err) { out = err.stdout || ''; }
-                  const gLines = out.trim().split('\n').filter(Boolean);
+                  const gLines = redactSecrets(out).trim().split('\n').filter(Boolean);
                   if (batchFilesOnly) {
                     text = `Files matching \`${a.pattern}\` (${gLines.length}):\n`;
                     for (const gl of gLines.slice(0, maxRes)) text += gl + '\n';
@@ -7320,7 +7738,7 @@ const _callToolHandler = async (request) => {
           if (err.killed) logToFile('WARN', `grep: timed out after 30s for pattern "${args.pattern}"`);
         }
         const grepElapsed = Date.now() - grepStart;
-        const lines = output.trim().split('\n').filter(Boolean);
+        const lines = redactSecrets(output).trim().split('\n').filter(Boolean);
         const total = lines.length;
         if (grepElapsed > 5000) logToFile('WARN', `grep: slow query "${args.pattern}" — ${total} matches in ${grepElapsed}ms`);
         const truncated = lines.slice(0, maxResults);
@@ -7498,10 +7916,11 @@ const _callToolHandler = async (request) => {
           return { content: [{ type: 'text', text: `Path escapes project root: ${args.path}` }], isError: true };
         }
         let content;
-        try { content = readFileSync(filePath, 'utf-8'); } catch (err) {
+        try { content = redactSecrets(readFileSync(filePath, 'utf-8')); } catch (err) {
           logToFile('WARN', `read: file not found: ${args.path} (${err.code || err.message})`);
           return { content: [{ type: 'text', text: `File not found: ${args.path}` }], isError: true };
         }
+        sessionOpenFile(args.path);
 
         // Method extraction mode: return only the specified method
         if (args.methodName) {
@@ -7803,6 +8222,10 @@ async function main() {
 // Cleanup on exit — kill all child processes and remove PID file
 function cleanup(reason) {
   logToFile('INFO', `Cleanup: ${reason || 'exit'}`);
+  if (globalServeQuery) {
+    // Best effort: serve also drops sessions left idle
+    globalServeQuery('session_end', { session: SESSION_ID }).catch(() => {});
+  }
   if (serveProcess) {
     logToFile('INFO', `Cleanup: killing serve process (PID ${serveProcess.pid})`);
     try { serveProcess.kill(); } catch {}
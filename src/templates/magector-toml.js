@@ -0,0 +1,50 @@
+/**
+ * Starter magector.toml written by `magector init`.
+ * Read by magector-core from the Magento root; CLI flags override it.
+ */
+export const MAGECTOR_TOML = `# Magector project configuration
+# Settings here are defaults — command-line flags take precedence.
+
+[index]
+# Index only modules matching these globs (empty = whole codebase).
+# only_modules = ["Magento_Checkout", "Vendor_*"]
+only_modules = []
+
+# Embedding batch size. Lower it on machines with little RAM.
+# batch_size = 256
+
+# Reproducible output (sorted insertion, stable IDs). Implies a full rebuild.
+deterministic = false
+
+# Follow symlinked module directories (modman / dev setups).
+follow_symlinks = false
+
+# Also index MFTF test XML (Test/Mftf); unit/integration tests stay excluded.
+include_tests = false
+
+# Also index Markdown docs outside vendor/ (dev docs, ADRs, module READMEs)
+# into the "docs" collection; search them with --namespace docs.
+include_docs = false
+
+# Pre-built read-only core index (e.g. vendor/magento) shared across
+# projects. Only files it does not cover are indexed locally.
+# core_index = ".magector/core.db"
+
+# Pooling over token embeddings: "cls", "mean" or "max". Changing it
+# rebuilds the index; queries always use the pooling the index was built with.
+# pooling = "mean"
+
+# Build the search graph from the first N dimensions of each vector and
+# rerank candidates with the full vector. Faster on large indexes with
+# Matryoshka-trained models; changing it rebuilds only the graph.
+# coarse_dim = 128
+
+# Two-stage search: pick candidate files on a file-level graph, then score
+# their chunks. Keeps latency low when files are split into many chunks.
+# two_stage = false
+
+[redact]
+# Extra regexes masked as [REDACTED] in indexed content and grep output,
+# on top of the built-in password / API key / private key patterns.
+# patterns = ['stripe_sk_live_\\w+']
+`;
@@ -150,7 +150,7 @@ async function main() {
       'magento_find_config', 'magento_find_template', 'magento_index',
       'magento_stats', 'magento_find_plugin', 'magento_find_observer',
       'magento_find_preference', 'magento_find_api', 'magento_find_controller',
-      'magento_find_block', 'magento_find_cron', 'magento_find_graphql',
+      'magento_find_block', 'magento_find_cron', 'magento_find_queue', 'magento_find_indexer', 'magento_find_console_command', 'magento_project_info', 'magento_find_graphql',
       'magento_find_db_schema', 'magento_module_structure',
       'magento_analyze_diff', 'magento_complexity', 'magento_describe',
       'magento_trace_flow', 'magento_trace_dependency', 'magento_error_parser',
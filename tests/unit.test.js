@@ -10,7 +10,7 @@
 
 import path from 'path';
 import { fileURLToPath } from 'url';
-import { mkdirSync, writeFileSync, rmSync, existsSync, readFileSync } from 'fs';
+import { mkdirSync, writeFileSync, rmSync, existsSync, readFileSync, realpathSync, symlinkSync } from 'fs';
 
 const __dirname = path.dirname(fileURLToPath(import.meta.url));
 
@@ -2212,25 +2212,7 @@ function testBm25Scoring() {
 function testQueryExpansion() {
   console.log('\n── Query Expansion ──');
 
-  const MAGENTO_SYNONYMS = {
-    plugin: ['interceptor', 'around method', 'before after'],
-    preference: ['di override', 'rewrite', 'di.xml for type'],
-    observer: ['event listener', 'event handler', 'events.xml'],
-    model: ['entity', 'resource model'],
-    block: ['view block', 'template block'],
-    controller: ['action class', 'execute method', 'route handler'],
-    cron: ['scheduled task', 'crontab.xml'],
-    api: ['rest endpoint', 'webapi', 'endpoint'],
-    layout: ['xml layout', 'handle', 'container', 'reference block'],
-    checkout: ['cart', 'quote', 'totals collection'],
-    order: ['sales order', 'order placement', 'order submit'],
-    product: ['catalog product', 'product entity'],
-    customer: ['customer entity', 'customer account'],
-    indexer: ['reindex', 'flat table', 'price index'],
-    payment: ['payment method', 'payment gateway', 'payment information'],
-    shipping: ['carrier', 'shipping method', 'delivery'],
-    stock: ['inventory', 'salable quantity', 'source item'],
-  };
+  const MAGENTO_SYNONYMS = JSON.parse(readFileSync(path.join(__dirname, '..', 'config', 'synonyms.json'), 'utf-8'));
 
   function expandQuery(query) {
     const terms = query.toLowerCase().split(/\s+/);
@@ -4920,6 +4902,15 @@ function testSafePath() {
     const rootAbs = path.resolve(root);
     const joined = path.resolve(rootAbs, String(rel));
     if (joined !== rootAbs && !joined.startsWith(rootAbs + path.sep)) return null;
+    if (existsSync(joined)) {
+      try {
+        const real = realpathSync(joined);
+        const realRoot = realpathSync(rootAbs);
+        if (real !== realRoot && !real.startsWith(realRoot + path.sep)) return null;
+      } catch {
+        return null;
+      }
+    }
     return joined;
   }
   function safeRelPath(root, rel) {
@@ -4964,6 +4955,25 @@ function testSafePath() {
   assertEq(safeRelPath(root, '../etc'), null, 'safeRelPath: rejects traversal');
   assertEq(safeRelPath(root, '/etc/passwd'), null, 'safeRelPath: rejects absolute');
 
+  // Symlinks pointing out of the root
+  const tmp = path.join(__dirname, '.tmp-safepath');
+  rmSync(tmp, { recursive: true, force: true });
+  mkdirSync(path.join(tmp, 'project', 'app'), { recursive: true });
+  mkdirSync(path.join(tmp, 'outside'), { recursive: true });
+  writeFileSync(path.join(tmp, 'outside', 'id_rsa'), 'key');
+  writeFileSync(path.join(tmp, 'project', 'app', 'a.php'), '<?php');
+  const proj = path.join(tmp, 'project');
+  try {
+    symlinkSync(path.join(tmp, 'outside'), path.join(proj, 'app', 'linked'));
+    symlinkSync(path.join(proj, 'app', 'a.php'), path.join(proj, 'app', 'alias.php'));
+    assertEq(safePath(proj, 'app/linked/id_rsa'), null, 'safePath: rejects file behind symlinked dir');
+    assertEq(safeRelPath(proj, 'app/linked'), null, 'safeRelPath: rejects symlinked dir escaping root');
+    assertEq(safePath(proj, 'app/alias.php'), path.join(proj, 'app', 'alias.php'),
+      'safePath: accepts symlink staying inside root');
+  } finally {
+    rmSync(tmp, { recursive: true, force: true });
+  }
+
   // Update.js safe-version validator must reject shell metacharacters.
   function isSafeVersion(v) {
     return typeof v === 'string' && /^[0-9]+\.[0-9]+\.[0-9]+(-[0-9A-Za-z.-]+)?$/.test(v);